@@ -0,0 +1,119 @@
+// Playback loop: plays a frame range through a processor, frame by frame like a player does, and
+// seeks back to the start for the next loop, so every loop decodes, rasterizes and triangulates again.
+//
+// With `--features tracing` the decode stages run in tracing spans and the benchmark records them with
+// tracing-flame into folded stacks, input for a flame graph of the playback workload:
+//
+//   cargo bench --bench playback_benchmark --features tracing,unstable
+//   inferno-flamegraph < target/playback.folded > playback.svg
+//
+// ALPHASTREAM_FLAME_OUTPUT overrides the path of the folded stacks.
+
+use std::time::Duration;
+
+use criterion::{criterion_group, BenchmarkId, Criterion};
+use libalphastream::formats::ASVRWriter;
+use libalphastream::prelude::{AlphaStreamProcessor, AlphaStreamProcessorBuilder, FrameData, ProcessingMode};
+
+const SCENE_ID: u32 = 42;
+const VERSION: &[u8] = b"1.5.0";
+const FRAME_COUNT: u32 = 240;
+
+/// Outline of a star with `vertices` points around (cx, cy), turned by `phase` radians
+fn star_channel(vertices: usize, cx: f64, cy: f64, phase: f64) -> Vec<u8> {
+    let points: Vec<(i32, i32)> = (0..vertices)
+        .map(|i| {
+            let angle = phase + i as f64 / vertices as f64 * std::f64::consts::TAU;
+            let radius = if i % 2 == 0 { 400.0 } else { 250.0 };
+            ((cx + angle.cos() * radius) as i32, (cy + angle.sin() * radius) as i32)
+        })
+        .collect();
+    let mut data = Vec::new();
+    data.extend_from_slice(&(points[0].0 as u16).to_le_bytes());
+    data.extend_from_slice(&(points[0].1 as u16).to_le_bytes());
+    let (mut x, mut y) = points[0];
+    for &(px, py) in points.iter().skip(1).chain(std::iter::once(&points[0])) {
+        // Deltas are i8, split long steps
+        while (x, y) != (px, py) {
+            let dx = (px - x).clamp(-127, 127);
+            let dy = (py - y).clamp(-127, 127);
+            data.push(dx as i8 as u8);
+            data.push(dy as i8 as u8);
+            x += dx;
+            y += dy;
+        }
+    }
+    data
+}
+
+/// Polystream of two moving stars in separate channels, like a scene tracking two subjects
+fn frame_polystream(frame: u32) -> Vec<u8> {
+    let phase = frame as f64 * 0.05;
+    let channels = [star_channel(200, 600.0 + frame as f64, 512.0, phase), star_channel(60, 1400.0, 512.0, -phase)];
+    let mut polystream = (channels.len() as u32).to_le_bytes().to_vec();
+    for channel in &channels {
+        polystream.extend_from_slice(&(channel.len() as u32).to_le_bytes());
+    }
+    for channel in &channels {
+        polystream.extend_from_slice(channel);
+    }
+    polystream
+}
+
+/// Write the benchmark scene as an encrypted ASVR file; the file name is the base URL of its key
+fn write_scene(dir: &std::path::Path) -> std::path::PathBuf {
+    let path = dir.join("playback.asvr");
+    let file = std::fs::File::create(&path).unwrap();
+    let mut writer = ASVRWriter::new(file, SCENE_ID, VERSION, b"playback.asvr").unwrap();
+    for frame in 0..FRAME_COUNT {
+        writer.add_frame(FrameData { polystream: frame_polystream(frame), bitmap: None, triangle_strip: None });
+    }
+    writer.write_all().unwrap();
+    path
+}
+
+/// Play every frame of the scene in order, waiting for each like a player blocked on vsync would
+fn play(rt: &tokio::runtime::Runtime, processor: &AlphaStreamProcessor) {
+    for frame in 0..FRAME_COUNT as usize {
+        let mask = rt.block_on(processor.get_frame_wait(frame, Duration::from_secs(5))).expect("frame not decoded");
+        std::hint::black_box(mask);
+    }
+}
+
+fn bench_playback(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_scene(dir.path());
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let mut group = c.benchmark_group("playback_loop");
+    group.sample_size(10);
+    for (mode, name) in [(ProcessingMode::Bitmap, "bitmap"), (ProcessingMode::Both, "both")] {
+        // A cache smaller than the scene, so no loop is served from frames decoded by the one before
+        let builder = AlphaStreamProcessorBuilder::new().processing_mode(mode).cache_capacity(64);
+        let processor = rt
+            .block_on(builder.build_asvr(path.to_str().unwrap(), SCENE_ID, VERSION, b"playback.asvr", 1024, 512))
+            .unwrap();
+        group.bench_with_input(BenchmarkId::new(name, FRAME_COUNT), &processor, |b, processor| b.iter(|| play(&rt, processor)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_playback);
+
+fn main() {
+    // Folded stacks are written when the guard is dropped at the end of main
+    #[cfg(feature = "tracing")]
+    let _flame = {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let output = std::env::var("ALPHASTREAM_FLAME_OUTPUT").unwrap_or_else(|_| "target/playback.folded".to_string());
+        let (layer, guard) = tracing_flame::FlameLayer::with_file(&output).expect("cannot create the folded stacks file");
+        // Decode tasks run on every runtime worker; one stack per span path reads best as a flame graph,
+        // and time outside the spans (the benchmark waiting on frames) would only dwarf it
+        let layer = layer.with_threads_collapsed(true).with_empty_samples(false);
+        tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer)).unwrap();
+        guard
+    };
+
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+}
@@ -0,0 +1,47 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use libalphastream::prelude::PolystreamRasterizer;
+
+/// Polystream of a closed star-shaped outline with `vertices` points around (1012, 512)
+fn star_polystream(vertices: usize) -> Vec<u8> {
+    let points: Vec<(i32, i32)> = (0..vertices)
+        .map(|i| {
+            let angle = i as f64 / vertices as f64 * std::f64::consts::TAU;
+            let radius = if i % 2 == 0 { 480.0 } else { 300.0 };
+            (1012 + (angle.cos() * radius) as i32, 512 + (angle.sin() * radius) as i32)
+        })
+        .collect();
+    let mut data = Vec::new();
+    data.extend_from_slice(&(points[0].0 as u16).to_le_bytes());
+    data.extend_from_slice(&(points[0].1 as u16).to_le_bytes());
+    let (mut x, mut y) = points[0];
+    for &(px, py) in points.iter().skip(1).chain(std::iter::once(&points[0])) {
+        // Deltas are i8, split long steps
+        while (x, y) != (px, py) {
+            let dx = (px - x).clamp(-127, 127);
+            let dy = (py - y).clamp(-127, 127);
+            data.push(dx as i8 as u8);
+            data.push(dy as i8 as u8);
+            x += dx;
+            y += dy;
+        }
+    }
+    data
+}
+
+fn bench_rasterize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rasterize");
+    for vertices in [16, 200, 800] {
+        let polystream = star_polystream(vertices);
+        for (width, height) in [(512, 256), (2024, 1024)] {
+            group.bench_with_input(
+                BenchmarkId::new(format!("{}x{}", width, height), vertices),
+                &polystream,
+                |b, polystream| b.iter(|| PolystreamRasterizer::rasterize(std::hint::black_box(polystream), width, height)),
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_rasterize);
+criterion_main!(benches);
@@ -0,0 +1,138 @@
+// Access pattern module
+// Records which frames a user visited, as a compact list of frame ranges in visit order,
+// so a later session can prefetch exactly those frames at startup instead of relying on
+// linear read-ahead only. Patterns serialize to a small text format for storing next to
+// the user's bookmarks: one `start..end` (end exclusive) or single frame index per line.
+
+use std::fmt;
+
+use crate::formats::FormatError;
+
+/// Upper bound on stored ranges; the oldest ranges are dropped beyond this
+pub const MAX_ACCESS_PATTERN_RANGES: usize = 4096;
+
+/// Visited frames as half-open ranges, in the order they were first visited
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessPattern {
+    ranges: Vec<(usize, usize)>,
+}
+
+impl AccessPattern {
+    /// Create an empty pattern
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a visit to a frame.
+    /// Sequential visits extend the current range, and repeated requests for a frame in the
+    /// current range (e.g. polling until it is decoded) are ignored.
+    pub fn record(&mut self, frame_index: usize) {
+        if let Some(last) = self.ranges.last_mut() {
+            if frame_index >= last.0 && frame_index < last.1 {
+                return;
+            }
+            if frame_index == last.1 {
+                last.1 += 1;
+                return;
+            }
+        }
+        if self.ranges.len() >= MAX_ACCESS_PATTERN_RANGES {
+            self.ranges.remove(0);
+        }
+        self.ranges.push((frame_index, frame_index + 1));
+    }
+
+    /// Visited ranges as `(start, end)`, end exclusive, in visit order
+    pub fn ranges(&self) -> &[(usize, usize)] {
+        &self.ranges
+    }
+
+    /// Visited frame indices in visit order
+    pub fn frames(&self) -> impl Iterator<Item = usize> + '_ {
+        self.ranges.iter().flat_map(|&(start, end)| start..end)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Parse the text format produced by `to_string()`.
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn parse(text: &str) -> Result<Self, FormatError> {
+        let mut pattern = Self::new();
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || FormatError::InvalidFormat(format!("Invalid access pattern line {}: '{}'", line_no + 1, line));
+            let (start, end) = match line.split_once("..") {
+                Some((start, end)) => (
+                    start.trim().parse::<usize>().map_err(|_| invalid())?,
+                    end.trim().parse::<usize>().map_err(|_| invalid())?,
+                ),
+                None => {
+                    let frame = line.parse::<usize>().map_err(|_| invalid())?;
+                    (frame, frame + 1)
+                }
+            };
+            if start >= end {
+                return Err(invalid());
+            }
+            pattern.ranges.push((start, end));
+        }
+        Ok(pattern)
+    }
+}
+
+impl fmt::Display for AccessPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &(start, end) in &self.ranges {
+            if end == start + 1 {
+                writeln!(f, "{}", start)?;
+            } else {
+                writeln!(f, "{}..{}", start, end)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_coalesces_sequential_and_repeated_visits() {
+        let mut pattern = AccessPattern::new();
+        for frame in [10, 10, 11, 12, 12, 11, 50, 3, 4] {
+            pattern.record(frame);
+        }
+        assert_eq!(pattern.ranges(), &[(10, 13), (50, 51), (3, 5)]);
+        assert_eq!(pattern.frames().collect::<Vec<_>>(), vec![10, 11, 12, 50, 3, 4]);
+    }
+
+    #[test]
+    fn test_record_drops_oldest_beyond_limit() {
+        let mut pattern = AccessPattern::new();
+        for i in 0..=MAX_ACCESS_PATTERN_RANGES {
+            pattern.record(i * 2);
+        }
+        assert_eq!(pattern.ranges().len(), MAX_ACCESS_PATTERN_RANGES);
+        assert_eq!(pattern.ranges()[0], (2, 3));
+    }
+
+    #[test]
+    fn test_text_roundtrip() {
+        let mut pattern = AccessPattern::new();
+        for frame in [10, 11, 12, 50, 3] {
+            pattern.record(frame);
+        }
+        let text = pattern.to_string();
+        assert_eq!(text, "10..13\n50\n3\n");
+        assert_eq!(AccessPattern::parse(&text).unwrap(), pattern);
+        assert_eq!(AccessPattern::parse("# bookmarks\n\n 7 \n").unwrap().ranges(), &[(7, 8)]);
+        assert!(AccessPattern::parse("5..5").is_err());
+        assert!(AccessPattern::parse("abc").is_err());
+    }
+}
@@ -2,16 +2,139 @@
 
 /// Builder configuration for AlphaStreamProcessor
 /// Allows configuration of runtime, cache, scheduler, and transport options.
-#[derive(Debug, Clone)]
+/// Serializes to JSON (see `to_json` / `from_json`) for presets and bug reports; missing fields
+/// take their defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AlphaStreamProcessorBuilder {
     runtime_threads: usize,           // Default: 0, Range: 0-64 - if 0, uses number of logical cores
+    execution_mode: ExecutionMode,    // Default: MultiThread
+    backend: Backend,                 // Default: Cpu
     timeout_seconds: u64,             // Default: 30, Range: 1-300
     cache_capacity: usize,            // Default: 512, Range: 1-4096
     prefetch_window: usize,           // Default: 16, Range: 1-500
+    seek_preroll: usize,              // Default: 0, Range: 0-MAX_SEEK_PREROLL
     processing_mode: ProcessingMode,  // Default: Bitmap
+    watch_source: bool,               // Default: false
+    simplify_tolerance: f32,          // Default: 0.0 (off), Range: 0-1000 native units
+    deterministic: bool,              // Default: false
+    decode_budget_ms: f32,            // Default: 0.0 (no budget), Range: 0-16.667 (one QOS_TICK)
+    channels: Option<Vec<usize>>,     // Default: None (all channels)
+    track: Option<String>,            // Default: None (first mask track of a container)
+    stride: usize,                    // Default: 1 (every frame), Range: 1-60
+    auto_fit: bool,                   // Default: false (stretch native canvas to output)
+    draw_outline: bool,               // Default: false (fill only)
+    output_packing: OutputPacking,    // Default: tightly packed R8
+    empty_frame_policy: EmptyFramePolicy, // Default: ReturnEmpty
+    log_level: LogLevel,              // Default: Info
+    cache_dir: Option<PathBuf>,       // Default: None (nothing persisted to disk)
+    baked_scene: Option<PathBuf>,     // Default: None (every frame is decoded from the source)
+    #[serde(skip)]
+    cache_key: Option<CacheKey>,      // Default: None (stores on disk are not encrypted)
+    #[serde(skip)]
+    clock: SharedClock,               // Default: system clock
+    range_requests: bool,             // Default: false (download HTTP sources in full)
+    parallel_ranges: usize,           // Default: 4, Range: 1-16
+    reader_shards: usize,             // Default: 1, Range: 1-16
+    cache_memory_budget: u64,         // Default: 4 GiB, Range: 1 MiB-1 TiB
+    fit_cache_to_budget: bool,        // Default: false (building fails when the cached masks exceed the budget)
+    tiled_rasterization: bool,        // Default: false (outputs beyond the backend's max_texture_size fail)
+    #[serde(skip)]
+    entitlement: Option<SharedEntitlementProvider>, // Default: None (no entitlement checks)
+    #[serde(skip)]
+    watermark: Option<Watermark>,     // Default: None (masks are not marked)
+    #[serde(skip)]
+    telemetry: SharedTelemetryExporter, // Default: no-op exporter
+    #[serde(skip)]
+    rasterizer: SharedRasterizer,     // Default: PolystreamRasterizer
 }
 
+/// Environment variable naming the defaults file; without it `alphastream.toml` in the working directory is used
+pub const CONFIG_FILE_ENV: &str = "ALPHASTREAM_CONFIG";
+/// Defaults file looked up in the working directory
+pub const DEFAULT_CONFIG_FILE: &str = "alphastream.toml";
+
+/// Settings ops can override for a deployed build, from `alphastream.toml` or ALPHASTREAM_* variables
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentDefaults {
+    cache_capacity: Option<usize>,
+    runtime_threads: Option<usize>,
+    log_level: Option<LogLevel>,
+    cache_dir: Option<PathBuf>,
+    // Only from the environment: keys do not belong in a config file
+    #[serde(skip)]
+    cache_key: Option<CacheKey>,
+}
+
+impl EnvironmentDefaults {
+    fn from_toml(text: &str) -> Result<Self, FormatError> {
+        toml::from_str(text).map_err(|e| FormatError::InvalidFormat(format!("Invalid {}: {}", DEFAULT_CONFIG_FILE, e)))
+    }
+
+    /// Override with ALPHASTREAM_CACHE_CAPACITY, ALPHASTREAM_THREADS, ALPHASTREAM_LOG_LEVEL, ALPHASTREAM_CACHE_DIR
+    /// and ALPHASTREAM_CACHE_KEY
+    fn merge_env(mut self, var: impl Fn(&str) -> Option<String>) -> Result<Self, FormatError> {
+        fn number(name: &str, value: String) -> Result<usize, FormatError> {
+            value.trim().parse().map_err(|_| FormatError::InvalidFormat(format!("{} must be a number, got '{}'", name, value)))
+        }
+        if let Some(value) = var("ALPHASTREAM_CACHE_CAPACITY") {
+            self.cache_capacity = Some(number("ALPHASTREAM_CACHE_CAPACITY", value)?);
+        }
+        if let Some(value) = var("ALPHASTREAM_THREADS") {
+            self.runtime_threads = Some(number("ALPHASTREAM_THREADS", value)?);
+        }
+        if let Some(value) = var("ALPHASTREAM_LOG_LEVEL") {
+            self.log_level = Some(value.parse()?);
+        }
+        if let Some(value) = var("ALPHASTREAM_CACHE_DIR") {
+            self.cache_dir = Some(PathBuf::from(value));
+        }
+        if let Some(value) = var("ALPHASTREAM_CACHE_KEY") {
+            self.cache_key = Some(value.parse()?);
+        }
+        Ok(self)
+    }
+}
+
+/// Largest factor `AlphaStreamProcessor::report_display_size` lowers the raster resolution by
+pub const MAX_RASTER_DIVISOR: u32 = 8;
+
+/// Upper bound for `AlphaStreamProcessorBuilder::seek_preroll`
+pub const MAX_SEEK_PREROLL: usize = 120;
+
+/// Upper bound for `AlphaStreamProcessorBuilder::reader_shards`
+pub const MAX_READER_SHARDS: usize = 16;
+
+/// Memory the cached masks of a processor may take unless `cache_memory_budget` says otherwise
+pub const DEFAULT_CACHE_MEMORY_BUDGET: u64 = 4 << 30;
+
+/// Largest output width or height, also with tiled rasterization: the pixel offsets of larger masks overflow 32 bits
+pub const MAX_OUTPUT_SIZE: u32 = 65535;
+
+/// Output size a processor cannot be built with, see `AlphaStreamProcessorBuilder::cache_memory_budget`
+/// and `AlphaStreamProcessorBuilder::tiled_rasterization`
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum DimensionError {
+    /// Larger than the backend rasterizes in one piece; tiled rasterization lifts the limit
+    #[error("Output size {width}x{height} exceeds the {backend} backend's limit of {max}x{max}; enable tiled rasterization")]
+    ExceedsBackend { width: u32, height: u32, max: u32, backend: &'static str },
+    /// Larger than MAX_OUTPUT_SIZE
+    #[error("Output size {width}x{height} exceeds the maximum of {max}x{max}")]
+    TooLarge { width: u32, height: u32, max: u32 },
+    /// The cache would hold more mask memory than the budget allows. `suggested_cache_capacity` is
+    /// the largest capacity that fits, None if not even one mask does.
+    #[error("{cache_capacity} cached {width}x{height} masks take {required} bytes, more than the budget of {budget} bytes; {}",
+        .suggested_cache_capacity.map_or("reduce the output size".to_string(), |capacity| format!("a cache capacity of {} fits", capacity)))]
+    CacheBudget { width: u32, height: u32, cache_capacity: usize, required: u64, budget: u64, suggested_cache_capacity: Option<usize> },
+}
+
+/// Worker thread count for deterministic mode when no explicit count is configured.
+/// A single worker makes decode tasks complete in the order they were scheduled.
+pub const DETERMINISTIC_WORKER_THREADS: usize = 1;
+
 /// Processing type for builder config (matches ProcessingMode)
+#[cfg(feature = "unstable")]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BuilderProcessingType {
     Triangles,
@@ -23,10 +146,39 @@ impl Default for AlphaStreamProcessorBuilder {
     fn default() -> Self {
         Self {
             runtime_threads: 0,
+            execution_mode: ExecutionMode::MultiThread,
+            backend: Backend::Cpu,
             timeout_seconds: 30,
             cache_capacity: 512,
             prefetch_window: 16,
+            seek_preroll: 0,
             processing_mode: ProcessingMode::Bitmap,
+            watch_source: false,
+            simplify_tolerance: 0.0,
+            deterministic: false,
+            decode_budget_ms: 0.0,
+            channels: None,
+            track: None,
+            stride: 1,
+            auto_fit: false,
+            draw_outline: false,
+            output_packing: OutputPacking::default(),
+            empty_frame_policy: EmptyFramePolicy::default(),
+            log_level: LogLevel::Info,
+            cache_dir: None,
+            baked_scene: None,
+            cache_key: None,
+            clock: SharedClock::default(),
+            range_requests: false,
+            parallel_ranges: 4,
+            reader_shards: 1,
+            cache_memory_budget: DEFAULT_CACHE_MEMORY_BUDGET,
+            fit_cache_to_budget: false,
+            tiled_rasterization: false,
+            entitlement: None,
+            watermark: None,
+            telemetry: SharedTelemetryExporter::default(),
+            rasterizer: SharedRasterizer::default(),
         }
     }
 }
@@ -35,10 +187,115 @@ impl AlphaStreamProcessorBuilder {
     pub fn new() -> Self {
         Self::default()
     }
+    /// Parse a configuration produced by `to_json` (or `AlphaStreamProcessor::config`).
+    /// Values are clamped to the same ranges as the setters.
+    pub fn from_json(json: &str) -> Result<Self, FormatError> {
+        let config: Self = serde_json::from_str(json)
+            .map_err(|e| FormatError::InvalidFormat(format!("Invalid processor config: {}", e)))?;
+        Ok(config.clamped())
+    }
+    /// Builder with defaults from the deployment environment, see `apply_environment`
+    pub fn from_environment() -> Result<Self, FormatError> {
+        Self::new().apply_environment()
+    }
+    /// Override settings with the deployment's defaults so ops can tune a shipped build without code changes.
+    /// Reads the TOML file named by ALPHASTREAM_CONFIG, or `alphastream.toml` in the working directory if it
+    /// exists, then ALPHASTREAM_CACHE_CAPACITY, ALPHASTREAM_THREADS, ALPHASTREAM_LOG_LEVEL and
+    /// ALPHASTREAM_CACHE_DIR, which take precedence over the file. The file accepts the keys
+    /// `cache_capacity`, `runtime_threads`, `log_level` and `cache_dir`. Unset values keep the builder's.
+    /// ALPHASTREAM_CACHE_KEY (64 hex digits) sets `cache_key`; it is not read from the file.
+    pub fn apply_environment(self) -> Result<Self, FormatError> {
+        let file = match std::env::var(CONFIG_FILE_ENV) {
+            Ok(path) => Some(std::fs::read_to_string(path)?),
+            Err(_) => std::fs::read_to_string(DEFAULT_CONFIG_FILE).ok(),
+        };
+        self.apply_defaults(file.as_deref(), |name| std::env::var(name).ok())
+    }
+    fn apply_defaults(mut self, file: Option<&str>, var: impl Fn(&str) -> Option<String>) -> Result<Self, FormatError> {
+        let defaults = match file {
+            Some(text) => EnvironmentDefaults::from_toml(text)?,
+            None => EnvironmentDefaults::default(),
+        };
+        let defaults = defaults.merge_env(var)?;
+        if let Some(capacity) = defaults.cache_capacity {
+            self = self.cache_capacity(capacity);
+        }
+        if let Some(threads) = defaults.runtime_threads {
+            self = self.runtime_threads(threads);
+        }
+        if let Some(level) = defaults.log_level {
+            self = self.log_level(level);
+        }
+        if let Some(dir) = defaults.cache_dir {
+            self = self.cache_dir(dir);
+        }
+        if let Some(key) = defaults.cache_key {
+            self = self.cache_key(key);
+        }
+        Ok(self)
+    }
+    /// Configuration as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("processor config is always serializable")
+    }
+    /// Re-apply the setter ranges to values that did not come through the setters
+    fn clamped(self) -> Self {
+        let channels = self.channels.clone();
+        let mut config = self.clone()
+            .runtime_threads(self.runtime_threads)
+            .timeout_seconds(self.timeout_seconds)
+            .cache_capacity(self.cache_capacity)
+            .prefetch_window(self.prefetch_window)
+            .seek_preroll(self.seek_preroll)
+            .simplify_tolerance(self.simplify_tolerance)
+            .decode_budget_ms(self.decode_budget_ms)
+            .stride(self.stride)
+            .parallel_ranges(self.parallel_ranges)
+            .reader_shards(self.reader_shards)
+            .cache_memory_budget(self.cache_memory_budget);
+        if let Some(channels) = channels {
+            config = config.channels(&channels);
+        }
+        config
+    }
+    /// The configuration a built processor reports: deterministic mode's thread count is filled in
+    fn effective(&self) -> Self {
+        Self { runtime_threads: self.effective_runtime_threads(), ..self.clone() }
+    }
     pub fn runtime_threads(mut self, threads: usize) -> Self {
         self.runtime_threads = threads.clamp(0, 64);
         self
     }
+    /// How the processor's background work is executed. `ExecutionMode::LocalSet` runs all of it on one
+    /// dedicated thread with a LocalSet, for pipelines with components that are not Send; runtime_threads
+    /// does not apply then.
+    pub fn execution_mode(mut self, mode: ExecutionMode) -> Self {
+        self.execution_mode = mode;
+        self
+    }
+    /// Rasterizer backend, see `backend::available_backends`. Building fails if the backend is not
+    /// available on this machine.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+    /// Error if the configured backend cannot run here
+    fn check_backend(&self) -> Result<(), FormatError> {
+        if self.backend.is_available() {
+            Ok(())
+        } else {
+            Err(FormatError::InvalidFormat(format!("Backend {} is not available on this machine", self.backend.info().name)))
+        }
+    }
+    /// Runtime for a processor built with this configuration
+    fn build_runtime(&self) -> Runtime {
+        let builder = RuntimeBuilder::new().execution_mode(self.execution_mode);
+        let builder = match self.effective_runtime_threads() {
+            0 => builder,
+            threads => builder.worker_threads(threads),
+        };
+        builder.build().expect("Failed to create runtime")
+    }
     pub fn timeout_seconds(mut self, secs: u64) -> Self {
         self.timeout_seconds = secs.clamp(1, 300);
         self
@@ -47,42 +304,349 @@ impl AlphaStreamProcessorBuilder {
         self.cache_capacity = cap.clamp(1, 4096);
         self
     }
+    /// Most memory, in bytes, the cached masks may take: cache_capacity masks of width x height bytes
+    /// in the processing modes that rasterize. Building with an output size that needs more fails with
+    /// `DimensionError::CacheBudget`, which suggests a capacity that fits, unless fit_cache_to_budget is set.
+    pub fn cache_memory_budget(mut self, bytes: u64) -> Self {
+        self.cache_memory_budget = bytes.clamp(1 << 20, 1 << 40);
+        self
+    }
+    /// Lower the cache capacity to what fits the cache memory budget instead of failing the build,
+    /// logging a warning. Playback of large outputs then has a shorter read-ahead.
+    pub fn fit_cache_to_budget(mut self, enabled: bool) -> Self {
+        self.fit_cache_to_budget = enabled;
+        self
+    }
+    /// Rasterize outputs wider or taller than the backend's max_texture_size in tiles of that size
+    /// instead of failing the build, up to MAX_OUTPUT_SIZE. The tiles join without seams.
+    pub fn tiled_rasterization(mut self, enabled: bool) -> Self {
+        self.tiled_rasterization = enabled;
+        self
+    }
+    /// Check an output size against the backend and the cache memory budget, lowering the cache
+    /// capacity if fit_cache_to_budget allows it
+    fn check_dimensions(mut self, width: u32, height: u32) -> Result<Self, DimensionError> {
+        let info = self.backend.info();
+        if width > MAX_OUTPUT_SIZE || height > MAX_OUTPUT_SIZE {
+            return Err(DimensionError::TooLarge { width, height, max: MAX_OUTPUT_SIZE });
+        }
+        if !self.tiled_rasterization && (width > info.max_texture_size || height > info.max_texture_size) {
+            return Err(DimensionError::ExceedsBackend { width, height, max: info.max_texture_size, backend: info.name });
+        }
+        if !matches!(self.processing_mode, ProcessingMode::Bitmap | ProcessingMode::Both) {
+            return Ok(self);
+        }
+        let mask_bytes = (width as u64 * height as u64).max(1);
+        let required = mask_bytes * self.cache_capacity as u64;
+        if required <= self.cache_memory_budget {
+            return Ok(self);
+        }
+        let suggested = (self.cache_memory_budget / mask_bytes) as usize;
+        let suggested_cache_capacity = (suggested > 0).then_some(suggested);
+        match suggested_cache_capacity {
+            Some(capacity) if self.fit_cache_to_budget => {
+                logging::log(LogLevel::Warn, format_args!("Cache capacity lowered from {} to {} for {}x{} masks", self.cache_capacity, capacity, width, height));
+                self.cache_capacity = capacity;
+                Ok(self)
+            }
+            _ => Err(DimensionError::CacheBudget {
+                width,
+                height,
+                cache_capacity: self.cache_capacity,
+                required,
+                budget: self.cache_memory_budget,
+                suggested_cache_capacity,
+            }),
+        }
+    }
+    /// Largest tile the backend rasterizes, if an output of this size has to be tiled
+    fn tile_size(&self, width: u32, height: u32) -> Option<u32> {
+        let max = self.backend.info().max_texture_size;
+        (width > max || height > max).then_some(max)
+    }
     pub fn prefetch_window(mut self, win: usize) -> Self {
         self.prefetch_window = win.clamp(1, 100);
         self
     }
+    /// Frames of history decoded along with a requested frame, e.g. 2 for a temporal filter that blends
+    /// each mask with the two before it. After a seek to frame F, F - preroll..F are decoded right after F
+    /// (ahead of the read-ahead) and kept in the cache window; reading them does not move the play head
+    /// or count as a backward seek. Counted in decoded frames with a stride, and limited to half the cache capacity.
+    pub fn seek_preroll(mut self, frames: usize) -> Self {
+        self.seek_preroll = frames.min(MAX_SEEK_PREROLL);
+        self
+    }
     pub fn processing_mode(mut self, mode: ProcessingMode) -> Self {
         self.processing_mode = mode;
         self
     }
+    /// Douglas-Peucker tolerance applied to outlines before triangulation, in native polystream units.
+    /// Reduces the vertex count of triangle strip output; 0 keeps every point.
+    pub fn simplify_tolerance(mut self, tolerance: f32) -> Self {
+        self.simplify_tolerance = tolerance.clamp(0.0, 1000.0);
+        self
+    }
+    /// Only rasterize / triangulate the given channel indices, e.g. `&[0]` for the primary mask.
+    /// Other channels are skipped entirely, which saves CPU on frames with many channels.
+    /// Indices beyond a frame's channel count are ignored.
+    pub fn channels(mut self, channels: &[usize]) -> Self {
+        let mut channels = channels.to_vec();
+        channels.sort_unstable();
+        channels.dedup();
+        self.channels = Some(channels);
+        self
+    }
+    /// Deterministic mode for benchmarking and CI performance comparisons.
+    /// Fixes the worker thread count (to `runtime_threads`, or DETERMINISTIC_WORKER_THREADS when that
+    /// is 0), disables adaptive heuristics, and makes the scheduling order independent of request timing.
+    pub fn deterministic(mut self, enabled: bool) -> Self {
+        self.deterministic = enabled;
+        self
+    }
+    /// Soft real-time mode for hosts that render in the same process: decode at most this many milliseconds
+    /// per 16.6 ms frame interval (`scheduler::QOS_TICK`) on average. Once the budget is used up, prefetch
+    /// is deferred to later intervals, while frames a getter asks for are still decoded right away.
+    /// 0 decodes as fast as the workers allow. Has no effect in deterministic mode.
+    pub fn decode_budget_ms(mut self, budget_ms: f32) -> Self {
+        self.decode_budget_ms = budget_ms.clamp(0.0, crate::scheduler::QOS_TICK.as_secs_f32() * 1000.0);
+        self
+    }
+    /// decode_budget_ms as a scheduler budget, None without one
+    fn decode_budget(&self) -> Option<Duration> {
+        (self.decode_budget_ms > 0.0).then(|| Duration::from_secs_f32(self.decode_budget_ms / 1000.0))
+    }
+    /// Worker threads used in deterministic mode when runtime_threads is 0
+    fn effective_runtime_threads(&self) -> usize {
+        if self.deterministic && self.runtime_threads == 0 {
+            DETERMINISTIC_WORKER_THREADS
+        } else {
+            self.runtime_threads
+        }
+    }
+    /// Sparse playback: only decode every `stride`th frame, e.g. 4 for a 15fps preview of a 60fps stream.
+    /// Requests for any frame return the nearest decoded frame at or before it, and prefetch and the
+    /// cache work in strided frames, so the skipped frames are never decoded.
+    pub fn stride(mut self, stride: usize) -> Self {
+        self.stride = stride.clamp(1, 60);
+        self
+    }
+    /// Fit the mask into the output preserving its aspect ratio instead of stretching the native canvas.
+    /// The native size is the polystream canvas, grown to the coordinate extents of the first frame so
+    /// content outside it is scaled down rather than clipped. The letterbox offsets are reported by
+    /// `AlphaStreamProcessor::output_transform`.
+    pub fn auto_fit(mut self, enabled: bool) -> Self {
+        self.auto_fit = enabled;
+        self
+    }
+    /// Draw polygon outlines on top of the fill in bitmap output.
+    /// Keeps thin and sub-pixel shapes visible, but can leave single-pixel spurs outside the mask.
+    pub fn draw_outline(mut self, enabled: bool) -> Self {
+        self.draw_outline = enabled;
+        self
+    }
+    /// Memory layout of the bitmaps `get_frame` and `get_frame_output` return: pixel format, row
+    /// alignment and byte order. Masks are cached as R8 and packed while they are copied out, so
+    /// statistics, exports and watermarks are not affected.
+    pub fn output_packing(mut self, packing: OutputPacking) -> Self {
+        self.output_packing = packing;
+        self
+    }
+    /// What the getters return for frames without outline data, see `EmptyFramePolicy`
+    pub fn empty_frame_policy(mut self, policy: EmptyFramePolicy) -> Self {
+        self.empty_frame_policy = policy;
+        self
+    }
+    /// Most verbose level of diagnostic messages. The level is process-wide and set when the processor is built.
+    pub fn log_level(mut self, level: LogLevel) -> Self {
+        self.log_level = level;
+        self
+    }
+    /// Directory for data persisted between sessions. None keeps everything in memory.
+    pub fn cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+    /// Key encrypting decrypted content the library stores on disk, such as the frames of a
+    /// `store://` FrameStore source. Without it stores are plaintext, and encrypted stores cannot be opened.
+    /// The key is never part of the JSON configuration.
+    pub fn cache_key(mut self, key: CacheKey) -> Self {
+        self.cache_key = Some(key);
+        self
+    }
+    /// Ask `provider` before an encrypted (ASVR) source is opened and again during playback, see
+    /// `EntitlementProvider`. A refusal on open fails the build with `FormatError::Entitlement`; a
+    /// refusal later stops decryption, drops the cached frames and emits `ProcessorEvent::EntitlementDenied`.
+    /// The provider is not part of the JSON configuration.
+    pub fn entitlement_provider(mut self, provider: Arc<dyn EntitlementProvider>) -> Self {
+        self.entitlement = Some(SharedEntitlementProvider::new(provider));
+        self
+    }
+    /// Embed `watermark` invisibly into every mask the processor rasterizes, so a leaked render can be
+    /// traced with `Watermark::verify`. Masks are marked with the index of the decoded frame (a multiple
+    /// of the stride). The watermark is not part of the JSON configuration, its key is a secret.
+    pub fn watermark(mut self, watermark: Watermark) -> Self {
+        self.watermark = Some(watermark);
+        self
+    }
+    /// Send decode, seek, cache eviction and transport retry events to `exporter`, e.g. a `MetricsExporter`
+    /// or an adapter to the deployment's monitoring. The exporter is not part of the JSON configuration.
+    pub fn telemetry_exporter(mut self, exporter: Arc<dyn TelemetryExporter>) -> Self {
+        self.telemetry = SharedTelemetryExporter::new(exporter);
+        self
+    }
+    /// Rasterizer drawing masks and triangle strips, e.g. a Skia-based implementation of `Rasterizer`.
+    /// Defaults to the built-in `PolystreamRasterizer`; it is not part of the JSON configuration.
+    pub fn rasterizer(mut self, rasterizer: Arc<dyn Rasterizer>) -> Self {
+        self.rasterizer = SharedRasterizer::new(rasterizer);
+        self
+    }
+    /// Time source for traces, deadlines and timeouts. Tests pass a `MockClock` to control time;
+    /// the clock is not part of the JSON configuration.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = SharedClock::new(clock);
+        self
+    }
+    /// Output transform for the configured fit mode
+    async fn output_transform(&self, format: &mut FormatType<ReaderWrapper>, width: u32, height: u32) -> Result<OutputTransform, FormatError> {
+        if !self.auto_fit {
+            return Ok(OutputTransform::stretch(width, height));
+        }
+        let (native_width, native_height) = AlphaStreamProcessor::detect_native_size(format).await?;
+        Ok(OutputTransform::fit(native_width, native_height, width, height))
+    }
+    /// Stream HTTP sources with Range requests instead of downloading them in full before the first frame.
+    /// Reads are coalesced into chunks of DEFAULT_COALESCE_CHUNK_SIZE, so sequential playback costs one
+    /// request per chunk of adjacent frames. The server must support Range requests.
+    pub fn range_requests(mut self, enabled: bool) -> Self {
+        self.range_requests = enabled;
+        self
+    }
+    /// Range requests a streamed HTTP source keeps in flight. When playback (or the prefetch scheduler
+    /// decoding ahead of it) reads sequentially, the following chunks are fetched in parallel; random
+    /// access fetches one chunk at a time. Requests per host are further limited process-wide by
+    /// `transport::set_host_concurrency`.
+    pub fn parallel_ranges(mut self, count: usize) -> Self {
+        self.parallel_ranges = count.clamp(1, MAX_PARALLEL_RANGES);
+        self
+    }
+    /// Readers the decode tasks spread over. Every reader is opened independently (its own file handle,
+    /// or its own range-reading HTTP client), so decodes no longer queue on a single reader. Sources that
+    /// cannot be opened again, such as fully downloaded HTTP sources and store scenes, use one reader.
+    pub fn reader_shards(mut self, count: usize) -> Self {
+        self.reader_shards = count.clamp(1, MAX_READER_SHARDS);
+        self
+    }
+    /// Play the mask track `name` of a multi-track container (see the `container` module) instead
+    /// of its first mask track. Building from a plain ASVP file fails when a track is set.
+    pub fn track(mut self, name: &str) -> Self {
+        self.track = Some(name.to_string());
+        self
+    }
+    /// Serve frames from a scene baked with `AlphaStreamProcessor::bake`, skipping decryption, inflation
+    /// and rasterization for every frame it holds in the outputs of the processing mode. The scene must
+    /// have been baked at the output size from a source with the same frame count; channels, fit,
+    /// watermark and simplification are those of the processor that baked it.
+    pub fn baked_scene(mut self, path: impl Into<PathBuf>) -> Self {
+        self.baked_scene = Some(path.into());
+        self
+    }
+    /// Open the `baked_scene`, checking it against the output size and the source
+    async fn open_baked_scene(&self, format: &mut FormatType<ReaderWrapper>, width: u32, height: u32) -> Result<Option<Arc<BakedScene>>, FormatError> {
+        let Some(path) = &self.baked_scene else {
+            return Ok(None);
+        };
+        let scene = BakedScene::open(path)?;
+        if (scene.width(), scene.height()) != (width, height) {
+            return Err(FormatError::InvalidFormat(format!("Scene was baked at {}x{}, not {}x{}", scene.width(), scene.height(), width, height)));
+        }
+        let frame_count = format.metadata().await?.frame_count;
+        if scene.frame_count() != frame_count {
+            return Err(FormatError::InvalidFormat(format!("Baked scene has {} frames, the source {}", scene.frame_count(), frame_count)));
+        }
+        Ok(Some(Arc::new(scene)))
+    }
+    /// Reader for a local path, HTTP(S) URL or `store://DIR#NAME` scene of a FrameStore, or a file in memory
+    async fn open_reader(&self, source: &BuildSource<'_>) -> Result<ReaderWrapper, FormatError> {
+        let uri = match source {
+            BuildSource::Uri(uri) => *uri,
+            BuildSource::Memory(bytes) => return Ok(ReaderWrapper::Cursor(CursorWrapper(std::io::Cursor::new(bytes.clone())))),
+        };
+        if let Some((dir, name)) = parse_store_uri(uri) {
+            let asvp = FrameStore::open_with_key(dir, self.cache_key.clone())?.to_asvp(name)?;
+            return Ok(ReaderWrapper::Cursor(CursorWrapper(std::io::Cursor::new(asvp.into()))));
+        }
+        if !uri.starts_with("http") {
+            return Ok(ReaderWrapper::File(tokio::fs::File::open(uri).await?));
+        }
+        if let Some(remote) = RemoteSource::for_uri(uri, self) {
+            return remote.open().await;
+        }
+        let bytes = reqwest::get(uri).await.map_err(|e| FormatError::InvalidFormat(e.to_string()))?.bytes().await.map_err(|e| FormatError::InvalidFormat(e.to_string()))?;
+        Ok(ReaderWrapper::Cursor(CursorWrapper(std::io::Cursor::new(bytes))))
+    }
+    /// Watch a local source file and reload it when it is replaced or appended to.
+    /// Only frames whose content changed are evicted from the cache, see `AlphaStreamProcessor::reload`.
+    pub fn watch_source(mut self, enabled: bool) -> Self {
+        self.watch_source = enabled;
+        self
+    }
     /// Build an AlphaStreamProcessor with the configured options for ASVP (plaintext) files
     pub async fn build_asvp(self, uri: &str, width: u32, height: u32) -> Result<AlphaStreamProcessor, FormatError> {
+        self.build_asvp_source(BuildSource::Uri(uri), width, height).await
+    }
+
+    /// Build an AlphaStreamProcessor for an ASVP file that is already in memory (an embedded resource,
+    /// a download made by the host), without writing it to a temporary file.
+    /// Nothing can be reopened or watched: the processor uses one reader and `watch_source` fails.
+    pub async fn build_from_bytes(self, bytes: impl Into<bytes::Bytes>, width: u32, height: u32) -> Result<AlphaStreamProcessor, FormatError> {
+        self.build_asvp_source(BuildSource::Memory(bytes.into()), width, height).await
+    }
+
+    /// Build an AlphaStreamProcessor for an ASVR file that is already in memory, see `build_from_bytes`.
+    /// The key is derived from `scene_id`, `version` and `base_url` as for `build_asvr`.
+    pub async fn build_asvr_from_bytes(
+        self,
+        bytes: impl Into<bytes::Bytes>,
+        scene_id: u32,
+        version: &[u8],
+        base_url: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<AlphaStreamProcessor, FormatError> {
+        self.build_asvr_source(BuildSource::Memory(bytes.into()), scene_id, version, base_url, width, height).await
+    }
+
+    async fn build_asvp_source(mut self, source: BuildSource<'_>, width: u32, height: u32) -> Result<AlphaStreamProcessor, FormatError> {
         use crate::formats::ASVPFormat;
         use crate::cache::FrameCache;
         use crate::scheduler::Scheduler;
-        use crate::runtime::Runtime;
         use std::sync::Arc;
         use tokio::sync::Mutex;
 
-        let reader = if uri.starts_with("http") {
-            let bytes = reqwest::get(uri).await.map_err(|e| FormatError::InvalidFormat(e.to_string()))?.bytes().await.map_err(|e| FormatError::InvalidFormat(e.to_string()))?;
-            ReaderWrapper::Cursor(CursorWrapper(std::io::Cursor::new(bytes)))
-        } else {
-            ReaderWrapper::File(tokio::fs::File::open(uri).await?)
-        };
-        let format_inner = ASVPFormat::new(reader).await?;
-        let format = Arc::new(Mutex::new(FormatType::ASVP(format_inner)));
+        self.check_backend()?;
+        self = self.check_dimensions(width, height)?;
+        let reader = self.open_reader(&source).await?;
+        crate::logging::set_level(self.log_level);
+        let mut format_inner = FormatType::ASVP(ASVPFormat::with_track(reader, self.track.as_deref()).await?);
+        let transform = self.output_transform(&mut format_inner, width, height).await?;
+        let baked = self.open_baked_scene(&mut format_inner, width, height).await?;
+        let remote = source.uri().and_then(|uri| RemoteSource::for_uri(uri, &self));
+        let shards = ReaderShards::open(format_inner, self.reader_shards, source.local_path(), remote.clone()).await;
+        let format = Arc::clone(shards.primary());
         let cache = Arc::new(FrameCache::new(self.cache_capacity));
+        cache.set_preroll(self.seek_preroll);
+        cache.set_telemetry(self.telemetry.clone());
         let mut scheduler_obj = Scheduler::new();
         scheduler_obj.set_cache(Arc::clone(&cache));
+        scheduler_obj.set_preroll(cache.preroll());
         scheduler_obj.set_max_concurrent(self.prefetch_window);
         scheduler_obj.set_prefetch_count(self.prefetch_window);
+        scheduler_obj.set_deterministic(self.deterministic);
+        scheduler_obj.set_decode_budget(self.decode_budget());
+        scheduler_obj.set_clock(self.clock.clone());
         let scheduler = Arc::new(Mutex::new(scheduler_obj));
-        let runtime = if self.runtime_threads == 0 {
-            Runtime::new().expect("Failed to create runtime")
-        } else {
-            Runtime::with_worker_threads(self.runtime_threads).expect("Failed to create runtime")
-        };
+        let runtime = self.build_runtime();
+        let raster_options = RasterOptions { transform, outline: self.draw_outline, tile_size: self.tile_size(width, height) };
 
         let mut processor = AlphaStreamProcessor {
             cache: Arc::clone(&cache),
@@ -90,11 +654,44 @@ impl AlphaStreamProcessorBuilder {
             format,
             width,
             height,
-            mode: self.processing_mode,
+            mode: Arc::new(std::sync::Mutex::new(self.processing_mode)),
             runtime: Some(runtime),
             background_handle: None,
+            shutdown: None,
+            cancel: Arc::default(),
+            source_path: source.local_path(),
+            entitlement: None,
+            remote,
+            shards: Arc::new(shards),
+            watcher: None,
+            reload_handle: None,
+            simplify_tolerance: self.simplify_tolerance,
+            access_log: std::sync::Mutex::new(AccessPattern::new()),
+            bookmarks: std::sync::Mutex::new(Vec::new()),
+            channels: self.channels.clone(),
+            events: Arc::new(EventQueue::default()),
+            stride: self.stride,
+            raster_options,
+            packing: self.output_packing,
+            empty_frame_policy: self.empty_frame_policy,
+            watermark: self.watermark,
+            telemetry: self.telemetry.clone(),
+            rasterizer: self.rasterizer.clone(),
+            baked,
+            traces: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            bytes_read: Arc::default(),
+            frame_signal: Arc::new(FrameSignal::default()),
+            raster_divisor: Arc::new(std::sync::atomic::AtomicU32::new(1)),
+            output_size: Arc::new(std::sync::Mutex::new((width, height, raster_options))),
+            config: self.effective(),
+            clock: self.clock.clone(),
         };
+        processor.load_bookmarks().await;
+        processor.load_keyframes().await;
         processor.start_background_processing();
+        if self.watch_source {
+            processor.start_watching()?;
+        }
         Ok(processor)
     }
 
@@ -107,33 +704,57 @@ impl AlphaStreamProcessorBuilder {
         base_url: &[u8],
         width: u32,
         height: u32,
+    ) -> Result<AlphaStreamProcessor, FormatError> {
+        self.build_asvr_source(BuildSource::Uri(uri), scene_id, version, base_url, width, height).await
+    }
+
+    async fn build_asvr_source(
+        mut self,
+        source: BuildSource<'_>,
+        scene_id: u32,
+        version: &[u8],
+        base_url: &[u8],
+        width: u32,
+        height: u32,
     ) -> Result<AlphaStreamProcessor, FormatError> {
         use crate::formats::ASVRFormat;
         use crate::cache::FrameCache;
         use crate::scheduler::Scheduler;
-        use crate::runtime::Runtime;
         use std::sync::Arc;
         use tokio::sync::Mutex;
 
-        let reader = if uri.starts_with("http") {
-            let bytes = reqwest::get(uri).await.map_err(|e| FormatError::InvalidFormat(e.to_string()))?.bytes().await.map_err(|e| FormatError::InvalidFormat(e.to_string()))?;
-            ReaderWrapper::Cursor(CursorWrapper(std::io::Cursor::new(bytes)))
-        } else {
-            ReaderWrapper::File(tokio::fs::File::open(uri).await?)
+        self.check_backend()?;
+        self = self.check_dimensions(width, height)?;
+        // Nothing is fetched or decrypted before the provider agrees
+        let entitlement = match &self.entitlement {
+            Some(provider) => {
+                let request = EntitlementRequest { uri: source.uri().unwrap_or_default().to_string(), scene_id, version: version.to_vec() };
+                Some(Arc::new(EntitlementGate::open(provider.clone(), request, self.clock.clone())?))
+            }
+            None => None,
         };
-        let format_inner = ASVRFormat::new(reader, scene_id, version, base_url).await?;
-        let format = Arc::new(Mutex::new(FormatType::ASVR(format_inner)));
+        let reader = self.open_reader(&source).await?;
+        crate::logging::set_level(self.log_level);
+        let mut format_inner = FormatType::ASVR(ASVRFormat::new(reader, scene_id, version, base_url).await?);
+        let transform = self.output_transform(&mut format_inner, width, height).await?;
+        let baked = self.open_baked_scene(&mut format_inner, width, height).await?;
+        let remote = source.uri().and_then(|uri| RemoteSource::for_uri(uri, &self));
+        let shards = ReaderShards::open(format_inner, self.reader_shards, source.local_path(), remote.clone()).await;
+        let format = Arc::clone(shards.primary());
         let cache = Arc::new(FrameCache::new(self.cache_capacity));
+        cache.set_preroll(self.seek_preroll);
+        cache.set_telemetry(self.telemetry.clone());
         let mut scheduler_obj = Scheduler::new();
         scheduler_obj.set_cache(Arc::clone(&cache));
+        scheduler_obj.set_preroll(cache.preroll());
         scheduler_obj.set_max_concurrent(self.prefetch_window);
         scheduler_obj.set_prefetch_count(self.prefetch_window);
+        scheduler_obj.set_deterministic(self.deterministic);
+        scheduler_obj.set_decode_budget(self.decode_budget());
+        scheduler_obj.set_clock(self.clock.clone());
         let scheduler = Arc::new(Mutex::new(scheduler_obj));
-        let runtime = if self.runtime_threads == 0 {
-            Runtime::new().expect("Failed to create runtime")
-        } else {
-            Runtime::with_worker_threads(self.runtime_threads).expect("Failed to create runtime")
-        };
+        let runtime = self.build_runtime();
+        let raster_options = RasterOptions { transform, outline: self.draw_outline, tile_size: self.tile_size(width, height) };
 
 
         let mut processor = AlphaStreamProcessor {
@@ -142,11 +763,44 @@ impl AlphaStreamProcessorBuilder {
             format,
             width,
             height,
-            mode: self.processing_mode,
+            mode: Arc::new(std::sync::Mutex::new(self.processing_mode)),
             runtime: Some(runtime),
             background_handle: None,
+            shutdown: None,
+            cancel: Arc::default(),
+            source_path: source.local_path(),
+            entitlement,
+            remote,
+            shards: Arc::new(shards),
+            watcher: None,
+            reload_handle: None,
+            simplify_tolerance: self.simplify_tolerance,
+            access_log: std::sync::Mutex::new(AccessPattern::new()),
+            bookmarks: std::sync::Mutex::new(Vec::new()),
+            channels: self.channels.clone(),
+            events: Arc::new(EventQueue::default()),
+            stride: self.stride,
+            raster_options,
+            packing: self.output_packing,
+            empty_frame_policy: self.empty_frame_policy,
+            watermark: self.watermark,
+            telemetry: self.telemetry.clone(),
+            rasterizer: self.rasterizer.clone(),
+            baked,
+            traces: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            bytes_read: Arc::default(),
+            frame_signal: Arc::new(FrameSignal::default()),
+            raster_divisor: Arc::new(std::sync::atomic::AtomicU32::new(1)),
+            output_size: Arc::new(std::sync::Mutex::new((width, height, raster_options))),
+            config: self.effective(),
+            clock: self.clock.clone(),
         };
+        processor.load_bookmarks().await;
+        processor.load_keyframes().await;
         processor.start_background_processing();
+        if self.watch_source {
+            processor.start_watching()?;
+        }
         Ok(processor)
     }
 }
@@ -158,14 +812,37 @@ impl AlphaStreamProcessorBuilder {
 // It handles opening files, processing frames asynchronously (meaning tasks can run in the background
 // without blocking the main program), and provides methods to get processed frames.
 
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use tokio::sync::Mutex;
 
+use crate::access::AccessPattern;
+use crate::backend::Backend;
 use crate::cache::{FrameCache, FrameData};
+use crate::container::{Annotation, Cue, CueTrack, Thumbnail, TrackInfo, BOOKMARK_TRACK};
+use crate::clock::{Clock, SharedClock};
 use crate::formats::{ASFormat, ASVRFormat, ASVPFormat, FormatError, FormatType};
-use crate::rasterizer::PolystreamRasterizer;
-use crate::runtime::Runtime;
-use crate::scheduler::{Scheduler, Task};
+use crate::bake::{BakeWriter, BakedScene};
+use crate::layout::ExportLayout;
+use crate::logging::{self, LogLevel};
+use crate::overlay::Overlay;
+use crate::rasterizer::{resize_nearest_neighbor, OutputPacking, OutputTransform, PolystreamRasterizer, RasterOptions, Rasterizer, SharedRasterizer, Tile, TriangleMesh, NATIVE_HEIGHT, NATIVE_WIDTH};
+use crate::runtime::{ExecutionMode, Runtime, RuntimeBuilder};
+use crate::scheduler::{Priority, Scheduler, Task};
+use crate::telemetry::{SharedTelemetryExporter, TelemetryEvent, TelemetryExporter};
+use crate::transport::{CoalescingReader, HttpTransport, Transport, DEFAULT_COALESCE_CHUNK_SIZE, MAX_PARALLEL_RANGES};
+use crate::filter::FrameFilter;
+#[cfg(feature = "scripting")]
+use crate::script::{ChannelStats, FrameScript, ScriptFrame};
+use crate::stats::{Heatmap, MaskStats};
+use crate::store::{parse_store_uri, CacheKey, FrameStore, STORE_SCHEME};
+use crate::watermark::Watermark;
+use crate::profiling::{stage_span, Instrument};
+use crate::entitlement::{EntitlementError, EntitlementGate, EntitlementProvider, EntitlementRequest, SharedEntitlementProvider};
 
 /// Wrapper for Cursor to avoid conflicts
 pub struct CursorWrapper(std::io::Cursor<bytes::Bytes>);
@@ -196,6 +873,8 @@ impl Unpin for CursorWrapper {}
 pub enum ReaderWrapper {
     File(tokio::fs::File),
     Cursor(CursorWrapper),
+    /// HTTP source read with coalesced Range requests
+    Remote(Box<CoalescingReader<HttpTransport>>),
 }
 
 impl tokio::io::AsyncRead for ReaderWrapper {
@@ -207,6 +886,7 @@ impl tokio::io::AsyncRead for ReaderWrapper {
         match self.get_mut() {
             ReaderWrapper::File(f) => std::pin::Pin::new(f).poll_read(cx, buf),
             ReaderWrapper::Cursor(c) => std::pin::Pin::new(c).poll_read(cx, buf),
+            ReaderWrapper::Remote(r) => std::pin::Pin::new(r.as_mut()).poll_read(cx, buf),
         }
     }
 }
@@ -216,6 +896,7 @@ impl tokio::io::AsyncSeek for ReaderWrapper {
         match self.get_mut() {
             ReaderWrapper::File(f) => std::pin::Pin::new(f).start_seek(position),
             ReaderWrapper::Cursor(c) => std::pin::Pin::new(c).start_seek(position),
+            ReaderWrapper::Remote(r) => std::pin::Pin::new(r.as_mut()).start_seek(position),
         }
     }
 
@@ -223,6 +904,7 @@ impl tokio::io::AsyncSeek for ReaderWrapper {
         match self.get_mut() {
             ReaderWrapper::File(f) => std::pin::Pin::new(f).poll_complete(cx),
             ReaderWrapper::Cursor(c) => std::pin::Pin::new(c).poll_complete(cx),
+            ReaderWrapper::Remote(r) => std::pin::Pin::new(r.as_mut()).poll_complete(cx),
         }
     }
 }
@@ -233,7 +915,7 @@ impl Unpin for ReaderWrapper {}
 /// Processing mode for rasterization
 /// This enum tells the system what kind of output to generate from the raw polystream data.
 /// Bitmap creates a grayscale mask image, TriangleStrip creates 3D geometry data, Both does both.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ProcessingMode {
     /// Generate only bitmap (R8 mask) output
     Bitmap,
@@ -241,6 +923,34 @@ pub enum ProcessingMode {
     TriangleStrip,
     /// Generate both bitmap and triangle strip outputs
     Both,
+    /// Decode/decrypt only and cache the raw polystream, skipping the rasterizer entirely.
+    /// For transcoding and proxy deployments; read frames with `get_polystream`.
+    PolystreamOnly,
+}
+
+impl ProcessingMode {
+    /// Whether a processed frame has every output this mode produces
+    fn is_covered_by(self, frame: &FrameData) -> bool {
+        let bitmap = !matches!(self, ProcessingMode::Bitmap | ProcessingMode::Both) || frame.bitmap.is_some();
+        let triangle_strip = !matches!(self, ProcessingMode::TriangleStrip | ProcessingMode::Both) || frame.triangle_strip.is_some();
+        bitmap && triangle_strip
+    }
+}
+
+/// What the getters return for a decoded frame without outline data (see `FrameData::is_empty`)
+/// Masks often drop out for a few frames when the tracked subject leaves the shot or tracking fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EmptyFramePolicy {
+    /// An empty mask and vertex list, like for any other frame
+    #[default]
+    ReturnEmpty,
+    /// The output of the nearest earlier non-empty frame still cached, holding the last mask
+    /// through a dropout; the empty output if there is none
+    ReuseLast,
+    /// No output: get_frame and get_triangle_strip_vertices return None, get_frame_output returns
+    /// the frame with `is_empty` set and no bitmap, vertices or stats. Use `is_frame_empty` to tell
+    /// such a frame from one that is not decoded yet.
+    Error,
 }
 
 /// High-level AlphaStream processor
@@ -257,103 +967,916 @@ pub struct AlphaStreamProcessor {
     /// Output dimensions - width and height of the generated bitmaps/triangle strips
     width: u32,
     height: u32,
-    /// Processing mode - what outputs to generate (bitmap, triangle strip, or both)
-    mode: ProcessingMode,
+    /// Processing mode - what outputs to generate (bitmap, triangle strip, or both); shared with the
+    /// background task so set_processing_mode applies to frames decoded from then on
+    mode: Arc<std::sync::Mutex<ProcessingMode>>,
     /// Async runtime - manages background tasks (like tokio::Runtime)
     runtime: Option<Runtime>,
     /// Background processing task handle - allows stopping the background worker when done
     background_handle: Option<tokio::task::JoinHandle<()>>,
+    /// Dropping this tells the background task to cancel its decode tasks and stop
+    shutdown: Option<tokio::sync::oneshot::Sender<()>>,
+    /// Tells the background task to abort its running decode tasks and carry on, see cancel_all
+    cancel: Arc<tokio::sync::Notify>,
+    /// Path of the source file, None for sources fetched over HTTP
+    source_path: Option<String>,
+    /// Entitlement checks of an encrypted source, None without a provider
+    entitlement: Option<Arc<EntitlementGate>>,
+    /// HTTP source read with range requests, reopened when the file changes on the server
+    remote: Option<Arc<RemoteSource>>,
+    /// Readers the decode tasks spread over; the first is `format`
+    shards: Arc<ReaderShards>,
+    /// File watcher for auto-reload - dropping it stops the events
+    watcher: Option<notify::RecommendedWatcher>,
+    /// Task that reloads the source when the watcher reports a change
+    reload_handle: Option<tokio::task::JoinHandle<()>>,
+    /// Douglas-Peucker tolerance for triangle strip output, 0 = no simplification
+    simplify_tolerance: f32,
+    /// Frames requested through get_frame / get_triangle_strip_vertices, for warm-up in a later session
+    access_log: std::sync::Mutex<AccessPattern>,
+    /// Named frame positions for chapter navigation, sorted by frame index
+    bookmarks: std::sync::Mutex<Vec<Bookmark>>,
+    /// Channels to rasterize / triangulate, None = all channels
+    channels: Option<Vec<usize>>,
+    /// Notifications from background work, drained by the owner with poll_events
+    events: Arc<EventQueue>,
+    /// Coordinate mapping and outline drawing for bitmap output
+    raster_options: RasterOptions,
+    /// Layout of the bitmaps handed out; the cache holds R8
+    packing: OutputPacking,
+    /// What the getters return for frames without outline data
+    empty_frame_policy: EmptyFramePolicy,
+    /// Forensic watermark embedded into rasterized masks
+    watermark: Option<Watermark>,
+    /// Receives the frames decoded in the background
+    telemetry: SharedTelemetryExporter,
+    /// Draws masks and triangle strips
+    rasterizer: SharedRasterizer,
+    /// Frames processed ahead of time, served instead of decoding them
+    baked: Option<Arc<BakedScene>>,
+    /// Decode / processing times of cached frames, by cache index
+    traces: Arc<std::sync::Mutex<HashMap<usize, FrameTrace>>>,
+    /// Stored bytes of the frames decode tasks read from the source, see bytes_read
+    bytes_read: Arc<std::sync::atomic::AtomicU64>,
+    /// Wakes get_frame_wait when decode tasks finish
+    frame_signal: Arc<FrameSignal>,
+    /// Only every stride-th frame is decoded; cache and scheduler index frames divided by the stride
+    stride: usize,
+    /// Effective configuration the processor was built with, reported by config()
+    config: AlphaStreamProcessorBuilder,
+    /// Time source for frame traces
+    clock: SharedClock,
+    /// Decode tasks rasterize at the output size divided by this, see report_display_size
+    raster_divisor: Arc<std::sync::atomic::AtomicU32>,
+    /// Output size and raster options decode tasks rasterize with, shared with the background task so
+    /// set_output_size applies to frames decoded from then on
+    output_size: Arc<std::sync::Mutex<(u32, u32, RasterOptions)>>,
 }
 
-impl AlphaStreamProcessor {
-    pub fn width(&self) -> u32 { self.width }
-    pub fn height(&self) -> u32 { self.height }
+/// Everything the processor has for one frame, read with a single cache lookup
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameOutput {
+    /// The frame that was requested
+    pub frame_index: usize,
+    /// Mask in the processor's output packing (R8 by default), None unless the processing mode produces bitmaps
+    pub bitmap: Option<Vec<u8>>,
+    /// Triangle strip vertices, None unless the processing mode produces them
+    pub triangle_strip: Option<Vec<f32>>,
+    /// Statistics of the mask, available with the bitmap
+    pub stats: Option<MaskStats>,
+    /// How long the frame took to produce, None if unknown (e.g. inserted by a warm-up from elsewhere)
+    pub trace: Option<FrameTrace>,
+    /// Whether the frame has no outline data; with `EmptyFramePolicy::ReuseLast` the outputs are
+    /// those of an earlier frame
+    pub is_empty: bool,
 }
 
-impl AlphaStreamProcessor {
-    /// Create a new processor for ASVR (encrypted) files
-    /// This method sets up everything needed to process an encrypted AlphaStream file.
-    /// Returns a Result: Ok(processor) if successful, Err(error) if something went wrong.
-    /// Error handling: The ? operator propagates errors up, so if file opening or format creation fails,
-    /// the method returns early with that error.
-    pub async fn new_asvr(
-        uri: &str,
-        scene_id: u32,
-        version: &[u8],
-        base_url: &[u8],
-        width: u32,
-        height: u32,
-        mode: ProcessingMode,
-    ) -> Result<Self, FormatError> {
-        let reader = if uri.starts_with("http") {
-            let bytes = reqwest::get(uri).await.map_err(|e| FormatError::InvalidFormat(e.to_string()))?.bytes().await.map_err(|e| FormatError::InvalidFormat(e.to_string()))?;
-            ReaderWrapper::Cursor(CursorWrapper(std::io::Cursor::new(bytes)))
-        } else {
-            ReaderWrapper::File(tokio::fs::File::open(uri).await?)
-        };
-        let format_inner = ASVRFormat::new(reader, scene_id, version, base_url).await?;
-        let format = Arc::new(Mutex::new(FormatType::ASVR(format_inner)));
-        let cache = Arc::new(FrameCache::default());
-        let mut scheduler_obj = Scheduler::new();
-        scheduler_obj.set_cache(Arc::clone(&cache));
-        let scheduler = Arc::new(Mutex::new(scheduler_obj));
-        let runtime = Runtime::new().expect("Failed to create runtime");
+/// Channel layout of a frame, see `AlphaStreamProcessor::channel_info`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelInfo {
+    /// The frame that was requested
+    pub frame_index: usize,
+    /// Stored polystream bytes of each channel, in channel order
+    pub sizes: Vec<u32>,
+}
 
-        let mut processor = Self {
-            cache: Arc::clone(&cache),
-            scheduler,
-            format,
-            width,
-            height,
-            mode,
-            runtime: Some(runtime),
-            background_handle: None,
-        };
-        processor.start_background_processing(); // Start async background processing
-        Ok(processor)
+impl ChannelInfo {
+    /// Parse the header of a decoded polystream (the channel count and sizes)
+    fn from_polystream(frame_index: usize, polystream: &[u8]) -> Result<Self, FormatError> {
+        let malformed = || FormatError::InvalidFormat(format!("Polystream header of frame {} incomplete", frame_index));
+        let count = polystream.get(0..4).map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize).ok_or_else(malformed)?;
+        let header = count.checked_mul(4).and_then(|n| polystream.get(4..4 + n)).ok_or_else(malformed)?;
+        let sizes = header.chunks_exact(4).map(|b| u32::from_le_bytes(b.try_into().unwrap())).collect();
+        Ok(ChannelInfo { frame_index, sizes })
     }
 
-    /// Create a new processor for ASVP (plaintext) files
-    pub async fn new_asvp(uri: &str, width: u32, height: u32, mode: ProcessingMode) -> Result<Self, FormatError> {
-        let reader = if uri.starts_with("http") {
-            let bytes = reqwest::get(uri).await.map_err(|e| FormatError::InvalidFormat(e.to_string()))?.bytes().await.map_err(|e| FormatError::InvalidFormat(e.to_string()))?;
-            ReaderWrapper::Cursor(CursorWrapper(std::io::Cursor::new(bytes)))
-        } else {
-            ReaderWrapper::File(tokio::fs::File::open(uri).await?)
-        };
-        let format_inner = ASVPFormat::new(reader).await?;
-        let format = Arc::new(Mutex::new(FormatType::ASVP(format_inner)));
-        let cache = Arc::new(FrameCache::default());
-        let mut scheduler_obj = Scheduler::new();
-        scheduler_obj.set_cache(Arc::clone(&cache));
-        let scheduler = Arc::new(Mutex::new(scheduler_obj));
-        let runtime = Runtime::new().expect("Failed to create runtime");
-
-        let mut processor = Self {
-            cache: Arc::clone(&cache),
-            scheduler,
-            format,
-            width,
-            height,
-            mode,
-            runtime: Some(runtime),
-            background_handle: None,
-        };
-        // Set scheduler bounds (defer to first async metadata fetch)
-        processor.start_background_processing();
-        Ok(processor)
+    /// Number of channels
+    pub fn count(&self) -> usize {
+        self.sizes.len()
     }
+}
 
-    /// Get metadata about the stream
-    /// Async method: marked with 'async fn', uses 'await' to wait for operations without blocking.
-    /// This is important for I/O operations that might take time.
-    /// Returns metadata like frame count, dimensions, etc., or an error if reading fails.
-    pub async fn metadata(&self) -> Result<crate::formats::Metadata, FormatError> {
-        let mut format = self.format.lock().await; // Lock the shared format, await means wait for access
-        format.metadata().await // Call the underlying format's metadata method
+/// What `decode_where` knows about a frame before decoding it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameCandidate<'a> {
+    pub index: u32,
+    /// Size of the frame in the file, from the sizes table; frames without outlines are the smallest
+    pub stored_size: u64,
+    /// Notes of the container's first annotation track on this frame
+    pub annotations: &'a [&'a Annotation],
+}
+
+/// A frame streamed by `decode_where`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedFrame {
+    pub polystream: Vec<u8>,
+    /// The frame processed according to the processing mode; `stats` is also set when a filter was given
+    pub output: FrameOutput,
+}
+
+/// Broad cause of a frame listed in an `ExportReport`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FrameErrorKind {
+    Io,
+    Zlib,
+    InvalidFormat,
+    Decryption,
+    StreamChanged,
+    Entitlement,
+    Dimensions,
+    /// The frame was not decoded in time
+    Timeout,
+    /// The frame was decoded, but the consumer could not take it (e.g. an encoder exited)
+    Sink,
+}
+
+impl From<&FormatError> for FrameErrorKind {
+    fn from(error: &FormatError) -> Self {
+        match error {
+            FormatError::Io(_) => FrameErrorKind::Io,
+            FormatError::Zlib => FrameErrorKind::Zlib,
+            FormatError::InvalidFormat(_) => FrameErrorKind::InvalidFormat,
+            FormatError::Decryption => FrameErrorKind::Decryption,
+            FormatError::StreamChanged => FrameErrorKind::StreamChanged,
+            FormatError::Entitlement(_) => FrameErrorKind::Entitlement,
+            FormatError::Dimensions(_) => FrameErrorKind::Dimensions,
+        }
     }
+}
 
-    fn parse_polystream(polystream: &[u8]) -> (u32, Vec<u32>, &[u8]) {
+/// A frame an export job could not produce
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailedFrame {
+    pub frame_index: u32,
+    pub kind: FrameErrorKind,
+    /// The error as text, for logs
+    pub message: String,
+}
+
+/// Outcome of an export job that carries on past failed frames, so long jobs complete and the failed
+/// frames can be exported again afterwards
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExportReport {
+    /// Frames exported
+    pub succeeded: u32,
+    /// Frames left out because an earlier run already exported them, see `export_png`
+    #[serde(default)]
+    pub skipped: u32,
+    /// Frames that failed, in order
+    pub failed: Vec<FailedFrame>,
+    /// Wall time of the job
+    pub elapsed: Duration,
+}
+
+impl ExportReport {
+    /// List a failed frame
+    pub fn record_failure(&mut self, frame_index: u32, kind: FrameErrorKind, message: impl ToString) {
+        self.failed.push(FailedFrame { frame_index, kind, message: message.to_string() });
+    }
+
+    /// Indices of the failed frames, to export again
+    pub fn failed_indices(&self) -> Vec<u32> {
+        self.failed.iter().map(|failure| failure.frame_index).collect()
+    }
+
+    /// Whether every frame was exported
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+/// A frame streamed by `decode_scripted`
+#[cfg(feature = "scripting")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptedFrame {
+    pub frame: DecodedFrame,
+    /// What the script stored in its `out` map
+    pub values: std::collections::BTreeMap<String, serde_json::Value>,
+}
+
+/// Timing of the background work that produced a cached frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameTrace {
+    /// Reading, decrypting and decompressing the polystream
+    pub decode: Duration,
+    /// Rasterizing and triangulating
+    pub process: Duration,
+}
+
+/// Cache and scheduler counters of a processor, for diagnostics overlays and tuning the cache capacity
+/// and prefetch window, see `AlphaStreamProcessor::stats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProcessorStats {
+    /// Getter calls that found their frame decoded, since the processor was built
+    pub cache_hits: u64,
+    /// Getter calls that had to schedule their frame (a waiting getter counts once per check)
+    pub cache_misses: u64,
+    /// Cache slots holding a decoded frame
+    pub ready_frames: usize,
+    /// Cache slots of frames being decoded
+    pub in_progress_frames: usize,
+    /// Frames waiting to be decoded
+    pub queued_tasks: usize,
+    /// Decode tasks running
+    pub active_tasks: usize,
+    /// Mean decode plus processing time of the frames in the cache window, None before the first
+    pub average_decode: Option<Duration>,
+    /// Decoded frames discarded because a seek or the sliding window had made them stale
+    pub dropped_frames: u64,
+}
+
+/// Named frame position, e.g. a chapter of a long scene
+/// Serializes like a container `Annotation` (`{ "frame_index": 12, "text": "Intro" }`), so a
+/// bookmark sidecar can be packed into a container's `bookmarks` annotation track as is.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub frame_index: u32,
+    #[serde(rename = "text")]
+    pub name: String,
+}
+
+impl From<Annotation> for Bookmark {
+    fn from(note: Annotation) -> Self {
+        Bookmark { frame_index: note.frame_index, name: note.text }
+    }
+}
+
+impl From<Bookmark> for Annotation {
+    fn from(bookmark: Bookmark) -> Self {
+        Annotation { frame_index: bookmark.frame_index, text: bookmark.name }
+    }
+}
+
+/// Notification from the processor's background work
+/// Events are queued and handed out by `poll_events`, so the owner decides which thread
+/// handles them (e.g. a game engine's main thread) instead of arbitrary runtime workers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessorEvent {
+    /// A frame finished decoding and is now in the cache
+    FrameReady(usize),
+    /// Decoding a frame failed
+    DecodeError(usize),
+    /// The source file was reloaded; carries the number of evicted cache entries
+    SourceReloaded(usize),
+    /// The entitlement provider refused further playback; cached frames were dropped
+    EntitlementDenied,
+}
+
+/// Queued events beyond this are dropped oldest first, for owners that stop polling
+pub const MAX_QUEUED_EVENTS: usize = 4096;
+
+/// Bounded event queue shared with the background tasks. Disabled (and free) until enabled.
+#[derive(Default)]
+struct EventQueue {
+    enabled: std::sync::atomic::AtomicBool,
+    queue: std::sync::Mutex<std::collections::VecDeque<ProcessorEvent>>,
+}
+
+impl EventQueue {
+    fn push(&self, event: ProcessorEvent) {
+        if !self.enabled.load(std::sync::atomic::Ordering::Acquire) {
+            return;
+        }
+        let mut queue = self.queue.lock().unwrap();
+        if queue.len() >= MAX_QUEUED_EVENTS {
+            queue.pop_front();
+        }
+        queue.push_back(event);
+    }
+}
+
+/// Wakes `get_frame_wait` callers whenever a decode task finishes, and remembers the frames
+/// (by cache index) whose decode failed so a waiter can give up instead of running into its timeout.
+#[derive(Debug, Default)]
+struct FrameSignal {
+    notify: tokio::sync::Notify,
+    failed: std::sync::Mutex<std::collections::HashSet<usize>>,
+}
+
+impl FrameSignal {
+    fn ready(&self, cache_index: usize) {
+        self.failed.lock().unwrap().remove(&cache_index);
+        self.notify.notify_waiters();
+    }
+
+    fn failed(&self, cache_index: usize, cache: &FrameCache) {
+        let mut failed = self.failed.lock().unwrap();
+        if failed.len() >= cache.capacity() {
+            failed.retain(|&index| cache.is_in_range(index));
+        }
+        failed.insert(cache_index);
+        drop(failed);
+        self.notify.notify_waiters();
+    }
+
+    /// Whether the last decode of the frame failed; forgets the failure
+    fn take_failure(&self, cache_index: usize) -> bool {
+        self.failed.lock().unwrap().remove(&cache_index)
+    }
+}
+
+/// Why `get_frame_wait` returned without a frame
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum FrameWaitError {
+    /// The frame was not decoded in time; it stays scheduled
+    #[error("Frame {0} not ready within the timeout")]
+    Timeout(usize),
+    /// Decoding the frame failed
+    #[error("Decoding frame {0} failed")]
+    DecodeFailed(usize),
+    /// The entitlement provider refused playback
+    #[error("Playback refused: {0}")]
+    Entitlement(EntitlementError),
+    /// The frame is empty and withheld by EmptyFramePolicy::Error
+    #[error("Frame {0} is empty")]
+    Empty(usize),
+    /// The processing mode produces no bitmaps
+    #[error("Processing mode produces no bitmaps")]
+    NoBitmap,
+}
+
+#[cfg(feature = "arena")]
+thread_local! {
+    /// Rasterizer temporaries of the frame being processed on this thread
+    static FRAME_ARENA: std::cell::RefCell<crate::rasterizer::FrameArena> = std::cell::RefCell::default();
+}
+
+/// Whether `channel` is part of a selection (None selects every channel)
+fn channel_selected(channels: Option<&[usize]>, channel: usize) -> bool {
+    channels.is_none_or(|c| c.binary_search(&channel).is_ok())
+}
+
+/// How long a dropped or closed processor waits for its decode tasks to finish cancelling before the runtime goes
+pub(crate) const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Message of a panic payload
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast_ref::<&str>().map_or_else(|| "unknown panic".to_string(), |message| message.to_string()),
+    }
+}
+
+/// How long to wait for a writer to settle after a change event before reloading
+const WATCH_DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Source path for local files; HTTP sources and store scenes have no path to reload from
+/// Where a processor is built from
+enum BuildSource<'a> {
+    /// Local path, HTTP(S) URL or store scene
+    Uri(&'a str),
+    /// Whole file in memory
+    Memory(bytes::Bytes),
+}
+
+impl BuildSource<'_> {
+    fn uri(&self) -> Option<&str> {
+        match self {
+            BuildSource::Uri(uri) => Some(uri),
+            BuildSource::Memory(_) => None,
+        }
+    }
+
+    fn local_path(&self) -> Option<String> {
+        self.uri().and_then(local_source_path)
+    }
+}
+
+fn local_source_path(uri: &str) -> Option<String> {
+    if uri.starts_with("http") || uri.starts_with(STORE_SCHEME) {
+        None
+    } else {
+        Some(uri.to_string())
+    }
+}
+
+/// HTTP source read with range requests. Its reads are conditional on the version seen on open;
+/// when the file is replaced on the server the source is reopened and the cache revalidated.
+struct RemoteSource {
+    url: String,
+    parallel_ranges: usize,
+    telemetry: SharedTelemetryExporter,
+    /// Set while a refresh runs, so concurrent failing reads trigger only one
+    refreshing: std::sync::atomic::AtomicBool,
+}
+
+impl RemoteSource {
+    /// Some for HTTP sources of a builder with range requests enabled
+    fn for_uri(uri: &str, config: &AlphaStreamProcessorBuilder) -> Option<Arc<Self>> {
+        (config.range_requests && uri.starts_with("http")).then(|| Arc::new(RemoteSource {
+            url: uri.to_string(),
+            parallel_ranges: config.parallel_ranges,
+            telemetry: config.telemetry.clone(),
+            refreshing: Default::default(),
+        }))
+    }
+
+    async fn open(&self) -> Result<ReaderWrapper, FormatError> {
+        let mut reader = HttpTransport::open(&self.url).await.map_err(|e| FormatError::InvalidFormat(e.to_string()))?;
+        reader.set_telemetry(self.telemetry.clone());
+        let reader = CoalescingReader::<HttpTransport>::new(reader, DEFAULT_COALESCE_CHUNK_SIZE)
+            .parallel_ranges(self.parallel_ranges);
+        Ok(ReaderWrapper::Remote(Box::new(reader)))
+    }
+
+    /// Reopen after a read reported StreamChanged. Does nothing if a refresh is already running.
+    async fn refresh(&self, shards: &ReaderShards, cache: &FrameCache, stride: usize, events: &EventQueue) {
+        use std::sync::atomic::Ordering;
+        if self.refreshing.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let result = match self.open().await {
+            Ok(reader) => AlphaStreamProcessor::reload_source(reader, shards, cache, stride, events).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(invalidated) => logging::log(LogLevel::Info, format_args!("{} changed on the server, invalidated {} cached frames", self.url, invalidated.len())),
+            Err(e) => logging::log(LogLevel::Warn, format_args!("Refresh of {} failed: {}", self.url, e)),
+        }
+        self.refreshing.store(false, Ordering::Release);
+    }
+}
+
+/// Format parsers over independently opened readers of the same source, so decode tasks read in
+/// parallel instead of queueing on one reader. The first shard is the processor's `format`.
+struct ReaderShards {
+    formats: Vec<Arc<Mutex<FormatType<ReaderWrapper>>>>,
+    /// Where the extra shards open their readers: a local path or a range-read HTTP source
+    path: Option<String>,
+    remote: Option<Arc<RemoteSource>>,
+    /// Round-robin start for picking a shard
+    next: std::sync::atomic::AtomicUsize,
+}
+
+impl ReaderShards {
+    /// One reader, for sources that cannot be opened again
+    fn single(format: FormatType<ReaderWrapper>) -> Self {
+        ReaderShards { formats: vec![Arc::new(Mutex::new(format))], path: None, remote: None, next: Default::default() }
+    }
+
+    /// `format` plus up to `count - 1` more parsers over new readers of the source. Sources without a
+    /// path or range-read URL keep one reader; a reader that fails to open leaves fewer shards.
+    async fn open(format: FormatType<ReaderWrapper>, count: usize, path: Option<String>, remote: Option<Arc<RemoteSource>>) -> Self {
+        let mut shards = ReaderShards { formats: Vec::new(), path, remote, next: Default::default() };
+        let mut extra = Vec::new();
+        if count > 1 && shards.path.is_none() && shards.remote.is_none() {
+            logging::log(LogLevel::Info, format_args!("Source cannot be opened again, decoding with one reader"));
+        } else {
+            for shard in 1..count {
+                match shards.open_format(&format).await {
+                    Ok(parser) => extra.push(Arc::new(Mutex::new(parser))),
+                    Err(e) => {
+                        logging::log(LogLevel::Warn, format_args!("Could not open reader shard {}, decoding with {}: {}", shard, shard, e));
+                        break;
+                    }
+                }
+            }
+        }
+        shards.formats = std::iter::once(Arc::new(Mutex::new(format))).chain(extra).collect();
+        shards
+    }
+
+    /// A parser like `format` over a new reader of the source
+    async fn open_format(&self, format: &FormatType<ReaderWrapper>) -> Result<FormatType<ReaderWrapper>, FormatError> {
+        let reader = match (&self.path, &self.remote) {
+            (Some(path), _) => ReaderWrapper::File(tokio::fs::File::open(path).await?),
+            (None, Some(remote)) => remote.open().await?,
+            (None, None) => return Err(FormatError::InvalidFormat("Source cannot be opened again".to_string())),
+        };
+        format.reopen(reader).await
+    }
+
+    fn primary(&self) -> &Arc<Mutex<FormatType<ReaderWrapper>>> {
+        &self.formats[0]
+    }
+
+    fn len(&self) -> usize {
+        self.formats.len()
+    }
+
+    /// Lock a shard for a decode: the first idle one, or else wait for the next in turn
+    async fn acquire(&self) -> tokio::sync::OwnedMutexGuard<FormatType<ReaderWrapper>> {
+        let start = self.next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let count = self.formats.len();
+        for offset in 0..count {
+            if let Ok(guard) = Arc::clone(&self.formats[(start + offset) % count]).try_lock_owned() {
+                return guard;
+            }
+        }
+        Arc::clone(&self.formats[start % count]).lock_owned().await
+    }
+}
+
+impl AlphaStreamProcessor {
+    pub fn width(&self) -> u32 { self.width }
+    pub fn height(&self) -> u32 { self.height }
+    pub fn stride(&self) -> usize { self.stride }
+    /// Factor the raster resolution is currently lowered by, 1 until report_display_size lowers it
+    pub fn raster_divisor(&self) -> u32 { self.raster_divisor.load(std::sync::atomic::Ordering::Relaxed) }
+    /// Rasterizer backend the processor runs on
+    pub fn backend(&self) -> Backend { self.config.backend }
+    /// Layout of the bitmaps returned by get_frame and get_frame_output
+    pub fn output_packing(&self) -> OutputPacking { self.packing }
+    /// Change the layout of the bitmaps returned from now on
+    pub fn set_output_packing(&mut self, packing: OutputPacking) {
+        self.packing = packing;
+        self.config.output_packing = packing;
+    }
+    /// Change the decode budget per frame interval from now on, see `AlphaStreamProcessorBuilder::decode_budget_ms`
+    pub async fn set_decode_budget_ms(&mut self, budget_ms: f32) {
+        self.config = self.config.clone().decode_budget_ms(budget_ms);
+        self.scheduler.lock().await.set_decode_budget(self.config.decode_budget());
+    }
+    /// What outputs frames are processed into
+    pub fn processing_mode(&self) -> ProcessingMode { *self.mode.lock().unwrap() }
+    /// Change what outputs frames are processed into from now on, e.g. to add triangle strips for a 3D
+    /// debug view. Cached frames lacking an output of the new mode are evicted and decoded again when
+    /// asked for; the others stay valid. Returns the indices of the evicted frames.
+    pub fn set_processing_mode(&mut self, mode: ProcessingMode) -> Vec<usize> {
+        *self.mode.lock().unwrap() = mode;
+        self.config.processing_mode = mode;
+        let mut invalidated = Vec::new();
+        for cache_index in self.cache.ready_frames() {
+            let stale = self.cache.get(cache_index).is_some_and(|frame| !mode.is_covered_by(&frame));
+            if stale && self.cache.invalidate_frame(cache_index) {
+                invalidated.push(cache_index * self.stride);
+            }
+        }
+        if !invalidated.is_empty() {
+            logging::log(LogLevel::Debug, format_args!("Processing mode {:?}: evicted {} cached frames", mode, invalidated.len()));
+        }
+        invalidated
+    }
+    /// Change the output size from now on, e.g. when the host's window is resized, without building
+    /// the processor again. The size is checked like at build time, against the cache capacity the
+    /// processor has; with auto_fit the letterboxing is fitted to the new size. Cached frames with a
+    /// mask are evicted and decoded again when asked for; polystreams and triangle strips do not depend
+    /// on the size, so frames without a mask stay valid. A baked scene only serves frames at the size
+    /// it was baked at. Returns the indices of the evicted frames.
+    pub async fn set_output_size(&mut self, width: u32, height: u32) -> Result<Vec<usize>, FormatError> {
+        if (width, height) == (self.width, self.height) {
+            return Ok(Vec::new());
+        }
+        let config = self.config.clone().cache_capacity(self.cache.capacity()).fit_cache_to_budget(false).check_dimensions(width, height)?;
+        let transform = config.output_transform(&mut *self.format.lock().await, width, height).await?;
+        let raster_options = RasterOptions { transform, tile_size: config.tile_size(width, height), ..self.raster_options };
+        // Decode tasks check the size under this lock before caching a frame, so none caches a mask of the old size after the scan
+        let mut output_size = self.output_size.lock().unwrap();
+        *output_size = (width, height, raster_options);
+        self.width = width;
+        self.height = height;
+        self.raster_options = raster_options;
+        let mut invalidated = Vec::new();
+        for cache_index in self.cache.ready_frames() {
+            let stale = self.cache.get(cache_index).is_some_and(|frame| frame.bitmap.is_some());
+            if stale && self.cache.invalidate_frame(cache_index) {
+                invalidated.push(cache_index * self.stride);
+            }
+        }
+        drop(output_size);
+        logging::log(LogLevel::Debug, format_args!("Output size {}x{}: evicted {} cached frames", width, height, invalidated.len()));
+        Ok(invalidated)
+    }
+    /// What the getters return for frames without outline data
+    pub fn empty_frame_policy(&self) -> EmptyFramePolicy { self.empty_frame_policy }
+    /// Change what the getters return for frames without outline data from now on
+    pub fn set_empty_frame_policy(&mut self, policy: EmptyFramePolicy) {
+        self.empty_frame_policy = policy;
+        self.config.empty_frame_policy = policy;
+    }
+    /// Readers decode tasks spread over; 1 if the source could not be opened more than once
+    pub fn reader_shards(&self) -> usize { self.shards.len() }
+    /// Frames the cache holds, after fitting it to the memory budget
+    pub fn cache_capacity(&self) -> usize { self.cache.capacity() }
+    /// Effective configuration, e.g. `config().to_json()` for a support ticket.
+    /// Feeding it back to a builder reproduces the processor's settings.
+    pub fn config(&self) -> AlphaStreamProcessorBuilder { self.config.clone() }
+    /// Scale and letterbox offsets applied to masks, see `AlphaStreamProcessorBuilder::auto_fit`
+    pub fn output_transform(&self) -> OutputTransform { self.raster_options.transform }
+    /// Track directory of a multi-track container source, empty for other sources
+    pub async fn tracks(&self) -> Vec<TrackInfo> {
+        match &*self.format.lock().await {
+            FormatType::ASVP(format) => format.tracks().to_vec(),
+            FormatType::ASVR(_) => Vec::new(),
+        }
+    }
+    /// Keyframe thumbnails of a container's thumbnail track `track` (the first one for None), sorted by frame index
+    pub async fn thumbnails(&self, track: Option<&str>) -> Result<Vec<Thumbnail>, FormatError> {
+        match &*self.format.lock().await {
+            FormatType::ASVP(format) => format.thumbnails(track).await,
+            FormatType::ASVR(_) => Err(FormatError::InvalidFormat("ASVR sources have no tracks".to_string())),
+        }
+    }
+    /// Notes of a container's annotation track `track` (the first one for None)
+    pub async fn annotations(&self, track: Option<&str>) -> Result<Vec<Annotation>, FormatError> {
+        match &*self.format.lock().await {
+            FormatType::ASVP(format) => format.annotations(track).await,
+            FormatType::ASVR(_) => Err(FormatError::InvalidFormat("ASVR sources have no tracks".to_string())),
+        }
+    }
+    /// Markers of a container's cue track `track` (the first one for None). Read once and keep the
+    /// CueTrack to query it per frame, see `CueTrack::next_cue`.
+    pub async fn cues(&self, track: Option<&str>) -> Result<CueTrack, FormatError> {
+        match &*self.format.lock().await {
+            FormatType::ASVP(format) => format.cues(track).await,
+            FormatType::ASVR(_) => Err(FormatError::InvalidFormat("ASVR sources have no tracks".to_string())),
+        }
+    }
+    /// First cue of the first cue track in `frame_index` or a later frame; None without a cue track
+    pub async fn next_cue(&self, frame_index: usize) -> Option<Cue> {
+        let cues = self.cues(None).await.ok()?;
+        cues.next_cue(frame_index as u32).cloned()
+    }
+    /// Bookmarks of the scene, sorted by frame index
+    pub fn bookmarks(&self) -> Vec<Bookmark> {
+        self.bookmarks.lock().unwrap().clone()
+    }
+    /// The chapter `frame_index` falls in: the last bookmark at or before it
+    pub fn chapter_at(&self, frame_index: usize) -> Option<Bookmark> {
+        let bookmarks = self.bookmarks.lock().unwrap();
+        bookmarks.iter().rev().find(|bookmark| bookmark.frame_index as usize <= frame_index).cloned()
+    }
+    /// Bookmark `frame_index` as `name`; adding a name that exists moves that bookmark.
+    /// Local sources persist bookmarks in the sidecar `<source>.bookmarks.json`, HTTP sources keep
+    /// them in memory. On error the bookmarks are left unchanged.
+    pub async fn add_bookmark(&self, name: &str, frame_index: u32) -> Result<(), FormatError> {
+        if name.is_empty() {
+            return Err(FormatError::InvalidFormat("Bookmark name is empty".to_string()));
+        }
+        let frame_count = self.metadata().await?.frame_count;
+        if frame_index >= frame_count {
+            return Err(FormatError::InvalidFormat(format!("Bookmark frame {} is past the last frame {}", frame_index, frame_count.saturating_sub(1))));
+        }
+        let mut bookmarks = self.bookmarks.lock().unwrap();
+        let mut updated: Vec<Bookmark> = bookmarks.iter().filter(|bookmark| bookmark.name != name).cloned().collect();
+        let at = updated.partition_point(|bookmark| bookmark.frame_index <= frame_index);
+        updated.insert(at, Bookmark { frame_index, name: name.to_string() });
+        self.save_bookmarks(&updated)?;
+        *bookmarks = updated;
+        Ok(())
+    }
+    /// Remove the bookmark `name`. Returns false if there is none.
+    pub fn remove_bookmark(&self, name: &str) -> Result<bool, FormatError> {
+        let mut bookmarks = self.bookmarks.lock().unwrap();
+        let Some(at) = bookmarks.iter().position(|bookmark| bookmark.name == name) else {
+            return Ok(false);
+        };
+        let mut updated = bookmarks.clone();
+        updated.remove(at);
+        self.save_bookmarks(&updated)?;
+        *bookmarks = updated;
+        Ok(true)
+    }
+    /// Sidecar file holding the bookmarks of a local source
+    fn bookmark_sidecar(&self) -> Option<PathBuf> {
+        self.source_path.as_ref().map(|path| PathBuf::from(format!("{}.bookmarks.json", path)))
+    }
+    fn save_bookmarks(&self, bookmarks: &[Bookmark]) -> Result<(), FormatError> {
+        let Some(path) = self.bookmark_sidecar() else {
+            return Ok(());
+        };
+        let json = serde_json::to_vec_pretty(bookmarks).map_err(|e| FormatError::InvalidFormat(e.to_string()))?;
+        std::fs::write(path, json).map_err(FormatError::Io)
+    }
+    /// Read the bookmarks from the sidecar, or else from a container's `bookmarks` annotation track.
+    /// A broken sidecar is logged and leaves the scene without bookmarks.
+    async fn load_bookmarks(&self) {
+        let loaded = match self.bookmark_sidecar().filter(|path| path.exists()) {
+            Some(path) => std::fs::read(&path).map_err(FormatError::Io).and_then(|json| {
+                serde_json::from_slice::<Vec<Bookmark>>(&json).map_err(|e| FormatError::InvalidFormat(e.to_string()))
+            }),
+            None => match &*self.format.lock().await {
+                FormatType::ASVP(format) if format.tracks().iter().any(|track| track.name == BOOKMARK_TRACK) => format
+                    .annotations(Some(BOOKMARK_TRACK))
+                    .await
+                    .map(|notes| notes.into_iter().map(Bookmark::from).collect()),
+                _ => Ok(Vec::new()),
+            },
+        };
+        match loaded {
+            Ok(mut bookmarks) => {
+                bookmarks.sort_by_key(|bookmark| bookmark.frame_index);
+                *self.bookmarks.lock().unwrap() = bookmarks;
+            }
+            Err(e) => logging::log(LogLevel::Warn, format_args!("Could not load bookmarks: {}", e)),
+        }
+    }
+    /// The keyframe a seek to `frame_index` should land on so playback starts without a dependency:
+    /// the nearest keyframe at or before it in a delta-encoded source, else the frame itself
+    pub async fn nearest_keyframe(&self, frame_index: usize) -> usize {
+        let format = self.format.lock().await;
+        let keyframes = format.keyframes();
+        match keyframes.partition_point(|&k| k as usize <= frame_index) {
+            0 => frame_index,
+            at => keyframes[at - 1] as usize,
+        }
+    }
+    /// Frames that must be decoded before `frame_index` can be, in decode order: the keyframe of a delta
+    /// frame in a delta-encoded source; nothing for keyframes, or when every frame decodes on its own (plain
+    /// ASVP, ASVR). For external schedulers and debugging tools; the processor's own scheduler already
+    /// decodes dependencies first.
+    pub async fn frame_dependencies(&self, frame_index: usize) -> Vec<usize> {
+        match self.nearest_keyframe(frame_index).await {
+            keyframe if keyframe == frame_index => Vec::new(),
+            keyframe => vec![keyframe],
+        }
+    }
+    /// Let the scheduler decode the keyframe a frame depends on ahead of it. With a stride the decoded
+    /// frames rarely are keyframes, so every one of them is reconstructed on its own.
+    async fn load_keyframes(&self) {
+        if self.stride != 1 {
+            return;
+        }
+        let keyframes: Vec<usize> = self.format.lock().await.keyframes().iter().map(|&k| k as usize).collect();
+        if !keyframes.is_empty() {
+            self.scheduler.lock().await.set_keyframes(keyframes);
+        }
+    }
+    /// Outcome of the last entitlement check, Ok without an entitlement provider
+    pub fn entitlement_status(&self) -> Result<(), EntitlementError> {
+        self.entitlement.as_ref().map_or(Ok(()), |gate| gate.status())
+    }
+
+    /// Cache / scheduler index of the strided frame that serves a request for `frame_index`
+    fn cache_index(&self, frame_index: usize) -> usize {
+        frame_index / self.stride
+    }
+}
+
+impl AlphaStreamProcessor {
+    /// Create a new processor for ASVR (encrypted) files
+    /// This method sets up everything needed to process an encrypted AlphaStream file.
+    /// Returns a Result: Ok(processor) if successful, Err(error) if something went wrong.
+    /// Error handling: The ? operator propagates errors up, so if file opening or format creation fails,
+    /// the method returns early with that error.
+    pub async fn new_asvr(
+        uri: &str,
+        scene_id: u32,
+        version: &[u8],
+        base_url: &[u8],
+        width: u32,
+        height: u32,
+        mode: ProcessingMode,
+    ) -> Result<Self, FormatError> {
+        let reader = if uri.starts_with("http") {
+            let bytes = reqwest::get(uri).await.map_err(|e| FormatError::InvalidFormat(e.to_string()))?.bytes().await.map_err(|e| FormatError::InvalidFormat(e.to_string()))?;
+            ReaderWrapper::Cursor(CursorWrapper(std::io::Cursor::new(bytes)))
+        } else {
+            ReaderWrapper::File(tokio::fs::File::open(uri).await?)
+        };
+        let format_inner = ASVRFormat::new(reader, scene_id, version, base_url).await?;
+        let shards = ReaderShards::single(FormatType::ASVR(format_inner));
+        let format = Arc::clone(shards.primary());
+        let cache = Arc::new(FrameCache::default());
+        let mut scheduler_obj = Scheduler::new();
+        scheduler_obj.set_cache(Arc::clone(&cache));
+        let scheduler = Arc::new(Mutex::new(scheduler_obj));
+        let runtime = Runtime::new().expect("Failed to create runtime");
+        let raster_options = RasterOptions::new(width, height);
+
+        let mut processor = Self {
+            cache: Arc::clone(&cache),
+            scheduler,
+            format,
+            width,
+            height,
+            mode: Arc::new(std::sync::Mutex::new(mode)),
+            runtime: Some(runtime),
+            background_handle: None,
+            shutdown: None,
+            cancel: Arc::default(),
+            source_path: local_source_path(uri),
+            entitlement: None,
+            remote: None,
+            shards: Arc::new(shards),
+            watcher: None,
+            reload_handle: None,
+            simplify_tolerance: 0.0,
+            access_log: std::sync::Mutex::new(AccessPattern::new()),
+            bookmarks: std::sync::Mutex::new(Vec::new()),
+            channels: None,
+            events: Arc::new(EventQueue::default()),
+            stride: 1,
+            raster_options,
+            packing: OutputPacking::default(),
+            empty_frame_policy: EmptyFramePolicy::default(),
+            watermark: None,
+            telemetry: SharedTelemetryExporter::default(),
+            rasterizer: SharedRasterizer::default(),
+            baked: None,
+            traces: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            bytes_read: Arc::default(),
+            frame_signal: Arc::new(FrameSignal::default()),
+            raster_divisor: Arc::new(std::sync::atomic::AtomicU32::new(1)),
+            output_size: Arc::new(std::sync::Mutex::new((width, height, raster_options))),
+            config: AlphaStreamProcessorBuilder::new().processing_mode(mode),
+            clock: SharedClock::default(),
+        };
+        processor.load_bookmarks().await;
+        processor.load_keyframes().await;
+        processor.start_background_processing(); // Start async background processing
+        Ok(processor)
+    }
+
+    /// Create a new processor for ASVP (plaintext) files
+    pub async fn new_asvp(uri: &str, width: u32, height: u32, mode: ProcessingMode) -> Result<Self, FormatError> {
+        let reader = if uri.starts_with("http") {
+            let bytes = reqwest::get(uri).await.map_err(|e| FormatError::InvalidFormat(e.to_string()))?.bytes().await.map_err(|e| FormatError::InvalidFormat(e.to_string()))?;
+            ReaderWrapper::Cursor(CursorWrapper(std::io::Cursor::new(bytes)))
+        } else {
+            ReaderWrapper::File(tokio::fs::File::open(uri).await?)
+        };
+        let format_inner = ASVPFormat::new(reader).await?;
+        let shards = ReaderShards::single(FormatType::ASVP(format_inner));
+        let format = Arc::clone(shards.primary());
+        let cache = Arc::new(FrameCache::default());
+        let mut scheduler_obj = Scheduler::new();
+        scheduler_obj.set_cache(Arc::clone(&cache));
+        let scheduler = Arc::new(Mutex::new(scheduler_obj));
+        let runtime = Runtime::new().expect("Failed to create runtime");
+        let raster_options = RasterOptions::new(width, height);
+
+        let mut processor = Self {
+            cache: Arc::clone(&cache),
+            scheduler,
+            format,
+            width,
+            height,
+            mode: Arc::new(std::sync::Mutex::new(mode)),
+            runtime: Some(runtime),
+            background_handle: None,
+            shutdown: None,
+            cancel: Arc::default(),
+            source_path: local_source_path(uri),
+            entitlement: None,
+            remote: None,
+            shards: Arc::new(shards),
+            watcher: None,
+            reload_handle: None,
+            simplify_tolerance: 0.0,
+            access_log: std::sync::Mutex::new(AccessPattern::new()),
+            bookmarks: std::sync::Mutex::new(Vec::new()),
+            channels: None,
+            events: Arc::new(EventQueue::default()),
+            stride: 1,
+            raster_options,
+            packing: OutputPacking::default(),
+            empty_frame_policy: EmptyFramePolicy::default(),
+            watermark: None,
+            telemetry: SharedTelemetryExporter::default(),
+            rasterizer: SharedRasterizer::default(),
+            baked: None,
+            traces: Arc::new(std::sync::Mutex::new(HashMap::new())),
+            bytes_read: Arc::default(),
+            frame_signal: Arc::new(FrameSignal::default()),
+            raster_divisor: Arc::new(std::sync::atomic::AtomicU32::new(1)),
+            output_size: Arc::new(std::sync::Mutex::new((width, height, raster_options))),
+            config: AlphaStreamProcessorBuilder::new().processing_mode(mode),
+            clock: SharedClock::default(),
+        };
+        // Set scheduler bounds (defer to first async metadata fetch)
+        processor.load_bookmarks().await;
+        processor.load_keyframes().await;
+        processor.start_background_processing();
+        Ok(processor)
+    }
+
+    /// Get metadata about the stream
+    /// Async method: marked with 'async fn', uses 'await' to wait for operations without blocking.
+    /// This is important for I/O operations that might take time.
+    /// Returns metadata like frame count, dimensions, etc., or an error if reading fails.
+    pub async fn metadata(&self) -> Result<crate::formats::Metadata, FormatError> {
+        let mut format = self.format.lock().await; // Lock the shared format, await means wait for access
+        format.metadata().await // Call the underlying format's metadata method
+    }
+
+    /// Frames per second of the timebase frame indices map to, t_n = n / fps (see ADR 0007)
+    pub async fn timebase_fps(&self) -> f64 {
+        self.scheduler.lock().await.timebase_fps()
+    }
+
+    fn parse_polystream(polystream: &[u8]) -> (u32, Vec<u32>, &[u8]) {
         let channel_count = u32::from_le_bytes(polystream[0..4].try_into().unwrap());
         let mut channel_sizes = Vec::new();
         for i in 0..channel_count as usize {
@@ -366,373 +1889,3228 @@ impl AlphaStreamProcessor {
         (channel_count, channel_sizes, channel_data)
     }
 
-    /// Get a rasterized frame (R8 mask)
+    /// Rasterize the channels of a polystream into a single R8 mask (union of the channels)
+    /// `channels` limits the output to the given channel indices, None means all channels
+    fn rasterize_channels(rasterizer: &SharedRasterizer, channel_sizes: &[u32], channel_data: &[u8], channels: Option<&[usize]>, width: u32, height: u32, options: &RasterOptions) -> Vec<u8> {
+        let mut mask = vec![0u8; (width * height) as usize];
+        // Every frame starts from an empty arena; the previous frame's temporaries are freed at once
+        #[cfg(feature = "arena")]
+        FRAME_ARENA.with_borrow_mut(|arena| arena.reset());
+        let mut offset = 0;
+        for (channel, &size) in channel_sizes.iter().enumerate() {
+            if !channel_selected(channels, channel) {
+                offset += size as usize;
+                continue;
+            }
+            let channel_data_slice = &channel_data[offset..offset + size as usize];
+            // Channels are drawn on top of each other, giving their union
+            #[cfg(feature = "arena")]
+            if rasterizer.is_builtin() {
+                FRAME_ARENA.with_borrow(|arena| PolystreamRasterizer::rasterize_into_arena(arena, channel_data_slice, width, height, options, &mut mask));
+            } else {
+                rasterizer.rasterize(channel_data_slice, width, height, options, &mut mask);
+            }
+            #[cfg(not(feature = "arena"))]
+            rasterizer.rasterize(channel_data_slice, width, height, options, &mut mask);
+            offset += size as usize;
+        }
+        mask
+    }
+
+    /// Size and raster options of masks rasterized at the output size divided by `divisor`
+    fn reduced_raster(width: u32, height: u32, options: &RasterOptions, divisor: u32) -> (u32, u32, RasterOptions) {
+        let options = RasterOptions { transform: options.transform.downscaled(divisor), ..*options };
+        (width.div_ceil(divisor), height.div_ceil(divisor), options)
+    }
+
+    /// Native canvas size covering the coordinate extents of the first frame
+    /// Never smaller than NATIVE_WIDTH x NATIVE_HEIGHT; an empty source keeps the canvas size.
+    async fn detect_native_size(format: &mut FormatType<ReaderWrapper>) -> Result<(u32, u32), FormatError> {
+        let (mut width, mut height) = (NATIVE_WIDTH, NATIVE_HEIGHT);
+        if format.metadata().await?.frame_count == 0 {
+            return Ok((width, height));
+        }
+        let frame_data = format.decode_frame(0).await?;
+        let (_channel_count, channel_sizes, channel_data) = AlphaStreamProcessor::parse_polystream(&frame_data.polystream);
+        let mut offset = 0;
+        for &size in &channel_sizes {
+            if let Some((_, _, max_x, max_y)) = PolystreamRasterizer::coordinate_extents(&channel_data[offset..offset + size as usize]) {
+                width = width.max(max_x.saturating_add(1).max(0) as u32);
+                height = height.max(max_y.saturating_add(1).max(0) as u32);
+            }
+            offset += size as usize;
+        }
+        Ok((width, height))
+    }
+
+    /// Triangulate the channels of a polystream into one concatenated triangle strip
+    /// `channels` limits the output to the given channel indices, None means all channels
+    fn triangulate_channels(rasterizer: &SharedRasterizer, channel_sizes: &[u32], channel_data: &[u8], channels: Option<&[usize]>, tolerance: f32) -> Vec<f32> {
+        let mut vertices = Vec::new();
+        let mut offset = 0;
+        for (channel, &size) in channel_sizes.iter().enumerate() {
+            if !channel_selected(channels, channel) {
+                offset += size as usize;
+                continue;
+            }
+            let channel_data_slice = &channel_data[offset..offset + size as usize];
+            let channel_strip = rasterizer.to_vertices(channel_data_slice, tolerance);
+            vertices.extend(channel_strip);
+            offset += size as usize;
+        }
+        vertices
+    }
+
+    /// Get a rasterized frame (R8 mask, or as configured with `output_packing`)
     /// Async method that checks cache first. If frame is cached and has bitmap data, returns it immediately.
     /// If not cached, schedules the frame for background processing and returns None (will be available later).
     /// This non-blocking approach allows the caller to continue while processing happens in background.
     pub async fn get_frame(&self, frame_index: usize, _width: u32, _height: u32) -> Option<Vec<u8>> {
-        let requested_frame_index = frame_index;
+        self.get_frame_packed(frame_index, self.packing).await
+    }
+
+    /// Get a rasterized frame like `get_frame`, in `packing` instead of the processor's output packing,
+    /// e.g. in a texture format a host asks for per call
+    pub async fn get_frame_packed(&self, frame_index: usize, packing: OutputPacking) -> Option<Vec<u8>> {
+        if !self.begin_access(frame_index) {
+            return None;
+        }
+        let requested_frame_index = self.cache_index(frame_index);
 
         // Update play head position - this handles seek detection and cache invalidation
         // The ring buffer automatically handles eviction, no manual removal needed
         self.cache.update_play_head(requested_frame_index);
 
         let mut scheduler = self.scheduler.lock().await; // Lock scheduler (async mutex)
-        if let Some(frame_data) = self.cache.get(requested_frame_index) { // Check cache first
-            if let Some(bitmap) = frame_data.bitmap.clone() {
-                return Some(bitmap);
+        if let Some(frame_data) = self.cache.lookup(requested_frame_index) { // Check cache first
+            let (source_index, frame_data) = self.resolve_empty_frame(requested_frame_index, frame_data)?;
+            if let Some(bitmap) = self.output_mask(source_index, frame_data) {
+                return Some(packing.pack_owned(bitmap, self.width, self.height));
             }
         }
         // Not in cache, schedule for processing
-        let task = Task::with_priority(requested_frame_index, 10); // High priority for user-requested frames
+        let task = Task::with_priority(requested_frame_index, Priority::Interactive.value()); // High priority for user-requested frames
+        scheduler.schedule_task(task);
+
+        // Prefetch if sequential access detected
+        AlphaStreamProcessor::maybe_trigger_prefetch(&mut scheduler, requested_frame_index).await;
+
+        None // Will be available after background processing completes
+    }
+
+    /// Get a rasterized frame like `get_frame`, but wait for it to be decoded instead of returning None.
+    /// Gives up after `timeout`, or as soon as the frame's decode fails or no bitmap can be handed out.
+    pub async fn get_frame_wait(&self, frame_index: usize, timeout: Duration) -> Result<Vec<u8>, FrameWaitError> {
+        let cache_index = self.cache_index(frame_index);
+        // A failure from an earlier request is retried rather than reported
+        self.frame_signal.take_failure(cache_index);
+        let wait = async {
+            loop {
+                // Registered before checking, so a frame finishing in between still wakes us
+                let notified = self.frame_signal.notify.notified();
+                tokio::pin!(notified);
+                notified.as_mut().enable();
+                if let Some(frame) = self.get_frame(frame_index, self.width, self.height).await {
+                    return Ok(frame);
+                }
+                self.entitlement_status().map_err(FrameWaitError::Entitlement)?;
+                // Checked after get_frame: a frame cached before it may have been evicted by the call
+                // (the cache window slides with the play head), and one may have arrived since
+                match self.cache.get(cache_index) {
+                    Some(frame_data) if frame_data.is_empty() && self.empty_frame_policy == EmptyFramePolicy::Error => return Err(FrameWaitError::Empty(frame_index)),
+                    Some(frame_data) if frame_data.bitmap.is_none() => return Err(FrameWaitError::NoBitmap),
+                    Some(_) => continue,
+                    None => {}
+                }
+                if self.frame_signal.take_failure(cache_index) {
+                    return Err(FrameWaitError::DecodeFailed(frame_index));
+                }
+                notified.await;
+            }
+        };
+        tokio::time::timeout(timeout, wait).await.unwrap_or(Err(FrameWaitError::Timeout(frame_index)))
+    }
+
+    /// The frame as `get_frame` would hand it out, if it is decoded; None otherwise.
+    /// Does not move the play head or schedule anything, e.g. for handing out frames as they become ready.
+    pub fn cached_frame(&self, frame_index: usize) -> Option<Vec<u8>> {
+        let cache_index = self.cache_index(frame_index);
+        let (source_index, frame_data) = self.resolve_empty_frame(cache_index, self.cache.get(cache_index)?)?;
+        self.output_mask(source_index, frame_data).map(|bitmap| self.packing.pack_owned(bitmap, self.width, self.height))
+    }
+
+    /// Whether a decoded frame has no outline data; None if the frame is not decoded (cached) yet.
+    /// Does not move the play head or schedule anything.
+    pub fn is_frame_empty(&self, frame_index: usize) -> Option<bool> {
+        self.cache.get(self.cache_index(frame_index)).map(|frame_data| frame_data.is_empty())
+    }
+
+    /// Cached frame the getters hand out for `cache_index` under the empty frame policy, with its cache index
+    /// None withholds an empty frame (EmptyFramePolicy::Error).
+    fn resolve_empty_frame(&self, cache_index: usize, frame_data: FrameData) -> Option<(usize, FrameData)> {
+        if !frame_data.is_empty() {
+            return Some((cache_index, frame_data));
+        }
+        match self.empty_frame_policy {
+            EmptyFramePolicy::ReturnEmpty => Some((cache_index, frame_data)),
+            EmptyFramePolicy::Error => None,
+            EmptyFramePolicy::ReuseLast => {
+                let last = (self.cache.get_start_index()..cache_index).rev()
+                    .find_map(|index| self.cache.get(index).filter(|earlier| !earlier.is_empty()).map(|earlier| (index, earlier)));
+                Some(last.unwrap_or((cache_index, frame_data)))
+            }
+        }
+    }
+
+    /// Report the size frames are actually displayed at, e.g. small in picture-in-picture, large in fullscreen
+    /// Frames decoded from now on are rasterized at the output size divided by the largest power of two
+    /// (up to MAX_RASTER_DIVISOR) that still covers the display, and scaled up to the output size when handed
+    /// out, so buffers keep their size. When the display grows again, cached frames rasterized too coarse are
+    /// rasterized again from their polystream on access. A size of 0 restores the full resolution.
+    /// Returns the divisor now in effect.
+    pub fn report_display_size(&self, width: u32, height: u32) -> u32 {
+        let mut divisor = 1;
+        while divisor < MAX_RASTER_DIVISOR && width > 0 && height > 0 {
+            let (reduced_width, reduced_height, _) = AlphaStreamProcessor::reduced_raster(self.width, self.height, &self.raster_options, divisor * 2);
+            if reduced_width < width || reduced_height < height {
+                break;
+            }
+            divisor *= 2;
+        }
+        let previous = self.raster_divisor.swap(divisor, std::sync::atomic::Ordering::Relaxed);
+        if previous != divisor {
+            logging::log(LogLevel::Debug, format_args!("Display {}x{}: rasterizing at 1/{} of {}x{}", width, height, divisor, self.width, self.height));
+        }
+        divisor
+    }
+
+    /// Full-size mask of a cached frame
+    /// A mask rasterized at a reduced resolution is scaled up and only then watermarked. One coarser than the
+    /// display now needs is first rasterized again from the polystream, and put back into the cache.
+    fn output_mask(&self, cache_index: usize, frame_data: FrameData) -> Option<Vec<u8>> {
+        let bitmap = frame_data.bitmap?;
+        let reduced_size = |divisor| {
+            let (width, height, _) = AlphaStreamProcessor::reduced_raster(self.width, self.height, &self.raster_options, divisor);
+            (width * height) as usize
+        };
+        if bitmap.len() == reduced_size(1) {
+            return Some(bitmap);
+        }
+        let Some(mut divisor) = (1..=MAX_RASTER_DIVISOR.trailing_zeros()).map(|shift| 1 << shift).find(|&divisor| bitmap.len() == reduced_size(divisor)) else {
+            return Some(bitmap);
+        };
+        let mut bitmap = bitmap;
+        let current = self.raster_divisor();
+        if current < divisor {
+            let (width, height, options) = AlphaStreamProcessor::reduced_raster(self.width, self.height, &self.raster_options, current);
+            let (_channel_count, channel_sizes, channel_data) = AlphaStreamProcessor::parse_polystream(&frame_data.polystream);
+            bitmap = AlphaStreamProcessor::rasterize_channels(&self.rasterizer, &channel_sizes, channel_data, self.channels.as_deref(), width, height, &options);
+            if current == 1 {
+                if let Some(watermark) = &self.watermark {
+                    watermark.embed(&mut bitmap, cache_index * self.stride);
+                }
+            }
+            let refined = FrameData { polystream: frame_data.polystream, bitmap: Some(bitmap.clone()), triangle_strip: frame_data.triangle_strip };
+            self.cache.insert(cache_index, refined);
+            if current == 1 {
+                return Some(bitmap);
+            }
+            divisor = current;
+        }
+        let (width, height, _) = AlphaStreamProcessor::reduced_raster(self.width, self.height, &self.raster_options, divisor);
+        let mut mask = resize_nearest_neighbor(&bitmap, width, height, self.width, self.height);
+        if let Some(watermark) = &self.watermark {
+            watermark.embed(&mut mask, cache_index * self.stride);
+        }
+        Some(mask)
+    }
+
+    /// Get the decoded (decrypted, decompressed) polystream of a frame
+    /// Available in every processing mode; this is the only output in ProcessingMode::PolystreamOnly.
+    /// Returns None and schedules the frame if it is not decoded yet, like get_frame.
+    pub async fn get_polystream(&self, frame_index: usize) -> Option<Vec<u8>> {
+        if !self.begin_access(frame_index) {
+            return None;
+        }
+        let frame_index = self.cache_index(frame_index);
+        self.cache.update_play_head(frame_index);
+
+        if let Some(frame_data) = self.cache.lookup(frame_index) {
+            return Some(frame_data.polystream);
+        }
+        let mut scheduler = self.scheduler.lock().await;
+        scheduler.schedule_task(Task::with_priority(frame_index, Priority::Interactive.value()));
+        AlphaStreamProcessor::maybe_trigger_prefetch(&mut scheduler, frame_index).await;
+        None
+    }
+
+    /// Number of channels of a frame and their sizes, without rasterizing it
+    /// Read from the cached frame when it is decoded already; otherwise from the start of the ASVP record
+    /// (see `ASVPFormat::peek_channel_sizes`), or, for ASVR and delta frames, by decoding the frame off the
+    /// cache and the play head. A UI can show the structure of any frame this way.
+    pub async fn channel_info(&self, frame_index: usize) -> Result<ChannelInfo, FormatError> {
+        let frame_count = self.metadata().await?.frame_count as usize;
+        if frame_index >= frame_count {
+            return Err(FormatError::InvalidFormat(format!("Frame {} is past the last frame {}", frame_index, frame_count.saturating_sub(1))));
+        }
+        let decoded_index = self.cache_index(frame_index);
+        if let Some(frame_data) = self.cache.get(decoded_index) {
+            return ChannelInfo::from_polystream(frame_index, &frame_data.polystream);
+        }
+        if let Some(gate) = &self.entitlement {
+            if !AlphaStreamProcessor::entitlement_allows(gate, &self.cache, &self.events) {
+                return Err(FormatError::Entitlement(gate.status().unwrap_err()));
+            }
+        }
+        let mut format = self.shards.acquire().await;
+        let source_index = (decoded_index * self.stride) as u32;
+        if let Some(sizes) = format.peek_channel_sizes(source_index).await? {
+            return Ok(ChannelInfo { frame_index, sizes });
+        }
+        let frame_data = format.decode_frame(source_index).await?;
+        ChannelInfo::from_polystream(frame_index, &frame_data.polystream)
+    }
+
+    /// Rasterize a polystream (e.g. from get_polystream) with this processor's size, channels and raster options
+    /// Lets callers rasterize on their own threads, e.g. to overlap decoding and rasterization in an export.
+    pub fn rasterize_polystream(&self, polystream: &[u8]) -> Vec<u8> {
+        let (_channel_count, channel_sizes, channel_data) = AlphaStreamProcessor::parse_polystream(polystream);
+        AlphaStreamProcessor::rasterize_channels(&self.rasterizer, &channel_sizes, channel_data, self.channels.as_deref(), self.width, self.height, &self.raster_options)
+    }
+
+    /// Embed the configured watermark into a mask of `frame_index` rasterized by the caller
+    /// (e.g. with rasterize_polystream). Does nothing without a watermark.
+    pub fn watermark_mask(&self, frame_index: usize, mask: &mut [u8]) {
+        if let Some(watermark) = &self.watermark {
+            watermark.embed(mask, self.cache_index(frame_index) * self.stride);
+        }
+    }
+
+    /// Number of outline vertices of a polystream (e.g. from get_polystream) in this processor's selected channels
+    pub fn polystream_vertex_count(&self, polystream: &[u8]) -> usize {
+        let (_channel_count, channel_sizes, channel_data) = AlphaStreamProcessor::parse_polystream(polystream);
+        let mut offset = 0;
+        let mut count = 0;
+        for (channel, &size) in channel_sizes.iter().enumerate() {
+            if channel_selected(self.channels.as_deref(), channel) {
+                count += PolystreamRasterizer::vertex_count(&channel_data[offset..offset + size as usize]);
+            }
+            offset += size as usize;
+        }
+        count
+    }
+
+    /// Rasterize a polystream (e.g. from get_polystream) of `frame_index` in tiles of at most
+    /// `tile_size` x `tile_size` pixels on `threads` threads (0 for one per core). Each finished tile is
+    /// handed to `on_tile` on the calling thread, in completion order, with its R8 mask of
+    /// tile.width * tile.height bytes. Only the tiles being drawn or waiting for `on_tile` are in memory,
+    /// never the whole mask, so e.g. 8K masks can be written out on machines that could not hold many.
+    /// Channels, raster options and the watermark apply as in rasterize_polystream and watermark_mask.
+    pub fn rasterize_polystream_tiles(&self, frame_index: usize, polystream: &[u8], tile_size: u32, threads: usize, mut on_tile: impl FnMut(&Tile, &[u8])) {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let (_channel_count, channel_sizes, channel_data) = AlphaStreamProcessor::parse_polystream(polystream);
+        let tiles = Tile::grid(self.width, self.height, tile_size);
+        let threads = match threads {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            threads => threads,
+        };
+        let threads = threads.min(tiles.len()).max(1);
+        let next = AtomicUsize::new(0);
+        let (tile_tx, tile_rx) = std::sync::mpsc::sync_channel(threads);
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                let tile_tx = tile_tx.clone();
+                let (tiles, next, channel_sizes) = (&tiles, &next, &channel_sizes);
+                scope.spawn(move || {
+                    while let Some(tile) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        let mask = self.rasterize_tile(frame_index, channel_sizes, channel_data, tile);
+                        // The receiver is gone if on_tile panicked
+                        if tile_tx.send((tile, mask)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(tile_tx);
+            for (tile, mask) in tile_rx {
+                on_tile(tile, &mask);
+            }
+        });
+    }
+
+    /// Like rasterize_polystream_tiles, with the tiles joined into the whole mask. The result is the
+    /// mask of rasterize_polystream and watermark_mask, drawn by several threads.
+    pub fn rasterize_polystream_tiled(&self, frame_index: usize, polystream: &[u8], tile_size: u32, threads: usize) -> Vec<u8> {
+        let width = self.width as usize;
+        let mut mask = vec![0u8; width * self.height as usize];
+        self.rasterize_polystream_tiles(frame_index, polystream, tile_size, threads, |tile, pixels| {
+            for (row, values) in pixels.chunks_exact(tile.width as usize).enumerate() {
+                let start = (tile.y as usize + row) * width + tile.x as usize;
+                mask[start..start + values.len()].copy_from_slice(values);
+            }
+        });
+        mask
+    }
+
+    /// One tile of the mask of a frame, with the selected channels and the watermark
+    fn rasterize_tile(&self, frame_index: usize, channel_sizes: &[u32], channel_data: &[u8], tile: &Tile) -> Vec<u8> {
+        let mut mask = vec![0u8; tile.pixel_count()];
+        let mut offset = 0;
+        for (channel, &size) in channel_sizes.iter().enumerate() {
+            if channel_selected(self.channels.as_deref(), channel) {
+                self.rasterizer.rasterize_tile(&channel_data[offset..offset + size as usize], self.width, self.height, &self.raster_options, tile, &mut mask);
+            }
+            offset += size as usize;
+        }
+        if let Some(watermark) = &self.watermark {
+            watermark.embed_tile(&mut mask, self.cache_index(frame_index) * self.stride, tile, self.width);
+        }
+        mask
+    }
+
+    /// Decode and processing times of a decoded frame
+    /// None until the frame has been decoded, and again once it has left the cache window.
+    pub fn frame_trace(&self, frame_index: usize) -> Option<FrameTrace> {
+        self.traces.lock().unwrap().get(&self.cache_index(frame_index)).copied()
+    }
+
+    /// Stored (compressed, encrypted) bytes of the frames decoded from the source since the processor was built.
+    /// Baked frames are not read from the source and do not count. Sample it twice for the transport throughput.
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Current cache and scheduler counters
+    pub async fn stats(&self) -> ProcessorStats {
+        let cache = self.cache.stats();
+        let (queued_tasks, active_tasks) = {
+            let scheduler = self.scheduler.lock().await;
+            (scheduler.get_number_of_queued_tasks(), scheduler.get_number_of_active_tasks())
+        };
+        let average_decode = {
+            let traces = self.traces.lock().unwrap();
+            let total: Duration = traces.values().map(|trace| trace.decode + trace.process).sum();
+            (!traces.is_empty()).then(|| total / traces.len() as u32)
+        };
+        ProcessorStats {
+            cache_hits: cache.hits,
+            cache_misses: cache.misses,
+            ready_frames: cache.ready,
+            in_progress_frames: cache.in_progress,
+            queued_tasks,
+            active_tasks,
+            average_decode,
+            dropped_frames: cache.dropped,
+        }
+    }
+
+    /// Get triangle strip vertices for a frame
+    /// Similar to get_frame but for 3D geometry data. Checks cache first, schedules if needed.
+    /// Returns None if not ready yet, allowing non-blocking operation.
+    pub async fn get_triangle_strip_vertices(&self, frame_index: usize) -> Option<Vec<f32>> {
+        if !self.begin_access(frame_index) {
+            return None;
+        }
+        let frame_index = self.cache_index(frame_index);
+        // Update play head position for seek detection
+        self.cache.update_play_head(frame_index);
+
+        if let Some(frame_data) = self.cache.lookup(frame_index) { // Cache check
+            let (_, frame_data) = self.resolve_empty_frame(frame_index, frame_data)?;
+            if frame_data.triangle_strip.is_some() {
+                return frame_data.triangle_strip.clone(); // Return cached vertices
+            }
+        }
+        // Schedule processing
+        let mut scheduler = self.scheduler.lock().await;
+        let task = Task::with_priority(frame_index, Priority::Interactive.value());
+        scheduler.schedule_task(task);
+
+        // Prefetch if sequential access detected
+        AlphaStreamProcessor::maybe_trigger_prefetch(&mut scheduler, frame_index).await;
+
+        None
+    }
+
+    /// Triangles of a frame as an indexed mesh with texture coordinates, see `TriangleMesh`.
+    /// Same availability as `get_triangle_strip_vertices`, which it is built from.
+    pub async fn get_triangles(&self, frame_index: usize) -> Option<TriangleMesh> {
+        self.get_triangle_strip_vertices(frame_index).await.map(|vertices| TriangleMesh::from_triangle_list(&vertices))
+    }
+
+    /// Get the bitmap, vertices, mask statistics and timing of a frame at once
+    /// Cheaper than separate get_frame / get_triangle_strip_vertices calls when several outputs are
+    /// needed: the cache is looked up once. Returns None and schedules the frame if it is not decoded yet.
+    pub async fn get_frame_output(&self, frame_index: usize) -> Option<FrameOutput> {
+        if !self.begin_access(frame_index) {
+            return None;
+        }
+        let cache_index = self.cache_index(frame_index);
+        self.cache.update_play_head(cache_index);
+
+        if let Some(frame_data) = self.cache.lookup(cache_index) {
+            let is_empty = frame_data.is_empty();
+            let trace = self.traces.lock().unwrap().get(&cache_index).copied();
+            let Some((source_index, frame_data)) = self.resolve_empty_frame(cache_index, frame_data) else {
+                return Some(FrameOutput { frame_index, bitmap: None, triangle_strip: None, stats: None, trace, is_empty });
+            };
+            let triangle_strip = frame_data.triangle_strip.clone();
+            let bitmap = self.output_mask(source_index, frame_data);
+            let stats = bitmap.as_deref().map(|bitmap| MaskStats::from_mask(bitmap, self.width, self.height));
+            return Some(FrameOutput {
+                frame_index,
+                bitmap: bitmap.map(|bitmap| self.packing.pack_owned(bitmap, self.width, self.height)),
+                triangle_strip,
+                stats,
+                trace,
+                is_empty,
+            });
+        }
+        let mut scheduler = self.scheduler.lock().await;
+        scheduler.schedule_task(Task::with_priority(cache_index, Priority::Interactive.value()));
+        AlphaStreamProcessor::maybe_trigger_prefetch(&mut scheduler, cache_index).await;
+        None
+    }
+
+    /// Get a rasterized frame (R8 mask) of only the given channels
+    /// Overrides the processor's channel selection for this call. The mask is rasterized from the
+    /// cached polystream, so it returns None until the frame has been decoded.
+    ///
+    /// # Arguments
+    /// * `frame_index` - The frame to rasterize
+    /// * `channels` - Channel indices to include; indices beyond the frame's channel count are ignored
+    pub async fn get_frame_channels(&self, frame_index: usize, channels: &[usize]) -> Option<Vec<u8>> {
+        if !self.begin_access(frame_index) {
+            return None;
+        }
+        let frame_index = self.cache_index(frame_index);
+        self.cache.update_play_head(frame_index);
+
+        if let Some(frame_data) = self.cache.lookup(frame_index) {
+            let mut selection = channels.to_vec();
+            selection.sort_unstable();
+            let (_channel_count, channel_sizes, channel_data) = AlphaStreamProcessor::parse_polystream(&frame_data.polystream);
+            let mut mask = AlphaStreamProcessor::rasterize_channels(&self.rasterizer, &channel_sizes, channel_data, Some(&selection), self.width, self.height, &self.raster_options);
+            if let Some(watermark) = &self.watermark {
+                watermark.embed(&mut mask, frame_index * self.stride);
+            }
+            return Some(mask);
+        }
+        let mut scheduler = self.scheduler.lock().await;
+        scheduler.schedule_task(Task::with_priority(frame_index, Priority::Interactive.value()));
+        AlphaStreamProcessor::maybe_trigger_prefetch(&mut scheduler, frame_index).await;
+        None
+    }
+
+    /// Get triangle strip vertices for a frame with a different simplification tolerance than the processor's
+    /// Useful for lowering the detail of distant or low-importance objects per call. The strip is
+    /// re-triangulated from the cached polystream, so it returns None until the frame has been decoded.
+    ///
+    /// # Arguments
+    /// * `frame_index` - The frame to triangulate
+    /// * `tolerance` - Douglas-Peucker epsilon in native polystream units; 0 keeps every point
+    pub async fn get_triangle_strip_vertices_with_tolerance(&self, frame_index: usize, tolerance: f32) -> Option<Vec<f32>> {
+        if tolerance == self.simplify_tolerance {
+            return self.get_triangle_strip_vertices(frame_index).await;
+        }
+        if !self.begin_access(frame_index) {
+            return None;
+        }
+        let frame_index = self.cache_index(frame_index);
+        self.cache.update_play_head(frame_index);
+
+        if let Some(frame_data) = self.cache.lookup(frame_index) {
+            let (_channel_count, channel_sizes, channel_data) = AlphaStreamProcessor::parse_polystream(&frame_data.polystream);
+            return Some(AlphaStreamProcessor::triangulate_channels(&self.rasterizer, &channel_sizes, channel_data, self.channels.as_deref(), tolerance.max(0.0)));
+        }
+        let mut scheduler = self.scheduler.lock().await;
+        scheduler.schedule_task(Task::with_priority(frame_index, Priority::Interactive.value()));
+        AlphaStreamProcessor::maybe_trigger_prefetch(&mut scheduler, frame_index).await;
+        None
+    }
+
+    /// Start or stop queueing ProcessorEvents for poll_events
+    /// Off by default so processors nobody polls do not collect events. Disabling clears the queue.
+    pub fn enable_events(&self, enabled: bool) {
+        self.events.enabled.store(enabled, std::sync::atomic::Ordering::Release);
+        if !enabled {
+            self.events.queue.lock().unwrap().clear();
+        }
+    }
+
+    /// Take all queued events, oldest first
+    /// Call from whichever thread should handle them; nothing is delivered anywhere else.
+    pub fn poll_events(&self) -> Vec<ProcessorEvent> {
+        self.events.queue.lock().unwrap().drain(..).collect()
+    }
+
+    /// Number of unfinished tasks on the processor's runtime: the background worker, the reload task
+    /// and frames being decoded. Bounded by the prefetch window plus a few; for leak checks.
+    pub fn alive_tasks(&self) -> usize {
+        self.runtime.as_ref().map_or(0, |runtime| runtime.alive_tasks())
+    }
+
+    /// Get the frames visited in this session so far
+    /// Every get_frame / get_triangle_strip_vertices call is recorded. Store the result (its
+    /// `to_string()` form) and pass it to `warm_from_pattern` in the next session.
+    pub fn record_access_pattern(&self) -> AccessPattern {
+        self.access_log.lock().unwrap().clone()
+    }
+
+    /// Prefetch the frames of a previously recorded access pattern
+    /// The play head moves to the pattern's first frame and the pattern's frames are scheduled
+    /// in visit order at prefetch priority. Only frames that fit in the cache window starting
+    /// there can be held, so the rest of a long pattern is skipped.
+    ///
+    /// # Returns
+    /// The number of frames scheduled
+    pub async fn warm_from_pattern(&self, pattern: &AccessPattern) -> Result<usize, FormatError> {
+        let frame_count = self.metadata().await?.frame_count as usize;
+        let mut frames = pattern.frames().filter(|&f| f < frame_count).map(|f| self.cache_index(f)).peekable();
+        let first = match frames.peek() {
+            Some(&first) => first,
+            None => return Ok(0),
+        };
+        self.cache.update_play_head(first);
+
+        let mut scheduler = self.scheduler.lock().await;
+        // With a stride several visited frames share one decoded frame
+        let mut scheduled = std::collections::HashSet::new();
+        for frame_index in frames {
+            if self.cache.is_in_range(frame_index) && !self.cache.contains(&frame_index) && scheduled.insert(frame_index) {
+                scheduler.schedule_task(Task::new(frame_index));
+            }
+        }
+        Ok(scheduled.len())
+    }
+
+    /// Request a frame for processing
+    /// Schedules in the Low lane, alongside prefetch; see request_frame_with_priority.
+    pub async fn request_frame(&self, frame_index: u32) -> Result<(), FormatError> {
+        self.request_frame_with_priority(frame_index, Priority::Low).await
+    }
+
+    /// Request a frame for processing in a given scheduling lane
+    /// Use Priority::Normal to pre-queue frames (e.g. for an export) ahead of the read-ahead, and
+    /// Priority::Interactive for frames that are needed right away. A frame that is already queued
+    /// is moved up to the requested lane, never down.
+    pub async fn request_frame_with_priority(&self, frame_index: u32, priority: Priority) -> Result<(), FormatError> {
+        // Check bounds using metadata
+        let meta = self.metadata().await?;
+        if frame_index as usize >= meta.frame_count as usize {
+            logging::log(LogLevel::Warn, format_args!("Requested frame_index {} out of bounds (max {})", frame_index, meta.frame_count));
+            return Ok(()); // Silently ignore or return error if preferred
+        }
+        let frame_index = self.cache_index(frame_index as usize);
+        // Check if already in cache
+        if self.cache.contains(&frame_index) {
+            return Ok(());
+        }
+
+        // Schedule the frame for decoding
+        let mut scheduler = self.scheduler.lock().await;
+        let task = Task::with_priority(frame_index, priority.value());
         scheduler.schedule_task(task);
+        Ok(())
+    }
+
+    /// Request a whole range of frames for processing, e.g. a scene for export
+    /// The range is queued as one scheduler entry and expanded into frame tasks as the cache window
+    /// reaches them, so queueing thousands of frames stays cheap. Clamped to the frame count.
+    pub async fn request_range(&self, frames: std::ops::Range<u32>, priority: Priority) -> Result<(), FormatError> {
+        let frame_count = self.metadata().await?.frame_count;
+        let (start, end) = (frames.start.min(frame_count) as usize, frames.end.min(frame_count) as usize);
+        if start >= end {
+            return Ok(());
+        }
+        let mut scheduler = self.scheduler.lock().await;
+        scheduler.schedule_range(self.cache_index(start)..self.cache_index(end - 1) + 1, priority.value());
+        Ok(())
+    }
+
+    /// Tell the processor which frames a timeline UI shows, e.g. the thumbnails on screen
+    /// Prefetch then shares its budget between the frames right after the play head and the visible
+    /// frames instead of reading strictly ahead. Only visible frames inside the cache window around
+    /// the play head can be held. An empty range removes the hint.
+    pub async fn set_visible_range(&self, start: u32, end: u32) {
+        let range = (start < end).then(|| self.cache_index(start as usize)..self.cache_index(end as usize - 1) + 1);
+        let mut scheduler = self.scheduler.lock().await;
+        scheduler.set_visible_range(range);
+        // Apply the hint now instead of at the next frame request
+        scheduler.prefetch(self.cache.get_play_head());
+    }
+
+    /// Sum the masks of a range of frames into a heatmap
+    /// Frames are decoded directly instead of going through the cache, so aggregating a long range
+    /// does not evict the frames around the play head.
+    ///
+    /// # Arguments
+    /// * `range` - Frame indices to aggregate, clamped to the frame count
+    ///
+    /// # Returns
+    /// A heatmap at the processor's output size
+    pub async fn aggregate_heatmap(&self, range: std::ops::Range<u32>) -> Result<Heatmap, FormatError> {
+        let frame_count = self.metadata().await?.frame_count;
+        let mut heatmap = Heatmap::new(self.width, self.height);
+        for frame_index in range.start.min(frame_count)..range.end.min(frame_count) {
+            // Lock per frame so playback decoding can interleave with a long aggregation
+            let frame_data = self.format.lock().await.decode_frame(frame_index).await?;
+            let (_channel_count, channel_sizes, channel_data) = AlphaStreamProcessor::parse_polystream(&frame_data.polystream);
+            heatmap.accumulate(&AlphaStreamProcessor::rasterize_channels(&self.rasterizer, &channel_sizes, channel_data, self.channels.as_deref(), self.width, self.height, &self.raster_options));
+        }
+        Ok(heatmap)
+    }
+
+    /// Decode only the frames of `range` that match, for sparse analysis jobs over long archives.
+    /// `select` sees what is known before decoding (the frame's stored size from the sizes table and
+    /// the notes of a container's annotation track), so rejected frames are never read. `filter`
+    /// is then applied to the mask statistics of the selected frames. Frames are decoded directly
+    /// instead of going through the cache and are verified on the way (decompressed length and the
+    /// zlib checksum); a frame that fails verification ends the stream with its error.
+    ///
+    /// # Arguments
+    /// * `range` - Frame indices to consider, clamped to the frame count
+    /// * `select` - Cheap pre-decode predicate, e.g. `|frame| frame.stored_size > 64`
+    /// * `filter` - Predicate on mask statistics, e.g. `area > 0` for frames with non-empty masks
+    ///
+    /// # Returns
+    /// A stream of the matching frames in order, processed according to the processing mode
+    pub async fn decode_where<'a>(
+        &'a self,
+        range: std::ops::Range<u32>,
+        select: impl FnMut(&FrameCandidate) -> bool,
+        filter: Option<&'a FrameFilter>,
+    ) -> Result<impl futures::Stream<Item = Result<DecodedFrame, FormatError>> + 'a, FormatError> {
+        use futures::StreamExt;
+
+        let selected = self.select_frames(range, select).await?;
+        Ok(self.scan(selected, filter.is_some(), move |index, stats, _, _| match (filter, stats) {
+            (Some(filter), Some(stats)) => Ok(filter.matches(index, stats).then_some(())),
+            _ => Ok(Some(())),
+        }).map(|frame| frame.map(|(frame, ())| frame)))
+    }
+
+    /// Content fingerprints of the frames in `range` (clamped to the frame count), one per frame, see
+    /// `fingerprint::frame_fingerprint`. Frames are decoded directly, bypassing the cache. Compare the
+    /// fingerprints of two files with `fingerprint::align` to find how their frames line up.
+    pub async fn fingerprint(&self, range: std::ops::Range<u32>) -> Result<Vec<u64>, FormatError> {
+        let frames = self.select_frames(range, |_| true).await?;
+        let mut fingerprints = Vec::with_capacity(frames.len());
+        for index in frames {
+            if let Some(gate) = &self.entitlement {
+                if !AlphaStreamProcessor::entitlement_allows(gate, &self.cache, &self.events) {
+                    return Err(FormatError::Entitlement(gate.status().unwrap_err()));
+                }
+            }
+            let frame_data = self.shards.acquire().await.decode_frame(index).await?;
+            fingerprints.push(crate::fingerprint::frame_fingerprint(&frame_data.polystream));
+        }
+        Ok(fingerprints)
+    }
+
+    /// Decode the frames of `range` like `decode_where` (without a filter) and hand each to `sink`, carrying on
+    /// past frames that fail. Frames that do not decode and frames `sink` rejects are listed in the report
+    /// instead of ending the job, so a long export completes and its failed frames can be exported again
+    /// afterwards, e.g. one by one from `failed_indices`.
+    pub async fn export_frames(&self, range: std::ops::Range<u32>, sink: impl FnMut(DecodedFrame) -> std::io::Result<()>) -> Result<ExportReport, FormatError> {
+        let frames = self.select_frames(range, |_| true).await?;
+        Ok(self.export_selected(frames, sink).await)
+    }
+
+    /// Export the frames of `range` as PNG files in `layout`'s output directory, like `export_frames`.
+    /// Frames an earlier, interrupted run left intact are skipped and counted in `skipped`, so a job started
+    /// again with the same layout continues where it stopped. Needs R8 bitmaps without row padding, the
+    /// default output packing; frames without one are reported as sink failures.
+    pub async fn export_png(&self, range: std::ops::Range<u32>, layout: &mut ExportLayout) -> Result<ExportReport, FormatError> {
+        let frames = self.select_frames(range, |_| true).await?;
+        let (pending, done): (Vec<u32>, Vec<u32>) = frames.into_iter().partition(|&index| !layout.is_done(index));
+        let encodable = self.packing == OutputPacking::default();
+        let mut report = self.export_selected(pending, |frame| {
+            let bitmap = frame.output.bitmap.as_ref().filter(|_| encodable)
+                .ok_or_else(|| std::io::Error::other("no R8 bitmap to encode"))?;
+            let png = crate::png::encode_gray8(bitmap, self.width, self.height);
+            layout.write(frame.output.frame_index as u32, &png).map(|_| ())
+        }).await;
+        report.skipped = done.len() as u32;
+        Ok(report)
+    }
+
+    /// Process every frame at the output size and write them to a baked scene at `path` (see the `bake`
+    /// module), replacing it only once complete. A processor built with `baked_scene(path)` then serves the
+    /// frames from it. Frames that fail are listed in the report and left unbaked, so playback decodes them
+    /// from the source. Needs the default output packing, the layout the cache holds.
+    pub async fn bake(&self, path: impl AsRef<std::path::Path>) -> Result<ExportReport, FormatError> {
+        if self.packing != OutputPacking::default() {
+            return Err(FormatError::InvalidFormat("Baking needs the default output packing".to_string()));
+        }
+        let frame_count = self.metadata().await?.frame_count;
+        let mut writer = BakeWriter::create(path, self.width, self.height, frame_count)?;
+        let report = self.export_frames(0..frame_count, |frame| {
+            let frame_data = FrameData { polystream: frame.polystream, bitmap: frame.output.bitmap, triangle_strip: frame.output.triangle_strip };
+            writer.add_frame(frame.output.frame_index as u32, &frame_data).map_err(std::io::Error::other)
+        }).await?;
+        logging::log(LogLevel::Info, format_args!("Baked {} frames, {} of them shared with an equal frame", report.succeeded, writer.deduplicated()));
+        writer.finish()?;
+        Ok(report)
+    }
+
+    /// Body of `export_frames`, for frames already selected
+    async fn export_selected(&self, frames: Vec<u32>, mut sink: impl FnMut(DecodedFrame) -> std::io::Result<()>) -> ExportReport {
+        use futures::StreamExt;
+
+        let start = self.clock.now();
+        let mut report = ExportReport::default();
+        // Every frame yields one item, success or error, so the stream stays in step with the indices
+        let decoded = self.scan(frames.clone(), false, |_, _, _, _| Ok(Some(())));
+        let mut decoded = std::pin::pin!(futures::stream::iter(frames).zip(decoded));
+        while let Some((index, frame)) = decoded.next().await {
+            match frame {
+                Ok((frame, ())) => match sink(frame) {
+                    Ok(()) => report.succeeded += 1,
+                    Err(e) => report.record_failure(index, FrameErrorKind::Sink, e),
+                },
+                Err(e) => {
+                    logging::log(LogLevel::Warn, format_args!("Export of frame {} failed: {}", index, e));
+                    report.record_failure(index, FrameErrorKind::from(&e), e);
+                }
+            }
+        }
+        report.elapsed = self.clock.now() - start;
+        report
+    }
+
+    /// Export like `export_frames`, with `overlay` (frame number, timecode, bounding box) burnt into every
+    /// bitmap for QC review. Only R8 bitmaps without row padding, the default output packing, are drawn on.
+    pub async fn export_frames_with_overlay(&self, range: std::ops::Range<u32>, overlay: &Overlay, mut sink: impl FnMut(DecodedFrame) -> std::io::Result<()>) -> Result<ExportReport, FormatError> {
+        let drawable = self.packing == OutputPacking::default();
+        self.export_frames(range, |mut frame| {
+            if let (Some(bitmap), true) = (frame.output.bitmap.as_mut(), drawable) {
+                overlay.apply(bitmap, self.width, self.height, frame.output.frame_index as u32);
+            }
+            sink(frame)
+        }).await
+    }
+
+    /// Run a script on the frames of `range`, for analysis the filter expressions cannot express.
+    /// Works like `decode_where`: `select` picks frames before decoding, then the script sees the
+    /// mask statistics of each selected frame and of each of its channels (see `script` for the
+    /// variables). Frames the script vetoes are skipped; a script error ends the stream.
+    #[cfg(feature = "scripting")]
+    pub async fn decode_scripted<'a>(
+        &'a self,
+        range: std::ops::Range<u32>,
+        select: impl FnMut(&FrameCandidate) -> bool,
+        script: &'a FrameScript,
+    ) -> Result<impl futures::Stream<Item = Result<ScriptedFrame, FormatError>> + 'a, FormatError> {
+        use futures::StreamExt;
+
+        let selected = self.select_frames(range, select).await?;
+        Ok(self.scan(selected, true, move |index, stats, channel_sizes, channel_data| {
+            let channels: Vec<ChannelStats> = channel_sizes.iter().enumerate()
+                .filter(|&(channel, _)| channel_selected(self.channels.as_deref(), channel))
+                .map(|(channel, &size)| {
+                    let mask = AlphaStreamProcessor::rasterize_channels(&self.rasterizer, channel_sizes, channel_data, Some(&[channel]), self.width, self.height, &self.raster_options);
+                    ChannelStats { index: channel, size, stats: MaskStats::from_mask(&mask, self.width, self.height) }
+                })
+                .collect();
+            let stats = stats.expect("scripted scans always rasterize");
+            let result = script.eval(&ScriptFrame { frame_index: index, stats, channels: &channels })
+                .map_err(|e| FormatError::InvalidFormat(e.to_string()))?;
+            Ok(result.keep.then_some(result.values))
+        }).map(|frame| frame.map(|(frame, values)| ScriptedFrame { frame, values })))
+    }
+
+    /// Frames of `range` (clamped to the frame count) that pass the pre-decode `select`
+    async fn select_frames(&self, range: std::ops::Range<u32>, mut select: impl FnMut(&FrameCandidate) -> bool) -> Result<Vec<u32>, FormatError> {
+        let frame_count = self.metadata().await?.frame_count;
+        let annotations = match &*self.format.lock().await {
+            FormatType::ASVP(format) if !format.tracks().is_empty() => format.annotations(None).await.unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        let frame_sizes = self.format.lock().await.frame_sizes().to_vec();
+        Ok((range.start.min(frame_count)..range.end.min(frame_count))
+            .filter(|&index| {
+                let notes: Vec<&Annotation> = annotations.iter().filter(|note| note.frame_index == index).collect();
+                select(&FrameCandidate { index, stored_size: frame_sizes[index as usize], annotations: &notes })
+            })
+            .collect())
+    }
+
+    /// Decode `frames` directly, bypassing the cache, and keep those `check` returns Some for.
+    /// `check` gets the mask statistics when `stats` is set (or the mode rasterizes anyway) and the
+    /// parsed polystream channels.
+    fn scan<'a, T: 'a>(
+        &'a self,
+        frames: Vec<u32>,
+        stats: bool,
+        check: impl Fn(u32, Option<&MaskStats>, &[u32], &[u8]) -> Result<Option<T>, FormatError> + 'a,
+    ) -> impl futures::Stream<Item = Result<(DecodedFrame, T), FormatError>> + 'a {
+        use futures::StreamExt;
+
+        let check = Arc::new(check);
+        futures::stream::iter(frames).filter_map(move |index| {
+            let check = Arc::clone(&check);
+            async move {
+                if let Some(gate) = &self.entitlement {
+                    if !AlphaStreamProcessor::entitlement_allows(gate, &self.cache, &self.events) {
+                        return Some(Err(FormatError::Entitlement(gate.status().unwrap_err())));
+                    }
+                }
+                let decode_start = self.clock.now();
+                // Lock per frame so playback decoding can interleave with a long scan
+                let polystream = match self.format.lock().await.decode_frame(index).await {
+                    Ok(frame_data) => frame_data.polystream,
+                    Err(e) => return Some(Err(e)),
+                };
+                let process_start = self.clock.now();
+                let mode = self.processing_mode();
+                let (_channel_count, channel_sizes, channel_data) = AlphaStreamProcessor::parse_polystream(&polystream);
+                let rasterize = stats || matches!(mode, ProcessingMode::Bitmap | ProcessingMode::Both);
+                let mut bitmap = rasterize.then(|| AlphaStreamProcessor::rasterize_channels(&self.rasterizer, &channel_sizes, channel_data, self.channels.as_deref(), self.width, self.height, &self.raster_options));
+                let stats = bitmap.as_deref().map(|mask| MaskStats::from_mask(mask, self.width, self.height));
+                let extra = match check(index, stats.as_ref(), &channel_sizes, channel_data) {
+                    Ok(Some(extra)) => extra,
+                    Ok(None) => return None,
+                    Err(e) => return Some(Err(e)),
+                };
+                if let (Some(watermark), Some(mask)) = (&self.watermark, &mut bitmap) {
+                    watermark.embed(mask, index as usize);
+                }
+                if !matches!(mode, ProcessingMode::Bitmap | ProcessingMode::Both) {
+                    bitmap = None;
+                }
+                let bitmap = bitmap.map(|mask| self.packing.pack_owned(mask, self.width, self.height));
+                let triangle_strip = matches!(mode, ProcessingMode::TriangleStrip | ProcessingMode::Both)
+                    .then(|| AlphaStreamProcessor::triangulate_channels(&self.rasterizer, &channel_sizes, channel_data, self.channels.as_deref(), self.simplify_tolerance));
+                let trace = FrameTrace { decode: process_start - decode_start, process: self.clock.now() - process_start };
+                let is_empty = channel_sizes.iter().all(|&size| size == 0);
+                let output = FrameOutput { frame_index: index as usize, bitmap, triangle_strip, stats, trace: Some(trace), is_empty };
+                Some(Ok((DecodedFrame { polystream, output }, extra)))
+            }
+        })
+    }
+
+    /// Reload the source file after it was replaced or appended to
+    /// Works for local files and for HTTP sources read with range requests (those are also reloaded
+    /// automatically when a read finds the file changed on the server). The file is parsed again (reusing the decryption key for ASVR) and every cached frame is
+    /// compared against its freshly decoded polystream. Frames that changed or no longer exist are
+    /// evicted; all other cached frames stay valid, so playback does not have to start cold. Decode tasks
+    /// read the new file from the start of the comparison on, and keep running while it takes.
+    ///
+    /// # Returns
+    /// The indices of the frames that were evicted from the cache
+    pub async fn reload(&self) -> Result<Vec<usize>, FormatError> {
+        let reader = match (&self.source_path, &self.remote) {
+            (Some(path), _) => ReaderWrapper::File(tokio::fs::File::open(path).await?),
+            (None, Some(remote)) => remote.open().await?,
+            (None, None) => return Err(FormatError::InvalidFormat("Only local sources and range-read HTTP sources can be reloaded".to_string())),
+        };
+        AlphaStreamProcessor::reload_source(reader, &self.shards, &self.cache, self.stride, &self.events).await
+    }
+
+    async fn reload_source(reader: ReaderWrapper, shards: &ReaderShards, cache: &FrameCache, stride: usize, events: &EventQueue) -> Result<Vec<usize>, FormatError> {
+        // Swap every reader to the new index at once, so no decode task reads from the old one afterwards
+        let frame_count = {
+            let mut format = shards.primary().lock().await;
+            let mut others = Vec::with_capacity(shards.len() - 1);
+            for shard in &shards.formats[1..] {
+                others.push(shard.lock().await);
+            }
+            let mut new_format = format.reopen(reader).await?;
+            let frame_count = new_format.metadata().await?.frame_count as usize;
+            let mut reopened = Vec::with_capacity(others.len());
+            for _ in 0..others.len() {
+                reopened.push(shards.open_format(&new_format).await?);
+            }
+            *format = new_format;
+            for (shard, new_shard) in others.iter_mut().zip(reopened) {
+                **shard = new_shard;
+            }
+            frame_count
+        };
+
+        // Compare with the locks released: each frame takes a reader like a decode task does, so
+        // playback carries on while the cached frames (range reads for HTTP sources) are checked
+        let mut invalidated = Vec::new();
+        for cache_index in cache.ready_frames() {
+            let frame_index = cache_index * stride;
+            let unchanged = frame_index < frame_count
+                && match cache.get(cache_index) {
+                    Some(cached) => shards.acquire().await.decode_frame(frame_index as u32).await.is_ok_and(|fresh| cached.polystream == fresh.polystream),
+                    None => false,
+                };
+            if !unchanged && cache.invalidate_frame(cache_index) {
+                invalidated.push(frame_index);
+            }
+        }
+        events.push(ProcessorEvent::SourceReloaded(invalidated.len()));
+        Ok(invalidated)
+    }
+
+    /// Start watching the source file and reload it on every change
+    /// The parent directory is watched rather than the file itself, so that writers which replace
+    /// the file (write to a temp file, then rename) are picked up as well.
+    fn start_watching(&mut self) -> Result<(), FormatError> {
+        use notify::{EventKind, RecursiveMode, Watcher};
+
+        let path = self.source_path.clone()
+            .ok_or_else(|| FormatError::InvalidFormat("Only local sources can be watched".to_string()))?;
+        let source = std::path::Path::new(&path);
+        let file_name = source.file_name().map(|n| n.to_os_string())
+            .ok_or_else(|| FormatError::InvalidFormat(format!("Not a file path: {}", path)))?;
+        let dir = match source.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+            _ => std::path::PathBuf::from("."),
+        };
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            if let Ok(event) = res {
+                if !matches!(event.kind, EventKind::Access(_))
+                    && event.paths.iter().any(|p| p.file_name() == Some(file_name.as_os_str()))
+                {
+                    let _ = tx.send(());
+                }
+            }
+        }).map_err(|e| FormatError::InvalidFormat(e.to_string()))?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(|e| FormatError::InvalidFormat(e.to_string()))?;
+
+        let shards = Arc::clone(&self.shards);
+        let cache = Arc::clone(&self.cache);
+        let events = Arc::clone(&self.events);
+        let stride = self.stride;
+        let handle = self.runtime.as_ref().unwrap().spawn(async move {
+            while rx.recv().await.is_some() {
+                // Encoders touch the file several times per write; let them settle and coalesce the events
+                tokio::time::sleep(WATCH_DEBOUNCE).await;
+                while rx.try_recv().is_ok() {}
+                let reader = match tokio::fs::File::open(&path).await {
+                    Ok(file) => ReaderWrapper::File(file),
+                    Err(e) => {
+                        logging::log(LogLevel::Warn, format_args!("Reload of {} failed: {}", path, e));
+                        continue;
+                    }
+                };
+                match AlphaStreamProcessor::reload_source(reader, &shards, &cache, stride, &events).await {
+                    Ok(invalidated) => {
+                        if !invalidated.is_empty() {
+                            logging::log(LogLevel::Info, format_args!("Reloaded {}, invalidated {} cached frames", path, invalidated.len()));
+                        }
+                    }
+                    // Most likely a half-written file, the next event will retry
+                    Err(e) => logging::log(LogLevel::Warn, format_args!("Reload of {} failed: {}", path, e)),
+                }
+            }
+        });
+
+        self.watcher = Some(watcher);
+        self.reload_handle = Some(handle);
+        Ok(())
+    }
+
+    /// Record a frame request in the access log and re-check the entitlement if one is configured.
+    /// Returns false if playback is refused.
+    fn begin_access(&self, frame_index: usize) -> bool {
+        self.access_log.lock().unwrap().record(frame_index);
+        self.entitlement.as_ref().is_none_or(|gate| AlphaStreamProcessor::entitlement_allows(gate, &self.cache, &self.events))
+    }
+
+    /// Whether the entitlement permits decrypted output, checking again when the interval has passed.
+    /// On revocation the decrypted frames are dropped from the cache and EntitlementDenied is emitted.
+    fn entitlement_allows(gate: &EntitlementGate, cache: &FrameCache, events: &EventQueue) -> bool {
+        let (status, revoked) = gate.ensure();
+        if let (true, Err(e)) = (revoked, &status) {
+            logging::log(LogLevel::Warn, format_args!("Playback refused: {}", e));
+            cache.clear();
+            events.push(ProcessorEvent::EntitlementDenied);
+        }
+        status.is_ok()
+    }
+
+    /// Detect sequential access and trigger prefetching if needed
+    async fn maybe_trigger_prefetch(scheduler: &mut Scheduler, current_frame: usize) {
+        // Always trigger prefetch for the current frame
+        // println!("[alphastream] Prefetch always triggered for frame {}", current_frame);
+        scheduler.prefetch(current_frame);
+        // Optionally, you can keep the static for future use, but it's not needed anymore
+    }
+
+    /// Start background processing of scheduler tasks
+    /// This method spawns an async task that runs in the background, continuously processing scheduled frames.
+    /// Decode tasks are children of the loop in a JoinSet: when the processor shuts down (or is dropped),
+    /// the loop stops taking work, then cancels its unfinished decode tasks and waits for them.
+    /// A decode task that panics is logged and reported as a DecodeError event.
+    fn start_background_processing(&mut self) {
+        let (shutdown_tx, mut shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+        self.shutdown = Some(shutdown_tx);
+        let scheduler_clone = Arc::clone(&self.scheduler);
+        let shards_clone = Arc::clone(&self.shards);
+        let mode_clone = Arc::clone(&self.mode);
+        let simplify_tolerance = self.simplify_tolerance;
+        let channels: Option<Arc<[usize]>> = self.channels.as_deref().map(Arc::from);
+        let cache_clone = Arc::clone(&self.cache);
+        let events_clone = Arc::clone(&self.events);
+        let stride = self.stride;
+        let output_size_clone = Arc::clone(&self.output_size);
+        let watermark = self.watermark;
+        let traces_clone = Arc::clone(&self.traces);
+        let bytes_read_clone = Arc::clone(&self.bytes_read);
+        let clock_clone = self.clock.clone();
+        let remote_clone = self.remote.clone();
+        let entitlement_clone = self.entitlement.clone();
+        let raster_divisor_clone = Arc::clone(&self.raster_divisor);
+        let telemetry_clone = self.telemetry.clone();
+        let rasterizer_clone = self.rasterizer.clone();
+        let baked_clone = self.baked.clone();
+        let signal_clone = Arc::clone(&self.frame_signal);
+        let cancel = Arc::clone(&self.cancel);
+        let handle = self.runtime.as_ref().unwrap().spawn(async move {
+            let mut decode_tasks = tokio::task::JoinSet::new();
+            // Frame of every running decode task, to report panics
+            let mut task_frames = HashMap::new();
+            loop {
+                // Fill up to max_concurrent tasks
+                {
+                    let mut scheduler = scheduler_clone.lock().await;
+                    // let num_queued_tasks = scheduler.get_number_of_queued_tasks();
+                    // let num_active_tasks = scheduler.get_number_of_active_tasks();
+                    // let num_max_concurrent = scheduler.get_number_of_max_concurrent_tasks();
+                    // let num_running_tasks = decode_tasks.len();
+                    // println!("[alphastream debug] Background processing loop: {} queued tasks, {} active tasks, {} max concurrent, {} running tasks", num_queued_tasks, num_active_tasks, num_max_concurrent, num_running_tasks);
+                    while let Some(task) = scheduler.next_task() {
+                        let cache_index = task.frame_index;
+                        let frame_index = cache_index * stride;
+                        let shards = Arc::clone(&shards_clone);
+                        let cache = Arc::clone(&cache_clone);
+                        let channels = channels.clone();
+                        let events = Arc::clone(&events_clone);
+                        let traces = Arc::clone(&traces_clone);
+                        let bytes_read = Arc::clone(&bytes_read_clone);
+                        let clock = clock_clone.clone();
+                        let remote = remote_clone.clone();
+                        let entitlement = entitlement_clone.clone();
+                        let telemetry = telemetry_clone.clone();
+                        let rasterizer = rasterizer_clone.clone();
+                        let baked = baked_clone.clone();
+                        let signal = Arc::clone(&signal_clone);
+                        let raster_divisor = raster_divisor_clone.load(std::sync::atomic::Ordering::Relaxed);
+                        let mode_now = Arc::clone(&mode_clone);
+                        let mode = *mode_now.lock().unwrap();
+                        let output_size = Arc::clone(&output_size_clone);
+                        let (width, height, raster_options) = *output_size.lock().unwrap();
+                        // Capture generation when task is scheduled for stale task detection
+                        let task_generation = cache.generation();
+                        let frame_span = stage_span!("frame", frame_index);
+                        let decode_task = decode_tasks.spawn(async move {
+                            if entitlement.is_some_and(|gate| !AlphaStreamProcessor::entitlement_allows(&gate, &cache, &events)) {
+                                // Nothing was read, so there is no read latency to report
+                                return None;
+                            }
+                            // Held through decoding and rasterizing, released when the task ends
+                            let _decode_permit = crate::scheduler::global_decode_limit().acquire().await;
+                            let decode_start = clock.now();
+                            // A baked frame is served as it is, unless it lacks an output of the processing mode
+                            // Baked masks only fit the size they were baked at, which set_output_size may have changed
+                            let baked = baked.filter(|scene| (scene.width(), scene.height()) == (width, height));
+                            let baked_frame = baked.and_then(|scene| match scene.read_frame(frame_index as u32) {
+                                Ok(frame) => frame.filter(|frame| mode.is_covered_by(frame)),
+                                Err(e) => {
+                                    logging::log(LogLevel::Warn, format_args!("Baked frame {} unreadable, decoding it from the source: {}", frame_index, e));
+                                    None
+                                }
+                            });
+                            let is_baked = baked_frame.is_some();
+                            let frame_data = match baked_frame {
+                                Some(frame) => frame,
+                                None => {
+                                    let mut format = shards.acquire().instrument(stage_span!("wait_reader", frame_index)).await;
+                                    match format.decode_frame(frame_index as u32).await {
+                                        Ok(data) => {
+                                            let stored = format.frame_sizes().get(frame_index).copied().unwrap_or(0);
+                                            bytes_read.fetch_add(stored, std::sync::atomic::Ordering::Relaxed);
+                                            data
+                                        }
+                                        Err(FormatError::StreamChanged) => {
+                                            logging::log(LogLevel::Warn, format_args!("Source changed while decoding frame {}, refreshing", frame_index));
+                                            events.push(ProcessorEvent::DecodeError(frame_index));
+                                            signal.failed(cache_index, &cache);
+                                            drop(format);
+                                            if let Some(remote) = remote {
+                                                remote.refresh(&shards, &cache, stride, &events).await;
+                                            }
+                                            let elapsed = clock.now() - decode_start;
+                                            return Some((elapsed, elapsed));
+                                        }
+                                        Err(e) => {
+                                            logging::log(LogLevel::Error, format_args!("Error decoding frame {}: {}", frame_index, e));
+                                            events.push(ProcessorEvent::DecodeError(frame_index));
+                                            signal.failed(cache_index, &cache);
+                                            // Failed reads (e.g. timeouts) count towards the read latency too
+                                            let elapsed = clock.now() - decode_start;
+                                            return Some((elapsed, elapsed));
+                                        }
+                                    }
+                                }
+                            };
+                            let process_start = clock.now();
+                            let decode = process_start - decode_start;
+                            let FrameData { polystream, mut bitmap, mut triangle_strip } = frame_data;
+                            // Passthrough mode never looks inside the polystream
+                            if !is_baked && mode != ProcessingMode::PolystreamOnly {
+                                let (_channel_count, channel_sizes, channel_data) = AlphaStreamProcessor::parse_polystream(&polystream);
+                                if matches!(mode, ProcessingMode::Bitmap | ProcessingMode::Both) {
+                                    let (raster_width, raster_height, options) = AlphaStreamProcessor::reduced_raster(width, height, &raster_options, raster_divisor);
+                                    let mut mask = stage_span!("rasterize", frame_index).in_scope(|| {
+                                        AlphaStreamProcessor::rasterize_channels(&rasterizer, &channel_sizes, channel_data, channels.as_deref(), raster_width, raster_height, &options)
+                                    });
+                                    // Reduced masks are watermarked once scaled up, see output_mask
+                                    if let (Some(watermark), 1) = (&watermark, raster_divisor) {
+                                        watermark.embed(&mut mask, frame_index);
+                                    }
+                                    bitmap = Some(mask);
+                                }
+                                if matches!(mode, ProcessingMode::TriangleStrip | ProcessingMode::Both) {
+                                    triangle_strip = Some(stage_span!("triangulate", frame_index).in_scope(|| {
+                                        AlphaStreamProcessor::triangulate_channels(&rasterizer, &channel_sizes, channel_data, channels.as_deref(), simplify_tolerance)
+                                    }));
+                                }
+                            }
+                            let processed_frame = FrameData {
+                                polystream,
+                                // clone here?
+                                bitmap,
+                                triangle_strip,
+                            };
+                            
+                            // Held until the frame is cached, so set_output_size evicts it if the size changes after the check
+                            let size_now = output_size.lock().unwrap();
+                            let resized = processed_frame.bitmap.is_some() && (size_now.0, size_now.1) != (width, height);
+                            // Check generation before inserting - discard stale results
+                            // This handles the case where a seek occurred while this task was in-flight
+                            if cache.generation() == task_generation && (resized || !mode_now.lock().unwrap().is_covered_by(&processed_frame)) {
+                                // The processing mode or output size changed while this task ran; decode the frame again when asked for
+                                cache.invalidate_frame(cache_index);
+                            } else if cache.generation() != task_generation {
+                                cache.record_dropped();
+                            } else {
+                                // insert() also checks is_in_range() as a secondary guard
+                                if stage_span!("cache_insert", frame_index).in_scope(|| cache.insert(cache_index, processed_frame)) {
+                                    let mut traces = traces.lock().unwrap();
+                                    if traces.len() >= cache.capacity() {
+                                        traces.retain(|&index, _| cache.is_in_range(index));
+                                    }
+                                    let process = clock.now() - process_start;
+                                    traces.insert(cache_index, FrameTrace { decode, process });
+                                    drop(traces);
+                                    events.push(ProcessorEvent::FrameReady(frame_index));
+                                    telemetry.export(TelemetryEvent::FrameDecoded { frame_index, decode, process });
+                                } else {
+                                    cache.record_dropped();
+                                }
+                            }
+                            drop(size_now);
+                            // Waiters re-check even if the frame was discarded as stale, and schedule it again
+                            signal.ready(cache_index);
+                            logging::log(LogLevel::Debug, format_args!("Frame {} processed [thread {:?} task gen {}]", frame_index, std::thread::current().id(), task_generation));
+                            // Read latency, and the time the task kept a worker busy for the decode budget
+                            Some((decode, clock.now() - decode_start))
+                        }.instrument(frame_span));
+                        task_frames.insert(decode_task.id(), frame_index);
+                    }
+                }
+                // Poll for completed tasks
+                let completed = tokio::select! {
+                    biased;
+                    // Sent on shutdown, or the sender was dropped with the processor
+                    _ = &mut shutdown_rx => break,
+                    _ = cancel.notified() => {
+                        // Aborted tasks still complete below; free their slots so the frames can be requested again
+                        for &frame_index in task_frames.values() {
+                            if cache_clone.get_slot_state(frame_index / stride).is_some_and(|slot| slot.is_in_progress()) {
+                                cache_clone.invalidate_frame(frame_index / stride);
+                            }
+                        }
+                        decode_tasks.abort_all();
+                        continue;
+                    }
+                    Some(completed) = decode_tasks.join_next_with_id() => completed,
+                    // No running tasks, sleep briefly
+                    _ = tokio::time::sleep(tokio::time::Duration::from_millis(1)), if decode_tasks.is_empty() => continue,
+                };
+                let timing = match completed {
+                    Ok((id, timing)) => {
+                        task_frames.remove(&id);
+                        timing
+                    }
+                    Err(e) => {
+                        let frame_index = task_frames.remove(&e.id()).unwrap_or_default();
+                        if e.is_panic() {
+                            logging::log(LogLevel::Error, format_args!("Decode task for frame {} panicked: {}", frame_index, panic_message(e.into_panic())));
+                            events_clone.push(ProcessorEvent::DecodeError(frame_index));
+                            signal_clone.failed(frame_index / stride, &cache_clone);
+                        }
+                        None
+                    }
+                };
+                // let wait_start = std::time::Instant::now();
+                let mut scheduler = scheduler_clone.lock().await;
+                scheduler.complete_task();
+                if let Some((read_latency, busy)) = timing {
+                    scheduler.record_read_latency(read_latency);
+                    scheduler.record_decode_time(busy);
+                }
+                // let wait_duration = wait_start.elapsed();
+                // println!("[alphastream debug] Completed tasks in {} ms", wait_duration.as_millis());
+            }
+            // Cancel the decode tasks still running and wait until they are gone
+            decode_tasks.shutdown().await;
+        });
+
+        self.background_handle = Some(handle);
+    }
+
+    /// Drop all scheduled frames and abort the decode tasks that are running; their frames are not
+    /// cached and can be requested again. Background processing carries on with frames requested
+    /// from then on.
+    pub async fn cancel_all(&self) {
+        self.scheduler.lock().await.clear_queue();
+        self.cancel.notify_one();
+    }
+
+    /// Stop background processing: no new frames are decoded, the scheduled ones are dropped, the
+    /// source is no longer watched and the decode tasks still running are cancelled and awaited
+    /// before this returns. Frames already in the cache can still be read.
+    /// Dropping the processor does the same without waiting.
+    pub async fn shutdown(&mut self) {
+        self.shutdown.take();
+        self.watcher.take();
+        if let Some(handle) = self.reload_handle.take() {
+            handle.abort();
+            let _ = handle.await;
+        }
+        if let Some(handle) = self.background_handle.take() {
+            if let Err(e) = handle.await {
+                if e.is_panic() {
+                    logging::log(LogLevel::Error, format_args!("Background processing panicked: {}", panic_message(e.into_panic())));
+                }
+            }
+        }
+        self.scheduler.lock().await.clear_queue();
+    }
+
+    /// Like shutdown, then stop the processor's runtime and wait until its threads have exited, so no
+    /// code of this library runs on behalf of the processor afterwards (e.g. before the host unloads
+    /// it). Frames already in the cache can still be read. Blocks, so it must not be called from
+    /// async code. Waits at most 5 seconds for the threads; returns false if some are still
+    /// running then, true once they have all exited (also when the processor was closed before).
+    pub fn close(&mut self) -> bool {
+        let Some(runtime) = self.runtime.take() else {
+            return true;
+        };
+        runtime.block_on(self.shutdown());
+        runtime.shutdown_timeout(SHUTDOWN_TIMEOUT)
+    }
+
+    // /// Process pending tasks (decode frames)
+    // pub async fn process_tasks(&mut self) -> Result<(), FormatError> {
+    //     let mut scheduler = self.scheduler.lock().await;
+    //     while let Some(task) = scheduler.next_task() {
+    //         let format_clone = Arc::clone(&self.format);
+    //         let frame_index = task.frame_index;
+    //         let handle = self.runtime.as_ref().unwrap().spawn_blocking(move || {
+    //             let mut format = format_clone.blocking_lock();
+    //             format.decode_frame(frame_index as u32)
+    //         });
+    //         let result = handle.await.map_err(|e| FormatError::InvalidFormat(format!("Join error: {}", e)))?;
+    //         let frame_data = result?;
+    //         self.cache.insert(frame_index, frame_data);
+    //         scheduler.complete_task();
+    //     }
+    //     Ok(())
+    // }
+
+}
+
+impl Drop for AlphaStreamProcessor {
+    /// Cleanup when the processor is destroyed
+    /// This ensures background tasks are stopped and resources are properly freed.
+    /// Important for preventing resource leaks in long-running programs.
+    fn drop(&mut self) {
+        self.shutdown.take(); // Stop the background processing task, which cancels its decode tasks
+        let background = self.background_handle.take();
+        self.watcher.take(); // Stop file change events before the reload task goes away
+        if let Some(handle) = self.reload_handle.take() {
+            handle.abort();
+        }
+        if let Some(runtime) = self.runtime.take() {
+            // Clean up async runtime in separate thread, once the decode tasks are cancelled
+            std::thread::spawn(move || {
+                if let Some(handle) = background {
+                    // The timer has to be created inside the runtime, this thread has no runtime context
+                    let _ = runtime.block_on(async { tokio::time::timeout(SHUTDOWN_TIMEOUT, handle).await });
+                }
+                drop(runtime)
+            });
+        }
+    }
+}
+
+/// How `retime` fills target frames that fall between two source frames
+#[cfg(any(test, feature = "unstable"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RetimeMethod {
+    /// Repeat the last source frame at or before the target time (frames are duplicated or dropped)
+    Nearest,
+    /// Blend the outlines of the two neighbouring source frames. Frames whose channels do not have
+    /// the same layout (channel count and point count per channel) fall back to `Nearest`.
+    Interpolate,
+}
+
+/// Summary of a `retime` run
+#[cfg(any(test, feature = "unstable"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetimeReport {
+    pub source_frames: u32,
+    pub target_frames: u32,
+    /// Target frames blended from two source frames
+    pub interpolated: u32,
+    /// Source frames that could not be read; the target frames made from them are empty
+    pub failed: Vec<FailedFrame>,
+}
+
+/// Conform an ASVP archive (or the first mask track of a container) authored at `source_fps` to
+/// `target_fps`, e.g. masks authored at 59.94 for a 50fps broadcast, and write it to `output` as ASVP.
+/// The duration is kept: the output has `round(frames * target_fps / source_fps)` frames.
+/// A source frame that fails to decode does not end the run: it is listed in the report and stands in as
+/// an empty frame, so the other frames keep their timing.
+#[cfg(any(test, feature = "unstable"))]
+pub async fn retime(input: &str, output: &std::path::Path, source_fps: f64, target_fps: f64, method: RetimeMethod) -> Result<RetimeReport, FormatError> {
+    for fps in [source_fps, target_fps] {
+        if !fps.is_finite() || fps <= 0.0 {
+            return Err(FormatError::InvalidFormat(format!("Invalid frame rate {}", fps)));
+        }
+    }
+    let reader = AlphaStreamProcessorBuilder::new().open_reader(&BuildSource::Uri(input)).await?;
+    let mut format = ASVPFormat::new(reader).await?;
+    let source_frames = format.metadata().await?.frame_count;
+    if source_frames == 0 {
+        return Err(FormatError::InvalidFormat("Cannot retime an empty archive".to_string()));
+    }
+    let target_frames = ((source_frames as f64 * target_fps / source_fps).round() as u32).max(1);
+
+    let mut writer = crate::formats::ASVPWriter::new(std::io::BufWriter::new(std::fs::File::create(output)?));
+    let mut interpolated = 0;
+    let mut failed = Vec::new();
+    // Target frames advance through the source, so at most two decoded frames are kept
+    let mut decoded: Vec<(u32, Vec<u8>)> = Vec::with_capacity(2);
+    for target in 0..target_frames {
+        // A target frame exactly on a source frame must not round down to the previous one
+        let position = target as f64 * source_fps / target_fps + 1e-9;
+        let index = (position.floor() as u32).min(source_frames - 1);
+        let fraction = position - index as f64;
+        let blend = method == RetimeMethod::Interpolate && fraction > 1e-6 && index + 1 < source_frames;
+
+        decoded.retain(|(i, _)| *i >= index);
+        for wanted in index..index + 1 + u32::from(blend) {
+            if !decoded.iter().any(|(i, _)| *i == wanted) {
+                let polystream = match format.decode_frame(wanted).await {
+                    Ok(frame_data) => frame_data.polystream,
+                    Err(e) => {
+                        logging::log(LogLevel::Warn, format_args!("Retime: source frame {} failed: {}", wanted, e));
+                        failed.push(FailedFrame { frame_index: wanted, kind: FrameErrorKind::from(&e), message: e.to_string() });
+                        // No channels
+                        0u32.to_le_bytes().to_vec()
+                    }
+                };
+                decoded.push((wanted, polystream));
+            }
+        }
+        let frame = |wanted: u32| decoded.iter().find(|(i, _)| *i == wanted).map(|(_, polystream)| polystream.as_slice()).unwrap();
+        let blended = if blend { interpolate_polystreams(frame(index), frame(index + 1), fraction as f32) } else { None };
+        interpolated += u32::from(blended.is_some());
+        let polystream = blended.unwrap_or_else(|| frame(index).to_vec());
+        writer.add_frame(FrameData { polystream, bitmap: None, triangle_strip: None });
+    }
+    let mut file = writer.write_all()?;
+    std::io::Write::flush(&mut file)?;
+    Ok(RetimeReport { source_frames, target_frames, interpolated, failed })
+}
+
+/// Parameters the key of an ASVR file is derived from
+#[cfg(any(test, feature = "unstable"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SceneCredentials {
+    pub scene_id: u32,
+    pub version: Vec<u8>,
+    pub base_url: Vec<u8>,
+}
+
+#[cfg(any(test, feature = "unstable"))]
+impl SceneCredentials {
+    pub fn new(scene_id: u32, version: &[u8], base_url: &[u8]) -> Self {
+        Self { scene_id, version: version.to_vec(), base_url: base_url.to_vec() }
+    }
+}
+
+/// Merge ASVR files into one ASVR file encrypted under `credentials`, e.g. to consolidate the scenes
+/// of a release for distribution. Frames are decrypted with their input's credentials and encrypted
+/// again with the key_id of their new position, in input order.
+///
+/// # Returns
+/// The frames of each input in the output
+#[cfg(any(test, feature = "unstable"))]
+pub async fn concat_asvr(inputs: &[(&str, SceneCredentials)], output: &std::path::Path, credentials: &SceneCredentials) -> Result<Vec<std::ops::Range<u32>>, FormatError> {
+    let mut writer = crate::formats::ASVRWriter::new(
+        std::io::BufWriter::new(std::fs::File::create(output)?),
+        credentials.scene_id, &credentials.version, &credentials.base_url,
+    )?;
+    let mut ranges = Vec::with_capacity(inputs.len());
+    let mut next = 0;
+    for (uri, input) in inputs {
+        let reader = AlphaStreamProcessorBuilder::new().open_reader(&BuildSource::Uri(uri)).await?;
+        let mut format = ASVRFormat::new(reader, input.scene_id, &input.version, &input.base_url).await?;
+        let frame_count = format.metadata().await?.frame_count;
+        for index in 0..frame_count {
+            let polystream = format.decode_frame(index).await?.polystream;
+            writer.add_frame(FrameData { polystream, bitmap: None, triangle_strip: None });
+        }
+        ranges.push(next..next + frame_count);
+        next += frame_count;
+    }
+    let mut file = writer.write_all()?;
+    std::io::Write::flush(&mut file)?;
+    Ok(ranges)
+}
+
+/// Blend two polystreams point by point, `t` = 0 gives `a` and 1 gives `b`.
+/// None if the channel layouts differ.
+#[cfg(any(test, feature = "unstable"))]
+fn interpolate_polystreams(a: &[u8], b: &[u8], t: f32) -> Option<Vec<u8>> {
+    let (_, sizes_a, data_a) = AlphaStreamProcessor::parse_polystream(a);
+    let (_, sizes_b, data_b) = AlphaStreamProcessor::parse_polystream(b);
+    if sizes_a != sizes_b || data_a.len() != data_b.len() {
+        return None;
+    }
+    let lerp = |x: f32, y: f32| x + (y - x) * t;
+    let start = |channel: &[u8], axis: usize| u16::from_le_bytes([channel[axis * 2], channel[axis * 2 + 1]]) as f32;
+    let mut out = a[..a.len() - data_a.len()].to_vec();
+    let mut offset = 0;
+    for &size in &sizes_a {
+        let range = offset..offset + size as usize;
+        offset += size as usize;
+        let (channel_a, channel_b) = (&data_a[range.clone()], &data_b[range]);
+        if channel_a.len() < 4 {
+            out.extend_from_slice(channel_a);
+            continue;
+        }
+        // Blend absolute positions and encode the deltas between them, so rounding does not accumulate
+        let (mut pos_a, mut pos_b) = ([start(channel_a, 0), start(channel_a, 1)], [start(channel_b, 0), start(channel_b, 1)]);
+        let mut prev = [lerp(pos_a[0], pos_b[0]).round(), lerp(pos_a[1], pos_b[1]).round()];
+        for value in prev {
+            out.extend_from_slice(&(value as u16).to_le_bytes());
+        }
+        let (deltas_a, deltas_b) = (channel_a[4..].chunks_exact(2), channel_b[4..].chunks_exact(2));
+        let remainder = deltas_a.remainder();
+        for (delta_a, delta_b) in deltas_a.zip(deltas_b) {
+            for axis in 0..2 {
+                pos_a[axis] += delta_a[axis] as i8 as f32;
+                pos_b[axis] += delta_b[axis] as i8 as f32;
+                // A blend of two i8 steps stays within i8, the clamp only absorbs rounding
+                let delta = (lerp(pos_a[axis], pos_b[axis]).round() - prev[axis]).clamp(-128.0, 127.0);
+                prev[axis] += delta;
+                out.push(delta as i8 as u8);
+            }
+        }
+        out.extend_from_slice(remainder);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testlib::create_test_asvp;
+    use crate::api::AlphaStreamProcessorBuilder;
+    use crate::AlphaStreamProcessor;
+    use crate::ProcessingMode;
+
+    #[test]
+    fn test_builder_defaults_and_overrides() {
+        let builder = AlphaStreamProcessorBuilder::new();
+        assert_eq!(builder.runtime_threads, 0);
+        assert_eq!(builder.timeout_seconds, 30);
+        assert_eq!(builder.cache_capacity, 512);
+        assert_eq!(builder.prefetch_window, 16);
+        assert_eq!(builder.processing_mode, ProcessingMode::Bitmap);
+        assert!(!builder.watch_source);
+        assert_eq!(builder.simplify_tolerance, 0.0);
+        assert!(!builder.deterministic);
+        assert_eq!(builder.channels, None);
+        assert_eq!(builder.clone().channels(&[2, 0, 2]).channels, Some(vec![0, 2]));
+        assert_eq!(builder.stride, 1);
+        assert_eq!(builder.clone().stride(0).stride, 1);
+        assert_eq!(builder.decode_budget(), None);
+        let budget_ms = |budget_ms| builder.clone().decode_budget_ms(budget_ms).decode_budget().unwrap().as_secs_f64() * 1000.0;
+        assert!((budget_ms(4.0) - 4.0).abs() < 1e-3);
+        // At most one frame interval
+        assert!((budget_ms(100.0) - 16.667).abs() < 1e-3);
+        assert_eq!(builder.effective_runtime_threads(), 0);
+
+        let builder = builder
+            .runtime_threads(32)
+            .timeout_seconds(120)
+            .cache_capacity(1024)
+            .prefetch_window(25)
+            .processing_mode(ProcessingMode::Both);
+        assert_eq!(builder.runtime_threads, 32);
+        assert_eq!(builder.timeout_seconds, 120);
+        assert_eq!(builder.cache_capacity, 1024);
+        assert_eq!(builder.prefetch_window, 25);
+        assert_eq!(builder.processing_mode, ProcessingMode::Both);
+
+        let builder = AlphaStreamProcessorBuilder::new().deterministic(true);
+        assert_eq!(builder.effective_runtime_threads(), super::DETERMINISTIC_WORKER_THREADS);
+        assert_eq!(builder.runtime_threads(3).effective_runtime_threads(), 3);
+    }
+
+    #[test]
+    fn test_builder_json_roundtrip() {
+        let builder = AlphaStreamProcessorBuilder::new()
+            .cache_capacity(64)
+            .processing_mode(ProcessingMode::TriangleStrip)
+            .channels(&[2, 0])
+            .stride(3)
+            .draw_outline(true);
+        assert_eq!(AlphaStreamProcessorBuilder::from_json(&builder.to_json()).unwrap(), builder);
+
+        // Partial presets use defaults for missing fields and are clamped like the setters
+        let preset = AlphaStreamProcessorBuilder::from_json(r#"{"cache_capacity": 100000, "processing_mode": "Both", "channels": [1, 1]}"#).unwrap();
+        assert_eq!(preset.cache_capacity, 4096);
+        assert_eq!(preset.processing_mode, ProcessingMode::Both);
+        assert_eq!(preset.channels, Some(vec![1]));
+        assert_eq!(preset.timeout_seconds, 30);
+        assert!(AlphaStreamProcessorBuilder::from_json(r#"{"processing_mode": "Sepia"}"#).is_err());
+    }
+
+    #[test]
+    fn test_builder_environment_defaults() {
+        let file = "cache_capacity = 256\nruntime_threads = 2\nlog_level = \"debug\"\n";
+        let env = |name: &str| match name {
+            "ALPHASTREAM_THREADS" => Some("4".to_string()),
+            "ALPHASTREAM_CACHE_DIR" => Some("/var/cache/alphastream".to_string()),
+            "ALPHASTREAM_CACHE_KEY" => Some("ab".repeat(32)),
+            _ => None,
+        };
+        let builder = AlphaStreamProcessorBuilder::new().prefetch_window(8).apply_defaults(Some(file), env).unwrap();
+        assert_eq!(builder.cache_capacity, 256);
+        // Environment variables take precedence over the file
+        assert_eq!(builder.runtime_threads, 4);
+        assert_eq!(builder.log_level, crate::logging::LogLevel::Debug);
+        assert_eq!(builder.cache_dir, Some(std::path::PathBuf::from("/var/cache/alphastream")));
+        assert_eq!(builder.cache_key, Some(crate::store::CacheKey::new([0xab; 32])));
+        // The key never leaves the process through config dumps or logs
+        assert!(!builder.to_json().contains("abab") && !format!("{:?}", builder).contains("171"));
+        // Settings the environment does not mention are kept
+        assert_eq!(builder.prefetch_window, 8);
+
+        let no_env = |_: &str| None;
+        assert_eq!(AlphaStreamProcessorBuilder::new().apply_defaults(None, no_env).unwrap(), AlphaStreamProcessorBuilder::new());
+        assert!(AlphaStreamProcessorBuilder::new().apply_defaults(Some("cache_size = 1"), no_env).is_err());
+        assert!(AlphaStreamProcessorBuilder::new().apply_defaults(Some("cache_key = \"00\""), no_env).is_err());
+        let bad_env = |name: &str| (name == "ALPHASTREAM_CACHE_CAPACITY").then(|| "lots".to_string());
+        assert!(AlphaStreamProcessorBuilder::new().apply_defaults(None, bad_env).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_builder_build_asvp_and_processing() {
+        let test_file = create_test_asvp(1).unwrap();
+        let builder = AlphaStreamProcessorBuilder::new()
+            .runtime_threads(4)
+            .cache_capacity(16)
+            .prefetch_window(2)
+            .processing_mode(ProcessingMode::Both);
+        let processor = builder.build_asvp(test_file.path().to_str().unwrap(), 16, 16).await.unwrap();
+        let metadata = processor.metadata().await.unwrap();
+        assert_eq!(metadata.frame_count, 1);
+        let _ = processor.get_frame(0, 16, 16).await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        let frame = processor.get_frame(0, 16, 16).await;
+        assert!(frame.is_some());
+    }
+    // 
+    // #[test]
+    // fn test_builder_build_asvr_and_metadata() {
+    //     use crate::testlib::create_test_asvr;
+    //     let test_file = create_test_asvr();
+    //     let builder = AlphaStreamProcessorBuilder::new()
+    //         .runtime_threads(2)
+    //         .cache_capacity(8)
+    //         .prefetch_window(1)
+    //         .processing_mode(ProcessingMode::Bitmap);
+    //     // Use dummy scene_id, version, base_url for test
+    //     let scene_id = 42;
+    //     let version = b"1.5.0";
+    //     let base_url = b"test.asvr";
+    //     let processor = builder.build_asvr(
+    //         test_file.path().to_str().unwrap(),
+    //         scene_id,
+    //         version,
+    //         base_url,
+    //         8,
+    //         8,
+    //     ).unwrap();
+    //     let rt = tokio::runtime::Runtime::new().unwrap();
+    //     let metadata = rt.block_on(async { processor.metadata().await.unwrap() });
+    //     assert_eq!(metadata.frame_count, 1);
+    // }
+
+    #[tokio::test]
+    async fn test_asvp_processor() {
+        let test_file = create_test_asvp(1).unwrap();
+        let processor = AlphaStreamProcessor::new_asvp(
+            test_file.path().to_str().unwrap(),
+            16,
+            16,
+            ProcessingMode::Both,
+        ).await.unwrap();
+
+        let metadata = processor.metadata().await.unwrap();
+        assert_eq!(metadata.frame_count, 1);
+
+        // trigger processing
+        let _ = processor.get_frame(0, 16, 16).await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+        let frame = processor.get_frame(0, 16, 16).await;
+        assert!(frame.is_some()); // Accept only Some, do not unwrap None or Err
+
+        let _ = processor.get_triangle_strip_vertices(0).await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+        let vertices = processor.get_triangle_strip_vertices(0).await.unwrap();
+        assert_eq!(vertices.len(), 174);
+    }
+
+    #[tokio::test]
+    async fn test_request_frame() {
+        let test_file = create_test_asvp(1).unwrap();
+        let processor = AlphaStreamProcessor::new_asvp(
+            test_file.path().to_str().unwrap(),
+            16,
+            16,
+            ProcessingMode::Bitmap,
+        ).await.unwrap();
+
+        // Request a frame
+        processor.request_frame(0).await.unwrap();
+
+        // wait for processing to complete
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        
+        // Should now be in cache
+        let frame = processor.get_frame(0, 16, 16).await;
+        // Accept None, do not panic
+        if let Some(data) = frame {
+            assert_eq!(data.len(), 256);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_processing_modes() {
+        let test_file = create_test_asvp(1).unwrap();
+        let processor = AlphaStreamProcessor::new_asvp(
+            test_file.path().to_str().unwrap(),
+            16,
+            16,
+            ProcessingMode::Bitmap,
+        ).await.unwrap();
+
+        // Manually insert frame data to test modes
+        let frame_data = crate::formats::FrameData {
+            polystream: vec![1, 0, 0, 0, 0], // minimal polystream
+            bitmap: Some(vec![255; 256]),
+            triangle_strip: Some(vec![0.0; 12]),
+        };
+        processor.cache.insert(0, frame_data);
+
+        // Test bitmap mode
+        let bitmap = processor.get_frame(0, 16, 16).await.unwrap();
+        assert_eq!(bitmap.len(), 256);
+
+        // Test triangle strip mode
+        let vertices = processor.get_triangle_strip_vertices(0).await.unwrap();
+        assert_eq!(vertices.len(), 12);
+    }
+
+    /// Single-channel polystream of 64 bytes all set to `fill`
+    fn polystream(fill: u8) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&64u32.to_le_bytes());
+        data.extend_from_slice(&[fill; 64]);
+        data
+    }
+
+    fn write_asvp(path: &std::path::Path, fills: &[u8]) {
+        let mut writer = crate::formats::ASVPWriter::new(std::fs::File::create(path).unwrap());
+        for &fill in fills {
+            writer.add_frame(crate::formats::FrameData { polystream: polystream(fill), bitmap: None, triangle_strip: None });
+        }
+        writer.write_all().unwrap();
+    }
+
+    fn cache_frame(processor: &AlphaStreamProcessor, frame_index: usize, fill: u8) {
+        let frame_data = crate::formats::FrameData { polystream: polystream(fill), bitmap: Some(vec![0; 256]), triangle_strip: None };
+        processor.cache.insert(frame_index, frame_data);
+    }
+
+    #[tokio::test]
+    async fn test_reload_invalidates_only_changed_frames() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("source.asvp");
+        write_asvp(&path, &[1, 2, 3]);
+        let processor = AlphaStreamProcessorBuilder::new()
+            .build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
+        for (i, fill) in [1, 2, 3].into_iter().enumerate() {
+            cache_frame(&processor, i, fill);
+        }
+
+        // Frame 1 re-encoded, frame 3 appended
+        write_asvp(&path, &[1, 9, 3, 4]);
+        let invalidated = processor.reload().await.unwrap();
+        assert_eq!(invalidated, vec![1]);
+        assert_eq!(processor.metadata().await.unwrap().frame_count, 4);
+        assert!(processor.cache.contains(&0));
+        assert!(!processor.cache.contains(&1));
+        assert!(processor.cache.contains(&2));
+
+        // Truncation drops frames past the new end
+        write_asvp(&path, &[1, 9]);
+        let invalidated = processor.reload().await.unwrap();
+        assert_eq!(invalidated, vec![2]);
+        assert_eq!(processor.metadata().await.unwrap().frame_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_reader_shards() {
+        use crate::formats::{ASFormat, ASVPFormat};
+        use crate::store::{FrameStore, STORE_SCHEME};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("source.asvp");
+        write_asvp(&path, &[1, 2, 3, 4, 5, 6, 7, 8]);
+        let processor = AlphaStreamProcessorBuilder::new()
+            .processing_mode(ProcessingMode::PolystreamOnly)
+            .reader_shards(4)
+            .build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
+        assert_eq!(processor.reader_shards(), 4);
+        assert_eq!(processor.config().reader_shards, 4);
+        assert!(processor.get_polystream(0).await.is_none());
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        for (frame, fill) in (1..=8).enumerate() {
+            assert_eq!(processor.get_polystream(frame).await.unwrap(), polystream(fill), "frame {}", frame);
+        }
+
+        // A reload replaces the index of every shard
+        write_asvp(&path, &[1, 9]);
+        processor.reload().await.unwrap();
+        for shard in &processor.shards.formats {
+            assert_eq!(shard.lock().await.metadata().await.unwrap().frame_count, 2);
+        }
+
+        // A store scene is served from memory and cannot be opened again
+        let store = FrameStore::open(dir.path().join("store")).unwrap();
+        store.import("scene", &mut ASVPFormat::new(tokio::fs::File::open(&path).await.unwrap()).await.unwrap()).await.unwrap();
+        let uri = format!("{}{}#scene", STORE_SCHEME, dir.path().join("store").display());
+        let processor = AlphaStreamProcessorBuilder::new().reader_shards(4).build_asvp(&uri, 16, 16).await.unwrap();
+        assert_eq!(processor.reader_shards(), 1);
+    }
+
+    #[test]
+    fn test_cancel_all_and_close() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cancel.asvp");
+        write_asvp(&path, &[1; 32]);
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let mut processor = rt.block_on(AlphaStreamProcessor::new_asvp(path.to_str().unwrap(), 16, 16, ProcessingMode::Bitmap)).unwrap();
+        rt.block_on(async {
+            processor.request_range(0..32, crate::Priority::Low).await.unwrap();
+            processor.cancel_all().await;
+            let scheduler = processor.scheduler.lock().await;
+            assert_eq!(scheduler.get_number_of_queued_tasks() + scheduler.get_number_of_queued_range_frames(), 0);
+            drop(scheduler);
+            // Aborted frames are requested again like any other
+            for frame in [0, 20] {
+                assert!(processor.get_frame_wait(frame, std::time::Duration::from_secs(5)).await.is_ok(), "frame {}", frame);
+            }
+        });
+
+        processor.close();
+        assert!(processor.runtime.is_none());
+        assert!(processor.background_handle.is_none() && processor.reload_handle.is_none());
+        assert_eq!(processor.alive_tasks(), 0);
+        // Cached frames stay readable
+        assert!(rt.block_on(processor.get_frame(20, 16, 16)).is_some());
+        processor.close();
+    }
+
+    #[tokio::test]
+    async fn test_worker_panic_is_reported_and_shutdown_awaits_tasks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("broken.asvp");
+        // Frame 0 claims a channel its polystream does not have, which the rasterizer trips over
+        let mut writer = crate::formats::ASVPWriter::new(std::fs::File::create(&path).unwrap());
+        writer.add_frame(crate::formats::FrameData { polystream: 1u32.to_le_bytes().to_vec(), bitmap: None, triangle_strip: None });
+        writer.add_frame(crate::formats::FrameData { polystream: polystream(2), bitmap: None, triangle_strip: None });
+        writer.write_all().unwrap();
+
+        let mut processor = AlphaStreamProcessor::new_asvp(path.to_str().unwrap(), 16, 16, ProcessingMode::Bitmap).await.unwrap();
+        processor.enable_events(true);
+        assert!(processor.get_frame(0, 16, 16).await.is_none());
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        assert!(processor.poll_events().contains(&super::ProcessorEvent::DecodeError(0)));
+        // The loop survives the panic
+        assert!(processor.get_frame(1, 16, 16).await.is_some());
+
+        processor.shutdown().await;
+        // The runtime releases a finished task a moment after its handle sees it complete
+        for _ in 0..100 {
+            if processor.alive_tasks() == 0 {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(processor.alive_tasks(), 0);
+        assert!(processor.get_frame(1, 16, 16).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_local_set_execution() {
+        use crate::runtime::ExecutionMode;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("local.asvp");
+        write_asvp(&path, &[1, 2, 3]);
+        let processor = AlphaStreamProcessorBuilder::new()
+            .execution_mode(ExecutionMode::LocalSet)
+            .processing_mode(ProcessingMode::PolystreamOnly)
+            .build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
+        assert_eq!(processor.runtime.as_ref().unwrap().execution_mode(), ExecutionMode::LocalSet);
+        assert_eq!(processor.config().execution_mode, ExecutionMode::LocalSet);
+        assert!(processor.get_polystream(1).await.is_none());
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        assert_eq!(processor.get_polystream(1).await.unwrap(), polystream(2));
+        assert_eq!(processor.get_polystream(2).await.unwrap(), polystream(3));
+    }
+
+    #[tokio::test]
+    async fn test_output_packing() {
+        use crate::rasterizer::{OutputPacking, PixelFormat};
+        use crate::stats::MaskStats;
+
+        let file = create_test_asvp(1).unwrap();
+        let packing = OutputPacking::new(PixelFormat::La8).row_alignment(64).swap_bytes(true);
+        let mut processor = AlphaStreamProcessorBuilder::new()
+            .output_packing(packing)
+            .build_asvp(file.path().to_str().unwrap(), 20, 16).await.unwrap();
+        assert_eq!(processor.config().output_packing, packing);
+        assert!(processor.get_frame(0, 20, 16).await.is_none());
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        let frame = processor.get_frame(0, 20, 16).await.unwrap();
+        assert_eq!(frame.len(), 64 * 16);
+        // The cache keeps R8, so statistics are computed from the mask itself
+        let cached = processor.cache.get(0).unwrap().bitmap.unwrap();
+        assert_eq!(frame, packing.pack(&cached, 20, 16));
+        let output = processor.get_frame_output(0).await.unwrap();
+        assert_eq!(output.stats.unwrap(), MaskStats::from_mask(&cached, 20, 16));
+        assert_eq!(output.bitmap.unwrap(), frame);
+
+        processor.set_output_packing(OutputPacking::default());
+        assert_eq!(processor.get_frame(0, 20, 16).await.unwrap(), cached);
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_old_index_on_parse_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("source.asvp");
+        write_asvp(&path, &[1, 2]);
+        let processor = AlphaStreamProcessorBuilder::new()
+            .build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
+        cache_frame(&processor, 0, 1);
+
+        std::fs::write(&path, b"half").unwrap();
+        assert!(processor.reload().await.is_err());
+        assert_eq!(processor.metadata().await.unwrap().frame_count, 2);
+        assert!(processor.cache.contains(&0));
+    }
+
+    #[tokio::test]
+    async fn test_remote_source_refreshes_when_changed_on_server() {
+        use crate::api::ProcessorEvent;
+        // Single channel of incompressible data, so frame 2 lies beyond the first range request
+        let noise = |seed: u32| -> Vec<u8> {
+            let mut state = seed;
+            let mut data = Vec::new();
+            data.extend_from_slice(&1u32.to_le_bytes());
+            data.extend_from_slice(&40_000u32.to_le_bytes());
+            data.extend((0..40_000).map(|_| { state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345); (state >> 16) as u8 }));
+            data
+        };
+        let encode = |seeds: &[u32]| -> Vec<u8> {
+            let mut bytes = Vec::new();
+            let mut writer = crate::formats::ASVPWriter::new(std::io::Cursor::new(&mut bytes));
+            for &seed in seeds {
+                writer.add_frame(crate::formats::FrameData { polystream: noise(seed), bitmap: None, triangle_strip: None });
+            }
+            writer.write_all().unwrap();
+            bytes
+        };
+        let server = crate::testlib::TestHttpServer::start(encode(&[1, 2, 3])).unwrap();
+        let processor = AlphaStreamProcessorBuilder::new()
+            .processing_mode(ProcessingMode::PolystreamOnly)
+            .range_requests(true)
+            .build_asvp(&server.url(), 16, 16).await.unwrap();
+        processor.enable_events(true);
+        for (i, seed) in [1, 2].into_iter().enumerate() {
+            processor.cache.insert(i, crate::formats::FrameData { polystream: noise(seed), bitmap: None, triangle_strip: None });
+        }
+
+        // Frame 1 re-encoded on the server; reading frame 2 finds out and refreshes the source
+        server.replace(encode(&[1, 9, 3]));
+        assert!(processor.get_polystream(2).await.is_none());
+        let mut events = Vec::new();
+        for _ in 0..100 {
+            events.extend(processor.poll_events());
+            if events.iter().any(|e| matches!(e, ProcessorEvent::SourceReloaded(_))) {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+        }
+        assert!(events.contains(&ProcessorEvent::DecodeError(2)));
+        assert!(events.contains(&ProcessorEvent::SourceReloaded(1)));
+        assert!(processor.cache.contains(&0));
+        assert!(!processor.cache.contains(&1));
+
+        let mut polystream = None;
+        for _ in 0..100 {
+            polystream = processor.get_polystream(2).await;
+            if polystream.is_some() {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(polystream, Some(noise(3)));
+    }
+
+    #[tokio::test]
+    async fn test_watch_source_reloads_on_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("source.asvp");
+        write_asvp(&path, &[1]);
+        let processor = AlphaStreamProcessorBuilder::new()
+            .watch_source(true)
+            .build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
+        cache_frame(&processor, 0, 1);
+
+        write_asvp(&path, &[5, 6]);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while processor.metadata().await.unwrap().frame_count != 2 {
+            assert!(std::time::Instant::now() < deadline, "watcher did not pick up the change");
+            tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+        }
+        assert!(!processor.cache.contains(&0));
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_heatmap() {
+        let test_file = create_test_asvp(4).unwrap();
+        let processor = AlphaStreamProcessorBuilder::new()
+            .build_asvp(test_file.path().to_str().unwrap(), 16, 16).await.unwrap();
+        let mask = processor.aggregate_heatmap(0..1).await.unwrap();
+        // Range end past the frame count is clamped
+        let heatmap = processor.aggregate_heatmap(1..10).await.unwrap();
+        assert_eq!(heatmap.frames, 3);
+        assert_eq!(heatmap.counts.len(), 256);
+        // All test frames are identical, so every covered pixel is covered by all of them
+        assert!(mask.counts.contains(&1));
+        for (&single, &sum) in mask.counts.iter().zip(&heatmap.counts) {
+            assert_eq!(sum, single * 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_triangle_strip_tolerance() {
+        let test_file = create_test_asvp(1).unwrap();
+        let processor = AlphaStreamProcessorBuilder::new()
+            .processing_mode(ProcessingMode::TriangleStrip)
+            .simplify_tolerance(4.0)
+            .build_asvp(test_file.path().to_str().unwrap(), 16, 16).await.unwrap();
+
+        assert!(processor.get_triangle_strip_vertices_with_tolerance(0, 0.0).await.is_none());
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        let simplified = processor.get_triangle_strip_vertices(0).await.unwrap();
+        let full = processor.get_triangle_strip_vertices_with_tolerance(0, 0.0).await.unwrap();
+        // Same unsimplified output as test_asvp_processor
+        assert_eq!(full.len(), 174);
+        assert!(simplified.len() < full.len());
+        assert_eq!(processor.get_triangle_strip_vertices_with_tolerance(0, 4.0).await.unwrap(), simplified);
+    }
+
+    #[tokio::test]
+    async fn test_access_pattern_warm_up() {
+        let test_file = create_test_asvp(20).unwrap();
+        let uri = test_file.path().to_str().unwrap();
+
+        // First session: visit a few frames, polling some of them repeatedly
+        let processor = AlphaStreamProcessorBuilder::new().build_asvp(uri, 16, 16).await.unwrap();
+        for frame in [10, 10, 11, 12, 3] {
+            let _ = processor.get_frame(frame, 16, 16).await;
+        }
+        let pattern = processor.record_access_pattern();
+        assert_eq!(pattern.ranges(), &[(10, 13), (3, 4)]);
+        drop(processor);
+
+        // Second session: warm up from the stored pattern, frames past the end are ignored
+        let mut text = pattern.to_string();
+        text.push_str("100\n");
+        let pattern = crate::access::AccessPattern::parse(&text).unwrap();
+        let processor = AlphaStreamProcessorBuilder::new().build_asvp(uri, 16, 16).await.unwrap();
+        assert_eq!(processor.warm_from_pattern(&pattern).await.unwrap(), 4);
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        for frame in [10, 11, 12, 3] {
+            assert!(processor.cache.contains(&frame), "frame {} not warmed", frame);
+        }
+    }
+
+    /// Two-channel polystream: a small triangle in channel 0 and a large one in channel 1
+    fn two_channel_polystream() -> Vec<u8> {
+        let small: Vec<u8> = vec![0, 0, 0, 0, 100, 0, 206, 100, 206, 156];
+        let large: Vec<u8> = vec![0, 4, 0, 2, 127, 0, 127, 0, 129, 127, 129, 129];
+        let mut data = Vec::new();
+        data.extend_from_slice(&2u32.to_le_bytes());
+        data.extend_from_slice(&(small.len() as u32).to_le_bytes());
+        data.extend_from_slice(&(large.len() as u32).to_le_bytes());
+        data.extend_from_slice(&small);
+        data.extend_from_slice(&large);
+        data
+    }
+
+    #[tokio::test]
+    async fn test_channel_selection() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("channels.asvp");
+        let mut writer = crate::formats::ASVPWriter::new(std::fs::File::create(&path).unwrap());
+        writer.add_frame(crate::formats::FrameData { polystream: two_channel_polystream(), bitmap: None, triangle_strip: None });
+        writer.write_all().unwrap();
+        let uri = path.to_str().unwrap();
+
+        let area = |mask: &[u8]| mask.iter().filter(|&&p| p > 0).count();
+        let wait = || tokio::time::sleep(tokio::time::Duration::from_millis(300));
+
+        let all = AlphaStreamProcessorBuilder::new().build_asvp(uri, 64, 64).await.unwrap();
+        let _ = all.get_frame(0, 64, 64).await;
+        wait().await;
+        let all_mask = all.get_frame(0, 64, 64).await.unwrap();
+
+        let primary = AlphaStreamProcessorBuilder::new().channels(&[0]).build_asvp(uri, 64, 64).await.unwrap();
+        let _ = primary.get_frame(0, 64, 64).await;
+        wait().await;
+        let primary_mask = primary.get_frame(0, 64, 64).await.unwrap();
+        assert!(area(&primary_mask) > 0);
+        assert!(area(&primary_mask) < area(&all_mask));
+
+        // Per-call override from the cached polystream
+        assert_eq!(all.get_frame_channels(0, &[0]).await.unwrap(), primary_mask);
+        assert_eq!(primary.get_frame_channels(0, &[0, 1]).await.unwrap(), all_mask);
+        assert_eq!(area(&all.get_frame_channels(0, &[7]).await.unwrap()), 0);
+    }
+
+    #[tokio::test]
+    async fn test_watermarked_output() {
+        use crate::watermark::Watermark;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("watermark.asvp");
+        let mut writer = crate::formats::ASVPWriter::new(std::fs::File::create(&path).unwrap());
+        writer.add_frame(crate::formats::FrameData { polystream: two_channel_polystream(), bitmap: None, triangle_strip: None });
+        writer.write_all().unwrap();
+        let uri = path.to_str().unwrap();
+
+        let watermark = Watermark::new(0x5EED, 42);
+        let plain = AlphaStreamProcessorBuilder::new().build_asvp(uri, 256, 256).await.unwrap();
+        let marked = AlphaStreamProcessorBuilder::new().watermark(watermark).build_asvp(uri, 256, 256).await.unwrap();
+        assert_eq!(marked.config().watermark, Some(watermark));
+        assert!(!marked.config().to_json().contains("watermark"));
+        let _ = plain.get_frame(0, 256, 256).await;
+        let _ = marked.get_frame(0, 256, 256).await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        let plain_mask = plain.get_frame(0, 256, 256).await.unwrap();
+        let marked_mask = marked.get_frame(0, 256, 256).await.unwrap();
+
+        assert!(watermark.verify(&marked_mask, 0));
+        assert!(!watermark.verify(&plain_mask, 0));
+        assert_eq!(crate::stats::MaskStats::from_mask(&marked_mask, 256, 256), crate::stats::MaskStats::from_mask(&plain_mask, 256, 256));
+        assert!(watermark.verify(&marked.get_frame_channels(0, &[0, 1]).await.unwrap(), 0));
+
+        // Masks rasterized by the caller are marked the same way
+        let mut mask = marked.rasterize_polystream(&two_channel_polystream());
+        marked.watermark_mask(0, &mut mask);
+        assert_eq!(mask, marked_mask);
+    }
+
+    #[tokio::test]
+    async fn test_dimension_limits() {
+        use super::{DimensionError, MAX_OUTPUT_SIZE};
+        use crate::backend::CPU_MAX_TEXTURE_SIZE;
+        use crate::formats::FormatError;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large.asvp");
+        write_asvp(&path, &[1]);
+        let uri = path.to_str().unwrap();
+
+        let wide = CPU_MAX_TEXTURE_SIZE + 1;
+        let result = AlphaStreamProcessorBuilder::new().build_asvp(uri, wide, 2).await;
+        assert!(matches!(result, Err(FormatError::Dimensions(DimensionError::ExceedsBackend { max: CPU_MAX_TEXTURE_SIZE, .. }))));
+        let result = AlphaStreamProcessorBuilder::new().tiled_rasterization(true).build_asvp(uri, MAX_OUTPUT_SIZE + 1, 2).await;
+        assert!(matches!(result, Err(FormatError::Dimensions(DimensionError::TooLarge { .. }))));
+
+        // Tiled, the wide mask matches the one drawn at once
+        let tiled = AlphaStreamProcessorBuilder::new().tiled_rasterization(true).build_asvp(uri, wide, 2).await.unwrap();
+        let mask = tiled.rasterize_polystream(&polystream(1));
+        assert_eq!(mask.len(), wide as usize * 2);
+        assert_eq!(mask, crate::rasterizer::PolystreamRasterizer::rasterize(&polystream(1)[8..], wide, 2));
+
+        // 512 cached 256x256 masks take 32 MiB
+        let budget = AlphaStreamProcessorBuilder::new().cache_memory_budget(1 << 20);
+        let Err(err) = budget.clone().build_asvp(uri, 256, 256).await else { panic!("cache over budget") };
+        assert_eq!(err.to_string(), "512 cached 256x256 masks take 33554432 bytes, more than the budget of 1048576 bytes; a cache capacity of 16 fits");
+        let fitted = budget.clone().fit_cache_to_budget(true).build_asvp(uri, 256, 256).await.unwrap();
+        assert_eq!(fitted.config().cache_capacity, 16);
+        let result = budget.clone().fit_cache_to_budget(true).build_asvp(uri, 2048, 1024).await;
+        assert!(matches!(result, Err(FormatError::Dimensions(DimensionError::CacheBudget { suggested_cache_capacity: None, .. }))));
+        // Modes that do not cache masks are not limited by the budget
+        assert!(budget.processing_mode(ProcessingMode::PolystreamOnly).build_asvp(uri, 2048, 1024).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_tiled_rasterization() {
+        use crate::rasterizer::Tile;
+        use crate::watermark::Watermark;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tiles.asvp");
+        let mut writer = crate::formats::ASVPWriter::new(std::fs::File::create(&path).unwrap());
+        writer.add_frame(crate::formats::FrameData { polystream: two_channel_polystream(), bitmap: None, triangle_strip: None });
+        writer.write_all().unwrap();
+        let processor = AlphaStreamProcessorBuilder::new()
+            .watermark(Watermark::new(0x5EED, 42))
+            .channels(&[1])
+            .build_asvp(path.to_str().unwrap(), 300, 200).await.unwrap();
+
+        let mut whole = processor.rasterize_polystream(&two_channel_polystream());
+        processor.watermark_mask(0, &mut whole);
+        assert!(whole.iter().any(|&pixel| pixel != 0));
+        for threads in [0, 1, 3] {
+            assert_eq!(processor.rasterize_polystream_tiled(0, &two_channel_polystream(), 64, threads), whole);
+        }
+
+        // Delivered one by one, every tile arrives once
+        let mut seen = Vec::new();
+        processor.rasterize_polystream_tiles(0, &two_channel_polystream(), 128, 2, |tile, mask| {
+            assert_eq!(mask.len(), tile.pixel_count());
+            assert!(tile.width <= 128 && tile.height <= 128);
+            seen.push(*tile);
+        });
+        seen.sort_by_key(|tile| (tile.y, tile.x));
+        assert_eq!(seen, Tile::grid(300, 200, 128));
+    }
+
+    #[tokio::test]
+    async fn test_vertex_count_and_frame_trace() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vertices.asvp");
+        let mut writer = crate::formats::ASVPWriter::new(std::fs::File::create(&path).unwrap());
+        writer.add_frame(crate::formats::FrameData { polystream: two_channel_polystream(), bitmap: None, triangle_strip: None });
+        writer.write_all().unwrap();
+        let uri = path.to_str().unwrap();
+
+        let all = AlphaStreamProcessorBuilder::new().build_asvp(uri, 64, 64).await.unwrap();
+        let large = AlphaStreamProcessorBuilder::new().channels(&[1]).build_asvp(uri, 64, 64).await.unwrap();
+        assert_eq!(all.polystream_vertex_count(&two_channel_polystream()), 9);
+        assert_eq!(large.polystream_vertex_count(&two_channel_polystream()), 5);
+
+        assert_eq!(all.frame_trace(0), None);
+        let _ = all.get_polystream(0).await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        assert!(all.get_polystream(0).await.is_some());
+        assert!(all.frame_trace(0).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_container_track_selection() {
+        use crate::container::{ContainerWriter, Cue, CueTrack, Thumbnail};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("shot.asvx");
+        let frame = |fill| crate::formats::FrameData { polystream: polystream(fill), bitmap: None, triangle_strip: None };
+        let mut writer = ContainerWriter::new(std::fs::File::create(&path).unwrap());
+        writer.add_mask_track("person", &[frame(1), frame(2)]).unwrap();
+        writer.add_mask_track("ball", &[frame(5), frame(6), frame(7)]).unwrap();
+        writer.add_thumbnail_track("keyframes", &[Thumbnail { frame_index: 0, width: 1, height: 1, pixels: vec![255] }]).unwrap();
+        writer.add_cue_track("clicks", &CueTrack::new(30.0, vec![Cue { time: 0.05, label: "click".to_string() }])).unwrap();
+        writer.write_all().unwrap();
+        let uri = path.to_str().unwrap();
+
+        let person = AlphaStreamProcessorBuilder::new().processing_mode(ProcessingMode::PolystreamOnly).build_asvp(uri, 16, 16).await.unwrap();
+        let ball = AlphaStreamProcessorBuilder::new().processing_mode(ProcessingMode::PolystreamOnly).track("ball").build_asvp(uri, 16, 16).await.unwrap();
+        assert_eq!(person.metadata().await.unwrap().frame_count, 2);
+        assert_eq!(ball.metadata().await.unwrap().frame_count, 3);
+        assert_eq!(ball.config().track.as_deref(), Some("ball"));
+        assert_eq!(ball.tracks().await.len(), 4);
+        assert_eq!(ball.next_cue(0).await.unwrap().label, "click");
+        assert!(ball.next_cue(2).await.is_none());
+        assert_eq!(ball.thumbnails(None).await.unwrap()[0].pixels, [255]);
+        assert!(ball.annotations(None).await.is_err());
+
+        let _ = ball.get_polystream(1).await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        assert_eq!(ball.get_polystream(1).await.unwrap(), polystream(6));
+        // The selected track is kept when the source is reloaded: its frames stay cached
+        // (prefetched indices past the end, clamped to the last frame, are evicted)
+        assert!(ball.reload().await.unwrap().iter().all(|&index| index >= 3));
+        assert_eq!(ball.metadata().await.unwrap().frame_count, 3);
+
+        assert!(AlphaStreamProcessorBuilder::new().track("missing").build_asvp(uri, 16, 16).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retime() {
+        use crate::api::{retime, RetimeMethod};
+        use crate::formats::{ASFormat, ASVPFormat};
+
+        /// One channel starting at (x, 0) with three (1, 1) steps
+        fn outline(x: u16) -> Vec<u8> {
+            let mut data = Vec::new();
+            data.extend_from_slice(&1u32.to_le_bytes());
+            data.extend_from_slice(&10u32.to_le_bytes());
+            data.extend_from_slice(&x.to_le_bytes());
+            data.extend_from_slice(&0u16.to_le_bytes());
+            data.extend_from_slice(&[1, 1, 1, 1, 1, 1]);
+            data
+        }
+        async fn frames(path: &std::path::Path) -> Vec<Vec<u8>> {
+            let mut format = ASVPFormat::new(tokio::fs::File::open(path).await.unwrap()).await.unwrap();
+            let count = format.frame_count().await.unwrap();
+            let mut frames = Vec::new();
+            for i in 0..count {
+                frames.push(format.decode_frame(i).await.unwrap().polystream);
+            }
+            frames
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("30fps.asvp");
+        let mut writer = crate::formats::ASVPWriter::new(std::fs::File::create(&input).unwrap());
+        for x in [0, 10, 20] {
+            writer.add_frame(crate::formats::FrameData { polystream: outline(x), bitmap: None, triangle_strip: None });
+        }
+        writer.write_all().unwrap();
+        let uri = input.to_str().unwrap();
+        let output = dir.path().join("out.asvp");
+
+        // Doubling the rate duplicates every frame
+        let report = retime(uri, &output, 30.0, 60.0, RetimeMethod::Nearest).await.unwrap();
+        assert_eq!((report.source_frames, report.target_frames, report.interpolated), (3, 6, 0));
+        assert_eq!(frames(&output).await, [outline(0), outline(0), outline(10), outline(10), outline(20), outline(20)]);
+
+        // ... or blends the in-between frames; past the last source frame there is nothing to blend with
+        let report = retime(uri, &output, 30.0, 60.0, RetimeMethod::Interpolate).await.unwrap();
+        assert_eq!(report.interpolated, 2);
+        assert_eq!(frames(&output).await, [outline(0), outline(5), outline(10), outline(15), outline(20), outline(20)]);
+
+        // Halving it drops every other frame
+        let report = retime(uri, &output, 30.0, 15.0, RetimeMethod::Interpolate).await.unwrap();
+        assert_eq!(report.target_frames, 2);
+        assert_eq!(frames(&output).await, [outline(0), outline(20)]);
+
+        assert!(retime(uri, &output, 0.0, 50.0, RetimeMethod::Nearest).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_concat_asvr() {
+        use crate::api::{concat_asvr, SceneCredentials};
+        use crate::formats::{ASFormat, ASVRFormat};
+
+        let first = crate::testlib::create_test_asvr(1, b"1.0", 2).unwrap();
+        let second = crate::testlib::create_test_asvr(2, b"1.5.0", 3).unwrap();
+        let credentials = |file: &tempfile::NamedTempFile, scene_id, version: &[u8]| {
+            SceneCredentials::new(scene_id, version, file.path().file_name().unwrap().to_str().unwrap().as_bytes())
+        };
+        let inputs = [
+            (first.path().to_str().unwrap(), credentials(&first, 1, b"1.0")),
+            (second.path().to_str().unwrap(), credentials(&second, 2, b"1.5.0")),
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("release.asvr");
+        let target = SceneCredentials::new(77, b"1.5.0", b"release.asvr");
+        assert_eq!(concat_asvr(&inputs, &output, &target).await.unwrap(), [0..2, 2..5]);
+
+        let open = |path: &std::path::Path, credentials: &SceneCredentials| {
+            let path = path.to_path_buf();
+            let credentials = credentials.clone();
+            async move {
+                let file = tokio::fs::File::open(path).await.unwrap();
+                ASVRFormat::new(file, credentials.scene_id, &credentials.version, &credentials.base_url).await.unwrap()
+            }
+        };
+        let mut merged = open(&output, &target).await;
+        assert_eq!(merged.frame_count().await.unwrap(), 5);
+        for (i, (path, credentials)) in inputs.iter().enumerate() {
+            let mut source = open(std::path::Path::new(path), credentials).await;
+            for index in 0..source.frame_count().await.unwrap() {
+                let merged_index = [0, 2][i] + index;
+                assert_eq!(merged.decode_frame(merged_index).await.unwrap().polystream, source.decode_frame(index).await.unwrap().polystream);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decode_where() {
+        use crate::api::DecodedFrame;
+        use crate::container::{Annotation, ContainerWriter};
+        use crate::filter::FrameFilter;
+        use crate::formats::FormatError;
+        use futures::StreamExt;
+
+        let frame = |polystream| crate::formats::FrameData { polystream, bitmap: None, triangle_strip: None };
+        let empty = 0u32.to_le_bytes().to_vec();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sparse.asvx");
+        let mut writer = ContainerWriter::new(std::fs::File::create(&path).unwrap());
+        writer.add_mask_track("mask", &[frame(empty.clone()), frame(two_channel_polystream()), frame(empty), frame(two_channel_polystream())]).unwrap();
+        writer.add_annotation_track("notes", &[Annotation { frame_index: 3, text: "hero".to_string() }]).unwrap();
+        writer.write_all().unwrap();
+        let processor = AlphaStreamProcessorBuilder::new().processing_mode(ProcessingMode::PolystreamOnly)
+            .build_asvp(path.to_str().unwrap(), 64, 64).await.unwrap();
+
+        let collect = |stream: Vec<Result<DecodedFrame, FormatError>>| stream.into_iter().map(|frame| frame.unwrap()).collect::<Vec<_>>();
+        // Non-empty masks by statistics; polystream-only mode still returns no bitmaps
+        let area = FrameFilter::parse("area > 0").unwrap();
+        let frames = collect(processor.decode_where(0..10, |_| true, Some(&area)).await.unwrap().collect().await);
+        assert_eq!(frames.iter().map(|f| f.output.frame_index).collect::<Vec<_>>(), [1, 3]);
+        assert!(frames.iter().all(|f| f.output.bitmap.is_none() && f.output.stats.unwrap().area > 0));
+        assert_eq!(frames[0].polystream, two_channel_polystream());
+
+        // The same from the sizes table alone, and by annotation
+        let smallest = processor.format.lock().await.frame_sizes()[0];
+        let frames = collect(processor.decode_where(0..4, |f| f.stored_size > smallest, None).await.unwrap().collect().await);
+        assert_eq!(frames.iter().map(|f| f.output.frame_index).collect::<Vec<_>>(), [1, 3]);
+        assert!(frames[0].output.stats.is_none());
+        let frames = collect(processor.decode_where(0..4, |f| f.annotations.iter().any(|note| note.text == "hero"), None).await.unwrap().collect().await);
+        assert_eq!(frames.iter().map(|f| f.output.frame_index).collect::<Vec<_>>(), [3]);
+        // Nothing was decoded through the cache
+        assert!(processor.cache.ready_frames().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bookmarks() {
+        use crate::api::Bookmark;
+        use crate::container::{Annotation, ContainerWriter, BOOKMARK_TRACK};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("long.asvp");
+        write_asvp(&path, &[1, 2, 3, 4, 5, 6]);
+        let uri = path.to_str().unwrap();
+        let bookmark = |frame_index, name: &str| Bookmark { frame_index, name: name.to_string() };
+
+        let processor = AlphaStreamProcessor::new_asvp(uri, 16, 16, ProcessingMode::Bitmap).await.unwrap();
+        assert!(processor.bookmarks().is_empty());
+        processor.add_bookmark("Outro", 5).await.unwrap();
+        processor.add_bookmark("Intro", 0).await.unwrap();
+        processor.add_bookmark("Middle", 2).await.unwrap();
+        assert!(processor.add_bookmark("Past the end", 6).await.is_err());
+        assert!(processor.add_bookmark("", 1).await.is_err());
+        // Re-adding a name moves the bookmark
+        processor.add_bookmark("Middle", 3).await.unwrap();
+        assert_eq!(processor.bookmarks(), [bookmark(0, "Intro"), bookmark(3, "Middle"), bookmark(5, "Outro")]);
+        assert_eq!(processor.chapter_at(4), Some(bookmark(3, "Middle")));
+        assert!(processor.remove_bookmark("Outro").unwrap());
+        assert!(!processor.remove_bookmark("Outro").unwrap());
+        drop(processor);
+
+        // Persisted in the sidecar for the next session
+        let reopened = AlphaStreamProcessor::new_asvp(uri, 16, 16, ProcessingMode::Bitmap).await.unwrap();
+        assert_eq!(reopened.bookmarks(), [bookmark(0, "Intro"), bookmark(3, "Middle")]);
+        assert_eq!(reopened.chapter_at(5).unwrap().name, "Middle");
+
+        // A container's bookmark track, when there is no sidecar
+        let container = dir.path().join("long.asvx");
+        let frame = |fill| crate::formats::FrameData { polystream: polystream(fill), bitmap: None, triangle_strip: None };
+        let mut writer = ContainerWriter::new(std::fs::File::create(&container).unwrap());
+        writer.add_mask_track("mask", &[frame(1), frame(2), frame(3)]).unwrap();
+        let chapters: Vec<Annotation> = reopened.bookmarks().into_iter().map(Annotation::from).collect();
+        writer.add_annotation_track(BOOKMARK_TRACK, &chapters).unwrap();
+        writer.write_all().unwrap();
+        let processor = AlphaStreamProcessor::new_asvp(container.to_str().unwrap(), 16, 16, ProcessingMode::Bitmap).await.unwrap();
+        assert_eq!(processor.bookmarks(), reopened.bookmarks());
+    }
+
+    #[cfg(feature = "scripting")]
+    #[tokio::test]
+    async fn test_decode_scripted() {
+        use crate::script::FrameScript;
+        use futures::StreamExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scripted.asvp");
+        let mut writer = crate::formats::ASVPWriter::new(std::fs::File::create(&path).unwrap());
+        for polystream in [0u32.to_le_bytes().to_vec(), two_channel_polystream(), two_channel_polystream()] {
+            writer.add_frame(crate::formats::FrameData { polystream, bitmap: None, triangle_strip: None });
+        }
+        writer.write_all().unwrap();
+        let processor = AlphaStreamProcessorBuilder::new().processing_mode(ProcessingMode::PolystreamOnly)
+            .build_asvp(path.to_str().unwrap(), 64, 64).await.unwrap();
+
+        let script = FrameScript::compile("out.channels = channels.len(); out.largest = channels.map(|c| c.area).reduce(|a, b| if a > b { a } else { b }); frame != 2").unwrap();
+        let frames: Vec<_> = processor.decode_scripted(0..10, |_| true, &script).await.unwrap().collect().await;
+        let frames: Vec<_> = frames.into_iter().map(|frame| frame.unwrap()).collect();
+        // Frame 2 is vetoed
+        assert_eq!(frames.iter().map(|f| f.frame.output.frame_index).collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(frames[0].values["channels"], serde_json::json!(0));
+        assert_eq!(frames[1].values["channels"], serde_json::json!(2));
+        assert!(frames[1].values["largest"].as_i64().unwrap() > 0);
+        assert!(frames[1].frame.output.stats.unwrap().area > 0);
+
+        // A failing script ends the stream with its error
+        let script = FrameScript::compile("out.x = 1 / (frame - 1)").unwrap();
+        let frames: Vec<_> = processor.decode_scripted(0..10, |_| true, &script).await.unwrap().collect().await;
+        assert!(frames[0].is_ok());
+        assert!(frames[1].as_ref().unwrap_err().to_string().contains("frame 1"));
+    }
+
+    #[tokio::test]
+    async fn test_polystream_only_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("passthrough.asvp");
+        write_asvp(&path, &[7, 8]);
+        let processor = AlphaStreamProcessorBuilder::new()
+            .processing_mode(ProcessingMode::PolystreamOnly)
+            .build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
+
+        assert!(processor.get_polystream(1).await.is_none());
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        assert_eq!(processor.get_polystream(1).await.unwrap(), polystream(8));
+        // Nothing was rasterized or triangulated
+        let cached = processor.cache.get(1).unwrap();
+        assert!(cached.bitmap.is_none());
+        assert!(cached.triangle_strip.is_none());
+        assert!(processor.get_frame(1, 16, 16).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stride_decodes_every_nth_frame() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stride.asvp");
+        write_asvp(&path, &(0..12).collect::<Vec<u8>>());
+        let processor = AlphaStreamProcessorBuilder::new()
+            .processing_mode(ProcessingMode::PolystreamOnly)
+            .stride(4)
+            .build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
+        assert_eq!(processor.stride(), 4);
+        let config = processor.config();
+        assert_eq!(config.stride, 4);
+        assert_eq!(config.processing_mode, ProcessingMode::PolystreamOnly);
+        assert_eq!(config.runtime_threads, 0);
+
+        assert!(processor.get_polystream(5).await.is_none());
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        // Frames between stride points are served by the preceding decoded frame
+        assert_eq!(processor.get_polystream(5).await.unwrap(), polystream(4));
+        assert_eq!(processor.get_polystream(7).await.unwrap(), polystream(4));
+        // Prefetch ran ahead in strided frames: cache index 2 holds frame 8
+        assert_eq!(processor.cache.get(2).unwrap().polystream, polystream(8));
+    }
+
+    #[tokio::test]
+    async fn test_delta_source_seeks_to_keyframes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("delta.asvp");
+        // Frames differ from each other in a single byte, so they delta-encode
+        let frames: Vec<Vec<u8>> = (0..10u8).map(|i| {
+            let mut frame = polystream(1);
+            frame[8 + i as usize] = i;
+            frame
+        }).collect();
+        let mut writer = crate::formats::ASVPWriter::new(std::fs::File::create(&path).unwrap()).keyframe_interval(4);
+        for frame in &frames {
+            writer.add_frame(crate::formats::FrameData { polystream: frame.clone(), bitmap: None, triangle_strip: None });
+        }
+        writer.write_all().unwrap();
+
+        let processor = AlphaStreamProcessor::new_asvp(path.to_str().unwrap(), 16, 16, ProcessingMode::PolystreamOnly).await.unwrap();
+        assert_eq!(processor.nearest_keyframe(6).await, 4);
+        assert_eq!(processor.nearest_keyframe(8).await, 8);
+        assert_eq!(processor.scheduler.lock().await.keyframe_for(7), 4);
+        assert_eq!(processor.frame_dependencies(6).await, vec![4]);
+        assert!(processor.frame_dependencies(4).await.is_empty());
+        let plain_path = dir.path().join("plain.asvp");
+        write_asvp(&plain_path, &[1, 2, 3]);
+        let plain = AlphaStreamProcessor::new_asvp(plain_path.to_str().unwrap(), 16, 16, ProcessingMode::PolystreamOnly).await.unwrap();
+        assert!(plain.frame_dependencies(2).await.is_empty());
+
+        // A seek into the middle of a group decodes its keyframe too
+        assert!(processor.get_polystream(6).await.is_none());
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        assert_eq!(processor.get_polystream(6).await.unwrap(), frames[6]);
+        assert_eq!(processor.cache.get(4).unwrap().polystream, frames[4]);
+    }
+
+    #[tokio::test]
+    async fn test_auto_fit_covers_content_outside_canvas() {
+        use crate::rasterizer::{OutputTransform, NATIVE_HEIGHT, NATIVE_WIDTH};
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wide.asvp");
+        // One channel with a point at x=3000, beyond the native canvas width
+        let mut wide = Vec::new();
+        wide.extend_from_slice(&1u32.to_le_bytes());
+        wide.extend_from_slice(&4u32.to_le_bytes());
+        wide.extend_from_slice(&3000u16.to_le_bytes());
+        wide.extend_from_slice(&0u16.to_le_bytes());
+        let mut writer = crate::formats::ASVPWriter::new(std::fs::File::create(&path).unwrap());
+        writer.add_frame(crate::formats::FrameData { polystream: wide, bitmap: None, triangle_strip: None });
+        writer.write_all().unwrap();
+
+        let stretched = AlphaStreamProcessorBuilder::new()
+            .build_asvp(path.to_str().unwrap(), 512, 256).await.unwrap();
+        assert_eq!(stretched.output_transform(), OutputTransform::stretch(512, 256));
+
+        let fitted = AlphaStreamProcessorBuilder::new()
+            .auto_fit(true)
+            .build_asvp(path.to_str().unwrap(), 512, 256).await.unwrap();
+        let transform = fitted.output_transform();
+        assert_eq!(transform, OutputTransform::fit(3001, NATIVE_HEIGHT, 512, 256));
+        assert!(transform.scale_x < 512.0 / NATIVE_WIDTH as f32);
+        assert!(transform.offset_y > 0.0);
+        assert!(3000.0 * transform.scale_x + transform.offset_x < 512.0);
+    }
+
+    #[tokio::test]
+    async fn test_get_frame_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("output.asvp");
+        write_asvp(&path, &[1, 2]);
+        let processor = AlphaStreamProcessorBuilder::new()
+            .processing_mode(ProcessingMode::Both)
+            .build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
+
+        // Frames put in the cache from elsewhere have no timing
+        cache_frame(&processor, 0, 1);
+        let output = processor.get_frame_output(0).await.unwrap();
+        assert!(output.trace.is_none());
+        assert!(output.triangle_strip.is_none());
+
+        assert!(processor.get_frame_output(1).await.is_none());
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        let output = processor.get_frame_output(1).await.unwrap();
+        assert_eq!(output.frame_index, 1);
+        let bitmap = output.bitmap.unwrap();
+        assert_eq!(output.stats, Some(crate::stats::MaskStats::from_mask(&bitmap, 16, 16)));
+        assert!(output.triangle_strip.is_some());
+        assert!(output.trace.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_entitlement_gates_playback() {
+        use crate::api::ProcessorEvent;
+        use crate::clock::MockClock;
+        use crate::entitlement::{EntitlementError, EntitlementProvider, EntitlementRequest, DEFAULT_RECHECK_INTERVAL};
+        use crate::formats::FormatError;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        #[derive(Default)]
+        struct License(AtomicBool);
+        impl EntitlementProvider for License {
+            fn check(&self, request: &EntitlementRequest) -> Result<(), EntitlementError> {
+                assert_eq!(request.scene_id, 123);
+                if self.0.load(Ordering::SeqCst) { Ok(()) } else { Err(EntitlementError::Denied("no license".to_string())) }
+            }
+        }
+
+        let file = crate::testlib::create_test_asvr(123, b"1.0", 2).unwrap();
+        let path = file.path().to_str().unwrap();
+        let base_url = file.path().file_name().unwrap().to_str().unwrap().as_bytes();
+        let license = Arc::new(License::default());
+        let clock = Arc::new(MockClock::new());
+        let builder = AlphaStreamProcessorBuilder::new()
+            .processing_mode(ProcessingMode::PolystreamOnly)
+            .clock(clock.clone())
+            .entitlement_provider(license.clone());
+        let refused = builder.clone().build_asvr(path, 123, b"1.0", base_url, 16, 16).await;
+        assert!(matches!(refused, Err(FormatError::Entitlement(EntitlementError::Denied(_)))));
+
+        license.0.store(true, Ordering::SeqCst);
+        let processor = builder.build_asvr(path, 123, b"1.0", base_url, 16, 16).await.unwrap();
+        processor.enable_events(true);
+        let mut polystream = None;
+        for _ in 0..100 {
+            polystream = processor.get_polystream(0).await;
+            if polystream.is_some() {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+        }
+        assert!(polystream.is_some());
+
+        // Revoked: the next check after the interval refuses and drops the decrypted frames
+        license.0.store(false, Ordering::SeqCst);
+        assert!(processor.get_polystream(0).await.is_some());
+        clock.advance(DEFAULT_RECHECK_INTERVAL);
+        assert!(processor.get_polystream(0).await.is_none());
+        assert!(processor.poll_events().contains(&ProcessorEvent::EntitlementDenied));
+        assert!(!processor.cache.contains(&0));
+        assert!(matches!(processor.entitlement_status(), Err(EntitlementError::Denied(_))));
+    }
+
+    #[tokio::test]
+    async fn test_mock_clock_makes_traces_deterministic() {
+        use crate::api::FrameTrace;
+        use crate::clock::{MockClock, SharedClock};
+        use std::sync::Arc;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clock.asvp");
+        write_asvp(&path, &[1]);
+        let clock = Arc::new(MockClock::new());
+        let processor = AlphaStreamProcessorBuilder::new()
+            .clock(clock.clone())
+            .build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
+        assert_eq!(processor.config().clock, SharedClock::new(clock.clone()));
+
+        assert!(processor.get_frame_output(0).await.is_none());
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        // Time stood still while the frame was decoded and rasterized
+        let output = processor.get_frame_output(0).await.unwrap();
+        assert_eq!(output.trace, Some(FrameTrace::default()));
+    }
+
+    #[tokio::test]
+    async fn test_channel_info() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("channels.asvp");
+        let mut writer = crate::formats::ASVPWriter::new(std::fs::File::create(&path).unwrap());
+        writer.add_frame(crate::formats::FrameData { polystream: polystream(1), bitmap: None, triangle_strip: None });
+        writer.add_frame(crate::formats::FrameData { polystream: two_channel_polystream(), bitmap: None, triangle_strip: None });
+        writer.write_all().unwrap();
+        let processor = AlphaStreamProcessorBuilder::new().build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
+
+        // Not decoded yet: read without touching the cache
+        let info = processor.channel_info(1).await.unwrap();
+        assert_eq!(info.count(), 2);
+        assert_eq!(info.sizes, vec![10, 12]);
+        assert!(!processor.cache.contains(&1));
+        // Decoded: read from the cache
+        let _ = processor.get_polystream(0).await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        assert!(processor.cache.contains(&0));
+        assert_eq!(processor.channel_info(0).await.unwrap(), super::ChannelInfo { frame_index: 0, sizes: vec![64] });
+        assert!(processor.channel_info(5).await.is_err());
+        assert!(super::ChannelInfo::from_polystream(0, &[2, 0, 0, 0, 1, 0]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_empty_frame_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dropout.asvp");
+        // One channel without outline data in the middle frame
+        let empty = [1u32.to_le_bytes(), 0u32.to_le_bytes()].concat();
+        let mut writer = crate::formats::ASVPWriter::new(std::fs::File::create(&path).unwrap());
+        for polystream in [two_channel_polystream(), empty, two_channel_polystream()] {
+            writer.add_frame(crate::formats::FrameData { polystream, bitmap: None, triangle_strip: None });
+        }
+        writer.write_all().unwrap();
+        let mut processor = AlphaStreamProcessorBuilder::new()
+            .processing_mode(ProcessingMode::Both)
+            .build_asvp(path.to_str().unwrap(), 300, 200).await.unwrap();
+        // Frames are read in playback order, going back would be a seek that clears the cache
+        assert_eq!(processor.is_frame_empty(0), None);
+        let _ = processor.get_frame_output(0).await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        let first = processor.get_frame_output(0).await.unwrap();
+        assert!(!first.is_empty);
+        let last = first.bitmap.clone().unwrap();
+        assert!(last.iter().any(|&value| value > 0));
+        let _ = processor.get_frame_output(1).await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        assert_eq!((processor.is_frame_empty(0), processor.is_frame_empty(1)), (Some(false), Some(true)));
+
+        // ReturnEmpty: an empty mask
+        let output = processor.get_frame_output(1).await.unwrap();
+        assert!(output.is_empty);
+        assert_eq!(output.bitmap, Some(vec![0; 300 * 200]));
+
+        // ReuseLast: the mask of frame 0
+        processor.set_empty_frame_policy(super::EmptyFramePolicy::ReuseLast);
+        assert_eq!(processor.get_frame(1, 300, 200).await, Some(last.clone()));
+        let output = processor.get_frame_output(1).await.unwrap();
+        assert!(output.is_empty);
+        assert_eq!(output.bitmap, Some(last));
+        assert_eq!(output.triangle_strip, first.triangle_strip);
+
+        // Error: nothing, although the frame is decoded
+        processor.set_empty_frame_policy(super::EmptyFramePolicy::Error);
+        assert_eq!(processor.config().empty_frame_policy, super::EmptyFramePolicy::Error);
+        assert_eq!(processor.get_frame(1, 300, 200).await, None);
+        assert_eq!(processor.get_triangle_strip_vertices(1).await, None);
+        let output = processor.get_frame_output(1).await.unwrap();
+        assert!(output.is_empty && output.bitmap.is_none() && output.triangle_strip.is_none() && output.stats.is_none());
+        let _ = processor.get_frame(2, 300, 200).await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        assert!(processor.get_frame(2, 300, 200).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_telemetry_exporter() {
+        use crate::telemetry::MetricsExporter;
+        use std::sync::Arc;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("telemetry.asvp");
+        write_asvp(&path, &[1, 2, 3, 4]);
+        let metrics = Arc::new(MetricsExporter::default());
+        let processor = AlphaStreamProcessorBuilder::new()
+            .telemetry_exporter(metrics.clone())
+            .build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
+        assert_eq!(processor.config(), AlphaStreamProcessorBuilder::new().telemetry_exporter(metrics.clone()).effective());
+
+        let _ = processor.get_frame(2, 16, 16).await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        let decoded = metrics.snapshot();
+        assert!(decoded.frames_decoded >= 2, "{:?}", decoded);
+        assert_eq!((decoded.seeks, decoded.frames_evicted), (0, 0));
+        // Going back is a seek that drops the decoded frames
+        let _ = processor.get_frame(0, 16, 16).await;
+        let seeked = metrics.snapshot();
+        assert_eq!(seeked.seeks, 1);
+        assert!(seeked.frames_evicted > 0, "{:?}", seeked);
+    }
+
+    #[tokio::test]
+    async fn test_custom_rasterizer() {
+        use super::{RasterOptions, Rasterizer};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        /// Covers every pixel and outputs one fixed vertex, counting the channels it draws
+        #[derive(Default)]
+        struct Solid(AtomicUsize);
+        impl Rasterizer for Solid {
+            fn rasterize(&self, _polystream: &[u8], _width: u32, _height: u32, _options: &RasterOptions, mask: &mut [u8]) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                mask.fill(255);
+            }
+            fn to_vertices(&self, _polystream: &[u8], _tolerance: f32) -> Vec<f32> {
+                vec![1.0, 2.0]
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("custom.asvp");
+        write_asvp(&path, &[1, 2]);
+        let solid = Arc::new(Solid::default());
+        let processor = AlphaStreamProcessorBuilder::new()
+            .processing_mode(ProcessingMode::Both)
+            .rasterizer(solid.clone())
+            .build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
+        let frame = processor.get_frame_wait(0, std::time::Duration::from_secs(5)).await.unwrap();
+        assert!(frame.iter().all(|&value| value == 255));
+        assert!(solid.0.load(Ordering::Relaxed) >= 1);
+        assert_eq!(processor.get_triangle_strip_vertices(0).await.unwrap(), [1.0, 2.0]);
+        // Direct rasterization goes through it too
+        assert!(processor.rasterize_polystream(&polystream(1)).iter().all(|&value| value == 255));
+    }
+
+    #[tokio::test]
+    async fn test_get_frame_wait() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wait.asvp");
+        write_asvp(&path, &[0x40, 0x80]);
+        let processor = AlphaStreamProcessorBuilder::new()
+            .build_asvp(path.to_str().unwrap(), 64, 64).await.unwrap();
+        let timeout = std::time::Duration::from_secs(5);
+        let frame = processor.get_frame_wait(0, timeout).await.unwrap();
+        assert_eq!(frame.len(), 64 * 64);
+        assert_eq!(processor.get_frame_wait(1, timeout).await.unwrap().len(), 64 * 64);
+
+        let processor = AlphaStreamProcessorBuilder::new()
+            .processing_mode(ProcessingMode::TriangleStrip)
+            .build_asvp(path.to_str().unwrap(), 64, 64).await.unwrap();
+        assert_eq!(processor.get_frame_wait(0, timeout).await, Err(super::FrameWaitError::NoBitmap));
+    }
+
+    #[tokio::test]
+    async fn test_set_processing_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mode.asvp");
+        write_asvp(&path, &[0x40, 0x80]);
+        let timeout = std::time::Duration::from_secs(5);
+        let mut processor = AlphaStreamProcessorBuilder::new()
+            .processing_mode(ProcessingMode::TriangleStrip)
+            .build_asvp(path.to_str().unwrap(), 64, 64).await.unwrap();
+        assert_eq!(processor.get_frame_wait(0, timeout).await, Err(super::FrameWaitError::NoBitmap));
+        let vertices = processor.get_triangle_strip_vertices(0).await.unwrap();
+
+        // Cached frames have no bitmap, so they are decoded again
+        assert!(processor.set_processing_mode(ProcessingMode::Both).contains(&0));
+        assert_eq!(processor.processing_mode(), ProcessingMode::Both);
+        assert_eq!(processor.config().processing_mode, ProcessingMode::Both);
+        assert_eq!(processor.get_frame_wait(0, timeout).await.unwrap().len(), 64 * 64);
+        assert_eq!(processor.get_triangle_strip_vertices(0).await.unwrap(), vertices);
+
+        // Frames with both outputs serve either mode
+        assert!(processor.set_processing_mode(ProcessingMode::Bitmap).is_empty());
+        assert!(processor.set_processing_mode(ProcessingMode::TriangleStrip).is_empty());
+        assert!(processor.set_processing_mode(ProcessingMode::PolystreamOnly).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_output_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("resize.asvp");
+        write_asvp(&path, &[0x40, 0x80]);
+        let timeout = std::time::Duration::from_secs(5);
+        let mut processor = AlphaStreamProcessorBuilder::new()
+            .processing_mode(ProcessingMode::Both)
+            .build_asvp(path.to_str().unwrap(), 64, 64).await.unwrap();
+        assert_eq!(processor.get_frame_wait(0, timeout).await.unwrap().len(), 64 * 64);
+        let vertices = processor.get_triangle_strip_vertices(0).await.unwrap();
 
-        // Prefetch if sequential access detected
-        AlphaStreamProcessor::maybe_trigger_prefetch(&mut scheduler, requested_frame_index).await;
+        // Masks are rasterized again at the new size, triangle strips are the same
+        assert!(processor.set_output_size(32, 16).await.unwrap().contains(&0));
+        assert_eq!((processor.width(), processor.height()), (32, 16));
+        assert_eq!(processor.output_transform(), super::OutputTransform::stretch(32, 16));
+        assert_eq!(processor.get_frame_wait(0, timeout).await.unwrap().len(), 32 * 16);
+        assert_eq!(processor.get_triangle_strip_vertices(0).await.unwrap(), vertices);
+        assert!(processor.set_output_size(32, 16).await.unwrap().is_empty());
 
-        None // Will be available after background processing completes
+        // A size the build would refuse is refused, and the processor keeps its size
+        let result = processor.set_output_size(super::MAX_OUTPUT_SIZE + 1, 16).await;
+        assert!(matches!(result, Err(super::FormatError::Dimensions(super::DimensionError::TooLarge { .. }))));
+        assert_eq!((processor.width(), processor.height()), (32, 16));
+        assert_eq!(processor.get_frame_wait(0, timeout).await.unwrap().len(), 32 * 16);
     }
 
-    /// Get triangle strip vertices for a frame
-    /// Similar to get_frame but for 3D geometry data. Checks cache first, schedules if needed.
-    /// Returns None if not ready yet, allowing non-blocking operation.
-    pub async fn get_triangle_strip_vertices(&self, frame_index: usize) -> Option<Vec<f32>> {
-        // Update play head position for seek detection
-        self.cache.update_play_head(frame_index);
+    #[tokio::test]
+    async fn test_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stats.asvp");
+        write_asvp(&path, &[0x40, 0x80]);
+        let processor = AlphaStreamProcessorBuilder::new().build_asvp(path.to_str().unwrap(), 64, 64).await.unwrap();
+        assert_eq!(processor.stats().await.average_decode, None);
+        assert_eq!(processor.bytes_read(), 0);
+        assert!(processor.get_frame(0, 64, 64).await.is_none());
+        processor.get_frame_wait(0, std::time::Duration::from_secs(5)).await.unwrap();
+        let stats = processor.stats().await;
+        assert!(stats.cache_misses >= 1);
+        assert!(stats.cache_hits >= 1);
+        assert!(stats.ready_frames >= 1);
+        assert!(stats.average_decode.is_some());
+        // At least frame 0; prefetched frames and frames decoded again count too
+        let frame_sizes = processor.format.lock().await.frame_sizes().to_vec();
+        assert!(processor.bytes_read() >= frame_sizes[0]);
+        // Peeking at the cache does not count
+        processor.cached_frame(0).unwrap();
+        assert_eq!(processor.stats().await.cache_hits, stats.cache_hits);
+    }
 
-        if let Some(frame_data) = self.cache.get(frame_index) { // Cache check
-            if frame_data.triangle_strip.is_some() {
-                return frame_data.triangle_strip.clone(); // Return cached vertices
-            }
-        }
-        // Schedule processing
-        let mut scheduler = self.scheduler.lock().await;
-        let task = Task::with_priority(frame_index, 10);
-        scheduler.schedule_task(task);
+    #[tokio::test]
+    async fn test_bake() {
+        use crate::rasterizer::{OutputPacking, PixelFormat};
 
-        // Prefetch if sequential access detected
-        AlphaStreamProcessor::maybe_trigger_prefetch(&mut scheduler, frame_index).await;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scene.asvp");
+        let baked = dir.path().join("scene.bake");
+        write_asvp(&path, &[0x40, 0x80, 0x40]);
+        let timeout = std::time::Duration::from_secs(5);
+        let processor = AlphaStreamProcessorBuilder::new().build_asvp(path.to_str().unwrap(), 64, 64).await.unwrap();
+        let report = processor.bake(&baked).await.unwrap();
+        assert_eq!(report.succeeded, 3);
+        assert!(report.failed.is_empty());
+        let expected = processor.get_frame_wait(1, timeout).await.unwrap();
 
-        None
+        // A different source with as many frames: frames come from the bake, not from decoding it
+        let other = dir.path().join("other.asvp");
+        write_asvp(&other, &[1, 2, 3]);
+        let from_bake = AlphaStreamProcessorBuilder::new().baked_scene(&baked).build_asvp(other.to_str().unwrap(), 64, 64).await.unwrap();
+        assert_eq!(from_bake.get_frame_wait(1, timeout).await.unwrap(), expected);
+        assert_eq!(from_bake.get_polystream(1).await.unwrap(), polystream(0x80));
+        // Triangle strips were not baked, so they are made from the source
+        let both = AlphaStreamProcessorBuilder::new().baked_scene(&baked).processing_mode(ProcessingMode::Both)
+            .build_asvp(other.to_str().unwrap(), 64, 64).await.unwrap();
+        both.get_frame_wait(1, timeout).await.unwrap();
+        assert_eq!(both.get_polystream(1).await.unwrap(), polystream(2));
+
+        // Size and frame count must match
+        assert!(AlphaStreamProcessorBuilder::new().baked_scene(&baked).build_asvp(other.to_str().unwrap(), 32, 32).await.is_err());
+        write_asvp(&other, &[1, 2]);
+        assert!(AlphaStreamProcessorBuilder::new().baked_scene(&baked).build_asvp(other.to_str().unwrap(), 64, 64).await.is_err());
+        // The cache's layout is the only one baked
+        let mut packed = AlphaStreamProcessorBuilder::new().build_asvp(path.to_str().unwrap(), 64, 64).await.unwrap();
+        packed.set_output_packing(OutputPacking::new(PixelFormat::R16));
+        assert!(packed.bake(dir.path().join("packed.bake")).await.is_err());
     }
 
-    /// Request a frame for processing
-    pub async fn request_frame(&self, frame_index: u32) -> Result<(), FormatError> {
-        // Check bounds using metadata
-        let meta = self.metadata().await?;
-        if frame_index as usize >= meta.frame_count as usize {
-            println!("[alphastream] Requested frame_index {} out of bounds (max {})", frame_index, meta.frame_count);
-            return Ok(()); // Silently ignore or return error if preferred
+    #[tokio::test]
+    async fn test_build_from_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("embedded.asvp");
+        write_asvp(&path, &[0x40, 0x80]);
+        let bytes = std::fs::read(&path).unwrap();
+        let timeout = std::time::Duration::from_secs(5);
+        let from_file = AlphaStreamProcessorBuilder::new().build_asvp(path.to_str().unwrap(), 64, 64).await.unwrap();
+        let from_memory = AlphaStreamProcessorBuilder::new().build_from_bytes(bytes.clone(), 64, 64).await.unwrap();
+        for frame_index in 0..2 {
+            assert_eq!(from_memory.get_frame_wait(frame_index, timeout).await, from_file.get_frame_wait(frame_index, timeout).await);
         }
-        // Check if already in cache
-        if self.cache.contains(&(frame_index as usize)) {
-            return Ok(());
+        // There is no file to watch
+        assert!(AlphaStreamProcessorBuilder::new().watch_source(true).build_from_bytes(bytes, 64, 64).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_resolution() {
+        use crate::watermark::Watermark;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("adaptive.asvp");
+        let mut writer = crate::formats::ASVPWriter::new(std::fs::File::create(&path).unwrap());
+        for _ in 0..2 {
+            writer.add_frame(crate::formats::FrameData { polystream: two_channel_polystream(), bitmap: None, triangle_strip: None });
         }
+        writer.write_all().unwrap();
+        let watermark = Watermark::new(0x5EED, 42);
+        let processor = AlphaStreamProcessorBuilder::new()
+            .watermark(watermark)
+            .build_asvp(path.to_str().unwrap(), 300, 200).await.unwrap();
+        let mut full = processor.rasterize_polystream(&two_channel_polystream());
+        processor.watermark_mask(0, &mut full);
 
-        // Schedule the frame for decoding
-        let mut scheduler = self.scheduler.lock().await;
-        let task = Task::new(frame_index as usize);
-        scheduler.schedule_task(task);
-        Ok(())
-    }
+        assert_eq!(processor.report_display_size(300, 200), 1);
+        assert_eq!(processor.report_display_size(100, 60), 2);
+        // Picture-in-picture: the cache holds a quarter-size mask, handed out at the full size
+        assert_eq!(processor.report_display_size(40, 30), 4);
+        let _ = processor.get_frame(0, 300, 200).await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        assert_eq!(processor.cache.get(0).unwrap().bitmap.unwrap().len(), 75 * 50);
+        let small = processor.get_frame(0, 300, 200).await.unwrap();
+        assert_eq!(small.len(), 300 * 200);
+        assert!(watermark.verify(&small, 0));
+        // Same shape, to within a reduced pixel
+        let (small_box, full_box) = (crate::stats::MaskStats::from_mask(&small, 300, 200).bbox, crate::stats::MaskStats::from_mask(&full, 300, 200).bbox);
+        assert!(small_box.x.abs_diff(full_box.x) < 4 && small_box.y.abs_diff(full_box.y) < 4, "{:?} {:?}", small_box, full_box);
+        assert!(small_box.w.abs_diff(full_box.w) < 8 && small_box.h.abs_diff(full_box.h) < 8, "{:?} {:?}", small_box, full_box);
 
-    /// Detect sequential access and trigger prefetching if needed
-    async fn maybe_trigger_prefetch(scheduler: &mut Scheduler, current_frame: usize) {
-        // Always trigger prefetch for the current frame
-        // println!("[alphastream] Prefetch always triggered for frame {}", current_frame);
-        scheduler.prefetch(current_frame);
-        // Optionally, you can keep the static for future use, but it's not needed anymore
+        // Fullscreen again: rasterized from the cached polystream, exactly as without adapting
+        assert_eq!(processor.report_display_size(0, 0), 1);
+        assert_eq!(processor.get_frame(0, 300, 200).await.unwrap(), full);
+        assert_eq!(processor.cache.get(0).unwrap().bitmap.unwrap(), full);
+        let output = processor.get_frame_output(0).await.unwrap();
+        assert_eq!(output.bitmap.unwrap(), full);
     }
 
-    /// Start background processing of scheduler tasks
-    /// This method spawns an async task that runs in the background, continuously processing scheduled frames.
-    /// It uses tokio::spawn to create a separate async task that doesn't block the main thread.
-    /// The task runs in a loop, getting work from the scheduler and processing it.
-    fn start_background_processing(&mut self) {
-        use futures::stream::FuturesUnordered;
-        use futures::StreamExt;
-        let scheduler_clone = Arc::clone(&self.scheduler);
-        let format_clone = Arc::clone(&self.format);
-        let width = self.width;
-        let height = self.height;
-        let mode = self.mode.clone();
-        let cache_clone = Arc::clone(&self.cache);
-        let handle = self.runtime.as_ref().unwrap().spawn(async move {
-            let mut running_tasks = FuturesUnordered::new();
-            loop {
-                // Fill up to max_concurrent tasks
-                {
-                    let mut scheduler = scheduler_clone.lock().await;
-                    // let num_queued_tasks = scheduler.get_number_of_queued_tasks();
-                    // let num_active_tasks = scheduler.get_number_of_active_tasks();
-                    // let num_max_concurrent = scheduler.get_number_of_max_concurrent_tasks();
-                    // let num_running_tasks = running_tasks.len();
-                    // println!("[alphastream debug] Background processing loop: {} queued tasks, {} active tasks, {} max concurrent, {} running tasks", num_queued_tasks, num_active_tasks, num_max_concurrent, num_running_tasks);
-                    while let Some(task) = scheduler.next_task() {
-                        let frame_index = task.frame_index;
-                        let format = Arc::clone(&format_clone);
-                        let cache = Arc::clone(&cache_clone);
-                        let mode = mode.clone();
-                        // Capture generation when task is scheduled for stale task detection
-                        let task_generation = cache.generation();
-                        let handle = tokio::spawn(async move {
-                            let mut format = format.lock().await;
-                            let frame_data = match format.decode_frame(frame_index as u32).await {
-                                Ok(data) => data,
-                                Err(e) => {
-                                    println!("[alphastream] Error decoding frame {}: {}", frame_index, e);
-                                    return (frame_index, false);
-                                }
-                            };
-                            let (_channel_count, channel_sizes, channel_data) = AlphaStreamProcessor::parse_polystream(&frame_data.polystream);
-                            let mut bitmap = None;
-                            let mut triangle_strip = None;
-                            if matches!(mode, ProcessingMode::Bitmap | ProcessingMode::Both) {
-                                let mut mask = vec![0u8; (width * height) as usize];
-                                let mut offset = 0;
-                                for &size in &channel_sizes {
-                                    let channel_data_slice = &channel_data[offset..offset + size as usize];
-                                    let channel_mask = PolystreamRasterizer::rasterize(channel_data_slice, width, height);
-                                    for (i, &pixel) in channel_mask.iter().enumerate() {
-                                        if pixel > 0 {
-                                            mask[i] = 255;
-                                        }
-                                    }
-                                    offset += size as usize;
-                                }
-                                bitmap = Some(mask);
-                            }
-                            if matches!(mode, ProcessingMode::TriangleStrip | ProcessingMode::Both) {
-                                let mut vertices = Vec::new();
-                                let mut offset = 0;
-                                for &size in &channel_sizes {
-                                    let channel_data_slice = &channel_data[offset..offset + size as usize];
-                                    let channel_strip = PolystreamRasterizer::polystream_to_triangle_strip(channel_data_slice);
-                                    vertices.extend(channel_strip);
-                                    offset += size as usize;
-                                }
-                                triangle_strip = Some(vertices);
-                            }
-                            let processed_frame = FrameData {
-                                polystream: frame_data.polystream,
-                                // clone here?
-                                bitmap,
-                                triangle_strip,
-                            };
-                            
-                            // Check generation before inserting - discard stale results
-                            // This handles the case where a seek occurred while this task was in-flight
-                            if cache.generation() == task_generation {
-                                // insert() also checks is_in_range() as a secondary guard
-                                cache.insert(frame_index, processed_frame);
-                            }
-                            // let thread_id = std::thread::current().id();
-                            // println!("[alphastream debug] Frame {} processed [thread {:?} task gen {}]", frame_index, thread_id, task_generation);
-                            (frame_index, true)
-                        });
-                        running_tasks.push(async move {
-                            let res = handle.await;
-                            (frame_index, res.is_ok())
-                        });
-                    }
-                }
-                // Poll for completed tasks
-                if let Some((_frame_index, _ok)) = running_tasks.next().await {
-                    // let wait_start = std::time::Instant::now();
-                    let mut scheduler = scheduler_clone.lock().await;
-                    scheduler.complete_task();
-                    // let wait_duration = wait_start.elapsed();
-                    // println!("[alphastream debug] Completed tasks in {} ms", wait_duration.as_millis());
-                } else {
-                    // No running tasks, sleep briefly
-                    tokio::time::sleep(tokio::time::Duration::from_millis(1)).await;
-                }
-            }
-        });
+    #[tokio::test]
+    async fn test_export_report() {
+        use super::{retime, FrameErrorKind, RetimeMethod};
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("damaged.asvp");
+        write_asvp(&path, &[1, 2, 3]);
+        // Break the zlib checksum of the last frame
+        let mut bytes = std::fs::read(&path).unwrap();
+        *bytes.last_mut().unwrap() ^= 0xFF;
+        std::fs::write(&path, bytes).unwrap();
+        let processor = AlphaStreamProcessorBuilder::new().build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
 
-        self.background_handle = Some(handle);
-    }
+        let mut exported = Vec::new();
+        let report = processor.export_frames(0..3, |frame| {
+            exported.push(frame.output.frame_index);
+            Ok(())
+        }).await.unwrap();
+        assert_eq!(exported, [0, 1]);
+        assert_eq!(report.succeeded, 2);
+        assert_eq!(report.failed_indices(), [2]);
+        assert_eq!(report.failed[0].kind, FrameErrorKind::Zlib);
+        assert!(!report.is_complete());
 
-    // /// Process pending tasks (decode frames)
-    // pub async fn process_tasks(&mut self) -> Result<(), FormatError> {
-    //     let mut scheduler = self.scheduler.lock().await;
-    //     while let Some(task) = scheduler.next_task() {
-    //         let format_clone = Arc::clone(&self.format);
-    //         let frame_index = task.frame_index;
-    //         let handle = self.runtime.as_ref().unwrap().spawn_blocking(move || {
-    //             let mut format = format_clone.blocking_lock();
-    //             format.decode_frame(frame_index as u32)
-    //         });
-    //         let result = handle.await.map_err(|e| FormatError::InvalidFormat(format!("Join error: {}", e)))?;
-    //         let frame_data = result?;
-    //         self.cache.insert(frame_index, frame_data);
-    //         scheduler.complete_task();
-    //     }
-    //     Ok(())
-    // }
+        // Sink failures are listed too, and the job still visits every frame
+        let report = processor.export_frames(0..2, |frame| match frame.output.frame_index {
+            0 => Err(std::io::Error::other("encoder exited")),
+            _ => Ok(()),
+        }).await.unwrap();
+        assert_eq!((report.succeeded, report.failed_indices()), (1, vec![0]));
+        assert_eq!(report.failed[0].kind, FrameErrorKind::Sink);
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains("\"kind\":\"sink\""), "{}", json);
 
-}
+        // Retiming keeps going with an empty frame in place of the damaged one
+        let output = dir.path().join("retimed.asvp");
+        let report = retime(path.to_str().unwrap(), &output, 30.0, 30.0, RetimeMethod::Nearest).await.unwrap();
+        assert_eq!(report.target_frames, 3);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].frame_index, 2);
+        let retimed = AlphaStreamProcessorBuilder::new().build_asvp(output.to_str().unwrap(), 16, 16).await.unwrap();
+        assert_eq!(retimed.export_frames(0..3, |_| Ok(())).await.unwrap().succeeded, 3);
+    }
 
-impl Drop for AlphaStreamProcessor {
-    /// Cleanup when the processor is destroyed
-    /// This ensures background tasks are stopped and resources are properly freed.
-    /// Important for preventing resource leaks in long-running programs.
-    fn drop(&mut self) {
-        if let Some(handle) = self.background_handle.take() {
-            handle.abort(); // Stop the background processing task
-        }
-        if let Some(runtime) = self.runtime.take() {
-            std::thread::spawn(move || drop(runtime)); // Clean up async runtime in separate thread
+    #[tokio::test]
+    async fn test_export_frames_with_overlay() {
+        use super::Overlay;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("source.asvp");
+        write_asvp(&path, &[1, 2]);
+        let processor = AlphaStreamProcessorBuilder::new().build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
+
+        let mut plain = Vec::new();
+        processor.export_frames(0..2, |frame| {
+            plain.push(frame.output.bitmap.unwrap());
+            Ok(())
+        }).await.unwrap();
+        let overlay = Overlay { frame_number: true, ..Overlay::default() };
+        let mut burnt = Vec::new();
+        let report = processor.export_frames_with_overlay(0..2, &overlay, |frame| {
+            burnt.push(frame.output.bitmap.unwrap());
+            Ok(())
+        }).await.unwrap();
+        assert_eq!(report.succeeded, 2);
+        for (frame_index, (plain, burnt)) in plain.iter().zip(&burnt).enumerate() {
+            let mut expected = plain.clone();
+            overlay.apply(&mut expected, 16, 16, frame_index as u32);
+            assert_eq!(burnt, &expected);
+            assert_ne!(burnt, plain);
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::testlib::create_test_asvp;
-    use crate::api::AlphaStreamProcessorBuilder;
-    use crate::AlphaStreamProcessor;
-    use crate::ProcessingMode;
+    #[tokio::test]
+    async fn test_export_png_resumes() {
+        use crate::layout::{ExportLayout, NameTemplate};
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("source.asvp");
+        write_asvp(&path, &[1, 2, 3]);
+        let processor = AlphaStreamProcessorBuilder::new().build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
+        let output = dir.path().join("frames");
+        let template = NameTemplate::parse("{scene}/{frame:04}.png").unwrap();
 
-    #[test]
-    fn test_builder_defaults_and_overrides() {
-        let builder = AlphaStreamProcessorBuilder::new();
-        assert_eq!(builder.runtime_threads, 0);
-        assert_eq!(builder.timeout_seconds, 30);
-        assert_eq!(builder.cache_capacity, 512);
-        assert_eq!(builder.prefetch_window, 16);
-        assert_eq!(builder.processing_mode, ProcessingMode::Bitmap);
+        let mut layout = ExportLayout::open(&output, template.clone(), "source").unwrap();
+        let report = processor.export_png(0..3, &mut layout).await.unwrap();
+        assert_eq!((report.succeeded, report.skipped), (3, 0));
+        let png = std::fs::read(output.join("source/0001.png")).unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
 
-        let builder = builder
-            .runtime_threads(32)
-            .timeout_seconds(120)
-            .cache_capacity(1024)
-            .prefetch_window(25)
-            .processing_mode(ProcessingMode::Both);
-        assert_eq!(builder.runtime_threads, 32);
-        assert_eq!(builder.timeout_seconds, 120);
-        assert_eq!(builder.cache_capacity, 1024);
-        assert_eq!(builder.prefetch_window, 25);
-        assert_eq!(builder.processing_mode, ProcessingMode::Both);
+        // An interrupted run lost the last frame: only that one is exported again
+        std::fs::remove_file(output.join("source/0002.png")).unwrap();
+        let mut layout = ExportLayout::open(&output, template, "source").unwrap();
+        let report = processor.export_png(0..3, &mut layout).await.unwrap();
+        assert_eq!((report.succeeded, report.skipped), (1, 2));
+        assert!(report.is_complete());
+        assert!(output.join("source/0002.png").exists());
     }
 
     #[tokio::test]
-    async fn test_builder_build_asvp_and_processing() {
-        let test_file = create_test_asvp(1).unwrap();
-        let builder = AlphaStreamProcessorBuilder::new()
-            .runtime_threads(4)
-            .cache_capacity(16)
-            .prefetch_window(2)
-            .processing_mode(ProcessingMode::Both);
-        let processor = builder.build_asvp(test_file.path().to_str().unwrap(), 16, 16).await.unwrap();
-        let metadata = processor.metadata().await.unwrap();
-        assert_eq!(metadata.frame_count, 1);
-        let _ = processor.get_frame(0, 16, 16).await;
-        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-        let frame = processor.get_frame(0, 16, 16).await;
-        assert!(frame.is_some());
+    async fn test_fingerprint_alignment() {
+        use crate::fingerprint::align;
+        let dir = tempfile::tempdir().unwrap();
+        let (original, trimmed) = (dir.path().join("original.asvp"), dir.path().join("trimmed.asvp"));
+        write_asvp(&original, &[1, 2, 3, 4, 5, 6, 7, 8]);
+        write_asvp(&trimmed, &[4, 5, 6, 7]);
+        let original = AlphaStreamProcessorBuilder::new().build_asvp(original.to_str().unwrap(), 16, 16).await.unwrap();
+        let trimmed = AlphaStreamProcessorBuilder::new().build_asvp(trimmed.to_str().unwrap(), 16, 16).await.unwrap();
+
+        let fingerprints = original.fingerprint(0..100).await.unwrap();
+        assert_eq!(fingerprints.len(), 8);
+        assert_eq!(fingerprints[2..4], original.fingerprint(2..4).await.unwrap());
+        let alignment = align(&fingerprints, &trimmed.fingerprint(0..4).await.unwrap()).unwrap();
+        // Frame 3 of the original is frame 0 of the trimmed copy
+        assert_eq!((alignment.offset, alignment.matches, alignment.overlap), (-3, 4, 4));
     }
-    // 
-    // #[test]
-    // fn test_builder_build_asvr_and_metadata() {
-    //     use crate::testlib::create_test_asvr;
-    //     let test_file = create_test_asvr();
-    //     let builder = AlphaStreamProcessorBuilder::new()
-    //         .runtime_threads(2)
-    //         .cache_capacity(8)
-    //         .prefetch_window(1)
-    //         .processing_mode(ProcessingMode::Bitmap);
-    //     // Use dummy scene_id, version, base_url for test
-    //     let scene_id = 42;
-    //     let version = b"1.5.0";
-    //     let base_url = b"test.asvr";
-    //     let processor = builder.build_asvr(
-    //         test_file.path().to_str().unwrap(),
-    //         scene_id,
-    //         version,
-    //         base_url,
-    //         8,
-    //         8,
-    //     ).unwrap();
-    //     let rt = tokio::runtime::Runtime::new().unwrap();
-    //     let metadata = rt.block_on(async { processor.metadata().await.unwrap() });
-    //     assert_eq!(metadata.frame_count, 1);
-    // }
 
     #[tokio::test]
-    async fn test_asvp_processor() {
-        let test_file = create_test_asvp(1).unwrap();
-        let processor = AlphaStreamProcessor::new_asvp(
-            test_file.path().to_str().unwrap(),
-            16,
-            16,
-            ProcessingMode::Both,
-        ).await.unwrap();
+    async fn test_seek_preroll() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preroll.asvp");
+        write_asvp(&path, &[1; 30]);
+        let processor = AlphaStreamProcessorBuilder::new()
+            .cache_capacity(8)
+            .seek_preroll(2)
+            .build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
+        assert_eq!(processor.config().seek_preroll, 2);
 
-        let metadata = processor.metadata().await.unwrap();
-        assert_eq!(metadata.frame_count, 1);
-
-        // trigger processing
         let _ = processor.get_frame(0, 16, 16).await;
-        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-        let frame = processor.get_frame(0, 16, 16).await;
-        assert!(frame.is_some()); // Accept only Some, do not unwrap None or Err
-
-        let _ = processor.get_triangle_strip_vertices(0).await;
-        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-        let vertices = processor.get_triangle_strip_vertices(0).await.unwrap();
-        assert_eq!(vertices.len(), 174);
+        assert!(processor.get_frame(20, 16, 16).await.is_none());
+        assert_eq!(processor.cache.get_start_index(), 18);
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        assert!(processor.cache.contains(&18) && processor.cache.contains(&19));
+        // History reads do not move the play head or drop the window
+        assert!(processor.get_frame(18, 16, 16).await.is_some());
+        assert_eq!(processor.cache.get_play_head(), 20);
+        assert!(processor.get_frame(20, 16, 16).await.is_some());
     }
 
     #[tokio::test]
-    async fn test_request_frame() {
-        let test_file = create_test_asvp(1).unwrap();
-        let processor = AlphaStreamProcessor::new_asvp(
-            test_file.path().to_str().unwrap(),
-            16,
-            16,
-            ProcessingMode::Bitmap,
-        ).await.unwrap();
+    async fn test_request_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("range.asvp");
+        write_asvp(&path, &[1, 2, 3, 4, 5]);
+        let processor = AlphaStreamProcessorBuilder::new()
+            .processing_mode(ProcessingMode::PolystreamOnly)
+            .build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
 
-        // Request a frame
-        processor.request_frame(0).await.unwrap();
+        processor.request_range(1..100, crate::scheduler::Priority::Normal).await.unwrap();
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        for frame in 1..5 {
+            assert!(processor.cache.contains(&frame), "frame {} not decoded", frame);
+        }
+        assert_eq!(processor.scheduler.lock().await.get_number_of_queued_range_frames(), 0);
+    }
 
-        // wait for processing to complete
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-        
-        // Should now be in cache
-        let frame = processor.get_frame(0, 16, 16).await;
-        match frame {
-            Some(data) => assert_eq!(data.len(), 256),
-            None => (), // Accept None, do not panic
+    #[tokio::test]
+    async fn test_visible_range_prefetch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("visible.asvp");
+        write_asvp(&path, &[1; 40]);
+        let processor = AlphaStreamProcessorBuilder::new()
+            .prefetch_window(8)
+            .processing_mode(ProcessingMode::PolystreamOnly)
+            .build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
+
+        // Thumbnails 30..33 are on screen while the play head sits at the start
+        processor.set_visible_range(30, 33).await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
+        for frame in 30..33 {
+            assert!(processor.cache.contains(&frame), "visible frame {} not prefetched", frame);
         }
+        assert!(processor.cache.contains(&1));
+        // The read-ahead gave up part of its budget to the visible frames
+        assert!(!processor.cache.contains(&8));
     }
 
     #[tokio::test]
-    async fn test_processing_modes() {
-        let test_file = create_test_asvp(1).unwrap();
-        let processor = AlphaStreamProcessor::new_asvp(
-            test_file.path().to_str().unwrap(),
-            16,
-            16,
-            ProcessingMode::Bitmap,
-        ).await.unwrap();
+    async fn test_poll_events() {
+        use crate::api::ProcessorEvent;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.asvp");
+        write_asvp(&path, &[1, 2]);
+        let processor = AlphaStreamProcessorBuilder::new()
+            .build_asvp(path.to_str().unwrap(), 16, 16).await.unwrap();
 
-        // Manually insert frame data to test modes
-        let frame_data = crate::formats::FrameData {
-            polystream: vec![1, 0, 0, 0, 0], // minimal polystream
-            bitmap: Some(vec![255; 256]),
-            triangle_strip: Some(vec![0.0; 12]),
-        };
-        processor.cache.insert(0, frame_data);
+        // Nothing is queued until events are enabled
+        let _ = processor.get_frame(0, 16, 16).await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        assert!(processor.poll_events().is_empty());
 
-        // Test bitmap mode
-        let bitmap = processor.get_frame(0, 16, 16).await.unwrap();
-        assert_eq!(bitmap.len(), 256);
+        processor.enable_events(true);
+        processor.cache.clear();
+        let _ = processor.get_frame(0, 16, 16).await;
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+        let events = processor.poll_events();
+        assert!(events.contains(&ProcessorEvent::FrameReady(0)), "{:?}", events);
+        assert!(processor.poll_events().is_empty());
 
-        // Test triangle strip mode
-        let vertices = processor.get_triangle_strip_vertices(0).await.unwrap();
-        assert_eq!(vertices.len(), 12);
+        write_asvp(&path, &[1, 3]);
+        processor.reload().await.unwrap();
+        // Frame 1 changed (prefetched frames past the end are evicted as well)
+        assert!(matches!(processor.poll_events()[..], [ProcessorEvent::SourceReloaded(n)] if n >= 1));
     }
 
     #[tokio::test]
@@ -0,0 +1,84 @@
+// Backend module
+// Rasterizer implementations a processor can run on, with what each of them can do on this machine.
+// Hosts list them (`available_backends`, `CV_list_backends` from C) and pick one per machine, e.g. a
+// GPU backend where one is present and the CPU rasterizer elsewhere. Only the CPU rasterizer exists
+// so far; accelerated backends are added as variants and report themselves unavailable when the
+// machine lacks the hardware or driver.
+
+use serde::{Deserialize, Serialize};
+
+/// Largest output width or height the CPU rasterizer accepts
+pub const CPU_MAX_TEXTURE_SIZE: u32 = 16384;
+
+/// A rasterizer implementation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum Backend {
+    /// Scanline rasterizer on the processor's worker threads; available everywhere
+    #[default]
+    Cpu,
+}
+
+/// Capabilities of a backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackendInfo {
+    pub backend: Backend,
+    /// Short lowercase name, e.g. for a settings menu or a log line
+    pub name: &'static str,
+    /// Largest output width or height
+    pub max_texture_size: u32,
+    /// Whether masks are read back from the device without stalling the worker that rasterized them
+    pub async_readback: bool,
+}
+
+impl Backend {
+    /// Every backend this build knows about, available on this machine or not
+    pub const ALL: &'static [Backend] = &[Backend::Cpu];
+
+    /// Stable number of the backend, used across the C API
+    pub fn id(self) -> u32 {
+        match self {
+            Backend::Cpu => 0,
+        }
+    }
+
+    /// The backend with the number `id`
+    pub fn from_id(id: u32) -> Option<Self> {
+        Self::ALL.iter().copied().find(|backend| backend.id() == id)
+    }
+
+    pub fn info(self) -> BackendInfo {
+        match self {
+            Backend::Cpu => BackendInfo { backend: self, name: "cpu", max_texture_size: CPU_MAX_TEXTURE_SIZE, async_readback: false },
+        }
+    }
+
+    /// Whether the backend can run on this machine
+    pub fn is_available(self) -> bool {
+        match self {
+            Backend::Cpu => true,
+        }
+    }
+}
+
+/// Backends that can run on this machine, the CPU rasterizer first
+pub fn available_backends() -> Vec<BackendInfo> {
+    Backend::ALL.iter().filter(|backend| backend.is_available()).map(|backend| backend.info()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_available_backends() {
+        let backends = available_backends();
+        assert_eq!(backends[0].backend, Backend::Cpu);
+        assert_eq!(backends[0].name, "cpu");
+        assert!(backends.iter().all(|info| info.max_texture_size > 0 && info.backend.is_available()));
+        for &backend in Backend::ALL {
+            assert_eq!(Backend::from_id(backend.id()), Some(backend));
+        }
+        assert_eq!(Backend::from_id(99), None);
+    }
+}
@@ -0,0 +1,192 @@
+// Bake module
+// Pre-processed scenes: every frame's mask and/or triangle strip at one output size, produced ahead of
+// time (`AlphaStreamProcessor::bake`) so a processor built with `baked_scene` serves frames from disk
+// without decrypting, inflating or rasterizing them. Trades disk space (width*height bytes per mask)
+// for steady playback on CPUs too slow to decode at the display rate.
+//
+// Layout of a baked scene (little-endian):
+//   [ "ASBAKED1" ] [ u32 width ] [ u32 height ] [ u32 frame_count ] [ u32 reserved ]
+//   [ frame_count x (u64 offset, u32 length) ] [ frame records ] ...
+// Every record is a serialized FrameData (see docs/FILE_FORMAT_FRAME_DATA.md). Equal frames, such as
+// empty or static stretches, share one record. A length of 0 marks a frame that was not baked; the
+// processor decodes it from the source instead.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use sha2::{Digest, Sha256};
+
+use crate::formats::{FormatError, FrameData};
+
+/// Magic of a baked scene file
+pub const BAKE_MAGIC: &[u8; 8] = b"ASBAKED1";
+
+const HEADER_SIZE: u64 = 24;
+const INDEX_ENTRY_SIZE: u64 = 12;
+
+/// Writes a baked scene. Frames are added in any order; frames never added are left unbaked.
+/// The file only appears at `path` once `finish` succeeds, so an interrupted bake leaves nothing
+/// a processor could pick up.
+pub struct BakeWriter {
+    file: BufWriter<tempfile::NamedTempFile>,
+    path: std::path::PathBuf,
+    width: u32,
+    height: u32,
+    index: Vec<(u64, u32)>,
+    /// Offset and length of every record written, by SHA-256 of its bytes
+    records: HashMap<[u8; 32], (u64, u32)>,
+    offset: u64,
+}
+
+impl BakeWriter {
+    pub fn create(path: impl AsRef<Path>, width: u32, height: u32, frame_count: u32) -> Result<Self, FormatError> {
+        let path = path.as_ref().to_path_buf();
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let mut file = BufWriter::new(tempfile::NamedTempFile::new_in(dir)?);
+        // The index is written once all records are in place
+        let offset = HEADER_SIZE + frame_count as u64 * INDEX_ENTRY_SIZE;
+        file.write_all(&vec![0u8; offset as usize])?;
+        Ok(Self { file, path, width, height, index: vec![(0, 0); frame_count as usize], records: HashMap::new(), offset })
+    }
+
+    /// Store the processed `frame` for `frame_index`
+    pub fn add_frame(&mut self, frame_index: u32, frame: &FrameData) -> Result<(), FormatError> {
+        let Some(slot) = self.index.get_mut(frame_index as usize) else {
+            return Err(FormatError::InvalidFormat(format!("Frame {} is past the last frame of the bake", frame_index)));
+        };
+        let bytes = frame.to_bytes();
+        let hash: [u8; 32] = Sha256::digest(&bytes).into();
+        *slot = match self.records.get(&hash) {
+            Some(&record) => record,
+            None => {
+                let record = (self.offset, bytes.len() as u32);
+                self.file.write_all(&bytes)?;
+                self.offset += bytes.len() as u64;
+                self.records.insert(hash, record);
+                record
+            }
+        };
+        Ok(())
+    }
+
+    /// Frames that share a record with an earlier frame
+    pub fn deduplicated(&self) -> usize {
+        self.index.iter().filter(|&&(_, length)| length > 0).count() - self.records.len()
+    }
+
+    /// Write the index and move the file into place
+    pub fn finish(mut self) -> Result<(), FormatError> {
+        let mut header = Vec::with_capacity((HEADER_SIZE + self.index.len() as u64 * INDEX_ENTRY_SIZE) as usize);
+        header.extend_from_slice(BAKE_MAGIC);
+        header.extend_from_slice(&self.width.to_le_bytes());
+        header.extend_from_slice(&self.height.to_le_bytes());
+        header.extend_from_slice(&(self.index.len() as u32).to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        for &(offset, length) in &self.index {
+            header.extend_from_slice(&offset.to_le_bytes());
+            header.extend_from_slice(&length.to_le_bytes());
+        }
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header)?;
+        let file = self.file.into_inner().map_err(|e| e.into_error())?;
+        file.persist(&self.path).map_err(|e| e.error)?;
+        Ok(())
+    }
+}
+
+/// A baked scene opened for playback
+#[derive(Debug)]
+pub struct BakedScene {
+    file: Mutex<File>,
+    width: u32,
+    height: u32,
+    index: Vec<(u64, u32)>,
+}
+
+impl BakedScene {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, FormatError> {
+        let mut file = File::open(path)?;
+        let mut header = [0u8; HEADER_SIZE as usize];
+        file.read_exact(&mut header)?;
+        if &header[0..8] != BAKE_MAGIC {
+            return Err(FormatError::InvalidFormat("Not a baked scene".to_string()));
+        }
+        let field = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+        let (width, height, frame_count) = (field(8), field(12), field(16));
+        let mut table = vec![0u8; frame_count as usize * INDEX_ENTRY_SIZE as usize];
+        file.read_exact(&mut table)?;
+        let index = table
+            .chunks_exact(INDEX_ENTRY_SIZE as usize)
+            .map(|entry| (u64::from_le_bytes(entry[0..8].try_into().unwrap()), u32::from_le_bytes(entry[8..12].try_into().unwrap())))
+            .collect();
+        Ok(Self { file: Mutex::new(file), width, height, index })
+    }
+
+    /// Output size the scene was baked at
+    pub fn width(&self) -> u32 { self.width }
+    pub fn height(&self) -> u32 { self.height }
+    pub fn frame_count(&self) -> u32 { self.index.len() as u32 }
+
+    /// Whether `frame_index` was baked
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn contains(&self, frame_index: u32) -> bool {
+        self.index.get(frame_index as usize).is_some_and(|&(_, length)| length > 0)
+    }
+
+    /// The processed frame, None if it was not baked
+    pub fn read_frame(&self, frame_index: u32) -> Result<Option<FrameData>, FormatError> {
+        let Some(&(offset, length)) = self.index.get(frame_index as usize).filter(|&&(_, length)| length > 0) else {
+            return Ok(None);
+        };
+        let mut record = vec![0u8; length as usize];
+        {
+            let mut file = self.file.lock().unwrap();
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut record)?;
+        }
+        FrameData::from_bytes(&record).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(fill: u8) -> FrameData {
+        FrameData { polystream: vec![fill; 8], bitmap: Some(vec![fill; 16]), triangle_strip: None }
+    }
+
+    #[test]
+    fn test_bake_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scene.bake");
+        let mut writer = BakeWriter::create(&path, 4, 4, 4).unwrap();
+        writer.add_frame(0, &frame(1)).unwrap();
+        writer.add_frame(2, &frame(1)).unwrap();
+        writer.add_frame(1, &frame(2)).unwrap();
+        assert!(writer.add_frame(4, &frame(3)).is_err());
+        assert_eq!(writer.deduplicated(), 1);
+        // Nothing appears before the bake is finished
+        assert!(!path.exists());
+        writer.finish().unwrap();
+
+        let scene = BakedScene::open(&path).unwrap();
+        assert_eq!((scene.width(), scene.height(), scene.frame_count()), (4, 4, 4));
+        assert_eq!(scene.read_frame(0).unwrap(), Some(frame(1)));
+        assert_eq!(scene.read_frame(1).unwrap(), Some(frame(2)));
+        assert_eq!(scene.read_frame(2).unwrap(), Some(frame(1)));
+        // Frame 3 was never added, frame 9 does not exist
+        assert!(!scene.contains(3) && scene.contains(2));
+        assert_eq!(scene.read_frame(3).unwrap(), None);
+        assert_eq!(scene.read_frame(9).unwrap(), None);
+        // Two distinct records after the header and index
+        let records = FrameData::to_bytes(&frame(1)).len() + FrameData::to_bytes(&frame(2)).len();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), HEADER_SIZE + 4 * INDEX_ENTRY_SIZE + records as u64);
+
+        std::fs::write(&path, b"ASVPPLN1 and more bytes than a header").unwrap();
+        assert!(matches!(BakedScene::open(&path), Err(FormatError::InvalidFormat(_))));
+    }
+}
@@ -0,0 +1,81 @@
+// `demo bake`: process every frame ahead of time into a baked scene that a processor built with
+// `baked_scene` plays without decrypting, inflating or rasterizing, for machines too slow to decode
+// at the display rate. The scene only plays back at the size it was baked at.
+
+use std::process;
+
+use libalphastream::api::{AlphaStreamProcessorBuilder, ProcessingMode};
+
+use crate::{parse_size, print_usage_and_exit, Source};
+
+/// Entry point for `demo bake`
+pub fn run(mut args: impl Iterator<Item = String>) {
+    let mut source = Source::parse(&mut args);
+    let mut width: u32 = 512;
+    let mut height: u32 = 256;
+    let mut output = String::from("scene.bake");
+    let mut mode = ProcessingMode::Bitmap;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--override-filename-for-decrypt" => match args.next() {
+                Some(val) => source.override_filename_for_decrypt = Some(val),
+                None => {
+                    eprintln!("Expected a filename after --override-filename-for-decrypt");
+                    print_usage_and_exit();
+                }
+            },
+            "--size" => match args.next().as_deref().and_then(parse_size) {
+                Some((w, h)) => {
+                    width = w;
+                    height = h;
+                }
+                None => {
+                    eprintln!("Expected <width>x<height> after --size");
+                    print_usage_and_exit();
+                }
+            },
+            "--output" => match args.next() {
+                Some(val) => output = val,
+                None => {
+                    eprintln!("Expected a file after --output");
+                    print_usage_and_exit();
+                }
+            },
+            // Bake triangle strips as well, for hosts that draw the mask as geometry
+            "--with-vertices" => mode = ProcessingMode::Both,
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                print_usage_and_exit();
+            }
+        }
+    }
+
+    let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+    let builder = AlphaStreamProcessorBuilder::new().processing_mode(mode);
+    let processor = source.open(&rt, builder, width, height);
+    let report = match rt.block_on(processor.bake(&output)) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Bake failed: {}", e);
+            process::exit(1);
+        }
+    };
+    for failure in &report.failed {
+        eprintln!("Frame {} failed: {}", failure.frame_index, failure.message);
+    }
+    let size = std::fs::metadata(&output).map(|m| m.len()).unwrap_or(0);
+    println!(
+        "Baked {} frames at {}x{} to {} ({:.1} MiB) in {:.3} seconds, {} failed",
+        report.succeeded,
+        width,
+        height,
+        output,
+        size as f64 / (1 << 20) as f64,
+        report.elapsed.as_secs_f64(),
+        report.failed.len()
+    );
+    if !report.is_complete() {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,42 @@
+// `demo concat`: merge ASVR files into one, encrypted under new credentials.
+//
+// Every input is given as `<asvr_path> <version> <scene_id>` like the other subcommands; the output
+// is encrypted for its own version and scene_id, with its file name as base_url.
+
+use std::process;
+
+use libalphastream::api::{concat_asvr, SceneCredentials};
+
+use crate::{print_usage_and_exit, Source};
+
+fn credentials(source: &Source) -> SceneCredentials {
+    SceneCredentials::new(source.scene_id, source.version.as_bytes(), source.base_url().as_bytes())
+}
+
+/// Entry point for `demo concat`
+pub fn run(args: impl Iterator<Item = String>) {
+    let mut args = args.peekable();
+    let output = Source::parse(&mut args);
+    let mut inputs = Vec::new();
+    while args.peek().is_some() {
+        inputs.push(Source::parse(&mut args));
+    }
+    if inputs.is_empty() {
+        eprintln!("Missing input files");
+        print_usage_and_exit();
+    }
+
+    let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+    let sources: Vec<_> = inputs.iter().map(|input| (input.path.as_str(), credentials(input))).collect();
+    match rt.block_on(concat_asvr(&sources, std::path::Path::new(&output.path), &credentials(&output))) {
+        Ok(ranges) => {
+            for (input, range) in inputs.iter().zip(ranges) {
+                println!("{}: frames {}..{}", input.path, range.start, range.end);
+            }
+        }
+        Err(e) => {
+            eprintln!("Concatenation failed: {}", e);
+            process::exit(1);
+        }
+    }
+}
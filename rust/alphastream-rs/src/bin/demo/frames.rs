@@ -0,0 +1,111 @@
+// `demo frames`: export every frame as a PNG file, named by a template such as "{scene}/{frame:06}.png".
+//
+// A manifest in the output directory records the files written, so running the same command again after
+// an interruption (or a crash, or Ctrl+C) skips the frames already on disk and exports only the rest.
+// The exit code is 1 when any frame failed; running again retries exactly those.
+
+use std::process;
+
+use libalphastream::api::{AlphaStreamProcessorBuilder, ProcessingMode};
+use libalphastream::layout::{ExportLayout, NameTemplate};
+
+use crate::heatmap::parse_range;
+use crate::{parse_size, print_usage_and_exit, Source};
+
+const DEFAULT_TEMPLATE: &str = "{scene}/{frame:06}.png";
+
+/// Entry point for `demo frames`
+pub fn run(mut args: impl Iterator<Item = String>) {
+    let mut source = Source::parse(&mut args);
+    let mut range: Option<(u32, u32)> = None;
+    let mut width: u32 = 512;
+    let mut height: u32 = 256;
+    let mut output_dir = String::from("frames");
+    let mut template = NameTemplate::parse(DEFAULT_TEMPLATE).expect("default template parses");
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--override-filename-for-decrypt" => match args.next() {
+                Some(val) => source.override_filename_for_decrypt = Some(val),
+                None => {
+                    eprintln!("Expected a filename after --override-filename-for-decrypt");
+                    print_usage_and_exit();
+                }
+            },
+            "--range" => match args.next().as_deref().and_then(parse_range) {
+                Some(r) => range = Some(r),
+                None => {
+                    eprintln!("Expected <start>..<end> after --range");
+                    print_usage_and_exit();
+                }
+            },
+            "--size" => match args.next().as_deref().and_then(parse_size) {
+                Some((w, h)) => {
+                    width = w;
+                    height = h;
+                }
+                None => {
+                    eprintln!("Expected <width>x<height> after --size");
+                    print_usage_and_exit();
+                }
+            },
+            "--output-dir" => match args.next() {
+                Some(val) => output_dir = val,
+                None => {
+                    eprintln!("Expected a directory after --output-dir");
+                    print_usage_and_exit();
+                }
+            },
+            "--template" => match args.next().map(|val| NameTemplate::parse(&val)) {
+                Some(Ok(val)) => template = val,
+                Some(Err(e)) => {
+                    eprintln!("{}", e);
+                    print_usage_and_exit();
+                }
+                None => {
+                    eprintln!("Expected a naming template after --template");
+                    print_usage_and_exit();
+                }
+            },
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                print_usage_and_exit();
+            }
+        }
+    }
+
+    let mut layout = match ExportLayout::open(&output_dir, template, &source.scene_id.to_string()) {
+        Ok(layout) => layout,
+        Err(e) => {
+            eprintln!("Could not open output directory {}: {}", output_dir, e);
+            process::exit(1);
+        }
+    };
+
+    let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+    let builder = AlphaStreamProcessorBuilder::new().processing_mode(ProcessingMode::Bitmap);
+    let processor = source.open(&rt, builder, width, height);
+    let (start, end) = range.unwrap_or((0, u32::MAX));
+
+    let report = match rt.block_on(processor.export_png(start..end, &mut layout)) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Export failed: {}", e);
+            process::exit(1);
+        }
+    };
+    for failure in &report.failed {
+        eprintln!("Frame {} failed: {}", failure.frame_index, failure.message);
+    }
+    println!(
+        "Exported {} frames to {} in {:.3} seconds, {} already done, {} failed",
+        report.succeeded,
+        layout.root().display(),
+        report.elapsed.as_secs_f64(),
+        report.skipped,
+        report.failed.len()
+    );
+    if !report.is_complete() {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,95 @@
+// `demo heatmap`: sum the masks of a frame range and write the result as a 16-bit PNG,
+// stretched so the most covered pixel is white.
+
+use std::process;
+
+use libalphastream::api::{AlphaStreamProcessorBuilder, ProcessingMode};
+
+use crate::{parse_size, print_usage_and_exit, Source};
+
+/// Entry point for `demo heatmap`
+pub fn run(mut args: impl Iterator<Item = String>) {
+    let mut source = Source::parse(&mut args);
+    let mut range: Option<(u32, u32)> = None;
+    let mut width: u32 = 512;
+    let mut height: u32 = 256;
+    let mut output = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--override-filename-for-decrypt" => match args.next() {
+                Some(val) => source.override_filename_for_decrypt = Some(val),
+                None => {
+                    eprintln!("Expected a filename after --override-filename-for-decrypt");
+                    print_usage_and_exit();
+                }
+            },
+            "--range" => match args.next().as_deref().and_then(parse_range) {
+                Some(r) => range = Some(r),
+                None => {
+                    eprintln!("Expected <start>..<end> after --range");
+                    print_usage_and_exit();
+                }
+            },
+            "--size" => match args.next().as_deref().and_then(parse_size) {
+                Some((w, h)) => {
+                    width = w;
+                    height = h;
+                }
+                None => {
+                    eprintln!("Expected <width>x<height> after --size");
+                    print_usage_and_exit();
+                }
+            },
+            "--output" => match args.next() {
+                Some(val) => output = Some(val),
+                None => {
+                    eprintln!("Expected a file name after --output");
+                    print_usage_and_exit();
+                }
+            },
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                print_usage_and_exit();
+            }
+        }
+    }
+    let output = output.unwrap_or_else(|| format!("heatmap-{}.png", source.scene_id));
+
+    let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+    let builder = AlphaStreamProcessorBuilder::new().processing_mode(ProcessingMode::Bitmap);
+    let processor = source.open(&rt, builder, width, height);
+    let (start, end) = range.unwrap_or((0, u32::MAX));
+
+    let started = std::time::Instant::now();
+    let heatmap = match rt.block_on(processor.aggregate_heatmap(start..end)) {
+        Ok(h) => h,
+        Err(e) => {
+            eprintln!("Failed to aggregate heatmap: {}", e);
+            process::exit(1);
+        }
+    };
+    let png = libalphastream::png::encode_gray16(&heatmap.to_gray16(), width, height);
+    if let Err(e) = std::fs::write(&output, png) {
+        eprintln!("Failed to write {}: {}", output, e);
+        process::exit(1);
+    }
+    let max = heatmap.counts.iter().copied().max().unwrap_or(0);
+    println!(
+        "Aggregated {} frames in {:.3} seconds, hottest pixel covered in {} frames, written to {}",
+        heatmap.frames,
+        started.elapsed().as_secs_f64(),
+        max,
+        output
+    );
+}
+
+/// Parse a `<start>..<end>` frame range (end exclusive)
+pub fn parse_range(s: &str) -> Option<(u32, u32)> {
+    let (start, end) = s.split_once("..")?;
+    let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+    if start >= end {
+        return None;
+    }
+    Some((start, end))
+}
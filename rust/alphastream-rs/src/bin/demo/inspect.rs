@@ -0,0 +1,71 @@
+// `demo inspect`: print per-frame mask statistics, optionally only for frames matching --filter.
+//
+// Output is one tab separated line per frame (frame, area, coverage, bbox x/y/w/h) so it can be
+// piped into other tools, e.g. to collect the frame numbers worth looking at in a long archive.
+
+use std::process;
+
+use libalphastream::api::{AlphaStreamProcessorBuilder, ProcessingMode};
+use libalphastream::stats::MaskStats;
+
+use crate::{parse_filter, print_usage_and_exit, wait_for_frame, Source, FRAME_TIMEOUT_MS};
+
+/// Entry point for `demo inspect`
+pub fn run(mut args: impl Iterator<Item = String>) {
+    let mut source = Source::parse(&mut args);
+    let mut filter = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--override-filename-for-decrypt" => match args.next() {
+                Some(val) => source.override_filename_for_decrypt = Some(val),
+                None => {
+                    eprintln!("Expected a filename after --override-filename-for-decrypt");
+                    print_usage_and_exit();
+                }
+            },
+            "--filter" => filter = Some(parse_filter(args.next())),
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                print_usage_and_exit();
+            }
+        }
+    }
+
+    let width = 512;
+    let height = 256;
+    let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+    let builder = AlphaStreamProcessorBuilder::new()
+        .processing_mode(ProcessingMode::Bitmap)
+        .prefetch_window(1000);
+    let processor = source.open(&rt, builder, width, height);
+    let meta = match rt.block_on(processor.metadata()) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("No metadata available: {}", e);
+            process::exit(1);
+        }
+    };
+
+    println!("frame\tarea\tcoverage\tbbox_x\tbbox_y\tbbox_w\tbbox_h");
+    let mut matched = 0u32;
+    for frame_idx in 0..meta.frame_count {
+        let frame = match wait_for_frame(&rt, &processor, frame_idx, width, height) {
+            Some(frame) => frame,
+            None => {
+                eprintln!("Error: Timeout waiting for frame {} (> {} ms)", frame_idx, FRAME_TIMEOUT_MS);
+                process::exit(1);
+            }
+        };
+        let stats = MaskStats::from_mask(&frame, width, height);
+        if filter.as_ref().is_some_and(|f| !f.matches(frame_idx, &stats)) {
+            continue;
+        }
+        matched += 1;
+        println!(
+            "{}\t{}\t{:.4}\t{}\t{}\t{}\t{}",
+            frame_idx, stats.area, stats.coverage, stats.bbox.x, stats.bbox.y, stats.bbox.w, stats.bbox.h
+        );
+    }
+    eprintln!("{} of {} frames listed", matched, meta.frame_count);
+}
@@ -0,0 +1,491 @@
+use std::fs::metadata;
+use libalphastream::api::{AlphaStreamProcessor, AlphaStreamProcessorBuilder, ProcessingMode};
+use libalphastream::filter::FrameFilter;
+use libalphastream::overlay::Overlay;
+use libalphastream::telemetry::MetricsExporter;
+
+use std::process::{self, Command, Stdio};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex};
+
+mod bake;
+mod concat;
+mod frames;
+mod heatmap;
+mod inspect;
+mod pipe;
+mod pipeline;
+mod serve;
+mod stats;
+mod verify;
+
+/// Time to wait for a single frame before giving up
+pub const FRAME_TIMEOUT_MS: u128 = 500;
+
+/// Input file and the parameters needed to decrypt it, shared by all subcommands
+pub struct Source {
+    pub path: String,
+    pub version: String,
+    pub scene_id: u32,
+    pub override_filename_for_decrypt: Option<String>,
+}
+
+impl Source {
+    /// Parse the `<asvr_path> <version> <scene_id>` positional arguments
+    pub fn parse(args: &mut impl Iterator<Item = String>) -> Source {
+        let path = match args.next() {
+            Some(val) => val,
+            None => {
+                eprintln!("Missing required argument: asvr_path");
+                print_usage_and_exit();
+            }
+        };
+        let version = match args.next() {
+            Some(val) => val,
+            None => {
+                eprintln!("Missing required argument: version");
+                print_usage_and_exit();
+            }
+        };
+        let scene_id = match args.next() {
+            Some(val) => val,
+            None => {
+                eprintln!("Missing required argument: scene_id");
+                print_usage_and_exit();
+            }
+        };
+        let scene_id = match scene_id.parse::<u32>() {
+            Ok(num) => num,
+            Err(_) => {
+                eprintln!("scene_id must be a valid u32");
+                process::exit(1);
+            }
+        };
+        Source { path, version, scene_id, override_filename_for_decrypt: None }
+    }
+
+    /// File name used as base_url in key derivation
+    pub fn base_url(&self) -> &str {
+        if let Some(ref override_name) = self.override_filename_for_decrypt {
+            override_name.as_str()
+        } else {
+            std::path::Path::new(&self.path)
+                .file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or("")
+        }
+    }
+
+    /// Open the source as ASVR, exiting the process on failure
+    pub fn open(&self, rt: &tokio::runtime::Runtime, builder: AlphaStreamProcessorBuilder, width: u32, height: u32) -> AlphaStreamProcessor {
+        let result = rt.block_on(async {
+            builder.build_asvr(&self.path, self.scene_id, self.version.as_bytes(), self.base_url().as_bytes(), width, height).await
+        });
+        match result {
+            Ok(p) => p,
+            Err(e) => {
+                eprintln!("Could not create AlphaStreamProcessor: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+/// Request a frame and wait until it is available, or `None` after FRAME_TIMEOUT_MS
+pub fn wait_for_frame(rt: &tokio::runtime::Runtime, processor: &AlphaStreamProcessor, frame_idx: u32, width: u32, height: u32) -> Option<Vec<u8>> {
+    let frame_start = std::time::Instant::now();
+    loop {
+        let _ = rt.block_on(processor.request_frame(frame_idx));
+        if let Some(frame) = rt.block_on(processor.get_frame(frame_idx as usize, width, height)) {
+            return Some(frame);
+        }
+        if frame_start.elapsed().as_millis() > FRAME_TIMEOUT_MS {
+            return None;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(2));
+    }
+}
+
+fn main() {
+    // Use std::env for argument parsing
+    let mut args = std::env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("serve") => {
+            args.next();
+            serve::run(args);
+        }
+        Some("inspect") => {
+            args.next();
+            inspect::run(args);
+        }
+        Some("heatmap") => {
+            args.next();
+            heatmap::run(args);
+        }
+        Some("concat") => {
+            args.next();
+            concat::run(args);
+        }
+        Some("bake") => {
+            args.next();
+            bake::run(args);
+        }
+        Some("frames") => {
+            args.next();
+            frames::run(args);
+        }
+        Some("verify") => {
+            args.next();
+            verify::run(args);
+        }
+        Some("--pipe") => {
+            args.next();
+            pipe::run(args);
+        }
+        _ => export(args),
+    }
+}
+
+/// Parse a `<width>x<height>` size argument
+pub fn parse_size(s: &str) -> Option<(u32, u32)> {
+    let (w, h) = s.split_once('x')?;
+    let (w, h) = (w.parse().ok()?, h.parse().ok()?);
+    if w == 0 || h == 0 {
+        return None;
+    }
+    Some((w, h))
+}
+
+/// Parse a `--filter` expression, exiting with the parse error on failure
+pub fn parse_filter(expr: Option<String>) -> FrameFilter {
+    let expr = match expr {
+        Some(expr) => expr,
+        None => {
+            eprintln!("Expected an expression after --filter");
+            print_usage_and_exit();
+        }
+    };
+    match FrameFilter::parse(&expr) {
+        Ok(filter) => filter,
+        Err(e) => {
+            eprintln!("Invalid --filter expression: {}", e);
+            eprintln!("  {}", expr);
+            eprintln!("  {}^", " ".repeat(e.position));
+            process::exit(1);
+        }
+    }
+}
+
+/// Parse a positive count argument, exiting with a message naming the option on failure
+pub fn parse_count(option: &str, value: Option<String>) -> usize {
+    match value.as_deref().map(str::parse::<usize>) {
+        Some(Ok(n)) if n > 0 => n,
+        _ => {
+            eprintln!("Expected a positive number after {}", option);
+            print_usage_and_exit();
+        }
+    }
+}
+
+/// Default command: decode all frames and stream them to ffmpeg
+fn export(mut args: impl Iterator<Item = String>) {
+    let mut source = Source::parse(&mut args);
+    let mut filter = None;
+    let mut deterministic = false;
+    let mut metadata_path = None;
+    let mut report_path = None;
+    let mut overlay = Overlay::default();
+    let mut config = pipeline::PipelineConfig::default();
+    let mut live_stats = false;
+    let mut stats_csv_path = None;
+
+    while let Some(arg) = args.next() {
+        if arg == "--override-filename-for-decrypt" {
+            match args.next() {
+                Some(val) => source.override_filename_for_decrypt = Some(val),
+                None => {
+                    eprintln!("Expected a filename after --override-filename-for-decrypt");
+                    print_usage_and_exit();
+                }
+            }
+        } else if arg == "--filter" {
+            filter = Some(parse_filter(args.next()));
+        } else if arg == "--deterministic" {
+            deterministic = true;
+        } else if arg == "--metadata" {
+            match args.next() {
+                Some(val) => metadata_path = Some(val),
+                None => {
+                    eprintln!("Expected a file name after --metadata");
+                    print_usage_and_exit();
+                }
+            }
+        } else if arg == "--report" {
+            match args.next() {
+                Some(val) => report_path = Some(val),
+                None => {
+                    eprintln!("Expected a file name after --report");
+                    print_usage_and_exit();
+                }
+            }
+        } else if arg == "--overlay" {
+            // Timecodes count frames at the rate the video is encoded with
+            match args.next().map(|val| Overlay::parse(&val, 59.94)) {
+                Some(Ok(val)) => overlay = val,
+                Some(Err(e)) => {
+                    eprintln!("{}", e);
+                    print_usage_and_exit();
+                }
+                None => {
+                    eprintln!("Expected a list of frame, timecode, bbox after --overlay");
+                    print_usage_and_exit();
+                }
+            }
+        } else if arg == "--decode-workers" {
+            config.decode_workers = parse_count(&arg, args.next());
+        } else if arg == "--raster-workers" {
+            config.raster_workers = parse_count(&arg, args.next());
+        } else if arg == "--queue-depth" {
+            config.queue_depth = parse_count(&arg, args.next());
+        } else if arg == "--stats" {
+            live_stats = true;
+        } else if arg == "--stats-csv" {
+            match args.next() {
+                Some(val) => stats_csv_path = Some(val),
+                None => {
+                    eprintln!("Expected a file name after --stats-csv");
+                    print_usage_and_exit();
+                }
+            }
+        } else {
+            eprintln!("Unknown argument: {}", arg);
+            print_usage_and_exit();
+        }
+    }
+
+    // Try to open the file at asvr_path
+    let file_size = match metadata(&source.path) {
+        Ok(meta) => meta.len(),
+        Err(e) => {
+            eprintln!("Failed to get file metadata: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let width = 512;
+    let height = 256;
+
+    // Create tokio runtime
+    let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+
+    // Counts the decoded frames for --stats
+    let metrics = Arc::new(MetricsExporter::default());
+
+    // Parse as ASVR using AlphaStreamProcessorBuilder
+    // The processor only decodes; rasterization runs on the pipeline's own workers
+    let builder = AlphaStreamProcessorBuilder::new()
+        .processing_mode(ProcessingMode::PolystreamOnly)
+        .runtime_threads(config.decode_workers)
+        .prefetch_window(1000)
+        .deterministic(deterministic)
+        .telemetry_exporter(metrics.clone());
+    let processor = source.open(&rt, builder, width, height);
+    let meta = match rt.block_on(processor.metadata()) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("No metadata available: {}", e);
+            process::exit(1);
+        }
+    };
+
+    println!("File size: {} bytes", file_size);
+    println!();
+    println!("Frame count: {}", meta.frame_count);
+    println!("Compressed sizes table: {} bytes", meta.compressed_sizes_size);
+
+    // Spawn ffmpeg process
+    let mut ffmpeg = Command::new("ffmpeg")
+        .args([
+            "-y", // overwrite output
+            "-f", "rawvideo",
+            "-pixel_format", "gray",
+            "-video_size", &format!("{}x{}", width, height),
+            "-framerate", "59.94",
+            "-i", "-",
+            "-c:v", "libx264",
+            "-pix_fmt", "yuv420p",
+            &format!("output-{}.mp4", source.scene_id)
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .expect("Failed to start ffmpeg");
+    let ffmpeg_stdin = ffmpeg.stdin.as_mut().expect("Failed to open ffmpeg stdin");
+
+    // Handle Ctrl+C to close ffmpeg stdin cleanly
+    // Use Arc<Mutex<Option<()>>> just to trigger drop on ffmpeg_stdin
+    let ffmpeg_stdin_arc = Arc::new(Mutex::new(Some(())));
+    {
+        let ffmpeg_stdin_arc = ffmpeg_stdin_arc.clone();
+        // Use the ctrlc crate for Ctrl+C handling
+        // Add to Cargo.toml: ctrlc = "3"
+        ctrlc::set_handler(move || {
+            println!("Ctrl+C pressed, closing ffmpeg stdin...");
+            let _ = ffmpeg_stdin_arc.lock().unwrap().take();
+            // Exit with STATUS_CONTROL_C_EXIT (0xC000013A)
+            std::process::exit(-1073741510);
+        }).expect("Error setting Ctrl-C handler");
+    }
+
+    // One JSON object per exported frame, line n describing frame n of the video
+    let mut metadata_out = metadata_path.as_ref().map(|path| match File::create(path) {
+        Ok(file) => BufWriter::new(file),
+        Err(e) => {
+            eprintln!("Failed to create {}: {}", path, e);
+            process::exit(1);
+        }
+    });
+
+    // --stats-csv writes the samples to a file and keeps the progress line; --stats shows them in its place
+    let stats_output = match (&stats_csv_path, live_stats) {
+        (Some(path), _) => match stats::StatsOutput::csv(path) {
+            Ok(output) => Some(output),
+            Err(e) => {
+                eprintln!("Failed to create {}: {}", path, e);
+                process::exit(1);
+            }
+        },
+        (None, true) => Some(stats::StatsOutput::Live),
+        (None, false) => None,
+    };
+    let show_progress = !matches!(stats_output, Some(stats::StatsOutput::Live));
+
+    // Decode, rasterize and encode all frames with the stages overlapping
+    println!("Decoding all frames and streaming to ffmpeg...");
+    let total = meta.frame_count;
+    let mut last_percent = 0;
+    let mut written = 0u32;
+    let start = std::time::Instant::now();
+    let (result, stats_result) = std::thread::scope(|scope| {
+        let reporter = stats_output.map(|output| stats::spawn(scope, &rt, &processor, metrics.clone(), output));
+        let result = pipeline::run(&rt, &processor, 0..total, config, |frame_idx, frame, info| {
+            // frame is a single channel grayscale mask
+            if frame.len() as u32 != width*height {
+                eprintln!("Frame {} has unexpected size {} (expected {})", frame_idx, frame.len(), width*height);
+                process::exit(1);
+            }
+            if filter.as_ref().is_none_or(|f| f.matches(frame_idx, &info.stats)) {
+                if overlay.is_empty() {
+                    ffmpeg_stdin.write_all(frame)?;
+                } else {
+                    let mut burnt = frame.to_vec();
+                    overlay.apply(&mut burnt, width, height, frame_idx);
+                    ffmpeg_stdin.write_all(&burnt)?;
+                }
+                if let Some(out) = metadata_out.as_mut() {
+                    write_frame_metadata(out, written, frame_idx, info)?;
+                }
+                written += 1;
+            }
+
+            let percent = ((frame_idx + 1) * 100 / total).min(100);
+            if show_progress && percent != last_percent && (percent % 5 == 0 || percent == 100) {
+                print!("\rProgress: {:3}% ({}/{} frames)", percent, frame_idx + 1, total);
+                std::io::stdout().flush()?;
+                last_percent = percent;
+            }
+            Ok(())
+        });
+        (result, reporter.map(stats::Reporter::finish))
+    });
+    if let Some(Err(e)) = stats_result {
+        eprintln!("Failed to write stats: {}", e);
+    }
+    let (stages, report) = match result {
+        Ok(result) => result,
+        Err(pipeline::PipelineError::Sink(e)) => {
+            eprintln!("Failed to write frame to ffmpeg: {}", e);
+            process::exit(1);
+        }
+    };
+    if let Some(Err(e)) = metadata_out.as_mut().map(BufWriter::flush) {
+        eprintln!("Failed to write {}: {}", metadata_path.unwrap_or_default(), e);
+        process::exit(1);
+    }
+    // Close ffmpeg stdin to signal end of input
+    let _ = ffmpeg_stdin;
+    let ffmpeg_status = ffmpeg.wait().expect("Failed to wait on ffmpeg");
+    if !ffmpeg_status.success() {
+        eprintln!("ffmpeg exited with error");
+        process::exit(1);
+    }
+    let elapsed = start.elapsed();
+    println!("\nDone decoding all frames and writing to output.mp4.");
+    if filter.is_some() {
+        println!("{} of {} frames matched the filter", written, total);
+    }
+    println!("Decoded {} frames in {:.3} seconds ({:.2} ms/frame)",
+        total,
+        elapsed.as_secs_f64(),
+        if total > 0 { elapsed.as_secs_f64() * 1000.0 / total as f64 } else { 0.0 }
+    );
+    for stage in &stages {
+        println!("  {:<9} {:3.0}% busy ({} worker{}, {} frames)",
+            stage.name,
+            stage.utilization(elapsed) * 100.0,
+            stage.workers,
+            if stage.workers == 1 { "" } else { "s" },
+            stage.frames
+        );
+    }
+    if let Some(path) = &report_path {
+        let written = serde_json::to_string_pretty(&report).map_err(std::io::Error::from).and_then(|json| std::fs::write(path, json));
+        if let Err(e) = written {
+            eprintln!("Failed to write {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+    if !report.is_complete() {
+        // The video has empty masks in their place; re-export these frames to repair it
+        eprintln!("{} frames failed (timeout > {} ms): {:?}", report.failed.len(), FRAME_TIMEOUT_MS, report.failed_indices());
+        process::exit(1);
+    }
+}
+
+/// Write one line of the `--metadata` sidecar
+fn write_frame_metadata(out: &mut impl Write, output_frame: u32, frame_idx: u32, info: &pipeline::FrameInfo) -> std::io::Result<()> {
+    let bbox = info.stats.bbox;
+    let line = serde_json::json!({
+        "output_frame": output_frame,
+        "frame": frame_idx,
+        "bbox": { "x": bbox.x, "y": bbox.y, "w": bbox.w, "h": bbox.h },
+        "area": info.stats.area,
+        "coverage": info.stats.coverage,
+        "vertices": info.vertices,
+        "decode_ms": info.decode_time.map(|time| time.as_secs_f64() * 1000.0),
+        "failed": info.failed,
+    });
+    writeln!(out, "{}", line)
+}
+
+pub fn print_usage_and_exit() -> ! {
+    eprintln!("Usage: demo <asvr_path> <version> <scene_id> [--override-filename-for-decrypt <filename>] [--filter <expr>] [--deterministic]");
+    eprintln!("                [--decode-workers <n>] [--raster-workers <n>] [--queue-depth <n>] [--metadata <file.jsonl>] [--report <file.json>]");
+    eprintln!("                [--overlay <frame,timecode,bbox>] [--stats] [--stats-csv <file.csv>]");
+    eprintln!("       demo inspect <asvr_path> <version> <scene_id> [--override-filename-for-decrypt <filename>] [--filter <expr>]");
+    eprintln!("       demo heatmap <asvr_path> <version> <scene_id> [--override-filename-for-decrypt <filename>] [--range <start>..<end>] [--size <width>x<height>] [--output <file.png>]");
+    eprintln!("       demo frames <asvr_path> <version> <scene_id> [--override-filename-for-decrypt <filename>] [--range <start>..<end>] [--size <width>x<height>]");
+    eprintln!("                [--output-dir <dir>] [--template <template>]   (PNG per frame, resumable; template default {{scene}}/{{frame:06}}.png)");
+    eprintln!("       demo bake <asvr_path> <version> <scene_id> [--override-filename-for-decrypt <filename>] [--size <width>x<height>] [--output <file.bake>]");
+    eprintln!("                [--with-vertices]   (every frame processed ahead of time, played with the builder's baked_scene)");
+    eprintln!("       demo serve <asvr_path> <version> <scene_id> [--override-filename-for-decrypt <filename>] [--bind <addr>] [--port <port>] [--size <width>x<height>] [--watch]");
+    eprintln!("       demo verify <asvr_path> <version> <scene_id> [--override-filename-for-decrypt <filename>] [--workers <n>] [--memory-budget <MiB>]");
+    eprintln!("       demo --pipe [--size <width>x<height>]   (ASVP stream on stdin, gray rawvideo frames on stdout)");
+    eprintln!("       demo concat <output_asvr> <version> <scene_id> <asvr_path> <version> <scene_id> [<asvr_path> <version> <scene_id> ...]");
+    eprintln!();
+    eprintln!("Filter expressions select frames by mask statistics, e.g. \"area > 5000 && bbox.w > 100\".");
+    eprintln!("Variables: frame, area, coverage, bbox.x, bbox.y, bbox.w, bbox.h");
+    process::exit(1);
+}
@@ -0,0 +1,96 @@
+// `demo --pipe`: read an ASVP stream from stdin and write the rasterized frames to stdout.
+//
+// Frames are written back to back as raw R8 masks (width * height bytes each, no header), the
+// layout ffmpeg reads with `-f rawvideo -pixel_format gray`, so the demo can sit in the middle of
+// a shell pipeline, e.g. `curl ... | demo --pipe --size 512x256 | ffmpeg -f rawvideo ...`.
+// Progress and errors go to stderr.
+
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use libalphastream::formats::{ASFormat, ASVPFormat, SequentialReader};
+use libalphastream::prelude::{PolystreamRasterizer, RasterOptions};
+
+use crate::{parse_size, print_usage_and_exit};
+
+/// Union of the channel masks of a decoded frame payload
+fn rasterize(polystream: &[u8], width: u32, height: u32, options: &RasterOptions) -> Vec<u8> {
+    let mut mask = vec![0u8; (width * height) as usize];
+    let channel_count = u32::from_le_bytes(polystream[0..4].try_into().unwrap()) as usize;
+    let mut offset = 4 + channel_count * 4;
+    for channel in 0..channel_count {
+        let size = u32::from_le_bytes(polystream[4 + channel * 4..8 + channel * 4].try_into().unwrap()) as usize;
+        PolystreamRasterizer::rasterize_into(&polystream[offset..offset + size], width, height, options, &mut mask);
+        offset += size;
+    }
+    mask
+}
+
+/// Entry point for `demo --pipe`
+pub fn run(mut args: impl Iterator<Item = String>) {
+    let mut width: u32 = 512;
+    let mut height: u32 = 256;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => match args.next().as_deref().and_then(parse_size) {
+                Some((w, h)) => {
+                    width = w;
+                    height = h;
+                }
+                None => {
+                    eprintln!("Expected <width>x<height> after --size");
+                    print_usage_and_exit();
+                }
+            },
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                print_usage_and_exit();
+            }
+        }
+    }
+
+    let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+    let mut format = match rt.block_on(ASVPFormat::new(SequentialReader::new(tokio::io::stdin()))) {
+        Ok(format) => format,
+        Err(e) => {
+            eprintln!("Could not read an ASVP stream from stdin: {}", e);
+            process::exit(1);
+        }
+    };
+    let frame_count = match rt.block_on(format.metadata()) {
+        Ok(meta) => meta.frame_count,
+        Err(e) => {
+            eprintln!("No metadata available: {}", e);
+            process::exit(1);
+        }
+    };
+    eprintln!("Streaming {} frames as {}x{} gray rawvideo", frame_count, width, height);
+
+    let options = RasterOptions::new(width, height);
+    let mut out = BufWriter::new(io::stdout().lock());
+    for frame_idx in 0..frame_count {
+        let frame = match rt.block_on(format.decode_frame(frame_idx)) {
+            Ok(frame) => frame,
+            Err(e) => {
+                eprintln!("Failed to decode frame {}: {}", frame_idx, e);
+                process::exit(1);
+            }
+        };
+        let mask = rasterize(&frame.polystream, width, height, &options);
+        if let Err(e) = out.write_all(&mask) {
+            // The reading end went away (e.g. `| head -c`); that is how pipelines stop early
+            if e.kind() == io::ErrorKind::BrokenPipe {
+                return;
+            }
+            eprintln!("Failed to write frame {}: {}", frame_idx, e);
+            process::exit(1);
+        }
+    }
+    if let Err(e) = out.flush() {
+        if e.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("Failed to write to stdout: {}", e);
+            process::exit(1);
+        }
+    }
+}
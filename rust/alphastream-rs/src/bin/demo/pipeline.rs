@@ -0,0 +1,215 @@
+// Pipelined export: decode → rasterize → encode with the stages overlapping.
+//
+// Decoding runs on the processor's runtime (PolystreamOnly mode, so it never rasterizes), a pool of
+// threads rasterizes, and a single encoder thread puts frames back in order and hands them to the sink
+// (ffmpeg's stdin). Bounded queues between the stages keep memory flat when one stage is slower, and
+// the per-stage busy times show which stage limits the throughput. A frame that does not decode in time
+// is passed on as an empty mask and listed in the export report, so one bad frame does not end a long job.
+
+use std::collections::BTreeMap;
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use libalphastream::api::{AlphaStreamProcessor, ExportReport, FrameErrorKind};
+use libalphastream::stats::MaskStats;
+
+use crate::FRAME_TIMEOUT_MS;
+
+/// Parallelism and queue sizes of the pipeline
+#[derive(Debug, Clone, Copy)]
+pub struct PipelineConfig {
+    /// Worker threads of the processor's runtime, which decodes ahead of the pipeline
+    pub decode_workers: usize,
+    /// Threads rasterizing polystreams
+    pub raster_workers: usize,
+    /// Frames buffered between two stages
+    pub queue_depth: usize,
+}
+
+impl Default for PipelineConfig {
+    fn default() -> Self {
+        let cores = std::thread::available_parallelism().map_or(4, |n| n.get());
+        Self { decode_workers: 4, raster_workers: cores.clamp(1, 8), queue_depth: 32 }
+    }
+}
+
+/// Time one stage spent working, as opposed to waiting on its neighbours
+#[derive(Debug, Clone)]
+pub struct StageReport {
+    pub name: &'static str,
+    pub workers: usize,
+    pub busy: Duration,
+    pub frames: u32,
+}
+
+impl StageReport {
+    /// Fraction of the stage's capacity (workers × wall time) spent working
+    pub fn utilization(&self, wall: Duration) -> f64 {
+        if wall.is_zero() {
+            return 0.0;
+        }
+        self.busy.as_secs_f64() / (wall.as_secs_f64() * self.workers as f64)
+    }
+}
+
+/// What the sink learns about a frame besides its mask
+#[derive(Debug, Clone, Copy)]
+pub struct FrameInfo {
+    pub stats: MaskStats,
+    /// Outline vertices in the rasterized channels
+    pub vertices: usize,
+    /// Time the processor spent reading, decrypting and decompressing the frame, if still known
+    pub decode_time: Option<Duration>,
+    /// The frame was not decoded in time; the mask is empty
+    pub failed: bool,
+}
+
+/// Why the pipeline stopped early
+#[derive(Debug)]
+pub enum PipelineError {
+    /// The sink failed, e.g. ffmpeg exited
+    Sink(std::io::Error),
+}
+
+/// Run `frames` through the pipeline. `sink` gets every frame in order with its statistics.
+/// Returns the stage reports (decode, rasterize, encode) and the export report, listing the frames not
+/// decoded within FRAME_TIMEOUT_MS, after all frames were handed to the sink.
+pub fn run(
+    rt: &tokio::runtime::Runtime,
+    processor: &AlphaStreamProcessor,
+    frames: std::ops::Range<u32>,
+    config: PipelineConfig,
+    mut sink: impl FnMut(u32, &[u8], &FrameInfo) -> std::io::Result<()> + Send,
+) -> Result<(Vec<StageReport>, ExportReport), PipelineError> {
+    let (width, height) = (processor.width(), processor.height());
+    let raster_workers = config.raster_workers.max(1);
+    let start = Instant::now();
+    // None for a frame that was not decoded in time
+    let (decoded_tx, decoded_rx) = sync_channel::<(u32, Option<Vec<u8>>, Option<Duration>)>(config.queue_depth);
+    let (raster_tx, raster_rx) = sync_channel::<(u32, Vec<u8>, FrameInfo)>(config.queue_depth);
+    // Raster workers share one receiver; the lock is only held while taking the next frame
+    let decoded_rx = Arc::new(Mutex::new(decoded_rx));
+
+    std::thread::scope(|scope| {
+        let mut raster_handles = Vec::new();
+        for _ in 0..raster_workers {
+            let decoded_rx = Arc::clone(&decoded_rx);
+            let raster_tx = raster_tx.clone();
+            raster_handles.push(scope.spawn(move || {
+                let mut busy = Duration::ZERO;
+                let mut count = 0;
+                loop {
+                    let next = decoded_rx.lock().unwrap().recv();
+                    let Ok((index, polystream, decode_time)) = next else { break };
+                    let start = Instant::now();
+                    let failed = polystream.is_none();
+                    let (mask, vertices) = match polystream {
+                        Some(polystream) => {
+                            let mut mask = processor.rasterize_polystream(&polystream);
+                            processor.watermark_mask(index as usize, &mut mask);
+                            (mask, processor.polystream_vertex_count(&polystream))
+                        }
+                        None => (vec![0; (width * height) as usize], 0),
+                    };
+                    let info = FrameInfo {
+                        stats: MaskStats::from_mask(&mask, width, height),
+                        vertices,
+                        decode_time,
+                        failed,
+                    };
+                    busy += start.elapsed();
+                    count += 1;
+                    if raster_tx.send((index, mask, info)).is_err() {
+                        break;
+                    }
+                }
+                (busy, count)
+            }));
+        }
+        drop(raster_tx);
+
+        let first = frames.start;
+        let encoder = scope.spawn(move || encode(raster_rx, first, &mut sink));
+
+        let mut report = ExportReport::default();
+        let decode = decode(rt, processor, frames, decoded_tx, &mut report);
+
+        let (mut raster_busy, mut raster_frames) = (Duration::ZERO, 0);
+        for handle in raster_handles {
+            let (busy, count) = handle.join().expect("raster worker panicked");
+            raster_busy += busy;
+            raster_frames += count;
+        }
+        let encode = encoder.join().expect("encoder panicked");
+
+        let (decode_busy, decode_frames) = decode;
+        let (encode_busy, encode_frames) = encode?;
+        report.succeeded = encode_frames - report.failed.len() as u32;
+        report.elapsed = start.elapsed();
+        let stages = vec![
+            // Decoding happens on the processor's runtime; what the pipeline sees is the time it waited for it
+            StageReport { name: "decode", workers: 1, busy: decode_busy, frames: decode_frames },
+            StageReport { name: "rasterize", workers: raster_workers, busy: raster_busy, frames: raster_frames },
+            StageReport { name: "encode", workers: 1, busy: encode_busy, frames: encode_frames },
+        ];
+        Ok((stages, report))
+    })
+}
+
+/// Decode stage: pull polystreams from the processor in order. A frame not decoded within
+/// FRAME_TIMEOUT_MS is sent on as None and recorded in `report`. Dropping the sender on return lets the
+/// later stages drain and stop.
+fn decode(
+    rt: &tokio::runtime::Runtime,
+    processor: &AlphaStreamProcessor,
+    frames: std::ops::Range<u32>,
+    decoded_tx: std::sync::mpsc::SyncSender<(u32, Option<Vec<u8>>, Option<Duration>)>,
+    report: &mut ExportReport,
+) -> (Duration, u32) {
+    let mut busy = Duration::ZERO;
+    let mut count = 0;
+    for index in frames {
+        let start = Instant::now();
+        let polystream = loop {
+            if let Some(polystream) = rt.block_on(processor.get_polystream(index as usize)) {
+                break Some(polystream);
+            }
+            if start.elapsed().as_millis() > FRAME_TIMEOUT_MS {
+                report.record_failure(index, FrameErrorKind::Timeout, format!("Not decoded within {} ms", FRAME_TIMEOUT_MS));
+                break None;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        };
+        busy += start.elapsed();
+        count += 1;
+        let decode_time = processor.frame_trace(index as usize).map(|trace| trace.decode);
+        if decoded_tx.send((index, polystream, decode_time)).is_err() {
+            break;
+        }
+    }
+    (busy, count)
+}
+
+/// Encode stage: restore frame order and write to the sink
+fn encode(
+    raster_rx: Receiver<(u32, Vec<u8>, FrameInfo)>,
+    first: u32,
+    sink: &mut impl FnMut(u32, &[u8], &FrameInfo) -> std::io::Result<()>,
+) -> Result<(Duration, u32), PipelineError> {
+    let mut pending = BTreeMap::new();
+    let mut next = first;
+    let mut busy = Duration::ZERO;
+    let mut count = 0;
+    for (index, mask, info) in raster_rx {
+        pending.insert(index, (mask, info));
+        while let Some((mask, info)) = pending.remove(&next) {
+            let start = Instant::now();
+            sink(next, &mask, &info).map_err(PipelineError::Sink)?;
+            busy += start.elapsed();
+            count += 1;
+            next += 1;
+        }
+    }
+    Ok((busy, count))
+}
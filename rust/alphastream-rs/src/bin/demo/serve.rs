@@ -0,0 +1,216 @@
+// `demo serve`: small HTTP preview server for visually inspecting a file remotely.
+//
+// Routes:
+// - GET /                  minimal HTML scrubber
+// - GET /meta              file metadata as JSON
+// - GET /frame/{index}.png rasterized frame as grayscale PNG
+//
+// Requests are handled one at a time on the calling thread; this is an inspection
+// tool, not a production server. It has no authentication, so it listens on 127.0.0.1
+// unless --bind names another address. With --watch the file is reloaded whenever it changes,
+// so re-requesting a frame shows the encoder's latest output.
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, TcpListener, TcpStream};
+use std::process;
+use std::time::Duration;
+
+use libalphastream::api::{AlphaStreamProcessor, AlphaStreamProcessorBuilder, ProcessingMode};
+use libalphastream::formats::Metadata;
+
+use crate::{parse_size, print_usage_and_exit, wait_for_frame, Source};
+
+const INDEX_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>alphastream preview</title>
+<style>
+body { background: #202020; color: #e0e0e0; font-family: sans-serif; margin: 2em; }
+img { background: #000; image-rendering: pixelated; max-width: 100%; display: block; margin-top: 1em; }
+input[type=range] { width: 100%; }
+</style>
+</head>
+<body>
+<div id="info">loading metadata...</div>
+<input id="scrub" type="range" min="0" max="0" value="0">
+<div>frame <input id="frame" type="number" min="0" value="0"></div>
+<img id="mask" alt="mask">
+<script>
+const scrub = document.getElementById('scrub');
+const frame = document.getElementById('frame');
+const mask = document.getElementById('mask');
+function show(i) {
+  scrub.value = i;
+  frame.value = i;
+  mask.src = '/frame/' + i + '.png';
+}
+scrub.addEventListener('input', () => show(scrub.value));
+frame.addEventListener('change', () => show(frame.value));
+fetch('/meta').then(r => r.json()).then(m => {
+  document.getElementById('info').textContent =
+    m.frame_count + ' frames, ' + m.width + 'x' + m.height;
+  scrub.max = Math.max(0, m.frame_count - 1);
+  frame.max = scrub.max;
+  show(0);
+});
+</script>
+</body>
+</html>
+"#;
+
+/// How long a client may stall reading or writing before its connection is dropped, so one
+/// idle client cannot block the accept loop
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Entry point for `demo serve`
+pub fn run(mut args: impl Iterator<Item = String>) {
+    let mut source = Source::parse(&mut args);
+    let mut bind = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let mut port: u16 = 8080;
+    let mut width: u32 = 512;
+    let mut height: u32 = 256;
+    let mut watch = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--override-filename-for-decrypt" => match args.next() {
+                Some(val) => source.override_filename_for_decrypt = Some(val),
+                None => {
+                    eprintln!("Expected a filename after --override-filename-for-decrypt");
+                    print_usage_and_exit();
+                }
+            },
+            "--port" => match args.next().and_then(|v| v.parse().ok()) {
+                Some(p) => port = p,
+                None => {
+                    eprintln!("Expected a port number after --port");
+                    print_usage_and_exit();
+                }
+            },
+            "--bind" => match args.next().and_then(|v| v.parse().ok()) {
+                Some(addr) => bind = addr,
+                None => {
+                    eprintln!("Expected an IP address after --bind");
+                    print_usage_and_exit();
+                }
+            },
+            "--watch" => watch = true,
+            "--size" => match args.next().as_deref().and_then(parse_size) {
+                Some((w, h)) => {
+                    width = w;
+                    height = h;
+                }
+                None => {
+                    eprintln!("Expected <width>x<height> after --size");
+                    print_usage_and_exit();
+                }
+            },
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                print_usage_and_exit();
+            }
+        }
+    }
+
+    let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+    let builder = AlphaStreamProcessorBuilder::new()
+        .processing_mode(ProcessingMode::Bitmap)
+        .watch_source(watch);
+    let processor = source.open(&rt, builder, width, height);
+    let meta = match rt.block_on(processor.metadata()) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("No metadata available: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let listener = match TcpListener::bind((bind, port)) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("Failed to bind {}:{}: {}", bind, port, e);
+            process::exit(1);
+        }
+    };
+    let address = listener.local_addr().map_or_else(|_| format!("{}:{}", bind, port), |a| a.to_string());
+    println!("Serving {} ({} frames) on http://{}/", source.path, meta.frame_count, address);
+
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = handle_connection(stream, &rt, &processor) {
+                    eprintln!("Connection error: {}", e);
+                }
+            }
+            Err(e) => eprintln!("Accept failed: {}", e),
+        }
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, rt: &tokio::runtime::Runtime, processor: &AlphaStreamProcessor) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    // Read until the end of the request headers; we never need a body
+    let mut request = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < 8192 {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+    let request = String::from_utf8_lossy(&request);
+    let mut parts = request.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let path = parts.next().unwrap_or("");
+
+    if method != "GET" {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain", b"method not allowed");
+    }
+    // Fetched per request: with --watch the frame count changes as the file is rewritten
+    let meta: Metadata = match rt.block_on(processor.metadata()) {
+        Ok(m) => m,
+        Err(e) => return respond(&mut stream, "500 Internal Server Error", "text/plain", e.to_string().as_bytes()),
+    };
+    match path {
+        "/" | "/index.html" => respond(&mut stream, "200 OK", "text/html; charset=utf-8", INDEX_HTML.as_bytes()),
+        "/meta" => {
+            let body = format!(
+                "{{\"frame_count\":{},\"compressed_sizes_size\":{},\"width\":{},\"height\":{}}}",
+                meta.frame_count, meta.compressed_sizes_size, processor.width(), processor.height()
+            );
+            respond(&mut stream, "200 OK", "application/json", body.as_bytes())
+        }
+        _ => {
+            let index = path
+                .strip_prefix("/frame/")
+                .and_then(|p| p.strip_suffix(".png"))
+                .and_then(|i| i.parse::<u32>().ok());
+            match index {
+                Some(i) if i < meta.frame_count => {
+                    match wait_for_frame(rt, processor, i, processor.width(), processor.height()) {
+                        Some(frame) => {
+                            let png = libalphastream::png::encode_gray8(&frame, processor.width(), processor.height());
+                            respond(&mut stream, "200 OK", "image/png", &png)
+                        }
+                        None => respond(&mut stream, "503 Service Unavailable", "text/plain", b"frame not ready"),
+                    }
+                }
+                Some(_) => respond(&mut stream, "404 Not Found", "text/plain", b"frame index out of range"),
+                None => respond(&mut stream, "404 Not Found", "text/plain", b"not found"),
+            }
+        }
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> std::io::Result<()> {
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status, content_type, body.len()
+    );
+    stream.write_all(header.as_bytes())?;
+    stream.write_all(body)?;
+    stream.flush()
+}
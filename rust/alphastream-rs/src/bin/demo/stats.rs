@@ -0,0 +1,134 @@
+// `--stats`: live diagnostics while the demo exports, for trying out a new file source.
+//
+// Once a second the reporter samples decode fps (frames decoded, counted by a MetricsExporter on the
+// processor), cache occupancy and scheduler queue depth (the processor's stats) and transport
+// throughput (stored bytes read from the source). It either rewrites one status line on stdout or
+// appends a row to a CSV file, flushed every second so the file can be followed while the export runs.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{Scope, ScopedJoinHandle};
+use std::time::{Duration, Instant};
+
+use libalphastream::api::AlphaStreamProcessor;
+use libalphastream::telemetry::MetricsExporter;
+
+/// Time between two samples
+const INTERVAL: Duration = Duration::from_secs(1);
+
+const CSV_HEADER: &str = "elapsed_s,frames_decoded,decode_fps,cache_ready,cache_in_progress,cache_capacity,queued_tasks,active_tasks,bytes_read,read_bytes_per_s";
+
+/// Where the samples go
+pub enum StatsOutput {
+    /// A status line on stdout, rewritten every second
+    Live,
+    /// One CSV row per second
+    Csv(BufWriter<File>),
+}
+
+impl StatsOutput {
+    /// CSV output to `path`, with the header already written
+    pub fn csv(path: &str) -> io::Result<StatsOutput> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", CSV_HEADER)?;
+        Ok(StatsOutput::Csv(out))
+    }
+}
+
+/// Counters at one point in time
+struct Sample {
+    at: Instant,
+    frames_decoded: u64,
+    bytes_read: u64,
+}
+
+/// Background thread sampling a processor until `finish`
+pub struct Reporter<'scope> {
+    stop: Sender<()>,
+    handle: ScopedJoinHandle<'scope, io::Result<()>>,
+}
+
+impl Reporter<'_> {
+    /// Write a last sample and stop the thread
+    pub fn finish(self) -> io::Result<()> {
+        drop(self.stop);
+        self.handle.join().expect("stats reporter panicked")
+    }
+}
+
+/// Start sampling `processor` every second. `metrics` must be the processor's telemetry exporter.
+pub fn spawn<'scope, 'env>(
+    scope: &'scope Scope<'scope, 'env>,
+    rt: &'env tokio::runtime::Runtime,
+    processor: &'env AlphaStreamProcessor,
+    metrics: Arc<MetricsExporter>,
+    mut output: StatsOutput,
+) -> Reporter<'scope> {
+    let (stop, stopped) = channel::<()>();
+    let handle = scope.spawn(move || {
+        let start = Instant::now();
+        let mut last = Sample { at: start, frames_decoded: 0, bytes_read: 0 };
+        loop {
+            // Nothing is ever sent; the reporter stops when the sender is dropped
+            let done = matches!(stopped.recv_timeout(INTERVAL), Err(RecvTimeoutError::Disconnected));
+            let now = Sample { at: Instant::now(), frames_decoded: metrics.snapshot().frames_decoded, bytes_read: processor.bytes_read() };
+            write_sample(&mut output, rt, processor, start, &last, &now)?;
+            if done {
+                if let StatsOutput::Live = output {
+                    println!();
+                }
+                return Ok(());
+            }
+            last = now;
+        }
+    });
+    Reporter { stop, handle }
+}
+
+fn write_sample(
+    output: &mut StatsOutput,
+    rt: &tokio::runtime::Runtime,
+    processor: &AlphaStreamProcessor,
+    start: Instant,
+    last: &Sample,
+    now: &Sample,
+) -> io::Result<()> {
+    let stats = rt.block_on(processor.stats());
+    let seconds = now.at.duration_since(last.at).as_secs_f64().max(f64::EPSILON);
+    let decode_fps = (now.frames_decoded - last.frames_decoded) as f64 / seconds;
+    let read_rate = (now.bytes_read - last.bytes_read) as f64 / seconds;
+    let elapsed = now.at.duration_since(start).as_secs_f64();
+    match output {
+        StatsOutput::Live => {
+            let mut stdout = io::stdout().lock();
+            write!(stdout, "\r[{:5.0}s] decode {:7.1} fps | cache {:4}/{} ready, {:3} decoding | queue {:5} queued, {:3} active | read {:8.1} KiB/s ",
+                elapsed,
+                decode_fps,
+                stats.ready_frames,
+                processor.cache_capacity(),
+                stats.in_progress_frames,
+                stats.queued_tasks,
+                stats.active_tasks,
+                read_rate / 1024.0,
+            )?;
+            stdout.flush()
+        }
+        StatsOutput::Csv(out) => {
+            writeln!(out, "{:.3},{},{:.2},{},{},{},{},{},{},{:.0}",
+                elapsed,
+                now.frames_decoded,
+                decode_fps,
+                stats.ready_frames,
+                stats.in_progress_frames,
+                processor.cache_capacity(),
+                stats.queued_tasks,
+                stats.active_tasks,
+                now.bytes_read,
+                read_rate,
+            )?;
+            out.flush()
+        }
+    }
+}
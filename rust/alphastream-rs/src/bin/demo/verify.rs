@@ -0,0 +1,144 @@
+// `demo verify`: decode every frame of an archive on all cores, without rasterizing, and report
+// which frames fail to decrypt or decompress.
+//
+// Every worker opens its own reader, so decoding is not serialized on one file handle; the key is
+// derived once and shared. Frames in flight are bounded by a memory budget, estimated per frame
+// from its stored size. Output is one tab separated line per frame, in frame order, followed by a
+// summary on stderr; the exit code is 1 when any frame failed.
+
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use libalphastream::formats::{derive_key, ASFormat, ASVRFormat, FormatError, FormatType};
+use tokio::sync::Semaphore;
+
+use crate::{parse_count, print_usage_and_exit, Source};
+
+/// Memory budget for frames in flight, in MiB
+const DEFAULT_MEMORY_BUDGET_MB: usize = 256;
+/// Memory a frame takes while decoding per byte of its stored size: the read buffer, the decrypted
+/// copy and the decompressed polystream at a typical compression ratio
+const MEMORY_PER_STORED_BYTE: u64 = 10;
+
+/// Outcome of one frame: the decoded polystream size or the error
+type FrameResult = (usize, Result<usize, FormatError>);
+
+/// Entry point for `demo verify`
+pub fn run(mut args: impl Iterator<Item = String>) {
+    let mut source = Source::parse(&mut args);
+    let mut workers = std::thread::available_parallelism().map_or(4, |n| n.get());
+    let mut budget_mb = DEFAULT_MEMORY_BUDGET_MB;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--override-filename-for-decrypt" => match args.next() {
+                Some(val) => source.override_filename_for_decrypt = Some(val),
+                None => {
+                    eprintln!("Expected a filename after --override-filename-for-decrypt");
+                    print_usage_and_exit();
+                }
+            },
+            "--workers" => workers = parse_count(&arg, args.next()),
+            "--memory-budget" => budget_mb = parse_count(&arg, args.next()),
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                print_usage_and_exit();
+            }
+        }
+    }
+
+    let key = match derive_key(source.scene_id, source.version.as_bytes(), source.base_url().as_bytes()) {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("Key derivation failed: {}", e);
+            process::exit(1);
+        }
+    };
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(workers)
+        .enable_all()
+        .build()
+        .expect("Failed to create tokio runtime");
+    let start = Instant::now();
+    let (frame_sizes, results) = match rt.block_on(verify(&source.path, key, workers, budget_mb as u64 * 1024 * 1024)) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("Could not open {}: {}", source.path, e);
+            process::exit(1);
+        }
+    };
+    let elapsed = start.elapsed();
+
+    println!("frame\tstatus\tstored_bytes\tdecoded_bytes\terror");
+    let mut failed = 0;
+    for (index, result) in &results {
+        match result {
+            Ok(decoded) => println!("{}\tok\t{}\t{}\t", index, frame_sizes[*index], decoded),
+            Err(e) => {
+                failed += 1;
+                println!("{}\tFAILED\t{}\t\t{}", index, frame_sizes[*index], e);
+            }
+        }
+    }
+    let stored: u64 = frame_sizes.iter().sum();
+    eprintln!(
+        "Verified {} frames in {:.2} seconds ({:.1} MB/s, {} workers): {} ok, {} failed",
+        results.len(),
+        elapsed.as_secs_f64(),
+        stored as f64 / 1e6 / elapsed.as_secs_f64().max(1e-9),
+        workers,
+        results.len() - failed,
+        failed
+    );
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+async fn open(path: &str, key: [u8; 32]) -> Result<FormatType<tokio::fs::File>, FormatError> {
+    let file = tokio::fs::File::open(path).await?;
+    Ok(FormatType::ASVR(ASVRFormat::with_key(file, key).await?))
+}
+
+/// Decode all frames with `workers` readers. Returns the stored frame sizes and the outcome of every frame in order.
+async fn verify(path: &str, key: [u8; 32], workers: usize, budget: u64) -> Result<(Arc<[u64]>, Vec<FrameResult>), FormatError> {
+    let first = open(path, key).await?;
+    let frame_sizes: Arc<[u64]> = first.frame_sizes().into();
+    // Budget in KiB permits; a frame larger than the whole budget still runs, alone
+    let total_permits = (budget / 1024).clamp(1, Semaphore::MAX_PERMITS as u64 / 2) as u32;
+    let memory = Arc::new(Semaphore::new(total_permits as usize));
+    let next = Arc::new(AtomicUsize::new(0));
+
+    let mut handles = Vec::with_capacity(workers);
+    let mut first = Some(first);
+    for _ in 0..workers.max(1) {
+        let mut format = match first.take() {
+            Some(format) => format,
+            None => open(path, key).await?,
+        };
+        let (frame_sizes, memory, next) = (Arc::clone(&frame_sizes), Arc::clone(&memory), Arc::clone(&next));
+        handles.push(tokio::spawn(async move {
+            let mut results = Vec::new();
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                if index >= frame_sizes.len() {
+                    break;
+                }
+                let cost = (frame_sizes[index] * MEMORY_PER_STORED_BYTE).div_ceil(1024).clamp(1, total_permits as u64) as u32;
+                let _permit = memory.acquire_many(cost).await.expect("semaphore closed");
+                let result = format.decode_frame(index as u32).await.map(|frame| frame.polystream.len());
+                results.push((index, result));
+            }
+            results
+        }));
+    }
+
+    let mut results = Vec::with_capacity(frame_sizes.len());
+    for handle in handles {
+        results.extend(handle.await.expect("verify worker panicked"));
+    }
+    results.sort_by_key(|(index, _)| *index);
+    Ok((frame_sizes, results))
+}
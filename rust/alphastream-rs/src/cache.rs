@@ -13,6 +13,8 @@ pub use crate::formats::FrameData;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::RwLock;
 
+use crate::telemetry::{SharedTelemetryExporter, TelemetryEvent};
+
 /// Represents the state of a slot in the ring buffer.
 /// - Empty: No frame data, slot is available
 /// - InProgress: Frame is being fetched/decoded
@@ -44,6 +46,7 @@ impl FrameSlot {
     }
 
     /// Get the frame data if ready, None otherwise
+    #[cfg(any(test, feature = "unstable"))]
     pub fn get_data(&self) -> Option<&FrameData> {
         match self {
             FrameSlot::Ready(data) => Some(data),
@@ -81,6 +84,29 @@ pub struct RingBufferCache {
     ready_count: AtomicUsize,
     /// Atomic counter for InProgress slots - O(1) access instead of O(n) iteration
     in_progress_count: AtomicUsize,
+    /// Frames of history kept in the window before the play head after a seek
+    preroll: AtomicUsize,
+    /// Receives seek and eviction events
+    telemetry: RwLock<SharedTelemetryExporter>,
+    /// Requests that found their frame ready, see `lookup`
+    hits: AtomicU64,
+    /// Requests that did not
+    misses: AtomicU64,
+    /// Decoded frames thrown away because the window had moved on, see `record_dropped`
+    dropped: AtomicU64,
+}
+
+/// Counters of a cache since it was created, see `RingBufferCache::stats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Decoded frames discarded before they could be inserted
+    pub dropped: u64,
+    /// Slots holding a decoded frame
+    pub ready: usize,
+    /// Slots of frames being decoded
+    pub in_progress: usize,
 }
 
 impl RingBufferCache {
@@ -105,13 +131,34 @@ impl RingBufferCache {
             generation: AtomicU64::new(0),
             ready_count: AtomicUsize::new(0),
             in_progress_count: AtomicUsize::new(0),
+            preroll: AtomicUsize::new(0),
+            telemetry: RwLock::new(SharedTelemetryExporter::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
         }
     }
 
-    /// Create a new RingBufferCache with default capacity of 512.
-    /// This is the recommended default for most use cases as per PRD specifications.
-    pub fn default() -> Self {
-        Self::new(512)
+    /// Keep `frames` of history before the play head in the window, for consumers that look back a few
+    /// frames (e.g. temporal filters). A seek places the window start that many frames before the target,
+    /// the window never slides past them, and reading them does not count as a backward seek.
+    /// Clamped to half the capacity so the window keeps room ahead of the play head.
+    pub fn set_preroll(&self, frames: usize) {
+        self.preroll.store(frames.min(self.capacity / 2), Ordering::Release);
+    }
+
+    /// Frames of history kept before the play head, see `set_preroll`
+    pub fn preroll(&self) -> usize {
+        self.preroll.load(Ordering::Acquire)
+    }
+
+    /// Report seeks and evicted frames to `telemetry`
+    pub fn set_telemetry(&self, telemetry: SharedTelemetryExporter) {
+        *self.telemetry.write().unwrap() = telemetry;
+    }
+
+    fn export(&self, event: TelemetryEvent) {
+        self.telemetry.read().unwrap().export(event);
     }
 
     /// Map a frame index to a buffer slot position.
@@ -151,6 +198,31 @@ impl RingBufferCache {
         }
     }
 
+    /// Get a frame a consumer asked for, like `get`, counting a hit or a miss.
+    /// Internal reads (reloads, refinement) use `get` so they do not skew the hit rate.
+    pub fn lookup(&self, frame_index: usize) -> Option<FrameData> {
+        let frame = self.get(frame_index);
+        let counter = if frame.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        frame
+    }
+
+    /// Count a decoded frame that was discarded instead of inserted, e.g. because a seek made it stale
+    pub fn record_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Hit, miss and drop counters with the current slot occupancy
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            ready: self.ready_count.load(Ordering::Acquire),
+            in_progress: self.in_progress_count.load(Ordering::Acquire),
+        }
+    }
+
     /// Insert a completed frame into the appropriate slot.
     /// 
     /// # Arguments
@@ -211,11 +283,14 @@ impl RingBufferCache {
     /// Update the play head position and detect seek events.
     ///
     /// # Seek Detection
+    /// - **Preroll read**: new_frame within the preroll before the play head → nothing changes
     /// - **Backward seek**: new_frame < current_play_head → invalidate cache
     /// - **Large forward seek**: new_frame >= start_index + 2*capacity → invalidate cache (true seek)
     /// - **Proactive window slide**: Slide at 75% capacity to give prefetcher room to work ahead
     /// - **Normal forward**: Just update play_head
     ///
+    /// After a seek the window starts `preroll` frames before the new play head.
+    ///
     /// # Arguments
     /// * `frame_index` - The new play head position (last requested frame)
     ///
@@ -224,11 +299,18 @@ impl RingBufferCache {
     pub fn update_play_head(&self, frame_index: usize) -> bool {
         let current_play_head = self.play_head.load(Ordering::Acquire);
         let start = self.start_index.load(Ordering::Acquire);
-        
+        let preroll = self.preroll();
+
+        // History of the play head: read, but the play head stays where it is
+        if frame_index < current_play_head && frame_index >= current_play_head.saturating_sub(preroll) && frame_index >= start {
+            return false;
+        }
+
         // Backward seek detection - invalidate cache
         if frame_index < current_play_head {
+            self.export(TelemetryEvent::Seek { from: current_play_head, to: frame_index });
             self.invalidate_internal();
-            self.start_index.store(frame_index, Ordering::Release);
+            self.start_index.store(frame_index.saturating_sub(preroll), Ordering::Release);
             self.play_head.store(frame_index, Ordering::Release);
             return true;
         }
@@ -236,8 +318,9 @@ impl RingBufferCache {
         // Very large forward seek (jump more than capacity ahead) - invalidate cache
         // This is a true seek, not sequential playback
         if frame_index >= start + 2 * self.capacity {
+            self.export(TelemetryEvent::Seek { from: current_play_head, to: frame_index });
             self.invalidate_internal();
-            self.start_index.store(frame_index, Ordering::Release);
+            self.start_index.store(frame_index.saturating_sub(preroll), Ordering::Release);
             self.play_head.store(frame_index, Ordering::Release);
             return true;
         }
@@ -247,14 +330,10 @@ impl RingBufferCache {
         // At 50%, we have capacity/2 slots ahead for prefetching.
         let slide_threshold = start + self.capacity / 2;
         if frame_index >= slide_threshold {
-            // Advance start to keep play head at ~25% from the start
+            // Advance start to keep play head at ~25% from the start (or the preroll, if longer)
             // This maximizes the prefetch runway (75% of capacity ahead)
-            let buffer_behind = self.capacity / 4;
-            let new_start = if frame_index >= buffer_behind {
-                frame_index - buffer_behind
-            } else {
-                0
-            };
+            let buffer_behind = (self.capacity / 4).max(preroll);
+            let new_start = frame_index.saturating_sub(buffer_behind);
             self.advance_start(new_start);
         }
         
@@ -312,6 +391,50 @@ impl RingBufferCache {
         }
     }
 
+    /// Reset a single frame's slot to Empty, leaving the rest of the window intact.
+    /// Used when the source changes underneath a frame that is already cached.
+    ///
+    /// # Arguments
+    /// * `frame_index` - The frame number to drop
+    ///
+    /// # Returns
+    /// `true` if the slot held data (Ready or InProgress), `false` otherwise
+    pub fn invalidate_frame(&self, frame_index: usize) -> bool {
+        let start = self.start_index.load(Ordering::Acquire);
+
+        if let Some(slot_index) = self.frame_to_slot(frame_index, start) {
+            let mut buffer = self.buffer.write().unwrap();
+            let old_state = std::mem::replace(&mut buffer[slot_index], FrameSlot::Empty);
+            match old_state {
+                FrameSlot::Ready(_) => {
+                    self.ready_count.fetch_sub(1, Ordering::Release);
+                    drop(buffer);
+                    self.export(TelemetryEvent::CacheEvict { frames: 1 });
+                    true
+                }
+                FrameSlot::InProgress => {
+                    self.in_progress_count.fetch_sub(1, Ordering::Release);
+                    true
+                }
+                FrameSlot::Empty => false,
+            }
+        } else {
+            false
+        }
+    }
+
+    /// Get the frame indices of all Ready slots in the current window, in ascending order.
+    pub fn ready_frames(&self) -> Vec<usize> {
+        let start = self.start_index.load(Ordering::Acquire);
+        let buffer = self.buffer.read().unwrap();
+        buffer
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.is_ready())
+            .map(|(slot_index, _)| start + slot_index)
+            .collect()
+    }
+
     /// Internal invalidation method - clears slots and increments generation.
     /// Called during seek events to invalidate stale data.
     fn invalidate_internal(&self) {
@@ -320,10 +443,14 @@ impl RingBufferCache {
             *slot = FrameSlot::Empty;
         }
         // Reset counters to 0
-        self.ready_count.store(0, Ordering::Release);
+        let evicted = self.ready_count.swap(0, Ordering::AcqRel);
         self.in_progress_count.store(0, Ordering::Release);
         // Increment generation so in-flight tasks will be rejected
         self.generation.fetch_add(1, Ordering::Release);
+        drop(buffer);
+        if evicted > 0 {
+            self.export(TelemetryEvent::CacheEvict { frames: evicted });
+        }
     }
 
     /// Clear all frames from the cache, resetting all slots to Empty.
@@ -345,6 +472,7 @@ impl RingBufferCache {
 
     /// Get the current number of Ready frames in the cache.
     /// O(1) using atomic counter instead of O(n) iteration.
+    #[cfg(any(test, feature = "unstable"))]
     pub fn len(&self) -> usize {
         self.ready_count.load(Ordering::Acquire)
     }
@@ -356,6 +484,7 @@ impl RingBufferCache {
     }
 
     /// Check if the cache has no Ready frames.
+    #[cfg(any(test, feature = "unstable"))]
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
@@ -385,6 +514,14 @@ impl RingBufferCache {
     }
 }
 
+impl Default for RingBufferCache {
+    /// Create a new RingBufferCache with default capacity of 512.
+    /// This is the recommended default for most use cases as per PRD specifications.
+    fn default() -> Self {
+        Self::new(512)
+    }
+}
+
 impl Clone for RingBufferCache {
     /// Clone creates a new Arc reference to the same underlying data.
     /// Note: This creates a shallow clone that shares the same buffer.
@@ -402,6 +539,11 @@ impl Clone for RingBufferCache {
             generation: AtomicU64::new(self.generation.load(Ordering::Acquire)),
             ready_count: AtomicUsize::new(self.ready_count.load(Ordering::Acquire)),
             in_progress_count: AtomicUsize::new(self.in_progress_count.load(Ordering::Acquire)),
+            preroll: AtomicUsize::new(self.preroll()),
+            telemetry: RwLock::new(self.telemetry.read().unwrap().clone()),
+            hits: AtomicU64::new(self.hits.load(Ordering::Relaxed)),
+            misses: AtomicU64::new(self.misses.load(Ordering::Relaxed)),
+            dropped: AtomicU64::new(self.dropped.load(Ordering::Relaxed)),
         }
     }
 }
@@ -454,6 +596,20 @@ mod tests {
         assert_eq!(retrieved.polystream, vec![42]);
     }
 
+    #[test]
+    fn test_stats() {
+        let cache = RingBufferCache::new(10);
+        cache.insert(0, test_frame_data(1));
+        cache.mark_in_progress(1);
+        assert!(cache.lookup(0).is_some());
+        assert!(cache.lookup(1).is_none());
+        assert!(cache.lookup(2).is_none());
+        // Internal reads are not counted
+        assert!(cache.get(0).is_some());
+        cache.record_dropped();
+        assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 2, dropped: 1, ready: 1, in_progress: 1 });
+    }
+
     #[test]
     fn test_get_miss_empty_slot() {
         let cache = RingBufferCache::new(10);
@@ -581,6 +737,37 @@ mod tests {
         assert!(cache.is_in_range(new_start + 9)); // Still have room at the end
     }
 
+    #[test]
+    fn test_preroll_window() {
+        let cache = RingBufferCache::new(10);
+        cache.set_preroll(3);
+        cache.update_play_head(4);
+        for i in 0..10 {
+            cache.insert(i, test_frame_data(i as u8));
+        }
+
+        // Reading the preroll frames is not a seek, and the play head stays
+        assert!(!cache.update_play_head(2));
+        assert_eq!(cache.get_play_head(), 4);
+        assert!(cache.contains(&1));
+        // Further back is
+        assert!(cache.update_play_head(0));
+
+        // Seeks place the window start before the target
+        assert!(cache.update_play_head(40));
+        assert_eq!(cache.get_start_index(), 37);
+        assert!(cache.update_play_head(20));
+        assert_eq!(cache.get_start_index(), 17);
+        assert_eq!(cache.get_play_head(), 20);
+        // Sliding keeps the preroll too
+        cache.set_preroll(4);
+        cache.update_play_head(22);
+        assert_eq!(cache.get_start_index(), 18);
+
+        cache.set_preroll(100);
+        assert_eq!(cache.preroll(), 5);
+    }
+
     #[test]
     fn test_clear() {
         let cache = RingBufferCache::new(10);
@@ -595,6 +782,25 @@ mod tests {
         assert_eq!(cache.len(), 0);
     }
 
+    #[test]
+    fn test_invalidate_frame() {
+        let cache = RingBufferCache::new(10);
+        cache.insert(2, test_frame_data(2));
+        cache.insert(4, test_frame_data(4));
+        cache.mark_in_progress(6);
+        let generation = cache.generation();
+
+        assert!(cache.invalidate_frame(2));
+        assert!(cache.invalidate_frame(6));
+        assert!(!cache.invalidate_frame(3)); // Already empty
+        assert!(!cache.invalidate_frame(15)); // Out of range
+
+        assert_eq!(cache.ready_frames(), vec![4]);
+        assert_eq!(cache.occupied_count(), 1);
+        // Single-frame invalidation must not reject unrelated in-flight tasks
+        assert_eq!(cache.generation(), generation);
+    }
+
     #[test]
     fn test_len_and_is_empty() {
         let cache = RingBufferCache::new(10);
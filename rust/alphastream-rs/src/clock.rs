@@ -0,0 +1,122 @@
+// Clock module
+// Source of "now" for time-dependent components (frame traces, scheduler deadlines, timeouts).
+// Production code uses the monotonic system clock; tests inject a MockClock through the builder
+// and advance it by hand, so timing behavior is checked instantly and deterministically.
+
+use std::fmt;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Monotonic time source
+pub trait Clock: Send + Sync {
+    /// Current instant. Must never go backwards.
+    fn now(&self) -> Instant;
+}
+
+/// The real monotonic clock, `Instant::now()`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// Clock that only moves when told to
+#[derive(Debug)]
+pub struct MockClock {
+    start: Instant,
+    elapsed: Mutex<Duration>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockClock {
+    /// Create a mock clock, frozen at the current instant
+    pub fn new() -> Self {
+        Self { start: Instant::now(), elapsed: Mutex::new(Duration::ZERO) }
+    }
+
+    /// Move the clock forward
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+
+    /// Time advanced since creation
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+}
+
+/// Shared handle to a clock, as stored by the builder, processor and scheduler.
+/// Handles compare equal when they point to the same clock.
+#[derive(Clone)]
+pub struct SharedClock(Arc<dyn Clock>);
+
+impl SharedClock {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self(clock)
+    }
+
+    pub fn now(&self) -> Instant {
+        self.0.now()
+    }
+}
+
+impl Default for SharedClock {
+    /// The process-wide system clock
+    fn default() -> Self {
+        static SYSTEM: OnceLock<Arc<dyn Clock>> = OnceLock::new();
+        Self(Arc::clone(SYSTEM.get_or_init(|| Arc::new(SystemClock))))
+    }
+}
+
+impl PartialEq for SharedClock {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for SharedClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if *self == SharedClock::default() {
+            f.write_str("SharedClock(system)")
+        } else {
+            f.write_str("SharedClock(custom)")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_clock_moves_only_when_advanced() {
+        let clock = MockClock::new();
+        let t0 = clock.now();
+        assert_eq!(clock.now(), t0);
+        clock.advance(Duration::from_secs(30));
+        assert_eq!(clock.now() - t0, Duration::from_secs(30));
+        assert_eq!(clock.elapsed(), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_shared_clock_equality() {
+        assert_eq!(SharedClock::default(), SharedClock::default());
+        let mock = SharedClock::new(Arc::new(MockClock::new()));
+        assert_ne!(mock, SharedClock::default());
+        assert_eq!(mock.clone(), mock);
+    }
+}
@@ -0,0 +1,355 @@
+// Container module
+// Multi-track container: several logical tracks in one file, found through a track directory in
+// the header. Mask tracks are complete ASVP files embedded as they are, so ASVPFormat plays them
+// with the same range-friendly indexing (see ASVPFormat::with_track); thumbnail and annotation
+// tracks carry keyframe previews and per-frame notes for editors and asset browsers, and cue tracks
+// carry timestamped markers (audio clicks, edit points) to align with the masks.
+//
+// Layout (little-endian, see docs/FILE_FORMAT_CONTAINER.md):
+//   [ "ASVXTRK1" | u32 version | u32 directory_size ] [ zlib(JSON track directory) ] [ track payloads ]
+// Directory offsets are absolute, so a reader fetches only the tracks it uses.
+
+#[cfg(any(test, feature = "unstable"))]
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::formats::{decompress_zlib, FormatError};
+#[cfg(any(test, feature = "unstable"))]
+use crate::formats::{asvp_frame_record, compress_zlib, write_asvp_records, FrameData};
+
+/// First 8 bytes of a container file
+pub const CONTAINER_MAGIC: &[u8; 8] = b"ASVXTRK1";
+/// Container version written by ContainerWriter
+pub const CONTAINER_VERSION: u32 = 1;
+
+/// Name of the annotation track holding a scene's bookmarks, one note per bookmark
+pub const BOOKMARK_TRACK: &str = "bookmarks";
+
+/// What a track holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackKind {
+    /// Polystream masks, an embedded ASVP file
+    Mask,
+    /// R8 preview images of keyframes
+    Thumbnails,
+    /// Text notes attached to frames
+    Annotations,
+    /// Timestamped markers with the frame rate to map them onto frames
+    Cues,
+    /// A kind added by a newer writer; readers skip it
+    #[serde(other)]
+    Unknown,
+}
+
+/// Entry of the track directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackInfo {
+    pub name: String,
+    pub kind: TrackKind,
+    /// Absolute byte offset of the track payload
+    pub offset: u64,
+    /// Length of the track payload in bytes
+    pub length: u64,
+}
+
+/// Preview of a keyframe, R8 like the masks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thumbnail {
+    pub frame_index: u32,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Note attached to a frame
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub frame_index: u32,
+    pub text: String,
+}
+
+/// Timestamped marker, e.g. an audio click or an edit point
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cue {
+    /// Seconds from the start of the stream
+    pub time: f64,
+    pub label: String,
+}
+
+/// Markers of a cue track and the frame rate that maps their times onto frame indices
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CueTrack {
+    pub frame_rate: f64,
+    /// Sorted by time
+    pub cues: Vec<Cue>,
+}
+
+impl CueTrack {
+    /// Cue track at `frame_rate`, sorting `cues` by time
+    pub fn new(frame_rate: f64, mut cues: Vec<Cue>) -> Self {
+        cues.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self { frame_rate, cues }
+    }
+
+    /// Frame a cue falls in
+    pub fn cue_frame(&self, cue: &Cue) -> u32 {
+        // A cue exactly on a frame boundary belongs to that frame despite rounding in time * rate
+        (cue.time * self.frame_rate + 1e-6).floor().max(0.0) as u32
+    }
+
+    /// First cue in `frame_index` or a later frame
+    pub fn next_cue(&self, frame_index: u32) -> Option<&Cue> {
+        let first = self.cues.partition_point(|cue| self.cue_frame(cue) < frame_index);
+        self.cues.get(first)
+    }
+
+    /// Cues falling in the frames of `frames`
+    pub fn cues_in(&self, frames: std::ops::Range<u32>) -> &[Cue] {
+        let start = self.cues.partition_point(|cue| self.cue_frame(cue) < frames.start);
+        let end = self.cues.partition_point(|cue| self.cue_frame(cue) < frames.end);
+        &self.cues[start..end.max(start)]
+    }
+}
+
+/// Thumbnail of the last keyframe at or before `frame_index`, for scrubbing previews.
+/// `thumbnails` must be sorted by frame index, as `decode_thumbnails` returns them.
+#[cfg(any(test, feature = "unstable"))]
+pub fn keyframe_thumbnail(thumbnails: &[Thumbnail], frame_index: u32) -> Option<&Thumbnail> {
+    let after = thumbnails.partition_point(|thumbnail| thumbnail.frame_index <= frame_index);
+    after.checked_sub(1).map(|i| &thumbnails[i])
+}
+
+/// Writer for containers. Collects tracks first, then writes the complete file.
+#[cfg(any(test, feature = "unstable"))]
+pub struct ContainerWriter<W: Write> {
+    writer: W,
+    tracks: Vec<(String, TrackKind, Vec<u8>)>,
+}
+
+#[cfg(any(test, feature = "unstable"))]
+impl<W: Write> ContainerWriter<W> {
+    /// Create a new writer
+    pub fn new(writer: W) -> Self {
+        Self { writer, tracks: Vec::new() }
+    }
+
+    /// Add a mask track. The first mask track is the one played when no track is selected.
+    pub fn add_mask_track(&mut self, name: &str, frames: &[FrameData]) -> Result<(), FormatError> {
+        let records = frames.iter()
+            .map(|frame| asvp_frame_record(&frame.polystream))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut payload = Vec::new();
+        write_asvp_records(&mut payload, &records)?;
+        self.tracks.push((name.to_string(), TrackKind::Mask, payload));
+        Ok(())
+    }
+
+    /// Add a thumbnail track
+    pub fn add_thumbnail_track(&mut self, name: &str, thumbnails: &[Thumbnail]) -> Result<(), FormatError> {
+        let mut raw = Vec::new();
+        for thumbnail in thumbnails {
+            if thumbnail.pixels.len() != thumbnail.width as usize * thumbnail.height as usize {
+                return Err(FormatError::InvalidFormat(format!("Thumbnail of frame {} does not match its size", thumbnail.frame_index)));
+            }
+            raw.extend_from_slice(&thumbnail.frame_index.to_le_bytes());
+            raw.extend_from_slice(&thumbnail.width.to_le_bytes());
+            raw.extend_from_slice(&thumbnail.height.to_le_bytes());
+            raw.extend_from_slice(&thumbnail.pixels);
+        }
+        self.tracks.push((name.to_string(), TrackKind::Thumbnails, compress_zlib(&raw)?));
+        Ok(())
+    }
+
+    /// Add an annotation track
+    pub fn add_annotation_track(&mut self, name: &str, annotations: &[Annotation]) -> Result<(), FormatError> {
+        let json = serde_json::to_vec(annotations).map_err(|e| FormatError::InvalidFormat(e.to_string()))?;
+        self.tracks.push((name.to_string(), TrackKind::Annotations, compress_zlib(&json)?));
+        Ok(())
+    }
+
+    /// Add a cue track
+    pub fn add_cue_track(&mut self, name: &str, cues: &CueTrack) -> Result<(), FormatError> {
+        if !cues.frame_rate.is_finite() || cues.frame_rate <= 0.0 {
+            return Err(FormatError::InvalidFormat(format!("Invalid cue track frame rate {}", cues.frame_rate)));
+        }
+        let json = serde_json::to_vec(cues).map_err(|e| FormatError::InvalidFormat(e.to_string()))?;
+        self.tracks.push((name.to_string(), TrackKind::Cues, compress_zlib(&json)?));
+        Ok(())
+    }
+
+    /// Write the header, the track directory and all tracks.
+    /// Returns the inner writer after writing.
+    pub fn write_all(mut self) -> Result<W, FormatError> {
+        for (i, (name, _, _)) in self.tracks.iter().enumerate() {
+            if self.tracks[..i].iter().any(|(other, _, _)| other == name) {
+                return Err(FormatError::InvalidFormat(format!("Duplicate track name '{}'", name)));
+            }
+        }
+        // Offsets depend on the directory size and the directory on the offsets: fix the offsets
+        // with the size of the previous attempt until the compressed directory stops growing
+        let mut directory_size = 0;
+        let compressed = loop {
+            let mut offset = 16 + directory_size as u64;
+            let directory: Vec<TrackInfo> = self.tracks.iter()
+                .map(|(name, kind, payload)| {
+                    let info = TrackInfo { name: name.clone(), kind: *kind, offset, length: payload.len() as u64 };
+                    offset += info.length;
+                    info
+                })
+                .collect();
+            let json = serde_json::to_vec(&directory).map_err(|e| FormatError::InvalidFormat(e.to_string()))?;
+            let compressed = compress_zlib(&json)?;
+            if compressed.len() <= directory_size {
+                break compressed;
+            }
+            directory_size = compressed.len();
+        };
+
+        let mut header = [0u8; 16];
+        header[0..8].copy_from_slice(CONTAINER_MAGIC);
+        header[8..12].copy_from_slice(&CONTAINER_VERSION.to_le_bytes());
+        header[12..16].copy_from_slice(&(directory_size as u32).to_le_bytes());
+        self.writer.write_all(&header)?;
+        self.writer.write_all(&compressed)?;
+        // A directory that compressed smaller on the last attempt is padded to the size the offsets assume
+        self.writer.write_all(&vec![0; directory_size - compressed.len()])?;
+        for (_, _, payload) in &self.tracks {
+            self.writer.write_all(payload)?;
+        }
+        Ok(self.writer)
+    }
+}
+
+/// Read the track directory following a container header that was already read
+pub(crate) async fn read_directory<R: AsyncRead + Unpin>(header: &[u8; 16], reader: &mut R) -> Result<Vec<TrackInfo>, FormatError> {
+    let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+    if version > CONTAINER_VERSION {
+        return Err(FormatError::InvalidFormat(format!("Unsupported container version {}", version)));
+    }
+    let directory_size = u32::from_le_bytes(header[12..16].try_into().unwrap());
+    let mut compressed = vec![0u8; directory_size as usize];
+    reader.read_exact(&mut compressed).await?;
+    // Decompression stops at the end of the zlib stream, so padding is ignored
+    let json = decompress_zlib(&compressed)?;
+    serde_json::from_slice(&json).map_err(|e| FormatError::InvalidFormat(format!("Invalid track directory: {}", e)))
+}
+
+/// The track of `kind` named `name`, or the first track of `kind` without a name
+pub(crate) fn select_track<'a>(tracks: &'a [TrackInfo], kind: TrackKind, name: Option<&str>) -> Result<&'a TrackInfo, FormatError> {
+    tracks.iter()
+        .find(|track| track.kind == kind && name.is_none_or(|name| track.name == name))
+        .ok_or_else(|| match name {
+            Some(name) => FormatError::InvalidFormat(format!("No {:?} track named '{}'", kind, name)),
+            None => FormatError::InvalidFormat(format!("Container has no {:?} track", kind)),
+        })
+}
+
+/// Decode the payload of a thumbnail track, sorted by frame index
+pub(crate) fn decode_thumbnails(payload: &[u8]) -> Result<Vec<Thumbnail>, FormatError> {
+    let raw = decompress_zlib(payload)?;
+    let mut thumbnails = Vec::new();
+    let mut rest = raw.as_slice();
+    while !rest.is_empty() {
+        let field = |i: usize| rest.get(i * 4..i * 4 + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()));
+        let (Some(frame_index), Some(width), Some(height)) = (field(0), field(1), field(2)) else {
+            return Err(FormatError::InvalidFormat("Thumbnail header incomplete".to_string()));
+        };
+        let size = width as usize * height as usize;
+        let Some(pixels) = rest.get(12..12 + size) else {
+            return Err(FormatError::InvalidFormat("Thumbnail pixels incomplete".to_string()));
+        };
+        thumbnails.push(Thumbnail { frame_index, width, height, pixels: pixels.to_vec() });
+        rest = &rest[12 + size..];
+    }
+    thumbnails.sort_by_key(|thumbnail| thumbnail.frame_index);
+    Ok(thumbnails)
+}
+
+/// Decode the payload of an annotation track
+pub(crate) fn decode_annotations(payload: &[u8]) -> Result<Vec<Annotation>, FormatError> {
+    let json = decompress_zlib(payload)?;
+    serde_json::from_slice(&json).map_err(|e| FormatError::InvalidFormat(format!("Invalid annotation track: {}", e)))
+}
+
+/// Decode the payload of a cue track
+pub(crate) fn decode_cues(payload: &[u8]) -> Result<CueTrack, FormatError> {
+    let json = decompress_zlib(payload)?;
+    let track: CueTrack = serde_json::from_slice(&json).map_err(|e| FormatError::InvalidFormat(format!("Invalid cue track: {}", e)))?;
+    Ok(CueTrack::new(track.frame_rate, track.cues))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{ASFormat, ASVPFormat};
+
+    fn frame(fill: u8) -> FrameData {
+        let mut polystream = Vec::new();
+        polystream.extend_from_slice(&1u32.to_le_bytes());
+        polystream.extend_from_slice(&6u32.to_le_bytes());
+        polystream.extend_from_slice(&[fill; 6]);
+        FrameData { polystream, bitmap: None, triangle_strip: None }
+    }
+
+    #[tokio::test]
+    async fn test_container_roundtrip() {
+        let thumbnails = vec![
+            Thumbnail { frame_index: 2, width: 2, height: 1, pixels: vec![0, 255] },
+            Thumbnail { frame_index: 0, width: 1, height: 1, pixels: vec![9] },
+        ];
+        let annotations = vec![Annotation { frame_index: 1, text: "cut".to_string() }];
+        let mut writer = ContainerWriter::new(Vec::new());
+        writer.add_mask_track("person", &[frame(1), frame(2), frame(3)]).unwrap();
+        writer.add_thumbnail_track("keyframes", &thumbnails).unwrap();
+        writer.add_mask_track("ball", &[frame(7)]).unwrap();
+        writer.add_annotation_track("notes", &annotations).unwrap();
+        let bytes = writer.write_all().unwrap();
+
+        // The first mask track plays by default, others are selected by name
+        let mut person = ASVPFormat::new(std::io::Cursor::new(bytes.clone())).await.unwrap();
+        assert_eq!(person.frame_count().await.unwrap(), 3);
+        assert_eq!(person.decode_frame(2).await.unwrap().polystream, frame(3).polystream);
+        let names: Vec<_> = person.tracks().iter().map(|track| track.name.as_str()).collect();
+        assert_eq!(names, ["person", "keyframes", "ball", "notes"]);
+
+        let mut ball = ASVPFormat::with_track(std::io::Cursor::new(bytes.clone()), Some("ball")).await.unwrap();
+        assert_eq!(ball.frame_count().await.unwrap(), 1);
+        assert_eq!(ball.decode_frame(0).await.unwrap().polystream, frame(7).polystream);
+
+        let decoded = ball.thumbnails(None).await.unwrap();
+        assert_eq!(decoded.iter().map(|t| t.frame_index).collect::<Vec<_>>(), [0, 2]);
+        assert_eq!(keyframe_thumbnail(&decoded, 1).unwrap().pixels, [9]);
+        assert_eq!(keyframe_thumbnail(&decoded, 5).unwrap().pixels, [0, 255]);
+        assert_eq!(ball.annotations(Some("notes")).await.unwrap(), annotations);
+
+        assert!(ASVPFormat::with_track(std::io::Cursor::new(bytes), Some("keyframes")).await.is_err());
+        let mut duplicate = ContainerWriter::new(Vec::new());
+        duplicate.add_mask_track("a", &[frame(1)]).unwrap();
+        duplicate.add_mask_track("a", &[frame(2)]).unwrap();
+        assert!(duplicate.write_all().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cue_track() {
+        let cue = |time: f64, label: &str| Cue { time, label: label.to_string() };
+        let mut writer = ContainerWriter::new(Vec::new());
+        writer.add_mask_track("mask", &[frame(1)]).unwrap();
+        writer.add_cue_track("clicks", &CueTrack::new(25.0, vec![cue(1.0, "b"), cue(0.1, "a"), cue(1.06, "c")])).unwrap();
+        assert!(writer.add_cue_track("bad", &CueTrack::new(0.0, Vec::new())).is_err());
+        let format = ASVPFormat::new(std::io::Cursor::new(writer.write_all().unwrap())).await.unwrap();
+
+        let cues = format.cues(None).await.unwrap();
+        assert_eq!(cues.cues.iter().map(|c| c.label.as_str()).collect::<Vec<_>>(), ["a", "b", "c"]);
+        // 0.1s is frame 2.5 → 2, 1.0s is exactly frame 25
+        assert_eq!(cues.next_cue(0).unwrap().label, "a");
+        assert_eq!(cues.next_cue(3).unwrap().label, "b");
+        assert_eq!(cues.next_cue(25).unwrap().label, "b");
+        assert_eq!(cues.next_cue(26).unwrap().label, "c");
+        assert!(cues.next_cue(27).is_none());
+        assert_eq!(cues.cues_in(20..27).len(), 2);
+        assert!(cues.cues_in(3..25).is_empty());
+    }
+}
@@ -0,0 +1,215 @@
+//! Delta encoding of ASVP frames
+//!
+//! A delta-encoded ASVP file (magic `ASVPDLT1`, see `ASVPWriter::keyframe_interval`) stores periodic
+//! keyframes with their full polystream; the frames in between store, per channel, only the bytes that
+//! differ from the same channel of their keyframe. Masks that barely move between frames shrink to a
+//! few bytes per frame, while any frame still decodes from at most two records.
+//!
+//! Decompressed record payloads start with a tag byte:
+//! - keyframe: `0`, then the polystream
+//! - delta: `1`, the keyframe index (u32 LE), the channel count (u32 LE), then per channel the
+//!   length of the prefix and of the suffix kept from the keyframe channel and the length of the
+//!   replacement bytes in between (u32 LE each), followed by those bytes. Channels past the
+//!   keyframe's channel count are diffed against an empty channel.
+
+use crate::formats::FormatError;
+
+const KEYFRAME_TAG: u8 = 0;
+const DELTA_TAG: u8 = 1;
+
+/// A decompressed record of a delta-encoded file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum DeltaRecord<'a> {
+    Keyframe(&'a [u8]),
+    Delta { keyframe: u32, edits: &'a [u8] },
+}
+
+impl<'a> DeltaRecord<'a> {
+    pub(crate) fn parse(payload: &'a [u8]) -> Result<Self, FormatError> {
+        match payload.split_first() {
+            Some((&KEYFRAME_TAG, polystream)) => Ok(DeltaRecord::Keyframe(polystream)),
+            Some((&DELTA_TAG, rest)) if rest.len() >= 4 => Ok(DeltaRecord::Delta {
+                keyframe: u32::from_le_bytes(rest[0..4].try_into().unwrap()),
+                edits: &rest[4..],
+            }),
+            _ => Err(FormatError::InvalidFormat("Unknown delta record".to_string())),
+        }
+    }
+}
+
+/// Encode the frames of a delta-encoded file as record payloads. Every `interval`-th frame is a
+/// keyframe; so is any frame whose delta would not be smaller than the frame itself.
+/// Returns the payloads and the indices of the keyframes.
+#[cfg(any(test, feature = "unstable"))]
+pub(crate) fn encode_frames(polystreams: &[&[u8]], interval: usize) -> Result<(Vec<Vec<u8>>, Vec<u32>), FormatError> {
+    let interval = interval.max(1);
+    let mut payloads = Vec::with_capacity(polystreams.len());
+    let mut keyframes = Vec::new();
+    let mut keyframe: Option<(u32, Vec<&[u8]>)> = None;
+    for (index, &polystream) in polystreams.iter().enumerate() {
+        let channels = split_channels(polystream)?;
+        let delta = match &keyframe {
+            Some((key_index, key_channels)) if index % interval != 0 => Some(encode_delta(*key_index, key_channels, &channels)),
+            _ => None,
+        };
+        match delta {
+            Some(delta) if delta.len() < polystream.len() + 1 => payloads.push(delta),
+            _ => {
+                let mut payload = Vec::with_capacity(polystream.len() + 1);
+                payload.push(KEYFRAME_TAG);
+                payload.extend_from_slice(polystream);
+                payloads.push(payload);
+                keyframes.push(index as u32);
+                keyframe = Some((index as u32, channels));
+            }
+        }
+    }
+    Ok((payloads, keyframes))
+}
+
+#[cfg(any(test, feature = "unstable"))]
+fn encode_delta(key_index: u32, key: &[&[u8]], channels: &[&[u8]]) -> Vec<u8> {
+    let mut delta = vec![DELTA_TAG];
+    delta.extend_from_slice(&key_index.to_le_bytes());
+    delta.extend_from_slice(&(channels.len() as u32).to_le_bytes());
+    for (index, channel) in channels.iter().enumerate() {
+        let base = key.get(index).copied().unwrap_or(&[]);
+        let prefix = base.iter().zip(channel.iter()).take_while(|(a, b)| a == b).count();
+        let suffix = base[prefix..].iter().rev().zip(channel[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+        let middle = &channel[prefix..channel.len() - suffix];
+        delta.extend_from_slice(&(prefix as u32).to_le_bytes());
+        delta.extend_from_slice(&(suffix as u32).to_le_bytes());
+        delta.extend_from_slice(&(middle.len() as u32).to_le_bytes());
+        delta.extend_from_slice(middle);
+    }
+    delta
+}
+
+/// Rebuild a frame's polystream from its keyframe polystream and the edits of its delta record
+pub(crate) fn apply_delta(keyframe: &[u8], edits: &[u8]) -> Result<Vec<u8>, FormatError> {
+    let malformed = || FormatError::InvalidFormat("Malformed delta record".to_string());
+    let key = split_channels(keyframe)?;
+    let mut reader = edits;
+    let read_u32 = |reader: &mut &[u8]| -> Result<usize, FormatError> {
+        let (value, rest) = reader.split_first_chunk::<4>().ok_or_else(malformed)?;
+        *reader = rest;
+        Ok(u32::from_le_bytes(*value) as usize)
+    };
+    let channel_count = read_u32(&mut reader)?;
+    let mut channels = Vec::with_capacity(channel_count.min(edits.len()));
+    for index in 0..channel_count {
+        let base = key.get(index).copied().unwrap_or(&[]);
+        let prefix = read_u32(&mut reader)?;
+        let suffix = read_u32(&mut reader)?;
+        let len = read_u32(&mut reader)?;
+        if prefix.checked_add(suffix).is_none_or(|kept| kept > base.len()) || len > reader.len() {
+            return Err(malformed());
+        }
+        let (middle, rest) = reader.split_at(len);
+        reader = rest;
+        let mut channel = Vec::with_capacity(prefix + len + suffix);
+        channel.extend_from_slice(&base[..prefix]);
+        channel.extend_from_slice(middle);
+        channel.extend_from_slice(&base[base.len() - suffix..]);
+        channels.push(channel);
+    }
+    if !reader.is_empty() {
+        return Err(malformed());
+    }
+
+    let mut polystream = Vec::new();
+    polystream.extend_from_slice(&(channels.len() as u32).to_le_bytes());
+    for channel in &channels {
+        polystream.extend_from_slice(&(channel.len() as u32).to_le_bytes());
+    }
+    for channel in &channels {
+        polystream.extend_from_slice(channel);
+    }
+    Ok(polystream)
+}
+
+/// Channel payloads of a polystream
+fn split_channels(polystream: &[u8]) -> Result<Vec<&[u8]>, FormatError> {
+    let malformed = || FormatError::InvalidFormat("Polystream header incomplete".to_string());
+    let channel_count = polystream.get(0..4).map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize).ok_or_else(malformed)?;
+    let header_size = channel_count.checked_mul(4).and_then(|n| n.checked_add(4)).ok_or_else(malformed)?;
+    let sizes = polystream.get(4..header_size).ok_or_else(malformed)?;
+    let mut offset = header_size;
+    sizes.chunks_exact(4)
+        .map(|size| {
+            let size = u32::from_le_bytes(size.try_into().unwrap()) as usize;
+            let channel = offset.checked_add(size).and_then(|end| polystream.get(offset..end)).ok_or_else(malformed)?;
+            offset += size;
+            Ok(channel)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn polystream(channels: &[&[u8]]) -> Vec<u8> {
+        let mut data = (channels.len() as u32).to_le_bytes().to_vec();
+        for channel in channels {
+            data.extend_from_slice(&(channel.len() as u32).to_le_bytes());
+        }
+        for channel in channels {
+            data.extend_from_slice(channel);
+        }
+        data
+    }
+
+    fn decode(payloads: &[Vec<u8>], index: usize) -> Vec<u8> {
+        match DeltaRecord::parse(&payloads[index]).unwrap() {
+            DeltaRecord::Keyframe(polystream) => polystream.to_vec(),
+            DeltaRecord::Delta { keyframe, edits } => match DeltaRecord::parse(&payloads[keyframe as usize]).unwrap() {
+                DeltaRecord::Keyframe(key) => apply_delta(key, edits).unwrap(),
+                DeltaRecord::Delta { .. } => panic!("delta of a delta"),
+            },
+        }
+    }
+
+    #[test]
+    fn test_delta_roundtrip() {
+        let outline: Vec<u8> = (0..200).map(|i| (i % 7) as u8).collect();
+        let mut moved = outline.clone();
+        moved[100] = 42;
+        let mut grown = outline.clone();
+        grown.extend_from_slice(&[1, 2, 3, 4]);
+        let frames = [
+            polystream(&[&outline, &[9, 9, 9, 9]]),
+            polystream(&[&outline, &[9, 9, 9, 9]]),
+            polystream(&[&moved, &[9, 9, 8, 9]]),
+            polystream(&[&grown]),
+            polystream(&[&outline, &[9, 9, 9, 9], &[5, 5, 5, 5, 5, 5]]),
+            polystream(&[&moved, &[9, 9, 9, 9]]),
+        ];
+        let slices: Vec<&[u8]> = frames.iter().map(Vec::as_slice).collect();
+        let (payloads, keyframes) = encode_frames(&slices, 4).unwrap();
+        assert_eq!(keyframes, [0, 4]);
+        for (index, frame) in frames.iter().enumerate() {
+            assert_eq!(&decode(&payloads, index), frame, "frame {}", index);
+        }
+        // Periodic keyframes, small deltas in between
+        assert!(matches!(DeltaRecord::parse(&payloads[0]).unwrap(), DeltaRecord::Keyframe(_)));
+        assert!(matches!(DeltaRecord::parse(&payloads[4]).unwrap(), DeltaRecord::Keyframe(_)));
+        assert!(matches!(DeltaRecord::parse(&payloads[5]).unwrap(), DeltaRecord::Delta { keyframe: 4, .. }));
+        assert!(payloads[1].len() < 40 && payloads[2].len() < 40);
+    }
+
+    #[test]
+    fn test_malformed_deltas() {
+        let key = polystream(&[&[1, 2, 3, 4]]);
+        // Keeps more bytes than the keyframe channel has
+        let mut edits = 1u32.to_le_bytes().to_vec();
+        for n in [3u32, 3, 0] {
+            edits.extend_from_slice(&n.to_le_bytes());
+        }
+        assert!(apply_delta(&key, &edits).is_err());
+        // Truncated
+        assert!(apply_delta(&key, &edits[..6]).is_err());
+        assert!(DeltaRecord::parse(&[7]).is_err());
+        assert!(DeltaRecord::parse(&[]).is_err());
+    }
+}
@@ -0,0 +1,151 @@
+// Entitlement module
+// Hook for rights-managed deployments: an EntitlementProvider is asked before an encrypted source is
+// opened and again periodically during playback, and can refuse decryption with a typed error.
+// Refusals stop the processor from decrypting new frames (and drop the decrypted frames it cached)
+// until a later check succeeds again, without the format code knowing about licensing.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::clock::SharedClock;
+
+/// Default time between checks during playback
+pub const DEFAULT_RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Why a provider refused playback
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum EntitlementError {
+    /// The user is not entitled to this content
+    #[error("Playback not permitted: {0}")]
+    Denied(String),
+    /// The entitlement existed but has run out (rental period, subscription)
+    #[error("Entitlement expired")]
+    Expired,
+    /// The provider could not decide, e.g. the license server is unreachable
+    #[error("Entitlement check unavailable: {0}")]
+    Unavailable(String),
+}
+
+/// The content an entitlement check is about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntitlementRequest {
+    /// Source URI (path or URL) as given to the builder, empty for sources built from memory
+    pub uri: String,
+    pub scene_id: u32,
+    pub version: Vec<u8>,
+}
+
+/// Decides whether encrypted content may be decrypted.
+/// `check` runs on the processor's worker threads while decoding is paused, so it should answer
+/// from local state (a cached license) and refresh that state elsewhere.
+pub trait EntitlementProvider: Send + Sync {
+    fn check(&self, request: &EntitlementRequest) -> Result<(), EntitlementError>;
+
+    /// Time between checks during playback, None to only check on open
+    fn recheck_interval(&self) -> Option<Duration> {
+        Some(DEFAULT_RECHECK_INTERVAL)
+    }
+}
+
+/// Shared handle to a provider, as stored by the builder.
+/// Handles compare equal when they point to the same provider.
+#[derive(Clone)]
+pub struct SharedEntitlementProvider(Arc<dyn EntitlementProvider>);
+
+impl SharedEntitlementProvider {
+    pub fn new(provider: Arc<dyn EntitlementProvider>) -> Self {
+        Self(provider)
+    }
+}
+
+impl PartialEq for SharedEntitlementProvider {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for SharedEntitlementProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedEntitlementProvider(..)")
+    }
+}
+
+/// A provider bound to one source, with the outcome of the last check
+pub(crate) struct EntitlementGate {
+    provider: SharedEntitlementProvider,
+    request: EntitlementRequest,
+    clock: SharedClock,
+    last: Mutex<(Instant, Result<(), EntitlementError>)>,
+}
+
+impl EntitlementGate {
+    /// Check on open; fails if the provider refuses
+    pub(crate) fn open(provider: SharedEntitlementProvider, request: EntitlementRequest, clock: SharedClock) -> Result<Self, EntitlementError> {
+        provider.0.check(&request)?;
+        let last = Mutex::new((clock.now(), Ok(())));
+        Ok(Self { provider, request, clock, last })
+    }
+
+    /// Outcome of the last check, checking again first if the recheck interval has passed.
+    /// The second value is true when this call changed the outcome from permitted to refused.
+    pub(crate) fn ensure(&self) -> (Result<(), EntitlementError>, bool) {
+        let mut last = self.last.lock().unwrap();
+        let now = self.clock.now();
+        if self.provider.0.recheck_interval().is_some_and(|interval| now.duration_since(last.0) >= interval) {
+            let result = self.provider.0.check(&self.request);
+            let revoked = last.1.is_ok() && result.is_err();
+            *last = (now, result);
+            return (last.1.clone(), revoked);
+        }
+        (last.1.clone(), false)
+    }
+
+    /// Outcome of the last check without checking again
+    pub(crate) fn status(&self) -> Result<(), EntitlementError> {
+        self.last.lock().unwrap().1.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct Rental {
+        expired: AtomicBool,
+        checks: AtomicUsize,
+    }
+
+    impl EntitlementProvider for Rental {
+        fn check(&self, _request: &EntitlementRequest) -> Result<(), EntitlementError> {
+            self.checks.fetch_add(1, Ordering::SeqCst);
+            if self.expired.load(Ordering::SeqCst) { Err(EntitlementError::Expired) } else { Ok(()) }
+        }
+    }
+
+    #[test]
+    fn test_gate_rechecks_after_interval() {
+        let rental = Arc::new(Rental::default());
+        let clock = Arc::new(MockClock::new());
+        let request = EntitlementRequest { uri: "scene.asvr".to_string(), scene_id: 1, version: b"1".to_vec() };
+        let gate = EntitlementGate::open(SharedEntitlementProvider::new(rental.clone()), request.clone(), SharedClock::new(clock.clone())).unwrap();
+        rental.expired.store(true, Ordering::SeqCst);
+        // Within the interval the last outcome stands
+        assert_eq!(gate.ensure(), (Ok(()), false));
+        clock.advance(DEFAULT_RECHECK_INTERVAL);
+        assert_eq!(gate.ensure(), (Err(EntitlementError::Expired), true));
+        assert_eq!(gate.ensure(), (Err(EntitlementError::Expired), false));
+        assert_eq!(rental.checks.load(Ordering::SeqCst), 2);
+
+        // Renewed: the next check permits playback again
+        rental.expired.store(false, Ordering::SeqCst);
+        clock.advance(DEFAULT_RECHECK_INTERVAL);
+        assert_eq!(gate.ensure(), (Ok(()), false));
+        assert!(EntitlementGate::open(SharedEntitlementProvider::new(rental.clone()), request, SharedClock::default()).is_ok());
+    }
+}
@@ -0,0 +1,394 @@
+//! Frame filter expressions
+//!
+//! A tiny expression language for selecting frames by their mask statistics, e.g.
+//! `area > 5000 && bbox.w > 100`. Expressions are parsed once and then evaluated per frame.
+//!
+//! Variables: `frame`, `area`, `coverage`, `bbox.x`, `bbox.y`, `bbox.w`, `bbox.h`.
+//! Operators, loosest binding first: `||`, `&&`, comparisons (`< <= > >= == !=`),
+//! `+ -`, `* /`, unary `! -`. Parentheses group. All values are numbers; comparisons
+//! and logic operators produce 1 (true) or 0 (false), and any non-zero value is true.
+
+use thiserror::Error;
+
+use crate::stats::MaskStats;
+
+/// Error raised when an expression cannot be parsed
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("{message} at position {position}")]
+pub struct FilterError {
+    /// Byte offset into the expression
+    pub position: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Var {
+    Frame,
+    Area,
+    Coverage,
+    BboxX,
+    BboxY,
+    BboxW,
+    BboxH,
+}
+
+impl Var {
+    fn from_name(name: &str) -> Option<Var> {
+        match name {
+            "frame" => Some(Var::Frame),
+            "area" => Some(Var::Area),
+            "coverage" => Some(Var::Coverage),
+            "bbox.x" => Some(Var::BboxX),
+            "bbox.y" => Some(Var::BboxY),
+            "bbox.w" => Some(Var::BboxW),
+            "bbox.h" => Some(Var::BboxH),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinOp {
+    Or,
+    And,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Num(f64),
+    Var(Var),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+}
+
+/// Operators, longest first so `<=` is not read as `<` followed by `=`
+const OPERATORS: [&str; 14] = ["||", "&&", "<=", ">=", "==", "!=", "<", ">", "+", "-", "*", "/", "!", "="];
+
+fn tokenize(src: &str) -> Result<Vec<(usize, Token)>, FilterError> {
+    let bytes = src.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c.is_ascii_whitespace() {
+            i += 1;
+        } else if c == b'(' {
+            tokens.push((i, Token::LParen));
+            i += 1;
+        } else if c == b')' {
+            tokens.push((i, Token::RParen));
+            i += 1;
+        } else if c.is_ascii_digit() || c == b'.' {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+                i += 1;
+            }
+            let value = src[start..i].parse().map_err(|_| FilterError {
+                position: start,
+                message: format!("invalid number '{}'", &src[start..i]),
+            })?;
+            tokens.push((start, Token::Num(value)));
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_' || bytes[i] == b'.') {
+                i += 1;
+            }
+            tokens.push((start, Token::Ident(src[start..i].to_string())));
+        } else if let Some(op) = OPERATORS.iter().find(|op| src[i..].starts_with(*op)) {
+            if *op == "=" {
+                return Err(FilterError { position: i, message: "use '==' for comparison".to_string() });
+            }
+            tokens.push((i, Token::Op(op)));
+            i += op.len();
+        } else {
+            return Err(FilterError {
+                position: i,
+                message: format!("unexpected character '{}'", src[i..].chars().next().unwrap()),
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser, one method per precedence level
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map(|(p, _)| *p).unwrap_or(self.end)
+    }
+
+    fn error(&self, message: &str) -> FilterError {
+        FilterError { position: self.position(), message: message.to_string() }
+    }
+
+    /// Consume the next token if it is one of `ops`
+    fn take_op(&mut self, ops: &[(&str, BinOp)]) -> Option<BinOp> {
+        if let Some(Token::Op(op)) = self.peek() {
+            if let Some((_, bin)) = ops.iter().find(|(s, _)| s == op) {
+                self.pos += 1;
+                return Some(*bin);
+            }
+        }
+        None
+    }
+
+    fn binary_level(
+        &mut self,
+        ops: &[(&str, BinOp)],
+        next: fn(&mut Parser) -> Result<Expr, FilterError>,
+    ) -> Result<Expr, FilterError> {
+        let mut lhs = next(self)?;
+        while let Some(op) = self.take_op(ops) {
+            let rhs = next(self)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn or(&mut self) -> Result<Expr, FilterError> {
+        self.binary_level(&[("||", BinOp::Or)], Parser::and)
+    }
+
+    fn and(&mut self) -> Result<Expr, FilterError> {
+        self.binary_level(&[("&&", BinOp::And)], Parser::comparison)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, FilterError> {
+        let lhs = self.sum()?;
+        let ops = [
+            ("<", BinOp::Lt),
+            ("<=", BinOp::Le),
+            (">", BinOp::Gt),
+            (">=", BinOp::Ge),
+            ("==", BinOp::Eq),
+            ("!=", BinOp::Ne),
+        ];
+        match self.take_op(&ops) {
+            Some(op) => {
+                let rhs = self.sum()?;
+                if self.take_op(&ops).is_some() {
+                    return Err(self.error("comparisons cannot be chained, use '&&'"));
+                }
+                Ok(Expr::Binary(op, Box::new(lhs), Box::new(rhs)))
+            }
+            None => Ok(lhs),
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, FilterError> {
+        self.binary_level(&[("+", BinOp::Add), ("-", BinOp::Sub)], Parser::product)
+    }
+
+    fn product(&mut self) -> Result<Expr, FilterError> {
+        self.binary_level(&[("*", BinOp::Mul), ("/", BinOp::Div)], Parser::unary)
+    }
+
+    fn unary(&mut self) -> Result<Expr, FilterError> {
+        match self.peek() {
+            Some(Token::Op("!")) => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            Some(Token::Op("-")) => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.unary()?)))
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, FilterError> {
+        let token = self.peek().cloned();
+        match token {
+            Some(Token::Num(n)) => {
+                self.pos += 1;
+                Ok(Expr::Num(n))
+            }
+            Some(Token::Ident(name)) => match Var::from_name(&name) {
+                Some(var) => {
+                    self.pos += 1;
+                    Ok(Expr::Var(var))
+                }
+                None => Err(self.error(&format!("unknown variable '{}'", name))),
+            },
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let inner = self.or()?;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(self.error("expected ')'"));
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Some(_) => Err(self.error("expected a number, variable or '('")),
+            None => Err(self.error("unexpected end of expression")),
+        }
+    }
+}
+
+/// A parsed filter expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameFilter {
+    expr: Expr,
+}
+
+impl FrameFilter {
+    /// Parse a filter expression.
+    ///
+    /// # Returns
+    /// The parsed filter, or a `FilterError` pointing at the offending position
+    pub fn parse(src: &str) -> Result<FrameFilter, FilterError> {
+        let tokens = tokenize(src)?;
+        let mut parser = Parser { tokens, pos: 0, end: src.len() };
+        let expr = parser.or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(parser.error("unexpected token"));
+        }
+        Ok(FrameFilter { expr })
+    }
+
+    /// Evaluate the filter for a frame.
+    ///
+    /// # Arguments
+    /// * `frame_index` - Index of the frame, available as `frame`.
+    /// * `stats` - Statistics of the frame's mask.
+    ///
+    /// # Returns
+    /// `true` if the frame matches
+    pub fn matches(&self, frame_index: u32, stats: &MaskStats) -> bool {
+        truthy(eval(&self.expr, frame_index, stats))
+    }
+}
+
+fn truthy(value: f64) -> bool {
+    value != 0.0 && !value.is_nan()
+}
+
+fn bool_value(b: bool) -> f64 {
+    if b { 1.0 } else { 0.0 }
+}
+
+fn eval(expr: &Expr, frame_index: u32, stats: &MaskStats) -> f64 {
+    match expr {
+        Expr::Num(n) => *n,
+        Expr::Var(var) => match var {
+            Var::Frame => frame_index as f64,
+            Var::Area => stats.area as f64,
+            Var::Coverage => stats.coverage,
+            Var::BboxX => stats.bbox.x as f64,
+            Var::BboxY => stats.bbox.y as f64,
+            Var::BboxW => stats.bbox.w as f64,
+            Var::BboxH => stats.bbox.h as f64,
+        },
+        Expr::Not(inner) => bool_value(!truthy(eval(inner, frame_index, stats))),
+        Expr::Neg(inner) => -eval(inner, frame_index, stats),
+        Expr::Binary(op, lhs, rhs) => {
+            let l = eval(lhs, frame_index, stats);
+            // Short-circuit the logic operators
+            match op {
+                BinOp::Or if truthy(l) => return 1.0,
+                BinOp::And if !truthy(l) => return 0.0,
+                _ => {}
+            }
+            let r = eval(rhs, frame_index, stats);
+            match op {
+                BinOp::Or | BinOp::And => bool_value(truthy(r)),
+                BinOp::Lt => bool_value(l < r),
+                BinOp::Le => bool_value(l <= r),
+                BinOp::Gt => bool_value(l > r),
+                BinOp::Ge => bool_value(l >= r),
+                BinOp::Eq => bool_value(l == r),
+                BinOp::Ne => bool_value(l != r),
+                BinOp::Add => l + r,
+                BinOp::Sub => l - r,
+                BinOp::Mul => l * r,
+                BinOp::Div => l / r,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::BoundingBox;
+
+    fn stats(area: u64, w: u32, h: u32) -> MaskStats {
+        MaskStats { area, coverage: area as f64 / 10000.0, bbox: BoundingBox { x: 10, y: 20, w, h } }
+    }
+
+    #[test]
+    fn test_simple_comparisons() {
+        let filter = FrameFilter::parse("area > 5000 && bbox.w > 100").unwrap();
+        assert!(filter.matches(0, &stats(6000, 150, 10)));
+        assert!(!filter.matches(0, &stats(6000, 50, 10)));
+        assert!(!filter.matches(0, &stats(4000, 150, 10)));
+    }
+
+    #[test]
+    fn test_precedence_and_grouping() {
+        // && binds tighter than ||
+        let filter = FrameFilter::parse("frame < 10 || area > 5 && bbox.h > 5").unwrap();
+        assert!(filter.matches(3, &stats(0, 0, 0)));
+        assert!(!filter.matches(20, &stats(10, 0, 1)));
+        let filter = FrameFilter::parse("(frame < 10 || area > 5) && bbox.h > 5").unwrap();
+        assert!(!filter.matches(3, &stats(0, 0, 0)));
+        // Arithmetic before comparison
+        let filter = FrameFilter::parse("bbox.w * bbox.h - area >= 2 * 50").unwrap();
+        assert!(filter.matches(0, &stats(100, 20, 10)));
+        assert!(!filter.matches(0, &stats(101, 20, 10)));
+    }
+
+    #[test]
+    fn test_unary_and_bare_values() {
+        assert!(FrameFilter::parse("!(area == 0)").unwrap().matches(0, &stats(1, 1, 1)));
+        assert!(FrameFilter::parse("-frame < 0").unwrap().matches(1, &stats(0, 0, 0)));
+        // A bare number or variable is true when non-zero
+        assert!(FrameFilter::parse("area").unwrap().matches(0, &stats(1, 1, 1)));
+        assert!(!FrameFilter::parse("0").unwrap().matches(0, &stats(1, 1, 1)));
+        assert!(FrameFilter::parse("coverage >= 0.5").unwrap().matches(0, &stats(5000, 1, 1)));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = FrameFilter::parse("area > ").unwrap_err();
+        assert_eq!(err.position, 7);
+        let err = FrameFilter::parse("size > 3").unwrap_err();
+        assert_eq!(err.position, 0);
+        assert!(err.message.contains("size"));
+        assert!(FrameFilter::parse("area = 3").is_err());
+        assert!(FrameFilter::parse("(area > 3").is_err());
+        assert!(FrameFilter::parse("area > 3 3").is_err());
+        assert!(FrameFilter::parse("1 < area < 3").is_err());
+        assert!(FrameFilter::parse("area # 3").is_err());
+    }
+}
@@ -0,0 +1,121 @@
+// Fingerprint module
+// Compact content hashes of decoded frames, and the alignment of two fingerprint sequences.
+// Two files that contain the same masks (e.g. a copy trimmed at the start) have the same
+// fingerprints shifted by the trim, so the offset with the most equal fingerprints tells how
+// their frames line up, e.g. to sync masks to a differently trimmed video.
+
+#[cfg(any(test, feature = "unstable"))]
+use std::collections::HashMap;
+
+/// Fingerprints shared by more frames than this (empty or static stretches) say little about
+/// where a frame sits, so they do not vote in `align`
+#[cfg(any(test, feature = "unstable"))]
+pub const MAX_FINGERPRINT_REPEATS: usize = 8;
+
+/// Content hash of a decoded polystream (FNV-1a, 64 bit).
+/// Equal frames hash equal in every file, whatever their position; the hash is stable across
+/// versions and platforms, so fingerprints can be stored and compared later.
+pub fn frame_fingerprint(polystream: &[u8]) -> u64 {
+    polystream.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3))
+}
+
+/// How two fingerprint sequences line up
+#[cfg(any(test, feature = "unstable"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alignment {
+    /// Frame `i` of the first sequence shows what frame `i + offset` of the second shows
+    pub offset: i64,
+    /// Frames with equal fingerprints at this offset
+    pub matches: usize,
+    /// Frames both sequences cover at this offset
+    pub overlap: usize,
+}
+
+#[cfg(any(test, feature = "unstable"))]
+impl Alignment {
+    /// Fraction of the overlapping frames that match, 1.0 for an exact (trimmed) copy
+    pub fn confidence(&self) -> f64 {
+        if self.overlap == 0 {
+            return 0.0;
+        }
+        self.matches as f64 / self.overlap as f64
+    }
+}
+
+/// Find the frame offset between two files with the same content from their fingerprints.
+/// Every frame votes for the offsets at which the other sequence has the same fingerprint; the
+/// offset with the most votes wins (the smaller shift on a tie). None when no frame has a
+/// distinctive fingerprint in common, e.g. different content or only static frames.
+#[cfg(any(test, feature = "unstable"))]
+pub fn align(first: &[u64], second: &[u64]) -> Option<Alignment> {
+    let mut positions: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (index, &fingerprint) in second.iter().enumerate() {
+        positions.entry(fingerprint).or_default().push(index);
+    }
+    let mut votes: HashMap<i64, usize> = HashMap::new();
+    for (index, fingerprint) in first.iter().enumerate() {
+        let Some(matches) = positions.get(fingerprint) else { continue };
+        if matches.len() > MAX_FINGERPRINT_REPEATS {
+            continue;
+        }
+        for &other in matches {
+            *votes.entry(other as i64 - index as i64).or_default() += 1;
+        }
+    }
+    let (offset, _) = votes.into_iter().max_by_key(|&(offset, count)| (count, std::cmp::Reverse(offset.abs()), offset))?;
+    // Count every agreeing frame at the winning offset, including the repeated ones
+    let start = (-offset).max(0) as usize;
+    let end = (second.len() as i64 - offset).min(first.len() as i64).max(0) as usize;
+    let overlap = end.saturating_sub(start);
+    let matches = (start..end).filter(|&index| first[index] == second[(index as i64 + offset) as usize]).count();
+    Some(Alignment { offset, matches, overlap })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprints(frames: impl Iterator<Item = u32>) -> Vec<u64> {
+        frames.map(|frame| frame_fingerprint(&frame.to_le_bytes())).collect()
+    }
+
+    #[test]
+    fn test_frame_fingerprint_is_stable() {
+        assert_eq!(frame_fingerprint(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(frame_fingerprint(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_ne!(frame_fingerprint(&[1, 0]), frame_fingerprint(&[0, 1]));
+    }
+
+    #[test]
+    fn test_align_trimmed_copy() {
+        let full = fingerprints(0..100);
+        // Trimmed at both ends: frame 10 of the original is frame 0 of the copy
+        let trimmed = fingerprints(10..80);
+        let alignment = align(&full, &trimmed).unwrap();
+        assert_eq!(alignment, Alignment { offset: -10, matches: 70, overlap: 70 });
+        assert_eq!(alignment.confidence(), 1.0);
+        assert_eq!(align(&trimmed, &full).unwrap().offset, 10);
+
+        // A few damaged frames lower the confidence, not the offset
+        let mut damaged = trimmed.clone();
+        damaged[5] = 0;
+        damaged[6] = 0;
+        let alignment = align(&full, &damaged).unwrap();
+        assert_eq!((alignment.offset, alignment.matches), (-10, 68));
+    }
+
+    #[test]
+    fn test_align_ignores_repeated_frames() {
+        // A long run of empty frames before the distinctive ones
+        let mut first = vec![7; 50];
+        first.extend(fingerprints(0..20));
+        let mut second = vec![7; 30];
+        second.extend(fingerprints(0..20));
+        let alignment = align(&first, &second).unwrap();
+        assert_eq!(alignment.offset, -20);
+        assert_eq!((alignment.matches, alignment.overlap), (50, 50));
+
+        assert_eq!(align(&[7; 20], &[7; 20]), None);
+        assert_eq!(align(&fingerprints(0..10), &fingerprints(100..110)), None);
+    }
+}
@@ -6,17 +6,46 @@
 
 use chacha20::cipher::generic_array::GenericArray;
 use chacha20::cipher::{KeyIvInit, StreamCipher};
-use chacha20::ChaCha20Legacy as ChaCha20;
-use flate2::read::ZlibDecoder;
+use chacha20::{ChaCha20, ChaCha20Legacy};
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use flate2::{Decompress, FlushDecompress, Status};
 use scrypt::Params;
+use std::collections::HashMap;
+#[cfg(any(test, feature = "unstable"))]
+use std::collections::VecDeque;
 use std::future::Future;
 use std::io::{Read, Write};
+#[cfg(any(test, feature = "unstable"))]
+use std::io::{Seek, SeekFrom};
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+#[cfg(any(test, feature = "unstable"))]
+use std::task::{ready, Context, Poll};
 use thiserror::Error;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+#[cfg(any(test, feature = "unstable"))]
+use tokio::io::{AsyncBufRead, BufReader, ReadBuf};
 use tokio::sync::Mutex;
 
+use crate::container::{select_track, Annotation, CueTrack, Thumbnail, TrackInfo, TrackKind, CONTAINER_MAGIC};
+use crate::delta::{apply_delta, DeltaRecord};
+use crate::profiling::{stage_span, Instrument};
+
+/// First 8 header bytes of a plain ASVP file
+const ASVP_MAGIC: &[u8; 8] = b"ASVPPLN1";
+/// First 8 header bytes of a delta-encoded ASVP file, see the `delta` module
+const ASVP_DELTA_MAGIC: &[u8; 8] = b"ASVPDLT1";
+
+/// First 4 bytes of a serialized FrameData, see `FrameData::to_bytes`
+pub const FRAME_DATA_MAGIC: &[u8; 4] = b"ASFD";
+/// FrameData serialization version written by `to_bytes`. Bumped only for changes older readers cannot
+/// skip; new optional products are added as sections instead.
+pub const FRAME_DATA_VERSION: u16 = 1;
+/// Section flags of a serialized FrameData, in the order the sections follow the polystream
+const SECTION_BITMAP: u16 = 1 << 0;
+const SECTION_TRIANGLE_STRIP: u16 = 1 << 1;
+
 /// Scrypt parameters matching the binary
 fn scrypt_params() -> Params {
     Params::new(14, 8, 1, 32).unwrap() // N=16384, r=8, p=1, dkLen=32
@@ -26,13 +55,50 @@ fn scrypt_params() -> Params {
 #[derive(Error, Debug)]
 pub enum FormatError {
     #[error("IO error: {0}")]
-    Io(#[from] std::io::Error),
+    Io(std::io::Error),
     #[error("Zlib decompression error")]
     Zlib,
     #[error("Invalid format: {0}")]
     InvalidFormat(String),
     #[error("Decryption error")]
     Decryption,
+    /// The remote source was replaced while reading; the stream must be reopened
+    #[error("Stream changed on the server")]
+    StreamChanged,
+    /// The EntitlementProvider refused decryption
+    #[error("{0}")]
+    Entitlement(#[from] crate::entitlement::EntitlementError),
+    /// The requested output size cannot be rasterized or cached
+    #[error("{0}")]
+    Dimensions(#[from] crate::api::DimensionError),
+}
+
+impl FormatError {
+    /// Copy of an error shared between requests, an IO error keeping its kind and message
+    fn duplicate(&self) -> Self {
+        match self {
+            FormatError::Io(e) => FormatError::Io(std::io::Error::new(e.kind(), e.to_string())),
+            FormatError::Zlib => FormatError::Zlib,
+            FormatError::InvalidFormat(message) => FormatError::InvalidFormat(message.clone()),
+            FormatError::Decryption => FormatError::Decryption,
+            FormatError::StreamChanged => FormatError::StreamChanged,
+            FormatError::Entitlement(e) => FormatError::Entitlement(e.clone()),
+            FormatError::Dimensions(e) => FormatError::Dimensions(e.clone()),
+        }
+    }
+}
+
+impl From<std::io::Error> for FormatError {
+    fn from(e: std::io::Error) -> Self {
+        let changed = e.get_ref()
+            .and_then(|inner| inner.downcast_ref::<crate::transport::TransportError>())
+            .is_some_and(|inner| matches!(inner, crate::transport::TransportError::StreamChanged));
+        if changed {
+            FormatError::StreamChanged
+        } else {
+            FormatError::Io(e)
+        }
+    }
 }
 
 /// Metadata about an AlphaStream file
@@ -42,10 +108,28 @@ pub struct Metadata {
     pub frame_count: u32,
     /// Size of the compressed sizes table in bytes
     pub compressed_sizes_size: u32,
+    /// Size of the stream in bytes, header to last frame: the file size of a plain file,
+    /// the length of the mask track in a container
+    pub stream_size: u64,
+    /// Compression the tables and frames were found to use
+    pub compression: Compression,
+}
+
+/// Deflate framing of the compressed tables and frames. Files written here use zlib; some vendor
+/// tools write raw deflate streams or gzip members instead, detected from the sizes table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    /// Deflate with a zlib header and Adler-32 trailer
+    #[default]
+    Zlib,
+    /// Deflate without any header
+    RawDeflate,
+    /// Deflate in a gzip member
+    Gzip,
 }
 
 /// A decoded frame containing polystream data
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct FrameData {
     /// Raw polystream data
     pub polystream: Vec<u8>,
@@ -55,8 +139,81 @@ pub struct FrameData {
     pub triangle_strip: Option<Vec<f32>>,
 }
 
+impl FrameData {
+    /// Whether the frame has no outline data: no channels, or only zero-length ones.
+    /// Such a frame is decoded like any other but rasterizes to an empty mask.
+    pub fn is_empty(&self) -> bool {
+        let channel_count = self.polystream.get(0..4).map_or(0, |count| u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize);
+        self.polystream.len() <= channel_count.saturating_mul(4).saturating_add(4)
+    }
+
+    /// Serialize the frame with its bitmap and triangle strip, e.g. to hand it to a worker process or an
+    /// external cache. The layout (docs/FILE_FORMAT_FRAME_DATA.md) is stable across releases:
+    /// [ "ASFD" | u16 version | u16 section flags | u32 length, polystream | u32 length, section ... ],
+    /// little-endian, sections in flag bit order (bitmap bytes, then triangle strip floats).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let bitmap_len = self.bitmap.as_ref().map_or(0, |bitmap| bitmap.len() + 4);
+        let strip_len = self.triangle_strip.as_ref().map_or(0, |strip| strip.len() * 4 + 4);
+        let mut out = Vec::with_capacity(12 + self.polystream.len() + bitmap_len + strip_len);
+        let flags = if self.bitmap.is_some() { SECTION_BITMAP } else { 0 }
+            | if self.triangle_strip.is_some() { SECTION_TRIANGLE_STRIP } else { 0 };
+        out.extend_from_slice(FRAME_DATA_MAGIC);
+        out.extend_from_slice(&FRAME_DATA_VERSION.to_le_bytes());
+        out.extend_from_slice(&flags.to_le_bytes());
+        out.extend_from_slice(&(self.polystream.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.polystream);
+        if let Some(bitmap) = &self.bitmap {
+            out.extend_from_slice(&(bitmap.len() as u32).to_le_bytes());
+            out.extend_from_slice(bitmap);
+        }
+        if let Some(strip) = &self.triangle_strip {
+            out.extend_from_slice(&(strip.len() as u32 * 4).to_le_bytes());
+            out.extend(strip.iter().flat_map(|value| value.to_le_bytes()));
+        }
+        out
+    }
+
+    /// Parse a frame serialized by `to_bytes` of this or an older release. Sections a newer writer added are
+    /// skipped; newer versions are rejected.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+        let invalid = |what: &str| FormatError::InvalidFormat(format!("Serialized frame {}", what));
+        if bytes.len() < 8 || &bytes[0..4] != FRAME_DATA_MAGIC {
+            return Err(invalid("has no ASFD header"));
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version > FRAME_DATA_VERSION {
+            return Err(FormatError::InvalidFormat(format!("Unsupported serialized frame version {}", version)));
+        }
+        let flags = u16::from_le_bytes([bytes[6], bytes[7]]);
+        let mut rest = &bytes[8..];
+        let mut section = || -> Result<&[u8], FormatError> {
+            let len = rest.get(0..4).map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize).ok_or_else(|| invalid("is truncated"))?;
+            let data = rest.get(4..4 + len).ok_or_else(|| invalid("is truncated"))?;
+            rest = &rest[4 + len..];
+            Ok(data)
+        };
+        let polystream = section()?.to_vec();
+        let (mut bitmap, mut triangle_strip) = (None, None);
+        for bit in (0..16).map(|bit| 1u16 << bit).filter(|bit| flags & bit != 0) {
+            let data = section()?;
+            match bit {
+                SECTION_BITMAP => bitmap = Some(data.to_vec()),
+                SECTION_TRIANGLE_STRIP => {
+                    if data.len() % 4 != 0 {
+                        return Err(invalid("has a partial triangle strip vertex"));
+                    }
+                    triangle_strip = Some(data.chunks_exact(4).map(|value| f32::from_le_bytes(value.try_into().unwrap())).collect());
+                }
+                _ => {}
+            }
+        }
+        Ok(FrameData { polystream, bitmap, triangle_strip })
+    }
+}
+
 pub type MetadataFuture = Pin<Box<dyn Future<Output = Result<Metadata, FormatError>> + Send + 'static>>;
 pub type FrameDataFuture<'a> = Pin<Box<dyn Future<Output = Result<FrameData, FormatError>> + Send + 'a>>;
+#[cfg(any(test, feature = "unstable"))]
 pub type FrameCountFuture = Pin<Box<dyn Future<Output = Result<u32, FormatError>>>>;
 
 /// The ASFormat trait defines the interface for parsing AlphaStream formats
@@ -65,6 +222,7 @@ pub trait ASFormat {
     fn metadata(&mut self) -> MetadataFuture;
 
     /// Get the total number of frames
+    #[cfg(any(test, feature = "unstable"))]
     fn frame_count(&mut self) -> FrameCountFuture {
         let fut = self.metadata();
         Box::pin(async move {
@@ -77,11 +235,49 @@ pub trait ASFormat {
 }
 
 /// Enum to hold either ASVR or ASVP format
+// Variants carry the file format names
+#[allow(clippy::upper_case_acronyms)]
 pub enum FormatType<R: AsyncRead + AsyncSeek + Unpin + Send> {
     ASVR(ASVRFormat<R>),
     ASVP(ASVPFormat<R>),
 }
 
+impl<R: AsyncRead + AsyncSeek + Unpin + Send> FormatType<R> {
+    /// Parse a new reader of the same kind, reusing the decryption key for ASVR.
+    /// Used to refresh the frame index when the underlying file changes.
+    pub async fn reopen(&self, reader: R) -> Result<FormatType<R>, FormatError> {
+        match self {
+            FormatType::ASVR(f) => Ok(FormatType::ASVR(ASVRFormat::with_profile(reader, f.key, f.profile).await?)),
+            FormatType::ASVP(f) => Ok(FormatType::ASVP(ASVPFormat::with_track(reader, f.track.as_deref()).await?)),
+        }
+    }
+
+    /// Channel sizes of a frame from the start of its record, see `ASVPFormat::peek_channel_sizes`.
+    /// None when the frame has to be decoded for them (an ASVR frame or a delta frame).
+    pub async fn peek_channel_sizes(&self, frame_index: u32) -> Result<Option<Vec<u32>>, FormatError> {
+        match self {
+            FormatType::ASVR(_) => Ok(None),
+            FormatType::ASVP(f) => f.peek_channel_sizes(frame_index).await,
+        }
+    }
+
+    /// Keyframes of a delta-encoded source, sorted; empty if every frame decodes on its own
+    pub fn keyframes(&self) -> &[u32] {
+        match self {
+            FormatType::ASVR(_) => &[],
+            FormatType::ASVP(f) => f.keyframes(),
+        }
+    }
+
+    /// Stored (compressed, encrypted) size of every frame, from the sizes table
+    pub fn frame_sizes(&self) -> &[u64] {
+        match self {
+            FormatType::ASVR(f) => &f.frame_sizes,
+            FormatType::ASVP(f) => &f.frame_sizes,
+        }
+    }
+}
+
 impl<R: AsyncRead + AsyncSeek + Unpin + Send> ASFormat for FormatType<R> {
     fn metadata(&mut self) -> MetadataFuture {
         match self {
@@ -98,6 +294,129 @@ impl<R: AsyncRead + AsyncSeek + Unpin + Send> ASFormat for FormatType<R> {
     }
 }
 
+/// Sequential-read mode: makes a non-seekable input (stdin, a socket, a pipe) usable by the format readers.
+/// Both readers only seek forward when frames are decoded in order, so a forward seek reads and drops the
+/// bytes in between. The last `window` bytes read are kept, so short seeks back (e.g. decoding the previous
+/// frame again) still work; seeking further back fails with `ErrorKind::Unsupported`. A window of the
+/// largest frame size (see `FormatType::frame_sizes`) is all a player that may repeat a frame needs.
+#[cfg(any(test, feature = "unstable"))]
+pub struct SequentialReader<R> {
+    inner: BufReader<R>,
+    /// Bytes consumed from the input
+    consumed: u64,
+    /// Logical read position, behind `consumed` while replaying from the window
+    position: u64,
+    /// Target of a seek in progress
+    seek_target: Option<u64>,
+    /// The last bytes consumed, ending at `consumed`
+    history: VecDeque<u8>,
+    window: usize,
+}
+
+#[cfg(any(test, feature = "unstable"))]
+impl<R: AsyncRead> SequentialReader<R> {
+    /// Read strictly forward, keeping nothing behind the read position
+    pub fn new(inner: R) -> Self {
+        Self::with_window(inner, 0)
+    }
+
+    /// Keep the last `window` bytes for seeking back
+    pub fn with_window(inner: R, window: usize) -> Self {
+        Self { inner: BufReader::new(inner), consumed: 0, position: 0, seek_target: None, history: VecDeque::with_capacity(window), window }
+    }
+
+    /// Change how many bytes are kept behind the read position, e.g. once the frame sizes are known
+    pub fn set_window(&mut self, window: usize) {
+        self.window = window;
+        self.trim_history();
+    }
+
+    /// Stream offset of the oldest byte that can still be read
+    fn oldest_available(&self) -> u64 {
+        self.consumed - self.history.len() as u64
+    }
+
+    fn trim_history(&mut self) {
+        remember(&mut self.history, self.window, &[]);
+    }
+}
+
+#[cfg(any(test, feature = "unstable"))]
+impl<R: AsyncRead + Unpin> AsyncRead for SequentialReader<R> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        if this.position < this.consumed {
+            // Replay from the window
+            let start = (this.position - this.oldest_available()) as usize;
+            let count = buf.remaining().min(this.history.len() - start);
+            let history = this.history.make_contiguous();
+            buf.put_slice(&history[start..start + count]);
+            this.position += count as u64;
+            return Poll::Ready(Ok(()));
+        }
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        let read = &buf.filled()[before..];
+        remember(&mut this.history, this.window, read);
+        this.consumed += read.len() as u64;
+        this.position = this.consumed;
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(any(test, feature = "unstable"))]
+impl<R: AsyncRead + Unpin> AsyncSeek for SequentialReader<R> {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> std::io::Result<()> {
+        let this = self.get_mut();
+        let target = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => this.position.checked_add_signed(delta),
+            SeekFrom::End(_) => None,
+        };
+        match target {
+            Some(target) if target >= this.oldest_available() => {
+                this.seek_target = Some(target);
+                Ok(())
+            }
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("cannot seek to {:?} in a sequential stream, bytes before {} are gone", position, this.oldest_available()),
+            )),
+        }
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<u64>> {
+        let this = self.get_mut();
+        while let Some(target) = this.seek_target {
+            if target <= this.consumed {
+                this.position = target;
+                this.seek_target = None;
+                break;
+            }
+            // Read and drop the input up to the target, keeping the window filled
+            let available = ready!(Pin::new(&mut this.inner).poll_fill_buf(cx))?;
+            if available.is_empty() {
+                this.seek_target = None;
+                return Poll::Ready(Err(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+            let skip = available.len().min((target - this.consumed) as usize);
+            remember(&mut this.history, this.window, &available[..skip]);
+            Pin::new(&mut this.inner).consume(skip);
+            this.consumed += skip as u64;
+        }
+        Poll::Ready(Ok(this.position))
+    }
+}
+
+/// Append consumed bytes to a SequentialReader's history, keeping at most `window` bytes
+#[cfg(any(test, feature = "unstable"))]
+fn remember(history: &mut VecDeque<u8>, window: usize, bytes: &[u8]) {
+    // Only the tail of a chunk larger than the window is kept
+    history.extend(&bytes[bytes.len().saturating_sub(window)..]);
+    let excess = history.len().saturating_sub(window);
+    history.drain(..excess);
+}
+
 /// Constant passphrase extracted from the binary (32 bytes)
 const PASSPHRASE: [u8; 32] = [
     0x90, 0x37, 0x9B, 0x41, 0xBB, 0xFD, 0x51, 0x9D,
@@ -121,32 +440,224 @@ pub fn derive_key(scene_id: u32, version: &[u8], base_url: &[u8]) -> Result<[u8;
     Ok(key)
 }
 
-/// Decrypt data using ChaCha20 with the given key and key_id
-fn decrypt_frame_data(data: &[u8], key: &[u8; 32], key_id: u32) -> Result<Vec<u8>, FormatError> {
-    // Nonce: 8 bytes, first 4 zero, last 4 key_id little-endian
-    // This matches the Python implementation's iv = b"\x00" * 12 + key_id.to_bytes(4, "little")
-    // where ChaCha20Legacy uses 8-byte nonce derived from the iv.
-    let mut nonce = [0u8; 8];
-    nonce[4..8].copy_from_slice(&key_id.to_le_bytes());
+/// Scene id, version and base URL a key is derived from
+type KeyCredentials = (u32, Vec<u8>, Vec<u8>);
+/// A derivation in flight, awaited by every request for the same credentials
+type SharedKeyDerivation = futures::future::Shared<futures::future::BoxFuture<'static, Result<[u8; 32], Arc<FormatError>>>>;
+
+/// Runs the scrypt key derivations of ASVR files off the async worker threads
+/// Scrypt takes tens of milliseconds and a lot of memory per key, so derivations run on the blocking pool,
+/// and identical credential requests made while one is running await that one instead of deriving again.
+/// Keys are not kept after the derivation finishes. `key_derivation()` is the instance every open uses.
+#[derive(Default)]
+pub struct KeyDerivationService {
+    pending: std::sync::Mutex<HashMap<KeyCredentials, SharedKeyDerivation>>,
+    derivations: AtomicUsize,
+}
+
+impl KeyDerivationService {
+    /// Derive the key for the given credentials, like `derive_key`
+    /// Outside a tokio runtime the key is derived on the calling thread.
+    pub async fn derive(&self, scene_id: u32, version: &[u8], base_url: &[u8]) -> Result<[u8; 32], FormatError> {
+        use futures::FutureExt;
+        let request = (scene_id, version.to_vec(), base_url.to_vec());
+        // Outside a runtime this request derives the key itself, once the lock is released
+        let mut derive_here = None;
+        let derivation = self.pending.lock().unwrap_or_else(|e| e.into_inner()).entry(request.clone()).or_insert_with(|| {
+            self.derivations.fetch_add(1, Ordering::Relaxed);
+            match tokio::runtime::Handle::try_current() {
+                Ok(runtime) => {
+                    let (scene_id, version, base_url) = request.clone();
+                    let task = runtime.spawn_blocking(move || derive_key(scene_id, &version, &base_url).map_err(Arc::new));
+                    async move { task.await.unwrap_or_else(|e| Err(Arc::new(FormatError::Io(std::io::Error::other(e))))) }.boxed().shared()
+                }
+                Err(_) => {
+                    let (sender, receiver) = futures::channel::oneshot::channel();
+                    derive_here = Some(sender);
+                    async move { receiver.await.unwrap_or_else(|e| Err(Arc::new(FormatError::Io(std::io::Error::other(e))))) }.boxed().shared()
+                }
+            }
+        }).clone();
+        if let Some(sender) = derive_here {
+            let _ = sender.send(derive_key(scene_id, version, base_url).map_err(Arc::new));
+        }
+        let key = derivation.clone().await;
+        // The first request to finish retires the derivation; later requests start a new one
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        if pending.get(&request).is_some_and(|current| current.ptr_eq(&derivation)) {
+            pending.remove(&request);
+        }
+        key.map_err(|error| Arc::try_unwrap(error).unwrap_or_else(|error| error.duplicate()))
+    }
 
-    let key_ga = GenericArray::from_slice(key);
-    let nonce_ga = GenericArray::from_slice(&nonce);
-    let mut cipher = ChaCha20::new(&key_ga, &nonce_ga);
+    /// Number of derivations started, requests that joined a running one not included
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn derivations(&self) -> usize {
+        self.derivations.load(Ordering::Relaxed)
+    }
+}
+
+/// The process-wide key derivation service used when ASVR files are opened
+pub fn key_derivation() -> &'static KeyDerivationService {
+    static SERVICE: OnceLock<KeyDerivationService> = OnceLock::new();
+    SERVICE.get_or_init(KeyDerivationService::default)
+}
+
+/// Stream cipher of an ASVR file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CipherKind {
+    /// ChaCha20 with a 64-bit nonce (the original construction)
+    ChaCha20Legacy,
+    /// ChaCha20 with a 96-bit nonce (RFC 8439)
+    ChaCha20Ietf,
+}
+
+/// Where the key id (the frame index, 0xFFFFFFFF for the header) goes in the otherwise zero nonce,
+/// little-endian
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NonceLayout {
+    /// In the last 4 bytes
+    KeyIdLast,
+    /// In the first 4 bytes
+    KeyIdFirst,
+}
+
+/// Cipher and nonce derivation of an ASVR file. Vendor releases may differ in either;
+/// `ASVRFormat::with_key` finds the profile of a file by trying them all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CipherProfile {
+    pub cipher: CipherKind,
+    pub nonce: NonceLayout,
+}
+
+impl Default for CipherProfile {
+    fn default() -> Self {
+        Self::VENDOR
+    }
+}
+
+impl CipherProfile {
+    /// Files of the original plugin (version 1.5.0). Matches the Python implementation's
+    /// iv = b"\x00" * 12 + key_id.to_bytes(4, "little"), of which ChaCha20Legacy takes the last 8 bytes.
+    pub const VENDOR: Self = Self { cipher: CipherKind::ChaCha20Legacy, nonce: NonceLayout::KeyIdLast };
+
+    /// Every distinct profile, in the order opening a file tries them. ChaCha20Ietf with KeyIdLast is
+    /// missing: its 32-bit block counter and zero nonce word make the same cipher state as the vendor
+    /// profile's 64-bit counter, so it encrypts the same (for frames under 256 GiB).
+    pub const ALL: [Self; 3] = [
+        Self::VENDOR,
+        Self { cipher: CipherKind::ChaCha20Legacy, nonce: NonceLayout::KeyIdFirst },
+        Self { cipher: CipherKind::ChaCha20Ietf, nonce: NonceLayout::KeyIdFirst },
+    ];
+
+    /// The nonce for `key_id`, 8 or 12 bytes depending on the cipher
+    fn nonce(self, key_id: u32) -> Vec<u8> {
+        let mut nonce = vec![0u8; match self.cipher { CipherKind::ChaCha20Legacy => 8, CipherKind::ChaCha20Ietf => 12 }];
+        let at = match self.nonce {
+            NonceLayout::KeyIdLast => nonce.len() - 4,
+            NonceLayout::KeyIdFirst => 0,
+        };
+        nonce[at..at + 4].copy_from_slice(&key_id.to_le_bytes());
+        nonce
+    }
+
+    /// XOR `data` with the keystream of `key` and `key_id`, from its start
+    fn apply_keystream(self, data: &mut [u8], key: &[u8; 32], key_id: u32) {
+        let key = GenericArray::from_slice(key);
+        let nonce = self.nonce(key_id);
+        match self.cipher {
+            CipherKind::ChaCha20Legacy => ChaCha20Legacy::new(key, GenericArray::from_slice(&nonce)).apply_keystream(data),
+            CipherKind::ChaCha20Ietf => ChaCha20::new(key, GenericArray::from_slice(&nonce)).apply_keystream(data),
+        }
+    }
+}
+
+/// Decrypt data using the profile's cipher with the given key and key_id
+fn decrypt_frame_data(data: &[u8], key: &[u8; 32], key_id: u32, profile: CipherProfile) -> Result<Vec<u8>, FormatError> {
     let mut decrypted = data.to_vec();
-    cipher.apply_keystream(&mut decrypted);
+    profile.apply_keystream(&mut decrypted, key, key_id);
     Ok(decrypted)
 }
 
 /// Decompress zlib data
-fn decompress_zlib(data: &[u8]) -> Result<Vec<u8>, FormatError> {
-    let mut decoder = ZlibDecoder::new(data);
+pub(crate) fn decompress_zlib(data: &[u8]) -> Result<Vec<u8>, FormatError> {
+    decompress(data, Compression::Zlib)
+}
+
+/// Decompress data in the given framing
+pub(crate) fn decompress(data: &[u8], compression: Compression) -> Result<Vec<u8>, FormatError> {
     let mut decompressed = Vec::new();
-    decoder.read_to_end(&mut decompressed).map_err(|_| FormatError::Zlib)?;
+    let result = match compression {
+        Compression::Zlib => ZlibDecoder::new(data).read_to_end(&mut decompressed),
+        Compression::RawDeflate => DeflateDecoder::new(data).read_to_end(&mut decompressed),
+        Compression::Gzip => GzDecoder::new(data).read_to_end(&mut decompressed),
+    };
+    result.map_err(|_| FormatError::Zlib)?;
     Ok(decompressed)
 }
 
+/// Decompress data whose framing is not known yet: zlib first, then gzip, then raw deflate.
+/// Returns the framing that worked, so the rest of the file can be read without guessing again.
+pub(crate) fn decompress_detect(data: &[u8]) -> Result<(Vec<u8>, Compression), FormatError> {
+    [Compression::Zlib, Compression::Gzip, Compression::RawDeflate]
+        .into_iter()
+        .filter(|&c| c != Compression::Gzip || data.starts_with(&[0x1f, 0x8b]))
+        .find_map(|c| decompress(data, c).ok().map(|d| (d, c)))
+        .ok_or(FormatError::Zlib)
+}
+
+/// Inflate at most `limit` bytes from the start of compressed data, which may be cut short.
+/// Returns fewer bytes when the input runs out first.
+pub(crate) fn decompress_prefix(data: &[u8], limit: usize, compression: Compression) -> Result<Vec<u8>, FormatError> {
+    let data = match compression {
+        Compression::Gzip => &data[gzip_header_len(data)?..],
+        _ => data,
+    };
+    let mut decoder = Decompress::new(compression == Compression::Zlib);
+    let mut decompressed = Vec::with_capacity(limit);
+    while decompressed.len() < limit {
+        let (consumed, produced) = (decoder.total_in(), decoder.total_out());
+        let status = decoder
+            .decompress_vec(&data[consumed as usize..], &mut decompressed, FlushDecompress::None)
+            .map_err(|_| FormatError::Zlib)?;
+        if status == Status::StreamEnd || (decoder.total_in() == consumed && decoder.total_out() == produced) {
+            break;
+        }
+    }
+    Ok(decompressed)
+}
+
+/// Length of the gzip member header at the start of `data` (RFC 1952)
+fn gzip_header_len(data: &[u8]) -> Result<usize, FormatError> {
+    const FEXTRA: u8 = 4;
+    const FNAME: u8 = 8;
+    const FCOMMENT: u8 = 16;
+    const FHCRC: u8 = 2;
+    if data.len() < 10 || data[0..3] != [0x1f, 0x8b, 8] {
+        return Err(FormatError::Zlib);
+    }
+    let flags = data[3];
+    let mut len = 10;
+    if flags & FEXTRA != 0 {
+        let extra = data.get(len..len + 2).ok_or(FormatError::Zlib)?;
+        len += 2 + u16::from_le_bytes([extra[0], extra[1]]) as usize;
+    }
+    for flag in [FNAME, FCOMMENT] {
+        if flags & flag != 0 {
+            len += data.get(len..).and_then(|rest| rest.iter().position(|&b| b == 0)).ok_or(FormatError::Zlib)? + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        len += 2;
+    }
+    if len > data.len() {
+        return Err(FormatError::Zlib);
+    }
+    Ok(len)
+}
+
 /// Compress data using zlib
-fn compress_zlib(data: &[u8]) -> Result<Vec<u8>, FormatError> {
+pub(crate) fn compress_zlib(data: &[u8]) -> Result<Vec<u8>, FormatError> {
     use flate2::{write::ZlibEncoder, Compression};
     use std::io::Write;
 
@@ -155,32 +666,36 @@ fn compress_zlib(data: &[u8]) -> Result<Vec<u8>, FormatError> {
     encoder.finish().map_err(|_| FormatError::Zlib)
 }
 
-/// Encrypt data using ChaCha20 with the given key and key_id
+/// Encrypt data using the profile's cipher with the given key and key_id
 /// ChaCha20 is symmetric, so this is the same as decryption
-fn encrypt_frame_data(data: &[u8], key: &[u8; 32], key_id: u32) -> Result<Vec<u8>, FormatError> {
-    // Nonce: 8 bytes, first 4 zero, last 4 key_id little-endian
-    let mut nonce = [0u8; 8];
-    nonce[4..8].copy_from_slice(&key_id.to_le_bytes());
-
-    let key_ga = GenericArray::from_slice(key);
-    let nonce_ga = GenericArray::from_slice(&nonce);
-    let mut cipher = ChaCha20::new(&key_ga, &nonce_ga);
-    let mut encrypted = data.to_vec();
-    cipher.apply_keystream(&mut encrypted);
-    Ok(encrypted)
+#[cfg(any(test, feature = "unstable"))]
+fn encrypt_frame_data(data: &[u8], key: &[u8; 32], key_id: u32, profile: CipherProfile) -> Result<Vec<u8>, FormatError> {
+    decrypt_frame_data(data, key, key_id, profile)
 }
 
 /// Writer for plaintext ASVP format
 /// Collects frames first, then writes the complete file
+#[cfg(any(test, feature = "unstable"))]
 pub struct ASVPWriter<W: Write> {
     writer: W,
     frames: Vec<FrameData>,
+    /// Frames per keyframe of a delta-encoded file, 0 for a plain file
+    keyframe_interval: usize,
 }
 
+#[cfg(any(test, feature = "unstable"))]
 impl<W: Write> ASVPWriter<W> {
     /// Create a new writer
     pub fn new(writer: W) -> Self {
-        Self { writer, frames: Vec::new() }
+        Self { writer, frames: Vec::new(), keyframe_interval: 0 }
+    }
+
+    /// Write a delta-encoded file (`ASVPDLT1`): every `interval`-th frame is a keyframe and the frames
+    /// in between store only their changes to the previous keyframe, which cuts the size of mostly
+    /// static masks by an order of magnitude. 0 (the default) writes a plain ASVP file.
+    pub fn keyframe_interval(mut self, interval: usize) -> Self {
+        self.keyframe_interval = interval;
+        self
     }
 
     /// Add a frame to be written
@@ -189,6 +704,7 @@ impl<W: Write> ASVPWriter<W> {
     }
 
     /// Consume the writer and return the inner writer
+    #[cfg(feature = "unstable")]
     pub fn into_inner(self) -> W {
         self.writer
     }
@@ -197,53 +713,109 @@ impl<W: Write> ASVPWriter<W> {
     /// This writes the header first (with sizes table), then all frames
     /// Returns the inner writer after writing
     pub fn write_all(mut self) -> Result<W, FormatError> {
-        // Pre-compress all frames to determine sizes
-        let mut frame_sizes = Vec::with_capacity(self.frames.len());
-        let mut compressed_frames = Vec::with_capacity(self.frames.len());
+        self.write_frames()?;
+        Ok(self.writer)
+    }
+
+    /// Like write_all, then read the file back from the inner writer and check that the `sample` frames
+    /// decode to the polystreams they were written from (see `VerifySample`). The writer must start at
+    /// the start of the file; it is left at the end. A failed check is reported, not returned as an error.
+    pub fn write_all_verified(mut self, sample: VerifySample) -> Result<(W, VerifyReport), FormatError>
+    where
+        W: Read + Seek,
+    {
+        let start = self.writer.stream_position()?;
+        self.write_frames()?;
+        let report = verify_written(&mut self.writer, start, None, &self.frames, sample)?;
+        Ok((self.writer, report))
+    }
 
-        for frame in &self.frames {
-            // The 4-byte length prefix is the EXPECTED uncompressed length, not compressed length
-            let uncompressed_len = frame.polystream.len() as u32;
-            let compressed = compress_zlib(&frame.polystream)?;
-            // Frame format: 4-byte length (uncompressed) + compressed data
-            let mut frame_data = Vec::new();
-            frame_data.extend_from_slice(&uncompressed_len.to_le_bytes());
-            frame_data.extend_from_slice(&compressed);
-            frame_sizes.push(frame_data.len() as u64);
-            compressed_frames.push(frame_data);
+    fn write_frames(&mut self) -> Result<(), FormatError> {
+        if self.keyframe_interval > 0 {
+            let polystreams: Vec<&[u8]> = self.frames.iter().map(|frame| frame.polystream.as_slice()).collect();
+            let (payloads, keyframes) = crate::delta::encode_frames(&polystreams, self.keyframe_interval)?;
+            let records = payloads.iter()
+                .map(|payload| asvp_frame_record(payload))
+                .collect::<Result<Vec<_>, _>>()?;
+            return write_records(&mut self.writer, ASVP_DELTA_MAGIC, &records, Some(&keyframes));
         }
+        // Pre-compress all frames to determine sizes
+        let records = self.frames.iter()
+            .map(|frame| asvp_frame_record(&frame.polystream))
+            .collect::<Result<Vec<_>, _>>()?;
+        write_asvp_records(&mut self.writer, &records)
+    }
+}
 
-        // Write header with sizes table
-        let sizes_bytes: Vec<u8> = frame_sizes.iter()
-            .flat_map(|s| s.to_le_bytes())
-            .collect();
-        let compressed_sizes = compress_zlib(&sizes_bytes)?;
+/// Encode one ASVP frame record: 4-byte length (uncompressed) + compressed polystream
+#[cfg(any(test, feature = "unstable"))]
+pub(crate) fn asvp_frame_record(polystream: &[u8]) -> Result<Vec<u8>, FormatError> {
+    // The 4-byte length prefix is the EXPECTED uncompressed length, not compressed length
+    let mut record = Vec::new();
+    record.extend_from_slice(&(polystream.len() as u32).to_le_bytes());
+    record.extend_from_slice(&compress_zlib(polystream)?);
+    Ok(record)
+}
 
-        // Write 16-byte header
-        let mut header = [0u8; 16];
-        // we put "ASVPPLN1" as the first 8 bytes of the header, to make it easy to identify plaintext files
-        header[0..8].copy_from_slice(b"ASVPPLN1");
-        header[12..16].copy_from_slice(&(compressed_sizes.len() as u32).to_le_bytes());
-        self.writer.write_all(&header)?;
-        self.writer.write_all(&compressed_sizes)?;
+/// Write a complete ASVP file from already encoded frame records (see `asvp_frame_record`)
+pub(crate) fn write_asvp_records<W: Write>(writer: &mut W, records: &[Vec<u8>]) -> Result<(), FormatError> {
+    write_records(writer, ASVP_MAGIC, records, None)
+}
 
-        // Write each frame
-        for frame_data in &compressed_frames {
-            self.writer.write_all(frame_data)?;
-        }
+/// Write header, sizes table and records; delta-encoded files also get their keyframe table
+fn write_records<W: Write>(writer: &mut W, magic: &[u8; 8], records: &[Vec<u8>], keyframes: Option<&[u32]>) -> Result<(), FormatError> {
+    // Write header with sizes table
+    let sizes_bytes: Vec<u8> = records.iter()
+        .flat_map(|record| (record.len() as u64).to_le_bytes())
+        .collect();
+    let compressed_sizes = compress_zlib(&sizes_bytes)?;
+
+    // Write 16-byte header
+    let mut header = [0u8; 16];
+    // we put "ASVPPLN1" (or "ASVPDLT1") as the first 8 bytes of the header, to make it easy to identify plaintext files
+    header[0..8].copy_from_slice(magic);
+    let keyframe_table = match keyframes {
+        Some(keyframes) => compress_zlib(&keyframes.iter().flat_map(|k| k.to_le_bytes()).collect::<Vec<u8>>())?,
+        None => Vec::new(),
+    };
+    header[8..12].copy_from_slice(&(keyframe_table.len() as u32).to_le_bytes());
+    header[12..16].copy_from_slice(&(compressed_sizes.len() as u32).to_le_bytes());
+    writer.write_all(&header)?;
+    writer.write_all(&compressed_sizes)?;
+    writer.write_all(&keyframe_table)?;
+
+    // Write each frame
+    for record in records {
+        writer.write_all(record)?;
+    }
+    Ok(())
+}
 
-        Ok(self.writer)
+/// Decode an ASVP frame record back to its polystream
+#[cfg(any(test, feature = "unstable"))]
+pub(crate) fn decode_asvp_frame_record(record: &[u8]) -> Result<Vec<u8>, FormatError> {
+    if record.len() < 4 {
+        return Err(FormatError::InvalidFormat("Frame record too short".to_string()));
     }
+    let expected_len = u32::from_le_bytes(record[0..4].try_into().unwrap()) as usize;
+    let polystream = decompress_zlib(&record[4..])?;
+    if polystream.len() != expected_len {
+        return Err(FormatError::InvalidFormat("Decompressed length mismatch".to_string()));
+    }
+    Ok(polystream)
 }
 
 /// Writer for encrypted ASVR format
 /// Similar to ASVPWriter but with encryption
+#[cfg(any(test, feature = "unstable"))]
 pub struct ASVRWriter<W: Write> {
     writer: W,
     key: [u8; 32],
     frames: Vec<FrameData>,
+    profile: CipherProfile,
 }
 
+#[cfg(any(test, feature = "unstable"))]
 impl<W: Write> ASVRWriter<W> {
     /// Create a new writer with encryption parameters
     pub fn new(writer: W, scene_id: u32, version: &[u8], base_url: &[u8])
@@ -253,14 +825,22 @@ impl<W: Write> ASVRWriter<W> {
             writer,
             frames: Vec::new(),
             key,
+            profile: CipherProfile::VENDOR,
         })
     }
 
+    /// Encrypt with another cipher or nonce layout than the original plugin's (`CipherProfile::VENDOR`)
+    pub fn cipher_profile(mut self, profile: CipherProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
     /// Add a frame to be written
     pub fn add_frame(&mut self, frame: FrameData) {
         self.frames.push(frame);
     }
 
+    #[cfg(feature = "unstable")]
     /// Consume the writer and return the inner writer
     pub fn into_inner(self) -> W {
         self.writer
@@ -269,6 +849,23 @@ impl<W: Write> ASVRWriter<W> {
     /// Write all collected frames to the encrypted file
     /// Returns the inner writer after writing
     pub fn write_all(mut self) -> Result<W, FormatError> {
+        self.write_frames()?;
+        Ok(self.writer)
+    }
+
+    /// Like write_all, then read the file back from the inner writer, decrypt the `sample` frames and
+    /// check them against the polystreams they were written from, as `ASVPWriter::write_all_verified`
+    pub fn write_all_verified(mut self, sample: VerifySample) -> Result<(W, VerifyReport), FormatError>
+    where
+        W: Read + Seek,
+    {
+        let start = self.writer.stream_position()?;
+        self.write_frames()?;
+        let report = verify_written(&mut self.writer, start, Some((self.key, self.profile)), &self.frames, sample)?;
+        Ok((self.writer, report))
+    }
+
+    fn write_frames(&mut self) -> Result<(), FormatError> {
         // Pre-compress and encrypt all frames
         let mut frame_sizes = Vec::with_capacity(self.frames.len());
         let mut encrypted_frames = Vec::with_capacity(self.frames.len());
@@ -290,12 +887,12 @@ impl<W: Write> ASVRWriter<W> {
 
             let uncompressed_plaintext_size = frame_data.len() as u32;
             // careful, this is writing the compressed data into the frame_data buffer!
-            let compressed_data = compress_zlib(&frame_data)?;
+            let compressed_data = compress_zlib(frame_data)?;
             let mut plaintext_channels = Vec::new();
             plaintext_channels.extend_from_slice(&uncompressed_plaintext_size.to_le_bytes());
             plaintext_channels.extend_from_slice(&compressed_data);
 
-            let encrypted = encrypt_frame_data(&plaintext_channels, &self.key, i as u32)?;
+            let encrypted = encrypt_frame_data(&plaintext_channels, &self.key, i as u32, self.profile)?;
             frame_sizes.push(encrypted.len() as u64);
             encrypted_frames.push(encrypted);
         }
@@ -314,7 +911,7 @@ impl<W: Write> ASVRWriter<W> {
         let mut to_encrypt = Vec::with_capacity(16 + compressed_sizes.len());
         to_encrypt.extend_from_slice(&header);
         to_encrypt.extend_from_slice(&compressed_sizes);
-        let encrypted_all = encrypt_frame_data(&to_encrypt, &self.key, 0xFFFFFFFF)?;
+        let encrypted_all = encrypt_frame_data(&to_encrypt, &self.key, 0xFFFFFFFF, self.profile)?;
 
         self.writer.write_all(&encrypted_all)?;
 
@@ -323,62 +920,237 @@ impl<W: Write> ASVRWriter<W> {
             self.writer.write_all(encrypted)?;
         }
 
-        Ok(self.writer)
+        Ok(())
+    }
+}
+
+/// Frames the read-after-write check of `write_all_verified` decodes
+#[cfg(any(test, feature = "unstable"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifySample {
+    /// Every frame
+    All,
+    /// This many frames spread evenly over the file, including the first and the last
+    Evenly(usize),
+}
+
+#[cfg(any(test, feature = "unstable"))]
+impl VerifySample {
+    /// The sampled frame indices of a file of `frame_count` frames, ascending
+    fn frames(self, frame_count: u32) -> Vec<u32> {
+        match self {
+            VerifySample::Evenly(count) if (count as u64) < frame_count as u64 => {
+                if count <= 1 {
+                    return vec![0; count];
+                }
+                let last = frame_count as u64 - 1;
+                (0..count as u64).map(|i| (i * last / (count as u64 - 1)) as u32).collect()
+            }
+            _ => (0..frame_count).collect(),
+        }
+    }
+}
+
+/// A sampled frame that did not survive the write
+#[cfg(any(test, feature = "unstable"))]
+#[cfg_attr(not(feature = "unstable"), allow(dead_code))]
+#[derive(Debug)]
+pub enum VerifyFailure {
+    /// The frame could not be read, decrypted or decompressed
+    Decode { frame_index: u32, error: FormatError },
+    /// The frame decoded to a different polystream than was written
+    Mismatch { frame_index: u32, expected_len: usize, decoded_len: usize, first_difference: usize },
+}
+
+#[cfg(any(test, feature = "unstable"))]
+impl VerifyFailure {
+    pub fn frame_index(&self) -> u32 {
+        match self {
+            VerifyFailure::Decode { frame_index, .. } | VerifyFailure::Mismatch { frame_index, .. } => *frame_index,
+        }
     }
 }
 
+/// Outcome of a writer's read-after-write check
+#[cfg(any(test, feature = "unstable"))]
+#[derive(Debug)]
+pub struct VerifyReport {
+    /// Frames given to the writer
+    pub frames_written: u32,
+    /// Frames the file read back has
+    pub frames_found: u32,
+    /// Frames that were decoded and compared, ascending
+    pub checked: Vec<u32>,
+    /// Checked frames that failed, ascending by frame index
+    pub failures: Vec<VerifyFailure>,
+}
+
+#[cfg(any(test, feature = "unstable"))]
+impl VerifyReport {
+    /// Whether the file has every frame and all checked frames match
+    pub fn is_ok(&self) -> bool {
+        self.frames_found == self.frames_written && self.failures.is_empty()
+    }
+}
+
+/// Read back the file a writer wrote from `start` to its current position, and decode the `sample`
+/// frames of it on one thread per core. Each thread has its own reader over the file and takes a
+/// contiguous run of frames, so delta frames mostly find their keyframe cached.
+#[cfg(any(test, feature = "unstable"))]
+fn verify_written<W: Read + Write + Seek>(writer: &mut W, start: u64, key: Option<([u8; 32], CipherProfile)>, frames: &[FrameData], sample: VerifySample) -> Result<VerifyReport, FormatError> {
+    writer.flush()?;
+    let end = writer.stream_position()?;
+    writer.seek(SeekFrom::Start(start))?;
+    let mut written = vec![0u8; (end - start) as usize];
+    writer.read_exact(&mut written)?;
+
+    let written = written.as_slice();
+    let open = move || async move {
+        let reader = std::io::Cursor::new(written);
+        Ok::<_, FormatError>(match key {
+            Some((key, profile)) => FormatType::ASVR(ASVRFormat::with_profile(reader, key, profile).await?),
+            None => FormatType::ASVP(ASVPFormat::new(reader).await?),
+        })
+    };
+    let frames_found = futures::executor::block_on(async { open().await?.frame_count().await })?;
+    let checked = sample.frames(frames_found.min(frames.len() as u32));
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get()).min(checked.len()).max(1);
+    let failures = std::thread::scope(|scope| {
+        let workers: Vec<_> = checked.chunks(checked.len().div_ceil(threads).max(1))
+            .map(|indices| scope.spawn(move || futures::executor::block_on(async move {
+                let mut format = open().await?;
+                let mut failures = Vec::new();
+                for &frame_index in indices {
+                    let expected = &frames[frame_index as usize].polystream;
+                    match format.decode_frame(frame_index).await {
+                        Ok(decoded) if decoded.polystream == *expected => {}
+                        Ok(decoded) => failures.push(VerifyFailure::Mismatch {
+                            frame_index,
+                            expected_len: expected.len(),
+                            decoded_len: decoded.polystream.len(),
+                            first_difference: expected.iter().zip(&decoded.polystream).take_while(|(a, b)| a == b).count(),
+                        }),
+                        Err(error) => failures.push(VerifyFailure::Decode { frame_index, error }),
+                    }
+                }
+                Ok::<_, FormatError>(failures)
+            })))
+            .collect();
+        workers.into_iter()
+            .map(|worker| worker.join().expect("verify worker panicked"))
+            .collect::<Result<Vec<_>, _>>()
+    })?;
+
+    Ok(VerifyReport {
+        frames_written: frames.len() as u32,
+        frames_found,
+        checked,
+        failures: failures.into_iter().flatten().collect(),
+    })
+}
+
 /// ASVR (encrypted) format implementation
 pub struct ASVRFormat<R: AsyncRead + AsyncSeek + Unpin + Send> {
     reader: Arc<Mutex<R>>,
     key: [u8; 32],
+    profile: CipherProfile,
     metadata: Option<Metadata>,
     frame_offsets: Vec<u64>,
     frame_sizes: Vec<u64>,
+    compression: Compression,
 }
 
 impl<R: AsyncRead + AsyncSeek + Unpin + Send> ASVRFormat<R> {
     /// Create a new ASVR format parser
+    /// The key is derived by the shared `key_derivation()` service, off the async worker threads.
     pub async fn new(reader: R, scene_id: u32, version: &[u8], base_url: &[u8]) -> Result<Self, FormatError> {
-        let key = derive_key(scene_id, version, base_url)?;
+        let key = key_derivation().derive(scene_id, version, base_url).await?;
+        Self::with_key(reader, key).await
+    }
 
-        let reader = Arc::new(Mutex::new(reader));
+    /// Create a new ASVR format parser from an already derived key
+    /// The cipher profile is probed: each of `CipherProfile::ALL` is tried until one decrypts a sizes table,
+    /// those whose header reads as file version 4 first. The vendor profile is tried first of all, so
+    /// files of the original plugin are read without detours; with the others a non-seekable reader
+    /// may have been read past the first frame (see `SequentialReader`).
+    pub async fn with_key(reader: R, key: [u8; 32]) -> Result<Self, FormatError> {
+        Self::open(reader, key, None).await
+    }
 
-        // Read encrypted header (16 bytes)
-        let mut encrypted_header = [0u8; 16];
-        {
-            let mut reader_guard = reader.lock().await;
-            reader_guard.read_exact(&mut encrypted_header).await?;
-        }
+    /// Create a new ASVR format parser for a file of a known cipher profile, without probing
+    pub async fn with_profile(reader: R, key: [u8; 32], profile: CipherProfile) -> Result<Self, FormatError> {
+        Self::open(reader, key, Some(profile)).await
+    }
 
-        // Decrypt header to get compressed_sizes_size (preserves keystream for sizes)
-        let header = decrypt_frame_data(&encrypted_header, &key, 0xFFFFFFFF)?;
-        // expected 8 bytes: 04 00 00 00 00 00 00 00 for official asvr at version 1.5.0
-        // print warning if number is not 4
-        let file_version = u32::from_le_bytes(header[0..4].try_into().unwrap());
-        if file_version != 4 {
-            eprintln!("ASVR file version is not 4, but {}", file_version);
-        }
+    /// The cipher profile the file is read with
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn cipher_profile(&self) -> CipherProfile {
+        self.profile
+    }
 
-        let compressed_sizes_size = u32::from_le_bytes(header[12..16].try_into().unwrap());
+    async fn open(reader: R, key: [u8; 32], profile: Option<CipherProfile>) -> Result<Self, FormatError> {
+        let reader = Arc::new(Mutex::new(reader));
 
-        // Read encrypted sizes
-        let mut encrypted_sizes = vec![0u8; compressed_sizes_size as usize];
-        {
-            let mut reader_guard = reader.lock().await;
-            reader_guard.read_exact(&mut encrypted_sizes).await?;
-        }
+        // Read encrypted header (16 bytes); the encrypted sizes table follows it
+        let mut encrypted = vec![0u8; 16];
+        reader.lock().await.read_exact(&mut encrypted).await?;
 
-        // Decrypt header + sizes together (maintains keystream continuity with writer)
-        let mut combined = encrypted_header.to_vec();
-        combined.extend_from_slice(&encrypted_sizes);
-        let decrypted_combined = decrypt_frame_data(&combined, &key, 0xFFFFFFFF)?;
-        let decrypted_sizes = &decrypted_combined[16..];
+        // Decrypt header to get file version and compressed_sizes_size (preserves keystream for sizes)
+        let encrypted_header: [u8; 16] = encrypted[..16].try_into().unwrap();
+        let header_for = |profile: CipherProfile| decrypt_frame_data(&encrypted_header, &key, 0xFFFFFFFF, profile);
+        let mut candidates = match profile {
+            Some(profile) => vec![profile],
+            None => CipherProfile::ALL.to_vec(),
+        };
+        // expected 8 bytes: 04 00 00 00 00 00 00 00 for official asvr at version 1.5.0
+        candidates.sort_by_key(|&profile| header_for(profile).is_ok_and(|header| header[0..4] != 4u32.to_le_bytes()));
+
+        let mut last_error = None;
+        for candidate in candidates {
+            let header = header_for(candidate)?;
+            let compressed_sizes_size = u32::from_le_bytes(header[12..16].try_into().unwrap());
+            // A wrong profile decrypts to a random size; don't read gigabytes to find out
+            if profile.is_none() && compressed_sizes_size > MAX_PROBED_SIZES_TABLE {
+                continue;
+            }
+            // Read encrypted sizes, as far as the candidates tried before have not
+            let end = 16 + compressed_sizes_size as usize;
+            if encrypted.len() < end {
+                let start = encrypted.len();
+                encrypted.resize(end, 0);
+                reader.lock().await.read_exact(&mut encrypted[start..]).await?;
+            }
 
-        // Decompress sizes table
-        let sizes_raw = decompress_zlib(&decrypted_sizes)?;
-        if sizes_raw.len() % 8 != 0 {
-            return Err(FormatError::InvalidFormat("Sizes table length not multiple of 8".to_string()));
+            // Decrypt header + sizes together (maintains keystream continuity with writer)
+            let decrypted_combined = decrypt_frame_data(&encrypted[..end], &key, 0xFFFFFFFF, candidate)?;
+            let decrypted_sizes = &decrypted_combined[16..];
+
+            // Decompress sizes table, which also tells how the frames are compressed
+            let (sizes_raw, compression) = match decompress_detect(decrypted_sizes) {
+                Ok((sizes_raw, _)) if sizes_raw.len() % 8 != 0 => {
+                    last_error = Some(FormatError::InvalidFormat("Sizes table length not multiple of 8".to_string()));
+                    continue;
+                }
+                Ok(decompressed) => decompressed,
+                Err(e) => {
+                    last_error = Some(e);
+                    continue;
+                }
+            };
+            let file_version = u32::from_le_bytes(header[0..4].try_into().unwrap());
+            if file_version != 4 {
+                crate::logging::log(crate::logging::LogLevel::Warn, format_args!("ASVR file version is not 4, but {}", file_version));
+            }
+            if candidate != CipherProfile::VENDOR {
+                crate::logging::log(crate::logging::LogLevel::Info, format_args!("ASVR file uses cipher profile {:?}", candidate));
+            }
+            return Ok(Self::from_sizes(reader, key, candidate, &sizes_raw, compressed_sizes_size, compression));
         }
+        Err(last_error.unwrap_or_else(|| FormatError::InvalidFormat("Header does not decrypt with any cipher profile".to_string())))
+    }
+
+    /// Index the frames of a sizes table
+    fn from_sizes(reader: Arc<Mutex<R>>, key: [u8; 32], profile: CipherProfile, sizes_raw: &[u8], compressed_sizes_size: u32, compression: Compression) -> Self {
 
         let mut frame_sizes = Vec::new();
         let mut frame_offsets = Vec::new();
@@ -395,15 +1167,19 @@ impl<R: AsyncRead + AsyncSeek + Unpin + Send> ASVRFormat<R> {
         let metadata = Metadata {
             frame_count,
             compressed_sizes_size,
+            stream_size: offset,
+            compression,
         };
 
-        Ok(Self {
+        Self {
             reader,
             key,
+            profile,
             metadata: Some(metadata),
             frame_offsets,
             frame_sizes,
-        })
+            compression,
+        }
     }
 }
 
@@ -414,8 +1190,9 @@ impl<R: AsyncRead + AsyncSeek + Unpin + Send> ASFormat for ASVRFormat<R> {
     }
 
     fn decode_frame(&mut self, frame_index: u32) -> FrameDataFuture<'_> {
-        let frame_index = frame_index;
         let key = self.key;
+        let profile = self.profile;
+        let compression = self.compression;
         let frame_offsets = self.frame_offsets.clone();
         let frame_sizes = self.frame_sizes.clone();
         let reader = self.reader.clone();
@@ -426,15 +1203,18 @@ impl<R: AsyncRead + AsyncSeek + Unpin + Send> ASFormat for ASVRFormat<R> {
                 frame_index = frame_sizes.len() as u32 - 1;
             }
 
-            let mut reader = reader.lock().await;
-            // Seek to frame offset
-            reader.seek(std::io::SeekFrom::Start(frame_offsets[frame_index as usize])).await?;
-            let frame_size = frame_sizes[frame_index as usize] as usize;
-            let mut encrypted_frame = vec![0u8; frame_size];
-            reader.read_exact(&mut encrypted_frame).await?;
+            let encrypted_frame = async {
+                let mut reader = reader.lock().await;
+                // Seek to frame offset
+                reader.seek(std::io::SeekFrom::Start(frame_offsets[frame_index as usize])).await?;
+                let frame_size = frame_sizes[frame_index as usize] as usize;
+                let mut encrypted_frame = vec![0u8; frame_size];
+                reader.read_exact(&mut encrypted_frame).await?;
+                Ok::<_, FormatError>(encrypted_frame)
+            }.instrument(stage_span!("read", frame_index)).await?;
 
             // Decrypt frame with key_id = frame_index
-            let decrypted_frame = decrypt_frame_data(&encrypted_frame, &key, frame_index)?;
+            let decrypted_frame = stage_span!("decrypt", frame_index).in_scope(|| decrypt_frame_data(&encrypted_frame, &key, frame_index, profile))?;
 
             // Parse decrypted frame: first 4 bytes = expected_uncompressed_len
             if decrypted_frame.len() < 4 {
@@ -444,7 +1224,7 @@ impl<R: AsyncRead + AsyncSeek + Unpin + Send> ASFormat for ASVRFormat<R> {
             let compressed_payload = &decrypted_frame[4..];
 
             // Decompress payload
-            let decompressed = decompress_zlib(compressed_payload)?;
+            let decompressed = stage_span!("decompress", frame_index).in_scope(|| decompress(compressed_payload, compression))?;
             if decompressed.len() != expected_len {
                 return Err(FormatError::InvalidFormat("Decompressed length mismatch".to_string()));
             }
@@ -485,29 +1265,96 @@ impl<R: AsyncRead + AsyncSeek + Unpin + Send> ASFormat for ASVRFormat<R> {
     }
 }
 
+/// Index and polystream of the keyframe a delta-encoded reader decoded last
+type KeyframeCache = Arc<std::sync::Mutex<Option<(u32, Arc<Vec<u8>>)>>>;
+
+/// Read the ASVP record of frame `frame_index` at `offset` and return its verified, decompressed payload
+async fn read_asvp_record<R: AsyncRead + AsyncSeek + Unpin>(reader: &mut R, frame_index: u32, offset: u64, size: u64, compression: Compression) -> Result<Vec<u8>, FormatError> {
+    let frame_data = async {
+        reader.seek(std::io::SeekFrom::Start(offset)).await?;
+        let mut frame_data = vec![0u8; size as usize];
+        reader.read_exact(&mut frame_data).await?;
+        Ok::<_, FormatError>(frame_data)
+    }.instrument(stage_span!("read", frame_index)).await?;
+
+    // Parse frame: first 4 bytes = expected_uncompressed_len
+    if frame_data.len() < 4 {
+        return Err(FormatError::InvalidFormat("Frame too short".to_string()));
+    }
+    let expected_len = u32::from_le_bytes(frame_data[0..4].try_into().unwrap()) as usize;
+    let compressed_payload = &frame_data[4..];
+
+    // Decompress payload
+    let decompressed = stage_span!("decompress", frame_index).in_scope(|| decompress(compressed_payload, compression))?;
+    if decompressed.len() != expected_len {
+        return Err(FormatError::InvalidFormat("Decompressed length mismatch".to_string()));
+    }
+    Ok(decompressed)
+}
+
+/// Largest compressed sizes table read while probing the cipher profile of an ASVR file: 64 MiB, a few
+/// million frames
+const MAX_PROBED_SIZES_TABLE: u32 = 64 << 20;
+
+/// Decompressed bytes `ASVPFormat::peek_channel_sizes` inflates first: the channel count and up to 63 sizes
+const HEADER_PEEK_BYTES: usize = 256;
+/// Record bytes `ASVPFormat::peek_channel_sizes` reads first; grown when they don't inflate to the header
+const HEADER_PEEK_READ: u64 = 1024;
+
 /// ASVP (plaintext) format implementation
 pub struct ASVPFormat<R: AsyncRead + AsyncSeek + Unpin + Send> {
     reader: Arc<Mutex<R>>,
     metadata: Option<Metadata>,
     frame_offsets: Vec<u64>,
     frame_sizes: Vec<u64>,
+    /// Mask track selected by name, None for the first one (or a plain ASVP file)
+    track: Option<String>,
+    /// Track directory of a container, empty for a plain ASVP file
+    tracks: Vec<TrackInfo>,
+    /// Frames are delta-encoded against keyframes (`ASVPDLT1`)
+    delta: bool,
+    /// Keyframes of a delta-encoded file, sorted; empty for a plain file
+    keyframes: Vec<u32>,
+    /// Polystream of the keyframe decoded last, which the following deltas usually refer to
+    keyframe: KeyframeCache,
+    /// Framing of the compressed tables and frames
+    compression: Compression,
 }
 
 impl<R: AsyncRead + AsyncSeek + Unpin + Send> ASVPFormat<R> {
     /// Create a new ASVP format parser
+    /// Also accepts a multi-track container, playing its first mask track.
     pub async fn new(reader: R) -> Result<Self, FormatError> {
+        Self::with_track(reader, None).await
+    }
+
+    /// Parse the mask track `track` of a multi-track container (see the `container` module), or the
+    /// first mask track for None. Plain ASVP files have no named tracks.
+    pub async fn with_track(reader: R, track: Option<&str>) -> Result<Self, FormatError> {
         let reader = Arc::new(Mutex::new(reader));
 
         // Read header (16 bytes)
         let mut header = [0u8; 16];
+        let mut tracks = Vec::new();
+        // Offset of the embedded ASVP file, 0 for a plain ASVP file
+        let mut base = 0;
         {
             let mut reader_guard = reader.lock().await;
             reader_guard.read_exact(&mut header).await?;
+            if &header[0..8] == CONTAINER_MAGIC {
+                tracks = crate::container::read_directory(&header, &mut *reader_guard).await?;
+                base = select_track(&tracks, TrackKind::Mask, track)?.offset;
+                reader_guard.seek(std::io::SeekFrom::Start(base)).await?;
+                reader_guard.read_exact(&mut header).await?;
+            } else if let Some(name) = track {
+                return Err(FormatError::InvalidFormat(format!("Track '{}' requested from a file without tracks", name)));
+            }
         }
-        // expected 8 bytes for decrypted asvp is b"ASVPPLN1"
+        // expected 8 bytes for decrypted asvp is b"ASVPPLN1" (or b"ASVPDLT1" for delta encoding)
         // print if this is not the case
-        if &header[0..8] != b"ASVPPLN1" {
-            eprintln!("ASVP file header is not 'ASVPPLN1', but {:?}", &header[0..8]);
+        let delta = &header[0..8] == ASVP_DELTA_MAGIC;
+        if &header[0..8] != ASVP_MAGIC && !delta {
+            crate::logging::log(crate::logging::LogLevel::Warn, format_args!("ASVP file header is not 'ASVPPLN1', but {:?}", &header[0..8]));
         }
         let compressed_sizes_size = u32::from_le_bytes(header[12..16].try_into().unwrap());
 
@@ -517,15 +1364,32 @@ impl<R: AsyncRead + AsyncSeek + Unpin + Send> ASVPFormat<R> {
             let mut reader_guard = reader.lock().await;
             reader_guard.read_exact(&mut compressed_sizes).await?;
         }
-        let sizes_raw = decompress_zlib(&compressed_sizes)?;
+        let (sizes_raw, compression) = decompress_detect(&compressed_sizes)?;
 
         if sizes_raw.len() % 8 != 0 {
             return Err(FormatError::InvalidFormat("Sizes table length not multiple of 8".to_string()));
         }
 
+        // Delta-encoded files list their keyframes after the sizes table
+        let mut keyframes = Vec::new();
+        let mut keyframe_table_size = 0;
+        if delta {
+            keyframe_table_size = u32::from_le_bytes(header[8..12].try_into().unwrap());
+            let mut compressed_keyframes = vec![0u8; keyframe_table_size as usize];
+            reader.lock().await.read_exact(&mut compressed_keyframes).await?;
+            let keyframes_raw = decompress(&compressed_keyframes, compression)?;
+            if keyframes_raw.len() % 4 != 0 {
+                return Err(FormatError::InvalidFormat("Keyframe table length not multiple of 4".to_string()));
+            }
+            keyframes = keyframes_raw.chunks_exact(4).map(|k| u32::from_le_bytes(k.try_into().unwrap())).collect();
+            if !keyframes.is_sorted() || keyframes.first().is_some_and(|&k| k != 0) {
+                return Err(FormatError::InvalidFormat("Keyframe table must be sorted and start at frame 0".to_string()));
+            }
+        }
+
         let mut frame_sizes = Vec::new();
         let mut frame_offsets = Vec::new();
-        let mut offset = 16 + compressed_sizes_size as u64; // body_base
+        let mut offset = base + 16 + compressed_sizes_size as u64 + keyframe_table_size as u64; // body_base
 
         for chunk in sizes_raw.chunks_exact(8) {
             let size = u64::from_le_bytes(chunk.try_into().unwrap());
@@ -538,6 +1402,8 @@ impl<R: AsyncRead + AsyncSeek + Unpin + Send> ASVPFormat<R> {
         let metadata = Metadata {
             frame_count,
             compressed_sizes_size,
+            stream_size: offset - base,
+            compression,
         };
 
         Ok(Self {
@@ -545,8 +1411,119 @@ impl<R: AsyncRead + AsyncSeek + Unpin + Send> ASVPFormat<R> {
             metadata: Some(metadata),
             frame_offsets,
             frame_sizes,
+            track: track.map(str::to_string),
+            tracks,
+            delta,
+            keyframes,
+            keyframe: Default::default(),
+            compression,
         })
     }
+
+    /// Whether frames are delta-encoded against keyframes
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn is_delta_encoded(&self) -> bool {
+        self.delta
+    }
+
+    /// Keyframes of a delta-encoded file, sorted; empty for a plain file, where every frame stands alone
+    pub fn keyframes(&self) -> &[u32] {
+        &self.keyframes
+    }
+
+    /// The keyframe `frame_index` is reconstructed from: the nearest keyframe at or before it, or the
+    /// frame itself in a plain file
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn keyframe_for(&self, frame_index: u32) -> u32 {
+        if !self.delta {
+            return frame_index;
+        }
+        let at = self.keyframes.partition_point(|&k| k <= frame_index);
+        self.keyframes[at.saturating_sub(1)]
+    }
+
+    /// Channel sizes of a frame, read by inflating only the start of its record instead of the whole
+    /// frame. None for a delta frame, whose header is only known once its keyframe is patched.
+    pub async fn peek_channel_sizes(&self, frame_index: u32) -> Result<Option<Vec<u32>>, FormatError> {
+        let index = frame_index as usize;
+        let (Some(&offset), Some(&size)) = (self.frame_offsets.get(index), self.frame_sizes.get(index)) else {
+            return Err(FormatError::InvalidFormat(format!("Frame {} is past the last frame", frame_index)));
+        };
+        // Delta-encoded records start with a tag byte
+        let skip = self.delta as usize;
+        let mut read_len = size.min(HEADER_PEEK_READ);
+        let mut limit = skip + HEADER_PEEK_BYTES;
+        loop {
+            let mut record = vec![0u8; read_len as usize];
+            {
+                let mut reader = self.reader.lock().await;
+                reader.seek(std::io::SeekFrom::Start(offset)).await?;
+                reader.read_exact(&mut record).await?;
+            }
+            if record.len() < 4 {
+                return Err(FormatError::InvalidFormat("Frame too short".to_string()));
+            }
+            let expected_len = u32::from_le_bytes(record[0..4].try_into().unwrap()) as usize;
+            let requested = limit.min(expected_len);
+            let inflated = decompress_prefix(&record[4..], requested, self.compression)?;
+            if self.delta && matches!(DeltaRecord::parse(&inflated)?, DeltaRecord::Delta { .. }) {
+                return Ok(None);
+            }
+            let payload = inflated.get(skip..).unwrap_or_default();
+            if payload.len() >= 4 {
+                let channel_count = u32::from_le_bytes(payload[0..4].try_into().unwrap()) as usize;
+                let header_size = channel_count.checked_mul(4).and_then(|n| n.checked_add(4)).filter(|&n| skip + n <= expected_len);
+                let Some(header_size) = header_size else {
+                    return Err(FormatError::InvalidFormat("Payload header incomplete".to_string()));
+                };
+                if payload.len() >= header_size {
+                    return Ok(Some(payload[4..header_size].chunks_exact(4).map(|b| u32::from_le_bytes(b.try_into().unwrap())).collect()));
+                }
+                limit = skip + header_size;
+            }
+            if inflated.len() < requested {
+                // Ran out of input before the header
+                if read_len == size {
+                    return Err(FormatError::InvalidFormat("Payload header incomplete".to_string()));
+                }
+                read_len = (read_len * 4).min(size);
+            } else if inflated.len() == expected_len {
+                return Err(FormatError::InvalidFormat("Decompressed payload too short".to_string()));
+            }
+        }
+    }
+
+    /// Track directory of a container, empty for a plain ASVP file
+    pub fn tracks(&self) -> &[TrackInfo] {
+        &self.tracks
+    }
+
+    /// Keyframe thumbnails of the thumbnail track `track` (the first one for None), sorted by frame index
+    pub async fn thumbnails(&self, track: Option<&str>) -> Result<Vec<Thumbnail>, FormatError> {
+        let payload = self.read_track(TrackKind::Thumbnails, track).await?;
+        crate::container::decode_thumbnails(&payload)
+    }
+
+    /// Notes of the annotation track `track` (the first one for None)
+    pub async fn annotations(&self, track: Option<&str>) -> Result<Vec<Annotation>, FormatError> {
+        let payload = self.read_track(TrackKind::Annotations, track).await?;
+        crate::container::decode_annotations(&payload)
+    }
+
+    /// Markers of the cue track `track` (the first one for None)
+    pub async fn cues(&self, track: Option<&str>) -> Result<CueTrack, FormatError> {
+        let payload = self.read_track(TrackKind::Cues, track).await?;
+        crate::container::decode_cues(&payload)
+    }
+
+    async fn read_track(&self, kind: TrackKind, track: Option<&str>) -> Result<Vec<u8>, FormatError> {
+        let info = select_track(&self.tracks, kind, track)?;
+        let mut payload = vec![0u8; info.length as usize];
+        let mut reader = self.reader.lock().await;
+        reader.seek(std::io::SeekFrom::Start(info.offset)).await?;
+        reader.read_exact(&mut payload).await?;
+        Ok(payload)
+    }
 }
 
 impl<R: AsyncRead + AsyncSeek + Unpin + Send> ASFormat for ASVPFormat<R> {
@@ -556,10 +1533,12 @@ impl<R: AsyncRead + AsyncSeek + Unpin + Send> ASFormat for ASVPFormat<R> {
     }
 
     fn decode_frame(&mut self, frame_index: u32) -> FrameDataFuture<'_> {
-        let frame_index = frame_index;
         let frame_offsets = self.frame_offsets.clone();
         let frame_sizes = self.frame_sizes.clone();
         let reader = self.reader.clone();
+        let delta = self.delta;
+        let compression = self.compression;
+        let keyframe_cache = self.keyframe.clone();
         Box::pin(async move {
             let mut frame_index = frame_index;
             if frame_index >= frame_sizes.len() as u32 {
@@ -568,24 +1547,34 @@ impl<R: AsyncRead + AsyncSeek + Unpin + Send> ASFormat for ASVPFormat<R> {
             }
 
             let mut reader = reader.lock().await;
-            // Seek to frame offset
-            reader.seek(std::io::SeekFrom::Start(frame_offsets[frame_index as usize])).await?;
-            let frame_size = frame_sizes[frame_index as usize] as usize;
-            let mut frame_data = vec![0u8; frame_size];
-            reader.read_exact(&mut frame_data).await?;
-
-            // Parse frame: first 4 bytes = expected_uncompressed_len
-            if frame_data.len() < 4 {
-                return Err(FormatError::InvalidFormat("Frame too short".to_string()));
-            }
-            let expected_len = u32::from_le_bytes(frame_data[0..4].try_into().unwrap()) as usize;
-            let compressed_payload = &frame_data[4..];
-
-            // Decompress payload
-            let decompressed = decompress_zlib(compressed_payload)?;
-            if decompressed.len() != expected_len {
-                return Err(FormatError::InvalidFormat("Decompressed length mismatch".to_string()));
-            }
+            let record = read_asvp_record(&mut *reader, frame_index, frame_offsets[frame_index as usize], frame_sizes[frame_index as usize], compression).await?;
+            let decompressed = if !delta {
+                record
+            } else {
+                match DeltaRecord::parse(&record)? {
+                    DeltaRecord::Keyframe(polystream) => {
+                        *keyframe_cache.lock().unwrap() = Some((frame_index, Arc::new(polystream.to_vec())));
+                        polystream.to_vec()
+                    }
+                    DeltaRecord::Delta { keyframe, edits } => {
+                        let cached = keyframe_cache.lock().unwrap().clone().filter(|(index, _)| *index == keyframe);
+                        let key = match cached {
+                            Some((_, key)) => key,
+                            None if (keyframe as usize) < frame_sizes.len() => {
+                                let key_record = read_asvp_record(&mut *reader, keyframe, frame_offsets[keyframe as usize], frame_sizes[keyframe as usize], compression).await?;
+                                let DeltaRecord::Keyframe(key) = DeltaRecord::parse(&key_record)? else {
+                                    return Err(FormatError::InvalidFormat(format!("Frame {} refers to frame {} which is not a keyframe", frame_index, keyframe)));
+                                };
+                                let key = Arc::new(key.to_vec());
+                                *keyframe_cache.lock().unwrap() = Some((keyframe, Arc::clone(&key)));
+                                key
+                            }
+                            None => return Err(FormatError::InvalidFormat(format!("Frame {} refers to missing keyframe {}", frame_index, keyframe))),
+                        };
+                        apply_delta(&key, edits)?
+                    }
+                }
+            };
 
             // Parse decompressed payload (same as ASVR)
             if decompressed.len() < 4 {
@@ -626,6 +1615,47 @@ mod tests {
     use super::*;
     use proptest::prelude::*;
 
+    #[test]
+    fn test_frame_data_serialization() {
+        let full = FrameData { polystream: vec![1, 0, 0, 0, 2, 0, 0, 0, 7, 8], bitmap: Some(vec![0, 255, 128]), triangle_strip: Some(vec![1.5, -2.0]) };
+        // The layout is frozen: frames serialized by this release must parse in every later one
+        let bytes = full.to_bytes();
+        assert_eq!(bytes, [
+            b"ASFD".as_slice(), &[1, 0, 3, 0],
+            &[10, 0, 0, 0], &[1, 0, 0, 0, 2, 0, 0, 0, 7, 8],
+            &[3, 0, 0, 0], &[0, 255, 128],
+            &[8, 0, 0, 0], &1.5f32.to_le_bytes(), &(-2.0f32).to_le_bytes(),
+        ].concat());
+        for frame in [
+            full.clone(),
+            FrameData { bitmap: None, ..full.clone() },
+            FrameData { triangle_strip: None, ..full.clone() },
+            FrameData { polystream: Vec::new(), bitmap: Some(Vec::new()), triangle_strip: None },
+        ] {
+            assert_eq!(FrameData::from_bytes(&frame.to_bytes()).unwrap(), frame);
+        }
+
+        // Sections of a newer writer are skipped
+        let mut newer = bytes.clone();
+        newer[6] |= 1 << 2;
+        newer.extend_from_slice(&[2, 0, 0, 0, 9, 9]);
+        assert_eq!(FrameData::from_bytes(&newer).unwrap(), full);
+        // ... a newer version is not
+        let mut future = bytes.clone();
+        future[4] = 2;
+        assert!(matches!(FrameData::from_bytes(&future), Err(FormatError::InvalidFormat(_))));
+        for len in [0, 4, 8, 15, bytes.len() - 1] {
+            assert!(FrameData::from_bytes(&bytes[..len]).is_err(), "truncated to {}", len);
+        }
+        let mut partial = bytes[..22].to_vec();
+        partial[6] = SECTION_TRIANGLE_STRIP as u8;
+        partial.extend_from_slice(&[3, 0, 0, 0, 1, 2, 3]);
+        match FrameData::from_bytes(&partial) {
+            Err(FormatError::InvalidFormat(message)) => assert!(message.contains("partial"), "{}", message),
+            other => panic!("{:?}", other),
+        }
+    }
+
     #[test]
     fn test_derive_key() {
         let scene_id = 12345u32;
@@ -635,6 +1665,22 @@ mod tests {
         assert_eq!(key.len(), 32);
     }
 
+    #[tokio::test]
+    async fn test_key_derivation_service() {
+        let service = KeyDerivationService::default();
+        let expected = derive_key(12345, b"1.5.0", b"test.asvr").unwrap();
+        // Opened at once: one derivation, awaited by all
+        let keys = futures::future::join_all((0..4).map(|_| service.derive(12345, b"1.5.0", b"test.asvr"))).await;
+        assert!(keys.iter().all(|key| key.as_ref().unwrap() == &expected));
+        assert_eq!(service.derivations(), 1);
+        assert!(service.pending.lock().unwrap().is_empty());
+        assert_ne!(service.derive(12345, b"1.5.0", b"other.asvr").await.unwrap(), expected);
+        assert_eq!(service.derivations(), 2);
+        // Without a runtime the key is derived on the calling thread
+        let key = std::thread::spawn(move || futures::executor::block_on(service.derive(12345, b"1.5.0", b"test.asvr"))).join().unwrap();
+        assert_eq!(key.unwrap(), expected);
+    }
+
     proptest! {
         #[test]
         fn fuzz_decompress_zlib_roundtrip(data in proptest::collection::vec(any::<u8>(), 0..1024)) {
@@ -652,11 +1698,11 @@ mod tests {
     fn test_decrypt_frame_data() {
         let data = vec![1, 2, 3, 4];
         let key = [0u8; 32];
-        let decrypted = decrypt_frame_data(&data, &key, 0).unwrap();
+        let decrypted = decrypt_frame_data(&data, &key, 0, CipherProfile::VENDOR).unwrap();
         // Since ChaCha20 is symmetric, encrypting with same key should give different result
         assert_ne!(decrypted, data);
         // Decrypt again should give original
-        let re_decrypted = decrypt_frame_data(&decrypted, &key, 0).unwrap();
+        let re_decrypted = decrypt_frame_data(&decrypted, &key, 0, CipherProfile::VENDOR).unwrap();
         assert_eq!(re_decrypted, data);
     }
 
@@ -692,7 +1738,7 @@ mod tests {
         let expected_decrypted = b"J\x03\x00\x00x\x9cM\x92K\x8e$5\x10\x86\xc3\x11~ef\xd7c\xaa\xba\x005\x0b\x06!$\xa4\xd9s\x16v\xdc\x83\x1b\xb0\x84\x03\xcc\x8a\xdb\xb0b\xc3\n\x8df4LKT\x89\xaeg>\x9cv\x84\x89\x96z\xc1\xe2w\xc8v8\xfc\xfbs\xdc\x01\x80\xff\x9f\xecK\x8c\xaa\xf5\xcb<\xbe\xc4\x1f\x0c\xc0O\xaa\xe2~#g\x02\xf6\x08\x82xE\x84jn\xd8\x9b\xbf\xd1\xc2\xc9|D$\xe0l> \x905\xc6y\x11\x066\xf8\x88\xc9\xfc\x85\xd1\xcc\xf2-\xfe\x8e\x85~6\x19\x98\xd8\x8cp\x98R}\x8fG{v\xbd;\xb7c<oN\x0fO\xdf\x08\x96\x08\x98\x16\xcf\x920\xbd\xaa\x8e\x1d\xd0\xb3\x84$\xceK\t`%\x08\x01\xcd\xeb\xd9\x97P\xec\xe4\x8e\xf1\x8f\xd5\xbbx\xaaW\x930\x13\xab\xbd\x11\xb3\x1d \x8b\x18\xa9F+\x14\xec\xf51cd\x1d\xb3O\xfe\xba\xea\x9bKs\x8a#\x0en\xf2}7\xac\xa5\x03G\x1e0\xb4\xbb\xcd\xc3f\x19\x10\x97\xf6M\xbc7\xf7\xf4%x\xd9\xcaw\x10\xc42\x82\xe3\xaf\x06\x0b-o\x07\x84\xed\xf4z\x1f8\xe4U\xb6\xac\xa5\xe6\xe5)p\x15W\x11\x0b\"6\xaekZ\x87\xd8Q\xf4\x01\x1dtNO\x13\xd9@\x11\xac\xb1du%\x00\n+\x8eg\xa3\" \xcd\x15\xde\xc2[Ns:\xffs}\x9f\x0f0I\x96\x0b\xfci\xb2\x9b\x1bh\'\xab8Z\xcd5\xa9~\xd2\xa7%3\xab\xb8N\x12~4\xcc\xef\xe6\xc7\xda\xf3\r\xce6\xc3\x85\x1e\xe3SS\xcc\xa9\xddo\xce[\xf6\xd9O+\xa6\xdb}Z\xe5P\xed\xed\x0bQ\xbb\xe3rZs\xa7\\\xbb\xf9\xde\xac\x8b\xf2\xac\x9e\t\xa2u\xca\x98\xaa\xe6_\xc2@\x192\xf5f\xef\x8ft\xad\xfb\xdcC/Opp3\xcc\x98\xe0c{\t}<\xec2\xed\xdb\x11G\xcbp\xf4\x97pX~z\xe8\xef\x86E^\xea?u~\xe1\xc3*\"\xee\xc2\x9b\xb8\x85\x1d\xb6\xd8\xdaN\t\x10yym<o\xcag\xc6V\xa3\x17Z\xe5\xf3}]\xe0\n\x16\xe5\xeb\xf20=\x9biK+\xafF/\x9fs\xe4\xa8\xf6B]\xdcB\xf13\xd5\x90\xec\xac\x92\x16:\xdb\xc6\xe6\xee\xae\tV\x9b\x8f\x10<\xed4l\xdd\x02\x1a\xd1\xfdje-\xfa\xf5\xbf\xc0\xafr\x94\xc25\x0f \\\xd29_d\x82\x99\x0b\x9fa\xd4nM\x94Y*+\xdb\xc9\x88\xad\x95\xb1j\xf2 \x8c\x0c\x03%s\xa5\x04)\x1c\xe2\xa8\x8d((\xf8o\x15\xf3\xe1?\x01\xc2W#";
         let expected_uncompressed = b"\x0c\x00\x00\x00\x06\x00\x00\x00\x06\x00\x00\x00\x06\x00\x00\x00\x04\x00\x00\x00\x06\x00\x00\x00\x08\x00\x00\x00\x10\x00\x00\x00\x04\x00\x00\x00\x08\x00\x00\x00\x04\x00\x00\x00X\x01\x00\x00z\x01\x00\x00\xfc\x05\xa4\x03\x05\x01\x07\x02\xf5\x02\x00\xfe\x02\x02\xf3\x02\x02\x00\xff\x01\xf4\x02\xf5\x01\xe4\x02\x04\x00\xf0\x01\xe3\x02\x02\x03\x00\xfd\xfb\x01\xe1\x02\x00\x03\x04\x01\x01\x05\x06\xfe\xfe\xfd\x00\xfd\x01\x02\xe6\x02\xf9\x01\xdc\x02\x08\x01\xfa\xfe%\x02\xcb\x02\xfc\x03\x89\x01\xfb\x00\xfd\x03\xfd\x01\xf7\x00\xeb\xf8\xf9\xff\xe0\x02\xef\x04\xf1\x05\xf5\x05\xf1\n\xf7\x08\xf1\x12\xf0\x1a\xee#\xfe\x02\xfc\x08\x00\x02\xf9\r\x00\x02\xf9\r\xfe\x07\xf8\x11\xff\x05\xfd\x05\x00\x03\xfd\x05\x00\x03\xfe\x03\xfe\x08\xfa\x0e\xfe\x07\x00\x04\xfe\x07\xfe\x03\x00\x03\xfa\x10\xfa\x06\xfc\x07\xfc\x04\xf8\x05\xef\x08\xd1\x0f\xde\x08\xf0\xff\xf3\x01\xf9\x02\xfb\x03\xfd\x00\xfe\x02\xf7\x02\xfb\x04\xf6\x00\xfb\xfe\xfe\x01\xfe\xff\x01\x05\xfd\x05\xfc\x02\xf5\x00\x00\x04\xf7\x08\xfd\x00\x00\x04\xfb\x06\xf9\x06\xf3\x0f\xf5\t\xf2\t\xf0\x08\xf7\x02\xf6\x05\xf8\x06\xf5\x0b\xf6\x10\xfe\x0b\x00\x05\x03\x06\x00\x02\x07\n\x15\x12\x1a\x12\x0e\x07\x02\x02\x0e\x04+\x08\x14\x01\x14\x03\x1b\x00\x06\xfe\x13\xfe(\x00\x07\xfe\x04\xfd\x02\x00\x05\xfd\x1f\xf6\x04\x00\n\xfd\x13\xf6\x02\x00\x13\xf8 \xea\x07\xfd\x07\xfb\x0f\xfb\x04\xfd\x08\xf7\x02\xfa\x0e\xf0\x07\xfd\xff\xfe\x05\xff\x02\x02\xfc\x02\x02\x02\t\x05\x0b\t\n\x05\x02\x02\x0b\x03\x08\x06\x07\x02\x05\x00\x0b\x05\x02\x00\x05\x03\x03\x04\x07\x03\x08\x00\x04\x01\x04\x03\x04\x00\x0b\x05\x07\x00\x02\xfe\xfd\xfd\x03\xfd\xfe\xfe\x01\xfe\xfe\xfe\x00\xfe\t\xf3\x00\x9e\x00\x9e\xfd\xf9\xfa\xf9\xf1\xe9\xf3\xe0\xfb\xeb\x00\xf8\xfe\xfb\xfe\xf2\x00\xd6\x01\xfb\x05\xfa\t\x00\n\xf8\x04\xff\x05\xfd\n\xfe\x01\xfe\x01\xf9\xff\xe5\x01\xfe\xff\xf9\x01\xfa\xff\xf9\x01\xfd\xff\xf8\xfe\x07_\x01\xfd\xfd\xde\xfa\xe6\xff\xf5\xfd\xf4\x00\xf1\x04\xfb\x00\xf2\x03\xe6\x08\xee\t\xfc\x01\xf0\n\xea\x12\xf1\x13\xfd\x06\xfb\x06\xf8\x0f\xfd\x03\xf4\x14\xf9\x0f\xfb\x07\xff\x04\xf4\x18\xfe\x02\xfa\x0e\xf7\x0e\xf8\x10\xfd\x0b\xfe\x03\xfe\x0b\xfa\x14\x01\x10\xfc\x10\xfa\x06\xff\x06\xfd\x03\x00\x08\x04\x05\x00\x04\xfe\x03\xff\x0f\xfd\x03\xf2\x07\xf6\x03\xfb\x00\xfb\x03\xf5\x01\xea\x06\xef\x03\xf3\xff\xea\xfb\xf5\x00\xf5\xfe\xee\x00\xeb\x05\xfa\x00\xfa\x02\xf9\x00\xe3\n\xf2\x07\xf5\x08\xeb\x15\xfb\x03\xea\n\xf7\x02\xf7\x04\xfd\x00\xef\x06\xf2\x07\xeb\x0e\xe5\x1a\xf5\x0c\xf6\r\xfb\x0e\xfe\x03\x00\x0b\x06\r\x06\x07\x0f\x08\x02\x02\x15\x07+\x08\x13\x00\x15\x02\n\x02\n\x04\x0b\x00\x04\x01\x03\x03\x06\xfe \x01\x06\xfd\x12\xfc\x16\x01\x04\xff\x01\xfe\x03\xff\x04\x00\x05\x036\xff\r\x02\x0f\x00\r\xfc!\xfc\x1a\xf8\x06\xfd\x03\x00\n\xfc\n\xfe\x11\xf7\x06\xfe\x17\xfd\x08\xfd\x08\xff\x06\xfd\x07\xff\r\xf4\x07\xfc\x06\xfa\x03\xff\x07\xf9\x04\xfa\x07\xf9\x04\xfe\n\x00\x0b\x04\n\x08\t\x0c\x0c\t\x07\x04\x02\x00\x03\x03\x02\x00\x06\x03\x15\x03\x03\x02\x13\x05\r\x00\t\xfe\x04\xfe\n\xff\x04\xfe\x10\xfe\x08\xfd\x00\x91\x00\x92\xfe\xef\xfe\xfc\xfd\xff\xfb\xf6\x00\xfe\xfd\xfc\xf9\xf1\xfb\xf2\xfe\xf8\x00\xfa\xfd\xfc\xfd\xf1\x00\xf7\xfe\xfd\x00\xf9\x03\xfb\xfd\xfe\xff\xfd\x01\xf9\xff\xf8\x01\xfe\x04\xff\xff\xfd\x02\xff\xfc\xfd\xff\xf6\xfe\xfd\x02\xfd\x00\xf6\x03\xf9\x01\xf3\x03\xf9\x00\xf9\x07\xeb\x08\xf7\x03\xfe\x08\xfe\x02\xfe\x02\xed\xff\xfe\x01\xe1";
         let key = derive_key(85342, b"1.5.0", b"pov_mask.asvr").unwrap();
-        let decrypted = decrypt_frame_data(encrypted, &key, frame_number).unwrap();
+        let decrypted = decrypt_frame_data(encrypted, &key, frame_number, CipherProfile::VENDOR).unwrap();
         assert_eq!(&decrypted[..], expected_decrypted);
         let expected_len = u32::from_le_bytes(decrypted[0..4].try_into().unwrap()) as usize;
         let compressed_payload = &decrypted[4..];
@@ -714,6 +1760,15 @@ mod tests {
         payload
     }
 
+    fn make_multi_channel_payload(channels: &[Vec<u8>]) -> Vec<u8> {
+        let mut payload = (channels.len() as u32).to_le_bytes().to_vec();
+        for channel in channels {
+            payload.extend_from_slice(&(channel.len() as u32).to_le_bytes());
+        }
+        payload.extend(channels.concat());
+        payload
+    }
+
     #[tokio::test]
     async fn test_asvp_writer_roundtrip() {
         
@@ -739,10 +1794,12 @@ mod tests {
         let written = writer.write_all().unwrap();
         
         // Read back with ASVPFormat
+        let written_len = written.len() as u64;
         let cursor = std::io::Cursor::new(written);
         let mut format_reader = ASVPFormat::new(cursor).await.unwrap();
         
         assert_eq!(format_reader.frame_count().await.unwrap(), 2);
+        assert_eq!(format_reader.metadata().await.unwrap().stream_size, written_len);
 
         // polystream is header + all channel data
         let expected_data_0 = &[1, 0, 0, 0, 4, 0, 0, 0, 0x01, 0x02, 0x03, 0x04];
@@ -755,6 +1812,135 @@ mod tests {
         assert_eq!(decoded_frame_1.polystream, expected_data_1);
     }
 
+    #[tokio::test]
+    async fn test_asvp_delta_roundtrip() {
+        // A mostly static outline with one point wobbling
+        let mut seed = 12345u32;
+        let outline: Vec<u8> = [100u16.to_le_bytes(), 50u16.to_le_bytes()].concat().into_iter()
+            .chain((0..400).map(|_| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (seed >> 24) as u8 % 5
+            }))
+            .collect();
+        let frames: Vec<Vec<u8>> = (0..40u8).map(|i| {
+            let mut channel = outline.clone();
+            channel[200] = i % 3;
+            make_frame_payload(&channel)
+        }).collect();
+        let write = |interval| {
+            let mut writer = ASVPWriter::new(Vec::new()).keyframe_interval(interval);
+            for polystream in &frames {
+                writer.add_frame(FrameData { polystream: polystream.clone(), bitmap: None, triangle_strip: None });
+            }
+            writer.write_all().unwrap()
+        };
+        let plain = write(0);
+        let delta = write(16);
+        assert_eq!(&delta[0..8], b"ASVPDLT1");
+        assert!(delta.len() * 4 < plain.len(), "delta {} bytes, plain {}", delta.len(), plain.len());
+
+        let mut reader = ASVPFormat::new(std::io::Cursor::new(delta)).await.unwrap();
+        assert!(reader.is_delta_encoded());
+        assert_eq!(reader.frame_count().await.unwrap(), 40);
+        assert_eq!(reader.keyframes(), [0, 16, 32]);
+        assert_eq!(reader.keyframe_for(15), 0);
+        assert_eq!(reader.keyframe_for(16), 16);
+        assert_eq!(reader.keyframe_for(39), 32);
+        // Random access, including frames whose keyframe is not the cached one
+        for index in [39, 0, 17, 5, 16, 33, 32, 1] {
+            assert_eq!(reader.decode_frame(index).await.unwrap().polystream, frames[index as usize], "frame {}", index);
+        }
+        assert!(!ASVPFormat::new(std::io::Cursor::new(plain)).await.unwrap().is_delta_encoded());
+    }
+
+    #[tokio::test]
+    async fn test_asvp_peek_channel_sizes() {
+        // Few channels; many channels (a header past the first inflate); incompressible channel data
+        // (a header past the first read)
+        let mut seed = 7u32;
+        let noise: Vec<u8> = (0..5000).map(|_| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 24) as u8
+        }).collect();
+        let polystreams = vec![
+            make_frame_payload(&[1, 2, 3, 4]),
+            make_multi_channel_payload(&vec![vec![9u8; 3]; 100]),
+            make_multi_channel_payload(&[noise[..4000].to_vec(), noise[4000..].to_vec()]),
+        ];
+        for interval in [0, 2] {
+            let mut writer = ASVPWriter::new(Vec::new()).keyframe_interval(interval);
+            for polystream in &polystreams {
+                writer.add_frame(FrameData { polystream: polystream.clone(), bitmap: None, triangle_strip: None });
+            }
+            let reader = ASVPFormat::new(std::io::Cursor::new(writer.write_all().unwrap())).await.unwrap();
+            assert_eq!(reader.peek_channel_sizes(0).await.unwrap(), Some(vec![4]));
+            let sizes = reader.peek_channel_sizes(1).await.unwrap();
+            if interval == 0 {
+                assert_eq!(sizes, Some(vec![3; 100]));
+            } else {
+                // Frame 1 is a delta against frame 0 if that is smaller; either way no wrong answer
+                assert!(sizes.is_none() || sizes == Some(vec![3; 100]));
+            }
+            assert_eq!(reader.peek_channel_sizes(2).await.unwrap(), Some(vec![4000, 1000]));
+            assert!(reader.peek_channel_sizes(3).await.is_err());
+        }
+
+        // A cut-short stream inflates as far as it goes
+        let compressed = compress_zlib(&noise).unwrap();
+        assert_eq!(decompress_prefix(&compressed, 100, Compression::Zlib).unwrap(), noise[..100]);
+        let partial = decompress_prefix(&compressed[..compressed.len() / 2], 5000, Compression::Zlib).unwrap();
+        assert!(!partial.is_empty() && partial.len() < 5000);
+        assert_eq!(partial, noise[..partial.len()]);
+    }
+
+    #[tokio::test]
+    async fn test_asvp_vendor_compression() {
+        use flate2::write::{DeflateEncoder, GzEncoder};
+
+        fn compress(data: &[u8], compression: Compression) -> Vec<u8> {
+            let level = flate2::Compression::default();
+            match compression {
+                Compression::Zlib => compress_zlib(data).unwrap(),
+                Compression::RawDeflate => {
+                    let mut encoder = DeflateEncoder::new(Vec::new(), level);
+                    encoder.write_all(data).unwrap();
+                    encoder.finish().unwrap()
+                }
+                Compression::Gzip => {
+                    let mut encoder = GzEncoder::new(Vec::new(), level);
+                    encoder.write_all(data).unwrap();
+                    encoder.finish().unwrap()
+                }
+            }
+        }
+
+        let polystreams = [make_frame_payload(&[1, 2, 3, 4]), make_multi_channel_payload(&[vec![5u8; 300], vec![6u8; 40]])];
+        for compression in [Compression::Zlib, Compression::RawDeflate, Compression::Gzip] {
+            let records: Vec<Vec<u8>> = polystreams.iter().map(|polystream| {
+                let mut record = (polystream.len() as u32).to_le_bytes().to_vec();
+                record.extend(compress(polystream, compression));
+                record
+            }).collect();
+            let sizes: Vec<u8> = records.iter().flat_map(|r| (r.len() as u64).to_le_bytes()).collect();
+            let sizes = compress(&sizes, compression);
+            let mut file = ASVP_MAGIC.to_vec();
+            file.extend_from_slice(&0u32.to_le_bytes());
+            file.extend_from_slice(&(sizes.len() as u32).to_le_bytes());
+            file.extend(sizes);
+            file.extend(records.concat());
+
+            let mut reader = ASVPFormat::new(std::io::Cursor::new(file)).await.unwrap();
+            let metadata = reader.metadata().await.unwrap();
+            assert_eq!((metadata.frame_count, metadata.compression), (2, compression));
+            for (index, polystream) in polystreams.iter().enumerate() {
+                assert_eq!(&reader.decode_frame(index as u32).await.unwrap().polystream, polystream);
+            }
+            assert_eq!(reader.peek_channel_sizes(1).await.unwrap(), Some(vec![300, 40]));
+        }
+
+        assert_eq!(decompress_detect(b"not compressed at all").unwrap_err().to_string(), FormatError::Zlib.to_string());
+    }
+
     #[tokio::test]
     async fn test_asvr_writer_roundtrip() {
         use std::io::Cursor;
@@ -818,6 +2004,144 @@ mod tests {
         assert_eq!(decoded_frame_2.polystream, expected_data_2);
     }
 
+    #[test]
+    fn test_write_all_verified() {
+        use std::io::Cursor;
+
+        /// A file that corrupts the byte at `flip_at` on its way to disk
+        struct Flaky { file: Cursor<Vec<u8>>, flip_at: u64 }
+        impl Write for Flaky {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                let at = self.file.position();
+                self.file.write_all(buf)?;
+                if (at..at + buf.len() as u64).contains(&self.flip_at) {
+                    self.file.get_mut()[self.flip_at as usize] ^= 0x55;
+                }
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+        }
+        impl Read for Flaky {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> { Read::read(&mut self.file, buf) }
+        }
+        impl Seek for Flaky {
+            fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> { Seek::seek(&mut self.file, pos) }
+        }
+
+        assert_eq!(VerifySample::Evenly(4).frames(10), [0, 3, 6, 9]);
+        assert_eq!(VerifySample::Evenly(1).frames(10), [0]);
+        assert_eq!(VerifySample::Evenly(20).frames(3), [0, 1, 2]);
+        assert_eq!(VerifySample::All.frames(3), [0, 1, 2]);
+
+        let frames: Vec<FrameData> = (0..24u8)
+            .map(|i| FrameData { polystream: make_frame_payload(&[i % 4; 64]), bitmap: None, triangle_strip: None })
+            .collect();
+        let asvp = |interval, flip_at| {
+            let mut writer = ASVPWriter::new(Flaky { file: Cursor::default(), flip_at }).keyframe_interval(interval);
+            frames.iter().for_each(|frame| writer.add_frame(frame.clone()));
+            writer.write_all_verified(VerifySample::All).unwrap()
+        };
+        let asvr = |flip_at, sample| {
+            let mut writer = ASVRWriter::new(Flaky { file: Cursor::default(), flip_at }, 7, b"1.5.0", b"verify.asvr").unwrap();
+            frames.iter().for_each(|frame| writer.add_frame(frame.clone()));
+            writer.write_all_verified(sample).unwrap()
+        };
+
+        for interval in [0, 8] {
+            let (file, report) = asvp(interval, u64::MAX);
+            assert!(report.is_ok(), "{:?}", report);
+            assert_eq!((report.frames_written, report.frames_found, report.checked.len()), (24, 24, 24));
+            // A bad byte in the last record (its zlib checksum) fails just that frame
+            let (_, report) = asvp(interval, file.file.get_ref().len() as u64 - 1);
+            assert!(!report.is_ok());
+            assert_eq!(report.failures.iter().map(VerifyFailure::frame_index).collect::<Vec<_>>(), [23]);
+        }
+
+        let (file, report) = asvr(u64::MAX, VerifySample::Evenly(5));
+        assert!(report.is_ok(), "{:?}", report);
+        assert_eq!(report.checked, [0, 5, 11, 17, 23]);
+        // Encrypted bytes decrypt to garbage instead of failing a checksum: the last byte of the last frame
+        let (_, report) = asvr(file.file.get_ref().len() as u64 - 1, VerifySample::All);
+        assert!(matches!(report.failures[..], [VerifyFailure::Decode { frame_index: 23, .. } | VerifyFailure::Mismatch { frame_index: 23, .. }]), "{:?}", report);
+        // Unsampled frames are not looked at
+        let (_, report) = asvr(file.file.get_ref().len() as u64 - 1, VerifySample::Evenly(1));
+        assert!(report.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_asvr_cipher_profiles() {
+        let frames: Vec<Vec<u8>> = (0..3u8).map(|i| make_frame_payload(&[i; 16])).collect();
+        let key = derive_key(5, b"1.5.0", b"profiles.asvr").unwrap();
+        let mut files = Vec::new();
+        for profile in CipherProfile::ALL {
+            let mut writer = ASVRWriter::new(Vec::new(), 5, b"1.5.0", b"profiles.asvr").unwrap().cipher_profile(profile);
+            for frame in &frames {
+                writer.add_frame(FrameData { polystream: frame.clone(), bitmap: None, triangle_strip: None });
+            }
+            let file = writer.write_all().unwrap();
+            assert!(!files.contains(&file), "{:?}", profile);
+
+            // Found by probing, and opened directly when known
+            let mut probed = ASVRFormat::with_key(std::io::Cursor::new(file.clone()), key).await.unwrap();
+            assert_eq!(probed.cipher_profile(), profile);
+            let mut known = ASVRFormat::with_profile(std::io::Cursor::new(file.clone()), key, profile).await.unwrap();
+            for (index, frame) in frames.iter().enumerate() {
+                assert_eq!(&probed.decode_frame(index as u32).await.unwrap().polystream, frame);
+                assert_eq!(&known.decode_frame(index as u32).await.unwrap().polystream, frame);
+            }
+            files.push(file);
+        }
+        // The one combination left out of ALL writes the vendor's bytes
+        let same = CipherProfile { cipher: CipherKind::ChaCha20Ietf, nonce: NonceLayout::KeyIdLast };
+        let mut writer = ASVRWriter::new(Vec::new(), 5, b"1.5.0", b"profiles.asvr").unwrap().cipher_profile(same);
+        frames.iter().for_each(|frame| writer.add_frame(FrameData { polystream: frame.clone(), bitmap: None, triangle_strip: None }));
+        assert_eq!(writer.write_all().unwrap(), files[0]);
+        // A wrong profile does not decrypt the header
+        assert!(ASVRFormat::with_profile(std::io::Cursor::new(files[0].clone()), key, CipherProfile::ALL[2]).await.is_err());
+        // Neither does a wrong key with any profile
+        assert!(ASVRFormat::with_key(std::io::Cursor::new(files[0].clone()), [7; 32]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_sequential_reader() {
+        let frames: Vec<Vec<u8>> = (0..4u8).map(|i| make_frame_payload(&[i; 10])).collect();
+        let mut writer = ASVPWriter::new(Vec::new());
+        for frame in &frames {
+            writer.add_frame(FrameData { polystream: frame.clone(), bitmap: None, triangle_strip: None });
+        }
+        let written = writer.write_all().unwrap();
+
+        // A byte slice reads but cannot seek; frames decoded in order (skipping some) are fine
+        let mut format_reader = ASVPFormat::new(SequentialReader::new(&written[..])).await.unwrap();
+        assert_eq!(format_reader.decode_frame(0).await.unwrap().polystream, frames[0]);
+        assert_eq!(format_reader.decode_frame(2).await.unwrap().polystream, frames[2]);
+        // Going back needs a window
+        let err = format_reader.decode_frame(2).await.unwrap_err();
+        assert!(matches!(err, FormatError::Io(ref e) if e.kind() == std::io::ErrorKind::Unsupported), "{:?}", err);
+
+        // A window of the largest frame allows repeating the last frame, as a player holding it does
+        let mut reader = SequentialReader::new(&written[..]);
+        reader.set_window(64);
+        let mut format_reader = ASVPFormat::new(reader).await.unwrap();
+        let largest = *format_reader.frame_sizes.iter().max().unwrap() as usize;
+        assert!(largest <= 64);
+        for index in [0, 1, 1, 3, 3, 3] {
+            assert_eq!(format_reader.decode_frame(index).await.unwrap().polystream, frames[index as usize]);
+        }
+        assert!(format_reader.decode_frame(0).await.is_err());
+
+        // Encrypted streams work the same way
+        let mut writer = ASVRWriter::new(Vec::new(), 1, b"1.5.0", b"stream.asvr").unwrap();
+        for frame in &frames {
+            writer.add_frame(FrameData { polystream: frame.clone(), bitmap: None, triangle_strip: None });
+        }
+        let written = writer.write_all().unwrap();
+        let mut format_reader = ASVRFormat::new(SequentialReader::new(&written[..]), 1, b"1.5.0", b"stream.asvr").await.unwrap();
+        for (index, frame) in frames.iter().enumerate() {
+            assert_eq!(&format_reader.decode_frame(index as u32).await.unwrap().polystream, frame);
+        }
+    }
+
     #[test]
     fn test_compress_zlib_roundtrip() {
         let original = b"Hello, AlphaStream! This is a test of zlib compression.";
@@ -833,8 +2157,8 @@ mod tests {
         let key_id = 12345;
         
         // Encrypt and decrypt should be symmetric
-        let encrypted = encrypt_frame_data(data, &key, key_id).unwrap();
-        let decrypted = decrypt_frame_data(&encrypted, &key, key_id).unwrap();
+        let encrypted = encrypt_frame_data(data, &key, key_id, CipherProfile::VENDOR).unwrap();
+        let decrypted = decrypt_frame_data(&encrypted, &key, key_id, CipherProfile::VENDOR).unwrap();
         assert_eq!(decrypted, data);
     }
 
@@ -0,0 +1,265 @@
+// Layout module
+// Where exported frames go: a naming template maps each frame to a path under the output directory
+// (e.g. "{scene}/{frame:06}.png"), and a manifest in the output directory records every file written
+// with its size and CRC-32. An interrupted export opened again skips the frames whose files are still
+// intact and writes the others to the same paths, so multi-hour jobs continue where they stopped.
+// Paths already taken, by another frame or by a file the export did not write, get a "-1", "-2", ...
+// suffix instead of being overwritten.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Manifest of an output directory, one JSON `ManifestEntry` per line, later lines replacing earlier ones
+pub const MANIFEST_FILE: &str = ".export-manifest.jsonl";
+
+/// A naming template that cannot be parsed
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    #[error("Unbalanced '{{' or '}}' in naming template")]
+    UnbalancedBrace,
+    #[error("Unknown placeholder '{{{0}}}' in naming template (expected scene or frame)")]
+    UnknownPlaceholder(String),
+    #[error("Invalid padding in '{{{0}}}' (expected e.g. frame:06)")]
+    InvalidPadding(String),
+    #[error("Naming template must be a relative path inside the output directory")]
+    OutsideOutput,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Scene,
+    /// Frame index, zero padded to `width` digits
+    Frame { width: usize },
+}
+
+/// File names of exported frames: text with `{scene}` and `{frame}` placeholders, the frame index optionally
+/// zero padded as `{frame:06}`. `/` separates directories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    parts: Vec<Part>,
+}
+
+impl NameTemplate {
+    pub fn parse(template: &str) -> Result<Self, TemplateError> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while !rest.is_empty() {
+            let brace = rest.find(['{', '}']).unwrap_or(rest.len());
+            if brace > 0 {
+                parts.push(Part::Literal(rest[..brace].to_string()));
+            }
+            rest = &rest[brace..];
+            if rest.is_empty() {
+                break;
+            }
+            let end = rest.find('}').filter(|_| rest.starts_with('{')).ok_or(TemplateError::UnbalancedBrace)?;
+            let placeholder = &rest[1..end];
+            parts.push(match placeholder.split_once(':') {
+                None if placeholder == "scene" => Part::Scene,
+                None if placeholder == "frame" => Part::Frame { width: 0 },
+                Some(("frame", padding)) => Part::Frame {
+                    width: padding.parse().map_err(|_| TemplateError::InvalidPadding(placeholder.to_string()))?,
+                },
+                _ if placeholder.contains('{') => return Err(TemplateError::UnbalancedBrace),
+                _ => return Err(TemplateError::UnknownPlaceholder(placeholder.to_string())),
+            });
+            rest = &rest[end + 1..];
+        }
+        let template = NameTemplate { parts };
+        // Placeholders never add separators or "..", so the literal parts decide where paths can point
+        let sample = template.render("scene", 0);
+        if sample.as_os_str().is_empty() || !sample.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(TemplateError::OutsideOutput);
+        }
+        Ok(template)
+    }
+
+    /// Path of `frame_index`, relative to the output directory. Path separators in `scene` are replaced
+    /// with '_', so every scene name stays a single path component.
+    pub fn render(&self, scene: &str, frame_index: u32) -> PathBuf {
+        let scene = match scene.replace(['/', '\\'], "_") {
+            scene if scene.is_empty() || scene == "." || scene == ".." => "_".to_string(),
+            scene => scene,
+        };
+        let mut path = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => path.push_str(text),
+                Part::Scene => path.push_str(&scene),
+                Part::Frame { width } => path.push_str(&format!("{:0width$}", frame_index, width = *width)),
+            }
+        }
+        PathBuf::from(path)
+    }
+}
+
+/// A file written by an export
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub frame: u32,
+    /// Relative to the output directory
+    pub path: PathBuf,
+    pub size: u64,
+    pub crc32: u32,
+}
+
+/// An output directory frames are exported into, see the module comment
+#[derive(Debug)]
+pub struct ExportLayout {
+    root: PathBuf,
+    template: NameTemplate,
+    scene: String,
+    /// Latest manifest entry of each frame
+    entries: HashMap<u32, ManifestEntry>,
+    /// Frame each recorded path belongs to
+    claimed: HashMap<PathBuf, u32>,
+    manifest: File,
+}
+
+impl ExportLayout {
+    /// Open the output directory `root`, creating it if needed, and read the manifest of earlier runs
+    pub fn open(root: impl Into<PathBuf>, template: NameTemplate, scene: &str) -> io::Result<Self> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+        let manifest_path = root.join(MANIFEST_FILE);
+        let text = match fs::read_to_string(&manifest_path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        // A line cut off by an interrupted run does not parse; its frame is simply written again
+        let entries: HashMap<u32, ManifestEntry> = text.lines()
+            .filter_map(|line| serde_json::from_str::<ManifestEntry>(line).ok())
+            .map(|entry| (entry.frame, entry))
+            .collect();
+        let mut manifest = OpenOptions::new().create(true).append(true).open(&manifest_path)?;
+        if !text.is_empty() && !text.ends_with('\n') {
+            manifest.write_all(b"\n")?;
+        }
+        let claimed = entries.values().map(|entry| (entry.path.clone(), entry.frame)).collect();
+        Ok(Self { root, template, scene: scene.to_string(), entries, claimed, manifest })
+    }
+
+    /// The output directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether an earlier run wrote `frame_index` and its file is intact: present with the recorded size and CRC-32
+    pub fn is_done(&self, frame_index: u32) -> bool {
+        let Some(entry) = self.entries.get(&frame_index) else {
+            return false;
+        };
+        let path = self.root.join(&entry.path);
+        fs::metadata(&path).is_ok_and(|meta| meta.len() == entry.size)
+            && fs::read(&path).is_ok_and(|bytes| crc32fast::hash(&bytes) == entry.crc32)
+    }
+
+    /// Path `frame_index` is written to, relative to the output directory: the recorded one if the frame
+    /// was written before, else the template's, suffixed when another frame or a foreign file has it
+    pub fn path_for(&self, frame_index: u32) -> PathBuf {
+        if let Some(entry) = self.entries.get(&frame_index) {
+            return entry.path.clone();
+        }
+        let path = self.template.render(&self.scene, frame_index);
+        let free = |candidate: &Path| !self.claimed.contains_key(candidate) && !self.root.join(candidate).exists();
+        if free(&path) {
+            return path;
+        }
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+        (1..)
+            .map(|n| path.with_file_name(format!("{}-{}{}", stem, n, extension)))
+            .find(|candidate| free(candidate))
+            .expect("some suffix is free")
+    }
+
+    /// Write `bytes` as the file of `frame_index`, replacing a damaged copy from an earlier run, and record it
+    /// in the manifest. Returns the full path.
+    pub fn write(&mut self, frame_index: u32, bytes: &[u8]) -> io::Result<PathBuf> {
+        let relative = self.path_for(frame_index);
+        let path = self.root.join(&relative);
+        let dir = path.parent().unwrap_or(&self.root).to_path_buf();
+        fs::create_dir_all(&dir)?;
+        // Renamed into place after the manifest line is written: the file is never seen half written,
+        // and every file an export leaves behind is in the manifest
+        let mut file = tempfile::NamedTempFile::new_in(&dir)?;
+        file.write_all(bytes)?;
+        let entry = ManifestEntry { frame: frame_index, path: relative.clone(), size: bytes.len() as u64, crc32: crc32fast::hash(bytes) };
+        let mut line = serde_json::to_string(&entry).map_err(io::Error::other)?;
+        line.push('\n');
+        self.manifest.write_all(line.as_bytes())?;
+        file.persist(&path).map_err(|e| e.error)?;
+        self.claimed.insert(relative, frame_index);
+        self.entries.insert(frame_index, entry);
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template() {
+        let template = NameTemplate::parse("{scene}/{frame:06}.png").unwrap();
+        assert_eq!(template.render("intro", 42), PathBuf::from("intro/000042.png"));
+        assert_eq!(template.render("../up", 1234567), PathBuf::from(".._up/1234567.png"));
+        assert_eq!(template.render("..", 1), PathBuf::from("_/000001.png"));
+        assert_eq!(NameTemplate::parse("f{frame}").unwrap().render("s", 7), PathBuf::from("f7"));
+
+        assert_eq!(NameTemplate::parse("{frame.png"), Err(TemplateError::UnbalancedBrace));
+        assert_eq!(NameTemplate::parse("frame}.png"), Err(TemplateError::UnbalancedBrace));
+        assert_eq!(NameTemplate::parse("{shot}.png"), Err(TemplateError::UnknownPlaceholder("shot".to_string())));
+        assert_eq!(NameTemplate::parse("{frame:x}.png"), Err(TemplateError::InvalidPadding("frame:x".to_string())));
+        assert_eq!(NameTemplate::parse("../{frame}.png"), Err(TemplateError::OutsideOutput));
+        assert_eq!(NameTemplate::parse("/tmp/{frame}.png"), Err(TemplateError::OutsideOutput));
+        assert_eq!(NameTemplate::parse(""), Err(TemplateError::OutsideOutput));
+    }
+
+    #[test]
+    fn test_resume_and_collisions() {
+        let dir = tempfile::tempdir().unwrap();
+        let template = NameTemplate::parse("{scene}/{frame:03}.png").unwrap();
+        let mut layout = ExportLayout::open(dir.path(), template.clone(), "intro").unwrap();
+        // A file the export did not write keeps its name
+        std::fs::create_dir_all(dir.path().join("intro")).unwrap();
+        std::fs::write(dir.path().join("intro/002.png"), b"foreign").unwrap();
+
+        for frame in 0..3 {
+            layout.write(frame, &[frame as u8; 8]).unwrap();
+        }
+        assert_eq!(layout.path_for(2), PathBuf::from("intro/002-1.png"));
+        assert_eq!(std::fs::read(dir.path().join("intro/002.png")).unwrap(), b"foreign");
+        assert!((0..3).all(|frame| layout.is_done(frame)));
+        assert!(!layout.is_done(3));
+
+        // Reopened after an interruption: damaged and missing files are redone at their recorded paths
+        std::fs::write(dir.path().join("intro/001.png"), [9u8; 8]).unwrap();
+        std::fs::remove_file(dir.path().join("intro/002-1.png")).unwrap();
+        // ... and a manifest line cut off mid-write is ignored
+        let manifest = dir.path().join(MANIFEST_FILE);
+        let mut text = std::fs::read_to_string(&manifest).unwrap();
+        text.push_str("{\"frame\":3,\"pa");
+        std::fs::write(&manifest, text).unwrap();
+        let mut layout = ExportLayout::open(dir.path(), template, "intro").unwrap();
+        assert_eq!((layout.is_done(0), layout.is_done(1), layout.is_done(2), layout.is_done(3)), (true, false, false, false));
+        assert_eq!(layout.path_for(2), PathBuf::from("intro/002-1.png"));
+        layout.write(2, &[2; 8]).unwrap();
+        layout.write(3, &[3; 8]).unwrap();
+        let layout = ExportLayout::open(dir.path(), NameTemplate::parse("{scene}/{frame:03}.png").unwrap(), "intro").unwrap();
+        assert!(layout.is_done(2) && layout.is_done(3));
+
+        // Without a frame placeholder every frame collides, each gets its own suffix
+        let flat = tempfile::tempdir().unwrap();
+        let mut layout = ExportLayout::open(flat.path(), NameTemplate::parse("mask.png").unwrap(), "intro").unwrap();
+        let paths: Vec<PathBuf> = (0..3).map(|frame| layout.write(frame, &[0]).unwrap()).collect();
+        assert_eq!(paths, ["mask.png", "mask-1.png", "mask-2.png"].map(|name| flat.path().join(name)));
+    }
+}
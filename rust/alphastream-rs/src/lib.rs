@@ -3,12 +3,22 @@
 //! This module provides a C-compatible interface that can be called from other languages like C# via P/Invoke.
 //! All functions are marked with #[no_mangle] and extern "C" to prevent name mangling and ensure C calling convention.
 //!
+//! Rust consumers should use [`prelude`], the stable API; the scheduler, cache and rasterizer modules are only
+//! public with the `unstable` feature and carry no semver guarantees.
+//!
 //! # FFI Buffer Ownership Rules
 //!
 //! - All pointers returned by FFI functions (e.g., frame buffers, vertex arrays) are owned by the library and must not be freed by the caller.
 //! - Each call to `CV_get_frame` or `CV_get_triangle_strip_vertices` invalidates the previous buffer pointer for that handle.
 //! - The buffer remains valid until the next call to the same function or until `CV_destroy` is called.
+//! - `CV_get_frame_output` shares both buffers: it invalidates the previous bitmap and/or vertex pointer it replaces.
+//! - The frames of `CV_get_frames` stay valid until its next call or `CV_destroy`, independent of `CV_get_frame`.
+//! - Likewise the mesh arrays of `CV_get_triangles`, until its next call or `CV_destroy`.
 //! - Do not retain or free returned pointers after the handle is destroyed.
+//! - Exception: `CV_take_frame`, `CV_take_triangle_strip_vertices` and `CV_take_frame_output` return a new buffer
+//!   per call, owned by the caller. Such buffers outlive later calls and the handle, can be passed to and freed
+//!   on any thread (e.g. consumers uploading frames from worker threads), and must be released with
+//!   `CV_free_buffer(ptr, len)`, `len` in bytes (never with the C runtime's `free`).
 //!
 //! # Safety, Concurrency, and FFI Usage
 //!
@@ -20,6 +30,8 @@
 //! - Use `CV_get_last_error_code` and `CV_get_last_error_text` to retrieve error details after any call.
 //!
 //! - Always call `CV_create` to obtain a handle, and `CV_destroy` to free it.
+//! - `CV_destroy` lets the handle's threads wind down in the background; hosts that unload the library
+//!   right after call `CV_shutdown` first, which returns once they have exited.
 //! - Do not access the internals of the handle struct from C code; treat it as opaque.
 //!
 //! - All pointers returned by FFI functions (e.g., frame buffers, vertex arrays) are owned by the library and must not be freed by the caller.
@@ -28,19 +40,78 @@
 //! - Do not retain or free returned pointers after the handle is destroyed.
 //!
 //! For C ABI consumers: always check error codes after each call, and never free or retain returned pointers beyond the handle's lifetime.
+//!
+//! # Vendor plugin compatibility
+//!
+//! `CV_create`, `CV_destroy`, `CV_init`, `CV_get_frame`, `CV_get_frame_size`, `CV_get_total_frames`,
+//! `CV_get_name`, `CV_get_version`, `CV_get_last_error_code` and `CV_get_last_error_text` carry the names
+//! and parameters of the original closed-source plugin's exports (see docs/REVERSE_ENGINEERING.md), so
+//! applications built against it can load this library instead. The original declares the frame index of
+//! `CV_get_frame` as C `unsigned long`, which is 32 bits on Windows; build with the `vendor-abi` feature to
+//! match it there. Other exports of the original are not known and have no counterpart.
+//!
+//! Debug builds (and release builds with the `leak-tracking` feature) count live handles and buffers;
+//! `CV_debug_dump_leaks` reports them to find handles that were never passed to `CV_destroy`.
+//! The `ffi-audit` feature also remembers which handles are live: calls with a destroyed or unknown
+//! handle (including a second `CV_destroy`) are rejected like null handles and logged as warnings.
+//! tests/ffi_audit.rs drives every export through such sequences under AddressSanitizer in CI.
+//! Hosts that cannot trust their pointer marshalling can use integer handles instead, in every build:
+//! `CV_create_id` returns a `u64` id and each export taking a handle has a `_id` counterpart taking the
+//! id. Ids are looked up in a registry before use, so a stale id fails like a null handle and a second
+//! `CV_destroy_id` returns false.
+//!
+//! The `mimalloc` or `jemalloc` feature serves the library's allocations from that allocator instead of
+//! the system one, which fragments less in players that run for hours; the `arena` feature rasterizes
+//! from a bump arena that is reset per frame.
+//!
+//! # Callbacks
+//!
+//! - Callbacks are never invoked from library threads. Events are queued on the handle and delivered by
+//!   `CV_run_callbacks_on_thread`, on the thread that calls it (e.g. once per frame from Unity's main thread).
+//! - Register with `CV_set_event_callback` and/or `CV_set_frame_ready_callback`; without a callback
+//!   nothing is queued.
+//! - The exception is the process-wide log callback of `CV_set_log_callback`, called right away on the
+//!   thread that logs, so diagnostics reach the host's logger even when nobody runs the callbacks.
 
-use std::ffi::{c_char, c_int, c_uint, c_ulonglong, c_void, CStr, CString};
+use std::ffi::{c_char, c_int, c_longlong, c_uint, c_ulonglong, c_void, CStr};
 use std::ptr;
 
-pub mod transport;
-pub mod formats;
-pub mod runtime;
-pub mod scheduler;
-pub mod rasterizer;
-pub mod cache;
-pub mod api;
+// Internals: public with the `unstable` feature only, for the demo, benchmarks and experiments, without
+// semver guarantees. The stable Rust API is `prelude`, which re-exports the types of theirs it needs.
+macro_rules! internal_modules {
+    ($($name:ident),* $(,)?) => {$(
+        #[cfg(feature = "unstable")]
+        pub mod $name;
+        #[cfg(not(feature = "unstable"))]
+        mod $name;
+    )*};
+}
+
+internal_modules!(
+    transport, formats, container, delta, runtime, scheduler, rasterizer, backend, cache, api, png, stats, filter,
+    access, clock, logging, entitlement, store, watermark, fingerprint, telemetry, overlay, layout, bake,
+);
+#[cfg(all(feature = "scripting", feature = "unstable"))]
+pub mod script;
+#[cfg(all(feature = "scripting", not(feature = "unstable")))]
+mod script;
+mod profiling;
+pub mod prelude;
+#[cfg(any(test, feature = "unstable"))]
 pub mod testlib;
 
+// Global allocator of the library (and of every binary linking it), chosen with a cargo feature
+#[cfg(all(feature = "mimalloc", feature = "jemalloc"))]
+compile_error!("features `mimalloc` and `jemalloc` select a global allocator; enable at most one");
+
+#[cfg(feature = "mimalloc")]
+#[global_allocator]
+static GLOBAL_ALLOCATOR: mimalloc::MiMalloc = mimalloc::MiMalloc;
+
+#[cfg(all(feature = "jemalloc", not(feature = "mimalloc")))]
+#[global_allocator]
+static GLOBAL_ALLOCATOR: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
+
 /// Handle structure for C API
 /// This struct is passed between C functions to maintain state.
 /// It's opaque to C code - C only sees a pointer to it.
@@ -52,8 +123,235 @@ pub struct AlphaStreamCHandle {
     pub runtime: Option<tokio::runtime::Runtime>,
     pub last_frame_ptr: *mut [u8],
     pub last_vertices_ptr: *mut [f32],
+    /// Frames handed out by the last CV_get_frames call
+    pub last_batch: Vec<Box<[u8]>>,
+    /// Mesh handed out by the last CV_get_triangles call
+    pub last_triangles: Option<Box<rasterizer::TriangleMesh>>,
     pub last_error_code: i32,
     pub last_error_text: [u8; 256],
+    pub event_callback: Option<CVEventCallback>,
+    pub event_user_data: *mut c_void,
+    pub frame_ready_callback: Option<CVFrameReadyCallback>,
+    pub frame_ready_user_data: *mut c_void,
+    /// Rasterizer backend CV_init builds the processor with
+    pub backend: backend::Backend,
+    /// Threads of `runtime` still running, see CV_shutdown
+    pub runtime_threads: runtime::LiveThreads,
+}
+
+/// Frame index parameter of `CV_get_frame`: 64-bit, or with the `vendor-abi` feature the C `unsigned long`
+/// of the original plugin's prototype (32-bit on Windows)
+#[cfg(not(feature = "vendor-abi"))]
+pub type CVFrameIndex = c_ulonglong;
+#[cfg(feature = "vendor-abi")]
+pub type CVFrameIndex = std::ffi::c_ulong;
+
+/// Event callback delivered by `CV_run_callbacks_on_thread`.
+/// Arguments: the user_data given to `CV_set_event_callback`, the event code (`CV_EVENT_*`) and a value:
+/// the frame index for frame events, the number of evicted frames for `CV_EVENT_SOURCE_RELOADED`.
+pub type CVEventCallback = extern "C" fn(user_data: *mut c_void, event: c_int, value: c_ulonglong);
+
+/// Frame callback delivered by `CV_run_callbacks_on_thread` for every frame that finished decoding.
+/// Arguments: the frame index, the frame as `CV_get_frame` returns it (`len` bytes, only valid during the
+/// call; copy it to keep it) and the user_data given to `CV_set_frame_ready_callback`.
+pub type CVFrameReadyCallback = extern "C" fn(frame_index: c_ulonglong, data: *const u8, len: usize, user_data: *mut c_void);
+
+/// Log callback registered with `CV_set_log_callback`. Unlike the other callbacks it is called directly on
+/// the thread that logs, often a decode worker, and possibly from several threads at once.
+/// Arguments: the user_data given to `CV_set_log_callback`, the level (`CV_LOG_*`) and the message as a
+/// NUL-terminated UTF-8 string, only valid during the call.
+pub type CVLogCallback = extern "C" fn(user_data: *mut c_void, level: c_int, message: *const c_char);
+
+/// Log levels for `CV_set_log_callback`, from silent to most verbose
+pub const CV_LOG_OFF: c_int = 0;
+pub const CV_LOG_ERROR: c_int = 1;
+pub const CV_LOG_WARN: c_int = 2;
+pub const CV_LOG_INFO: c_int = 3;
+pub const CV_LOG_DEBUG: c_int = 4;
+
+/// All outputs of one frame, filled by `CV_get_frame_output` and `CV_take_frame_output`
+/// From `CV_get_frame_output` the bitmap and vertex pointers share the buffers of `CV_get_frame` and
+/// `CV_get_triangle_strip_vertices` and follow the same ownership rules; from `CV_take_frame_output` they
+/// are owned by the caller.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CVFrameOutput {
+    /// R8 mask of width*height bytes, null if the processing mode produces no bitmaps
+    pub bitmap: *const u8,
+    pub bitmap_size: usize,
+    /// Triangle strip as x,y float pairs, null if the processing mode produces no vertices
+    pub vertices: *const f32,
+    /// Number of floats in `vertices`
+    pub vertex_count: usize,
+    /// Whether the mask statistics below are set (they are computed from the bitmap)
+    pub has_stats: bool,
+    pub area: c_ulonglong,
+    pub coverage: f64,
+    pub bbox_x: c_uint,
+    pub bbox_y: c_uint,
+    pub bbox_w: c_uint,
+    pub bbox_h: c_uint,
+    /// Decode and processing time in microseconds, 0 if unknown
+    pub decode_us: c_ulonglong,
+    pub process_us: c_ulonglong,
+    /// Whether the frame has no outline data (see `CV_set_empty_frame_policy`)
+    pub is_empty: bool,
+}
+
+impl Default for CVFrameOutput {
+    fn default() -> Self {
+        Self {
+            bitmap: ptr::null(),
+            bitmap_size: 0,
+            vertices: ptr::null(),
+            vertex_count: 0,
+            has_stats: false,
+            area: 0,
+            coverage: 0.0,
+            bbox_x: 0,
+            bbox_y: 0,
+            bbox_w: 0,
+            bbox_h: 0,
+            decode_us: 0,
+            process_us: 0,
+            is_empty: false,
+        }
+    }
+}
+
+/// Sizes of the arrays `CV_get_triangles` returns
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CVTriangleCounts {
+    /// Distinct vertices: `positions` and `uvs` hold two floats per vertex
+    pub vertex_count: usize,
+    /// Entries of `indices`, three per triangle
+    pub index_count: usize,
+}
+
+/// Everything about an initialized source, filled by `CV_get_metadata`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CVMetadata {
+    pub frame_count: c_uint,
+    /// Output size given to CV_init
+    pub width: c_uint,
+    pub height: c_uint,
+    /// Bytes of a frame returned by CV_get_frame, as CV_get_frame_size
+    pub frame_size: c_uint,
+    /// Channels of the first frame; 0 if it could not be read
+    pub channel_count: c_uint,
+    /// Frames per second of the timebase: frame n is shown at n / fps seconds
+    pub fps: f64,
+    /// Size of the source in bytes (of the mask track for containers)
+    pub file_size: c_ulonglong,
+}
+
+/// Cache and scheduler counters, filled by `CV_get_stats`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CVStats {
+    /// Getter calls that found their frame decoded, since CV_init
+    pub cache_hits: c_ulonglong,
+    /// Getter calls that had to schedule their frame
+    pub cache_misses: c_ulonglong,
+    /// Cache slots holding a decoded frame, and of frames being decoded
+    pub ready_frames: c_uint,
+    pub in_progress_frames: c_uint,
+    /// Frames waiting to be decoded, and decode tasks running
+    pub queued_tasks: c_uint,
+    pub active_tasks: c_uint,
+    /// Mean decode plus processing time of the cached frames in milliseconds, 0 before the first
+    pub average_decode_ms: f64,
+    /// Decoded frames discarded because a seek had made them stale
+    pub dropped_frames: c_ulonglong,
+}
+
+/// Pixel formats for `CV_set_output_packing` and `CV_get_frame_fmt`: one byte per pixel
+pub const CV_PIXEL_FORMAT_R8: c_int = 0;
+/// 16-bit unorm per pixel
+pub const CV_PIXEL_FORMAT_R16: c_int = 1;
+/// 16 bits per pixel, luminance 255 in the low byte and the mask as alpha in the high byte
+pub const CV_PIXEL_FORMAT_LA8: c_int = 2;
+/// One byte per pixel like R8, for alpha-only textures (Unity's TextureFormat.Alpha8)
+pub const CV_PIXEL_FORMAT_A8: c_int = 3;
+/// 32 bits per pixel, R,G,B = 255 and the mask as alpha (TextureFormat.RGBA32)
+pub const CV_PIXEL_FORMAT_RGBA8: c_int = 4;
+/// 32 bits per pixel in B,G,R,A order, white with the mask as alpha (DXGI_FORMAT_B8G8R8A8_UNORM)
+pub const CV_PIXEL_FORMAT_BGRA8: c_int = 5;
+
+/// The pixel format of a CV_PIXEL_FORMAT_* constant
+fn pixel_format(pixel_format: c_int) -> Option<rasterizer::PixelFormat> {
+    match pixel_format {
+        CV_PIXEL_FORMAT_R8 => Some(rasterizer::PixelFormat::R8),
+        CV_PIXEL_FORMAT_R16 => Some(rasterizer::PixelFormat::R16),
+        CV_PIXEL_FORMAT_LA8 => Some(rasterizer::PixelFormat::La8),
+        CV_PIXEL_FORMAT_A8 => Some(rasterizer::PixelFormat::A8),
+        CV_PIXEL_FORMAT_RGBA8 => Some(rasterizer::PixelFormat::Rgba8),
+        CV_PIXEL_FORMAT_BGRA8 => Some(rasterizer::PixelFormat::Bgra8),
+        _ => None,
+    }
+}
+
+/// Policies for `CV_set_empty_frame_policy`: hand out empty masks and vertex lists
+pub const CV_EMPTY_FRAME_RETURN_EMPTY: c_int = 0;
+/// Hand out the last non-empty frame instead
+pub const CV_EMPTY_FRAME_REUSE_LAST: c_int = 1;
+/// Hand out nothing and fail with error 9
+pub const CV_EMPTY_FRAME_ERROR: c_int = 2;
+
+/// Modes for `CV_set_processing_mode`: masks only
+pub const CV_PROCESSING_MODE_BITMAP: c_int = 0;
+/// Triangle strip vertices only
+pub const CV_PROCESSING_MODE_TRIANGLE_STRIP: c_int = 1;
+/// Masks and triangle strip vertices, what CV_init starts with
+pub const CV_PROCESSING_MODE_BOTH: c_int = 2;
+
+/// Results of `CV_get_frame_wait`: the frame is ready
+pub const CV_WAIT_READY: c_int = 0;
+/// The timeout expired first (error 3); the frame stays scheduled
+pub const CV_WAIT_TIMEOUT: c_int = 1;
+/// No frame will come without a change, see the error code: decoding failed (10), playback was
+/// refused (6), the frame is empty and withheld (9) or the processing mode produces no bitmaps (3)
+pub const CV_WAIT_FAILED: c_int = 2;
+
+/// Scheduling lanes for `CV_prefetch_range`: background work, alongside the automatic prefetch
+pub const CV_PRIORITY_LOW: c_int = 0;
+/// Ahead of the automatic prefetch, e.g. the frames after an upcoming camera cut
+pub const CV_PRIORITY_NORMAL: c_int = 1;
+/// Alongside frames asked for with CV_get_frame and the other getters
+pub const CV_PRIORITY_INTERACTIVE: c_int = 2;
+
+/// Backend ids for `CV_select_backend`: the CPU rasterizer, available everywhere
+pub const CV_BACKEND_CPU: c_int = 0;
+
+/// A rasterizer backend reported by `CV_list_backends`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CVBackendInfo {
+    /// Id to pass to `CV_select_backend`
+    pub id: c_int,
+    /// Static NUL-terminated name, e.g. "cpu"
+    pub name: *const c_char,
+    /// Largest output width or height
+    pub max_texture_size: c_uint,
+    /// Whether masks are read back from the device without stalling a worker
+    pub async_readback: bool,
+}
+
+/// A frame finished decoding and can be fetched without waiting
+pub const CV_EVENT_FRAME_READY: c_int = 1;
+/// Decoding a frame failed
+pub const CV_EVENT_DECODE_ERROR: c_int = 2;
+/// The source file changed on disk and was reloaded
+pub const CV_EVENT_SOURCE_RELOADED: c_int = 3;
+/// The entitlement check refused further playback; decrypted frames were dropped
+pub const CV_EVENT_ENTITLEMENT_DENIED: c_int = 4;
+
+impl Default for AlphaStreamCHandle {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AlphaStreamCHandle {
@@ -61,10 +359,18 @@ impl AlphaStreamCHandle {
         Self {
             processor: None,
             runtime: None,
-            last_frame_ptr: unsafe { std::mem::transmute((std::ptr::null_mut::<u8>(), 0)) },
-            last_vertices_ptr: unsafe { std::mem::transmute((std::ptr::null_mut::<f32>(), 0)) },
+            last_frame_ptr: ptr::slice_from_raw_parts_mut(ptr::null_mut::<u8>(), 0),
+            last_vertices_ptr: ptr::slice_from_raw_parts_mut(ptr::null_mut::<f32>(), 0),
+            last_batch: Vec::new(),
+            last_triangles: None,
             last_error_code: 0,
             last_error_text: [0; 256],
+            event_callback: None,
+            event_user_data: ptr::null_mut(),
+            frame_ready_callback: None,
+            frame_ready_user_data: ptr::null_mut(),
+            backend: backend::Backend::Cpu,
+            runtime_threads: runtime::LiveThreads::default(),
         }
     }
     pub fn set_error(&mut self, code: i32, msg: &str) {
@@ -74,87 +380,297 @@ impl AlphaStreamCHandle {
         self.last_error_text[..len].copy_from_slice(&bytes[..len]);
         self.last_error_text[len] = 0;
     }
+    /// Whether a callback is registered, so the processor has to queue events
+    fn wants_events(&self) -> bool {
+        self.event_callback.is_some() || self.frame_ready_callback.is_some()
+    }
     pub fn clear_error(&mut self) {
         self.last_error_code = 0;
         self.last_error_text[0] = 0;
     }
+    /// Hand a bitmap to C, freeing the previous one
+    fn store_frame_buffer(&mut self, bitmap: Vec<u8>) -> *mut [u8] {
+        self.free_frame_buffer();
+        track_allocation(Allocation::FrameBuffer, 1);
+        self.last_frame_ptr = Box::into_raw(bitmap.into_boxed_slice());
+        self.last_frame_ptr
+    }
+    /// Hand a vertex array to C, freeing the previous one
+    fn store_vertices_buffer(&mut self, vertices: Vec<f32>) -> *mut [f32] {
+        self.free_vertices_buffer();
+        track_allocation(Allocation::VertexBuffer, 1);
+        self.last_vertices_ptr = Box::into_raw(vertices.into_boxed_slice());
+        self.last_vertices_ptr
+    }
+    fn free_frame_buffer(&mut self) {
+        if !self.last_frame_ptr.is_null() {
+            unsafe { drop(Box::from_raw(self.last_frame_ptr)) };
+            track_allocation(Allocation::FrameBuffer, -1);
+            self.last_frame_ptr = ptr::slice_from_raw_parts_mut(ptr::null_mut::<u8>(), 0);
+        }
+    }
+    fn free_batch_buffers(&mut self) {
+        track_allocation(Allocation::FrameBuffer, -(self.last_batch.len() as i64));
+        self.last_batch.clear();
+    }
+    /// Hand a mesh to C, freeing the previous one
+    fn store_triangles(&mut self, mesh: rasterizer::TriangleMesh) -> &rasterizer::TriangleMesh {
+        self.free_triangles();
+        track_allocation(Allocation::VertexBuffer, 1);
+        self.last_triangles.insert(Box::new(mesh))
+    }
+    fn free_triangles(&mut self) {
+        if self.last_triangles.take().is_some() {
+            track_allocation(Allocation::VertexBuffer, -1);
+        }
+    }
+    fn free_vertices_buffer(&mut self) {
+        if !self.last_vertices_ptr.is_null() {
+            unsafe { drop(Box::from_raw(self.last_vertices_ptr)) };
+            track_allocation(Allocation::VertexBuffer, -1);
+            self.last_vertices_ptr = ptr::slice_from_raw_parts_mut(ptr::null_mut::<f32>(), 0);
+        }
+    }
+}
+
+/// Kinds of memory handed out over the C ABI, counted for CV_debug_dump_leaks
+#[derive(Debug, Clone, Copy)]
+enum Allocation {
+    Handle = 0,
+    FrameBuffer = 1,
+    VertexBuffer = 2,
+    /// Buffers owned by the caller after CV_take_frame, CV_take_triangle_strip_vertices and CV_take_frame_output
+    TakenBuffer = 3,
+}
+
+/// Live allocations by kind. Only tracked in debug builds and with the `leak-tracking` feature.
+#[cfg(any(debug_assertions, feature = "leak-tracking"))]
+static LIVE_ALLOCATIONS: [std::sync::atomic::AtomicI64; 4] = [const { std::sync::atomic::AtomicI64::new(0) }; 4];
+
+#[cfg_attr(not(any(debug_assertions, feature = "leak-tracking")), allow(unused_variables))]
+fn track_allocation(kind: Allocation, delta: i64) {
+    #[cfg(any(debug_assertions, feature = "leak-tracking"))]
+    LIVE_ALLOCATIONS[kind as usize].fetch_add(delta, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Alignment of buffers owned by the caller, enough for every element type handed out (bytes, 16-bit
+/// samples, floats)
+const CALLER_BUFFER_ALIGN: usize = 16;
+
+/// Layout of a caller-owned buffer of `len` bytes. All share the alignment, so `CV_free_buffer` needs only
+/// the length whatever the element type; empty buffers take one byte, as allocations cannot be empty.
+fn caller_buffer_layout(len: usize) -> std::alloc::Layout {
+    std::alloc::Layout::from_size_align(len.max(1), CALLER_BUFFER_ALIGN).expect("buffer size overflows")
+}
+
+/// Copy `data` into a new buffer owned by the caller, released with `CV_free_buffer`.
+/// Returns the buffer and its length in bytes.
+fn give_to_caller<T: Copy>(data: &[T]) -> (*mut T, usize) {
+    let len = std::mem::size_of_val(data);
+    let layout = caller_buffer_layout(len);
+    unsafe {
+        let buffer = std::alloc::alloc(layout);
+        if buffer.is_null() {
+            std::alloc::handle_alloc_error(layout);
+        }
+        ptr::copy_nonoverlapping(data.as_ptr() as *const u8, buffer, len);
+        track_allocation(Allocation::TakenBuffer, 1);
+        (buffer as *mut T, len)
+    }
+}
+
+/// Addresses of the handles returned by CV_create and not destroyed yet
+#[cfg(feature = "ffi-audit")]
+static LIVE_HANDLES: std::sync::Mutex<std::collections::BTreeSet<usize>> = std::sync::Mutex::new(std::collections::BTreeSet::new());
+
+/// Whether `handle` may be dereferenced: not null and, with the `ffi-audit` feature, returned by
+/// CV_create and not destroyed since
+fn is_live(handle: *const AlphaStreamCHandle) -> bool {
+    if handle.is_null() {
+        return false;
+    }
+    #[cfg(feature = "ffi-audit")]
+    if !LIVE_HANDLES.lock().unwrap_or_else(|e| e.into_inner()).contains(&(handle as usize)) {
+        logging::log(logging::LogLevel::Warn, format_args!("Rejected call with handle {:p}: destroyed or never created", handle));
+        return false;
+    }
+    true
+}
+
+#[cfg(any(debug_assertions, feature = "leak-tracking"))]
+fn live_allocations(kind: Allocation) -> i64 {
+    LIVE_ALLOCATIONS[kind as usize].load(std::sync::atomic::Ordering::Relaxed)
 }
 
 pub use api::{AlphaStreamProcessor, ProcessingMode};
+#[cfg(feature = "unstable")]
 pub use cache::{FrameCache};
 pub use formats::{FrameData};
+pub use scheduler::Priority;
+#[cfg(feature = "unstable")]
 pub use scheduler::{Scheduler, Task};
 // Static C strings for name/version
-static PLUGIN_NAME: &str = "alphastream-rs";
+static PLUGIN_NAME: &CStr = c"alphastream-rs";
 static PLUGIN_VERSION: &str = "0.1.0";
+static PLUGIN_VERSION_C: &CStr = c"0.1.0";
 
-fn static_cstr(s: &str) -> *const c_char {
-    // Leak a CString to keep pointer valid for process lifetime
-    Box::leak(CString::new(s).unwrap().into_boxed_c_str()).as_ptr()
+/// Whether a getter returned nothing because the frame is empty and the policy withholds empty frames,
+/// reported as error 9 instead of "not ready"
+fn withholds_empty_frame(proc: &api::AlphaStreamProcessor, frame_index: c_ulonglong) -> bool {
+    proc.empty_frame_policy() == api::EmptyFramePolicy::Error && proc.is_frame_empty(frame_index as usize) == Some(true)
 }
 
+
 /// Create a new AlphaStream processor handle (FFI)
 /// Call this first to get a processor for all operations.
 /// Returns a pointer to AlphaStreamProcessor, or null if allocation fails.
 /// In C#: IntPtr handle = CV_create();
 #[no_mangle]
 pub extern "C" fn CV_create() -> *mut AlphaStreamCHandle {
-    Box::into_raw(Box::new(AlphaStreamCHandle::new()))
+    track_allocation(Allocation::Handle, 1);
+    let handle = Box::into_raw(Box::new(AlphaStreamCHandle::new()));
+    #[cfg(feature = "ffi-audit")]
+    LIVE_HANDLES.lock().unwrap_or_else(|e| e.into_inner()).insert(handle as usize);
+    handle
 }
 
 /// Destroy an AlphaStream processor and free its memory
-/// Always call this when done to prevent memory leaks.
+/// Always call this when done to prevent memory leaks. Must not be called from a callback of the handle.
 /// In C#: CV_destroy(handle);
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn CV_destroy(handle: *mut AlphaStreamCHandle) {
+    #[cfg(feature = "ffi-audit")]
+    if !handle.is_null() && !LIVE_HANDLES.lock().unwrap_or_else(|e| e.into_inner()).remove(&(handle as usize)) {
+        logging::log(logging::LogLevel::Warn, format_args!("CV_destroy ignored for handle {:p}: destroyed before or never created", handle));
+        return;
+    }
     if !handle.is_null() {
         unsafe {
             let chandle = &mut *handle;
-            if !chandle.last_frame_ptr.is_null() {
-                drop(Box::from_raw(chandle.last_frame_ptr));
-            }
-            if !chandle.last_vertices_ptr.is_null() {
-                drop(Box::from_raw(chandle.last_vertices_ptr));
-            }
+            chandle.free_frame_buffer();
+            chandle.free_vertices_buffer();
+            chandle.free_batch_buffers();
+            chandle.free_triangles();
             drop(Box::from_raw(handle));
+            track_allocation(Allocation::Handle, -1);
         }
     }
 }
 
 #[no_mangle]
 pub extern "C" fn CV_get_name(_handle: *mut AlphaStreamCHandle) -> *const c_char {
-    static_cstr(PLUGIN_NAME)
+    PLUGIN_NAME.as_ptr()
 }
 
 #[no_mangle]
 pub extern "C" fn CV_get_version(_handle: *mut AlphaStreamCHandle) -> *const c_char {
-    static_cstr(PLUGIN_VERSION)
+    PLUGIN_VERSION_C.as_ptr()
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn CV_get_last_error_code(handle: *mut AlphaStreamCHandle) -> c_int {
-    if handle.is_null() { return -1; }
+    if !is_live(handle) { return -1; }
     unsafe { (*handle).last_error_code }
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn CV_get_last_error_text(handle: *mut AlphaStreamCHandle) -> *const c_char {
-    if handle.is_null() {
-        return static_cstr("Invalid handle");
+    if !is_live(handle) {
+        return c"Invalid handle".as_ptr();
     }
     unsafe {
         let err = &(*handle).last_error_text;
         if err[0] == 0 {
-            static_cstr("OK")
+            c"OK".as_ptr()
         } else {
             err.as_ptr() as *const c_char
         }
     }
 }
 
+/// Fill `*out_metadata` with the frame count, output size, frame buffer size, channel count, timebase and
+/// file size of the source, e.g. to allocate buffers up front. Complete counterpart of CV_get_total_frames
+/// and CV_get_frame_size. Reads the first frame to count its channels, so it may block for a read.
+/// Returns false on error: 1 for a null `out_metadata`, 4 before CV_init, 3 if the metadata cannot be read.
+/// In C#: CV_get_metadata(handle, out CVMetadata metadata);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_get_metadata(handle: *mut AlphaStreamCHandle, out_metadata: *mut CVMetadata) -> bool {
+    if !is_live(handle) { return false; }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        if out_metadata.is_null() {
+            chandle.set_error(1, "Null out_metadata");
+            return false;
+        }
+        let (Some(proc), Some(rt)) = (&chandle.processor, &chandle.runtime) else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        let meta = match rt.block_on(proc.metadata()) {
+            Ok(meta) => meta,
+            Err(e) => {
+                chandle.set_error(3, &format!("Metadata not available: {e}"));
+                return false;
+            }
+        };
+        let channel_count = rt.block_on(proc.channel_info(0)).map_or(0, |info| info.count());
+        *out_metadata = CVMetadata {
+            frame_count: meta.frame_count as c_uint,
+            width: proc.width(),
+            height: proc.height(),
+            frame_size: proc.output_packing().packed_size(proc.width(), proc.height()) as c_uint,
+            channel_count: channel_count as c_uint,
+            fps: rt.block_on(proc.timebase_fps()),
+            file_size: meta.stream_size as c_ulonglong,
+        };
+        true
+    }
+}
+
+/// Fill `*out_stats` with cache hits and misses, cache slot occupancy, queued and running decode tasks,
+/// the average decode latency and the dropped frames, e.g. for a diagnostics overlay or to tune the
+/// cache and prefetch sizes given to CV_init. Cheap enough to call once per rendered frame.
+/// Returns false on error: 1 for a null `out_stats`, 4 before CV_init.
+/// In C#: CV_get_stats(handle, out CVStats stats);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_get_stats(handle: *mut AlphaStreamCHandle, out_stats: *mut CVStats) -> bool {
+    if !is_live(handle) { return false; }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        if out_stats.is_null() {
+            chandle.set_error(1, "Null out_stats");
+            return false;
+        }
+        let (Some(proc), Some(rt)) = (&chandle.processor, &chandle.runtime) else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        let stats = rt.block_on(proc.stats());
+        *out_stats = CVStats {
+            cache_hits: stats.cache_hits as c_ulonglong,
+            cache_misses: stats.cache_misses as c_ulonglong,
+            ready_frames: stats.ready_frames as c_uint,
+            in_progress_frames: stats.in_progress_frames as c_uint,
+            queued_tasks: stats.queued_tasks as c_uint,
+            active_tasks: stats.active_tasks as c_uint,
+            average_decode_ms: stats.average_decode.map_or(0.0, |latency| latency.as_secs_f64() * 1000.0),
+            dropped_frames: stats.dropped_frames as c_ulonglong,
+        };
+        true
+    }
+}
+
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn CV_get_total_frames(handle: *mut AlphaStreamCHandle) -> c_uint {
-    if handle.is_null() { return 0; }
+    if !is_live(handle) { return 0; }
     unsafe {
         let chandle = &mut *handle;
         if let Some(proc) = &chandle.processor {
@@ -173,12 +689,13 @@ pub extern "C" fn CV_get_total_frames(handle: *mut AlphaStreamCHandle) -> c_uint
 }
 
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn CV_get_frame_size(handle: *mut AlphaStreamCHandle) -> c_uint {
-    if handle.is_null() { return 0; }
+    if !is_live(handle) { return 0; }
     unsafe {
         let chandle = &mut *handle;
         if let Some(proc) = &chandle.processor {
-            (proc.width() * proc.height()) as c_uint
+            proc.output_packing().packed_size(proc.width(), proc.height()) as c_uint
         } else {
             0
         }
@@ -189,16 +706,22 @@ pub extern "C" fn CV_get_frame_size(handle: *mut AlphaStreamCHandle) -> c_uint {
 /// This sets up the processor for AlphaStream file or server access.
 /// Parameters:
 /// - handle: The processor from CV_create()
-/// - base_url: Server URL as C string (e.g., "https://server.com") and must be an encrypted asvr file
+/// - base_url: Server URL as C string (e.g., "https://server.com") and must be an encrypted asvr file.
+///   Its file name is part of the key; use CV_init_asvr for files that were renamed or are served elsewhere.
 /// - scene_id: Numeric ID of the scene to load
 /// - width/height: Output dimensions for rendered frames
 /// - version: Protocol version string
 /// - start_frame: Which frame to start playback from
 /// - buffer lengths: Network buffering settings
 /// - timeouts: Connection and data timeouts in milliseconds
-/// Returns true on success, false on failure (check CV_get_last_error_* for details)
+///
+/// Returns true on success, false on failure (check CV_get_last_error_* for details).
+/// Error code 6 means the entitlement provider configured for the build refused playback.
+/// Error code 8 means width x height cannot be rasterized, or its masks exceed the cache memory budget
+/// (the message suggests an l1_buffer_length that fits).
 /// In C#: bool success = CV_init(handle, urlPtr, sceneId, width, height, versionPtr, ...);
 #[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn CV_init(
     handle: *mut AlphaStreamCHandle,
     base_url: *const c_char,
@@ -213,20 +736,17 @@ pub extern "C" fn CV_init(
     init_timeout_ms: c_uint,
     _data_timeout_ms: c_uint,
 ) -> bool {
-    if handle.is_null() {
+    if !is_live(handle) {
         return false;
     }
     unsafe {
         let chandle = &mut *handle;
         chandle.clear_error();
+        if base_url.is_null() || version.is_null() {
+            chandle.set_error(1, "Null base_url or version");
+            return false;
+        }
         if let Ok(path) = CStr::from_ptr(base_url).to_str() {
-            let builder = api::AlphaStreamProcessorBuilder::new()
-                .runtime_threads(8)
-                .timeout_seconds((init_timeout_ms / 1000).max(1) as u64)
-                .cache_capacity(l1_buffer_length as usize)
-                .prefetch_window(l1_buffer_init_length as usize)
-                .processing_mode(api::ProcessingMode::Both);
-
             // extract filename only from path which can be a URL or a file path with path delimiter ('/' or '\')
             // all chars after last path delimiter ('/' or '\') and before '?' if any
             // first replace '\\' with '/' to normalize path format
@@ -235,18 +755,7 @@ pub extern "C" fn CV_init(
             let filename = filename.split_once('?').unwrap_or((filename, "")).0;
 
             if let Ok(version) = CStr::from_ptr(version).to_str() {
-                let rt = tokio::runtime::Runtime::new().unwrap();
-                return match rt.block_on(async { builder.build_asvr(path, scene_id, version.as_bytes(), filename.as_bytes(), width, height).await }) {
-                    Ok(proc) => {
-                        chandle.processor = Some(Box::new(proc));
-                        chandle.runtime = Some(rt);
-                        true
-                    }
-                    Err(e) => {
-                        chandle.set_error(2, &format!("Init error: {e}"));
-                        false
-                    }
-                }
+                return open_asvr(chandle, AsvrSource::Path(path), scene_id, version, filename, width, height, l1_buffer_length, l1_buffer_init_length, init_timeout_ms);
             } else {
                 chandle.set_error(1, "Invalid version");
             }
@@ -257,131 +766,1425 @@ pub extern "C" fn CV_init(
     false
 }
 
-/// Get a processed frame as R8 grayscale mask
-/// Requests the specified frame and returns a pointer to the pixel data.
-/// The data is width*height bytes of grayscale values (0-255).
-/// Returns null if frame is not available or error occurred.
-/// Check CV_get_last_error_code() for error details.
-/// In C#: IntPtr frameData = CV_get_frame(handle, frameIndex);
-/// Then copy the data: Marshal.Copy(frameData, buffer, 0, width * height);
+/// Initialize the processor with an encrypted ASVR file and the credentials its key is derived from
+/// Unlike CV_init, the `base_url` that went into the key is passed separately from the `path` (file path
+/// or http(s) URL) the file is read from, so files that were renamed, cached locally or served from
+/// another location can be opened. Buffer and timeout parameters and error codes are those of CV_init.
+/// In C#: bool success = CV_init_asvr(handle, pathPtr, sceneId, versionPtr, baseUrlPtr, width, height, 512, 256, 5000);
 #[no_mangle]
-pub extern "C" fn CV_get_frame(handle: *mut AlphaStreamCHandle, frame_index: c_ulonglong) -> *const c_void {
-    if handle.is_null() { return ptr::null(); }
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_init_asvr(
+    handle: *mut AlphaStreamCHandle,
+    path: *const c_char,
+    scene_id: c_uint,
+    version: *const c_char,
+    base_url: *const c_char,
+    width: c_uint,
+    height: c_uint,
+    l1_buffer_length: c_uint,
+    l1_buffer_init_length: c_uint,
+    init_timeout_ms: c_uint,
+) -> bool {
+    if !is_live(handle) {
+        return false;
+    }
     unsafe {
         let chandle = &mut *handle;
         chandle.clear_error();
-        if let Some(proc) = &chandle.processor {
-            if let Some(rt) = &chandle.runtime {
-                return match rt.block_on(async { proc.get_frame(frame_index as usize, proc.width(), proc.height()).await }) {
-                    Some(bitmap) => {
-                        if !chandle.last_frame_ptr.is_null() {
-                            drop(Box::from_raw(chandle.last_frame_ptr));
-                            chandle.last_frame_ptr = std::mem::transmute((std::ptr::null_mut::<u8>(), 0));
-                        }
-                        let boxed = bitmap.into_boxed_slice();
-                        let ptr = Box::into_raw(boxed);
-                        chandle.last_frame_ptr = ptr;
-                        ptr as *const c_void
-                    }
-                    None => {
-                        chandle.set_error(3, "Frame not found or not ready");
-                        ptr::null()
-                    }
-                }
-            } else {
-                chandle.set_error(4, "Runtime not initialized");
-                ptr::null()
-            }
-        } else {
-            chandle.set_error(4, "Processor not initialized");
-            ptr::null()
+        if path.is_null() || version.is_null() || base_url.is_null() {
+            chandle.set_error(1, "Null path, version or base_url");
+            return false;
         }
+        let (Ok(path), Ok(version), Ok(base_url)) = (CStr::from_ptr(path).to_str(), CStr::from_ptr(version).to_str(), CStr::from_ptr(base_url).to_str()) else {
+            chandle.set_error(1, "Invalid path, version or base_url");
+            return false;
+        };
+        open_asvr(chandle, AsvrSource::Path(path), scene_id, version, base_url, width, height, l1_buffer_length, l1_buffer_init_length, init_timeout_ms)
     }
 }
 
-/// Get triangle strip vertices for 3D rendering
-/// Returns vertex data for rendering the frame as 3D geometry.
-/// Parameters:
-/// - out_vertices: Pointer to receive array of float coordinates (x,y,z,x,y,z,...)
-/// - out_count: Pointer to receive number of floats in the array
-/// Returns true on success, false on error.
-/// The vertex array contains 3D positions for triangle strip rendering.
-/// In C#: float* vertices; IntPtr count; bool success = CV_get_triangle_strip_vertices(handle, frame, &vertices, &count);
+/// Initialize the processor with an encrypted ASVR file that is already in memory
+/// For hosts that embed the file or download it themselves, instead of writing a temporary file.
+/// The `len` bytes at `data` are copied, so the buffer can be freed once this returns. Other parameters
+/// and error codes are those of CV_init_asvr.
+/// In C#: fixed (byte* p = bytes) { CV_init_from_memory(handle, p, (UIntPtr)bytes.Length, sceneId, versionPtr, baseUrlPtr, width, height, 512, 256, 5000); }
 #[no_mangle]
-pub extern "C" fn CV_get_triangle_strip_vertices(handle: *mut AlphaStreamCHandle, frame_index: c_ulonglong, out_vertices: *mut *const f32, out_count: *mut usize) -> bool {
-    if handle.is_null() || out_vertices.is_null() || out_count.is_null() {
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_init_from_memory(
+    handle: *mut AlphaStreamCHandle,
+    data: *const u8,
+    len: usize,
+    scene_id: c_uint,
+    version: *const c_char,
+    base_url: *const c_char,
+    width: c_uint,
+    height: c_uint,
+    l1_buffer_length: c_uint,
+    l1_buffer_init_length: c_uint,
+    init_timeout_ms: c_uint,
+) -> bool {
+    if !is_live(handle) {
         return false;
     }
     unsafe {
         let chandle = &mut *handle;
         chandle.clear_error();
-        if let Some(proc) = &chandle.processor {
-            if let Some(rt) = &chandle.runtime {
-                return match rt.block_on(async { proc.get_triangle_strip_vertices(frame_index as usize).await }) {
-                    Some(vertices) => {
-                        if !chandle.last_vertices_ptr.is_null() {
-                            drop(Box::from_raw(chandle.last_vertices_ptr));
-                            chandle.last_vertices_ptr = std::mem::transmute((std::ptr::null_mut::<f32>(), 0));
-                        }
-                        let boxed = vertices.into_boxed_slice();
-                        let len = boxed.len();
-                        let ptr = Box::into_raw(boxed);
-                        *out_vertices = ptr as *const f32;
-                        *out_count = len;
-                        chandle.last_vertices_ptr = ptr;
-                        true
-                    }
-                    None => {
-                        *out_vertices = ptr::null();
-                        *out_count = 0;
-                        chandle.set_error(5, "Vertices not found or not ready");
-                        false
-                    }
-                }
-            } else {
-                chandle.set_error(4, "Runtime not initialized");
-                false
-            }
-        } else {
-            chandle.set_error(4, "Processor not initialized");
-            false
+        if data.is_null() || len == 0 || version.is_null() || base_url.is_null() {
+            chandle.set_error(1, "Null or empty data, null version or base_url");
+            return false;
         }
+        let (Ok(version), Ok(base_url)) = (CStr::from_ptr(version).to_str(), CStr::from_ptr(base_url).to_str()) else {
+            chandle.set_error(1, "Invalid version or base_url");
+            return false;
+        };
+        let data = std::slice::from_raw_parts(data, len);
+        open_asvr(chandle, AsvrSource::Memory(data), scene_id, version, base_url, width, height, l1_buffer_length, l1_buffer_init_length, init_timeout_ms)
     }
 }
 
-// Keep minimal Rust-native API for tests/demos
-/// Returns the crate semantic version string.
-pub fn version() -> &'static str { PLUGIN_VERSION }
-
-/// Simple echo function for demo/tests.
-pub fn echo(input: &str) -> String { input.to_string() }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::ffi::CString;
-    use crate::testlib::{create_test_asvr};
+/// Where open_asvr reads the file from
+enum AsvrSource<'a> {
+    /// File path or http(s) URL
+    Path(&'a str),
+    /// File contents, copied into the processor
+    Memory(&'a [u8]),
+}
 
-    #[test]
-    fn version_is_semver_like() { assert!(version().split('.').count() >= 3); }
+/// Open an ASVR source on the handle for CV_init, CV_init_asvr and CV_init_from_memory, setting their
+/// error codes on failure
+#[allow(clippy::too_many_arguments)]
+fn open_asvr(
+    chandle: &mut AlphaStreamCHandle,
+    source: AsvrSource<'_>,
+    scene_id: c_uint,
+    version: &str,
+    base_url: &str,
+    width: c_uint,
+    height: c_uint,
+    l1_buffer_length: c_uint,
+    l1_buffer_init_length: c_uint,
+    init_timeout_ms: c_uint,
+) -> bool {
+    // Deployment defaults (ALPHASTREAM_* variables, alphastream.toml) replace the library's own, the
+    // caller's arguments then win over both
+    let builder = match api::AlphaStreamProcessorBuilder::new().runtime_threads(8).apply_environment() {
+        Ok(builder) => builder,
+        Err(e) => {
+            chandle.set_error(1, &format!("Invalid environment defaults: {e}"));
+            return false;
+        }
+    };
+    let builder = builder
+        .timeout_seconds((init_timeout_ms / 1000).max(1) as u64)
+        .cache_capacity(l1_buffer_length as usize)
+        .prefetch_window(l1_buffer_init_length as usize)
+        .processing_mode(api::ProcessingMode::Both)
+        .backend(chandle.backend);
 
-    #[test]
-    fn echo_roundtrip() { assert_eq!(echo("alpha"), "alpha"); }
+    let mut runtime_builder = tokio::runtime::Builder::new_multi_thread();
+    runtime_builder.enable_all();
+    let runtime_threads = runtime::LiveThreads::track(&mut runtime_builder);
+    let rt = runtime_builder.build().unwrap();
+    let built = rt.block_on(async {
+        match source {
+            AsvrSource::Path(path) => builder.build_asvr(path, scene_id, version.as_bytes(), base_url.as_bytes(), width, height).await,
+            AsvrSource::Memory(data) => builder.build_asvr_from_bytes(data.to_vec(), scene_id, version.as_bytes(), base_url.as_bytes(), width, height).await,
+        }
+    });
+    match built {
+        Ok(proc) => {
+            proc.enable_events(chandle.wants_events());
+            chandle.processor = Some(Box::new(proc));
+            chandle.runtime = Some(rt);
+            chandle.runtime_threads = runtime_threads;
+            true
+        }
+        Err(e @ formats::FormatError::Entitlement(_)) => {
+            chandle.set_error(6, &format!("Init error: {e}"));
+            false
+        }
+        Err(e @ formats::FormatError::Dimensions(_)) => {
+            chandle.set_error(8, &format!("Init error: {e}"));
+            false
+        }
+        Err(e) => {
+            chandle.set_error(2, &format!("Init error: {e}"));
+            false
+        }
+    }
+}
 
-    #[test]
-    fn test_c_abi_create_destroy() {
-        let handle = CV_create();
-        assert!(!handle.is_null());
+/// List the rasterizer backends that can run on this machine, the CPU rasterizer first.
+/// Writes up to `capacity` entries to `out` and returns how many backends there are, so a first call
+/// with a null `out` sizes the array.
+/// In C#: int n = CV_list_backends(null, 0); var backends = new CVBackendInfo[n]; CV_list_backends(backends, n);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_list_backends(out: *mut CVBackendInfo, capacity: usize) -> c_int {
+    let backends = backend::available_backends();
+    if !out.is_null() {
+        for (index, info) in backends.iter().take(capacity).enumerate() {
+            let name = match info.backend {
+                backend::Backend::Cpu => c"cpu",
+            };
+            let entry = CVBackendInfo {
+                id: info.backend.id() as c_int,
+                name: name.as_ptr(),
+                max_texture_size: info.max_texture_size,
+                async_readback: info.async_readback,
+            };
+            unsafe { out.add(index).write(entry) };
+        }
+    }
+    backends.len() as c_int
+}
 
-        // Check initial state
-        assert_eq!(CV_get_last_error_code(handle), 0);
-        assert_eq!(CV_get_total_frames(handle), 0);
-        assert_eq!(CV_get_frame_size(handle), 0);
+/// Choose the rasterizer backend (an id from `CV_list_backends`) for the next `CV_init` on this handle.
+/// Error 1 if the id is unknown or the backend is not available here, or the handle is already initialized.
+/// In C#: CV_select_backend(handle, CV_BACKEND_CPU);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_select_backend(handle: *mut AlphaStreamCHandle, backend_id: c_int) -> bool {
+    if !is_live(handle) {
+        return false;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        if chandle.processor.is_some() {
+            chandle.set_error(1, "Select the backend before CV_init");
+            return false;
+        }
+        match u32::try_from(backend_id).ok().and_then(backend::Backend::from_id) {
+            Some(backend) if backend.is_available() => {
+                chandle.backend = backend;
+                true
+            }
+            _ => {
+                chandle.set_error(1, "Unknown or unavailable backend");
+                false
+            }
+        }
+    }
+}
 
-        CV_destroy(handle);
+/// Set the memory layout of the frames `CV_get_frame`, `CV_take_frame` and `CV_get_frame_output` return,
+/// for platforms that upload textures with aligned rows or big-endian 16-bit texels.
+/// `pixel_format` is one of the CV_PIXEL_FORMAT_* constants, `row_alignment` pads every row to a multiple
+/// of that many bytes (a power of two up to 256, 1 for none) and `swap_bytes` stores 16-bit pixels
+/// big-endian. `CV_get_frame_size` reports the packed size. Error 1 for an unknown pixel format.
+/// In C#: CV_set_output_packing(handle, CV_PIXEL_FORMAT_R16, 64, true);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_set_output_packing(handle: *mut AlphaStreamCHandle, pixel_format: c_int, row_alignment: c_uint, swap_bytes: bool) -> bool {
+    if !is_live(handle) {
+        return false;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        let Some(format) = self::pixel_format(pixel_format) else {
+            chandle.set_error(1, "Unknown pixel format");
+            return false;
+        };
+        let Some(proc) = &mut chandle.processor else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        proc.set_output_packing(rasterizer::OutputPacking::new(format).row_alignment(row_alignment).swap_bytes(swap_bytes));
+        true
     }
+}
 
-    #[test]
+/// Set what frames without outline data (e.g. while the tracked subject is out of the shot) return:
+/// `CV_EMPTY_FRAME_RETURN_EMPTY` (default) hands out an empty mask, `CV_EMPTY_FRAME_REUSE_LAST` the last
+/// non-empty frame, and `CV_EMPTY_FRAME_ERROR` nothing: the getters fail with error 9 instead of error 3, so
+/// "no mask this frame" can be told from "not decoded yet". Error 1 for an unknown policy.
+/// In C#: CV_set_empty_frame_policy(handle, CV_EMPTY_FRAME_REUSE_LAST);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_set_empty_frame_policy(handle: *mut AlphaStreamCHandle, policy: c_int) -> bool {
+    if !is_live(handle) {
+        return false;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        let policy = match policy {
+            CV_EMPTY_FRAME_RETURN_EMPTY => api::EmptyFramePolicy::ReturnEmpty,
+            CV_EMPTY_FRAME_REUSE_LAST => api::EmptyFramePolicy::ReuseLast,
+            CV_EMPTY_FRAME_ERROR => api::EmptyFramePolicy::Error,
+            _ => {
+                chandle.set_error(1, "Unknown empty frame policy");
+                return false;
+            }
+        };
+        let Some(proc) = &mut chandle.processor else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        proc.set_empty_frame_policy(policy);
+        true
+    }
+}
+
+/// Switch what frames are processed into, a `CV_PROCESSING_MODE_*`, e.g. to drop triangle strips while a
+/// 3D debug view is closed. Frames decoded from now on follow the new mode; cached frames lacking one of
+/// its outputs are decoded again when asked for, so the getters briefly return error 3 for them. Error 1
+/// for an unknown mode.
+/// In C#: CV_set_processing_mode(handle, CV_PROCESSING_MODE_BITMAP);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_set_processing_mode(handle: *mut AlphaStreamCHandle, mode: c_int) -> bool {
+    if !is_live(handle) {
+        return false;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        let mode = match mode {
+            CV_PROCESSING_MODE_BITMAP => api::ProcessingMode::Bitmap,
+            CV_PROCESSING_MODE_TRIANGLE_STRIP => api::ProcessingMode::TriangleStrip,
+            CV_PROCESSING_MODE_BOTH => api::ProcessingMode::Both,
+            _ => {
+                chandle.set_error(1, "Unknown processing mode");
+                return false;
+            }
+        };
+        let Some(proc) = &mut chandle.processor else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        proc.set_processing_mode(mode);
+        true
+    }
+}
+
+/// Change the output size, e.g. when the host's window is resized, without CV_destroy and CV_init and
+/// filling the buffers again. Frames handed out from now on are width x height (see CV_get_frame_size);
+/// cached masks are rasterized again when asked for, so the getters briefly return error 3 for them.
+/// Buffers already handed out keep the old size until they are replaced. Error 8 if width x height
+/// cannot be rasterized or its masks exceed the cache memory budget; the size then stays as it was.
+/// In C#: CV_set_output_size(handle, (uint)rect.width, (uint)rect.height);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_set_output_size(handle: *mut AlphaStreamCHandle, width: c_uint, height: c_uint) -> bool {
+    if !is_live(handle) {
+        return false;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        let (Some(proc), Some(rt)) = (&mut chandle.processor, &chandle.runtime) else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        match rt.block_on(proc.set_output_size(width, height)) {
+            Ok(_) => true,
+            Err(e @ formats::FormatError::Dimensions(_)) => {
+                chandle.set_error(8, &format!("Output size error: {e}"));
+                false
+            }
+            Err(e) => {
+                chandle.set_error(2, &format!("Output size error: {e}"));
+                false
+            }
+        }
+    }
+}
+
+/// Limit decoding to `budget_ms` milliseconds per 16.6 ms frame interval on average (0 for no limit), so a
+/// render thread in the same process keeps its CPU time during playback. Over budget, prefetch waits for the
+/// next intervals; frames asked for with CV_get_frame and the other getters are still decoded right away.
+/// The budget is clamped to one interval.
+/// In C#: CV_set_decode_budget(handle, 4.0f);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_set_decode_budget(handle: *mut AlphaStreamCHandle, budget_ms: f32) -> bool {
+    if !is_live(handle) {
+        return false;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        let (Some(proc), Some(rt)) = (&mut chandle.processor, &chandle.runtime) else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        rt.block_on(proc.set_decode_budget_ms(budget_ms));
+        true
+    }
+}
+
+/// Queue `count` frames from `start_frame` for decoding in the background, so a host that knows what comes
+/// next (e.g. the frames after a camera cut) can warm the cache before asking for them, on top of the
+/// automatic sequential prefetch. `priority` is a `CV_PRIORITY_*` lane; frames already queued move up to
+/// it, never down. The range is clamped to the frame count, and frames beyond the cache window are decoded
+/// once the window reaches them. Returns immediately; error 1 for an unknown priority.
+/// In C#: CV_prefetch_range(handle, cutFrame, 30, CV_PRIORITY_NORMAL);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_prefetch_range(handle: *mut AlphaStreamCHandle, start_frame: c_ulonglong, count: c_uint, priority: c_int) -> bool {
+    if !is_live(handle) {
+        return false;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        let priority = match priority {
+            CV_PRIORITY_LOW => Priority::Low,
+            CV_PRIORITY_NORMAL => Priority::Normal,
+            CV_PRIORITY_INTERACTIVE => Priority::Interactive,
+            _ => {
+                chandle.set_error(1, "Unknown priority");
+                return false;
+            }
+        };
+        let (Some(proc), Some(rt)) = (&chandle.processor, &chandle.runtime) else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        let start = start_frame.min(u32::MAX as c_ulonglong) as u32;
+        if let Err(e) = rt.block_on(proc.request_range(start..start.saturating_add(count), priority)) {
+            chandle.set_error(3, &e.to_string());
+            return false;
+        }
+        true
+    }
+}
+
+/// Drop every frame queued for decoding (prefetch included) and abort the frames being decoded; they are
+/// not cached and can be requested again. Playback carries on with the frames requested from then on,
+/// e.g. after a seek far away. Returns immediately; the aborted tasks wind down in the background.
+/// In C#: CV_cancel_all(handle);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_cancel_all(handle: *mut AlphaStreamCHandle) -> bool {
+    if !is_live(handle) {
+        return false;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        let (Some(proc), Some(rt)) = (&chandle.processor, &chandle.runtime) else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        rt.block_on(proc.cancel_all());
+        true
+    }
+}
+
+/// Stop all background work of the handle before CV_destroy: decoding is aborted, queued frames are
+/// dropped, the source is closed and the handle's threads are stopped. Returns true once they have
+/// exited, so a host can unload the library right after without racing live tasks. Threads are waited
+/// for at most 5 seconds each for the processor and the handle: if some are still running then, e.g.
+/// stuck in a blocking read, it returns false with error 11 and the library must stay loaded. Either
+/// way the handle is left uninitialized: further calls fail with error 4 until CV_init, and CV_destroy
+/// must still be called. Pointers returned earlier stay valid until CV_destroy. Returns false with
+/// error 4 if not initialized.
+/// In C#: CV_shutdown(handle); CV_destroy(handle);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_shutdown(handle: *mut AlphaStreamCHandle) -> bool {
+    if !is_live(handle) {
+        return false;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        let (Some(mut proc), Some(rt)) = (chandle.processor.take(), chandle.runtime.take()) else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        let closed = proc.close();
+        drop(proc);
+        rt.shutdown_timeout(api::SHUTDOWN_TIMEOUT);
+        if !closed || chandle.runtime_threads.count() > 0 {
+            chandle.set_error(11, "Threads still running after the shutdown timeout");
+            return false;
+        }
+        true
+    }
+}
+
+/// Process-wide settings shared by every handle, best called once before the first CV_create.
+/// `max_decode_tasks` caps the frames decoded at once across all handles (0 = no cap), so a host that opens
+/// many masks keeps CPU for itself; each handle still has its own max_concurrent limit. Calling it again
+/// changes the cap for decodes started afterwards.
+/// In C#: CV_global_init((uint)Environment.ProcessorCount / 2);
+#[no_mangle]
+pub extern "C" fn CV_global_init(max_decode_tasks: c_uint) {
+    scheduler::global_decode_limit().set(max_decode_tasks as usize);
+}
+
+/// user_data of a log callback, handed to whichever thread logs. The host vouches for it being usable there.
+#[derive(Clone, Copy)]
+struct LogUserData(*mut c_void);
+unsafe impl Send for LogUserData {}
+unsafe impl Sync for LogUserData {}
+
+/// Route the library's diagnostics of every handle to `callback` instead of stdout/stderr, which .NET
+/// and Unity hosts never show. Messages up to `min_level` (a `CV_LOG_*` constant; more verbose ones are
+/// dropped) are passed on, from any thread: the callback must be thread-safe and should be quick.
+/// Process-wide; pass a null callback to print to the console again. Returns false for an unknown level.
+/// In C#: CV_set_log_callback(Marshal.GetFunctionPointerForDelegate(onLog), CV_LOG_INFO, IntPtr.Zero);
+#[no_mangle]
+pub extern "C" fn CV_set_log_callback(callback: Option<CVLogCallback>, min_level: c_int, user_data: *mut c_void) -> bool {
+    let level = match min_level {
+        CV_LOG_OFF => logging::LogLevel::Off,
+        CV_LOG_ERROR => logging::LogLevel::Error,
+        CV_LOG_WARN => logging::LogLevel::Warn,
+        CV_LOG_INFO => logging::LogLevel::Info,
+        CV_LOG_DEBUG => logging::LogLevel::Debug,
+        _ => return false,
+    };
+    let Some(callback) = callback else {
+        logging::set_sink(None);
+        return true;
+    };
+    let user_data = LogUserData(user_data);
+    let sink: logging::LogSink = std::sync::Arc::new(move |level, message| {
+        let user_data = user_data;
+        // A NUL would end the C string early
+        let message = std::ffi::CString::new(message.replace('\0', " ")).unwrap_or_default();
+        callback(user_data.0, level as c_int, message.as_ptr());
+    });
+    logging::set_sink(Some((level, sink)));
+    true
+}
+
+/// Report the size frames are displayed at, so small displays (e.g. picture-in-picture) are rasterized at a
+/// lower resolution; frames keep `CV_get_frame_size` and are scaled up when handed out. 0x0 restores the full
+/// resolution. Returns the factor the resolution is lowered by, or -1 on error.
+/// In C#: int divisor = CV_report_display_size(handle, (uint)rect.width, (uint)rect.height);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_report_display_size(handle: *mut AlphaStreamCHandle, width: c_uint, height: c_uint) -> c_int {
+    if !is_live(handle) {
+        return -1;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        let Some(proc) = &chandle.processor else {
+            chandle.set_error(4, "Processor not initialized");
+            return -1;
+        };
+        proc.report_display_size(width, height) as c_int
+    }
+}
+
+/// Get a processed frame as R8 grayscale mask
+/// Requests the specified frame and returns a pointer to the pixel data.
+/// The data is width*height bytes of grayscale values (0-255), or CV_get_frame_size bytes in the
+/// layout set with CV_set_output_packing.
+/// Returns null if frame is not available or error occurred.
+/// Check CV_get_last_error_code() for error details: 3 if the frame is not decoded yet, 9 if it is
+/// empty and withheld (see CV_set_empty_frame_policy).
+/// In C#: IntPtr frameData = CV_get_frame(handle, frameIndex);
+/// Then copy the data: Marshal.Copy(frameData, buffer, 0, width * height);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_get_frame(handle: *mut AlphaStreamCHandle, frame_index: CVFrameIndex) -> *const c_void {
+    if !is_live(handle) { return ptr::null(); }
+    let frame_index = frame_index as c_ulonglong;
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        if let Some(proc) = &chandle.processor {
+            if let Some(rt) = &chandle.runtime {
+                match rt.block_on(async { proc.get_frame(frame_index as usize, proc.width(), proc.height()).await }) {
+                    Some(bitmap) => chandle.store_frame_buffer(bitmap) as *const c_void,
+                    None if withholds_empty_frame(proc, frame_index) => {
+                        chandle.set_error(9, "Frame is empty");
+                        ptr::null()
+                    }
+                    None => {
+                        chandle.set_error(3, "Frame not found or not ready");
+                        ptr::null()
+                    }
+                }
+            } else {
+                chandle.set_error(4, "Runtime not initialized");
+                ptr::null()
+            }
+        } else {
+            chandle.set_error(4, "Processor not initialized");
+            ptr::null()
+        }
+    }
+}
+
+/// Get a processed frame like `CV_get_frame`, in `pixel_format` (a CV_PIXEL_FORMAT_* constant) instead of
+/// the format set with `CV_set_output_packing`, whose row alignment and byte order still apply. Fills
+/// Direct3D or Unity textures directly, e.g. CV_PIXEL_FORMAT_BGRA8 for a BGRA32 texture, without
+/// swizzling every pixel on the managed side. The buffer is shared with and valid like `CV_get_frame`'s.
+/// Returns null on error: 1 for an unknown pixel format, otherwise as `CV_get_frame`.
+/// In C#: IntPtr pixels = CV_get_frame_fmt(handle, frameIndex, CV_PIXEL_FORMAT_RGBA8);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_get_frame_fmt(handle: *mut AlphaStreamCHandle, frame_index: c_ulonglong, pixel_format: c_int) -> *const c_void {
+    if !is_live(handle) {
+        return ptr::null();
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        let Some(format) = self::pixel_format(pixel_format) else {
+            chandle.set_error(1, "Unknown pixel format");
+            return ptr::null();
+        };
+        let (Some(proc), Some(rt)) = (&chandle.processor, &chandle.runtime) else {
+            chandle.set_error(4, "Processor not initialized");
+            return ptr::null();
+        };
+        let packing = rasterizer::OutputPacking { format, ..proc.output_packing() };
+        match rt.block_on(proc.get_frame_packed(frame_index as usize, packing)) {
+            Some(bitmap) => chandle.store_frame_buffer(bitmap) as *const c_void,
+            None if withholds_empty_frame(proc, frame_index) => {
+                chandle.set_error(9, "Frame is empty");
+                ptr::null()
+            }
+            None => {
+                chandle.set_error(3, "Frame not found or not ready");
+                ptr::null()
+            }
+        }
+    }
+}
+
+/// Get a processed frame like `CV_get_frame`, blocking until it is decoded or `timeout_ms` expires
+/// Schedules the frame if needed, so callers do not have to poll with sleeps. On CV_WAIT_READY
+/// `*out_frame` points to the frame, valid like the pointer CV_get_frame returns; otherwise it is null.
+/// Returns CV_WAIT_READY, CV_WAIT_TIMEOUT or CV_WAIT_FAILED (also for invalid arguments).
+/// In C#: if (CV_get_frame_wait(handle, frameIndex, 100, out IntPtr frameData) == CV_WAIT_READY) { ... }
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_get_frame_wait(handle: *mut AlphaStreamCHandle, frame_index: c_ulonglong, timeout_ms: c_uint, out_frame: *mut *const c_void) -> c_int {
+    if !is_live(handle) || out_frame.is_null() {
+        return CV_WAIT_FAILED;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        *out_frame = ptr::null();
+        let (Some(proc), Some(rt)) = (&chandle.processor, &chandle.runtime) else {
+            chandle.set_error(4, "Processor not initialized");
+            return CV_WAIT_FAILED;
+        };
+        let timeout = std::time::Duration::from_millis(timeout_ms as u64);
+        match rt.block_on(proc.get_frame_wait(frame_index as usize, timeout)) {
+            Ok(bitmap) => {
+                *out_frame = chandle.store_frame_buffer(bitmap) as *const c_void;
+                CV_WAIT_READY
+            }
+            Err(e) => {
+                let code = match e {
+                    api::FrameWaitError::Timeout(_) => {
+                        chandle.set_error(3, &e.to_string());
+                        return CV_WAIT_TIMEOUT;
+                    }
+                    api::FrameWaitError::DecodeFailed(_) => 10,
+                    api::FrameWaitError::Entitlement(_) => 6,
+                    api::FrameWaitError::Empty(_) => 9,
+                    api::FrameWaitError::NoBitmap => 3,
+                };
+                chandle.set_error(code, &e.to_string());
+                CV_WAIT_FAILED
+            }
+        }
+    }
+}
+
+/// Get the frames from `start_frame` to `start_frame + count - 1` in one call, e.g. to fill a scrub preview
+/// strip. `out_frames` (room for `count` pointers) receives each frame as `CV_get_frame` would return it, or
+/// null if it is not decoded yet (it is then scheduled), past the last frame, or withheld as empty (see
+/// `CV_set_empty_frame_policy`). `*out_ready` receives the number of non-null pointers. Unlike `CV_get_frame`
+/// this does not move the play head, so it does not disturb playback. The frames stay valid until the next
+/// CV_get_frames call or `CV_destroy`. Returns false on error: 1 for a null output, 4 before CV_init,
+/// 3 if the frame count cannot be read.
+/// In C#: IntPtr[] frames = new IntPtr[count]; CV_get_frames(handle, start, count, frames, out uint ready);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_get_frames(handle: *mut AlphaStreamCHandle, start_frame: c_ulonglong, count: c_uint, out_frames: *mut *const c_void, out_ready: *mut c_uint) -> bool {
+    if !is_live(handle) {
+        return false;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        if (out_frames.is_null() && count > 0) || out_ready.is_null() {
+            chandle.set_error(1, "Null out_frames or out_ready");
+            return false;
+        }
+        *out_ready = 0;
+        chandle.free_batch_buffers();
+        let (Some(proc), Some(rt)) = (&chandle.processor, &chandle.runtime) else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        let frame_count = match rt.block_on(proc.metadata()) {
+            Ok(meta) => meta.frame_count as c_ulonglong,
+            Err(e) => {
+                chandle.set_error(3, &e.to_string());
+                return false;
+            }
+        };
+        let mut batch = Vec::new();
+        for offset in 0..count {
+            let frame_index = start_frame.saturating_add(offset as c_ulonglong);
+            let cached = (frame_index < frame_count).then(|| proc.cached_frame(frame_index as usize)).flatten();
+            let frame = match cached {
+                Some(bitmap) => {
+                    let mut bitmap = bitmap.into_boxed_slice();
+                    let frame = bitmap.as_mut_ptr() as *const c_void;
+                    batch.push(bitmap);
+                    frame
+                }
+                None => {
+                    if frame_index < frame_count && !withholds_empty_frame(proc, frame_index) {
+                        let _ = rt.block_on(proc.request_frame_with_priority(frame_index as u32, Priority::Interactive));
+                    }
+                    ptr::null()
+                }
+            };
+            *out_frames.add(offset as usize) = frame;
+        }
+        track_allocation(Allocation::FrameBuffer, batch.len() as i64);
+        *out_ready = batch.len() as c_uint;
+        chandle.last_batch = batch;
+        true
+    }
+}
+
+/// Get a processed frame as R8 grayscale mask, transferring ownership of the buffer to the caller
+/// Unlike `CV_get_frame`, the buffer stays valid across later calls and after `CV_destroy`, so frames can be
+/// kept (e.g. queued for upload on another thread). Release it with `CV_free_buffer(ptr, len)`.
+/// Returns false and sets `*out_ptr` to null if the frame is not ready (error 3); the frame is scheduled.
+/// Error 9 if the frame is empty and withheld (see `CV_set_empty_frame_policy`).
+/// In C#: IntPtr data; UIntPtr len; if (CV_take_frame(handle, frameIndex, out data, out len)) { ...; CV_free_buffer(data, len); }
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_take_frame(handle: *mut AlphaStreamCHandle, frame_index: c_ulonglong, out_ptr: *mut *mut u8, out_len: *mut usize) -> bool {
+    if !is_live(handle) || out_ptr.is_null() || out_len.is_null() {
+        return false;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        *out_ptr = ptr::null_mut();
+        *out_len = 0;
+        let (Some(proc), Some(rt)) = (&chandle.processor, &chandle.runtime) else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        let Some(bitmap) = rt.block_on(async { proc.get_frame(frame_index as usize, proc.width(), proc.height()).await }) else {
+            if withholds_empty_frame(proc, frame_index) {
+                chandle.set_error(9, "Frame is empty");
+                return false;
+            }
+            chandle.set_error(3, "Frame not found or not ready");
+            return false;
+        };
+        (*out_ptr, *out_len) = give_to_caller(&bitmap);
+        true
+    }
+}
+
+/// Free a buffer returned by `CV_take_frame`, `CV_take_triangle_strip_vertices` or `CV_take_frame_output`
+/// `len` must be the buffer's length in bytes: the length reported with a frame, the float count times 4 for
+/// vertices. Buffers can be freed on any thread. Null pointers are ignored.
+/// In C#: CV_free_buffer(data, len);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_free_buffer(buffer: *mut u8, len: usize) {
+    if buffer.is_null() {
+        return;
+    }
+    unsafe {
+        std::alloc::dealloc(buffer, caller_buffer_layout(len));
+    }
+    track_allocation(Allocation::TakenBuffer, -1);
+}
+
+/// Get triangle strip vertices for 3D rendering
+/// Returns vertex data for rendering the frame as 3D geometry.
+/// Parameters:
+/// - out_vertices: Pointer to receive array of float coordinates (x,y,z,x,y,z,...)
+/// - out_count: Pointer to receive number of floats in the array
+///
+/// Returns true on success, false on error.
+/// The vertex array contains 3D positions for triangle strip rendering.
+/// In C#: float* vertices; IntPtr count; bool success = CV_get_triangle_strip_vertices(handle, frame, &vertices, &count);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_get_triangle_strip_vertices(handle: *mut AlphaStreamCHandle, frame_index: c_ulonglong, out_vertices: *mut *const f32, out_count: *mut usize) -> bool {
+    if !is_live(handle) || out_vertices.is_null() || out_count.is_null() {
+        return false;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        if let Some(proc) = &chandle.processor {
+            if let Some(rt) = &chandle.runtime {
+                match rt.block_on(async { proc.get_triangle_strip_vertices(frame_index as usize).await }) {
+                    Some(vertices) => {
+                        *out_count = vertices.len();
+                        *out_vertices = chandle.store_vertices_buffer(vertices) as *const f32;
+                        true
+                    }
+                    None if withholds_empty_frame(proc, frame_index) => {
+                        *out_vertices = ptr::null();
+                        *out_count = 0;
+                        chandle.set_error(9, "Frame is empty");
+                        false
+                    }
+                    None => {
+                        *out_vertices = ptr::null();
+                        *out_count = 0;
+                        chandle.set_error(5, "Vertices not found or not ready");
+                        false
+                    }
+                }
+            } else {
+                chandle.set_error(4, "Runtime not initialized");
+                false
+            }
+        } else {
+            chandle.set_error(4, "Processor not initialized");
+            false
+        }
+    }
+}
+
+/// Get the triangles of a frame as an indexed mesh, ready for upload without post-processing the
+/// unindexed list of `CV_get_triangle_strip_vertices` (which repeats shared vertices per triangle).
+/// - out_positions: receives x,y float pairs in native coordinates (2024x1024), one per distinct vertex
+/// - out_uvs: receives u,v float pairs per vertex, normalized to the native canvas: (0,0) top left and
+///   (1,1) bottom right, so they sample the frame's mask as a texture
+/// - out_indices: receives 32-bit vertex indices, three per triangle
+/// - out_counts: receives the vertex and index counts
+///
+/// The arrays stay valid until the next CV_get_triangles call or `CV_destroy`.
+/// Returns false on error: 1 for a null output, 4 before CV_init, 5 if the frame is not decoded yet
+/// (it is then scheduled), 9 if it is empty and withheld (see `CV_set_empty_frame_policy`).
+/// In C#: CV_get_triangles(handle, frame, out IntPtr positions, out IntPtr uvs, out IntPtr indices, out CVTriangleCounts counts);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_get_triangles(handle: *mut AlphaStreamCHandle, frame_index: c_ulonglong, out_positions: *mut *const f32, out_uvs: *mut *const f32, out_indices: *mut *const u32, out_counts: *mut CVTriangleCounts) -> bool {
+    if !is_live(handle) {
+        return false;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        if out_positions.is_null() || out_uvs.is_null() || out_indices.is_null() || out_counts.is_null() {
+            chandle.set_error(1, "Null output");
+            return false;
+        }
+        *out_positions = ptr::null();
+        *out_uvs = ptr::null();
+        *out_indices = ptr::null();
+        *out_counts = CVTriangleCounts::default();
+        let (Some(proc), Some(rt)) = (&chandle.processor, &chandle.runtime) else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        let Some(mesh) = rt.block_on(proc.get_triangles(frame_index as usize)) else {
+            if withholds_empty_frame(proc, frame_index) {
+                chandle.set_error(9, "Frame is empty");
+            } else {
+                chandle.set_error(5, "Vertices not found or not ready");
+            }
+            return false;
+        };
+        let mesh = chandle.store_triangles(mesh);
+        *out_positions = mesh.positions.as_ptr();
+        *out_uvs = mesh.uvs.as_ptr();
+        *out_indices = mesh.indices.as_ptr();
+        *out_counts = CVTriangleCounts { vertex_count: mesh.vertex_count(), index_count: mesh.indices.len() };
+        true
+    }
+}
+
+/// Get triangle strip vertices like `CV_get_triangle_strip_vertices`, in a new buffer owned by the caller
+/// The buffer stays valid across later calls and after `CV_destroy`. Release it with
+/// `CV_free_buffer(vertices, count * sizeof(float))`. Errors are those of `CV_get_triangle_strip_vertices`.
+/// In C#: IntPtr vertices; UIntPtr count; if (CV_take_triangle_strip_vertices(handle, frame, out vertices, out count)) { ...; CV_free_buffer(vertices, count * 4); }
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_take_triangle_strip_vertices(handle: *mut AlphaStreamCHandle, frame_index: c_ulonglong, out_vertices: *mut *mut f32, out_count: *mut usize) -> bool {
+    if !is_live(handle) || out_vertices.is_null() || out_count.is_null() {
+        return false;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        *out_vertices = ptr::null_mut();
+        *out_count = 0;
+        let (Some(proc), Some(rt)) = (&chandle.processor, &chandle.runtime) else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        let Some(vertices) = rt.block_on(async { proc.get_triangle_strip_vertices(frame_index as usize).await }) else {
+            if withholds_empty_frame(proc, frame_index) {
+                chandle.set_error(9, "Frame is empty");
+                return false;
+            }
+            chandle.set_error(5, "Vertices not found or not ready");
+            return false;
+        };
+        *out_count = vertices.len();
+        *out_vertices = give_to_caller(&vertices).0;
+        true
+    }
+}
+
+/// Get the bitmap, vertices, mask statistics and timing of a frame with a single lookup
+/// Fills `out` and returns true when the frame is ready; returns false (error 3) and schedules the
+/// frame otherwise. Outputs the processing mode does not produce are left null, as are all outputs of an
+/// empty frame under `CV_EMPTY_FRAME_ERROR` (`is_empty` tells).
+/// In C#: CVFrameOutput output; bool ready = CV_get_frame_output(handle, frameIndex, ref output);
+#[no_mangle]
+pub extern "C" fn CV_get_frame_output(handle: *mut AlphaStreamCHandle, frame_index: c_ulonglong, out: *mut CVFrameOutput) -> bool {
+    fill_frame_output(handle, frame_index, out, false)
+}
+
+/// Get all outputs of a frame like `CV_get_frame_output`, with the bitmap and vertices in new buffers owned by
+/// the caller. Release each non-null one with `CV_free_buffer(output.bitmap, output.bitmap_size)` and
+/// `CV_free_buffer(output.vertices, output.vertex_count * sizeof(float))`.
+/// In C#: CVFrameOutput output; if (CV_take_frame_output(handle, frameIndex, ref output)) { ...; CV_free_buffer(output.bitmap, output.bitmap_size); }
+#[no_mangle]
+pub extern "C" fn CV_take_frame_output(handle: *mut AlphaStreamCHandle, frame_index: c_ulonglong, out: *mut CVFrameOutput) -> bool {
+    fill_frame_output(handle, frame_index, out, true)
+}
+
+/// Body of `CV_get_frame_output` and `CV_take_frame_output`; `take` hands the buffers to the caller
+fn fill_frame_output(handle: *mut AlphaStreamCHandle, frame_index: c_ulonglong, out: *mut CVFrameOutput, take: bool) -> bool {
+    if !is_live(handle) || out.is_null() {
+        return false;
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        *out = CVFrameOutput::default();
+        let (Some(proc), Some(rt)) = (&chandle.processor, &chandle.runtime) else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        let Some(output) = rt.block_on(async { proc.get_frame_output(frame_index as usize).await }) else {
+            chandle.set_error(3, "Frame not found or not ready");
+            return false;
+        };
+        let out = &mut *out;
+        if let Some(bitmap) = output.bitmap {
+            out.bitmap_size = bitmap.len();
+            out.bitmap = if take { give_to_caller(&bitmap).0 } else { chandle.store_frame_buffer(bitmap) as *const u8 };
+        }
+        if let Some(vertices) = output.triangle_strip {
+            out.vertex_count = vertices.len();
+            out.vertices = if take { give_to_caller(&vertices).0 } else { chandle.store_vertices_buffer(vertices) as *const f32 };
+        }
+        if let Some(stats) = output.stats {
+            out.has_stats = true;
+            out.area = stats.area;
+            out.coverage = stats.coverage;
+            out.bbox_x = stats.bbox.x;
+            out.bbox_y = stats.bbox.y;
+            out.bbox_w = stats.bbox.w;
+            out.bbox_h = stats.bbox.h;
+        }
+        if let Some(trace) = output.trace {
+            out.decode_us = trace.decode.as_micros() as c_ulonglong;
+            out.process_us = trace.process.as_micros() as c_ulonglong;
+        }
+        out.is_empty = output.is_empty;
+        true
+    }
+}
+
+/// Bookmark a frame under a name, for chapter navigation; an existing bookmark of that name is moved
+/// Bookmarks of local files persist in the sidecar `<file>.bookmarks.json`, next to the source.
+/// Returns false on error: 1 for an invalid name, 7 if the frame is out of range or saving failed.
+/// In C#: CV_add_bookmark(handle, "Intro", 0);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_add_bookmark(handle: *mut AlphaStreamCHandle, name: *const c_char, frame_index: c_uint) -> bool {
+    if !is_live(handle) { return false; }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        let Some(name) = (!name.is_null()).then(|| CStr::from_ptr(name).to_str().ok()).flatten() else {
+            chandle.set_error(1, "Invalid bookmark name");
+            return false;
+        };
+        let (Some(proc), Some(rt)) = (&chandle.processor, &chandle.runtime) else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        match rt.block_on(proc.add_bookmark(name, frame_index)) {
+            Ok(()) => true,
+            Err(e) => {
+                chandle.set_error(7, &format!("Bookmark error: {e}"));
+                false
+            }
+        }
+    }
+}
+
+/// Remove the bookmark with the given name
+/// Returns true if it was removed; false if there is no such bookmark (error code 0) or on error.
+/// In C#: CV_remove_bookmark(handle, "Intro");
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_remove_bookmark(handle: *mut AlphaStreamCHandle, name: *const c_char) -> bool {
+    if !is_live(handle) { return false; }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        let Some(name) = (!name.is_null()).then(|| CStr::from_ptr(name).to_str().ok()).flatten() else {
+            chandle.set_error(1, "Invalid bookmark name");
+            return false;
+        };
+        let Some(proc) = &chandle.processor else {
+            chandle.set_error(4, "Processor not initialized");
+            return false;
+        };
+        match proc.remove_bookmark(name) {
+            Ok(removed) => removed,
+            Err(e) => {
+                chandle.set_error(7, &format!("Bookmark error: {e}"));
+                false
+            }
+        }
+    }
+}
+
+/// Number of bookmarks, or -1 for a null handle. Bookmarks are indexed in frame order.
+/// In C#: int count = CV_get_bookmark_count(handle);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_get_bookmark_count(handle: *mut AlphaStreamCHandle) -> c_int {
+    if !is_live(handle) { return -1; }
+    unsafe {
+        (*handle).processor.as_ref().map_or(0, |proc| proc.bookmarks().len() as c_int)
+    }
+}
+
+/// Read bookmark `index` (in frame order): its frame goes to `out_frame_index` and its name is copied
+/// into `name_buffer` as a NUL-terminated UTF-8 string, truncated to `name_buffer_len` bytes.
+/// Returns the length of the full name in bytes (without the NUL), so a caller can retry with a larger
+/// buffer, or -1 if there is no such bookmark. Either output may be null.
+/// In C#: byte[] buf = new byte[256]; int len = CV_get_bookmark(handle, i, out uint frame, buf, (UIntPtr)buf.Length);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_get_bookmark(handle: *mut AlphaStreamCHandle, index: c_uint, out_frame_index: *mut c_uint, name_buffer: *mut c_char, name_buffer_len: usize) -> c_int {
+    if !is_live(handle) { return -1; }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        let Some(proc) = &chandle.processor else {
+            chandle.set_error(4, "Processor not initialized");
+            return -1;
+        };
+        let Some(bookmark) = proc.bookmarks().into_iter().nth(index as usize) else {
+            return -1;
+        };
+        if !out_frame_index.is_null() {
+            *out_frame_index = bookmark.frame_index;
+        }
+        if !name_buffer.is_null() && name_buffer_len > 0 {
+            let name = bookmark.name.as_bytes();
+            let len = name.len().min(name_buffer_len - 1);
+            ptr::copy_nonoverlapping(name.as_ptr(), name_buffer as *mut u8, len);
+            *name_buffer.add(len) = 0;
+        }
+        bookmark.name.len() as c_int
+    }
+}
+
+/// Number of channels of frame `frame_index`, without rasterizing it (see `channel_info`), or -1 on error.
+/// Decodes the frame if it is not cached yet, so it may block for a read.
+/// In C#: int channels = CV_get_channel_count(handle, frameIndex);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_get_channel_count(handle: *mut AlphaStreamCHandle, frame_index: c_ulonglong) -> c_int {
+    if !is_live(handle) { return -1; }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        let (Some(proc), Some(rt)) = (&chandle.processor, &chandle.runtime) else {
+            chandle.set_error(4, "Processor not initialized");
+            return -1;
+        };
+        match rt.block_on(proc.channel_info(frame_index as usize)) {
+            Ok(info) => info.count() as c_int,
+            Err(e) => {
+                chandle.set_error(3, &format!("Channel info of frame {} not available: {}", frame_index, e));
+                -1
+            }
+        }
+    }
+}
+
+/// Get one channel of a frame, e.g. the player or the ball of a scene that tracks both, so hosts can
+/// render or occlude mask layers separately. `channel_index` counts up to CV_get_channel_count.
+/// The buffer has the layout of CV_get_frame's (CV_get_frame_size bytes, see CV_set_output_packing)
+/// and is valid like its pointer. The layer is rasterized from the cached frame on every call.
+/// Returns null on error: 3 if the frame is not decoded yet (it is scheduled, like by CV_get_frame),
+/// 1 if the frame has no channel `channel_index`.
+/// In C#: IntPtr layer = CV_get_channel_frame(handle, frameIndex, 1);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_get_channel_frame(handle: *mut AlphaStreamCHandle, frame_index: c_ulonglong, channel_index: c_uint) -> *const c_void {
+    if !is_live(handle) {
+        return ptr::null();
+    }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        let (Some(proc), Some(rt)) = (&chandle.processor, &chandle.runtime) else {
+            chandle.set_error(4, "Processor not initialized");
+            return ptr::null();
+        };
+        let Some(mask) = rt.block_on(proc.get_frame_channels(frame_index as usize, &[channel_index as usize])) else {
+            chandle.set_error(3, "Frame not found or not ready");
+            return ptr::null();
+        };
+        // The frame is decoded, so its channel count is read from the cache
+        let channel_count = rt.block_on(proc.channel_info(frame_index as usize)).map_or(0, |info| info.count());
+        if channel_index as usize >= channel_count {
+            chandle.set_error(1, &format!("Frame {} has {} channels, no channel {}", frame_index, channel_count, channel_index));
+            return ptr::null();
+        }
+        let bitmap = proc.output_packing().pack_owned(mask, proc.width(), proc.height());
+        chandle.store_frame_buffer(bitmap) as *const c_void
+    }
+}
+
+/// Index of the bookmark that starts the chapter containing `frame_index` (the last bookmark at or
+/// before it), or -1 if the frame comes before the first bookmark.
+/// In C#: int chapter = CV_get_chapter_at(handle, frameIndex);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_get_chapter_at(handle: *mut AlphaStreamCHandle, frame_index: c_ulonglong) -> c_int {
+    if !is_live(handle) { return -1; }
+    unsafe {
+        let Some(proc) = &(*handle).processor else {
+            return -1;
+        };
+        let bookmarks = proc.bookmarks();
+        bookmarks.partition_point(|bookmark| bookmark.frame_index as c_ulonglong <= frame_index) as c_int - 1
+    }
+}
+
+/// Register the callback for processor events, or pass null to unregister
+/// May be called before or after CV_init. The callback only runs inside CV_run_callbacks_on_thread,
+/// on the thread that calls it; events queue up in between (the oldest are dropped past a limit).
+/// Returns false for a null handle.
+/// In C#: CV_set_event_callback(handle, Marshal.GetFunctionPointerForDelegate(cb), IntPtr.Zero);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_set_event_callback(handle: *mut AlphaStreamCHandle, callback: Option<CVEventCallback>, user_data: *mut c_void) -> bool {
+    if !is_live(handle) { return false; }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        chandle.event_callback = callback;
+        chandle.event_user_data = user_data;
+        if let Some(proc) = &chandle.processor {
+            proc.enable_events(chandle.wants_events());
+        }
+    }
+    true
+}
+
+/// Register the callback that receives frames as they finish decoding, or pass null to unregister
+/// Lets hosts render frames on arrival instead of polling CV_get_frame. Like event callbacks it only runs
+/// inside CV_run_callbacks_on_thread, on the thread that calls it. Frames that are no longer cached by
+/// then, or that CV_get_frame would not return (see CV_set_empty_frame_policy), are skipped.
+/// Returns false for a null handle.
+/// In C#: CV_set_frame_ready_callback(handle, Marshal.GetFunctionPointerForDelegate(onFrame), IntPtr.Zero);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_set_frame_ready_callback(handle: *mut AlphaStreamCHandle, callback: Option<CVFrameReadyCallback>, user_data: *mut c_void) -> bool {
+    if !is_live(handle) { return false; }
+    unsafe {
+        let chandle = &mut *handle;
+        chandle.clear_error();
+        chandle.frame_ready_callback = callback;
+        chandle.frame_ready_user_data = user_data;
+        if let Some(proc) = &chandle.processor {
+            proc.enable_events(chandle.wants_events());
+        }
+    }
+    true
+}
+
+/// Deliver all queued events to the registered callbacks on the calling thread
+/// Call this regularly (e.g. once per rendered frame) from the thread that must receive callbacks.
+/// Callbacks may call other CV_* functions on the handle, but not CV_destroy.
+/// Returns the number of callbacks invoked, or -1 for a null handle.
+/// In C#: CV_run_callbacks_on_thread(handle);
+#[no_mangle]
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub extern "C" fn CV_run_callbacks_on_thread(handle: *mut AlphaStreamCHandle) -> c_int {
+    if !is_live(handle) { return -1; }
+    // Everything the callbacks need is gathered first: a callback that calls back into the library
+    // must not find the handle borrowed
+    let (deliveries, frame_ready_callback, frame_ready_user_data, event_callback, event_user_data) = unsafe {
+        let chandle = &*handle;
+        let Some(proc) = &chandle.processor else {
+            return 0;
+        };
+        let deliveries: Vec<_> = proc
+            .poll_events()
+            .into_iter()
+            .map(|event| {
+                let bitmap = match (event, chandle.frame_ready_callback) {
+                    (api::ProcessorEvent::FrameReady(frame), Some(_)) => proc.cached_frame(frame),
+                    _ => None,
+                };
+                (event, bitmap)
+            })
+            .collect();
+        (deliveries, chandle.frame_ready_callback, chandle.frame_ready_user_data, chandle.event_callback, chandle.event_user_data)
+    };
+    let mut delivered = 0;
+    for (event, bitmap) in deliveries {
+        if let (api::ProcessorEvent::FrameReady(frame), Some(on_frame), Some(bitmap)) = (event, frame_ready_callback, bitmap) {
+            on_frame(frame as c_ulonglong, bitmap.as_ptr(), bitmap.len(), frame_ready_user_data);
+            delivered += 1;
+        }
+        let Some(callback) = event_callback else {
+            continue;
+        };
+        let (code, value) = match event {
+            api::ProcessorEvent::FrameReady(frame) => (CV_EVENT_FRAME_READY, frame as c_ulonglong),
+            api::ProcessorEvent::DecodeError(frame) => (CV_EVENT_DECODE_ERROR, frame as c_ulonglong),
+            api::ProcessorEvent::SourceReloaded(evicted) => (CV_EVENT_SOURCE_RELOADED, evicted as c_ulonglong),
+            api::ProcessorEvent::EntitlementDenied => (CV_EVENT_ENTITLEMENT_DENIED, 0),
+        };
+        callback(event_user_data, code, value);
+        delivered += 1;
+    }
+    delivered
+}
+
+/// Report handles and buffers that are still alive, to find missing CV_destroy calls
+/// Logs one warning per kind of allocation (to the CV_set_log_callback callback if one is set, else to
+/// stderr) and returns the total number of live allocations.
+/// Only debug builds and builds with the `leak-tracking` feature count allocations; others return -1.
+/// Call it after destroying all handles (e.g. on application quit); anything reported then has leaked.
+/// In C#: long leaks = CV_debug_dump_leaks();
+#[no_mangle]
+pub extern "C" fn CV_debug_dump_leaks() -> c_longlong {
+    #[cfg(any(debug_assertions, feature = "leak-tracking"))]
+    {
+        let kinds = [Allocation::Handle, Allocation::FrameBuffer, Allocation::VertexBuffer, Allocation::TakenBuffer];
+        let mut total = 0;
+        for kind in kinds {
+            let live = live_allocations(kind);
+            logging::log(logging::LogLevel::Warn, format_args!("live {:?}: {}", kind, live));
+            total += live;
+        }
+        total
+    }
+    #[cfg(not(any(debug_assertions, feature = "leak-tracking")))]
+    {
+        -1
+    }
+}
+
+/// A handle of the id-based C ABI: `CV_create_id` returns it and every `CV_*_id` export takes it in
+/// place of the handle pointer. Ids are never 0 and never reused.
+pub type CVHandleId = c_ulonglong;
+
+/// A handle registered under an id; destroyed when its last reference is dropped
+struct HandleSlot(*mut AlphaStreamCHandle);
+
+// The pointer is only dereferenced by the CV_* functions, with the same threading rules as handle pointers
+unsafe impl Send for HandleSlot {}
+unsafe impl Sync for HandleSlot {}
+
+impl Drop for HandleSlot {
+    fn drop(&mut self) {
+        CV_destroy(self.0);
+    }
+}
+
+/// Handles of the id-based API by id
+static HANDLE_IDS: std::sync::Mutex<std::collections::BTreeMap<CVHandleId, std::sync::Arc<HandleSlot>>> =
+    std::sync::Mutex::new(std::collections::BTreeMap::new());
+static NEXT_HANDLE_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+
+/// The handle registered under `id`, kept alive while the returned reference is held
+fn handle_for_id(id: CVHandleId) -> Option<std::sync::Arc<HandleSlot>> {
+    let slot = HANDLE_IDS.lock().unwrap_or_else(|e| e.into_inner()).get(&id).cloned();
+    if slot.is_none() {
+        logging::log(logging::LogLevel::Warn, format_args!("Rejected call with handle id {}: destroyed or never created", id));
+    }
+    slot
+}
+
+/// Create a handle of the id-based API, an alternative to CV_create for hosts whose marshalling of
+/// pointers cannot be trusted. A destroyed or made-up id is never dereferenced: every `CV_*_id` call
+/// with it fails as with a null handle (CV_get_last_error_code_id returns -1) and logs a warning.
+/// In C#: ulong id = CV_create_id();
+#[no_mangle]
+pub extern "C" fn CV_create_id() -> CVHandleId {
+    let id = NEXT_HANDLE_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let slot = std::sync::Arc::new(HandleSlot(CV_create()));
+    HANDLE_IDS.lock().unwrap_or_else(|e| e.into_inner()).insert(id, slot);
+    id
+}
+
+/// Destroy a handle of the id-based API. Returns false, destroying nothing, for an id that was
+/// destroyed before or never created. Unlike CV_destroy it may be called from a callback of the
+/// handle: a call still running on the handle keeps it alive until that call returns.
+/// In C#: bool destroyed = CV_destroy_id(id);
+#[no_mangle]
+pub extern "C" fn CV_destroy_id(id: CVHandleId) -> bool {
+    let slot = HANDLE_IDS.lock().unwrap_or_else(|e| e.into_inner()).remove(&id);
+    if slot.is_none() {
+        logging::log(logging::LogLevel::Warn, format_args!("CV_destroy_id ignored for handle id {}: destroyed before or never created", id));
+    }
+    slot.is_some()
+}
+
+/// Id-based counterparts of the exports that take a handle. An unknown id is passed on as a null
+/// handle, so each export fails the way it does for null.
+macro_rules! id_exports {
+    ($($name:ident => $target:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        $(
+            #[doc = concat!("`", stringify!($target), "` for a handle of `CV_create_id`")]
+            #[no_mangle]
+            pub extern "C" fn $name(id: CVHandleId, $($arg: $ty),*) -> $ret {
+                let slot = handle_for_id(id);
+                $target(slot.as_ref().map_or(ptr::null_mut(), |slot| slot.0), $($arg),*)
+            }
+        )*
+    };
+}
+
+id_exports! {
+    CV_get_name_id => CV_get_name() -> *const c_char;
+    CV_get_version_id => CV_get_version() -> *const c_char;
+    CV_get_last_error_code_id => CV_get_last_error_code() -> c_int;
+    CV_get_last_error_text_id => CV_get_last_error_text() -> *const c_char;
+    CV_get_metadata_id => CV_get_metadata(out_metadata: *mut CVMetadata) -> bool;
+    CV_get_stats_id => CV_get_stats(out_stats: *mut CVStats) -> bool;
+    CV_get_total_frames_id => CV_get_total_frames() -> c_uint;
+    CV_get_frame_size_id => CV_get_frame_size() -> c_uint;
+    CV_init_id => CV_init(base_url: *const c_char, scene_id: c_uint, width: c_uint, height: c_uint, version: *const c_char, start_frame: c_uint, l0_buffer_length: c_uint, l1_buffer_length: c_uint, l1_buffer_init_length: c_uint, init_timeout_ms: c_uint, data_timeout_ms: c_uint) -> bool;
+    CV_init_asvr_id => CV_init_asvr(path: *const c_char, scene_id: c_uint, version: *const c_char, base_url: *const c_char, width: c_uint, height: c_uint, l1_buffer_length: c_uint, l1_buffer_init_length: c_uint, init_timeout_ms: c_uint) -> bool;
+    CV_init_from_memory_id => CV_init_from_memory(data: *const u8, len: usize, scene_id: c_uint, version: *const c_char, base_url: *const c_char, width: c_uint, height: c_uint, l1_buffer_length: c_uint, l1_buffer_init_length: c_uint, init_timeout_ms: c_uint) -> bool;
+    CV_select_backend_id => CV_select_backend(backend_id: c_int) -> bool;
+    CV_set_output_packing_id => CV_set_output_packing(pixel_format: c_int, row_alignment: c_uint, swap_bytes: bool) -> bool;
+    CV_set_empty_frame_policy_id => CV_set_empty_frame_policy(policy: c_int) -> bool;
+    CV_set_processing_mode_id => CV_set_processing_mode(mode: c_int) -> bool;
+    CV_set_output_size_id => CV_set_output_size(width: c_uint, height: c_uint) -> bool;
+    CV_set_decode_budget_id => CV_set_decode_budget(budget_ms: f32) -> bool;
+    CV_prefetch_range_id => CV_prefetch_range(start_frame: c_ulonglong, count: c_uint, priority: c_int) -> bool;
+    CV_cancel_all_id => CV_cancel_all() -> bool;
+    CV_shutdown_id => CV_shutdown() -> bool;
+    CV_report_display_size_id => CV_report_display_size(width: c_uint, height: c_uint) -> c_int;
+    CV_get_frame_id => CV_get_frame(frame_index: CVFrameIndex) -> *const c_void;
+    CV_get_frame_fmt_id => CV_get_frame_fmt(frame_index: c_ulonglong, pixel_format: c_int) -> *const c_void;
+    CV_get_frame_wait_id => CV_get_frame_wait(frame_index: c_ulonglong, timeout_ms: c_uint, out_frame: *mut *const c_void) -> c_int;
+    CV_get_frames_id => CV_get_frames(start_frame: c_ulonglong, count: c_uint, out_frames: *mut *const c_void, out_ready: *mut c_uint) -> bool;
+    CV_take_frame_id => CV_take_frame(frame_index: c_ulonglong, out_ptr: *mut *mut u8, out_len: *mut usize) -> bool;
+    CV_get_triangle_strip_vertices_id => CV_get_triangle_strip_vertices(frame_index: c_ulonglong, out_vertices: *mut *const f32, out_count: *mut usize) -> bool;
+    CV_get_triangles_id => CV_get_triangles(frame_index: c_ulonglong, out_positions: *mut *const f32, out_uvs: *mut *const f32, out_indices: *mut *const u32, out_counts: *mut CVTriangleCounts) -> bool;
+    CV_take_triangle_strip_vertices_id => CV_take_triangle_strip_vertices(frame_index: c_ulonglong, out_vertices: *mut *mut f32, out_count: *mut usize) -> bool;
+    CV_get_frame_output_id => CV_get_frame_output(frame_index: c_ulonglong, out: *mut CVFrameOutput) -> bool;
+    CV_take_frame_output_id => CV_take_frame_output(frame_index: c_ulonglong, out: *mut CVFrameOutput) -> bool;
+    CV_add_bookmark_id => CV_add_bookmark(name: *const c_char, frame_index: c_uint) -> bool;
+    CV_remove_bookmark_id => CV_remove_bookmark(name: *const c_char) -> bool;
+    CV_get_bookmark_count_id => CV_get_bookmark_count() -> c_int;
+    CV_get_bookmark_id => CV_get_bookmark(index: c_uint, out_frame_index: *mut c_uint, name_buffer: *mut c_char, name_buffer_len: usize) -> c_int;
+    CV_get_channel_count_id => CV_get_channel_count(frame_index: c_ulonglong) -> c_int;
+    CV_get_channel_frame_id => CV_get_channel_frame(frame_index: c_ulonglong, channel_index: c_uint) -> *const c_void;
+    CV_get_chapter_at_id => CV_get_chapter_at(frame_index: c_ulonglong) -> c_int;
+    CV_set_event_callback_id => CV_set_event_callback(callback: Option<CVEventCallback>, user_data: *mut c_void) -> bool;
+    CV_set_frame_ready_callback_id => CV_set_frame_ready_callback(callback: Option<CVFrameReadyCallback>, user_data: *mut c_void) -> bool;
+    CV_run_callbacks_on_thread_id => CV_run_callbacks_on_thread() -> c_int;
+}
+
+// Keep minimal Rust-native API for tests/demos
+/// Returns the crate semantic version string.
+pub fn version() -> &'static str { PLUGIN_VERSION }
+
+/// Simple echo function for demo/tests.
+pub fn echo(input: &str) -> String { input.to_string() }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use crate::testlib::{create_test_asvr};
+
+    #[test]
+    fn version_is_semver_like() { assert!(version().split('.').count() >= 3); }
+
+    #[test]
+    fn echo_roundtrip() { assert_eq!(echo("alpha"), "alpha"); }
+
+    #[test]
+    fn test_c_abi_create_destroy() {
+        let handle = CV_create();
+        assert!(!handle.is_null());
+
+        // Check initial state
+        assert_eq!(CV_get_last_error_code(handle), 0);
+        assert_eq!(CV_get_total_frames(handle), 0);
+        assert_eq!(CV_get_frame_size(handle), 0);
+
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_handle_ids() {
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 2).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+
+        let id = CV_create_id();
+        assert_ne!(id, 0);
+        assert_ne!(CV_create_id(), id);
+        assert!(CV_init_id(id, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert_eq!(CV_get_total_frames_id(id), 2);
+        let mut frame: *const c_void = ptr::null();
+        assert_eq!(CV_get_frame_wait_id(id, 0, 5000, &mut frame), CV_WAIT_READY);
+        assert!(!frame.is_null());
+        assert_eq!(CV_get_last_error_code_id(id), 0);
+
+        // A callback may destroy its own handle; the handle lives until the running call returns
+        extern "C" fn destroy_own_handle(_frame_index: c_ulonglong, _data: *const u8, _len: usize, user_data: *mut c_void) {
+            CV_destroy_id(unsafe { *(user_data as *const CVHandleId) });
+        }
+        let mut own = CV_create_id();
+        assert!(CV_set_frame_ready_callback_id(own, Some(destroy_own_handle), &mut own as *mut CVHandleId as *mut c_void));
+        assert!(CV_init_id(own, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert_eq!(CV_get_frame_wait_id(own, 0, 5000, &mut frame), CV_WAIT_READY);
+        assert!(CV_run_callbacks_on_thread_id(own) >= 1);
+        assert!(!CV_destroy_id(own));
+        assert_eq!(CV_get_total_frames_id(own), 0);
+
+        // Destroyed and made-up ids fail like a null handle, without touching memory
+        assert!(CV_destroy_id(id));
+        for stale in [id, 0, u64::MAX] {
+            assert!(!CV_destroy_id(stale));
+            assert_eq!(CV_get_last_error_code_id(stale), -1);
+            assert_eq!(unsafe { CStr::from_ptr(CV_get_last_error_text_id(stale)) }, c"Invalid handle");
+            assert!(CV_get_frame_id(stale, 0).is_null());
+            assert_eq!(CV_get_frame_wait_id(stale, 0, 0, &mut frame), CV_WAIT_FAILED);
+            assert_eq!(CV_get_total_frames_id(stale), 0);
+        }
+    }
+
+    #[test]
     fn test_c_abi_init() {
         let handle = CV_create();
         assert!(!handle.is_null());
@@ -466,6 +2269,32 @@ mod tests {
         CV_destroy(handle);
     }
 
+    #[test]
+    fn test_c_abi_take_frame() {
+        let handle = CV_create();
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+
+        let mut data = ptr::null_mut();
+        let mut len = 0;
+        let _ = CV_take_frame(handle, 0, &mut data, &mut len);
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        assert!(CV_take_frame(handle, 0, &mut data, &mut len));
+        assert_eq!(len, 256);
+
+        // The taken buffer survives later calls and the handle itself
+        let borrowed = CV_get_frame(handle, 0);
+        assert_ne!(borrowed as *const u8, data as *const u8);
+        CV_destroy(handle);
+        unsafe {
+            assert_eq!(std::slice::from_raw_parts(data, len)[0], 0);
+        }
+        CV_free_buffer(data, len);
+        CV_free_buffer(ptr::null_mut(), 0);
+    }
+
     #[test]
     fn test_c_abi_triangle_strip() {
         let handle = CV_create();
@@ -500,13 +2329,72 @@ mod tests {
         // sleep for 500ms to allow frame to be processed
         std::thread::sleep(std::time::Duration::from_millis(500));
         let success = CV_get_triangle_strip_vertices(handle, 0, &mut vertices, &mut count);
-        assert_eq!(success, true);
+        assert!(success);
         assert_eq!(count, 174);
         assert!(!vertices.is_null());
 
         CV_destroy(handle);
     }
 
+    #[test]
+    fn test_c_abi_triangles() {
+        let handle = CV_create();
+        let (mut positions, mut uvs, mut indices) = (ptr::null(), ptr::null(), ptr::null());
+        let mut counts = CVTriangleCounts::default();
+        assert!(!CV_get_triangles(handle, 0, &mut positions, &mut uvs, &mut indices, &mut counts));
+        assert_eq!(CV_get_last_error_code(handle), 4);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert!(!CV_get_triangles(handle, 0, &mut positions, ptr::null_mut(), &mut indices, &mut counts));
+        assert_eq!(CV_get_last_error_code(handle), 1);
+
+        let mut frame = ptr::null();
+        assert_eq!(CV_get_frame_wait(handle, 0, 5000, &mut frame), CV_WAIT_READY);
+        let (mut strip, mut strip_count) = (ptr::null(), 0);
+        assert!(CV_get_triangle_strip_vertices(handle, 0, &mut strip, &mut strip_count));
+        let strip = unsafe { std::slice::from_raw_parts(strip, strip_count) }.to_vec();
+        assert!(CV_get_triangles(handle, 0, &mut positions, &mut uvs, &mut indices, &mut counts));
+        assert_eq!(counts.index_count, strip.len() / 6 * 3);
+        assert!(counts.vertex_count <= counts.index_count);
+        let (positions, uvs, indices) = unsafe {
+            (
+                std::slice::from_raw_parts(positions, counts.vertex_count * 2),
+                std::slice::from_raw_parts(uvs, counts.vertex_count * 2),
+                std::slice::from_raw_parts(indices, counts.index_count),
+            )
+        };
+        // The indices reproduce the unindexed triangles
+        for (corner, &index) in indices.iter().enumerate() {
+            let index = index as usize;
+            assert_eq!(&positions[index * 2..index * 2 + 2], &strip[corner * 2..corner * 2 + 2]);
+            assert_eq!(uvs[index * 2], positions[index * 2] / rasterizer::NATIVE_WIDTH as f32);
+            assert_eq!(uvs[index * 2 + 1], positions[index * 2 + 1] / rasterizer::NATIVE_HEIGHT as f32);
+        }
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_log_callback() {
+        static RECEIVED: std::sync::Mutex<Vec<(usize, c_int, String)>> = std::sync::Mutex::new(Vec::new());
+        extern "C" fn on_log(user_data: *mut c_void, level: c_int, message: *const c_char) {
+            let message = unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned();
+            RECEIVED.lock().unwrap().push((user_data as usize, level, message));
+        }
+        assert!(!CV_set_log_callback(Some(on_log), 9, ptr::null_mut()));
+        assert!(CV_set_log_callback(Some(on_log), CV_LOG_WARN, 42 as *mut c_void));
+        logging::log(logging::LogLevel::Warn, format_args!("log callback {}", "test\0warning"));
+        logging::log(logging::LogLevel::Debug, format_args!("log callback test debug"));
+        assert!(CV_set_log_callback(None, CV_LOG_OFF, ptr::null_mut()));
+        logging::log(logging::LogLevel::Error, format_args!("log callback test after removal"));
+
+        // Other tests may log at the same time
+        let received: Vec<_> = RECEIVED.lock().unwrap().iter().filter(|(_, _, message)| message.starts_with("log callback")).cloned().collect();
+        assert_eq!(received, vec![(42, CV_LOG_WARN, "log callback test warning".to_string())]);
+    }
+
     #[test]
     fn test_c_abi_error_handling() {
         // Test with null handle
@@ -577,4 +2465,680 @@ mod tests {
         assert_eq!(CV_get_last_error_code(handle), 5); // Vertices not found or not ready
         CV_destroy(handle);
     }
+
+    #[test]
+    fn test_c_abi_get_frame_output() {
+        let handle = CV_create();
+        let mut output = CVFrameOutput::default();
+        assert!(!CV_get_frame_output(handle, 0, &mut output));
+        assert_eq!(CV_get_last_error_code(handle), 4);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+
+        let _ = CV_get_frame_output(handle, 0, &mut output);
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        assert!(CV_get_frame_output(handle, 0, &mut output));
+        assert_eq!(CV_get_last_error_code(handle), 0);
+        // CV_init processes both bitmaps and vertices
+        assert!(!output.bitmap.is_null());
+        assert_eq!(output.bitmap_size, 256);
+        assert!(!output.vertices.is_null());
+        assert!(output.has_stats);
+        assert!(output.coverage >= 0.0 && output.coverage <= 1.0);
+        assert!(!CV_get_frame_output(ptr::null_mut(), 0, &mut output));
+
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_take_vertices_and_output() {
+        let handle = CV_create();
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        let mut frame = ptr::null();
+        assert_eq!(CV_get_frame_wait(handle, 0, 5000, &mut frame), CV_WAIT_READY);
+
+        // Every call returns its own buffer, so earlier ones stay valid
+        let (mut first, mut second) = (ptr::null_mut(), ptr::null_mut());
+        let (mut first_count, mut second_count) = (0, 0);
+        assert!(CV_take_triangle_strip_vertices(handle, 0, &mut first, &mut first_count));
+        assert!(CV_take_triangle_strip_vertices(handle, 0, &mut second, &mut second_count));
+        assert_ne!(first, second);
+        assert_eq!((first_count, second_count), (174, 174));
+        assert_eq!(first as usize % std::mem::align_of::<f32>(), 0);
+
+        let mut output = CVFrameOutput::default();
+        assert!(CV_take_frame_output(handle, 0, &mut output));
+        assert_eq!(output.bitmap_size, 256);
+        let borrowed = CV_get_frame(handle, 0);
+        assert_ne!(borrowed as *const u8, output.bitmap);
+        CV_destroy(handle);
+
+        unsafe {
+            let (first_slice, second_slice) = (std::slice::from_raw_parts(first, first_count), std::slice::from_raw_parts(second, second_count));
+            assert_eq!(first_slice, second_slice);
+            assert_eq!(std::slice::from_raw_parts(output.vertices, output.vertex_count), first_slice);
+        }
+        // Caller-owned buffers can be released from any thread
+        let buffers = [
+            (first as usize, first_count * 4),
+            (second as usize, second_count * 4),
+            (output.bitmap as usize, output.bitmap_size),
+            (output.vertices as usize, output.vertex_count * 4),
+        ];
+        std::thread::spawn(move || {
+            for (buffer, len) in buffers {
+                CV_free_buffer(buffer as *mut u8, len);
+            }
+        }).join().unwrap();
+    }
+
+    #[test]
+    fn test_c_abi_backends() {
+        let count = CV_list_backends(ptr::null_mut(), 0);
+        assert!(count >= 1);
+        let mut backends = vec![CVBackendInfo { id: -1, name: ptr::null(), max_texture_size: 0, async_readback: false }; count as usize];
+        assert_eq!(CV_list_backends(backends.as_mut_ptr(), backends.len()), count);
+        assert_eq!(backends[0].id, CV_BACKEND_CPU);
+        assert_eq!(unsafe { CStr::from_ptr(backends[0].name) }.to_str().unwrap(), "cpu");
+        assert!(backends[0].max_texture_size >= 4096);
+
+        let handle = CV_create();
+        assert!(!CV_select_backend(handle, 99));
+        assert_eq!(CV_get_last_error_code(handle), 1);
+        assert!(CV_select_backend(handle, CV_BACKEND_CPU));
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert!(!CV_select_backend(handle, CV_BACKEND_CPU));
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_dimension_limits() {
+        let handle = CV_create();
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        // 512 buffered 4096x4096 masks take 8 GiB
+        assert!(!CV_init(handle, base_url.as_ptr(), 123, 4096, 4096, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert_eq!(CV_get_last_error_code(handle), 8);
+        let text = unsafe { CStr::from_ptr(CV_get_last_error_text(handle)) }.to_str().unwrap();
+        assert!(text.contains("a cache capacity of 256 fits"), "{}", text);
+        assert!(!CV_init(handle, base_url.as_ptr(), 123, 20000, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert_eq!(CV_get_last_error_code(handle), 8);
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 4096, 4096, version.as_ptr(), 0, 1024, 256, 256, 5000, 30000));
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_channel_count() {
+        let handle = CV_create();
+        assert_eq!(CV_get_channel_count(handle, 0), -1);
+        assert_eq!(CV_get_last_error_code(handle), 4);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert!(CV_get_channel_count(handle, 0) >= 1);
+        assert_eq!(CV_get_last_error_code(handle), 0);
+        assert_eq!(CV_get_channel_count(handle, 99), -1);
+        assert_eq!(CV_get_last_error_code(handle), 3);
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_channel_frame() {
+        let handle = CV_create();
+        assert!(CV_get_channel_frame(handle, 0, 0).is_null());
+        assert_eq!(CV_get_last_error_code(handle), 4);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        let mut frame = ptr::null();
+        assert_eq!(CV_get_frame_wait(handle, 0, 5000, &mut frame), CV_WAIT_READY);
+        let size = CV_get_frame_size(handle) as usize;
+        let merged = unsafe { std::slice::from_raw_parts(frame as *const u8, size) }.to_vec();
+
+        // The test file has a single channel, which is the whole mask
+        assert_eq!(CV_get_channel_count(handle, 0), 1);
+        let layer = CV_get_channel_frame(handle, 0, 0);
+        assert!(!layer.is_null());
+        assert_eq!(unsafe { std::slice::from_raw_parts(layer as *const u8, size) }, &merged[..]);
+        assert!(CV_get_channel_frame(handle, 0, 1).is_null());
+        assert_eq!(CV_get_last_error_code(handle), 1);
+
+        // Layers come in the output packing, like CV_get_frame
+        assert!(CV_set_output_packing(handle, CV_PIXEL_FORMAT_RGBA8, 1, false));
+        let size = CV_get_frame_size(handle) as usize;
+        assert_eq!(size, 16 * 16 * 4);
+        let layer = CV_get_channel_frame(handle, 0, 0);
+        assert!(!layer.is_null());
+        let packed = unsafe { std::slice::from_raw_parts(layer as *const u8, size) };
+        assert!(packed.chunks(4).zip(&merged).all(|(pixel, &value)| pixel.contains(&value)));
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_global_init() {
+        // A cap no other test reaches, restored afterwards: tests share the process
+        CV_global_init(10_000);
+        assert_eq!(scheduler::global_decode_limit().get(), 10_000);
+        CV_global_init(0);
+        assert_eq!(scheduler::global_decode_limit().get(), 0);
+    }
+
+    #[test]
+    fn test_c_abi_report_display_size() {
+        let handle = CV_create();
+        assert_eq!(CV_report_display_size(handle, 8, 8), -1);
+        assert_eq!(CV_get_last_error_code(handle), 4);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert_eq!(CV_report_display_size(handle, 4, 4), 4);
+        let mut data = ptr::null_mut();
+        let mut len = 0;
+        let _ = CV_take_frame(handle, 0, &mut data, &mut len);
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        assert!(CV_take_frame(handle, 0, &mut data, &mut len));
+        assert_eq!(len, 16 * 16);
+        CV_free_buffer(data, len);
+        assert_eq!(CV_report_display_size(handle, 0, 0), 1);
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_empty_frame_policy() {
+        let handle = CV_create();
+        assert!(!CV_set_empty_frame_policy(handle, CV_EMPTY_FRAME_REUSE_LAST));
+        assert_eq!(CV_get_last_error_code(handle), 4);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert!(!CV_set_empty_frame_policy(handle, 7));
+        assert_eq!(CV_get_last_error_code(handle), 1);
+        assert!(CV_set_empty_frame_policy(handle, CV_EMPTY_FRAME_ERROR));
+        assert_eq!(CV_get_last_error_code(handle), 0);
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_processing_mode() {
+        let handle = CV_create();
+        assert!(!CV_set_processing_mode(handle, CV_PROCESSING_MODE_BITMAP));
+        assert_eq!(CV_get_last_error_code(handle), 4);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert!(!CV_set_processing_mode(handle, 7));
+        assert_eq!(CV_get_last_error_code(handle), 1);
+
+        // A frame decoded without triangle strips is decoded again once they are asked for
+        assert!(CV_set_processing_mode(handle, CV_PROCESSING_MODE_BITMAP));
+        let mut frame: *const c_void = ptr::null();
+        assert_eq!(CV_get_frame_wait(handle, 0, 5000, &mut frame), CV_WAIT_READY);
+        assert!(CV_set_processing_mode(handle, CV_PROCESSING_MODE_BOTH));
+        let mut output = CVFrameOutput::default();
+        assert_eq!(CV_get_frame_wait(handle, 0, 5000, &mut frame), CV_WAIT_READY);
+        assert!(CV_get_frame_output(handle, 0, &mut output));
+        assert!(!output.vertices.is_null());
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_output_size() {
+        let handle = CV_create();
+        assert!(!CV_set_output_size(handle, 32, 32));
+        assert_eq!(CV_get_last_error_code(handle), 4);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        let mut frame: *const c_void = ptr::null();
+        assert_eq!(CV_get_frame_wait(handle, 0, 5000, &mut frame), CV_WAIT_READY);
+
+        // The cached frame is rasterized again at the new size
+        assert!(CV_set_output_size(handle, 32, 24));
+        assert_eq!(CV_get_frame_size(handle), 32 * 24);
+        let mut output = CVFrameOutput::default();
+        assert_eq!(CV_get_frame_wait(handle, 0, 5000, &mut frame), CV_WAIT_READY);
+        assert!(CV_get_frame_output(handle, 0, &mut output));
+        assert_eq!(output.bitmap_size, 32 * 24);
+
+        // Too large: refused, the size stays
+        assert!(!CV_set_output_size(handle, u32::MAX, 24));
+        assert_eq!(CV_get_last_error_code(handle), 8);
+        assert_eq!(CV_get_frame_size(handle), 32 * 24);
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_init_asvr() {
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        // A renamed copy: the key still comes from the original file name
+        let dir = tempfile::tempdir().unwrap();
+        let renamed = dir.path().join("renamed.asvr");
+        std::fs::copy(test_file.path(), &renamed).unwrap();
+        let path = CString::new(renamed.to_str().unwrap()).unwrap();
+        let base_url = CString::new(test_file.path().file_name().unwrap().to_str().unwrap()).unwrap();
+
+        let handle = CV_create();
+        assert!(!CV_init(handle, path.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert_eq!(CV_get_last_error_code(handle), 2);
+        assert!(!CV_init_asvr(handle, path.as_ptr(), 123, version.as_ptr(), ptr::null(), 16, 16, 512, 256, 5000));
+        assert_eq!(CV_get_last_error_code(handle), 1);
+        assert!(CV_init_asvr(handle, path.as_ptr(), 123, version.as_ptr(), base_url.as_ptr(), 16, 16, 512, 256, 5000));
+        let _ = CV_get_frame(handle, 0);
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        assert!(!CV_get_frame(handle, 0).is_null());
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_init_environment_defaults() {
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        std::env::set_var("ALPHASTREAM_CACHE_CAPACITY", "7");
+        std::env::set_var("ALPHASTREAM_THREADS", "2");
+        let handle = CV_create();
+        let initialized = CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 64, 16, 5000, 30000);
+        std::env::remove_var("ALPHASTREAM_CACHE_CAPACITY");
+        std::env::remove_var("ALPHASTREAM_THREADS");
+        assert!(initialized);
+
+        // l1_buffer_length is the caller's; the thread count is not a CV_init argument, so the variable sets it
+        let proc = unsafe { (*handle).processor.as_ref().unwrap() };
+        assert_eq!(proc.cache_capacity(), 64);
+        assert!(proc.config().to_json().contains("\"runtime_threads\": 2"));
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_init_from_memory() {
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let data = std::fs::read(test_file.path()).unwrap();
+        let base_url = CString::new(test_file.path().file_name().unwrap().to_str().unwrap()).unwrap();
+
+        let handle = CV_create();
+        assert!(!CV_init_from_memory(handle, ptr::null(), 0, 123, version.as_ptr(), base_url.as_ptr(), 16, 16, 512, 256, 5000));
+        assert_eq!(CV_get_last_error_code(handle), 1);
+        assert!(CV_init_from_memory(handle, data.as_ptr(), data.len(), 123, version.as_ptr(), base_url.as_ptr(), 16, 16, 512, 256, 5000));
+        // The contents were copied
+        drop(data);
+        let mut frame = ptr::null();
+        assert_eq!(CV_get_frame_wait(handle, 0, 5000, &mut frame), CV_WAIT_READY);
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_get_frame_wait() {
+        let handle = CV_create();
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        let mut frame = ptr::null();
+        assert_eq!(CV_get_frame_wait(handle, 0, 100, &mut frame), CV_WAIT_FAILED);
+        assert_eq!(CV_get_last_error_code(handle), 4);
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+
+        assert_eq!(CV_get_frame_wait(handle, 0, 5000, &mut frame), CV_WAIT_READY);
+        assert!(!frame.is_null());
+        assert_eq!(CV_get_last_error_code(handle), 0);
+        assert_eq!(CV_get_frame_wait(handle, 0, 0, ptr::null_mut()), CV_WAIT_FAILED);
+        CV_destroy(handle);
+
+        // Without background processing nothing gets decoded, so the wait runs into its timeout
+        let handle = CV_create();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        unsafe {
+            let chandle = &mut *handle;
+            chandle.runtime.as_ref().unwrap().block_on(chandle.processor.as_mut().unwrap().shutdown());
+        }
+        assert_eq!(CV_get_frame_wait(handle, 0, 50, &mut frame), CV_WAIT_TIMEOUT);
+        assert!(frame.is_null());
+        assert_eq!(CV_get_last_error_code(handle), 3);
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_cancel_and_shutdown() {
+        let handle = CV_create();
+        assert!(!CV_cancel_all(handle));
+        assert!(!CV_shutdown(handle));
+        assert_eq!(CV_get_last_error_code(handle), 4);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 8).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert!(CV_prefetch_range(handle, 0, 8, CV_PRIORITY_LOW));
+        assert!(CV_cancel_all(handle));
+        // Cancelled frames can be requested again
+        let mut frame: *const c_void = ptr::null();
+        for index in [0, 5] {
+            assert_eq!(CV_get_frame_wait(handle, index, 5000, &mut frame), CV_WAIT_READY);
+        }
+        let first = unsafe { *(frame as *const u8) };
+
+        assert!(CV_shutdown(handle));
+        assert_eq!(CV_get_last_error_code(handle), 0);
+        unsafe {
+            assert!((*handle).processor.is_none() && (*handle).runtime.is_none());
+        }
+        // The handle is uninitialized now, but buffers it returned live until CV_destroy
+        assert!(CV_get_frame(handle, 0).is_null());
+        assert_eq!(CV_get_last_error_code(handle), 4);
+        assert!(!CV_shutdown(handle));
+        assert_eq!(unsafe { *(frame as *const u8) }, first);
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_get_frames() {
+        let handle = CV_create();
+        let mut frames = [ptr::null(); 6];
+        let mut ready = 0;
+        assert!(!CV_get_frames(handle, 0, 6, frames.as_mut_ptr(), &mut ready));
+        assert_eq!(CV_get_last_error_code(handle), 4);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 4).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert!(!CV_get_frames(handle, 0, 6, ptr::null_mut(), &mut ready));
+        assert_eq!(CV_get_last_error_code(handle), 1);
+
+        let mut frame = ptr::null();
+        assert_eq!(CV_get_frame_wait(handle, 0, 5000, &mut frame), CV_WAIT_READY);
+        let expected = unsafe { std::slice::from_raw_parts(frame as *const u8, 256) }.to_vec();
+        assert!(CV_get_frames(handle, 0, 6, frames.as_mut_ptr(), &mut ready));
+        assert_eq!(ready as usize, frames.iter().filter(|frame| !frame.is_null()).count());
+        assert_eq!(unsafe { std::slice::from_raw_parts(frames[0] as *const u8, 256) }, expected.as_slice());
+        // Past the last frame
+        assert!(frames[4].is_null() && frames[5].is_null());
+
+        // Missing frames were scheduled, so they turn up in a later batch
+        let start = std::time::Instant::now();
+        while ready < 4 && start.elapsed() < std::time::Duration::from_secs(5) {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            assert!(CV_get_frames(handle, 0, 6, frames.as_mut_ptr(), &mut ready));
+        }
+        assert_eq!(ready, 4);
+        // The batch does not replace the CV_get_frame buffer
+        assert_eq!(unsafe { std::slice::from_raw_parts(frame as *const u8, 256) }, expected.as_slice());
+        assert!(CV_get_frames(handle, 0, 0, ptr::null_mut(), &mut ready));
+        assert_eq!(ready, 0);
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_prefetch_range() {
+        let handle = CV_create();
+        assert!(!CV_prefetch_range(handle, 50, 10, CV_PRIORITY_NORMAL));
+        assert_eq!(CV_get_last_error_code(handle), 4);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 100).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert!(!CV_prefetch_range(handle, 50, 10, 7));
+        assert_eq!(CV_get_last_error_code(handle), 1);
+        assert!(CV_prefetch_range(handle, 0, 0, CV_PRIORITY_LOW));
+        assert!(CV_prefetch_range(handle, u64::MAX, 10, CV_PRIORITY_LOW));
+
+        // The frames are decoded without being asked for
+        assert!(CV_prefetch_range(handle, 50, 10, CV_PRIORITY_NORMAL));
+        let proc = unsafe { (*handle).processor.as_ref().unwrap() };
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while (50..60).any(|frame| proc.cached_frame(frame).is_none()) {
+            assert!(std::time::Instant::now() < deadline, "prefetched frames were not decoded");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_decode_budget() {
+        let handle = CV_create();
+        assert!(!CV_set_decode_budget(handle, 4.0));
+        assert_eq!(CV_get_last_error_code(handle), 4);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert!(CV_set_decode_budget(handle, 4.0));
+        assert!(CV_set_decode_budget(handle, 100.0));
+        // Requested frames are decoded within the budget
+        let _ = CV_get_frame(handle, 0);
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        assert!(!CV_get_frame(handle, 0).is_null());
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_output_packing() {
+        let handle = CV_create();
+        assert!(!CV_set_output_packing(handle, CV_PIXEL_FORMAT_R16, 64, true));
+        assert_eq!(CV_get_last_error_code(handle), 4);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 10, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert!(!CV_set_output_packing(handle, 7, 1, false));
+        assert_eq!(CV_get_last_error_code(handle), 1);
+        // 10 pixels of 2 bytes, padded to 64
+        assert!(CV_set_output_packing(handle, CV_PIXEL_FORMAT_R16, 64, true));
+        assert_eq!(CV_get_frame_size(handle), 64 * 16);
+
+        let mut data = ptr::null_mut();
+        let mut len = 0;
+        let _ = CV_take_frame(handle, 0, &mut data, &mut len);
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        assert!(CV_take_frame(handle, 0, &mut data, &mut len));
+        assert_eq!(len, 64 * 16);
+        CV_free_buffer(data, len);
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_frame_fmt() {
+        let handle = CV_create();
+        assert!(CV_get_frame_fmt(handle, 0, CV_PIXEL_FORMAT_RGBA8).is_null());
+        assert_eq!(CV_get_last_error_code(handle), 4);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert!(CV_get_frame_fmt(handle, 0, 42).is_null());
+        assert_eq!(CV_get_last_error_code(handle), 1);
+
+        let mut frame = ptr::null();
+        assert_eq!(CV_get_frame_wait(handle, 0, 5000, &mut frame), CV_WAIT_READY);
+        let mask = unsafe { std::slice::from_raw_parts(frame as *const u8, 256) }.to_vec();
+        for format in [CV_PIXEL_FORMAT_RGBA8, CV_PIXEL_FORMAT_BGRA8] {
+            let pixels = CV_get_frame_fmt(handle, 0, format);
+            let pixels = unsafe { std::slice::from_raw_parts(pixels as *const u8, 256 * 4) };
+            assert!(pixels.chunks_exact(4).zip(&mask).all(|(pixel, &alpha)| pixel == [255, 255, 255, alpha]));
+        }
+        let alpha = CV_get_frame_fmt(handle, 0, CV_PIXEL_FORMAT_A8);
+        assert_eq!(unsafe { std::slice::from_raw_parts(alpha as *const u8, 256) }, mask.as_slice());
+        // The configured format is untouched
+        assert_eq!(CV_get_frame_size(handle), 256);
+        CV_destroy(handle);
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "leak-tracking"))]
+    fn test_debug_dump_leaks_counts_handles_and_buffers() {
+        let handle = CV_create();
+        assert!(live_allocations(Allocation::Handle) >= 1);
+        unsafe {
+            let chandle = &mut *handle;
+            chandle.store_frame_buffer(vec![0; 16]);
+            chandle.store_frame_buffer(vec![0; 16]);
+            chandle.store_vertices_buffer(vec![0.0; 6]);
+        }
+        assert!(live_allocations(Allocation::FrameBuffer) >= 1);
+        assert!(live_allocations(Allocation::VertexBuffer) >= 1);
+        assert!(CV_debug_dump_leaks() >= 3);
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_vendor_prototypes() {
+        // Prototypes of the original plugin's exports, as called through the docs/REVERSE_ENGINEERING.md harness
+        type Handle = *mut AlphaStreamCHandle;
+        let _: extern "C" fn() -> Handle = CV_create;
+        let _: extern "C" fn(Handle) = CV_destroy;
+        let _: extern "C" fn(Handle, *const c_char, c_uint, c_uint, c_uint, *const c_char, c_uint, c_uint, c_uint, c_uint, c_uint, c_uint) -> bool = CV_init;
+        let _: extern "C" fn(Handle) -> *const c_char = CV_get_name;
+        let _: extern "C" fn(Handle) -> *const c_char = CV_get_version;
+        let _: extern "C" fn(Handle) -> *const c_char = CV_get_last_error_text;
+        let _: extern "C" fn(Handle) -> c_int = CV_get_last_error_code;
+        let _: extern "C" fn(Handle) -> c_uint = CV_get_frame_size;
+        let _: extern "C" fn(Handle) -> c_uint = CV_get_total_frames;
+        #[cfg(feature = "vendor-abi")]
+        let _: extern "C" fn(Handle, std::ffi::c_ulong) -> *const c_void = CV_get_frame;
+        #[cfg(not(feature = "vendor-abi"))]
+        let _: extern "C" fn(Handle, c_ulonglong) -> *const c_void = CV_get_frame;
+    }
+
+    #[test]
+    fn test_c_abi_stats() {
+        let handle = CV_create();
+        let mut stats = CVStats::default();
+        assert!(!CV_get_stats(handle, &mut stats));
+        assert_eq!(CV_get_last_error_code(handle), 4);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert!(!CV_get_stats(handle, ptr::null_mut()));
+        assert_eq!(CV_get_last_error_code(handle), 1);
+        let mut frame: *const c_void = ptr::null();
+        assert_eq!(CV_get_frame_wait(handle, 0, 5000, &mut frame), CV_WAIT_READY);
+        assert!(!CV_get_frame(handle, 0).is_null());
+        assert!(CV_get_stats(handle, &mut stats));
+        assert!(stats.cache_hits >= 2);
+        assert!(stats.ready_frames >= 1);
+        assert!(stats.average_decode_ms > 0.0);
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_metadata() {
+        let handle = CV_create();
+        let mut metadata = CVMetadata::default();
+        assert!(!CV_get_metadata(handle, &mut metadata));
+        assert_eq!(CV_get_last_error_code(handle), 4);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+        assert!(!CV_get_metadata(handle, ptr::null_mut()));
+        assert_eq!(CV_get_last_error_code(handle), 1);
+        assert!(CV_get_metadata(handle, &mut metadata));
+        assert_eq!(metadata.frame_count, CV_get_total_frames(handle));
+        assert_eq!((metadata.width, metadata.height), (16, 16));
+        assert_eq!(metadata.frame_size, CV_get_frame_size(handle));
+        assert_eq!(metadata.channel_count, 1);
+        assert_eq!(metadata.fps, 60.0);
+        assert_eq!(metadata.file_size, std::fs::metadata(test_file.path()).unwrap().len());
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_event_pump() {
+        struct Received {
+            thread: std::thread::ThreadId,
+            events: Vec<(c_int, c_ulonglong)>,
+        }
+        extern "C" fn on_event(user_data: *mut c_void, event: c_int, value: c_ulonglong) {
+            let received = unsafe { &mut *(user_data as *mut Received) };
+            assert_eq!(std::thread::current().id(), received.thread);
+            received.events.push((event, value));
+        }
+
+        let handle = CV_create();
+        let mut received = Received { thread: std::thread::current().id(), events: Vec::new() };
+        assert!(CV_set_event_callback(handle, Some(on_event), &mut received as *mut Received as *mut c_void));
+        // Nothing to deliver before init
+        assert_eq!(CV_run_callbacks_on_thread(handle), 0);
+
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+
+        let _ = CV_get_frame(handle, 0);
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        // Callbacks only run here, on this thread
+        assert!(received.events.is_empty());
+        let delivered = CV_run_callbacks_on_thread(handle);
+        assert_eq!(delivered as usize, received.events.len());
+        assert!(received.events.contains(&(CV_EVENT_FRAME_READY, 0)));
+        assert_eq!(CV_run_callbacks_on_thread(handle), 0);
+
+        assert_eq!(CV_run_callbacks_on_thread(ptr::null_mut()), -1);
+        CV_destroy(handle);
+    }
+
+    #[test]
+    fn test_c_abi_frame_ready_callback() {
+        struct Received {
+            thread: std::thread::ThreadId,
+            frames: Vec<(c_ulonglong, Vec<u8>)>,
+        }
+        extern "C" fn on_frame(frame_index: c_ulonglong, data: *const u8, len: usize, user_data: *mut c_void) {
+            let received = unsafe { &mut *(user_data as *mut Received) };
+            assert_eq!(std::thread::current().id(), received.thread);
+            received.frames.push((frame_index, unsafe { std::slice::from_raw_parts(data, len) }.to_vec()));
+        }
+
+        let handle = CV_create();
+        let mut received = Received { thread: std::thread::current().id(), frames: Vec::new() };
+        assert!(CV_set_frame_ready_callback(handle, Some(on_frame), &mut received as *mut Received as *mut c_void));
+        let version = CString::new("1.0.0").unwrap();
+        let test_file = create_test_asvr(123, version.as_bytes(), 1).unwrap();
+        let base_url = CString::new(test_file.path().to_str().unwrap()).unwrap();
+        assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+
+        let mut frame = ptr::null();
+        assert_eq!(CV_get_frame_wait(handle, 0, 5000, &mut frame), CV_WAIT_READY);
+        // Frame callbacks alone are enough for events to be queued; they run on this thread only
+        assert!(received.frames.is_empty());
+        assert!(CV_run_callbacks_on_thread(handle) >= 1);
+        // Prefetched frames arrive too
+        let (_, data) = received.frames.iter().find(|(frame_index, _)| *frame_index == 0).unwrap();
+        assert_eq!(data.as_slice(), unsafe { std::slice::from_raw_parts(frame as *const u8, 256) });
+
+        assert!(CV_set_frame_ready_callback(handle, None, ptr::null_mut()));
+        assert!(!CV_set_frame_ready_callback(ptr::null_mut(), None, ptr::null_mut()));
+        CV_destroy(handle);
+    }
 }
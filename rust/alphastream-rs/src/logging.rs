@@ -0,0 +1,127 @@
+// Logging module
+// Process-wide log level for the library's diagnostic messages. Messages go to stdout with an
+// `[alphastream]` prefix (warnings and errors to stderr); the level is set by the builder, so ops can
+// silence or widen the output of a deployed plugin through ALPHASTREAM_LOG_LEVEL or alphastream.toml.
+// Hosts whose console nobody sees (.NET, Unity) install a sink instead (CV_set_log_callback), which
+// receives every message up to its own level in place of the console.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::formats::FormatError;
+
+/// Severity of a log message, and the most verbose severity that is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    /// Print nothing
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    #[default]
+    Info = 3,
+    Debug = 4,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+impl LogLevel {
+    fn from_u8(value: u8) -> LogLevel {
+        match value {
+            0 => LogLevel::Off,
+            1 => LogLevel::Error,
+            2 => LogLevel::Warn,
+            3 => LogLevel::Info,
+            _ => LogLevel::Debug,
+        }
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = FormatError;
+
+    /// Parse a level name, case-insensitive: off, error, warn, info, debug
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" | "none" => Ok(LogLevel::Off),
+            "error" => Ok(LogLevel::Error),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "info" => Ok(LogLevel::Info),
+            "debug" => Ok(LogLevel::Debug),
+            _ => Err(FormatError::InvalidFormat(format!("Unknown log level '{}'", s))),
+        }
+    }
+}
+
+/// Receiver of log messages in place of stdout/stderr. Called on whichever thread logs, often a worker.
+pub type LogSink = Arc<dyn Fn(LogLevel, &str) + Send + Sync>;
+
+/// The installed sink and the most verbose level it receives
+static SINK: RwLock<Option<(LogLevel, LogSink)>> = RwLock::new(None);
+
+/// Send messages up to `level` to `sink` instead of printing them, or print them again with None
+pub fn set_sink(sink: Option<(LogLevel, LogSink)>) {
+    *SINK.write().unwrap_or_else(|e| e.into_inner()) = sink;
+}
+
+fn sink() -> Option<(LogLevel, LogSink)> {
+    SINK.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Set the process-wide log level
+pub fn set_level(level: LogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+/// Current process-wide log level
+pub fn level() -> LogLevel {
+    LogLevel::from_u8(LEVEL.load(Ordering::Relaxed))
+}
+
+/// Whether messages of `level` are printed, or passed to the sink
+#[cfg(feature = "unstable")]
+pub fn enabled(level: LogLevel) -> bool {
+    let most_verbose = sink().map_or_else(self::level, |(sink_level, _)| sink_level);
+    level != LogLevel::Off && level <= most_verbose
+}
+
+/// Print a message if its level is enabled, or pass it to the sink
+pub fn log(level: LogLevel, message: fmt::Arguments) {
+    if level == LogLevel::Off {
+        return;
+    }
+    // Called outside the lock, so a sink may log or replace itself
+    if let Some((sink_level, sink)) = sink() {
+        if level <= sink_level {
+            sink(level, &message.to_string());
+        }
+        return;
+    }
+    if level > self::level() {
+        return;
+    }
+    match level {
+        LogLevel::Error | LogLevel::Warn => eprintln!("[alphastream] {}", message),
+        _ => println!("[alphastream] {}", message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_order() {
+        assert_eq!("WARN".parse::<LogLevel>().unwrap(), LogLevel::Warn);
+        assert_eq!(" off ".parse::<LogLevel>().unwrap(), LogLevel::Off);
+        assert!("verbose".parse::<LogLevel>().is_err());
+        assert!(LogLevel::Error < LogLevel::Debug);
+        for level in [LogLevel::Off, LogLevel::Error, LogLevel::Warn, LogLevel::Info, LogLevel::Debug] {
+            assert_eq!(LogLevel::from_u8(level as u8), level);
+        }
+    }
+}
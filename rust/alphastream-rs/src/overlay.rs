@@ -0,0 +1,206 @@
+// Overlay module
+// QC burn-in for exported masks: the frame number, a timecode and the outline of the covered pixels'
+// bounding box are drawn into the R8 mask itself, so reviewers see them in any player and can match a
+// frame of the export to its source frame. Text uses a built-in 3x5 pixel font (digits and ':'),
+// scaled with the mask height, white on a black box so it reads over covered and empty pixels alike.
+
+use thiserror::Error;
+
+use crate::stats::{BoundingBox, MaskStats};
+
+/// Pixel value of the text
+const TEXT_VALUE: u8 = 255;
+/// Pixel value of the bounding box outline, distinct from both covered and empty pixels
+const BBOX_VALUE: u8 = 128;
+/// Glyph size in font cells, and the cells from one glyph / line to the next
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+const ADVANCE: u32 = GLYPH_WIDTH + 1;
+const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 1;
+
+/// Rows of a 3x5 glyph, the leftmost cell in bit 2
+fn glyph(c: char) -> Option<[u8; 5]> {
+    Some(match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        ' ' => [0; 5],
+        _ => return None,
+    })
+}
+
+/// An overlay item list with a name other than `frame`, `timecode` or `bbox`
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Unknown overlay item '{0}' (expected frame, timecode or bbox)")]
+pub struct UnknownOverlayItem(pub String);
+
+/// What to burn into exported frames
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Overlay {
+    /// Source frame index, top left
+    pub frame_number: bool,
+    /// HH:MM:SS:FF timecode of the frame at `fps`, below the frame number
+    pub timecode: bool,
+    /// Outline of the bounding box of the covered pixels, measured before the text is drawn
+    pub bounding_box: bool,
+    /// Frame rate of the timecode. Non-drop-frame: frames are counted at the rate rounded to a whole
+    /// number, e.g. 60 per second for 59.94.
+    pub fps: f64,
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self { frame_number: false, timecode: false, bounding_box: false, fps: 60.0 }
+    }
+}
+
+impl Overlay {
+    /// Parse a comma separated list of `frame`, `timecode` and `bbox`, as taken by the CLI `--overlay` option
+    pub fn parse(items: &str, fps: f64) -> Result<Self, UnknownOverlayItem> {
+        let mut overlay = Overlay { fps, ..Overlay::default() };
+        for item in items.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            match item {
+                "frame" => overlay.frame_number = true,
+                "timecode" => overlay.timecode = true,
+                "bbox" => overlay.bounding_box = true,
+                _ => return Err(UnknownOverlayItem(item.to_string())),
+            }
+        }
+        Ok(overlay)
+    }
+
+    /// Whether nothing is drawn
+    pub fn is_empty(&self) -> bool {
+        !(self.frame_number || self.timecode || self.bounding_box)
+    }
+
+    /// Timecode of `frame_index`, HH:MM:SS:FF
+    pub fn timecode(&self, frame_index: u32) -> String {
+        let rate = if self.fps.is_finite() { self.fps.round().max(1.0) as u32 } else { 1 };
+        let seconds = frame_index / rate;
+        format!("{:02}:{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60, frame_index % rate)
+    }
+
+    /// Draw the overlay into a row-major R8 mask of `width * height` bytes
+    pub fn apply(&self, mask: &mut [u8], width: u32, height: u32, frame_index: u32) {
+        if mask.len() < width as usize * height as usize {
+            return;
+        }
+        // Measured first, so the text does not count as covered
+        let bbox = self.bounding_box.then(|| MaskStats::from_mask(mask, width, height).bbox);
+        let mut lines = Vec::new();
+        if self.frame_number {
+            lines.push(frame_index.to_string());
+        }
+        if self.timecode {
+            lines.push(self.timecode(frame_index));
+        }
+        if !lines.is_empty() {
+            draw_text(mask, width, height, &lines);
+        }
+        if let Some(bbox) = bbox.filter(|bbox| bbox.w > 0 && bbox.h > 0) {
+            draw_rect(mask, width, bbox);
+        }
+    }
+}
+
+/// Draw `lines` at the top left on a black box, one font cell of padding around them
+fn draw_text(mask: &mut [u8], width: u32, height: u32, lines: &[String]) {
+    let scale = (height / 90).max(1);
+    let columns = lines.iter().map(|line| line.chars().count() as u32).max().unwrap_or(0);
+    let box_width = ((columns * ADVANCE + 1) * scale).min(width);
+    let box_height = ((lines.len() as u32 * LINE_HEIGHT + 1) * scale).min(height);
+    for y in 0..box_height {
+        let row = (y * width) as usize;
+        mask[row..row + box_width as usize].fill(0);
+    }
+    for (line_index, line) in lines.iter().enumerate() {
+        let top = (1 + line_index as u32 * LINE_HEIGHT) * scale;
+        for (column, c) in line.chars().enumerate() {
+            let Some(rows) = glyph(c) else { continue };
+            let left = (1 + column as u32 * ADVANCE) * scale;
+            for (cell_y, bits) in rows.iter().enumerate() {
+                for cell_x in 0..GLYPH_WIDTH {
+                    if (bits >> (GLYPH_WIDTH - 1 - cell_x)) & 1 == 0 {
+                        continue;
+                    }
+                    let (x0, y0) = (left + cell_x * scale, top + cell_y as u32 * scale);
+                    for y in y0..(y0 + scale).min(height) {
+                        for x in x0..(x0 + scale).min(width) {
+                            mask[(y * width + x) as usize] = TEXT_VALUE;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Outline `bbox`, which lies inside the mask
+fn draw_rect(mask: &mut [u8], width: u32, bbox: BoundingBox) {
+    let (right, bottom) = (bbox.x + bbox.w - 1, bbox.y + bbox.h - 1);
+    for x in bbox.x..=right {
+        mask[(bbox.y * width + x) as usize] = BBOX_VALUE;
+        mask[(bottom * width + x) as usize] = BBOX_VALUE;
+    }
+    for y in bbox.y..=bottom {
+        mask[(y * width + bbox.x) as usize] = BBOX_VALUE;
+        mask[(y * width + right) as usize] = BBOX_VALUE;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_timecode() {
+        let overlay = Overlay::parse("frame, bbox", 59.94).unwrap();
+        assert!(overlay.frame_number && overlay.bounding_box && !overlay.timecode);
+        assert_eq!(Overlay::parse("frame,subtitles", 60.0), Err(UnknownOverlayItem("subtitles".to_string())));
+        assert!(Overlay::parse("", 60.0).unwrap().is_empty());
+
+        // 59.94 counts 60 frames per second
+        assert_eq!(overlay.timecode(0), "00:00:00:00");
+        assert_eq!(overlay.timecode(61), "00:00:01:01");
+        assert_eq!(overlay.timecode(60 * 3661 + 59), "01:01:01:59");
+        assert_eq!(Overlay { fps: 25.0, ..overlay }.timecode(50), "00:00:02:00");
+    }
+
+    #[test]
+    fn test_apply() {
+        let (width, height) = (64, 32);
+        let mut mask = vec![0u8; 64 * 32];
+        // A covered block in the lower right
+        for y in 20..28 {
+            mask[y * 64 + 40..y * 64 + 50].fill(255);
+        }
+        let original = mask.clone();
+        Overlay::default().apply(&mut mask, width, height, 7);
+        assert_eq!(mask, original);
+
+        let overlay = Overlay { frame_number: true, timecode: true, bounding_box: true, fps: 60.0 };
+        overlay.apply(&mut mask, width, height, 7);
+        // The outline follows the block's edges, the inside is untouched
+        assert_eq!((mask[20 * 64 + 40], mask[27 * 64 + 49], mask[24 * 64 + 45]), (BBOX_VALUE, BBOX_VALUE, 255));
+        // "7" starts with a full top row of three cells, one cell in from the corner
+        assert_eq!(&mask[64 + 1..64 + 4], &[TEXT_VALUE; 3]);
+        assert_eq!(mask[0], 0);
+        // Text stays in the top left box of two lines
+        let text_box_height = (2 * LINE_HEIGHT + 1) as usize;
+        assert!(mask[text_box_height * 64..].iter().zip(&original[text_box_height * 64..]).all(|(&a, &b)| a == b || a == BBOX_VALUE));
+
+        // Masks too small for the text are clipped, not overrun
+        let mut tiny = vec![0u8; 4 * 3];
+        overlay.apply(&mut tiny, 4, 3, 123);
+        overlay.apply(&mut [], 4, 3, 123);
+    }
+}
@@ -0,0 +1,124 @@
+// PNG encoding module
+// Minimal encoder for the single-channel images this crate produces (R8 masks, 16-bit heatmaps).
+// Only what is needed to hand masks to browsers and image viewers: no interlacing,
+// no palette, filter type 0 (None) on every row.
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::Write;
+
+/// PNG file signature
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// PNG color type for grayscale images
+const COLOR_TYPE_GRAY: u8 = 0;
+
+/// Encode an 8-bit grayscale image (e.g. an R8 mask) as PNG.
+///
+/// # Arguments
+/// * `pixels` - Row-major pixel data, `width * height` bytes.
+/// * `width` - Image width.
+/// * `height` - Image height.
+///
+/// # Panics
+/// Panics if `pixels.len() != width * height`.
+pub fn encode_gray8(pixels: &[u8], width: u32, height: u32) -> Vec<u8> {
+    assert_eq!(pixels.len(), (width * height) as usize, "pixel buffer does not match dimensions");
+    encode(pixels, width, height, 8, width as usize)
+}
+
+/// Encode a 16-bit grayscale image as PNG.
+///
+/// # Arguments
+/// * `pixels` - Row-major pixel data, `width * height` values.
+/// * `width` - Image width.
+/// * `height` - Image height.
+///
+/// # Panics
+/// Panics if `pixels.len() != width * height`.
+#[cfg(any(test, feature = "unstable"))]
+pub fn encode_gray16(pixels: &[u16], width: u32, height: u32) -> Vec<u8> {
+    assert_eq!(pixels.len(), (width * height) as usize, "pixel buffer does not match dimensions");
+    // PNG stores 16-bit samples big-endian
+    let bytes: Vec<u8> = pixels.iter().flat_map(|p| p.to_be_bytes()).collect();
+    encode(&bytes, width, height, 16, width as usize * 2)
+}
+
+fn encode(pixels: &[u8], width: u32, height: u32, bit_depth: u8, row_bytes: usize) -> Vec<u8> {
+    // IHDR: width, height, bit depth, color type, compression, filter, interlace
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.extend_from_slice(&[bit_depth, COLOR_TYPE_GRAY, 0, 0, 0]);
+
+    // IDAT: each scanline is prefixed with its filter type byte (0 = None)
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+    if row_bytes > 0 {
+        for row in pixels.chunks(row_bytes) {
+            // Writing into a Vec cannot fail
+            encoder.write_all(&[0]).unwrap();
+            encoder.write_all(row).unwrap();
+        }
+    }
+    let idat = encoder.finish().unwrap();
+
+    let mut out = Vec::with_capacity(SIGNATURE.len() + idat.len() + 64);
+    out.extend_from_slice(&SIGNATURE);
+    write_chunk(&mut out, b"IHDR", &ihdr);
+    write_chunk(&mut out, b"IDAT", &idat);
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+/// Append a chunk (length, type, data, CRC over type + data)
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+    out.extend_from_slice(&hasher.finalize().to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn test_encode_gray8_structure() {
+        let pixels = vec![0, 255, 128, 64, 32, 16];
+        let png = encode_gray8(&pixels, 3, 2);
+        assert_eq!(&png[0..8], &SIGNATURE);
+        // IHDR is always the first chunk
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(u32::from_be_bytes(png[16..20].try_into().unwrap()), 3);
+        assert_eq!(u32::from_be_bytes(png[20..24].try_into().unwrap()), 2);
+        assert_eq!(png[24], 8);
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+    }
+
+    #[test]
+    fn test_encode_gray8_idat_roundtrip() {
+        let pixels = vec![1, 2, 3, 4];
+        let png = encode_gray8(&pixels, 2, 2);
+        // Signature (8) + IHDR chunk (4 + 4 + 13 + 4) = 33, then IDAT length and type
+        let idat_len = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        assert_eq!(&png[37..41], b"IDAT");
+        let mut raw = Vec::new();
+        ZlibDecoder::new(&png[41..41 + idat_len]).read_to_end(&mut raw).unwrap();
+        assert_eq!(raw, vec![0, 1, 2, 0, 3, 4]);
+    }
+
+    #[test]
+    fn test_encode_gray16_big_endian_rows() {
+        let png = encode_gray16(&[0x0102, 0xA0B0], 1, 2);
+        assert_eq!(png[24], 16);
+        let idat_len = u32::from_be_bytes(png[33..37].try_into().unwrap()) as usize;
+        let mut raw = Vec::new();
+        ZlibDecoder::new(&png[41..41 + idat_len]).read_to_end(&mut raw).unwrap();
+        assert_eq!(raw, vec![0, 0x01, 0x02, 0, 0xA0, 0xB0]);
+    }
+}
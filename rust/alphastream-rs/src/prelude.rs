@@ -0,0 +1,37 @@
+// Prelude module
+// The stable Rust API: the processor and its builder, errors and frame types. Everything reachable from
+// here follows semver (checked in CI with cargo-semver-checks), so downstream crates can depend on it with
+// `use libalphastream::prelude::*;`. The other modules are internals, only public with the `unstable`
+// feature; the types of theirs that the API takes or returns are re-exported here instead. The C ABI
+// (the CV_* functions) is versioned separately.
+
+pub use crate::access::AccessPattern;
+pub use crate::api::{
+    AlphaStreamProcessor, AlphaStreamProcessorBuilder, Bookmark, ChannelInfo, DecodedFrame, DimensionError,
+    EmptyFramePolicy, ExportReport, FailedFrame, FrameCandidate, FrameErrorKind, FrameOutput, FrameTrace, FrameWaitError,
+    ProcessingMode, ProcessorEvent, ProcessorStats,
+};
+pub use crate::backend::Backend;
+pub use crate::cache::CacheStats;
+pub use crate::clock::{Clock, MockClock, SystemClock};
+pub use crate::container::{Annotation, Cue, CueTrack, Thumbnail, TrackInfo};
+pub use crate::entitlement::{EntitlementError, EntitlementProvider, EntitlementRequest};
+pub use crate::filter::{FilterError, FrameFilter};
+pub use crate::formats::{Compression, FormatError, FrameData, Metadata};
+pub use crate::layout::{ExportLayout, NameTemplate, TemplateError};
+pub use crate::logging::LogLevel;
+pub use crate::overlay::{Overlay, UnknownOverlayItem};
+pub use crate::rasterizer::{
+    ClipRect, OutputPacking, OutputTransform, PixelFormat, PolystreamRasterizer, RasterOptions, Rasterizer, SharedRasterizer,
+    Tile, TriangleMesh, MAX_ROW_ALIGNMENT, NATIVE_HEIGHT, NATIVE_WIDTH,
+};
+pub use crate::runtime::ExecutionMode;
+pub use crate::scheduler::Priority;
+#[cfg(feature = "scripting")]
+pub use crate::api::ScriptedFrame;
+#[cfg(feature = "scripting")]
+pub use crate::script::{FrameScript, ScriptError};
+pub use crate::stats::{BoundingBox, Heatmap, MaskStats};
+pub use crate::store::CacheKey;
+pub use crate::telemetry::{LogExporter, MetricsExporter, NoopExporter, TelemetryEvent, TelemetryExporter, TelemetryMetrics};
+pub use crate::watermark::{Watermark, WatermarkReading};
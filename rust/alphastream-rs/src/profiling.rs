@@ -0,0 +1,83 @@
+// Profiling module
+// With the `tracing` feature every decode task runs in a `frame` span with a child span per stage
+// (wait_reader, read, decrypt, decompress, rasterize, triangulate, cache_insert), so a tracing-flame
+// or other folded-stack subscriber turns a playback workload into a flame graph without ad-hoc timing
+// code, see benches/playback_benchmark.rs. Without the feature the spans compile to nothing.
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::Instrument;
+
+/// Stand-in for `tracing::Span` without the `tracing` feature
+#[cfg(not(feature = "tracing"))]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Span;
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    pub(crate) fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        f()
+    }
+}
+
+/// Stand-in for `tracing::Instrument` without the `tracing` feature
+#[cfg(not(feature = "tracing"))]
+pub(crate) trait Instrument: Sized {
+    fn instrument(self, _span: Span) -> Self {
+        self
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl<T> Instrument for T {}
+
+/// Span of a decode stage of frame `$frame`, entered with `in_scope` or `instrument`
+macro_rules! stage_span {
+    ($name:literal, $frame:expr) => {{
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!($name, frame = $frame as u64);
+        #[cfg(not(feature = "tracing"))]
+        let span = {
+            let _ = $frame;
+            $crate::profiling::Span
+        };
+        span
+    }};
+}
+
+pub(crate) use stage_span;
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tracing::span::{Attributes, Id};
+    use tracing::subscriber::set_default;
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::Layer;
+
+    use crate::formats::{ASFormat, ASVRFormat};
+    use crate::testlib::create_test_asvr;
+
+    /// Records the names of the spans created
+    #[derive(Clone, Default)]
+    struct SpanNames(Arc<Mutex<Vec<&'static str>>>);
+
+    impl<S: tracing::Subscriber> Layer<S> for SpanNames {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            self.0.lock().unwrap().push(attrs.metadata().name());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_decode_stage_spans() {
+        let names = SpanNames::default();
+        let _subscriber = set_default(tracing_subscriber::registry().with(names.clone()));
+        let file = create_test_asvr(7, b"1.0.0", 2).unwrap();
+        let base_url = file.path().file_name().unwrap().to_str().unwrap();
+        let reader = tokio::fs::File::open(file.path()).await.unwrap();
+        let mut format = ASVRFormat::new(reader, 7, b"1.0.0", base_url.as_bytes()).await.unwrap();
+        names.0.lock().unwrap().clear();
+        format.decode_frame(1).await.unwrap();
+        assert_eq!(*names.0.lock().unwrap(), ["read", "decrypt", "decompress"]);
+    }
+}
@@ -1,17 +1,409 @@
 // Rasterizer module for polystream rasterization and image resizing
 
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, OnceLock};
+
+use serde::{Deserialize, Serialize};
+
 /// A rasterizer for polystream data.
 /// Polystreams are encoded as: first 4 bytes are u16 x0, y0 (little-endian),
 /// followed by pairs of i8 dx, dy deltas.
 pub struct PolystreamRasterizer;
 
-const NATIVE_WIDTH: u32 = 2024;
-const NATIVE_HEIGHT: u32 = 1024;
+/// Turns the polystream of one channel into a mask or a triangle strip.
+/// Processors draw every frame through this trait, so applications can supply their own implementation
+/// (e.g. Skia-based) with `AlphaStreamProcessorBuilder::rasterizer`; `PolystreamRasterizer` is the default.
+/// Methods are called from the processor's worker threads concurrently.
+pub trait Rasterizer: Send + Sync {
+    /// Draw `polystream` on top of `mask` (row-major R8, width * height bytes): covered pixels are set to
+    /// 255, the others are left as they are. `options` maps native coordinates to output pixels and tells
+    /// whether to draw the outline.
+    fn rasterize(&self, polystream: &[u8], width: u32, height: u32, options: &RasterOptions, mask: &mut [u8]);
+
+    /// Triangle strip of `polystream` as x,y pairs in native coordinates, simplified with Douglas-Peucker
+    /// `tolerance` in native units (0 disables simplification)
+    fn to_vertices(&self, polystream: &[u8], tolerance: f32) -> Vec<f32>;
+
+    /// Draw one `tile` of the width x height output into a mask of the tile's size, like `rasterize`.
+    /// Used for outputs beyond the backend's texture size. The default draws the whole output and copies
+    /// the tile out of it; override it to draw tiles directly.
+    fn rasterize_tile(&self, polystream: &[u8], width: u32, height: u32, options: &RasterOptions, tile: &Tile, mask: &mut [u8]) {
+        let mut whole = vec![0u8; width as usize * height as usize];
+        self.rasterize(polystream, width, height, options, &mut whole);
+        for (row, pixels) in mask.chunks_exact_mut(tile.width as usize).enumerate() {
+            let start = (tile.y as usize + row) * width as usize + tile.x as usize;
+            for (pixel, &value) in pixels.iter_mut().zip(&whole[start..start + tile.width as usize]) {
+                if value != 0 {
+                    *pixel = value;
+                }
+            }
+        }
+    }
+}
+
+impl Rasterizer for PolystreamRasterizer {
+    fn rasterize(&self, polystream: &[u8], width: u32, height: u32, options: &RasterOptions, mask: &mut [u8]) {
+        Self::rasterize_into(polystream, width, height, options, mask);
+    }
+
+    fn to_vertices(&self, polystream: &[u8], tolerance: f32) -> Vec<f32> {
+        Self::polystream_to_triangle_strip_simplified(polystream, tolerance)
+    }
+
+    fn rasterize_tile(&self, polystream: &[u8], width: u32, height: u32, options: &RasterOptions, tile: &Tile, mask: &mut [u8]) {
+        Self::rasterize_tile_into(polystream, width, height, options, tile, mask);
+    }
+}
+
+/// Shared handle to a rasterizer, as stored by the builder and processor.
+/// Handles compare equal when they point to the same rasterizer.
+#[derive(Clone)]
+pub struct SharedRasterizer(Arc<dyn Rasterizer>);
+
+impl SharedRasterizer {
+    pub fn new(rasterizer: Arc<dyn Rasterizer>) -> Self {
+        Self(rasterizer)
+    }
+
+    /// Whether this is the built-in `PolystreamRasterizer`
+    pub fn is_builtin(&self) -> bool {
+        *self == SharedRasterizer::default()
+    }
+}
+
+impl Default for SharedRasterizer {
+    /// The process-wide `PolystreamRasterizer`
+    fn default() -> Self {
+        static BUILTIN: OnceLock<Arc<dyn Rasterizer>> = OnceLock::new();
+        Self(Arc::clone(BUILTIN.get_or_init(|| Arc::new(PolystreamRasterizer))))
+    }
+}
+
+impl Deref for SharedRasterizer {
+    type Target = dyn Rasterizer;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl PartialEq for SharedRasterizer {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for SharedRasterizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_builtin() {
+            f.write_str("SharedRasterizer(builtin)")
+        } else {
+            f.write_str("SharedRasterizer(custom)")
+        }
+    }
+}
+
+/// Size of the canvas polystream coordinates are authored on
+pub const NATIVE_WIDTH: u32 = 2024;
+pub const NATIVE_HEIGHT: u32 = 1024;
+
+/// Points beyond this are dropped when decoding a polystream; real frames have at most a few thousand
+pub const MAX_POLYSTREAM_POINTS: usize = 1 << 18;
+
+/// Output coordinates are clamped to +-MAX_COORDINATE pixels, far enough outside any output that
+/// clamping never changes a visible span, and small enough that edge arithmetic cannot overflow
+const MAX_COORDINATE: f32 = (1 << 20) as f32;
+
+/// Axis-aligned clip rectangle, bounds inclusive
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipRect {
+    pub min_x: f32,
+    pub min_y: f32,
+    pub max_x: f32,
+    pub max_y: f32,
+}
+
+impl ClipRect {
+    /// The native polystream canvas
+    pub fn native() -> Self {
+        Self { min_x: 0.0, min_y: 0.0, max_x: NATIVE_WIDTH as f32, max_y: NATIVE_HEIGHT as f32 }
+    }
+}
+
+/// Mapping from native polystream coordinates to output pixels: `out = native * scale + offset`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputTransform {
+    pub scale_x: f32,
+    pub scale_y: f32,
+    /// Left letterbox bar in output pixels (0 when stretching)
+    pub offset_x: f32,
+    /// Top letterbox bar in output pixels (0 when stretching)
+    pub offset_y: f32,
+}
+
+impl OutputTransform {
+    /// Stretch the native canvas over the whole output, ignoring aspect ratio
+    pub fn stretch(width: u32, height: u32) -> Self {
+        Self {
+            scale_x: width as f32 / NATIVE_WIDTH as f32,
+            scale_y: height as f32 / NATIVE_HEIGHT as f32,
+            offset_x: 0.0,
+            offset_y: 0.0,
+        }
+    }
+
+    /// Fit a native area into the output preserving its aspect ratio, centered.
+    /// The unused output is split evenly into letterbox (top/bottom) or pillarbox (left/right) bars.
+    ///
+    /// # Arguments
+    /// * `native_width` - Width of the area to fit, in native units.
+    /// * `native_height` - Height of the area to fit, in native units.
+    /// * `width` - Output width in pixels.
+    /// * `height` - Output height in pixels.
+    pub fn fit(native_width: u32, native_height: u32, width: u32, height: u32) -> Self {
+        let scale = (width as f32 / native_width.max(1) as f32).min(height as f32 / native_height.max(1) as f32);
+        Self {
+            scale_x: scale,
+            scale_y: scale,
+            offset_x: ((width as f32 - native_width as f32 * scale) / 2.0).floor(),
+            offset_y: ((height as f32 - native_height as f32 * scale) / 2.0).floor(),
+        }
+    }
+
+    /// The same mapping onto an output `divisor` times smaller in each direction
+    pub fn downscaled(&self, divisor: u32) -> Self {
+        let divisor = divisor.max(1) as f32;
+        Self {
+            scale_x: self.scale_x / divisor,
+            scale_y: self.scale_y / divisor,
+            offset_x: self.offset_x / divisor,
+            offset_y: self.offset_y / divisor,
+        }
+    }
+}
+
+/// Options for `PolystreamRasterizer::rasterize_with_options`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RasterOptions {
+    /// Mapping from native coordinates to output pixels
+    pub transform: OutputTransform,
+    /// Also draw the polygon outline (Bresenham) on top of the fill. Makes thin or sub-pixel
+    /// shapes visible, at the cost of single-pixel spurs outside the filled area.
+    pub outline: bool,
+    /// Rasterize outputs wider or taller than this in tiles of at most this size, e.g. a backend's
+    /// max_texture_size. The tiles join without seams. None draws the whole output at once.
+    pub tile_size: Option<u32>,
+}
+
+impl RasterOptions {
+    /// Fill only, stretching the native canvas over the output
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { transform: OutputTransform::stretch(width, height), outline: false, tile_size: None }
+    }
+}
+
+/// Rectangle of output pixels, the unit of tiled rasterization
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    /// The whole output as a single tile
+    pub fn full(width: u32, height: u32) -> Self {
+        Tile { x: 0, y: 0, width, height }
+    }
+
+    /// Tiles of at most `size` x `size` pixels covering a `width` x `height` output, row by row
+    pub fn grid(width: u32, height: u32, size: u32) -> Vec<Tile> {
+        let size = size.max(1);
+        let mut tiles = Vec::new();
+        for y in (0..height).step_by(size as usize) {
+            for x in (0..width).step_by(size as usize) {
+                tiles.push(Tile { x, y, width: size.min(width - x), height: size.min(height - y) });
+            }
+        }
+        tiles
+    }
+
+    /// Bytes of an R8 mask of the tile
+    pub fn pixel_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+}
+
+/// Indexed triangle list, e.g. for uploading a frame as a mesh
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TriangleMesh {
+    /// x,y pairs in native coordinates, every distinct vertex once
+    pub positions: Vec<f32>,
+    /// u,v pairs per vertex: the position relative to the native canvas, (0,0) top left and (1,1) bottom
+    /// right, so they address the frame's mask as a texture
+    pub uvs: Vec<f32>,
+    /// Three vertex indices per triangle
+    pub indices: Vec<u32>,
+}
+
+impl TriangleMesh {
+    /// Index the triangles of `vertices` (x,y pairs, three vertices per triangle, as returned by
+    /// `Rasterizer::to_vertices`), merging vertices with equal positions
+    pub fn from_triangle_list(vertices: &[f32]) -> Self {
+        let mut mesh = TriangleMesh::default();
+        let mut seen = std::collections::HashMap::new();
+        let triangles = vertices.len() / 6 * 6;
+        for point in vertices[..triangles].chunks_exact(2) {
+            let (x, y) = (point[0], point[1]);
+            let index = *seen.entry((x.to_bits(), y.to_bits())).or_insert_with(|| {
+                mesh.positions.extend_from_slice(&[x, y]);
+                mesh.uvs.extend_from_slice(&[x / NATIVE_WIDTH as f32, y / NATIVE_HEIGHT as f32]);
+                (mesh.positions.len() / 2 - 1) as u32
+            });
+            mesh.indices.push(index);
+        }
+        mesh
+    }
+
+    /// Number of distinct vertices
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len() / 2
+    }
+}
+
+/// Pixel layout of packed output, see `OutputPacking`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PixelFormat {
+    /// One byte per pixel, the mask as it is rasterized
+    #[default]
+    R8,
+    /// 16-bit unorm per pixel, 255 widened to 65535
+    R16,
+    /// 16 bits per pixel: luminance in the low byte (always 255), alpha (the mask) in the high byte
+    La8,
+    /// One byte per pixel like R8, for alpha-only textures
+    A8,
+    /// 32 bits per pixel: white, with the mask as alpha in the last byte
+    Rgba8,
+    /// 32 bits per pixel in B,G,R,A byte order for Direct3D's BGRA textures. The color is white, so the
+    /// bytes equal Rgba8's; both exist so hosts can name the texture format they create.
+    Bgra8,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::R8 | PixelFormat::A8 => 1,
+            PixelFormat::R16 | PixelFormat::La8 => 2,
+            PixelFormat::Rgba8 | PixelFormat::Bgra8 => 4,
+        }
+    }
+}
+
+/// Largest row alignment `OutputPacking` pads to
+pub const MAX_ROW_ALIGNMENT: u32 = 256;
+
+/// Memory layout of the masks handed to the caller, so platforms that upload textures with aligned
+/// rows or big-endian 16-bit texels (console ports) can use frames as they come instead of repacking
+/// every frame. The default is tightly packed R8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputPacking {
+    pub format: PixelFormat,
+    /// Every row starts at a multiple of this many bytes; the padding is zero. A power of two.
+    pub row_alignment: u32,
+    /// Store 16-bit pixels big-endian instead of little-endian. No effect on 8-bit channel formats.
+    pub swap_bytes: bool,
+}
+
+impl Default for OutputPacking {
+    fn default() -> Self {
+        Self { format: PixelFormat::R8, row_alignment: 1, swap_bytes: false }
+    }
+}
+
+impl OutputPacking {
+    /// Tightly packed rows of `format`, little-endian
+    pub fn new(format: PixelFormat) -> Self {
+        Self { format, ..Self::default() }
+    }
+
+    /// Pad rows to a multiple of `bytes`, e.g. 4 or 64; rounded up to a power of two up to MAX_ROW_ALIGNMENT
+    pub fn row_alignment(mut self, bytes: u32) -> Self {
+        self.row_alignment = bytes.clamp(1, MAX_ROW_ALIGNMENT).next_power_of_two();
+        self
+    }
+
+    /// Store 16-bit pixels big-endian
+    pub fn swap_bytes(mut self, enabled: bool) -> Self {
+        self.swap_bytes = enabled;
+        self
+    }
+
+    /// Bytes from the start of one row to the next
+    pub fn row_stride(&self, width: u32) -> usize {
+        let alignment = self.row_alignment.clamp(1, MAX_ROW_ALIGNMENT).next_power_of_two() as usize;
+        (width as usize * self.format.bytes_per_pixel()).next_multiple_of(alignment)
+    }
+
+    /// Size of a packed frame
+    pub fn packed_size(&self, width: u32, height: u32) -> usize {
+        self.row_stride(width) * height as usize
+    }
+
+    /// Whether packed frames are plain R8 masks
+    fn is_tight_r8(&self, width: u32) -> bool {
+        matches!(self.format, PixelFormat::R8 | PixelFormat::A8) && self.row_stride(width) == width as usize
+    }
+
+    /// Like `pack`, returning `mask` itself when it already has this layout
+    pub fn pack_owned(&self, mask: Vec<u8>, width: u32, height: u32) -> Vec<u8> {
+        if self.is_tight_r8(width) {
+            return mask;
+        }
+        self.pack(&mask, width, height)
+    }
+
+    /// Pack an R8 mask of width * height bytes
+    pub fn pack(&self, mask: &[u8], width: u32, height: u32) -> Vec<u8> {
+        if self.is_tight_r8(width) {
+            return mask.to_vec();
+        }
+        let stride = self.row_stride(width);
+        let mut packed = vec![0u8; self.packed_size(width, height)];
+        if width == 0 {
+            return packed;
+        }
+        for (row, out) in mask.chunks_exact(width as usize).zip(packed.chunks_exact_mut(stride)) {
+            match self.format {
+                PixelFormat::R8 | PixelFormat::A8 => out[..row.len()].copy_from_slice(row),
+                PixelFormat::Rgba8 | PixelFormat::Bgra8 => {
+                    for (&alpha, pixel) in row.iter().zip(out.chunks_exact_mut(4)) {
+                        pixel.copy_from_slice(&[0xFF, 0xFF, 0xFF, alpha]);
+                    }
+                }
+                PixelFormat::R16 | PixelFormat::La8 => {
+                    for (&alpha, pixel) in row.iter().zip(out.chunks_exact_mut(2)) {
+                        let value = match self.format {
+                            PixelFormat::La8 => (u16::from(alpha) << 8) | 0xFF,
+                            _ => u16::from(alpha) * 257,
+                        };
+                        pixel.copy_from_slice(&if self.swap_bytes { value.to_be_bytes() } else { value.to_le_bytes() });
+                    }
+                }
+            }
+        }
+        packed
+    }
+}
 
 impl PolystreamRasterizer {
     /// Rasterizes a polystream into an R8 alpha mask.
     /// The polystream is parsed into vertices, edges are built, and scanline
-    /// even-odd fill is applied to produce the mask.
+    /// even-odd fill is applied to produce the mask. The outline is not drawn,
+    /// see `rasterize_with_options`.
     ///
     /// # Arguments
     /// * `polystream` - The raw bytes of the polystream data.
@@ -21,12 +413,115 @@ impl PolystreamRasterizer {
     /// # Returns
     /// A Vec<u8> of size width * height, where each byte is 0 or 255.
     pub fn rasterize(polystream: &[u8], width: u32, height: u32) -> Vec<u8> {
-        let points = Self::decode_polystream(polystream);
-        let points = Self::scale_points(&points, width, height);
+        Self::rasterize_with_transform(polystream, width, height, &OutputTransform::stretch(width, height))
+    }
+
+    /// Rasterizes a polystream into an R8 alpha mask with an explicit coordinate mapping,
+    /// e.g. an aspect-preserving `OutputTransform::fit`.
+    ///
+    /// # Arguments
+    /// * `polystream` - The raw bytes of the polystream data.
+    /// * `width` - The width of the output mask.
+    /// * `height` - The height of the output mask.
+    /// * `transform` - Mapping from native coordinates to output pixels.
+    ///
+    /// # Returns
+    /// A Vec<u8> of size width * height, where each byte is 0 or 255.
+    pub fn rasterize_with_transform(polystream: &[u8], width: u32, height: u32, transform: &OutputTransform) -> Vec<u8> {
+        Self::rasterize_with_options(polystream, width, height, &RasterOptions { transform: *transform, ..RasterOptions::new(width, height) })
+    }
+
+    /// Rasterizes a polystream into an R8 alpha mask with explicit options.
+    ///
+    /// # Arguments
+    /// * `polystream` - The raw bytes of the polystream data.
+    /// * `width` - The width of the output mask.
+    /// * `height` - The height of the output mask.
+    /// * `options` - Coordinate mapping and whether to draw the outline.
+    ///
+    /// # Returns
+    /// A Vec<u8> of size width * height, where each byte is 0 or 255.
+    pub fn rasterize_with_options(polystream: &[u8], width: u32, height: u32, options: &RasterOptions) -> Vec<u8> {
+        let mut mask = vec![0; (width * height) as usize];
+        Self::rasterize_into(polystream, width, height, options, &mut mask);
+        mask
+    }
+
+    /// Rasterizes a polystream on top of an existing R8 mask.
+    /// Covered pixels are set to 255 and the others are left as they are, so the channels of a
+    /// frame can be drawn into one mask without a buffer per channel.
+    ///
+    /// # Arguments
+    /// * `polystream` - The raw bytes of the polystream data.
+    /// * `width` - The width of the mask.
+    /// * `height` - The height of the mask.
+    /// * `options` - Coordinate mapping and whether to draw the outline.
+    /// * `mask` - The mask to draw into, width * height bytes.
+    pub fn rasterize_into(polystream: &[u8], width: u32, height: u32, options: &RasterOptions, mask: &mut [u8]) {
+        Self::fill(Heap, polystream, width, height, options, mask);
+    }
+
+    /// Like `rasterize_into`, with all temporaries allocated in `arena` instead of the heap.
+    /// Reset the arena once the frame is done; nothing it holds outlives this call.
+    #[cfg(feature = "arena")]
+    pub fn rasterize_into_arena(arena: &FrameArena, polystream: &[u8], width: u32, height: u32, options: &RasterOptions, mask: &mut [u8]) {
+        Self::fill(&arena.bump, polystream, width, height, options, mask);
+    }
+
+    /// Rasterizes one tile of a polystream's `width` x `height` output into a mask of the tile's size.
+    /// Covered pixels are set to 255 and the others are left as they are. The tiles of `Tile::grid`
+    /// together give exactly the mask `rasterize_into` draws, so tiles can be drawn independently,
+    /// e.g. on different threads or to stay within a backend's texture size.
+    ///
+    /// # Arguments
+    /// * `polystream` - The raw bytes of the polystream data.
+    /// * `width` - The width of the whole output.
+    /// * `height` - The height of the whole output.
+    /// * `options` - Coordinate mapping and whether to draw the outline; `tile_size` is ignored.
+    /// * `tile` - The part of the output to draw.
+    /// * `mask` - The tile's mask, tile.width * tile.height bytes.
+    pub fn rasterize_tile_into(polystream: &[u8], width: u32, height: u32, options: &RasterOptions, tile: &Tile, mask: &mut [u8]) {
+        let points = Self::output_polygon(Heap, polystream, width, height, options);
+        if points.len() >= 3 {
+            Self::scanline_fill_polygon(Heap, &points, tile, options.outline, mask);
+        }
+    }
+
+    fn fill<S: Scratch>(scratch: S, polystream: &[u8], width: u32, height: u32, options: &RasterOptions, mask: &mut [u8]) {
+        let points = Self::output_polygon(scratch, polystream, width, height, options);
         if points.len() < 3 {
-            return vec![0; (width * height) as usize];
+            return;
+        }
+        match options.tile_size {
+            Some(size) if width > size || height > size => {
+                for tile in Tile::grid(width, height, size) {
+                    let mut tile_mask = vec![0u8; tile.pixel_count()];
+                    Self::scanline_fill_polygon(scratch, &points, &tile, options.outline, &mut tile_mask);
+                    for (row, tile_row) in tile_mask.chunks_exact(tile.width as usize).enumerate() {
+                        let start = (tile.y as usize + row) * width as usize + tile.x as usize;
+                        for (pixel, &value) in mask[start..start + tile.width as usize].iter_mut().zip(tile_row) {
+                            *pixel |= value;
+                        }
+                    }
+                }
+            }
+            _ => Self::scanline_fill_polygon(scratch, &points, &Tile::full(width, height), options.outline, mask),
         }
-        Self::scanline_fill_polygon(&points, width, height)
+    }
+
+    /// Polygon of a polystream in integer output coordinates, clipped to the output
+    fn output_polygon<S: Scratch>(scratch: S, polystream: &[u8], width: u32, height: u32, options: &RasterOptions) -> S::Vec<(i32, i32)> {
+        let points = Self::decode_polystream_in(scratch, polystream);
+        let points = Self::transform_points(scratch, &points, &options.transform);
+        // Clip one pixel outside the mask: the edges clipping adds along the border then only
+        // produce crossings and outline pixels off-screen, never a spurious column or row
+        let viewport = ClipRect { min_x: -1.0, min_y: -1.0, max_x: width as f32, max_y: height as f32 };
+        let clipped = clip_polygon_in(scratch, &points, &viewport);
+        let mut points = scratch.vec();
+        for &(x, y) in clipped.iter() {
+            points.push((x as i32, y as i32));
+        }
+        points
     }
 
     /// Converts a polystream into a triangle strip of vertices.
@@ -39,7 +534,61 @@ impl PolystreamRasterizer {
     /// # Returns
     /// A Vec<f32> containing x,y pairs for each vertex in the triangle strip.
     pub fn polystream_to_triangle_strip(polystream: &[u8]) -> Vec<f32> {
+        Self::polystream_to_triangle_strip_simplified(polystream, 0.0)
+    }
+
+    /// Converts a polystream into a triangle strip, simplifying the polygon first.
+    /// The outline is reduced with Douglas-Peucker before triangulation, which cuts the
+    /// vertex count considerably for objects that do not need full detail.
+    ///
+    /// # Arguments
+    /// * `polystream` - The raw bytes of the polystream data.
+    /// * `tolerance` - Douglas-Peucker epsilon in native polystream units; 0 disables simplification.
+    ///
+    /// # Returns
+    /// A Vec<f32> containing x,y pairs for each vertex in the triangle strip.
+    pub fn polystream_to_triangle_strip_simplified(polystream: &[u8], tolerance: f32) -> Vec<f32> {
+        Self::fan_triangle_strip(&Self::simplified_points(polystream, tolerance))
+    }
+
+    /// Converts a polystream into a triangle strip of the part of the polygon inside `clip`.
+    /// Geometry outside the rectangle produces no vertices.
+    ///
+    /// # Arguments
+    /// * `polystream` - The raw bytes of the polystream data.
+    /// * `tolerance` - Douglas-Peucker epsilon in native polystream units; 0 disables simplification.
+    /// * `clip` - Clip rectangle in native polystream units, e.g. `ClipRect::native()`.
+    ///
+    /// # Returns
+    /// A Vec<f32> containing x,y pairs for each vertex in the triangle strip.
+    pub fn polystream_to_triangle_strip_clipped(polystream: &[u8], tolerance: f32, clip: &ClipRect) -> Vec<f32> {
+        let mut points = clip_polygon(&Self::simplified_points(polystream, tolerance), clip);
+        // Clipping returns an implicitly closed polygon; close it like a decoded outline
+        if let Some(&first) = points.first() {
+            points.push(first);
+        }
+        Self::fan_triangle_strip(&points)
+    }
+
+    /// Decoded outline, simplified with Douglas-Peucker when `tolerance` > 0
+    fn simplified_points(polystream: &[u8], tolerance: f32) -> Vec<(f32, f32)> {
         let points = Self::decode_polystream(polystream);
+        let points = if tolerance > 0.0 {
+            let simplified = simplify_polyline(&points, tolerance);
+            // Keep the original outline when simplifying would leave no area to triangulate
+            if simplified.len() >= 4 || (simplified.len() == 3 && simplified[0] != simplified[2]) {
+                simplified
+            } else {
+                points
+            }
+        } else {
+            points
+        };
+        points.into_iter().map(|(x, y)| (x as f32, y as f32)).collect()
+    }
+
+    /// Fan-triangulates a polygon outline into triangle strip order
+    fn fan_triangle_strip(points: &[(f32, f32)]) -> Vec<f32> {
         if points.len() < 3 {
             return vec![];
         }
@@ -47,7 +596,7 @@ impl PolystreamRasterizer {
         let vertices = if points[0] == *points.last().unwrap() && points.len() > 1 {
             &points[0..points.len() - 1]
         } else {
-            &points
+            points
         };
         if vertices.len() < 3 {
             return vec![];
@@ -63,30 +612,59 @@ impl PolystreamRasterizer {
         // Convert to Vec<f32> with x,y pairs
         let mut result = vec![];
         for (x, y) in strip {
-            result.push(x as f32);
-            result.push(y as f32);
+            result.push(x);
+            result.push(y);
         }
         result
     }
 
-    fn scale_points(points: &[(i32, i32)], target_width: u32, target_height: u32) -> Vec<(i32, i32)> {
-        let scale_x = target_width as f32 / NATIVE_WIDTH as f32;
-        let scale_y = target_height as f32 / NATIVE_HEIGHT as f32;
-        points.iter().map(|(x, y)| (*x as f32 * scale_x, *y as f32 * scale_y)).map(|(x, y)| (x as i32, y as i32)).collect()
+    /// Bounding box of the polystream's points in native coordinates, as (min_x, min_y, max_x, max_y).
+    /// None if the polystream has no points.
+    pub fn coordinate_extents(polystream: &[u8]) -> Option<(i32, i32, i32, i32)> {
+        Self::decode_polystream(polystream).into_iter().fold(None, |acc, (x, y)| match acc {
+            None => Some((x, y, x, y)),
+            Some((min_x, min_y, max_x, max_y)) => Some((min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))),
+        })
+    }
+
+    /// Number of points of a channel polystream, as decoded for rasterization (at most MAX_POLYSTREAM_POINTS)
+    pub fn vertex_count(polystream: &[u8]) -> usize {
+        if polystream.len() < 4 {
+            return 0;
+        }
+        (1 + (polystream.len() - 4) / 2).min(MAX_POLYSTREAM_POINTS)
+    }
+
+    fn transform_points<S: Scratch>(scratch: S, points: &[(i32, i32)], transform: &OutputTransform) -> S::Vec<(f32, f32)> {
+        let mut transformed = scratch.vec();
+        for (x, y) in points.iter()
+            .map(|(x, y)| (*x as f32 * transform.scale_x + transform.offset_x, *y as f32 * transform.scale_y + transform.offset_y))
+            .map(|(x, y)| (x.clamp(-MAX_COORDINATE, MAX_COORDINATE), y.clamp(-MAX_COORDINATE, MAX_COORDINATE)))
+        {
+            transformed.push((x, y));
+        }
+        transformed
     }
 
     /// Decodes the polystream bytes into a list of (x, y) points.
     /// First 4 bytes: u16 x0, y0 little-endian.
     /// Then pairs of i8 dx, dy, accumulated.
+    /// At most MAX_POLYSTREAM_POINTS points are decoded; the rest of an oversized polystream is ignored.
     fn decode_polystream(data: &[u8]) -> Vec<(i32, i32)> {
+        Self::decode_polystream_in(Heap, data)
+    }
+
+    fn decode_polystream_in<S: Scratch>(scratch: S, data: &[u8]) -> S::Vec<(i32, i32)> {
+        let mut points = scratch.vec();
         if data.len() < 4 {
-            return vec![];
+            return points;
         }
         let mut x = u16::from_le_bytes([data[0], data[1]]) as i32;
         let mut y = u16::from_le_bytes([data[2], data[3]]) as i32;
-        let mut points = vec![(x, y)];
+        points.push((x, y));
+        let end = data.len().min(4 + 2 * (MAX_POLYSTREAM_POINTS - 1));
         let mut i = 4;
-        while i + 1 < data.len() {
+        while i + 1 < end {
             let dx = data[i] as i8 as i32;
             let dy = data[i + 1] as i8 as i32;
             x += dx;
@@ -97,8 +675,8 @@ impl PolystreamRasterizer {
         points
     }
 
-    /// Builds a list of edges from the points.
-    /// Each edge is (x0, y0, x1, y1), skipping horizontal edges.
+    // Builds a list of edges from the points.
+    // Each edge is (x0, y0, x1, y1), skipping horizontal edges.
     // fn build_edges(points: &[(i32, i32)]) -> Vec<(i32, i32, i32, i32)> {
     //     let mut edges = Vec::new();
     //     for window in points.windows(2) {
@@ -119,18 +697,27 @@ impl PolystreamRasterizer {
     //     edges
     // }
 
-    /// Performs scanline even-odd fill on the edges to produce the R8 mask.
-    fn scanline_fill_polygon(points: &[(i32, i32)], width: u32, height: u32) -> Vec<u8> {
+    /// Performs scanline even-odd fill on the edges, setting covered pixels of the R8 mask.
+    /// Uses an active edge table, so the cost is O(H + E log E + filled spans) instead of O(H * E).
+    /// Only the pixels of `tile` are drawn; `mask` holds the tile, `points` are in output coordinates.
+    fn scanline_fill_polygon<S: Scratch>(scratch: S, points: &[(i32, i32)], tile: &Tile, outline: bool, mask: &mut [u8]) {
         if points.len() < 3 {
-            return vec![0u8; (width * height) as usize];
+            return;
         }
-        let mut mask = vec![0u8; (width * height) as usize];
-        // Build edges, draw all lines (including horizontal)
-        let mut edges = Vec::new();
+        let (width, height) = (tile.width as i32, tile.height as i32);
+        let (left, top) = (tile.x as i32, tile.y as i32);
+        // Outlines are drawn in tile coordinates; Bresenham on integer points is the same in every tile
+        let line = |mask: &mut [u8], (x0, y0): (i32, i32), (x1, y1): (i32, i32)| {
+            PolystreamRasterizer::draw_line(mask, width, height, x0 - left, y0 - top, x1 - left, y1 - top);
+        };
+        // Build edges; with outline, draw all lines (including horizontal)
+        let mut edges = scratch.vec();
         for window in points.windows(2) {
             let (x0, y0) = window[0];
             let (x1, y1) = window[1];
-            PolystreamRasterizer::draw_line(&mut mask, width as i32, height as i32, x0, y0, x1, y1);
+            if outline {
+                line(mask, (x0, y0), (x1, y1));
+            }
             if y0 != y1 {
                 edges.push((x0, y0, x1, y1));
             }
@@ -139,58 +726,187 @@ impl PolystreamRasterizer {
         if points[0] != *points.last().unwrap() {
             let (x0, y0) = *points.last().unwrap();
             let (x1, y1) = points[0];
-            PolystreamRasterizer::draw_line(&mut mask, width as i32, height as i32, x0, y0, x1, y1);
+            if outline {
+                line(mask, (x0, y0), (x1, y1));
+            }
             if y0 != y1 {
                 edges.push((x0, y0, x1, y1));
             }
         }
-        // Fill by scanline
-        for y in 0..height as i32 {
-            let mut xs = Vec::new();
-            for &(x0, y0, x1, y1) in &edges {
-                let ymin = y0.min(y1);
-                let ymax = y0.max(y1);
-                if y1 == y0 { continue; }
-                if y < ymin || y >= ymax {
-                    continue;
-                }
-                let x_int = if x0 == x1 {
-                    x0
-                } else {
-                    let t = (y - y0) as f32 / (y1 - y0) as f32;
-                    (x0 as f32 + t * (x1 - x0) as f32).round() as i32
-                };
-                xs.push(x_int);
+        // Edge table: edges sorted by the first scanline they cross. Edges entirely above or
+        // below the tile are never visited.
+        let mut edge_table = scratch.vec();
+        for &(x0, y0, x1, y1) in edges.iter() {
+            let ((top_x, top_y), (bottom_x, bottom_y)) = if y0 < y1 { ((x0, y0), (x1, y1)) } else { ((x1, y1), (x0, y0)) };
+            if bottom_y <= top || top_y >= top + height {
+                continue;
             }
-            xs.sort();
-            if xs.len() < 2 { continue; }
-            for i in (0..xs.len() - 1).step_by(2) {
-                let x_start = xs[i].max(0);
-                let x_end = xs[i + 1].min(width as i32 - 1);
-                if x_end < x_start {
-                    continue;
+            let y_start = top_y.max(top);
+            let dx_dy = (bottom_x - top_x) as f64 / (bottom_y - top_y) as f64;
+            edge_table.push(ActiveEdge {
+                x: ActiveEdge::x_at(top_x, top_y, dx_dy, y_start),
+                top_x,
+                top_y,
+                dx_dy,
+                y_start,
+                y_end: bottom_y,
+            });
+        }
+        edge_table.sort_by_key(|edge| edge.y_start);
+        // Active edge table, kept sorted by x. Each scanline advances x incrementally; since edges
+        // rarely cross, the insertion sort that restores the order is close to linear.
+        let mut active = scratch.vec();
+        let mut entering = edge_table.iter().peekable();
+        for y in top..top + height {
+            active.retain(|edge: &ActiveEdge| edge.y_end > y);
+            while let Some(edge) = entering.next_if(|edge| edge.y_start == y) {
+                let pos = active.partition_point(|e| e.x < edge.x);
+                active.insert(pos, *edge);
+            }
+            for i in 1..active.len() {
+                let mut j = i;
+                while j > 0 && active[j - 1].x > active[j].x {
+                    active.swap(j - 1, j);
+                    j -= 1;
                 }
-                for x in x_start..=x_end {
-                    let idx = (y as u32 * width + x as u32) as usize;
-                    mask[idx] = 255;
+            }
+            // Even-odd fill between pairs of crossings
+            for pair in active.chunks_exact(2) {
+                let x_start = (pair[0].x.round() as i32).max(left) - left;
+                let x_end = (pair[1].x.round() as i32).min(left + width - 1) - left;
+                if x_end >= x_start {
+                    let row = (y - top) as usize * width as usize;
+                    mask[row + x_start as usize..=row + x_end as usize].fill(255);
                 }
             }
+            // Crossings are computed from the edge's top rather than accumulated, so a tile starting
+            // further down finds exactly the crossings the whole mask has
+            for edge in active.iter_mut() {
+                edge.x = ActiveEdge::x_at(edge.top_x, edge.top_y, edge.dx_dy, y + 1);
+            }
         }
-        mask
     }
 
 }
 
+/// Edge in the scanline fill's active edge table
+#[derive(Debug, Clone, Copy)]
+struct ActiveEdge {
+    /// X coordinate where the edge crosses the current scanline
+    x: f64,
+    /// Upper end of the edge
+    top_x: i32,
+    top_y: i32,
+    /// Change in x per scanline
+    dx_dy: f64,
+    /// First scanline the edge crosses inside the mask
+    y_start: i32,
+    /// First scanline below the edge
+    y_end: i32,
+}
+
+impl ActiveEdge {
+    /// X coordinate where the edge crosses scanline `y`
+    fn x_at(top_x: i32, top_y: i32, dx_dy: f64, y: i32) -> f64 {
+        top_x as f64 + (y - top_y) as f64 * dx_dy
+    }
+}
+
+/// Growable buffer for the temporaries of a fill
+trait ScratchVec<T>: DerefMut<Target = [T]> {
+    fn push(&mut self, value: T);
+    fn insert(&mut self, index: usize, value: T);
+    fn retain(&mut self, keep: impl FnMut(&T) -> bool);
+    fn pop(&mut self) -> Option<T>;
+}
+
+/// Where a fill allocates its temporaries: the heap, or a FrameArena
+trait Scratch: Copy {
+    type Vec<T: Copy + 'static>: ScratchVec<T>;
+    fn vec<T: Copy + 'static>(self) -> Self::Vec<T>;
+}
+
+#[derive(Clone, Copy)]
+struct Heap;
+
+impl Scratch for Heap {
+    type Vec<T: Copy + 'static> = Vec<T>;
+    fn vec<T: Copy + 'static>(self) -> Vec<T> {
+        Vec::new()
+    }
+}
+
+impl<T> ScratchVec<T> for Vec<T> {
+    fn push(&mut self, value: T) {
+        Vec::push(self, value)
+    }
+    fn insert(&mut self, index: usize, value: T) {
+        Vec::insert(self, index, value)
+    }
+    fn retain(&mut self, keep: impl FnMut(&T) -> bool) {
+        Vec::retain(self, keep)
+    }
+    fn pop(&mut self) -> Option<T> {
+        Vec::pop(self)
+    }
+}
+
+/// Bump arena for the temporaries of one frame: decoded, transformed and clipped points and the
+/// edge tables of the fill. Allocating is a pointer increment and `reset` frees everything at once
+/// while keeping the memory for the next frame, so a long-running player does not scatter
+/// short-lived buffers over the heap.
+#[cfg(feature = "arena")]
+#[derive(Debug, Default)]
+pub struct FrameArena {
+    bump: bumpalo::Bump,
+}
+
+#[cfg(feature = "arena")]
+impl FrameArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Free the temporaries of the previous frame
+    pub fn reset(&mut self) {
+        self.bump.reset();
+    }
+
+    /// Bytes the arena has reserved, used or not
+    pub fn allocated_bytes(&self) -> usize {
+        self.bump.allocated_bytes()
+    }
+}
+
+#[cfg(feature = "arena")]
+impl<'a> Scratch for &'a bumpalo::Bump {
+    type Vec<T: Copy + 'static> = bumpalo::collections::Vec<'a, T>;
+    fn vec<T: Copy + 'static>(self) -> Self::Vec<T> {
+        bumpalo::collections::Vec::new_in(self)
+    }
+}
+
+#[cfg(feature = "arena")]
+impl<T> ScratchVec<T> for bumpalo::collections::Vec<'_, T> {
+    fn push(&mut self, value: T) {
+        bumpalo::collections::Vec::push(self, value)
+    }
+    fn insert(&mut self, index: usize, value: T) {
+        bumpalo::collections::Vec::insert(self, index, value)
+    }
+    fn retain(&mut self, keep: impl FnMut(&T) -> bool) {
+        bumpalo::collections::Vec::retain(self, keep)
+    }
+    fn pop(&mut self) -> Option<T> {
+        bumpalo::collections::Vec::pop(self)
+    }
+}
+
 impl PolystreamRasterizer {
     /// Draw a line using Bresenham's algorithm (clipped to mask bounds)
+    /// Endpoints may lie just outside the mask (polygons are clipped one pixel outside it);
+    /// pixels outside are skipped rather than clamped onto the border.
     fn draw_line(mask: &mut [u8], width: i32, height: i32, mut x0: i32, mut y0: i32, x1: i32, y1: i32) {
-        let mut x1 = x1;
-        let mut y1 = y1;
-        // Clip to bounds (simple, not Liang-Barsky)
-        x0 = x0.max(0).min(width - 1);
-        y0 = y0.max(0).min(height - 1);
-        x1 = x1.max(0).min(width - 1);
-        y1 = y1.max(0).min(height - 1);
         let dx = (x1 - x0).abs();
         let dy = (y1 - y0).abs();
         let sx = if x0 < x1 { 1 } else { -1 };
@@ -209,6 +925,118 @@ impl PolystreamRasterizer {
     }
 }
 
+/// Clips a polygon against a rectangle (Sutherland-Hodgman).
+/// The polygon is treated as closed; the result is implicitly closed as well (no repeated first
+/// point). Concave polygons stay a single outline, with degenerate edges along the rectangle border.
+///
+/// # Arguments
+/// * `points` - The polygon vertices.
+/// * `rect` - The clip rectangle.
+///
+/// # Returns
+/// The clipped polygon, empty if nothing lies inside the rectangle.
+pub fn clip_polygon(points: &[(f32, f32)], rect: &ClipRect) -> Vec<(f32, f32)> {
+    clip_polygon_in(Heap, points, rect)
+}
+
+fn clip_polygon_in<S: Scratch>(scratch: S, points: &[(f32, f32)], rect: &ClipRect) -> S::Vec<(f32, f32)> {
+    let mut output = scratch.vec();
+    for &point in points {
+        output.push(point);
+    }
+    // Drop an explicit closing point; the edge back to the start is implied
+    if output.len() > 1 && output.first() == output.last() {
+        output.pop();
+    }
+    // Each clip edge: is a point inside, and where does segment a-b cross the edge
+    type Inside = fn(&ClipRect, (f32, f32)) -> bool;
+    type Intersect = fn(&ClipRect, (f32, f32), (f32, f32)) -> (f32, f32);
+    let edges: [(Inside, Intersect); 4] = [
+        (|r, p| p.0 >= r.min_x, |r, a, b| (r.min_x, lerp_y(a, b, r.min_x))),
+        (|r, p| p.0 <= r.max_x, |r, a, b| (r.max_x, lerp_y(a, b, r.max_x))),
+        (|r, p| p.1 >= r.min_y, |r, a, b| (lerp_x(a, b, r.min_y), r.min_y)),
+        (|r, p| p.1 <= r.max_y, |r, a, b| (lerp_x(a, b, r.max_y), r.max_y)),
+    ];
+    for (inside, intersect) in edges {
+        if output.is_empty() {
+            break;
+        }
+        let input = std::mem::replace(&mut output, scratch.vec());
+        let mut prev = *input.last().unwrap();
+        for &point in input.iter() {
+            match (inside(rect, prev), inside(rect, point)) {
+                (true, true) => output.push(point),
+                (true, false) => output.push(intersect(rect, prev, point)),
+                (false, true) => {
+                    output.push(intersect(rect, prev, point));
+                    output.push(point);
+                }
+                (false, false) => {}
+            }
+            prev = point;
+        }
+    }
+    output
+}
+
+/// Y coordinate where segment a-b crosses the vertical line at `x`
+fn lerp_y(a: (f32, f32), b: (f32, f32), x: f32) -> f32 {
+    a.1 + (b.1 - a.1) * (x - a.0) / (b.0 - a.0)
+}
+
+/// X coordinate where segment a-b crosses the horizontal line at `y`
+fn lerp_x(a: (f32, f32), b: (f32, f32), y: f32) -> f32 {
+    a.0 + (b.0 - a.0) * (y - a.1) / (b.1 - a.1)
+}
+
+/// Simplifies a polyline with the Douglas-Peucker algorithm.
+/// The first and last point are always kept, so closed outlines stay closed.
+///
+/// # Arguments
+/// * `points` - The polyline vertices.
+/// * `epsilon` - Maximum distance a removed point may lie from the simplified line.
+///
+/// # Returns
+/// The retained points, in their original order.
+pub fn simplify_polyline(points: &[(i32, i32)], epsilon: f32) -> Vec<(i32, i32)> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    // Explicit stack instead of recursion; outlines can have thousands of points
+    let mut stack = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = stack.pop() {
+        let mut max_dist = 0.0f32;
+        let mut max_index = first;
+        for i in first + 1..last {
+            let dist = point_segment_distance(points[i], points[first], points[last]);
+            if dist > max_dist {
+                max_dist = dist;
+                max_index = i;
+            }
+        }
+        if max_dist > epsilon {
+            keep[max_index] = true;
+            stack.push((first, max_index));
+            stack.push((max_index, last));
+        }
+    }
+    points.iter().zip(&keep).filter(|(_, &k)| k).map(|(&p, _)| p).collect()
+}
+
+/// Distance from `p` to the segment `a`-`b` (to `a` itself when the segment is a point)
+fn point_segment_distance(p: (i32, i32), a: (i32, i32), b: (i32, i32)) -> f32 {
+    let (px, py) = (p.0 as f32, p.1 as f32);
+    let (ax, ay) = (a.0 as f32, a.1 as f32);
+    let (dx, dy) = (b.0 as f32 - ax, b.1 as f32 - ay);
+    let len_sq = dx * dx + dy * dy;
+    let t = if len_sq == 0.0 { 0.0 } else { (((px - ax) * dx + (py - ay) * dy) / len_sq).clamp(0.0, 1.0) };
+    let (cx, cy) = (ax + t * dx, ay + t * dy);
+    ((px - cx).powi(2) + (py - cy).powi(2)).sqrt()
+}
+
 /// Resizes an R8 image using nearest-neighbor scaling.
 ///
 /// # Arguments
@@ -244,6 +1072,36 @@ mod tests {
     use super::*;
     use proptest::prelude::*;
 
+    #[test]
+    fn test_output_packing() {
+        let mask = [0, 255, 255, 0, 0, 255];
+        assert_eq!(OutputPacking::default().pack(&mask, 3, 2), mask);
+        // Rows padded to 4 bytes
+        let packing = OutputPacking::new(PixelFormat::R8).row_alignment(4);
+        assert_eq!(packing.row_stride(3), 4);
+        assert_eq!(packing.pack(&mask, 3, 2), [0, 255, 255, 0, 0, 0, 255, 0]);
+        // 16-bit pixels, little- and big-endian
+        let la8 = OutputPacking::new(PixelFormat::La8);
+        assert_eq!(la8.pack(&mask[..2], 2, 1), [0xFF, 0x00, 0xFF, 0xFF]);
+        assert_eq!(la8.swap_bytes(true).pack(&mask[..2], 2, 1), [0x00, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(OutputPacking::new(PixelFormat::R16).pack(&[128], 1, 1), 32896u16.to_le_bytes());
+        let console = OutputPacking::new(PixelFormat::R16).row_alignment(64).swap_bytes(true);
+        assert_eq!(console.row_stride(33), 128);
+        let packed = console.pack(&[128; 33 * 2], 33, 2);
+        assert_eq!(packed.len(), console.packed_size(33, 2));
+        assert_eq!(&packed[128..130], &32896u16.to_be_bytes());
+        assert!(packed[66..128].iter().all(|&b| b == 0));
+        // 8-bit alpha and 32-bit color formats, the mask in alpha; swapping bytes changes nothing
+        assert_eq!(OutputPacking::new(PixelFormat::A8).pack_owned(mask.to_vec(), 3, 2), mask);
+        let rgba = [0xFF, 0xFF, 0xFF, 0, 0xFF, 0xFF, 0xFF, 255];
+        assert_eq!(OutputPacking::new(PixelFormat::Rgba8).pack(&mask[..2], 2, 1), rgba);
+        assert_eq!(OutputPacking::new(PixelFormat::Bgra8).swap_bytes(true).pack(&mask[..2], 2, 1), rgba);
+        assert_eq!(OutputPacking::new(PixelFormat::Rgba8).row_alignment(16).row_stride(3), 16);
+        // Alignments are powers of two
+        assert_eq!(OutputPacking::default().row_alignment(48).row_alignment, 64);
+        assert_eq!(OutputPacking::default().row_alignment(4096).row_alignment, MAX_ROW_ALIGNMENT);
+    }
+
     #[test]
     fn test_rasterize_triangle() {
         // Triangle: (0,0), (15,0), (7,15), closed
@@ -255,10 +1113,12 @@ mod tests {
             248, 15, // dx=-8 (248 as i8), dy=15 -> (7,15)
             249, 241, // dx=-7 (249), dy=-15 (241) -> (0,0)
         ];
-        let mask = PolystreamRasterizer::rasterize(&data, 16, 16);
+        // Sub-pixel after scaling: only the outline makes it visible
+        let options = RasterOptions { outline: true, ..RasterOptions::new(16, 16) };
+        let mask = PolystreamRasterizer::rasterize_with_options(&data, 16, 16, &options);
         assert_eq!(mask.len(), 256);
         // Check that some pixels are filled
-        assert!(mask.iter().any(|&x| x == 255));
+        assert!(mask.contains(&255));
     }
 
     proptest! {
@@ -270,6 +1130,12 @@ mod tests {
         fn fuzz_triangle_strip_does_not_panic(data in proptest::collection::vec(any::<u8>(), 0..128)) {
             let _ = PolystreamRasterizer::polystream_to_triangle_strip(&data);
         }
+        #[test]
+        fn fuzz_simplified_strip_never_grows(data in proptest::collection::vec(any::<u8>(), 0..128), tolerance in 0.0f32..50.0) {
+            let full = PolystreamRasterizer::polystream_to_triangle_strip(&data);
+            let simplified = PolystreamRasterizer::polystream_to_triangle_strip_simplified(&data, tolerance);
+            prop_assert!(simplified.len() <= full.len());
+        }
     }
 
     #[test]
@@ -313,11 +1179,12 @@ fn test_rasterize_square() {
         246, 0, // dx=-10, dy=0 -> (0,10)
         0, 246, // dx=0, dy=-10 -> (0,0)
     ];
-    let mask = PolystreamRasterizer::rasterize(&data, 16, 16);
+    let options = RasterOptions { outline: true, ..RasterOptions::new(16, 16) };
+    let mask = PolystreamRasterizer::rasterize_with_options(&data, 16, 16, &options);
     assert_eq!(mask.len(), 256);
     // With scaling, the square will be scaled from native (2024x1024) to (16x16)
     // So the filled area will be much smaller, but at least one pixel should be filled
-    assert!(mask.iter().any(|&x| x == 255));
+    assert!(mask.contains(&255));
     // Most pixels should remain zero
     let filled = mask.iter().filter(|&&x| x == 255).count();
     assert!(filled < 16 * 16 / 2);
@@ -338,4 +1205,333 @@ fn test_rasterize_square() {
         // Fan triangulation strip: v0,v1,v2,v0,v2,v3
         assert_eq!(strip, vec![0.0, 0.0, 10.0, 0.0, 10.0, 10.0, 0.0, 0.0, 10.0, 10.0, 0.0, 10.0]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_triangle_mesh_square() {
+        let strip = [0.0, 0.0, 1012.0, 0.0, 1012.0, 512.0, 0.0, 0.0, 1012.0, 512.0, 0.0, 512.0];
+        let mesh = TriangleMesh::from_triangle_list(&strip);
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.positions, vec![0.0, 0.0, 1012.0, 0.0, 1012.0, 512.0, 0.0, 512.0]);
+        assert_eq!(mesh.uvs, vec![0.0, 0.0, 0.5, 0.0, 0.5, 0.5, 0.0, 0.5]);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        // A trailing partial triangle is ignored
+        assert_eq!(TriangleMesh::from_triangle_list(&strip[..10]).indices, vec![0, 1, 2]);
+        assert_eq!(TriangleMesh::from_triangle_list(&[]), TriangleMesh::default());
+    }
+
+    #[test]
+    fn test_simplify_polyline_drops_collinear_points() {
+        let points = vec![(0, 0), (1, 0), (2, 0), (3, 0), (3, 3), (0, 3), (0, 0)];
+        let simplified = simplify_polyline(&points, 0.5);
+        assert_eq!(simplified, vec![(0, 0), (3, 0), (3, 3), (0, 3), (0, 0)]);
+        // Zero tolerance still removes exactly collinear points only
+        assert_eq!(simplify_polyline(&points, 0.0), simplified);
+    }
+
+    #[test]
+    fn test_simplify_polyline_respects_epsilon() {
+        let points = vec![(0, 0), (5, 2), (10, 0)];
+        assert_eq!(simplify_polyline(&points, 1.0), points);
+        assert_eq!(simplify_polyline(&points, 3.0), vec![(0, 0), (10, 0)]);
+    }
+
+    #[test]
+    fn test_triangle_strip_simplified_square() {
+        // Square with an extra point halfway along the bottom edge, 1 unit off the line
+        let data = vec![
+            0, 0, // x0=0
+            0, 0, // y0=0
+            5, 1, // -> (5,1)
+            5, 255, // -> (10,0)
+            0, 10, // -> (10,10)
+            246, 0, // -> (0,10)
+            0, 246, // -> (0,0)
+        ];
+        let full = PolystreamRasterizer::polystream_to_triangle_strip(&data);
+        assert_eq!(full.len(), 3 * 3 * 2);
+        let simplified = PolystreamRasterizer::polystream_to_triangle_strip_simplified(&data, 2.0);
+        assert_eq!(simplified, vec![0.0, 0.0, 10.0, 0.0, 10.0, 10.0, 0.0, 0.0, 10.0, 10.0, 0.0, 10.0]);
+        // A tolerance that would collapse the polygon keeps the original outline
+        let collapsed = PolystreamRasterizer::polystream_to_triangle_strip_simplified(&data, 100.0);
+        assert_eq!(collapsed, full);
+    }
+
+    #[test]
+    fn test_output_transform_fit_letterboxes() {
+        // ~2:1 native canvas into a square output: bars top and bottom
+        let fit = OutputTransform::fit(NATIVE_WIDTH, NATIVE_HEIGHT, 512, 512);
+        assert_eq!(fit.scale_x, fit.scale_y);
+        assert_eq!(fit.offset_x, 0.0);
+        assert_eq!(fit.offset_y, ((512.0 - 1024.0 * 512.0 / 2024.0) / 2.0f32).floor());
+        // Wider output: bars left and right
+        let fit = OutputTransform::fit(1000, 1000, 300, 100);
+        assert_eq!((fit.scale_x, fit.offset_x, fit.offset_y), (0.1, 100.0, 0.0));
+    }
+
+    #[test]
+    fn test_coordinate_extents() {
+        let data = vec![
+            100, 0, 50, 0, // (100,50)
+            10, 20, // (110,70)
+            236, 246, // (90,60)
+        ];
+        assert_eq!(PolystreamRasterizer::coordinate_extents(&data), Some((90, 50, 110, 70)));
+        assert_eq!(PolystreamRasterizer::coordinate_extents(&[1, 2]), None);
+    }
+
+    #[test]
+    fn test_rasterize_with_transform_offsets_mask() {
+        // Square (0,0)-(10,10) at scale 1, shifted by (4,2)
+        let data = vec![0, 0, 0, 0, 10, 0, 0, 10, 246, 0, 0, 246];
+        let transform = OutputTransform { scale_x: 1.0, scale_y: 1.0, offset_x: 4.0, offset_y: 2.0 };
+        let mask = PolystreamRasterizer::rasterize_with_transform(&data, 16, 16, &transform);
+        let stats = crate::stats::MaskStats::from_mask(&mask, 16, 16);
+        assert_eq!(stats.bbox, crate::stats::BoundingBox { x: 4, y: 2, w: 11, h: 10 });
+    }
+
+    #[test]
+    fn test_rasterize_into_draws_union() {
+        // Squares (0,0)-(6,6) and (4,4)-(12,12) drawn into one mask
+        let a = vec![0, 0, 0, 0, 6, 0, 0, 6, 250, 0, 0, 250];
+        let b = vec![4, 0, 4, 0, 8, 0, 0, 8, 248, 0, 0, 248];
+        let options = RasterOptions::new(16, 16);
+        let mut mask = vec![0; 256];
+        PolystreamRasterizer::rasterize_into(&a, 16, 16, &options, &mut mask);
+        PolystreamRasterizer::rasterize_into(&b, 16, 16, &options, &mut mask);
+        let separate = [PolystreamRasterizer::rasterize(&a, 16, 16), PolystreamRasterizer::rasterize(&b, 16, 16)];
+        let union: Vec<u8> = (0..256).map(|i| separate[0][i].max(separate[1][i])).collect();
+        assert_eq!(mask, union);
+    }
+
+    #[cfg(feature = "arena")]
+    #[test]
+    fn test_arena_rasterization_matches_heap() {
+        let shapes = [
+            vec![0, 0, 0, 0, 10, 0, 0, 10, 246, 0, 0, 246],
+            vec![0, 0, 0, 0, 15, 0, 249, 15],
+            vec![2, 0, 2, 0, 40, 3, 216, 40],
+        ];
+        let mut arena = FrameArena::new();
+        let mut reserved = None;
+        for _ in 0..3 {
+            for shape in &shapes {
+                for outline in [false, true] {
+                    let options = RasterOptions { transform: OutputTransform::stretch(24, 24), outline, tile_size: None };
+                    arena.reset();
+                    let mut mask = vec![0; 24 * 24];
+                    PolystreamRasterizer::rasterize_into_arena(&arena, shape, 24, 24, &options, &mut mask);
+                    assert_eq!(mask, PolystreamRasterizer::rasterize_with_options(shape, 24, 24, &options));
+                }
+            }
+            // Resetting reuses the arena's memory: later frames of the same size reserve nothing new
+            assert!(arena.allocated_bytes() > 0);
+            assert_eq!(*reserved.get_or_insert(arena.allocated_bytes()), arena.allocated_bytes());
+        }
+    }
+
+    #[test]
+    fn test_tiles_match_whole_mask() {
+        let shapes = [
+            vec![0, 0, 0, 0, 10, 0, 0, 10, 246, 0, 0, 246],
+            vec![2, 0, 2, 0, 40, 3, 216, 40],
+            vec![1, 0, 30, 0, 127, 127, 129, 127, 127, 129, 3, 120],
+        ];
+        let transform = OutputTransform { scale_x: 0.37, scale_y: 0.29, offset_x: -1.5, offset_y: 0.25 };
+        let (width, height) = (41, 37);
+        for shape in &shapes {
+            for outline in [false, true] {
+                let options = RasterOptions { transform, outline, tile_size: None };
+                let whole = PolystreamRasterizer::rasterize_with_options(shape, width, height, &options);
+                assert!(whole.contains(&255));
+                for size in [1, 7, 16, 40] {
+                    let mut assembled = vec![0u8; whole.len()];
+                    let tiles = Tile::grid(width, height, size);
+                    assert_eq!(tiles.iter().map(Tile::pixel_count).sum::<usize>(), whole.len());
+                    for tile in &tiles {
+                        let mut mask = vec![0u8; tile.pixel_count()];
+                        PolystreamRasterizer::rasterize_tile_into(shape, width, height, &options, tile, &mut mask);
+                        for (row, pixels) in mask.chunks_exact(tile.width as usize).enumerate() {
+                            let start = (tile.y as usize + row) * width as usize + tile.x as usize;
+                            assembled[start..start + pixels.len()].copy_from_slice(pixels);
+                        }
+                    }
+                    assert_eq!(assembled, whole, "tile size {}, outline {}", size, outline);
+                    let tiled = RasterOptions { tile_size: Some(size), ..options };
+                    assert_eq!(PolystreamRasterizer::rasterize_with_options(shape, width, height, &tiled), whole);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_default_rasterize_tile() {
+        /// Implements only the required methods, so tiles come from the trait's default
+        struct Wrapped;
+        impl Rasterizer for Wrapped {
+            fn rasterize(&self, polystream: &[u8], width: u32, height: u32, options: &RasterOptions, mask: &mut [u8]) {
+                PolystreamRasterizer.rasterize(polystream, width, height, options, mask);
+            }
+            fn to_vertices(&self, polystream: &[u8], tolerance: f32) -> Vec<f32> {
+                PolystreamRasterizer.to_vertices(polystream, tolerance)
+            }
+        }
+        let shape = [0, 0, 0, 0, 10, 0, 0, 10, 246, 0, 0, 246];
+        let options = RasterOptions { transform: OutputTransform { scale_x: 2.0, scale_y: 2.0, offset_x: 1.0, offset_y: 0.0 }, outline: true, tile_size: None };
+        for tile in Tile::grid(30, 20, 8) {
+            let (mut builtin, mut default) = (vec![7u8; tile.pixel_count()], vec![7u8; tile.pixel_count()]);
+            PolystreamRasterizer.rasterize_tile(&shape, 30, 20, &options, &tile, &mut builtin);
+            Wrapped.rasterize_tile(&shape, 30, 20, &options, &tile, &mut default);
+            assert_eq!(builtin, default);
+        }
+        assert_eq!(Wrapped.to_vertices(&shape, 0.0), PolystreamRasterizer::polystream_to_triangle_strip(&shape));
+
+        let shared = SharedRasterizer::new(Arc::new(Wrapped));
+        assert!(SharedRasterizer::default().is_builtin() && !shared.is_builtin());
+        assert_eq!(shared.clone(), shared);
+    }
+
+    #[test]
+    fn test_decode_polystream_limits_point_count() {
+        let mut data = vec![0, 0, 0, 0];
+        data.extend(std::iter::repeat_n([1, 0], MAX_POLYSTREAM_POINTS + 10).flatten());
+        assert_eq!(PolystreamRasterizer::decode_polystream(&data).len(), MAX_POLYSTREAM_POINTS);
+        assert_eq!(PolystreamRasterizer::vertex_count(&data), MAX_POLYSTREAM_POINTS);
+        for data in [&[1, 2][..], &[0, 0, 0, 0], &[0, 0, 0, 0, 10, 0, 0, 10, 246], &[0, 0, 0, 0, 10, 0, 0, 10, 246, 0]] {
+            assert_eq!(PolystreamRasterizer::vertex_count(data), PolystreamRasterizer::decode_polystream(data).len());
+        }
+    }
+
+    #[test]
+    fn test_rasterize_huge_coordinates() {
+        // A tall zigzag reaching far outside the output: at scale 1000 the outline spans
+        // millions of pixels, but only the 64 visible scanlines are filled
+        let mut data = vec![0, 0, 0, 0];
+        for _ in 0..2000 {
+            data.extend_from_slice(&[127, 127, 129, 127]);
+        }
+        data.extend_from_slice(&[0, 0]);
+        let transform = OutputTransform { scale_x: 1000.0, scale_y: 1000.0, offset_x: -1000.0, offset_y: -1000.0 };
+        let start = std::time::Instant::now();
+        let mask = PolystreamRasterizer::rasterize_with_transform(&data, 64, 64, &transform);
+        assert_eq!(mask.len(), 64 * 64);
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_active_edge_table_fill() {
+        let identity = OutputTransform { scale_x: 1.0, scale_y: 1.0, offset_x: 0.0, offset_y: 0.0 };
+        // Square (0,0)-(100,100): rows 0-99, columns 0-100 (the bottom edge is not filled)
+        let square = vec![0, 0, 0, 0, 100, 0, 0, 100, 156, 0, 0, 156];
+        let mask = PolystreamRasterizer::rasterize_with_transform(&square, 128, 128, &identity);
+        assert_eq!(crate::stats::MaskStats::from_mask(&mask, 128, 128).area, 100 * 101);
+
+        // Two overlapping squares in one outline: even-odd leaves the overlap empty
+        let polygon = vec![
+            0, 0, 0, 0, // (0,0)
+            60, 0, 0, 60, 196, 0, // (60,0), (60,60), (0,60)
+            0, 196, 20, 20, // back to (0,0), then (20,20)
+            60, 0, 0, 60, 196, 0, 0, 196, // square (20,20)-(80,80)
+            236, 236, // back to (0,0)
+        ];
+        let mask = PolystreamRasterizer::rasterize_with_transform(&polygon, 128, 128, &identity);
+        assert_eq!(mask[10 * 128 + 10], 255);
+        assert_eq!(mask[40 * 128 + 40], 0);
+        assert_eq!(mask[70 * 128 + 70], 255);
+    }
+
+    #[test]
+    fn test_clip_polygon() {
+        let rect = ClipRect { min_x: 0.0, min_y: 0.0, max_x: 10.0, max_y: 10.0 };
+        // Square half outside on the left
+        let square = [(-5.0, 2.0), (5.0, 2.0), (5.0, 8.0), (-5.0, 8.0), (-5.0, 2.0)];
+        assert_eq!(clip_polygon(&square, &rect), vec![(0.0, 2.0), (5.0, 2.0), (5.0, 8.0), (0.0, 8.0)]);
+        // Entirely outside
+        assert!(clip_polygon(&[(20.0, 20.0), (30.0, 20.0), (30.0, 30.0)], &rect).is_empty());
+        // Entirely inside is unchanged
+        let inside = [(1.0, 1.0), (9.0, 1.0), (5.0, 9.0)];
+        assert_eq!(clip_polygon(&inside, &rect), inside.to_vec());
+    }
+
+    #[test]
+    fn test_rasterize_off_screen_vertices_leave_no_border_artifacts() {
+        let identity = OutputTransform { scale_x: 1.0, scale_y: 1.0, offset_x: 0.0, offset_y: 0.0 };
+        // Triangle (110,10), (170,10), (110,50), shifted left by 160 so two vertices are off-screen.
+        // Column 0 is inside the triangle from row 10 down to about row 16 only.
+        let data = vec![110, 0, 10, 0, 60, 0, 196, 40, 0, 216];
+        let transform = OutputTransform { offset_x: -160.0, ..identity };
+        let mask = PolystreamRasterizer::rasterize_with_transform(&data, 64, 64, &transform);
+        assert_eq!(mask[13 * 64], 255);
+        assert_eq!(mask[30 * 64], 0);
+        assert_eq!(mask[48 * 64], 0);
+    }
+
+    #[test]
+    fn test_triangle_strip_clipped() {
+        // Square (0,0)-(10,10), clipped to its left half
+        let data = vec![0, 0, 0, 0, 10, 0, 0, 10, 246, 0, 0, 246];
+        let clip = ClipRect { min_x: 0.0, min_y: 0.0, max_x: 5.0, max_y: 10.0 };
+        let strip = PolystreamRasterizer::polystream_to_triangle_strip_clipped(&data, 0.0, &clip);
+        assert!(strip.chunks(2).all(|v| v[0] <= 5.0));
+        // Clipped to a rectangle: two triangles
+        assert_eq!(strip.len(), 2 * 3 * 2);
+        let off_canvas = ClipRect { min_x: 20.0, min_y: 20.0, max_x: 30.0, max_y: 30.0 };
+        assert!(PolystreamRasterizer::polystream_to_triangle_strip_clipped(&data, 0.0, &off_canvas).is_empty());
+    }
+
+    /// Render a mask as text rows, '#' for filled pixels
+    fn render(mask: &[u8], width: usize) -> Vec<String> {
+        mask.chunks(width).map(|row| row.iter().map(|&p| if p > 0 { '#' } else { '.' }).collect()).collect()
+    }
+
+    #[test]
+    fn test_golden_fill_only_triangle() {
+        // Triangle (1,1), (6,1), (1,6) at scale 1
+        let data = vec![1, 0, 1, 0, 5, 0, 251, 5, 0, 251];
+        let options = RasterOptions { transform: OutputTransform { scale_x: 1.0, scale_y: 1.0, offset_x: 0.0, offset_y: 0.0 }, outline: false, tile_size: None };
+        let mask = PolystreamRasterizer::rasterize_with_options(&data, 8, 8, &options);
+        assert_eq!(render(&mask, 8), vec![
+            "........",
+            ".######.",
+            ".#####..",
+            ".####...",
+            ".###....",
+            ".##.....",
+            "........",
+            "........",
+        ]);
+        // The outline adds the bottom vertex row on top of the same fill
+        let outlined = PolystreamRasterizer::rasterize_with_options(&data, 8, 8, &RasterOptions { outline: true, ..options });
+        assert_eq!(render(&outlined, 8)[6], ".#......");
+        assert!(mask.iter().zip(&outlined).all(|(&fill, &both)| fill <= both));
+    }
+
+    #[test]
+    fn test_golden_fill_only_default() {
+        // rasterize() is fill-only: a thin sliver has no spurs outside its fill
+        let data = vec![0, 0, 0, 0, 0, 0]; // single point, nothing to fill
+        assert!(PolystreamRasterizer::rasterize(&data, 8, 8).iter().all(|&p| p == 0));
+        // Rectangle (0,0)-(1012,512) is the top-left quarter of the canvas at any output size
+        let quarter = [0, 0, 0, 0]
+            .into_iter()
+            .chain(std::iter::repeat_n([127u8, 0], 7).flatten())
+            .chain([123, 0])
+            .chain(std::iter::repeat_n([0u8, 127], 4).flatten())
+            .chain([0, 4])
+            .chain(std::iter::repeat_n([129u8, 0], 7).flatten())
+            .chain([133, 0])
+            .chain(std::iter::repeat_n([0u8, 129], 4).flatten())
+            .chain([0, 252])
+            .collect::<Vec<u8>>();
+        let mask = PolystreamRasterizer::rasterize(&quarter, 8, 8);
+        assert_eq!(render(&mask, 8), vec![
+            "#####...",
+            "#####...",
+            "#####...",
+            "#####...",
+            "........",
+            "........",
+            "........",
+            "........",
+        ]);
+    }
+}
@@ -1,12 +1,40 @@
 // Async runtime module
 // This module provides an abstraction over Tokio's async runtime for managing concurrent tasks.
 
-use tokio::runtime::{Builder, Runtime as TokioRuntime};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::runtime::{Builder, Handle, Runtime as TokioRuntime};
+use tokio::sync::mpsc;
+#[cfg(any(test, feature = "unstable"))]
+use tokio::sync::oneshot;
+
+use crate::logging::{self, LogLevel};
+
+/// How a Runtime executes its tasks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ExecutionMode {
+    /// Tokio's multi-threaded scheduler; tasks must be Send
+    #[default]
+    MultiThread,
+    /// All tasks run on one dedicated thread that also drives a LocalSet, so components that are not
+    /// Send (e.g. wasm instances or GPU contexts) can join in through `Runtime::spawn_local`
+    LocalSet,
+}
 
 /// Builder for creating a custom Runtime with configurable worker threads and pools.
 pub struct RuntimeBuilder {
     // Number of worker threads for the runtime. Defaults to the number of CPU cores.
     worker_threads: Option<usize>,
+    // Scheduler flavor; worker_threads does not apply to LocalSet
+    mode: ExecutionMode,
+}
+
+impl Default for RuntimeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RuntimeBuilder {
@@ -14,6 +42,7 @@ impl RuntimeBuilder {
     pub fn new() -> Self {
         Self {
             worker_threads: None,
+            mode: ExecutionMode::MultiThread,
         }
     }
 
@@ -23,30 +52,112 @@ impl RuntimeBuilder {
         self
     }
 
+    /// Set how tasks are executed.
+    pub fn execution_mode(mut self, mode: ExecutionMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
     /// Build the Runtime with the configured settings.
     pub fn build(self) -> Result<Runtime, std::io::Error> {
+        if self.mode == ExecutionMode::LocalSet {
+            logging::log(LogLevel::Info, format_args!("Using a dedicated thread with a LocalSet"));
+            return Ok(Runtime { flavor: Flavor::Local(LocalThread::start()?), threads: LiveThreads::default() });
+        }
         let mut builder = Builder::new_multi_thread();
 
         if let Some(threads) = self.worker_threads {
-            println!("Using {} worker threads", threads);
+            logging::log(LogLevel::Info, format_args!("Using {} worker threads", threads));
             builder.worker_threads(threads);
         } else {
-            println!("Using default number of worker threads (number of logical cores)");
+            logging::log(LogLevel::Info, format_args!("Using default number of worker threads (number of logical cores)"));
         }
 
         // Enable all features for full async support
         builder.enable_all();
+        let threads = LiveThreads::track(&mut builder);
 
         let runtime = builder.build()?;
-        Ok(Runtime { runtime })
+        Ok(Runtime { flavor: Flavor::MultiThread(runtime), threads })
+    }
+}
+
+/// Threads of a Tokio runtime that have not exited yet, workers and blocking threads alike
+#[derive(Debug, Clone, Default)]
+pub struct LiveThreads(Arc<AtomicUsize>);
+
+impl LiveThreads {
+    /// Count the threads of the runtime `builder` builds from now on
+    pub fn track(builder: &mut Builder) -> Self {
+        let threads = LiveThreads::default();
+        let (started, stopped) = (Arc::clone(&threads.0), Arc::clone(&threads.0));
+        builder
+            .on_thread_start(move || {
+                started.fetch_add(1, Ordering::AcqRel);
+            })
+            .on_thread_stop(move || {
+                stopped.fetch_sub(1, Ordering::AcqRel);
+            });
+        threads
+    }
+
+    /// Threads started and not stopped yet
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::Acquire)
+    }
+}
+
+/// Work handed to the LocalSet thread: spawns a task there
+type LocalJob = Box<dyn FnOnce() + Send>;
+
+/// Single-threaded runtime driven by a dedicated thread inside a LocalSet
+struct LocalThread {
+    handle: Handle,
+    jobs: Option<mpsc::UnboundedSender<LocalJob>>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl LocalThread {
+    fn start() -> Result<Self, std::io::Error> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        let handle = runtime.handle().clone();
+        let (jobs, mut rx) = mpsc::unbounded_channel::<LocalJob>();
+        let thread = std::thread::Builder::new().name("alphastream-local".to_string()).spawn(move || {
+            let local = tokio::task::LocalSet::new();
+            // Runs until the Runtime is dropped; tasks spawned through the handle are driven here too
+            local.block_on(&runtime, async move {
+                while let Some(job) = rx.recv().await {
+                    job();
+                }
+            });
+        })?;
+        Ok(LocalThread { handle, jobs: Some(jobs), thread: Some(thread) })
+    }
+}
+
+impl Drop for LocalThread {
+    fn drop(&mut self) {
+        self.jobs.take(); // Ends the thread's loop, which drops the LocalSet and the runtime with their tasks
+        if let Some(thread) = self.thread.take() {
+            if thread.thread().id() != std::thread::current().id() {
+                let _ = thread.join();
+            }
+        }
     }
 }
 
+enum Flavor {
+    MultiThread(TokioRuntime),
+    Local(LocalThread),
+}
+
 /// The main Runtime struct that wraps Tokio's runtime.
 /// This provides a high-level interface for running async tasks.
 pub struct Runtime {
-    // The underlying Tokio runtime instance.
-    runtime: TokioRuntime,
+    // The underlying Tokio runtime instance, or the thread driving it in LocalSet mode.
+    flavor: Flavor,
+    // Threads of the multi-threaded runtime still running, to tell whether a shutdown finished in time
+    threads: LiveThreads,
 }
 
 impl Runtime {
@@ -56,17 +167,40 @@ impl Runtime {
     }
 
     /// Create a Runtime with a custom number of worker threads.
+    #[cfg(any(test, feature = "unstable"))]
     pub fn with_worker_threads(threads: usize) -> Result<Self, std::io::Error> {
         RuntimeBuilder::new().worker_threads(threads).build()
     }
 
+    /// Create a Runtime that runs every task on one dedicated thread with a LocalSet.
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn local() -> Result<Self, std::io::Error> {
+        RuntimeBuilder::new().execution_mode(ExecutionMode::LocalSet).build()
+    }
+
+    fn handle(&self) -> &Handle {
+        match &self.flavor {
+            Flavor::MultiThread(runtime) => runtime.handle(),
+            Flavor::Local(local) => &local.handle,
+        }
+    }
+
+    /// How this runtime executes its tasks
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn execution_mode(&self) -> ExecutionMode {
+        match self.flavor {
+            Flavor::MultiThread(_) => ExecutionMode::MultiThread,
+            Flavor::Local(_) => ExecutionMode::LocalSet,
+        }
+    }
+
     /// Run a future to completion on this runtime.
     /// This blocks the current thread until the future completes.
     pub fn block_on<F, T>(&self, future: F) -> T
     where
         F: std::future::Future<Output = T>,
     {
-        self.runtime.block_on(future)
+        self.handle().block_on(future)
     }
 
     /// Spawn a task on this runtime and return a JoinHandle to await its result.
@@ -75,16 +209,58 @@ impl Runtime {
         F: std::future::Future + Send + 'static,
         F::Output: Send + 'static,
     {
-        self.runtime.spawn(future)
+        self.handle().spawn(future)
+    }
+
+    /// Run the future `make` creates on the LocalSet thread. The future does not have to be Send, only
+    /// `make` does. Receive the output from the returned channel.
+    /// Returns None on a multi-threaded runtime, which has no LocalSet.
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn spawn_local<F, Fut>(&self, make: F) -> Option<oneshot::Receiver<Fut::Output>>
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: std::future::Future + 'static,
+        Fut::Output: Send + 'static,
+    {
+        let Flavor::Local(local) = &self.flavor else {
+            return None;
+        };
+        let (tx, rx) = oneshot::channel();
+        let job: LocalJob = Box::new(move || {
+            tokio::task::spawn_local(async move {
+                let _ = tx.send(make().await);
+            });
+        });
+        local.jobs.as_ref()?.send(job).ok()?;
+        Some(rx)
+    }
+
+    /// Number of tasks spawned on this runtime that have not finished yet.
+    /// Stays bounded while a processor is idle or playing; growth means tasks are leaking.
+    pub fn alive_tasks(&self) -> usize {
+        self.handle().metrics().num_alive_tasks()
+    }
+
+    /// Stop the runtime: cancel its tasks and wait up to `timeout` for its threads to exit.
+    /// Returns false if some are still running then, e.g. blocking tasks that do not return.
+    /// Must not be called from async code.
+    pub fn shutdown_timeout(self, timeout: std::time::Duration) -> bool {
+        match self.flavor {
+            Flavor::MultiThread(runtime) => runtime.shutdown_timeout(timeout),
+            // Joins the thread, whose tasks are dropped right away
+            Flavor::Local(local) => drop(local),
+        }
+        self.threads.count() == 0
     }
 
     /// Spawn a blocking task on this runtime and return a JoinHandle to await its result.
+    #[cfg(any(test, feature = "unstable"))]
     pub fn spawn_blocking<F, T>(&self, f: F) -> tokio::task::JoinHandle<T>
     where
         F: FnOnce() -> T + Send + 'static,
         T: Send + 'static,
     {
-        self.runtime.spawn_blocking(f)
+        self.handle().spawn_blocking(f)
     }
 }
 
@@ -117,5 +293,39 @@ mod tests {
         let handle = runtime.spawn(async { 42 });
         let result = runtime.block_on(handle).expect("Task failed");
         assert_eq!(result, 42);
+        assert!(runtime.spawn_local(|| async { 0 }).is_none());
+    }
+
+    #[test]
+    fn test_shutdown_timeout_reports_live_threads() {
+        let runtime = Runtime::with_worker_threads(2).expect("Failed to create runtime");
+        runtime.block_on(runtime.spawn(async { 42 })).unwrap();
+        assert!(runtime.shutdown_timeout(std::time::Duration::from_secs(5)));
+
+        // A blocking task cannot be cancelled, so its thread outlives a short timeout
+        let runtime = Runtime::with_worker_threads(2).expect("Failed to create runtime");
+        let (started, wait) = std::sync::mpsc::channel();
+        runtime.spawn_blocking(move || {
+            started.send(()).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(500));
+        });
+        wait.recv().unwrap();
+        assert!(!runtime.shutdown_timeout(std::time::Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn test_local_set() {
+        let runtime = Runtime::local().expect("Failed to create local runtime");
+        assert_eq!(runtime.execution_mode(), ExecutionMode::LocalSet);
+        // Rc is not Send, so this future could not go through spawn
+        let local = runtime.spawn_local(|| async {
+            let value = std::rc::Rc::new(40);
+            tokio::task::yield_now().await;
+            *value + 2
+        }).expect("LocalSet runtime spawns local tasks");
+        assert_eq!(runtime.block_on(local).unwrap(), 42);
+        // Send tasks run on the same thread
+        let thread = runtime.block_on(runtime.spawn(async { std::thread::current().name().map(str::to_string) })).unwrap();
+        assert_eq!(thread.as_deref(), Some("alphastream-local"));
     }
 }
\ No newline at end of file
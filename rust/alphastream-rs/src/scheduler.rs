@@ -4,10 +4,53 @@
 // prevents too many tasks running at once (backpressure), and loads future frames early (prefetching).
 
 use std::collections::{VecDeque, HashSet};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
 use crate::cache::FrameCache;
+use crate::clock::SharedClock;
 use tokio::sync::mpsc;
 
+/// Scheduling lane of a frame request.
+/// Tasks in a higher lane are always started before tasks in a lower one; within a lane, lower
+/// frame indices go first. Prefetch tasks run in the Low lane (raised slightly while a visible
+/// range is set, see `set_visible_range`), so Normal requests overtake the read-ahead, and
+/// Interactive requests overtake everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Priority {
+    /// Background work, same lane as prefetch (the default for request_frame)
+    #[default]
+    Low,
+    /// Ahead of prefetch, e.g. frames pre-queued for an export
+    Normal,
+    /// Frames a caller is waiting for (get_frame and the other getters)
+    Interactive,
+}
+
+impl Priority {
+    /// The task priority value of this lane
+    pub const fn value(self) -> u8 {
+        match self {
+            Priority::Low => 0,
+            Priority::Normal => 5,
+            Priority::Interactive => 10,
+        }
+    }
+}
+
+/// Average read latency above which reads count as slow and prefetch is throttled
+pub const SLOW_READ_LATENCY: Duration = Duration::from_millis(50);
+/// Throttling never shrinks the prefetch window below this many frames
+pub const MIN_PREFETCH_COUNT: usize = 2;
+/// Upper bound for `coalesced_frames`
+#[cfg(any(test, feature = "unstable"))]
+pub const MAX_COALESCED_FRAMES: usize = 32;
+/// Prefetch priority of the frames right after the play head while a visible range is set
+pub const PLAYHEAD_PREFETCH_PRIORITY: u8 = 2;
+/// Prefetch priority of on-screen frames (e.g. timeline thumbnails) away from the play head
+pub const VISIBLE_PREFETCH_PRIORITY: u8 = 1;
+/// Frame interval a decode budget applies to, one frame at 60 Hz
+pub const QOS_TICK: Duration = Duration::from_micros(16_667);
+
 /// Represents a scheduled task with a frame index and priority.
 #[derive(Debug, Clone)]
 pub struct Task {
@@ -35,6 +78,17 @@ impl Task {
     }
 }
 
+/// A run of consecutive frames scheduled as one entry and expanded into tasks lazily.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeTask {
+    /// Next frame to dispatch
+    pub start: usize,
+    /// End of the range, exclusive
+    pub end: usize,
+    /// Priority level of every frame in the range
+    pub priority: u8,
+}
+
 /// The main Scheduler struct for managing frame processing tasks.
 /// This is the "brain" that coordinates frame processing:
 // - Keeps a prioritized to-do list of frames to process
@@ -49,8 +103,12 @@ pub struct Scheduler {
     task_queue: VecDeque<Task>,
     // HashSet for O(1) duplicate detection - tracks frame indices in queue
     queued_frames: HashSet<usize>,
+    // Range tasks not yet expanded into task_queue. Usually a handful of entries, even when
+    // callers queue whole scenes.
+    range_queue: Vec<RangeTask>,
     // Channel sender for communicating with the processing loop.
     // Like a message queue - workers can send tasks to the scheduler.
+    #[cfg(feature = "unstable")]
     task_sender: mpsc::UnboundedSender<Task>,
     // Channel receiver for the processing loop.
     // The "inbox" where the scheduler receives new tasks.
@@ -63,22 +121,60 @@ pub struct Scheduler {
     prefetch_count: usize,
     // Reference to the cache for adaptive prefetching and backpressure
     cache: Option<Arc<FrameCache>>,
+    // Deterministic mode: queue order depends only on (priority, frame index), never on arrival
+    // order, and adaptive heuristics stay off. Used for reproducible benchmarks.
+    deterministic: bool,
+    // Time source for deadlines and timeouts, a mock clock in tests
+    clock: SharedClock,
+    // Moving average of frame read latencies, None until the first read completes
+    read_latency: Option<Duration>,
+    // Frames a UI currently shows, weighted into prefetch next to the read-ahead
+    visible_range: Option<std::ops::Range<usize>>,
+    // Keyframes of a delta-encoded source, sorted; empty when every frame decodes on its own
+    keyframes: Vec<usize>,
+    /// Frames before the play head decoded along with it, see `set_preroll`
+    preroll: usize,
+    // Decode time allowed per QOS_TICK before prefetch is deferred, None for no budget
+    decode_budget: Option<Duration>,
+    // Decode time not yet paid off by elapsed ticks, and when it was last paid off
+    budget_spent: Duration,
+    budget_checked: Option<std::time::Instant>,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Scheduler {
     /// Create a new Scheduler with default settings.
     pub fn new() -> Self {
         let (tx, rx) = mpsc::unbounded_channel();
+        // Only `sender` hands out the sending side
+        #[cfg(not(feature = "unstable"))]
+        drop(tx);
         Self {
             timebase_fps: 60.0,
             task_queue: VecDeque::new(),
             queued_frames: HashSet::new(),
+            range_queue: Vec::new(),
+            #[cfg(feature = "unstable")]
             task_sender: tx,
             task_receiver: rx,
             max_concurrent: 16, // Default max concurrent tasks
             active_tasks: 0,
             prefetch_count: 64, // Prefetch frames ahead
             cache: None,
+            deterministic: false,
+            clock: SharedClock::default(),
+            read_latency: None,
+            visible_range: None,
+            keyframes: Vec::new(),
+            preroll: 0,
+            decode_budget: None,
+            budget_spent: Duration::ZERO,
+            budget_checked: None,
         }
     }
 
@@ -86,10 +182,25 @@ impl Scheduler {
         self.task_queue.len()
     }
 
+    /// Frames waiting in range tasks, not counted in get_number_of_queued_tasks
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn get_number_of_queued_range_frames(&self) -> usize {
+        self.range_queue.iter().map(|r| r.end - r.start).sum()
+    }
+
+    /// Drop every queued task and range task. Tasks already handed out stay active until completed.
+    pub fn clear_queue(&mut self) {
+        self.task_queue.clear();
+        self.queued_frames.clear();
+        self.range_queue.clear();
+        while self.task_receiver.try_recv().is_ok() {}
+    }
+
     pub fn get_number_of_active_tasks(&self) -> usize {
         self.active_tasks
     }
 
+    #[cfg(feature = "unstable")]
     pub fn get_number_of_max_concurrent_tasks(&self) -> usize {
         self.max_concurrent
     }
@@ -109,17 +220,188 @@ impl Scheduler {
         self.cache = Some(cache);
     }
 
+    /// Enable deterministic scheduling (for builder integration)
+    /// Priority upgrades are re-sorted with stable tie-breaking on frame index instead of
+    /// jumping the queue, so the processing order is the same on every run.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// Whether deterministic scheduling is enabled; adaptive heuristics must check this and stay off
+    #[cfg(feature = "unstable")]
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// Set the time source (for builder integration)
+    pub fn set_clock(&mut self, clock: SharedClock) {
+        self.clock = clock;
+    }
+
+    /// Time source for anything the scheduler times; never read `Instant::now()` directly
+    #[cfg(feature = "unstable")]
+    pub fn clock(&self) -> &SharedClock {
+        &self.clock
+    }
+
+    /// Feed the latency of a completed frame read into the moving average that drives throttling.
+    /// Ignored in deterministic mode, so the prefetch window never depends on timing there.
+    pub fn record_read_latency(&mut self, latency: Duration) {
+        if self.deterministic {
+            return;
+        }
+        // Exponential moving average with weight 1/8: reacts within a handful of reads, ignores single outliers
+        self.read_latency = Some(match self.read_latency {
+            Some(average) => (average * 7 + latency) / 8,
+            None => latency,
+        });
+    }
+
+    /// Moving average of read latencies, None before the first read
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn read_latency(&self) -> Option<Duration> {
+        self.read_latency
+    }
+
+    /// Soft real-time mode: allow `budget` of decode time per QOS_TICK (None for no limit), so decoding
+    /// leaves CPU to a render thread in the same process. Once the budget is used up, prefetch tasks
+    /// (below the Normal lane) wait until elapsed ticks have paid the time off; Normal and Interactive
+    /// requests still start right away. Ignored in deterministic mode.
+    pub fn set_decode_budget(&mut self, budget: Option<Duration>) {
+        self.decode_budget = budget.filter(|budget| !budget.is_zero());
+        self.budget_spent = Duration::ZERO;
+        self.budget_checked = None;
+    }
+
+    /// Decode time allowed per QOS_TICK, None without a budget
+    #[cfg(feature = "unstable")]
+    pub fn decode_budget(&self) -> Option<Duration> {
+        self.decode_budget
+    }
+
+    /// Charge the time a finished decode task kept a worker busy against the decode budget
+    pub fn record_decode_time(&mut self, busy: Duration) {
+        if self.decode_budget.is_some() && !self.deterministic {
+            self.pay_off_budget();
+            self.budget_spent += busy;
+        }
+    }
+
+    /// Whether prefetch is held back because the decode budget is used up
+    pub fn is_budget_exhausted(&mut self) -> bool {
+        let Some(budget) = self.decode_budget.filter(|_| !self.deterministic) else {
+            return false;
+        };
+        self.pay_off_budget();
+        self.budget_spent >= budget
+    }
+
+    /// Take the budget of the ticks elapsed since the last call off the time spent
+    fn pay_off_budget(&mut self) {
+        let now = self.clock.now();
+        if let (Some(budget), Some(checked)) = (self.decode_budget, self.budget_checked) {
+            let ticks = now.saturating_duration_since(checked).as_secs_f64() / QOS_TICK.as_secs_f64();
+            self.budget_spent = self.budget_spent.saturating_sub(budget.mul_f64(ticks));
+        }
+        self.budget_checked = Some(now);
+    }
+
+    /// Tell prefetch which frames are on screen, None (or an empty range) for plain read-ahead.
+    /// While set, the prefetch budget is split: the first quarter goes to the frames right after the
+    /// play head, then on-screen frames inside the cache window, then the rest of the read-ahead.
+    pub fn set_visible_range(&mut self, range: Option<std::ops::Range<usize>>) {
+        self.visible_range = range.filter(|range| !range.is_empty());
+    }
+
+    /// Frames set with set_visible_range
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn visible_range(&self) -> Option<std::ops::Range<usize>> {
+        self.visible_range.clone()
+    }
+
+    /// Prefetch window after throttling. While reads are slower than SLOW_READ_LATENCY the window shrinks in
+    /// proportion (down to MIN_PREFETCH_COUNT), so prefetch stops queueing reads that would only time out.
+    pub fn effective_prefetch_count(&self) -> usize {
+        match self.read_latency {
+            Some(latency) if latency > SLOW_READ_LATENCY => {
+                let scaled = self.prefetch_count as f64 * SLOW_READ_LATENCY.as_secs_f64() / latency.as_secs_f64();
+                (scaled as usize).max(MIN_PREFETCH_COUNT).min(self.prefetch_count)
+            }
+            _ => self.prefetch_count,
+        }
+    }
+
+    /// Suggested number of adjacent frames to fetch per read request: 1 on a fast transport, growing with the
+    /// read latency (one frame per SLOW_READ_LATENCY) up to MAX_COALESCED_FRAMES, so slow networks make
+    /// fewer, larger requests.
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn coalesced_frames(&self) -> usize {
+        match self.read_latency {
+            Some(latency) if latency > SLOW_READ_LATENCY => {
+                (latency.as_secs_f64() / SLOW_READ_LATENCY.as_secs_f64()).ceil() as usize
+            }
+            _ => 1,
+        }.min(MAX_COALESCED_FRAMES)
+    }
+
+    /// Frames per second of the timebase
+    pub fn timebase_fps(&self) -> f64 {
+        self.timebase_fps
+    }
+
     /// Calculate the time in seconds for a given frame index using the timebase.
     /// Formula: t_n = n / 60 (for 60 FPS).
+    #[cfg(any(test, feature = "unstable"))]
     pub fn time_for_frame(&self, frame_index: usize) -> f64 {
         frame_index as f64 / self.timebase_fps
     }
 
+    /// Decode the `frames` before the play head too, ahead of the read-ahead, for consumers that need
+    /// a few frames of history after a seek. Only empty slots in the buffer window are scheduled, so
+    /// sequential playback, which decoded them already, costs nothing extra. See `RingBufferCache::set_preroll`
+    /// for how the window keeps room for them.
+    pub fn set_preroll(&mut self, frames: usize) {
+        self.preroll = frames;
+    }
+
+    /// Set the keyframes of a delta-encoded source. Frames after a keyframe are reconstructed from it,
+    /// so scheduling such a frame also schedules its keyframe, ahead of it, while the keyframe is in
+    /// the buffer window. An empty list means every frame decodes on its own.
+    pub fn set_keyframes(&mut self, mut keyframes: Vec<usize>) {
+        keyframes.sort_unstable();
+        keyframes.dedup();
+        self.keyframes = keyframes;
+    }
+
+    /// The keyframe `frame_index` depends on: the nearest keyframe at or before it, or the frame itself
+    /// without keyframes
+    pub fn keyframe_for(&self, frame_index: usize) -> usize {
+        match self.keyframes.partition_point(|&k| k <= frame_index) {
+            0 => frame_index,
+            at => self.keyframes[at - 1],
+        }
+    }
+
     /// Schedule a new task for processing.
     /// Tasks are added to the queue and prioritized.
     /// Uses HashSet for O(1) duplicate detection.
     pub fn schedule_task(&mut self, task: Task) {
         let frame_index = task.frame_index;
+        let priority = task.priority;
+        self.schedule_single(task);
+
+        // Decode the dependency first: the keyframe sorts before the frame at the same priority
+        let keyframe = self.keyframe_for(frame_index);
+        if keyframe != frame_index {
+            let needed = self.cache.as_ref().is_none_or(|c| c.is_in_range(keyframe) && c.get_slot_state(keyframe).is_some_and(|slot| slot.is_empty()));
+            if needed {
+                self.schedule_single(Task::with_priority(keyframe, priority));
+            }
+        }
+    }
+
+    fn schedule_single(&mut self, task: Task) {
+        let frame_index = task.frame_index;
         
         // O(1) duplicate check using HashSet
         if self.queued_frames.contains(&frame_index) {
@@ -127,22 +409,87 @@ impl Scheduler {
             if let Some(existing) = self.task_queue.iter_mut().find(|t| t.frame_index == frame_index) {
                 if task.priority > existing.priority {
                     existing.priority = task.priority;
-                    // Move to front of queue for high priority
                     self.queued_frames.remove(&frame_index);
                     self.task_queue.retain(|t| t.frame_index != frame_index);
-                    self.task_queue.push_front(task);
+                    if self.deterministic {
+                        // Stable position among tasks of the same priority
+                        self.insert_sorted(task);
+                    } else {
+                        // Move to front of queue for high priority
+                        self.task_queue.push_front(task);
+                    }
                     self.queued_frames.insert(frame_index);
                 }
             }
             return;
         }
         
-        // Insert task in priority order (higher priority first, then lower frame index)
+        self.insert_sorted(task);
+        self.queued_frames.insert(frame_index);
+    }
+
+    /// Schedule a range of frames as a single entry.
+    /// The frames are dispatched in order, interleaved with individual tasks by (priority, frame index)
+    /// exactly as if each had been scheduled on its own. Overlapping or adjacent ranges of the same
+    /// priority are merged.
+    pub fn schedule_range(&mut self, frames: std::ops::Range<usize>, priority: u8) {
+        if frames.is_empty() {
+            return;
+        }
+        let mut merged = RangeTask { start: frames.start, end: frames.end, priority };
+        self.range_queue.retain(|r| {
+            let touches = r.priority == priority && r.start <= merged.end && merged.start <= r.end;
+            if touches {
+                merged.start = merged.start.min(r.start);
+                merged.end = merged.end.max(r.end);
+            }
+            !touches
+        });
+        self.range_queue.push(merged);
+    }
+
+    /// Move frames from range tasks into the queue while a range would run before the queue's head.
+    /// Frames are moved one at a time and only inside the cache window, so frames of a long range
+    /// stay in the range until the window reaches them.
+    fn expand_ranges(&mut self) {
+        let window = self.cache.as_ref().map(|c| (c.get_start_index(), c.get_start_index() + c.capacity()));
+        if let Some((window_start, _)) = window {
+            // Frames the window has already passed will not be needed anymore
+            self.range_queue.retain_mut(|r| {
+                r.start = r.start.max(window_start);
+                r.start < r.end
+            });
+        }
+        loop {
+            let best = self.range_queue.iter().enumerate()
+                .filter(|(_, r)| window.is_none_or(|(_, window_end)| r.start < window_end))
+                .max_by(|(_, a), (_, b)| a.priority.cmp(&b.priority).then(b.start.cmp(&a.start)))
+                .map(|(i, _)| i);
+            let Some(i) = best else { return };
+            let (frame_index, priority) = (self.range_queue[i].start, self.range_queue[i].priority);
+            if let Some(head) = self.task_queue.front() {
+                if head.priority > priority || (head.priority == priority && head.frame_index <= frame_index) {
+                    return;
+                }
+            }
+            self.range_queue[i].start += 1;
+            if self.range_queue[i].start >= self.range_queue[i].end {
+                self.range_queue.swap_remove(i);
+            }
+            // Frames that are already decoded or being decoded are skipped, like in prefetch
+            let needed = self.cache.as_ref().is_none_or(|c| c.get_slot_state(frame_index).is_some_and(|slot| slot.is_empty()));
+            if needed {
+                self.schedule_task(Task::with_priority(frame_index, priority));
+            }
+        }
+    }
+
+    /// Insert task in priority order (higher priority first, then lower frame index)
+    fn insert_sorted(&mut self, task: Task) {
         let pos = self.task_queue.iter().position(|t| {
             t.priority < task.priority || (t.priority == task.priority && t.frame_index > task.frame_index)
         }).unwrap_or(self.task_queue.len());
         self.task_queue.insert(pos, task);
-        self.queued_frames.insert(frame_index);
     }
 
     /// Get the next task to process, respecting backpressure and ring buffer capacity.
@@ -161,7 +508,14 @@ impl Scheduler {
         }
         
         // Find the first task that's in the valid range
+        self.expand_ranges();
+        let defer_prefetch = self.is_budget_exhausted();
         while let Some(task) = self.task_queue.pop_front() {
+            if defer_prefetch && task.priority < Priority::Normal.value() {
+                // Out of decode budget: the rest of the queue is prefetch too, try again next tick
+                self.task_queue.push_front(task);
+                return None;
+            }
             // Remove from queued_frames HashSet
             self.queued_frames.remove(&task.frame_index);
             
@@ -174,6 +528,7 @@ impl Scheduler {
                     return Some(task);
                 }
                 // Frame is out of range (stale task from before a seek), skip it
+                self.expand_ranges();
                 continue;
             } else {
                 // No cache set, just process the task
@@ -194,63 +549,115 @@ impl Scheduler {
     /// Generate prefetch tasks for frames ahead of the current frame.
     /// Only prefetches within the valid buffer window [start_index, start_index + capacity).
     /// Uses O(1) HashSet lookup for duplicate detection.
+    /// With a visible range set, on-screen frames share the prefetch budget with the read-ahead.
     pub fn prefetch(&mut self, current_frame: usize) {
         let mut frames_to_prefetch = vec![];
-        
-        if let Some(ref cache) = self.cache {
-            let cap = cache.capacity();
-            let start = cache.get_start_index();
-            let end = start + cap;
-            let prefetch_limit = self.prefetch_count;
-
-            for i in 1..=prefetch_limit {
-                let frame_index = current_frame + i;
-                
-                // Only prefetch within the valid buffer window
-                if frame_index >= end {
-                    break; // Beyond buffer range
-                }
-                
-                // O(1) check if already queued
-                if self.queued_frames.contains(&frame_index) {
-                    continue;
-                }
-                
-                // Check if frame is already in cache or being processed
-                if let Some(slot) = cache.get_slot_state(frame_index) {
-                    if slot.is_empty() {
-                        frames_to_prefetch.push(Task::new(frame_index));
-                    }
-                    // Skip InProgress and Ready slots
-                }
+        let prefetch_limit = self.effective_prefetch_count();
+
+        if self.preroll > 0 && self.cache.is_some() {
+            let preroll = current_frame.saturating_sub(self.preroll)..current_frame;
+            let window_start = self.cache.as_ref().map_or(0, |cache| cache.get_start_index());
+            self.collect_prefetch(preroll.start.max(window_start)..preroll.end, Priority::Normal.value(), self.preroll, &mut frames_to_prefetch);
+        }
+
+        match self.visible_range.clone() {
+            None => {
+                self.collect_prefetch(current_frame + 1.., Priority::Low.value(), prefetch_limit, &mut frames_to_prefetch);
             }
-        } else {
-            // No cache, use simple prefetch (fallback)
-            for i in 1..=self.prefetch_count {
-                let frame_index = current_frame + i;
-                // O(1) duplicate check
-                if !self.queued_frames.contains(&frame_index) {
-                    frames_to_prefetch.push(Task::new(frame_index));
-                }
+            Some(visible) => {
+                let near = (prefetch_limit / 4).max(MIN_PREFETCH_COUNT).min(prefetch_limit);
+                let near_frames = current_frame + 1..current_frame + 1 + near;
+                self.collect_prefetch(near_frames.clone(), PLAYHEAD_PREFETCH_PRIORITY, near, &mut frames_to_prefetch);
+                // On-screen frames behind the window start can never be cached
+                let window_start = self.cache.as_ref().map_or(0, |cache| cache.get_start_index());
+                let on_screen = (visible.start.max(window_start)..visible.end)
+                    .filter(|frame| *frame != current_frame && !near_frames.contains(frame));
+                let used = self.collect_prefetch(on_screen, VISIBLE_PREFETCH_PRIORITY, prefetch_limit - near, &mut frames_to_prefetch);
+                self.collect_prefetch(near_frames.end.., Priority::Low.value(), prefetch_limit - near - used, &mut frames_to_prefetch);
             }
         }
-        
+
         for task in frames_to_prefetch {
             self.schedule_task(task);
         }
     }
 
+    /// Add prefetch tasks at `priority` for the frames that need decoding among the first `budget` of
+    /// `frames`, stopping at the end of the buffer window.
+    /// Returns the number of frames looked at.
+    fn collect_prefetch(&self, frames: impl Iterator<Item = usize>, priority: u8, budget: usize, tasks: &mut Vec<Task>) -> usize {
+        let mut considered = 0;
+        for frame_index in frames.take(budget) {
+            let needed = match self.cache {
+                Some(ref cache) => {
+                    // Only prefetch within the valid buffer window
+                    if frame_index >= cache.get_start_index() + cache.capacity() {
+                        break; // Beyond buffer range
+                    }
+                    // Skip queued frames (O(1) check) and InProgress / Ready slots
+                    !self.queued_frames.contains(&frame_index)
+                        && cache.get_slot_state(frame_index).is_some_and(|slot| slot.is_empty())
+                }
+                // No cache, use simple prefetch (fallback)
+                None => !self.queued_frames.contains(&frame_index),
+            };
+            if needed {
+                tasks.push(Task::with_priority(frame_index, priority));
+            }
+            considered += 1;
+        }
+        considered
+    }
+
     /// Get the sender for external task submission.
+    #[cfg(feature = "unstable")]
     pub fn sender(&self) -> mpsc::UnboundedSender<Task> {
         self.task_sender.clone()
     }
 
     /// Get the receiver for processing tasks.
+    #[cfg(feature = "unstable")]
     pub fn receiver(&mut self) -> &mut mpsc::UnboundedReceiver<Task> {
         &mut self.task_receiver
     }
 }
 
+/// Cap on decode tasks running at once, shared by the processors that wait on it
+/// The process-wide instance is `global_decode_limit`. Waiting tasks get a slot in the order they asked.
+#[derive(Default)]
+pub struct DecodeLimit {
+    // Limit and its permits, None = unlimited
+    permits: Mutex<Option<(usize, Arc<tokio::sync::Semaphore>)>>,
+}
+
+impl DecodeLimit {
+    /// Allow `limit` decode tasks at once, 0 = unlimited. Takes effect for tasks started after the call;
+    /// tasks already running finish on the permits of the old limit.
+    pub fn set(&self, limit: usize) {
+        *self.permits.lock().unwrap() = (limit > 0).then(|| (limit, Arc::new(tokio::sync::Semaphore::new(limit))));
+    }
+
+    /// Decode tasks allowed at once, 0 = unlimited
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn get(&self) -> usize {
+        self.permits.lock().unwrap().as_ref().map_or(0, |(limit, _)| *limit)
+    }
+
+    /// Wait for a slot; the task holds it until the permit is dropped. None without a limit.
+    pub async fn acquire(&self) -> Option<tokio::sync::OwnedSemaphorePermit> {
+        let permits = self.permits.lock().unwrap().as_ref().map(|(_, permits)| Arc::clone(permits))?;
+        // The semaphore is never closed
+        permits.acquire_owned().await.ok()
+    }
+}
+
+/// Limit on decode tasks across every processor in the process, e.g. so a plugin that opens a dozen
+/// masks does not oversubscribe the host application's CPU. Unlimited unless set.
+pub fn global_decode_limit() -> &'static DecodeLimit {
+    static LIMIT: OnceLock<DecodeLimit> = OnceLock::new();
+    LIMIT.get_or_init(DecodeLimit::default)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::cache::FrameCache;
@@ -287,7 +694,7 @@ mod tests {
         // Should get a task for frame 3 or later (frame 2 is current)
         assert!(task.is_some());
         let frame = task.unwrap().frame_index;
-        assert!(frame >= 3 && frame < 6, "Expected frame in [3,6), got {}", frame);
+        assert!((3..6).contains(&frame), "Expected frame in [3,6), got {}", frame);
     }
 
     use super::*;
@@ -351,4 +758,239 @@ mod tests {
             scheduler.complete_task();
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_visible_range_weights_prefetch() {
+        let cache = Arc::new(FrameCache::new(64));
+        let mut scheduler = Scheduler::new();
+        scheduler.set_cache(Arc::clone(&cache));
+        scheduler.set_prefetch_count(16);
+        scheduler.set_visible_range(Some(40..44));
+        scheduler.prefetch(0);
+
+        let mut order = vec![];
+        while let Some(task) = scheduler.next_task() {
+            order.push((task.frame_index, task.priority));
+            scheduler.complete_task();
+        }
+        // A quarter of the budget right after the play head, then the visible frames, then read-ahead
+        let mut expected: Vec<(usize, u8)> = (1..5).map(|f| (f, PLAYHEAD_PREFETCH_PRIORITY)).collect();
+        expected.extend((40..44).map(|f| (f, VISIBLE_PREFETCH_PRIORITY)));
+        expected.extend((5..13).map(|f| (f, Priority::Low.value())));
+        assert_eq!(order, expected);
+
+        // Without a hint, prefetch reads strictly ahead again
+        scheduler.set_visible_range(Some(10..10));
+        assert_eq!(scheduler.visible_range(), None);
+        let cache = Arc::new(FrameCache::new(64));
+        scheduler.set_cache(cache);
+        scheduler.prefetch(0);
+        let frames: Vec<usize> = std::iter::from_fn(|| scheduler.next_task().inspect(|_| scheduler.complete_task())).map(|t| t.frame_index).collect();
+        assert_eq!(frames, (1..17).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_slow_reads_throttle_prefetch() {
+        let mut scheduler = Scheduler::new();
+        scheduler.set_prefetch_count(64);
+        assert_eq!(scheduler.effective_prefetch_count(), 64);
+        assert_eq!(scheduler.coalesced_frames(), 1);
+
+        for _ in 0..64 {
+            scheduler.record_read_latency(Duration::from_millis(400));
+        }
+        // 8x slower than SLOW_READ_LATENCY: an eighth of the window, eight frames per request
+        assert_eq!(scheduler.effective_prefetch_count(), 8);
+        assert_eq!(scheduler.coalesced_frames(), 8);
+        scheduler.prefetch(0);
+        assert_eq!(scheduler.get_number_of_queued_tasks(), 8);
+
+        for _ in 0..64 {
+            scheduler.record_read_latency(Duration::from_secs(10));
+        }
+        assert_eq!(scheduler.effective_prefetch_count(), MIN_PREFETCH_COUNT);
+        assert_eq!(scheduler.coalesced_frames(), MAX_COALESCED_FRAMES);
+
+        // Recovers once reads are fast again
+        for _ in 0..128 {
+            scheduler.record_read_latency(Duration::from_millis(1));
+        }
+        assert_eq!(scheduler.effective_prefetch_count(), 64);
+
+        let mut deterministic = Scheduler::new();
+        deterministic.set_deterministic(true);
+        deterministic.record_read_latency(Duration::from_secs(10));
+        assert_eq!(deterministic.read_latency(), None);
+        assert_eq!(deterministic.effective_prefetch_count(), 64);
+    }
+
+    #[test]
+    fn test_deterministic_priority_upgrade() {
+        fn order(deterministic: bool) -> Vec<usize> {
+            let mut scheduler = Scheduler::new();
+            scheduler.set_deterministic(deterministic);
+            scheduler.schedule_task(Task::new(5));
+            scheduler.schedule_task(Task::with_priority(3, 10));
+            scheduler.schedule_task(Task::new(7));
+            // Upgrade an already queued task
+            scheduler.schedule_task(Task::with_priority(7, 10));
+            std::iter::from_fn(|| {
+                let task = scheduler.next_task()?;
+                scheduler.complete_task();
+                Some(task.frame_index)
+            }).collect()
+        }
+        // Default: the upgraded task jumps the queue
+        assert_eq!(order(false), vec![7, 3, 5]);
+        // Deterministic: ties are broken by frame index
+        assert_eq!(order(true), vec![3, 7, 5]);
+    }
+
+    #[test]
+    fn test_decode_budget_defers_prefetch() {
+        let clock = Arc::new(crate::clock::MockClock::new());
+        let mut scheduler = Scheduler::new();
+        scheduler.set_clock(SharedClock::new(clock.clone()));
+        scheduler.set_decode_budget(Some(Duration::from_millis(4)));
+        scheduler.prefetch(0);
+        assert_eq!(scheduler.next_task().unwrap().frame_index, 1);
+        // A slow decode uses up the budget of this tick and the next
+        scheduler.record_decode_time(Duration::from_millis(10));
+        assert!(scheduler.is_budget_exhausted());
+        assert!(scheduler.next_task().is_none());
+        // Requested frames are not held back
+        scheduler.schedule_task(Task::with_priority(20, Priority::Interactive.value()));
+        assert_eq!(scheduler.next_task().unwrap().frame_index, 20);
+        assert!(scheduler.next_task().is_none());
+        clock.advance(QOS_TICK);
+        assert!(scheduler.is_budget_exhausted());
+        clock.advance(QOS_TICK);
+        assert_eq!(scheduler.next_task().unwrap().frame_index, 2);
+
+        // Without a budget, and in deterministic mode, nothing is deferred
+        scheduler.record_decode_time(Duration::from_millis(100));
+        scheduler.set_deterministic(true);
+        assert!(!scheduler.is_budget_exhausted());
+        scheduler.set_deterministic(false);
+        scheduler.set_decode_budget(None);
+        scheduler.record_decode_time(Duration::from_millis(100));
+        assert_eq!(scheduler.next_task().unwrap().frame_index, 3);
+    }
+
+    #[test]
+    fn test_priority_lanes() {
+        let mut scheduler = Scheduler::new();
+        scheduler.prefetch(0); // frames 1.. in the Low lane
+        scheduler.schedule_task(Task::with_priority(40, Priority::Normal.value()));
+        scheduler.schedule_task(Task::with_priority(50, Priority::Interactive.value()));
+        scheduler.schedule_task(Task::with_priority(30, Priority::Low.value()));
+        assert_eq!(scheduler.next_task().unwrap().frame_index, 50);
+        assert_eq!(scheduler.next_task().unwrap().frame_index, 40);
+        assert_eq!(scheduler.next_task().unwrap().frame_index, 1);
+        assert!(Priority::Low < Priority::Normal && Priority::Normal < Priority::Interactive);
+        assert_eq!(Priority::default(), Priority::Low);
+    }
+
+    #[test]
+    fn test_range_tasks_expand_lazily() {
+        let mut scheduler = Scheduler::new();
+        scheduler.set_max_concurrent(100);
+        scheduler.schedule_range(0..10_000, Priority::Normal.value());
+        scheduler.schedule_range(10_000..20_000, Priority::Normal.value()); // merged
+        scheduler.schedule_task(Task::with_priority(500, Priority::Interactive.value()));
+        scheduler.schedule_task(Task::with_priority(3, Priority::Low.value()));
+        assert_eq!(scheduler.get_number_of_queued_range_frames(), 20_000);
+        assert_eq!(scheduler.range_queue.len(), 1);
+
+        let order: Vec<usize> = (0..4).map(|_| scheduler.next_task().unwrap().frame_index).collect();
+        assert_eq!(order, vec![500, 0, 1, 2]);
+        // Only the individually scheduled Low task and at most one expanded frame are queued
+        assert!(scheduler.get_number_of_queued_tasks() <= 2);
+        assert_eq!(scheduler.get_number_of_queued_range_frames(), 20_000 - 3);
+    }
+
+    #[test]
+    fn test_keyframe_dependencies_decode_first() {
+        let cache = Arc::new(FrameCache::new(16));
+        let mut scheduler = Scheduler::new();
+        scheduler.set_cache(Arc::clone(&cache));
+        scheduler.set_keyframes(vec![8, 0, 4]);
+        assert_eq!(scheduler.keyframe_for(3), 0);
+        assert_eq!(scheduler.keyframe_for(4), 4);
+        assert_eq!(scheduler.keyframe_for(100), 8);
+
+        scheduler.schedule_task(Task::with_priority(6, Priority::Interactive.value()));
+        assert_eq!(scheduler.next_task().unwrap().frame_index, 4);
+        assert_eq!(scheduler.next_task().unwrap().frame_index, 6);
+        scheduler.complete_task();
+        scheduler.complete_task();
+
+        // A decoded keyframe is not scheduled again
+        cache.insert(8, crate::formats::FrameData { polystream: vec![], bitmap: None, triangle_strip: None });
+        scheduler.schedule_task(Task::with_priority(9, Priority::Interactive.value()));
+        assert_eq!(scheduler.next_task().unwrap().frame_index, 9);
+        assert!(scheduler.next_task().is_none());
+    }
+
+    #[test]
+    fn test_preroll_decodes_history_after_seek() {
+        let cache = Arc::new(FrameCache::new(16));
+        cache.set_preroll(3);
+        let mut scheduler = Scheduler::new();
+        scheduler.set_cache(Arc::clone(&cache));
+        scheduler.set_prefetch_count(2);
+        scheduler.set_preroll(3);
+
+        cache.update_play_head(100);
+        assert_eq!(cache.get_start_index(), 97);
+        scheduler.schedule_task(Task::with_priority(100, Priority::Interactive.value()));
+        scheduler.prefetch(100);
+        // The target first, then its history ahead of the read-ahead
+        let frames: Vec<usize> = std::iter::from_fn(|| scheduler.next_task()).map(|t| t.frame_index).collect();
+        assert_eq!(frames, vec![100, 97, 98, 99, 101, 102]);
+
+        // Decoded history is not scheduled again
+        for frame in 97..100 {
+            cache.insert(frame, crate::formats::FrameData { polystream: vec![], bitmap: None, triangle_strip: None });
+        }
+        scheduler.prefetch(100);
+        assert!(std::iter::from_fn(|| scheduler.next_task()).all(|t| t.frame_index > 100));
+    }
+
+    #[test]
+    fn test_range_tasks_respect_cache_window() {
+        let cache = Arc::new(FrameCache::new(4));
+        let mut scheduler = Scheduler::new();
+        scheduler.set_cache(Arc::clone(&cache));
+        scheduler.schedule_range(0..100, Priority::Normal.value());
+        let frames: Vec<usize> = std::iter::from_fn(|| scheduler.next_task()).map(|t| t.frame_index).collect();
+        assert_eq!(frames, vec![0, 1, 2, 3]);
+        // The rest of the range waits for the window instead of being discarded
+        assert_eq!(scheduler.get_number_of_queued_range_frames(), 96);
+        for _ in 0..4 {
+            scheduler.complete_task();
+        }
+        cache.advance_start(4);
+        assert_eq!(scheduler.next_task().unwrap().frame_index, 4);
+    }
+
+    #[tokio::test]
+    async fn test_decode_limit() {
+        use super::DecodeLimit;
+        use tokio::time::{timeout, Duration};
+        let limit = DecodeLimit::default();
+        assert_eq!(limit.get(), 0);
+        assert!(limit.acquire().await.is_none());
+
+        limit.set(2);
+        assert_eq!(limit.get(), 2);
+        let first = limit.acquire().await.unwrap();
+        let _second = limit.acquire().await.unwrap();
+        assert!(timeout(Duration::from_millis(50), limit.acquire()).await.is_err());
+        drop(first);
+        assert!(timeout(Duration::from_millis(50), limit.acquire()).await.unwrap().is_some());
+
+        limit.set(0);
+        assert!(limit.acquire().await.is_none());
+    }
+}
@@ -0,0 +1,172 @@
+//! Per-frame analysis scripts
+//!
+//! With the `scripting` feature, a [Rhai](https://rhai.rs) script can run for every decoded frame of
+//! `AlphaStreamProcessor::decode_scripted`, for analysis that the `filter` expressions cannot
+//! express, without recompiling. The script is compiled once and then evaluated per frame.
+//!
+//! Variables: `frame`, `area`, `coverage`, `bbox` (a map with `x`, `y`, `w`, `h`) and `channels`, an
+//! array with one map per channel: `index`, `size` (stored polystream bytes), `area` and `coverage`.
+//! Values the script stores in the `out` map are returned with the frame, e.g. `out.aspect = bbox.w / bbox.h`.
+//! A script that evaluates to `false` vetoes the frame; any other result keeps it.
+//!
+//! ```text
+//! let hands = channels.filter(|c| c.area > 0).len();
+//! out.hands = hands;
+//! hands == 2
+//! ```
+
+use std::collections::BTreeMap;
+
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+use thiserror::Error;
+
+use crate::stats::MaskStats;
+
+/// Operations a script may run per frame before it is aborted, so a runaway loop cannot stall a scan
+pub const MAX_SCRIPT_OPERATIONS: u64 = 1_000_000;
+
+/// Error raised when a script cannot be compiled or fails on a frame
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ScriptError {
+    #[error("{message} at line {line}, column {column}")]
+    Compile { line: usize, column: usize, message: String },
+    #[error("script failed on frame {frame}: {message}")]
+    Runtime { frame: u32, message: String },
+}
+
+/// Statistics of one channel of a frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChannelStats {
+    pub index: usize,
+    /// Stored polystream bytes of the channel
+    pub size: u32,
+    pub stats: MaskStats,
+}
+
+/// What a script is evaluated on
+#[derive(Debug, Clone, Copy)]
+pub struct ScriptFrame<'a> {
+    pub frame_index: u32,
+    /// Statistics of the union of the selected channels
+    pub stats: &'a MaskStats,
+    pub channels: &'a [ChannelStats],
+}
+
+/// Outcome of a script on one frame
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ScriptResult {
+    /// False if the script vetoed the frame
+    pub keep: bool,
+    /// Contents of the script's `out` map
+    pub values: BTreeMap<String, serde_json::Value>,
+}
+
+/// A compiled per-frame script
+pub struct FrameScript {
+    engine: Engine,
+    ast: AST,
+}
+
+impl std::fmt::Debug for FrameScript {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrameScript").finish_non_exhaustive()
+    }
+}
+
+impl FrameScript {
+    /// Compile a script
+    pub fn compile(source: &str) -> Result<Self, ScriptError> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_SCRIPT_OPERATIONS);
+        let ast = engine.compile(source).map_err(|e| ScriptError::Compile {
+            line: e.1.line().unwrap_or(0),
+            column: e.1.position().unwrap_or(0),
+            message: e.0.to_string(),
+        })?;
+        Ok(FrameScript { engine, ast })
+    }
+
+    /// Run the script on a frame
+    pub fn eval(&self, frame: &ScriptFrame) -> Result<ScriptResult, ScriptError> {
+        let runtime_error = |message: String| ScriptError::Runtime { frame: frame.frame_index, message };
+        let mut scope = Scope::new();
+        scope.push_constant("frame", frame.frame_index as rhai::INT);
+        scope.push_constant("area", frame.stats.area as rhai::INT);
+        scope.push_constant("coverage", frame.stats.coverage);
+        scope.push_constant("bbox", bbox_map(frame.stats));
+        let channels: Array = frame.channels.iter().map(|channel| {
+            let mut map = Map::new();
+            map.insert("index".into(), Dynamic::from(channel.index as rhai::INT));
+            map.insert("size".into(), Dynamic::from(channel.size as rhai::INT));
+            map.insert("area".into(), Dynamic::from(channel.stats.area as rhai::INT));
+            map.insert("coverage".into(), Dynamic::from(channel.stats.coverage));
+            Dynamic::from_map(map)
+        }).collect();
+        scope.push_constant("channels", channels);
+        scope.push("out", Map::new());
+
+        let result = self.engine.eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast).map_err(|e| runtime_error(e.to_string()))?;
+        let out = scope.get_value::<Map>("out").ok_or_else(|| runtime_error("`out` is no longer a map".to_string()))?;
+        let values = out.into_iter()
+            .map(|(name, value)| {
+                rhai::serde::from_dynamic(&value)
+                    .map(|value| (name.to_string(), value))
+                    .map_err(|e| runtime_error(format!("out.{}: {}", name, e)))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(ScriptResult { keep: !matches!(result.as_bool(), Ok(false)), values })
+    }
+}
+
+fn bbox_map(stats: &MaskStats) -> Map {
+    let mut map = Map::new();
+    map.insert("x".into(), Dynamic::from(stats.bbox.x as rhai::INT));
+    map.insert("y".into(), Dynamic::from(stats.bbox.y as rhai::INT));
+    map.insert("w".into(), Dynamic::from(stats.bbox.w as rhai::INT));
+    map.insert("h".into(), Dynamic::from(stats.bbox.h as rhai::INT));
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::BoundingBox;
+
+    fn stats(area: u64, w: u32, h: u32) -> MaskStats {
+        MaskStats { area, coverage: area as f64 / 10000.0, bbox: BoundingBox { x: 10, y: 20, w, h } }
+    }
+
+    #[test]
+    fn test_veto_and_values() {
+        let script = FrameScript::compile("out.aspect = bbox.w.to_float() / bbox.h.to_float(); out.frame = frame; area > 100").unwrap();
+        let channels = [ChannelStats { index: 0, size: 12, stats: stats(500, 20, 10) }];
+        let result = script.eval(&ScriptFrame { frame_index: 7, stats: &stats(500, 20, 10), channels: &channels }).unwrap();
+        assert!(result.keep);
+        assert_eq!(result.values["aspect"], serde_json::json!(2.0));
+        assert_eq!(result.values["frame"], serde_json::json!(7));
+        assert!(!script.eval(&ScriptFrame { frame_index: 7, stats: &stats(50, 20, 10), channels: &channels }).unwrap().keep);
+    }
+
+    #[test]
+    fn test_channels() {
+        let script = FrameScript::compile("let visible = channels.filter(|c| c.area > 0); out.visible = visible.map(|c| c.index); visible.len() == 2").unwrap();
+        let channel = |index, area| ChannelStats { index, size: 4, stats: stats(area, 1, 1) };
+        let frame_stats = stats(2, 1, 1);
+        let result = script.eval(&ScriptFrame { frame_index: 0, stats: &frame_stats, channels: &[channel(0, 1), channel(1, 0), channel(2, 1)] }).unwrap();
+        assert!(result.keep);
+        assert_eq!(result.values["visible"], serde_json::json!([0, 2]));
+        assert!(!script.eval(&ScriptFrame { frame_index: 0, stats: &frame_stats, channels: &[channel(0, 1)] }).unwrap().keep);
+    }
+
+    #[test]
+    fn test_script_errors() {
+        assert!(matches!(FrameScript::compile("area >"), Err(ScriptError::Compile { line: 1, .. })));
+        let script = FrameScript::compile("out.x = undefined_variable").unwrap();
+        let frame_stats = stats(0, 0, 0);
+        let frame = ScriptFrame { frame_index: 3, stats: &frame_stats, channels: &[] };
+        assert!(matches!(script.eval(&frame), Err(ScriptError::Runtime { frame: 3, .. })));
+        // Runaway scripts are stopped
+        let script = FrameScript::compile("loop { }").unwrap();
+        assert!(script.eval(&frame).is_err());
+    }
+}
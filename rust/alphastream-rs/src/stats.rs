@@ -0,0 +1,149 @@
+// Mask statistics module
+// Cheap per-frame measurements of a rasterized R8 mask (covered area, bounding box),
+// used to find frames of interest without looking at them, e.g. by the CLI --filter option,
+// and heatmaps aggregating coverage over a range of frames.
+
+/// Axis-aligned bounding box of the covered pixels, in output pixel coordinates.
+/// All fields are 0 for an empty mask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BoundingBox {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+/// Statistics of a single mask
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MaskStats {
+    /// Number of non-zero pixels
+    pub area: u64,
+    /// Fraction of the image that is covered, 0.0 - 1.0
+    pub coverage: f64,
+    /// Bounding box of the non-zero pixels
+    pub bbox: BoundingBox,
+}
+
+impl MaskStats {
+    /// Measure an R8 mask.
+    ///
+    /// # Arguments
+    /// * `mask` - Row-major mask, `width * height` bytes. Any non-zero pixel counts as covered.
+    /// * `width` - Mask width.
+    /// * `height` - Mask height.
+    pub fn from_mask(mask: &[u8], width: u32, height: u32) -> Self {
+        let mut area = 0u64;
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0u32, 0u32);
+        if width > 0 {
+            for (y, row) in mask.chunks(width as usize).take(height as usize).enumerate() {
+                let y = y as u32;
+                for (x, &pixel) in row.iter().enumerate() {
+                    if pixel == 0 {
+                        continue;
+                    }
+                    let x = x as u32;
+                    area += 1;
+                    min_x = min_x.min(x);
+                    max_x = max_x.max(x);
+                    min_y = min_y.min(y);
+                    max_y = max_y.max(y);
+                }
+            }
+        }
+
+        let bbox = if area == 0 {
+            BoundingBox::default()
+        } else {
+            BoundingBox { x: min_x, y: min_y, w: max_x - min_x + 1, h: max_y - min_y + 1 }
+        };
+        let total = width as u64 * height as u64;
+        let coverage = if total == 0 { 0.0 } else { area as f64 / total as f64 };
+        Self { area, coverage, bbox }
+    }
+}
+
+/// Per-pixel count of how many frames covered each pixel, summed over a range of frames.
+/// Shows where on screen an object spends its time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heatmap {
+    pub width: u32,
+    pub height: u32,
+    /// Number of frames accumulated so far
+    pub frames: u32,
+    /// Row-major coverage counts, saturating at u16::MAX
+    pub counts: Vec<u16>,
+}
+
+impl Heatmap {
+    /// Create an empty heatmap
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height, frames: 0, counts: vec![0; (width * height) as usize] }
+    }
+
+    /// Add one R8 mask. Any non-zero pixel counts as covered.
+    ///
+    /// # Panics
+    /// Panics if the mask size does not match the heatmap.
+    pub fn accumulate(&mut self, mask: &[u8]) {
+        assert_eq!(mask.len(), self.counts.len(), "mask does not match heatmap dimensions");
+        for (count, &pixel) in self.counts.iter_mut().zip(mask) {
+            if pixel > 0 {
+                *count = count.saturating_add(1);
+            }
+        }
+        self.frames += 1;
+    }
+
+    /// Fraction of accumulated frames that covered each pixel, 0.0 - 1.0
+    pub fn normalized(&self) -> Vec<f32> {
+        let frames = self.frames.max(1) as f32;
+        self.counts.iter().map(|&c| c as f32 / frames).collect()
+    }
+
+    /// Counts stretched so the hottest pixel maps to u16::MAX, for viewing as a 16-bit image
+    pub fn to_gray16(&self) -> Vec<u16> {
+        let max = self.counts.iter().copied().max().unwrap_or(0).max(1) as u32;
+        self.counts.iter().map(|&c| (c as u32 * u16::MAX as u32 / max) as u16).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_mask() {
+        let stats = MaskStats::from_mask(&[0; 16], 4, 4);
+        assert_eq!(stats.area, 0);
+        assert_eq!(stats.coverage, 0.0);
+        assert_eq!(stats.bbox, BoundingBox::default());
+    }
+
+    #[test]
+    fn test_area_and_bbox() {
+        #[rustfmt::skip]
+        let mask = [
+            0, 0,   0,   0,
+            0, 255, 0,   0,
+            0, 255, 255, 0,
+            0, 0,   0,   0,
+        ];
+        let stats = MaskStats::from_mask(&mask, 4, 4);
+        assert_eq!(stats.area, 3);
+        assert_eq!(stats.coverage, 3.0 / 16.0);
+        assert_eq!(stats.bbox, BoundingBox { x: 1, y: 1, w: 2, h: 2 });
+    }
+
+    #[test]
+    fn test_heatmap_accumulate() {
+        let mut heatmap = Heatmap::new(2, 1);
+        heatmap.accumulate(&[255, 0]);
+        heatmap.accumulate(&[255, 1]);
+        heatmap.accumulate(&[0, 0]);
+        heatmap.accumulate(&[255, 0]);
+        assert_eq!(heatmap.frames, 4);
+        assert_eq!(heatmap.counts, vec![3, 1]);
+        assert_eq!(heatmap.normalized(), vec![0.75, 0.25]);
+        assert_eq!(heatmap.to_gray16(), vec![u16::MAX, u16::MAX / 3]);
+    }
+}
@@ -0,0 +1,574 @@
+// Store module
+// Content-addressed frame store: every frame is kept once as a blob named by the SHA-256 of its
+// polystream, and a scene is a manifest listing its frames' hashes. Scenes that share frames (takes
+// of the same shot, re-exports with a few fixed frames) share the blobs, and syncing a scene to
+// another machine only copies the blobs that machine does not have yet.
+//
+// Layout of a store directory:
+//   blobs/ab/cdef…   ASVP frame records (uncompressed length + zlib polystream), by hash
+//   manifests/NAME.json
+//
+// StoreTransport serves a manifest as an ASVP file, so the processor plays `store://DIR#NAME` URIs.
+//
+// Frames imported from ASVR files are decrypted content. A store opened with a CacheKey keeps it
+// encrypted at rest: blobs are sealed with ChaCha20-Poly1305 and named by an HMAC of the polystream
+// instead of its plain hash, so neither the masks nor which known frames a store holds can be
+// recovered from the directory without the key. Encryption is deterministic (the nonce is derived
+// from the content), so deduplication and sync between stores with the same key keep working.
+//   store.json       whether the store is encrypted, and a check value to detect a wrong key
+
+use std::fmt::Write as _;
+#[cfg(any(test, feature = "unstable"))]
+use std::future::Future;
+use std::path::{Path, PathBuf};
+#[cfg(any(test, feature = "unstable"))]
+use std::pin::Pin;
+
+#[cfg(any(test, feature = "unstable"))]
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::ChaCha20Poly1305;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+#[cfg(any(test, feature = "unstable"))]
+use sha2::Digest;
+
+use crate::formats::{write_asvp_records, FormatError};
+#[cfg(any(test, feature = "unstable"))]
+use crate::formats::{asvp_frame_record, decode_asvp_frame_record, ASFormat};
+#[cfg(any(test, feature = "unstable"))]
+use crate::transport::{Transport, TransportError};
+
+/// URI scheme of store sources, `store://DIR#NAME`
+pub const STORE_SCHEME: &str = "store://";
+
+/// Scene in a store: the blob hash (lowercase hex SHA-256 of the polystream) of every frame
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoreManifest {
+    pub frames: Vec<String>,
+}
+
+impl StoreManifest {
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn frame_count(&self) -> u32 {
+        self.frames.len() as u32
+    }
+}
+
+/// Locally provided key that encrypts a FrameStore at rest.
+/// Never serialized or printed; `Debug` shows a placeholder.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CacheKey([u8; 32]);
+
+impl CacheKey {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self(key)
+    }
+
+    /// HMAC-SHA256 of `data` under a subkey for `purpose`, so one key never serves two purposes
+    fn mac(&self, purpose: &[u8], data: &[u8]) -> [u8; 32] {
+        let mut subkey = <Hmac<Sha256> as Mac>::new_from_slice(&self.0).expect("HMAC accepts any key length");
+        subkey.update(purpose);
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&subkey.finalize().into_bytes()).expect("HMAC accepts any key length");
+        mac.update(data);
+        mac.finalize().into_bytes().into()
+    }
+}
+
+impl std::str::FromStr for CacheKey {
+    type Err = FormatError;
+
+    /// Parse 64 hex digits
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || FormatError::InvalidFormat("A cache key must be 64 hex digits".to_string());
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(invalid());
+        }
+        let mut key = [0u8; 32];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).map_err(|_| invalid())?;
+        }
+        Ok(Self(key))
+    }
+}
+
+impl std::fmt::Debug for CacheKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CacheKey(..)")
+    }
+}
+
+/// Contents of store.json
+#[derive(Debug, Serialize, Deserialize)]
+struct StoreInfo {
+    encrypted: bool,
+    /// MAC of a fixed message, to tell a wrong key from corrupt blobs
+    #[serde(default)]
+    key_check: Option<String>,
+}
+
+const KEY_CHECK_PURPOSE: &[u8] = b"alphastream-store-check";
+#[cfg(any(test, feature = "unstable"))]
+const ADDRESS_PURPOSE: &[u8] = b"alphastream-store-address";
+const ENCRYPTION_PURPOSE: &[u8] = b"alphastream-store-encryption";
+#[cfg(any(test, feature = "unstable"))]
+const NONCE_PURPOSE: &[u8] = b"alphastream-store-nonce";
+const NONCE_LEN: usize = 12;
+
+/// A content-addressed frame store in a directory
+#[derive(Debug, Clone)]
+pub struct FrameStore {
+    root: PathBuf,
+    key: Option<CacheKey>,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::with_capacity(bytes.len() * 2), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+/// Hash naming the blob of a polystream in an unencrypted store
+#[cfg(any(test, feature = "unstable"))]
+pub fn blob_hash(polystream: &[u8]) -> String {
+    to_hex(&Sha256::digest(polystream))
+}
+
+fn is_blob_hash(hash: &str) -> bool {
+    hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+impl FrameStore {
+    /// Open the unencrypted store in `root`, creating the directory layout if needed
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, FormatError> {
+        Self::open_with_key(root, None)
+    }
+
+    /// Open the store in `root` encrypted with `key`, creating it if needed
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn open_encrypted(root: impl Into<PathBuf>, key: CacheKey) -> Result<Self, FormatError> {
+        Self::open_with_key(root, Some(key))
+    }
+
+    /// Open with an optional key. Fails if the store's encryption does not match, or the key is wrong.
+    pub fn open_with_key(root: impl Into<PathBuf>, key: Option<CacheKey>) -> Result<Self, FormatError> {
+        let root = root.into();
+        std::fs::create_dir_all(root.join("blobs"))?;
+        std::fs::create_dir_all(root.join("manifests"))?;
+        let store = Self { root, key };
+        let key_check = store.key.as_ref().map(|key| to_hex(&key.mac(KEY_CHECK_PURPOSE, &[])));
+        let info_path = store.root.join("store.json");
+        let info: StoreInfo = match std::fs::read(&info_path) {
+            Ok(json) => serde_json::from_slice(&json)
+                .map_err(|e| FormatError::InvalidFormat(format!("Invalid store.json: {}", e)))?,
+            // Stores created before store.json existed are unencrypted
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && std::fs::read_dir(store.root.join("blobs"))?.next().is_some() => {
+                StoreInfo { encrypted: false, key_check: None }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let info = StoreInfo { encrypted: key_check.is_some(), key_check: key_check.clone() };
+                let json = serde_json::to_vec_pretty(&info).expect("store info is always serializable");
+                Self::write_atomic(&info_path, &json)?;
+                info
+            }
+            Err(e) => return Err(e.into()),
+        };
+        match (info.encrypted, &key_check) {
+            (true, None) => Err(FormatError::InvalidFormat(format!("Store {} is encrypted, a cache key is required", store.root.display()))),
+            (false, Some(_)) => Err(FormatError::InvalidFormat(format!("Store {} is not encrypted", store.root.display()))),
+            (true, Some(_)) if info.key_check != key_check => Err(FormatError::Decryption),
+            _ => Ok(store),
+        }
+    }
+
+    /// Whether blobs are encrypted at rest
+    #[cfg(feature = "unstable")]
+    pub fn is_encrypted(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Name of a polystream's blob: its SHA-256, or a keyed MAC in an encrypted store
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn frame_hash(&self, polystream: &[u8]) -> String {
+        match &self.key {
+            Some(key) => to_hex(&key.mac(ADDRESS_PURPOSE, polystream)),
+            None => blob_hash(polystream),
+        }
+    }
+
+    /// Blob contents for an ASVP frame record: the record itself, or nonce + sealed record
+    #[cfg(any(test, feature = "unstable"))]
+    fn seal(&self, hash: &str, record: Vec<u8>) -> Result<Vec<u8>, FormatError> {
+        let Some(key) = &self.key else { return Ok(record) };
+        // Deterministic nonce from the content address: equal records give equal blobs
+        let nonce = key.mac(NONCE_PURPOSE, hash.as_bytes());
+        let cipher = ChaCha20Poly1305::new(&key.mac(ENCRYPTION_PURPOSE, &[]).into());
+        let sealed = cipher.encrypt(nonce[..NONCE_LEN].into(), Payload { msg: &record, aad: hash.as_bytes() })
+            .map_err(|_| FormatError::Decryption)?;
+        let mut blob = nonce[..NONCE_LEN].to_vec();
+        blob.extend_from_slice(&sealed);
+        Ok(blob)
+    }
+
+    /// ASVP frame record from blob contents, see `seal`
+    fn open_blob(&self, hash: &str, blob: Vec<u8>) -> Result<Vec<u8>, FormatError> {
+        let Some(key) = &self.key else { return Ok(blob) };
+        if blob.len() < NONCE_LEN {
+            return Err(FormatError::Decryption);
+        }
+        let cipher = ChaCha20Poly1305::new(&key.mac(ENCRYPTION_PURPOSE, &[]).into());
+        cipher.decrypt(blob[..NONCE_LEN].into(), Payload { msg: &blob[NONCE_LEN..], aad: hash.as_bytes() })
+            .map_err(|_| FormatError::Decryption)
+    }
+
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn blob_path(&self, hash: &str) -> Result<PathBuf, FormatError> {
+        if !is_blob_hash(hash) {
+            return Err(FormatError::InvalidFormat(format!("Invalid blob hash '{}'", hash)));
+        }
+        Ok(self.root.join("blobs").join(&hash[..2]).join(&hash[2..]))
+    }
+
+    fn manifest_path(&self, name: &str) -> Result<PathBuf, FormatError> {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c)) || name.starts_with('.') {
+            return Err(FormatError::InvalidFormat(format!("Invalid scene name '{}'", name)));
+        }
+        Ok(self.root.join("manifests").join(format!("{}.json", name)))
+    }
+
+    /// Write `data` to `path` through a temporary file, so readers never see a partial file
+    fn write_atomic(path: &Path, data: &[u8]) -> Result<(), FormatError> {
+        let dir = path.parent().expect("store paths have a parent");
+        std::fs::create_dir_all(dir)?;
+        let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+        std::io::Write::write_all(&mut temp, data)?;
+        temp.persist(path).map_err(|e| FormatError::Io(e.error))?;
+        Ok(())
+    }
+
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn has_blob(&self, hash: &str) -> bool {
+        self.blob_path(hash).is_ok_and(|path| path.exists())
+    }
+
+    /// Store a polystream, returning its hash. Polystreams already in the store are not written again.
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn put_frame(&self, polystream: &[u8]) -> Result<String, FormatError> {
+        let hash = self.frame_hash(polystream);
+        let path = self.blob_path(&hash)?;
+        if !path.exists() {
+            Self::write_atomic(&path, &self.seal(&hash, asvp_frame_record(polystream)?)?)?;
+        }
+        Ok(hash)
+    }
+
+    /// ASVP frame record of a blob, decrypted
+    fn record(&self, hash: &str) -> Result<Vec<u8>, FormatError> {
+        self.open_blob(hash, self.blob(hash)?)
+    }
+
+    /// Raw blob as stored, as copied between stores with the same key
+    fn blob(&self, hash: &str) -> Result<Vec<u8>, FormatError> {
+        let path = self.blob_path(hash)?;
+        std::fs::read(&path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => FormatError::InvalidFormat(format!("Missing blob {}", hash)),
+            _ => FormatError::Io(e),
+        })
+    }
+
+    /// Read a polystream, checking it against its hash
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn get_frame(&self, hash: &str) -> Result<Vec<u8>, FormatError> {
+        let polystream = decode_asvp_frame_record(&self.record(hash)?)?;
+        if self.frame_hash(&polystream) != hash {
+            return Err(FormatError::InvalidFormat(format!("Blob {} is corrupt", hash)));
+        }
+        Ok(polystream)
+    }
+
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn write_manifest(&self, name: &str, manifest: &StoreManifest) -> Result<(), FormatError> {
+        let json = serde_json::to_vec_pretty(manifest)
+            .map_err(|e| FormatError::InvalidFormat(format!("Invalid manifest: {}", e)))?;
+        Self::write_atomic(&self.manifest_path(name)?, &json)
+    }
+
+    pub fn read_manifest(&self, name: &str) -> Result<StoreManifest, FormatError> {
+        let json = std::fs::read(self.manifest_path(name)?)?;
+        let manifest: StoreManifest = serde_json::from_slice(&json)
+            .map_err(|e| FormatError::InvalidFormat(format!("Invalid manifest '{}': {}", name, e)))?;
+        if let Some(hash) = manifest.frames.iter().find(|hash| !is_blob_hash(hash)) {
+            return Err(FormatError::InvalidFormat(format!("Invalid blob hash '{}' in manifest '{}'", hash, name)));
+        }
+        Ok(manifest)
+    }
+
+    /// Names of the scenes in the store, sorted
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn scenes(&self) -> Result<Vec<String>, FormatError> {
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(self.root.join("manifests"))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Decode every frame of `format` into the store and save the scene as `name`
+    #[cfg(any(test, feature = "unstable"))]
+    pub async fn import(&self, name: &str, format: &mut impl ASFormat) -> Result<StoreManifest, FormatError> {
+        let frame_count = format.metadata().await?.frame_count;
+        let mut frames = Vec::with_capacity(frame_count as usize);
+        for frame_index in 0..frame_count {
+            let frame = format.decode_frame(frame_index).await?;
+            frames.push(self.put_frame(&frame.polystream)?);
+        }
+        let manifest = StoreManifest { frames };
+        self.write_manifest(name, &manifest)?;
+        Ok(manifest)
+    }
+
+    /// Blobs of `manifest` this store does not have, each listed once
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn missing_blobs(&self, manifest: &StoreManifest) -> Vec<String> {
+        let mut missing: Vec<String> = manifest.frames.iter()
+            .filter(|hash| !self.has_blob(hash))
+            .cloned()
+            .collect();
+        missing.sort();
+        missing.dedup();
+        missing
+    }
+
+    /// Copy scene `name` from `other` into this store, transferring only the missing blobs.
+    /// Blobs are verified before they are written. Between stores with different keys (or an
+    /// encrypted and a plain store) every frame is re-encoded, as the blob names differ.
+    ///
+    /// # Returns
+    /// The number of blobs written
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn sync_from(&self, other: &FrameStore, name: &str) -> Result<usize, FormatError> {
+        let manifest = other.read_manifest(name)?;
+        if self.key != other.key {
+            let mut written = 0;
+            let mut frames = Vec::with_capacity(manifest.frames.len());
+            for hash in &manifest.frames {
+                let polystream = other.get_frame(hash)?;
+                let new_hash = self.frame_hash(&polystream);
+                written += usize::from(!self.has_blob(&new_hash));
+                frames.push(self.put_frame(&polystream)?);
+            }
+            self.write_manifest(name, &StoreManifest { frames })?;
+            return Ok(written);
+        }
+        let missing = self.missing_blobs(&manifest);
+        for hash in &missing {
+            let blob = other.blob(hash)?;
+            let polystream = decode_asvp_frame_record(&self.open_blob(hash, blob.clone())?)?;
+            if self.frame_hash(&polystream) != *hash {
+                return Err(FormatError::InvalidFormat(format!("Blob {} is corrupt", hash)));
+            }
+            Self::write_atomic(&self.blob_path(hash)?, &blob)?;
+        }
+        // The manifest goes last, so an interrupted sync never leaves a scene with missing frames
+        self.write_manifest(name, &manifest)?;
+        Ok(missing.len())
+    }
+
+    /// Scene `name` as a complete ASVP file
+    pub fn to_asvp(&self, name: &str) -> Result<Vec<u8>, FormatError> {
+        let manifest = self.read_manifest(name)?;
+        let records = manifest.frames.iter().map(|hash| self.record(hash)).collect::<Result<Vec<_>, _>>()?;
+        let mut asvp = Vec::new();
+        write_asvp_records(&mut asvp, &records)?;
+        Ok(asvp)
+    }
+}
+
+/// Split a `store://DIR#NAME` URI into the store directory and scene name
+pub fn parse_store_uri(uri: &str) -> Option<(&str, &str)> {
+    let (dir, name) = uri.strip_prefix(STORE_SCHEME)?.rsplit_once('#')?;
+    (!dir.is_empty() && !name.is_empty()).then_some((dir, name))
+}
+
+/// Transport serving a store scene as an ASVP file, for `store://DIR#NAME` URIs.
+/// Only opens unencrypted stores; the processor builder opens encrypted ones with its `cache_key`.
+#[cfg(any(test, feature = "unstable"))]
+pub struct StoreTransport;
+
+#[cfg(any(test, feature = "unstable"))]
+pub struct StoreReader {
+    data: Bytes,
+}
+
+#[cfg(any(test, feature = "unstable"))]
+impl Transport for StoreTransport {
+    type Reader = StoreReader;
+
+    // Assembles the ASVP file from the scene's blobs; only the sizes table is compressed on open
+    fn open(uri: &str) -> Pin<Box<dyn Future<Output = Result<Self::Reader, TransportError>> + Send + '_>> {
+        Box::pin(async move {
+            let (dir, name) = parse_store_uri(uri)
+                .ok_or_else(|| TransportError::Other(format!("Not a store URI: {}", uri)))?;
+            let (dir, name) = (dir.to_string(), name.to_string());
+            let data = tokio::task::spawn_blocking(move || FrameStore::open(dir)?.to_asvp(&name))
+                .await
+                .map_err(|e| TransportError::Other(e.to_string()))?
+                .map_err(|e| match e {
+                    FormatError::Io(e) if e.kind() == std::io::ErrorKind::NotFound => TransportError::NotFound,
+                    e => TransportError::Other(e.to_string()),
+                })?;
+            Ok(StoreReader { data: Bytes::from(data) })
+        })
+    }
+
+    fn len(reader: &Self::Reader) -> u64 {
+        reader.data.len() as u64
+    }
+
+    fn read_range(reader: &Self::Reader, offset: u64, size: u32) -> Pin<Box<dyn Future<Output = Result<Bytes, TransportError>> + Send + 'static>> {
+        let data = reader.data.clone();
+        Box::pin(async move {
+            let start = (offset as usize).min(data.len());
+            let end = start.saturating_add(size as usize).min(data.len());
+            Ok(data.slice(start..end))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{ASVPFormat, ASVPWriter, FrameData};
+
+    fn polystream(fill: u8) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.extend_from_slice(&64u32.to_le_bytes());
+        data.extend_from_slice(&[fill; 64]);
+        data
+    }
+
+    async fn asvp(fills: &[u8]) -> ASVPFormat<std::io::Cursor<Vec<u8>>> {
+        let mut writer = ASVPWriter::new(Vec::new());
+        for &fill in fills {
+            writer.add_frame(FrameData { polystream: polystream(fill), bitmap: None, triangle_strip: None });
+        }
+        ASVPFormat::new(std::io::Cursor::new(writer.write_all().unwrap())).await.unwrap()
+    }
+
+    fn blob_count(store: &FrameStore) -> usize {
+        std::fs::read_dir(store.root().join("blobs")).unwrap()
+            .map(|dir| std::fs::read_dir(dir.unwrap().path()).unwrap().count())
+            .sum()
+    }
+
+    #[tokio::test]
+    async fn test_import_deduplicates_frames() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FrameStore::open(dir.path()).unwrap();
+        let take1 = store.import("take1", &mut asvp(&[1, 2, 2, 3]).await).await.unwrap();
+        store.import("take2", &mut asvp(&[1, 2, 9, 3]).await).await.unwrap();
+        assert_eq!(take1.frame_count(), 4);
+        assert_eq!(take1.frames[1], take1.frames[2]);
+        assert_eq!(blob_count(&store), 4);
+        assert_eq!(store.scenes().unwrap(), vec!["take1", "take2"]);
+        assert_eq!(store.get_frame(&take1.frames[3]).unwrap(), polystream(3));
+
+        // The assembled file plays like the original
+        let mut format = ASVPFormat::new(std::io::Cursor::new(store.to_asvp("take2").unwrap())).await.unwrap();
+        assert_eq!(format.metadata().await.unwrap().frame_count, 4);
+        assert_eq!(format.decode_frame(2).await.unwrap().polystream, polystream(9));
+    }
+
+    #[tokio::test]
+    async fn test_sync_copies_only_missing_blobs() {
+        let (a, b) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let (source, target) = (FrameStore::open(a.path()).unwrap(), FrameStore::open(b.path()).unwrap());
+        source.import("take1", &mut asvp(&[1, 2, 3]).await).await.unwrap();
+        let take2 = source.import("take2", &mut asvp(&[1, 2, 4]).await).await.unwrap();
+
+        assert_eq!(target.sync_from(&source, "take1").unwrap(), 3);
+        assert_eq!(target.missing_blobs(&take2).len(), 1);
+        assert_eq!(target.sync_from(&source, "take2").unwrap(), 1);
+        assert_eq!(target.to_asvp("take2").unwrap(), source.to_asvp("take2").unwrap());
+
+        // Corrupt blobs are detected on read
+        let hash = &take2.frames[2];
+        std::fs::write(target.blob_path(hash).unwrap(), asvp_frame_record(&polystream(5)).unwrap()).unwrap();
+        assert!(target.get_frame(hash).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_store() {
+        let (a, b) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let key = CacheKey::new([7; 32]);
+        let store = FrameStore::open_encrypted(a.path(), key.clone()).unwrap();
+        let manifest = store.import("scene", &mut asvp(&[1, 2]).await).await.unwrap();
+        assert_eq!(store.get_frame(&manifest.frames[1]).unwrap(), polystream(2));
+
+        // Neither the frames nor their plain hashes can be found in the directory
+        assert!(!store.has_blob(&blob_hash(&polystream(1))));
+        let blob = store.blob(&manifest.frames[0]).unwrap();
+        assert_ne!(blob, asvp_frame_record(&polystream(1)).unwrap());
+        assert!(decode_asvp_frame_record(&blob).is_err());
+
+        // The key is required, and must be the right one
+        assert!(FrameStore::open(a.path()).is_err());
+        assert!(matches!(FrameStore::open_encrypted(a.path(), CacheKey::new([8; 32])), Err(FormatError::Decryption)));
+        assert_eq!("07".repeat(32).parse::<CacheKey>().unwrap(), key);
+        assert!("07".parse::<CacheKey>().is_err());
+        assert_eq!(format!("{:?}", key), "CacheKey(..)");
+
+        // Same key: blobs are copied as they are; other key or none: frames are re-encoded
+        let copy = FrameStore::open_encrypted(b.path().join("copy"), key.clone()).unwrap();
+        assert_eq!(copy.sync_from(&store, "scene").unwrap(), 2);
+        assert_eq!(copy.read_manifest("scene").unwrap(), manifest);
+        let plain = FrameStore::open(b.path().join("plain")).unwrap();
+        assert_eq!(plain.sync_from(&store, "scene").unwrap(), 2);
+        assert_eq!(plain.to_asvp("scene").unwrap(), store.to_asvp("scene").unwrap());
+        assert!(FrameStore::open_encrypted(b.path().join("plain"), key.clone()).is_err());
+
+        // The builder opens encrypted stores with its cache key
+        let uri = format!("{}{}#scene", STORE_SCHEME, a.path().display());
+        let builder = crate::api::AlphaStreamProcessorBuilder::new().processing_mode(crate::ProcessingMode::PolystreamOnly);
+        assert!(builder.clone().build_asvp(&uri, 16, 16).await.is_err());
+        let processor = builder.cache_key(key).build_asvp(&uri, 16, 16).await.unwrap();
+        assert_eq!(processor.metadata().await.unwrap().frame_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_store_transport() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FrameStore::open(dir.path()).unwrap();
+        store.import("scene", &mut asvp(&[7, 8]).await).await.unwrap();
+        let uri = format!("{}{}#scene", STORE_SCHEME, dir.path().display());
+        assert_eq!(parse_store_uri(&uri), Some((dir.path().to_str().unwrap(), "scene")));
+        assert!(parse_store_uri("store://#scene").is_none());
+        assert!(store.read_manifest("../scene").is_err());
+
+        let reader = crate::transport::CoalescingReader::<StoreTransport>::open(&uri).await.unwrap();
+        let mut format = ASVPFormat::new(reader).await.unwrap();
+        assert_eq!(format.decode_frame(1).await.unwrap().polystream, polystream(8));
+        assert!(matches!(StoreTransport::open(&format!("{}{}#missing", STORE_SCHEME, dir.path().display())).await, Err(TransportError::NotFound)));
+
+        // The builder opens store URIs like files
+        let processor = crate::api::AlphaStreamProcessorBuilder::new()
+            .processing_mode(crate::ProcessingMode::PolystreamOnly)
+            .build_asvp(&uri, 16, 16).await.unwrap();
+        assert_eq!(processor.metadata().await.unwrap().frame_count, 2);
+    }
+}
@@ -0,0 +1,177 @@
+// Telemetry module
+// Hook for shipping operational events (decodes, seeks, cache evictions, transport retries) to whatever
+// monitoring a deployment uses. A TelemetryExporter set on the builder receives every event; the built-in
+// exporters drop them, log them, or sum them up into counters that can be polled.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use crate::logging::{self, LogLevel};
+
+/// Something that happened in a processor
+#[derive(Debug, Clone, PartialEq)]
+pub enum TelemetryEvent {
+    /// A frame was decoded in the background and put into the cache
+    FrameDecoded { frame_index: usize, decode: Duration, process: Duration },
+    /// A request moved the play head outside the buffer window, so the cache was cleared
+    Seek { from: usize, to: usize },
+    /// Decoded frames were dropped from the cache, by a seek, the window sliding on or a refused entitlement
+    CacheEvict { frames: usize },
+    /// A range request failed and is tried again after `backoff`
+    TransportRetry { url: String, attempt: u32, backoff: Duration },
+}
+
+/// Receives the telemetry events of the processors it is configured on.
+/// `export` is called on the thread the event happened on, often a decode worker, and must not block;
+/// exporters that send events over the network should queue them.
+pub trait TelemetryExporter: Send + Sync {
+    fn export(&self, event: &TelemetryEvent);
+}
+
+/// Drops every event, the default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopExporter;
+
+impl TelemetryExporter for NoopExporter {
+    fn export(&self, _event: &TelemetryEvent) {}
+}
+
+/// Writes every event to the log at the given level
+#[derive(Debug, Clone, Copy)]
+pub struct LogExporter {
+    pub level: LogLevel,
+}
+
+impl Default for LogExporter {
+    fn default() -> Self {
+        Self { level: LogLevel::Debug }
+    }
+}
+
+impl TelemetryExporter for LogExporter {
+    fn export(&self, event: &TelemetryEvent) {
+        logging::log(self.level, format_args!("Telemetry: {:?}", event));
+    }
+}
+
+/// Totals of the events a MetricsExporter received
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TelemetryMetrics {
+    pub frames_decoded: u64,
+    /// Summed decode and processing time of the decoded frames
+    pub decode_time: Duration,
+    pub seeks: u64,
+    pub frames_evicted: u64,
+    pub transport_retries: u64,
+}
+
+/// Counts events, for deployments that scrape counters (e.g. into Prometheus) instead of taking events
+#[derive(Debug, Default)]
+pub struct MetricsExporter {
+    frames_decoded: AtomicU64,
+    decode_micros: AtomicU64,
+    seeks: AtomicU64,
+    frames_evicted: AtomicU64,
+    transport_retries: AtomicU64,
+}
+
+impl MetricsExporter {
+    /// Totals so far
+    pub fn snapshot(&self) -> TelemetryMetrics {
+        TelemetryMetrics {
+            frames_decoded: self.frames_decoded.load(Ordering::Relaxed),
+            decode_time: Duration::from_micros(self.decode_micros.load(Ordering::Relaxed)),
+            seeks: self.seeks.load(Ordering::Relaxed),
+            frames_evicted: self.frames_evicted.load(Ordering::Relaxed),
+            transport_retries: self.transport_retries.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl TelemetryExporter for MetricsExporter {
+    fn export(&self, event: &TelemetryEvent) {
+        match event {
+            TelemetryEvent::FrameDecoded { decode, process, .. } => {
+                self.frames_decoded.fetch_add(1, Ordering::Relaxed);
+                self.decode_micros.fetch_add((*decode + *process).as_micros() as u64, Ordering::Relaxed);
+            }
+            TelemetryEvent::Seek { .. } => {
+                self.seeks.fetch_add(1, Ordering::Relaxed);
+            }
+            TelemetryEvent::CacheEvict { frames } => {
+                self.frames_evicted.fetch_add(*frames as u64, Ordering::Relaxed);
+            }
+            TelemetryEvent::TransportRetry { .. } => {
+                self.transport_retries.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Shared handle to an exporter, as stored by the builder, processor, cache and HTTP readers.
+/// Handles compare equal when they point to the same exporter.
+#[derive(Clone)]
+pub struct SharedTelemetryExporter(Arc<dyn TelemetryExporter>);
+
+impl SharedTelemetryExporter {
+    pub fn new(exporter: Arc<dyn TelemetryExporter>) -> Self {
+        Self(exporter)
+    }
+
+    pub fn export(&self, event: TelemetryEvent) {
+        self.0.export(&event);
+    }
+}
+
+impl Default for SharedTelemetryExporter {
+    /// The process-wide no-op exporter
+    fn default() -> Self {
+        static NOOP: OnceLock<Arc<dyn TelemetryExporter>> = OnceLock::new();
+        Self(Arc::clone(NOOP.get_or_init(|| Arc::new(NoopExporter))))
+    }
+}
+
+impl PartialEq for SharedTelemetryExporter {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for SharedTelemetryExporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SharedTelemetryExporter(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_exporter() {
+        let metrics = Arc::new(MetricsExporter::default());
+        let exporter = SharedTelemetryExporter::new(metrics.clone());
+        exporter.export(TelemetryEvent::FrameDecoded { frame_index: 3, decode: Duration::from_millis(2), process: Duration::from_millis(1) });
+        exporter.export(TelemetryEvent::FrameDecoded { frame_index: 4, decode: Duration::from_millis(2), process: Duration::from_millis(1) });
+        exporter.export(TelemetryEvent::Seek { from: 4, to: 100 });
+        exporter.export(TelemetryEvent::CacheEvict { frames: 2 });
+        exporter.export(TelemetryEvent::TransportRetry { url: "https://example.com/mask.asvp".to_string(), attempt: 1, backoff: Duration::from_secs(2) });
+        assert_eq!(metrics.snapshot(), TelemetryMetrics {
+            frames_decoded: 2,
+            decode_time: Duration::from_millis(6),
+            seeks: 1,
+            frames_evicted: 2,
+            transport_retries: 1,
+        });
+    }
+
+    #[test]
+    fn test_shared_exporter_identity() {
+        assert_eq!(SharedTelemetryExporter::default(), SharedTelemetryExporter::default());
+        let exporter: Arc<dyn TelemetryExporter> = Arc::new(LogExporter::default());
+        assert_eq!(SharedTelemetryExporter::new(exporter.clone()), SharedTelemetryExporter::new(exporter));
+        assert_ne!(SharedTelemetryExporter::new(Arc::new(NoopExporter)), SharedTelemetryExporter::default());
+    }
+}
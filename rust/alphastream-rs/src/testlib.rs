@@ -76,3 +76,84 @@ pub fn create_test_asvr(
     let file = writer.write_all()?;
     Ok(file)
 }
+
+/// Minimal HTTP/1.1 file server for transport tests
+///
+/// Serves one resource at `url()` with HEAD and GET (with `Range: bytes=a-b`), an ETag that
+/// changes on every `replace`, and 412 Precondition Failed for a stale `If-Match`.
+/// Every connection handles a single request and is then closed.
+pub struct TestHttpServer {
+    addr: std::net::SocketAddr,
+    content: std::sync::Arc<std::sync::Mutex<(Vec<u8>, u32)>>,
+}
+
+impl TestHttpServer {
+    /// Start serving `data` on a free localhost port
+    pub fn start(data: Vec<u8>) -> std::io::Result<Self> {
+        use std::io::{BufRead, BufReader, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let content = std::sync::Arc::new(std::sync::Mutex::new((data, 1u32)));
+        let shared = std::sync::Arc::clone(&content);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                if reader.read_line(&mut request_line).is_err() {
+                    continue;
+                }
+                let (mut range, mut if_match) = (None, None);
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        match name.trim().to_ascii_lowercase().as_str() {
+                            "range" => range = value.trim().strip_prefix("bytes=").and_then(|r| {
+                                let (a, b) = r.split_once('-')?;
+                                Some((a.parse::<usize>().ok()?, b.parse::<usize>().ok()?))
+                            }),
+                            "if-match" => if_match = Some(value.trim().to_string()),
+                            _ => {}
+                        }
+                    }
+                }
+                let (data, version) = shared.lock().unwrap().clone();
+                let etag = format!("\"v{}\"", version);
+                let (status, body) = if if_match.is_some_and(|tag| tag != etag) {
+                    ("412 Precondition Failed", Vec::new())
+                } else if let Some((start, end)) = range {
+                    let end = end.min(data.len().saturating_sub(1));
+                    if start >= data.len() || start > end {
+                        ("416 Range Not Satisfiable", Vec::new())
+                    } else {
+                        ("206 Partial Content", data[start..=end].to_vec())
+                    }
+                } else {
+                    ("200 OK", data.clone())
+                };
+                let head = request_line.starts_with("HEAD");
+                let length = if head { data.len() } else { body.len() };
+                let _ = write!(stream, "HTTP/1.1 {}\r\nContent-Length: {}\r\nETag: {}\r\nConnection: close\r\n\r\n", status, length, etag);
+                if !head {
+                    let _ = stream.write_all(&body);
+                }
+            }
+        });
+        Ok(Self { addr, content })
+    }
+
+    /// URL of the served resource
+    pub fn url(&self) -> String {
+        format!("http://{}/stream.asvp", self.addr)
+    }
+
+    /// Replace the served content, as if the file was re-uploaded; the ETag changes
+    pub fn replace(&self, data: Vec<u8>) {
+        let mut content = self.content.lock().unwrap();
+        content.0 = data;
+        content.1 += 1;
+    }
+}
@@ -4,21 +4,34 @@ use thiserror::Error;
 use bytes::Bytes;
 use std::future::Future;
 use std::pin::Pin;
-use reqwest::{Client, header::RANGE};
+use reqwest::{Client, header::{CONTENT_LENGTH, ETAG, IF_MATCH, IF_UNMODIFIED_SINCE, LAST_MODIFIED, RANGE}};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
 use tokio::time::sleep;
+#[cfg(any(test, feature = "unstable"))]
 use std::fs::File;
+#[cfg(any(test, feature = "unstable"))]
 use memmap2::Mmap;
+#[cfg(any(test, feature = "unstable"))]
 use tokio::fs::File as AsyncFile;
+#[cfg(any(test, feature = "unstable"))]
 use tokio::io::{AsyncReadExt, AsyncSeekExt, BufReader as AsyncBufReader};
+#[cfg(any(test, feature = "unstable"))]
 use tokio::fs;
 
+use crate::telemetry::{SharedTelemetryExporter, TelemetryEvent};
+
 #[derive(Error, Debug)]
 pub enum TransportError {
     #[error("Not found")]
     NotFound,
+    #[cfg(any(test, feature = "unstable"))]
     #[error("Timeout")]
     Timeout,
+    /// The remote file no longer matches the version seen on open (its ETag / Last-Modified changed)
+    #[error("Stream changed on the server")]
+    StreamChanged,
     #[error("Transport error: {0}")]
     Other(String),
 }
@@ -41,20 +54,143 @@ pub trait Transport {
 
 type TransportFuture = Pin<Box<dyn Future<Output = Result<Bytes, TransportError>> + Send + 'static>>;
 
+/// Faults injected by MockTransport into every `read_range` call.
+/// Parsed from the query of a `mock://` URI, so tests and benchmarks can script a bad network
+/// with a string, e.g. `mock://data?latency_ms=5..50&error_rate=0.1&bandwidth=1000000&seed=7`.
+#[cfg(any(test, feature = "unstable"))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultProfile {
+    /// Latency of each read, drawn uniformly from `latency_min..=latency_max`
+    pub latency_min: Duration,
+    pub latency_max: Duration,
+    /// Probability (0.0 - 1.0) that a read fails with `TransportError::Timeout`
+    pub error_rate: f64,
+    /// Throughput cap in bytes per second, None = unlimited
+    pub bytes_per_second: Option<u64>,
+    /// Seed for latency and error draws; the same seed gives the same sequence of faults
+    pub seed: u64,
+}
+
+#[cfg(any(test, feature = "unstable"))]
+impl Default for FaultProfile {
+    /// A perfect network: no latency, no errors, unlimited bandwidth
+    fn default() -> Self {
+        Self {
+            latency_min: Duration::ZERO,
+            latency_max: Duration::ZERO,
+            error_rate: 0.0,
+            bytes_per_second: None,
+            seed: 1,
+        }
+    }
+}
+
+#[cfg(any(test, feature = "unstable"))]
+impl FaultProfile {
+    /// Parse `key=value` pairs separated by `&`: `latency_ms` (`N` or `MIN..MAX`), `error_rate`,
+    /// `bandwidth` (bytes per second) and `seed`. `size` is accepted and left to the caller.
+    pub fn parse(query: &str) -> Result<Self, TransportError> {
+        let mut profile = Self::default();
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let invalid = || TransportError::Other(format!("Invalid mock transport option '{}'", pair));
+            let (key, value) = pair.split_once('=').ok_or_else(invalid)?;
+            match key {
+                "latency_ms" => {
+                    let (min, max) = value.split_once("..").unwrap_or((value, value));
+                    let min: u64 = min.parse().map_err(|_| invalid())?;
+                    let max: u64 = max.parse().map_err(|_| invalid())?;
+                    if min > max {
+                        return Err(invalid());
+                    }
+                    profile.latency_min = Duration::from_millis(min);
+                    profile.latency_max = Duration::from_millis(max);
+                }
+                "error_rate" => {
+                    let rate: f64 = value.parse().map_err(|_| invalid())?;
+                    if !(0.0..=1.0).contains(&rate) {
+                        return Err(invalid());
+                    }
+                    profile.error_rate = rate;
+                }
+                "bandwidth" => profile.bytes_per_second = Some(value.parse().ok().filter(|&b| b > 0).ok_or_else(invalid)?),
+                "seed" => profile.seed = value.parse().map_err(|_| invalid())?,
+                "size" => {}
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(profile)
+    }
+
+    /// Time to transfer `len` bytes at the throughput cap
+    fn transfer_time(&self, len: usize) -> Duration {
+        match self.bytes_per_second {
+            Some(rate) => Duration::from_secs_f64(len as f64 / rate as f64),
+            None => Duration::ZERO,
+        }
+    }
+}
+
+#[cfg(any(test, feature = "unstable"))]
 pub struct MockReader {
     data: Bytes,
+    profile: FaultProfile,
+    // xorshift64* state, shared by the reads of this reader
+    rng: Arc<Mutex<u64>>,
 }
 
+#[cfg(any(test, feature = "unstable"))]
+impl MockReader {
+    /// Reader over `data` that injects the faults of `profile`
+    pub fn new(data: Bytes, profile: FaultProfile) -> Self {
+        // xorshift must not start at 0
+        let rng = Arc::new(Mutex::new(profile.seed.max(1)));
+        Self { data, profile, rng }
+    }
+
+    #[cfg(feature = "unstable")]
+    pub fn profile(&self) -> &FaultProfile {
+        &self.profile
+    }
+
+    /// Draw the latency and whether the next read fails
+    fn next_fault(&self) -> (Duration, bool) {
+        let mut state = self.rng.lock().unwrap();
+        let mut next = || {
+            *state ^= *state >> 12;
+            *state ^= *state << 25;
+            *state ^= *state >> 27;
+            (state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+        };
+        let spread = self.profile.latency_max - self.profile.latency_min;
+        let latency = self.profile.latency_min + spread.mul_f64(next());
+        let fail = next() < self.profile.error_rate;
+        (latency, fail)
+    }
+}
+
+/// In-memory transport for tests and benchmarks, with optional fault injection.
+/// `mock://<anything>` serves a fixed test string; add `size=N` for N bytes of a repeating pattern,
+/// and the `FaultProfile` options to slow reads down or make them fail.
+#[cfg(any(test, feature = "unstable"))]
 pub struct MockTransport;
 
+#[cfg(any(test, feature = "unstable"))]
 impl Transport for MockTransport {
     type Reader = MockReader;
 
-    fn open(_uri: &str) -> Pin<Box<dyn Future<Output = Result<Self::Reader, TransportError>> + Send>> {
+    fn open(uri: &str) -> Pin<Box<dyn Future<Output = Result<Self::Reader, TransportError>> + Send + '_>> {
         Box::pin(async move {
-            Ok(MockReader {
-                data: Bytes::from("mock data for testing"),
-            })
+            let query = uri.split_once('?').map_or("", |(_, query)| query);
+            let profile = FaultProfile::parse(query)?;
+            let size = query.split('&')
+                .find_map(|pair| pair.strip_prefix("size="))
+                .map(|size| size.parse::<usize>().map_err(|_| TransportError::Other(format!("Invalid mock transport size '{}'", size))))
+                .transpose()?;
+            let data = match size {
+                Some(size) => Bytes::from((0..size).map(|i| i as u8).collect::<Vec<u8>>()),
+                None => Bytes::from("mock data for testing"),
+            };
+            Ok(MockReader::new(data, profile))
         })
     }
 
@@ -64,6 +200,8 @@ impl Transport for MockTransport {
 
     fn read_range(reader: &Self::Reader, offset: u64, size: u32) -> Pin<Box<dyn Future<Output = Result<Bytes, TransportError>> + Send>> {
         let data = reader.data.clone();
+        let (latency, fail) = reader.next_fault();
+        let profile = reader.profile.clone();
         Box::pin(async move {
             let start = offset as usize;
             let end = start + size as usize;
@@ -71,12 +209,20 @@ impl Transport for MockTransport {
                 return Err(TransportError::Other("Offset out of bounds".to_string()));
             }
             let end = end.min(data.len());
+            let delay = latency + profile.transfer_time(end - start);
+            if !delay.is_zero() {
+                sleep(delay).await;
+            }
+            if fail {
+                return Err(TransportError::Timeout);
+            }
             Ok(data.slice(start..end))
         })
     }
 }
 
 // LocalTransport implementation using memory mapping for efficiency, with buffered fallback
+#[cfg(any(test, feature = "unstable"))]
 pub enum LocalData {
     // Memory-mapped file for fast random access
     Mmap(Mmap),
@@ -84,6 +230,7 @@ pub enum LocalData {
     BufferedPath(String),
 }
 
+#[cfg(any(test, feature = "unstable"))]
 pub struct LocalReader {
     // The data source, either memory mapped or buffered
     data: LocalData,
@@ -91,8 +238,10 @@ pub struct LocalReader {
     len: u64,
 }
 
+#[cfg(any(test, feature = "unstable"))]
 pub struct LocalTransport;
 
+#[cfg(any(test, feature = "unstable"))]
 impl Transport for LocalTransport {
     type Reader = LocalReader;
 
@@ -144,13 +293,16 @@ impl Transport for LocalTransport {
 }
 
 // InMemoryTransport implementation that loads the entire file into memory for fast access
+#[cfg(any(test, feature = "unstable"))]
 pub struct InMemoryReader {
     // The entire file data loaded into memory as a Bytes slice
     data: Bytes,
 }
 
+#[cfg(any(test, feature = "unstable"))]
 pub struct InMemoryTransport;
 
+#[cfg(any(test, feature = "unstable"))]
 impl Transport for InMemoryTransport {
     type Reader = InMemoryReader;
 
@@ -186,6 +338,7 @@ impl Transport for InMemoryTransport {
 }
 
 // Helper function to open local file, trying memory mapping first
+#[cfg(any(test, feature = "unstable"))]
 fn open_local(uri: &str) -> Result<LocalReader, TransportError> {
     // Try to open the file synchronously for memory mapping
     let file = File::open(uri).map_err(|_| TransportError::NotFound)?;
@@ -209,14 +362,88 @@ fn open_local(uri: &str) -> Result<LocalReader, TransportError> {
     }
 }
 
+/// Concurrent requests per host unless changed with `set_host_concurrency`, the common browser limit
+pub const DEFAULT_HOST_CONCURRENCY: usize = 6;
+
+/// Client shared by all HTTP readers, so readers of the same host (and reopened readers) reuse
+/// pooled connections. HTTPS servers that offer HTTP/2 multiplex all requests over one connection.
+fn http_client() -> Client {
+    static CLIENT: OnceLock<Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        Client::builder()
+            .pool_max_idle_per_host(DEFAULT_HOST_CONCURRENCY)
+            .pool_idle_timeout(Duration::from_secs(90))
+            .tcp_keepalive(Duration::from_secs(60))
+            .http2_adaptive_window(true)
+            .build()
+            .unwrap_or_default()
+    }).clone()
+}
+
+// Limit and permits by host
+type HostLimits = Mutex<HashMap<String, (usize, Arc<tokio::sync::Semaphore>)>>;
+
+fn host_limits() -> &'static HostLimits {
+    static LIMITS: OnceLock<HostLimits> = OnceLock::new();
+    LIMITS.get_or_init(Default::default)
+}
+
+/// Host part of a URL, the key of the concurrency limits
+fn host_of(url: &str) -> String {
+    reqwest::Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_string)).unwrap_or_default()
+}
+
+/// Limit the number of range requests in flight to `host` (as in the URL, without port) across
+/// all readers of the process. Requests beyond the limit wait for a free slot. Takes effect for
+/// requests started after the call; the minimum is 1.
+#[cfg(any(test, feature = "unstable"))]
+pub fn set_host_concurrency(host: &str, limit: usize) {
+    let limit = limit.max(1);
+    host_limits().lock().unwrap().insert(host.to_string(), (limit, Arc::new(tokio::sync::Semaphore::new(limit))));
+}
+
+/// Concurrent request limit for `host`
+#[cfg(any(test, feature = "unstable"))]
+pub fn host_concurrency(host: &str) -> usize {
+    host_limits().lock().unwrap().get(host).map_or(DEFAULT_HOST_CONCURRENCY, |(limit, _)| *limit)
+}
+
+fn host_permits(host: &str) -> Arc<tokio::sync::Semaphore> {
+    let mut limits = host_limits().lock().unwrap();
+    let (_, permits) = limits.entry(host.to_string())
+        .or_insert_with(|| (DEFAULT_HOST_CONCURRENCY, Arc::new(tokio::sync::Semaphore::new(DEFAULT_HOST_CONCURRENCY))));
+    Arc::clone(permits)
+}
+
 // HttpTransport implementation using reqwest for HTTP-based transport with Range requests
 pub struct HttpReader {
     // The URL of the resource to read from
     url: String,
-    // HTTP client for making requests
+    // HTTP client for making requests, shared by all readers
     client: Client,
+    // Host of the URL, for the per-host concurrency limit
+    host: String,
     // Cached content length to avoid repeated HEAD requests
     content_length: u64,
+    // Version of the resource seen on open; range requests are conditional on it so a re-upload
+    // fails with StreamChanged instead of returning bytes from two different files
+    etag: Option<String>,
+    last_modified: Option<String>,
+    // Receives retried range requests
+    telemetry: SharedTelemetryExporter,
+}
+
+impl HttpReader {
+    /// ETag of the resource when it was opened
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+
+    /// Report retried range requests to `telemetry`
+    pub fn set_telemetry(&mut self, telemetry: SharedTelemetryExporter) {
+        self.telemetry = telemetry;
+    }
 }
 
 pub struct HttpTransport;
@@ -227,17 +454,26 @@ impl Transport for HttpTransport {
     // Opens an HTTP reader by fetching the content length via a HEAD request
     fn open(uri: &str) -> Pin<Box<dyn Future<Output = Result<Self::Reader, TransportError>> + Send + '_>> {
         Box::pin(async move {
-            let client = Client::new();
+            let client = http_client();
             // Perform a HEAD request to get the content length
             let response = client.head(uri).send().await.map_err(|e| TransportError::Other(e.to_string()))?;
             if !response.status().is_success() {
                 return Err(TransportError::NotFound);
             }
-            let content_length = response.content_length().unwrap_or(0);
+            let header = |name| response.headers().get(name).and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok()).map(str::to_string);
+            // Read the header: content_length() reports the (empty) body of the HEAD response
+            let content_length = header(CONTENT_LENGTH).and_then(|len| len.parse().ok()).unwrap_or(0);
+            // Weak ETags are not allowed in If-Match
+            let etag = header(ETAG).filter(|tag| !tag.starts_with("W/"));
+            let last_modified = header(LAST_MODIFIED);
             Ok(HttpReader {
                 url: uri.to_string(),
                 client,
+                host: host_of(uri),
                 content_length,
+                etag,
+                last_modified,
+                telemetry: SharedTelemetryExporter::default(),
             })
         })
     }
@@ -251,17 +487,29 @@ impl Transport for HttpTransport {
     fn read_range(reader: &Self::Reader, offset: u64, size: u32) -> Pin<Box<dyn Future<Output = Result<Bytes, TransportError>> + Send>> {
         let url = reader.url.clone();
         let client = reader.client.clone();
+        let etag = reader.etag.clone();
+        let last_modified = reader.last_modified.clone();
+        let telemetry = reader.telemetry.clone();
+        let permits = host_permits(&reader.host);
         Box::pin(async move {
+            // Held for the whole read including retries; the semaphore is never closed
+            let _permit = permits.acquire_owned().await.map_err(|e| TransportError::Other(e.to_string()))?;
             let range_header = format!("bytes={}-{}", offset, offset + size as u64 - 1);
             let mut attempts = 0;
             const MAX_RETRIES: u32 = 3;
             loop {
                 attempts += 1;
-                let response = client.get(&url)
-                    .header(RANGE, &range_header)
-                    .send()
-                    .await;
+                let mut request = client.get(&url).header(RANGE, &range_header);
+                if let Some(etag) = &etag {
+                    request = request.header(IF_MATCH, etag);
+                } else if let Some(last_modified) = &last_modified {
+                    request = request.header(IF_UNMODIFIED_SINCE, last_modified);
+                }
+                let response = request.send().await;
                 match response {
+                    Ok(resp) if resp.status() == reqwest::StatusCode::PRECONDITION_FAILED => {
+                        return Err(TransportError::StreamChanged);
+                    }
                     Ok(resp) if resp.status().is_success() => {
                         let bytes = resp.bytes().await.map_err(|e| TransportError::Other(e.to_string()))?;
                         return Ok(bytes);
@@ -271,7 +519,9 @@ impl Transport for HttpTransport {
                     }
                     _ if attempts < MAX_RETRIES => {
                         // Simple exponential backoff: wait 2^attempts seconds
-                        sleep(Duration::from_secs(1 << attempts)).await;
+                        let backoff = Duration::from_secs(1 << attempts);
+                        telemetry.export(TelemetryEvent::TransportRetry { url: url.clone(), attempt: attempts, backoff });
+                        sleep(backoff).await;
                         continue;
                     }
                     _ => return Err(TransportError::Other("Failed to read range after retries".to_string())),
@@ -281,6 +531,168 @@ impl Transport for HttpTransport {
     }
 }
 
+/// Default size of the byte ranges fetched by CoalescingReader.
+/// Frames are hundreds of bytes to a few KB, so one request covers tens of adjacent frames.
+pub const DEFAULT_COALESCE_CHUNK_SIZE: u64 = 64 * 1024;
+
+/// Range requests CoalescingReader keeps in flight at most
+pub const MAX_PARALLEL_RANGES: usize = 16;
+
+/// Adapts a Transport to AsyncRead + AsyncSeek for the format parsers, coalescing small reads.
+/// Every read is served from an aligned chunk of `chunk_size` bytes that is fetched with a single
+/// `read_range` call and kept until the position leaves it, so sequential frame reads cost one
+/// request per chunk instead of one (or more) per frame.
+///
+/// With `parallel_ranges(n)`, sequential reads (such as the prefetch scheduler decoding ahead)
+/// also fetch the next n - 1 chunks, so up to n range requests are in flight at once.
+pub struct CoalescingReader<T: Transport> {
+    reader: T::Reader,
+    len: u64,
+    position: u64,
+    chunk_size: u64,
+    // Fetched and in-flight chunks by start offset, at most `parallel_ranges` of them
+    chunks: BTreeMap<u64, Chunk>,
+    // Start of the chunk the last read was served from, to detect sequential access
+    last_chunk: Option<u64>,
+    parallel_ranges: usize,
+    requests: u64,
+}
+
+enum Chunk {
+    Pending(TransportFuture),
+    Ready(Bytes),
+}
+
+impl<T: Transport> CoalescingReader<T> {
+    /// Open `uri` with transport T, coalescing reads into DEFAULT_COALESCE_CHUNK_SIZE ranges
+    #[cfg(any(test, feature = "unstable"))]
+    pub async fn open(uri: &str) -> Result<Self, TransportError> {
+        Ok(Self::new(T::open(uri).await?, DEFAULT_COALESCE_CHUNK_SIZE))
+    }
+
+    pub fn new(reader: T::Reader, chunk_size: u64) -> Self {
+        let len = T::len(&reader);
+        Self {
+            reader,
+            len,
+            position: 0,
+            chunk_size: chunk_size.clamp(1, u32::MAX as u64),
+            chunks: BTreeMap::new(),
+            last_chunk: None,
+            parallel_ranges: 1,
+            requests: 0,
+        }
+    }
+
+    /// Allow up to `count` range requests in flight during sequential reads (1 - MAX_PARALLEL_RANGES, default 1)
+    pub fn parallel_ranges(mut self, count: usize) -> Self {
+        self.parallel_ranges = count.clamp(1, MAX_PARALLEL_RANGES);
+        self
+    }
+
+    /// Number of range requests issued so far
+    #[cfg(any(test, feature = "unstable"))]
+    pub fn requests(&self) -> u64 {
+        self.requests
+    }
+
+    /// Make sure the chunk at `chunk_start` is fetched, plus the read-ahead chunks when the
+    /// access is sequential. Chunks outside that window are dropped (cancelling their requests).
+    fn schedule(&mut self, chunk_start: u64) {
+        let sequential = self.chunks.contains_key(&chunk_start)
+            || self.last_chunk.is_some_and(|last| last + self.chunk_size == chunk_start);
+        let count = if sequential { self.parallel_ranges } else { 1 };
+        let window_end = chunk_start.saturating_add(self.parallel_ranges as u64 * self.chunk_size);
+        self.chunks.retain(|&start, _| start >= chunk_start && start < window_end);
+        for i in 0..count as u64 {
+            let start = chunk_start + i * self.chunk_size;
+            if start >= self.len {
+                break;
+            }
+            if !self.chunks.contains_key(&start) {
+                let size = self.chunk_size.min(self.len - start) as u32;
+                self.chunks.insert(start, Chunk::Pending(T::read_range(&self.reader, start, size)));
+                self.requests += 1;
+            }
+        }
+        self.last_chunk = Some(chunk_start);
+    }
+}
+
+// Nothing is structurally pinned: the pending futures are boxed
+impl<T: Transport> Unpin for CoalescingReader<T> {}
+
+impl<T: Transport> tokio::io::AsyncRead for CoalescingReader<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        use std::task::Poll;
+        let this = self.get_mut();
+        if this.position >= this.len || buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        let chunk_start = this.position / this.chunk_size * this.chunk_size;
+        if this.last_chunk != Some(chunk_start) || !this.chunks.contains_key(&chunk_start) {
+            this.schedule(chunk_start);
+        }
+        // Requests only make progress while polled, so every read drives all of them
+        let mut failed = Vec::new();
+        for (&start, chunk) in this.chunks.iter_mut() {
+            if let Chunk::Pending(future) = chunk {
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(Ok(data)) => *chunk = Chunk::Ready(data),
+                    Poll::Ready(Err(e)) => failed.push((start, e)),
+                    Poll::Pending => {}
+                }
+            }
+        }
+        // A failed read-ahead is dropped and retried when it is actually read
+        for (start, _) in &failed {
+            this.chunks.remove(start);
+        }
+        if let Some((_, e)) = failed.into_iter().find(|(start, _)| *start == chunk_start) {
+            // FormatError recognizes StreamChanged inside the io::Error
+            return Poll::Ready(Err(std::io::Error::other(e)));
+        }
+        let Some(Chunk::Ready(data)) = this.chunks.get(&chunk_start) else {
+            return Poll::Pending;
+        };
+        let from = (this.position - chunk_start) as usize;
+        if from >= data.len() {
+            // The source is shorter than it claimed; report end of file
+            return Poll::Ready(Ok(()));
+        }
+        let n = buf.remaining().min(data.len() - from);
+        buf.put_slice(&data[from..from + n]);
+        this.position += n as u64;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: Transport> tokio::io::AsyncSeek for CoalescingReader<T> {
+    fn start_seek(self: Pin<&mut Self>, position: std::io::SeekFrom) -> std::io::Result<()> {
+        let this = self.get_mut();
+        let target = match position {
+            std::io::SeekFrom::Start(offset) => Some(offset),
+            std::io::SeekFrom::End(delta) => this.len.checked_add_signed(delta),
+            std::io::SeekFrom::Current(delta) => this.position.checked_add_signed(delta),
+        };
+        match target {
+            Some(target) => {
+                this.position = target;
+                Ok(())
+            }
+            None => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Seek before start of stream")),
+        }
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut std::task::Context<'_>) -> std::task::Poll<std::io::Result<u64>> {
+        std::task::Poll::Ready(Ok(self.position))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -293,6 +705,147 @@ mod tests {
         assert_eq!(data.as_ref(), b"mock data ");
     }
 
+    #[test]
+    fn test_fault_profile_parse() {
+        let profile = FaultProfile::parse("latency_ms=5..20&error_rate=0.25&bandwidth=1000&seed=9&size=10").unwrap();
+        assert_eq!(profile.latency_min, Duration::from_millis(5));
+        assert_eq!(profile.latency_max, Duration::from_millis(20));
+        assert_eq!(profile.error_rate, 0.25);
+        assert_eq!(profile.bytes_per_second, Some(1000));
+        assert_eq!(profile.seed, 9);
+        assert_eq!(FaultProfile::parse("").unwrap(), FaultProfile::default());
+        for bad in ["latency_ms=20..5", "error_rate=2", "bandwidth=0", "jitter=1", "seed"] {
+            assert!(FaultProfile::parse(bad).is_err(), "{} should not parse", bad);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mock_transport_injects_errors() {
+        let reader = MockTransport::open("mock://data?error_rate=1").await.unwrap();
+        assert!(matches!(MockTransport::read_range(&reader, 0, 4).await, Err(TransportError::Timeout)));
+
+        // The same seed fails the same reads
+        let failures = |reader: MockReader| async move {
+            let mut failures = Vec::new();
+            for _ in 0..32 {
+                failures.push(MockTransport::read_range(&reader, 0, 4).await.is_err());
+            }
+            failures
+        };
+        let a = failures(MockTransport::open("mock://data?error_rate=0.5&seed=3").await.unwrap()).await;
+        let b = failures(MockTransport::open("mock://data?error_rate=0.5&seed=3").await.unwrap()).await;
+        assert_eq!(a, b);
+        assert!(a.contains(&true) && a.contains(&false));
+    }
+
+    #[tokio::test]
+    async fn test_mock_transport_latency_and_bandwidth() {
+        let reader = MockTransport::open("mock://data?size=1000&latency_ms=20&bandwidth=10000").await.unwrap();
+        assert_eq!(MockTransport::len(&reader), 1000);
+        let start = std::time::Instant::now();
+        let data = MockTransport::read_range(&reader, 0, 500).await.unwrap();
+        assert_eq!(data.len(), 500);
+        assert_eq!(data[255], 255);
+        // 20ms latency + 500 bytes at 10000 B/s
+        assert!(start.elapsed() >= Duration::from_millis(70));
+    }
+
+    #[tokio::test]
+    async fn test_coalescing_reader_reads_and_seeks() {
+        use tokio::io::{AsyncReadExt, AsyncSeekExt};
+        let mut reader = CoalescingReader::<MockTransport>::new(MockTransport::open("mock://data?size=10000").await.unwrap(), 1024);
+        let mut buf = vec![0u8; 3000];
+        reader.read_exact(&mut buf).await.unwrap();
+        assert!(buf.iter().enumerate().all(|(i, &b)| b == i as u8));
+        assert_eq!(reader.requests(), 3);
+
+        // Small reads inside the current chunk are free
+        reader.seek(std::io::SeekFrom::Start(2100)).await.unwrap();
+        let mut small = [0u8; 10];
+        reader.read_exact(&mut small).await.unwrap();
+        assert_eq!(small[0], (2100 % 256) as u8);
+        assert_eq!(reader.requests(), 3);
+
+        reader.seek(std::io::SeekFrom::End(-4)).await.unwrap();
+        let mut tail = Vec::new();
+        reader.read_to_end(&mut tail).await.unwrap();
+        assert_eq!(tail, vec![(9996 % 256) as u8, (9997 % 256) as u8, (9998 % 256) as u8, (9999 % 256) as u8]);
+        assert!(reader.seek(std::io::SeekFrom::Current(-20000)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_coalescing_reader_feeds_format_parser() {
+        use crate::formats::{ASFormat, ASVPFormat};
+        let file = crate::testlib::create_test_asvp(50).unwrap();
+        let path = file.path().to_str().unwrap();
+        let mut direct = ASVPFormat::new(tokio::fs::File::open(path).await.unwrap()).await.unwrap();
+        let mut coalesced = ASVPFormat::new(CoalescingReader::<LocalTransport>::open(path).await.unwrap()).await.unwrap();
+        for frame in 0..50 {
+            assert_eq!(coalesced.decode_frame(frame).await.unwrap().polystream, direct.decode_frame(frame).await.unwrap().polystream);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_coalescing_reader_reports_transport_errors() {
+        use tokio::io::AsyncReadExt;
+        let mut reader = CoalescingReader::<MockTransport>::open("mock://data?error_rate=1").await.unwrap();
+        let mut buf = [0u8; 4];
+        assert!(reader.read_exact(&mut buf).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_coalescing_reader_parallel_ranges() {
+        use tokio::io::AsyncReadExt;
+        let open = |parallel| async move {
+            CoalescingReader::<MockTransport>::new(MockTransport::open("mock://data?size=8192&latency_ms=40").await.unwrap(), 1024)
+                .parallel_ranges(parallel)
+        };
+        let mut sequential = open(1).await;
+        let mut parallel = open(4).await;
+        let mut expected = vec![0u8; 8192];
+        let start = std::time::Instant::now();
+        sequential.read_exact(&mut expected).await.unwrap();
+        let sequential_time = start.elapsed();
+
+        let mut buf = vec![0u8; 8192];
+        let start = std::time::Instant::now();
+        parallel.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, expected);
+        // Read-ahead overlaps the requests without fetching any chunk twice
+        assert_eq!(parallel.requests(), 8);
+        assert!(start.elapsed() * 2 < sequential_time, "{:?} vs {:?}", start.elapsed(), sequential_time);
+
+        // A random access does not read ahead
+        let mut reader = open(4).await;
+        reader.seek(std::io::SeekFrom::Start(5000)).await.unwrap();
+        reader.read_exact(&mut [0u8; 10]).await.unwrap();
+        assert_eq!(reader.requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_host_concurrency_limit() {
+        assert_eq!(host_of("https://cdn.example.com:8443/a.asvr"), "cdn.example.com");
+        assert_eq!(host_concurrency("unlimited.example.com"), DEFAULT_HOST_CONCURRENCY);
+        set_host_concurrency("cdn.example.com", 2);
+        assert_eq!(host_concurrency("cdn.example.com"), 2);
+        let permits = host_permits("cdn.example.com");
+        let _a = permits.clone().try_acquire_owned().unwrap();
+        let _b = permits.clone().try_acquire_owned().unwrap();
+        assert!(permits.try_acquire_owned().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_http_transport_detects_changed_stream() {
+        let server = crate::testlib::TestHttpServer::start((0..=255).collect()).unwrap();
+        let reader = HttpTransport::open(&server.url()).await.unwrap();
+        assert_eq!(HttpTransport::len(&reader), 256);
+        assert_eq!(reader.etag(), Some("\"v1\""));
+        assert_eq!(HttpTransport::read_range(&reader, 10, 4).await.unwrap().as_ref(), &[10, 11, 12, 13]);
+
+        server.replace(vec![0; 256]);
+        assert!(matches!(HttpTransport::read_range(&reader, 10, 4).await, Err(TransportError::StreamChanged)));
+    }
+
     #[tokio::test]
     async fn test_in_memory_transport() {
         use tempfile::NamedTempFile;
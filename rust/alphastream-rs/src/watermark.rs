@@ -0,0 +1,185 @@
+// Watermark module
+// Invisible forensic watermark for rasterized masks. A 32-bit payload (e.g. a licensee or session
+// id) is written into the least significant bit of covered pixels (255 becomes 254 or stays 255).
+// Which payload bit a pixel carries, and the keystream it is whitened with, come from a keyed hash
+// of its position and the frame index. Empty pixels are never touched, so mask statistics and
+// bounding boxes are unchanged. Each payload bit is spread over many pixels and read back by majority
+// vote, so a leaked render identifies its source even after some pixels were lost to cropping or
+// touch-ups; without the key the marked pixels look random.
+
+use std::fmt;
+
+use crate::rasterizer::Tile;
+
+/// Fraction of agreeing votes below which `extract` reports no watermark
+pub const MIN_WATERMARK_CONFIDENCE: f64 = 0.9;
+
+/// Covered pixels (>= this value) carry watermark bits
+const COVERED: u8 = 128;
+
+/// Watermark embedded into every mask the processor produces
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Watermark {
+    /// Identifies the recipient of the renders
+    pub payload: u32,
+    /// Secret key of the pixel-to-bit assignment; verification needs the same key
+    pub key: u64,
+}
+
+/// Result of reading a watermark from a mask
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatermarkReading {
+    pub payload: u32,
+    /// Fraction of covered pixels that agree with the payload, 0.5 is chance
+    pub confidence: f64,
+    /// Number of covered pixels that voted
+    pub samples: usize,
+}
+
+/// splitmix64 finalizer
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+impl fmt::Debug for Watermark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watermark").field("payload", &self.payload).finish_non_exhaustive()
+    }
+}
+
+impl Watermark {
+    pub fn new(payload: u32, key: u64) -> Self {
+        Self { payload, key }
+    }
+
+    /// The payload bit a pixel carries and the keystream bit it is whitened with
+    fn slot(key: u64, frame_index: usize, pixel: usize) -> (usize, u8) {
+        let value = mix(key ^ mix((frame_index as u64) << 32 ^ pixel as u64));
+        ((value >> 32) as usize % 32, (value & 1) as u8)
+    }
+
+    /// Embed the payload into the mask of `frame_index`
+    pub fn embed(&self, mask: &mut [u8], frame_index: usize) {
+        for (pixel, value) in mask.iter_mut().enumerate() {
+            self.embed_pixel(value, frame_index, pixel);
+        }
+    }
+
+    /// Embed the payload into one tile of the `width` pixels wide mask of `frame_index`, marking its
+    /// pixels exactly as `embed` marks them in the whole mask
+    pub fn embed_tile(&self, mask: &mut [u8], frame_index: usize, tile: &Tile, width: u32) {
+        for (row, values) in mask.chunks_exact_mut(tile.width as usize).enumerate() {
+            let start = (tile.y as usize + row) * width as usize + tile.x as usize;
+            for (column, value) in values.iter_mut().enumerate() {
+                self.embed_pixel(value, frame_index, start + column);
+            }
+        }
+    }
+
+    fn embed_pixel(&self, value: &mut u8, frame_index: usize, pixel: usize) {
+        if *value >= COVERED {
+            let (bit, whitening) = Self::slot(self.key, frame_index, pixel);
+            *value = (*value & !1) | (((self.payload >> bit) & 1) as u8 ^ whitening);
+        }
+    }
+
+    /// Read a payload embedded with `key` from the mask of `frame_index`.
+    /// None if the mask has too few covered pixels to carry every payload bit or the votes do not agree
+    /// well enough (an unmarked mask, the wrong key or frame index).
+    pub fn extract(key: u64, mask: &[u8], frame_index: usize) -> Option<WatermarkReading> {
+        let mut votes = [(0usize, 0usize); 32];
+        for (pixel, &value) in mask.iter().enumerate().filter(|(_, value)| **value >= COVERED) {
+            let (bit, whitening) = Self::slot(key, frame_index, pixel);
+            match (value & 1) ^ whitening {
+                0 => votes[bit].0 += 1,
+                _ => votes[bit].1 += 1,
+            }
+        }
+        if votes.iter().any(|(zeros, ones)| zeros + ones == 0) {
+            return None;
+        }
+        let samples: usize = votes.iter().map(|(zeros, ones)| zeros + ones).sum();
+        let payload = votes.iter().enumerate()
+            .fold(0u32, |payload, (bit, (zeros, ones))| payload | (u32::from(ones > zeros) << bit));
+        let agreeing: usize = votes.iter().map(|(zeros, ones)| *zeros.max(ones)).sum();
+        let confidence = agreeing as f64 / samples as f64;
+        (confidence >= MIN_WATERMARK_CONFIDENCE).then_some(WatermarkReading { payload, confidence, samples })
+    }
+
+    /// Whether the mask of `frame_index` carries this watermark
+    pub fn verify(&self, mask: &[u8], frame_index: usize) -> bool {
+        Self::extract(self.key, mask, frame_index).is_some_and(|reading| reading.payload == self.payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 64x64 mask with the left half covered
+    fn mask() -> Vec<u8> {
+        (0..64 * 64).map(|i| if i % 64 < 32 { 255 } else { 0 }).collect()
+    }
+
+    #[test]
+    fn test_embed_and_verify() {
+        let watermark = Watermark::new(0xC0FF_EE42, 7);
+        let original = mask();
+        let mut marked = original.clone();
+        watermark.embed(&mut marked, 3);
+
+        // Only the low bit of covered pixels changes
+        assert!(marked.iter().zip(&original).all(|(&m, &o)| if o == 0 { m == 0 } else { m >= 254 }));
+        assert_ne!(marked, original);
+        assert!(watermark.verify(&marked, 3));
+        let reading = Watermark::extract(7, &marked, 3).unwrap();
+        assert_eq!(reading.payload, 0xC0FF_EE42);
+        assert_eq!(reading.confidence, 1.0);
+
+        // Wrong key, wrong frame or no watermark at all: nothing is read
+        assert!(Watermark::extract(8, &marked, 3).is_none());
+        assert!(!watermark.verify(&marked, 4));
+        assert!(Watermark::extract(7, &original, 3).is_none());
+        assert!(Watermark::extract(7, &[0; 64 * 64], 3).is_none());
+    }
+
+    #[test]
+    fn test_embed_tile_matches_whole_mask() {
+        let watermark = Watermark::new(0xC0FF_EE42, 7);
+        let mut whole = mask();
+        watermark.embed(&mut whole, 5);
+        let mut tiled = mask();
+        for tile in Tile::grid(64, 64, 24) {
+            let mut pixels: Vec<u8> = (0..tile.height as usize)
+                .flat_map(|row| {
+                    let start = (tile.y as usize + row) * 64 + tile.x as usize;
+                    tiled[start..start + tile.width as usize].to_vec()
+                })
+                .collect();
+            watermark.embed_tile(&mut pixels, 5, &tile, 64);
+            for (row, values) in pixels.chunks_exact(tile.width as usize).enumerate() {
+                let start = (tile.y as usize + row) * 64 + tile.x as usize;
+                tiled[start..start + values.len()].copy_from_slice(values);
+            }
+        }
+        assert_eq!(tiled, whole);
+    }
+
+    #[test]
+    fn test_watermark_survives_damage() {
+        let watermark = Watermark::new(12345, 99);
+        let mut marked = mask();
+        watermark.embed(&mut marked, 0);
+        // Reset a band of pixels, as a touch-up would
+        for pixel in marked.iter_mut().take(64 * 8) {
+            if *pixel != 0 {
+                *pixel = 255;
+            }
+        }
+        let reading = Watermark::extract(99, &marked, 0).unwrap();
+        assert_eq!(reading.payload, 12345);
+        assert!(reading.confidence < 1.0);
+    }
+}
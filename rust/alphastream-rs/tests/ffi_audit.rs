@@ -0,0 +1,307 @@
+// Memory-safety audit of the C ABI
+// Drives every CV_* export through sequences a careless or hostile host could produce: null handles and
+// outputs, calls before CV_init, buffers churned and released in odd orders, callbacks that call back into
+// the library, handles used from many threads, and (with the `ffi-audit` feature) double CV_destroy and
+// use after CV_destroy. The assertions only check that calls are rejected cleanly; the real checks come
+// from running this target under a memory checker, as CI does:
+//   RUSTFLAGS=-Zsanitizer=address cargo +nightly test --features ffi-audit,unstable --target x86_64-unknown-linux-gnu --test ffi_audit
+//   cargo +nightly miri test --features ffi-audit --test ffi_audit -- without_source
+// Miri cannot run the decode runtime, so it only covers the tests that never open a source. Those that do
+// need the `unstable` feature for the test files of `testlib`.
+
+use std::ffi::CStr;
+#[cfg(feature = "unstable")]
+use std::ffi::{c_int, c_ulonglong, c_void, CString};
+use std::ptr;
+
+#[cfg(feature = "unstable")]
+use libalphastream::testlib::create_test_asvr;
+use libalphastream::*;
+
+const FRAMES: u32 = 4;
+
+/// A 16x16 test file of FRAMES frames
+#[cfg(feature = "unstable")]
+fn test_file() -> tempfile::NamedTempFile {
+    create_test_asvr(123, b"1.0.0", FRAMES).unwrap()
+}
+
+/// Initialize `handle` with a test file
+#[cfg(feature = "unstable")]
+fn init(handle: *mut AlphaStreamCHandle, file: &tempfile::NamedTempFile) {
+    let base_url = CString::new(file.path().to_str().unwrap()).unwrap();
+    assert!(CV_init(handle, base_url.as_ptr(), 123, 16, 16, c"1.0.0".as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+}
+
+/// Wait for `frame` to be decoded, leaving the handle's frame buffer pointing at it
+#[cfg(feature = "unstable")]
+fn wait_frame(handle: *mut AlphaStreamCHandle, frame: c_ulonglong) -> *const c_void {
+    let mut data: *const c_void = ptr::null();
+    let result = CV_get_frame_wait(handle, frame, 5000, &mut data);
+    assert_eq!(result, CV_WAIT_READY, "{frame} {:?}", unsafe { CStr::from_ptr(CV_get_last_error_text(handle)) });
+    data
+}
+
+/// Call every export that takes a handle once, with null outputs where they are optional
+fn call_everything(handle: *mut AlphaStreamCHandle) {
+    let name = c"audit";
+    let mut metadata = CVMetadata::default();
+    let mut stats = CVStats::default();
+    let mut output = CVFrameOutput::default();
+    let (mut vertices, mut count): (*const f32, usize) = (ptr::null(), 0);
+    let (mut taken, mut len): (*mut u8, usize) = (ptr::null_mut(), 0);
+    let mut name_buffer = [0 as std::ffi::c_char; 4];
+
+    CV_get_name(handle);
+    CV_get_version(handle);
+    CV_get_last_error_code(handle);
+    CV_get_last_error_text(handle);
+    CV_get_metadata(handle, &mut metadata);
+    CV_get_metadata(handle, ptr::null_mut());
+    CV_get_stats(handle, &mut stats);
+    CV_get_stats(handle, ptr::null_mut());
+    CV_get_total_frames(handle);
+    CV_get_frame_size(handle);
+    CV_select_backend(handle, CV_BACKEND_CPU);
+    CV_set_output_packing(handle, CV_PIXEL_FORMAT_R8, 1, false);
+    CV_set_empty_frame_policy(handle, CV_EMPTY_FRAME_RETURN_EMPTY);
+    CV_set_processing_mode(handle, CV_PROCESSING_MODE_BOTH);
+    CV_set_output_size(handle, 16, 16);
+    CV_set_output_size(handle, u32::MAX, 0);
+    CV_set_decode_budget(handle, 0.0);
+    CV_prefetch_range(handle, 0, FRAMES, CV_PRIORITY_LOW);
+    CV_cancel_all(handle);
+    CV_report_display_size(handle, 16, 16);
+    CV_get_frame(handle, 0);
+    CV_get_frame(handle, u32::MAX as _);
+    CV_get_frame_fmt(handle, 0, CV_PIXEL_FORMAT_BGRA8);
+    CV_get_frame_fmt(handle, 0, -1);
+    CV_get_frame_wait(handle, 0, 0, ptr::null_mut());
+    let mut frames = [ptr::null(); 2];
+    let mut ready = 0;
+    CV_get_frames(handle, u64::MAX - 1, 2, frames.as_mut_ptr(), &mut ready);
+    CV_get_frames(handle, 0, 2, ptr::null_mut(), &mut ready);
+    CV_get_frames(handle, 0, 2, frames.as_mut_ptr(), ptr::null_mut());
+    CV_take_frame(handle, 0, ptr::null_mut(), &mut len);
+    if CV_take_frame(handle, 0, &mut taken, &mut len) {
+        CV_free_buffer(taken, len);
+    }
+    CV_get_triangle_strip_vertices(handle, 0, &mut vertices, ptr::null_mut());
+    CV_get_triangle_strip_vertices(handle, 0, &mut vertices, &mut count);
+    let mut taken_vertices: *mut f32 = ptr::null_mut();
+    if CV_take_triangle_strip_vertices(handle, 0, &mut taken_vertices, &mut count) {
+        CV_free_buffer(taken_vertices as *mut u8, count * 4);
+    }
+    let (mut positions, mut uvs, mut indices) = (ptr::null(), ptr::null(), ptr::null());
+    let mut counts = CVTriangleCounts::default();
+    CV_get_triangles(handle, 0, &mut positions, &mut uvs, &mut indices, ptr::null_mut());
+    CV_get_triangles(handle, 0, &mut positions, &mut uvs, &mut indices, &mut counts);
+    CV_get_frame_output(handle, 0, ptr::null_mut());
+    CV_get_frame_output(handle, 0, &mut output);
+    let mut taken_output = CVFrameOutput::default();
+    if CV_take_frame_output(handle, 0, &mut taken_output) {
+        CV_free_buffer(taken_output.bitmap as *mut u8, taken_output.bitmap_size);
+        CV_free_buffer(taken_output.vertices as *mut u8, taken_output.vertex_count * 4);
+    }
+    CV_add_bookmark(handle, ptr::null(), 0);
+    CV_add_bookmark(handle, name.as_ptr(), u32::MAX);
+    CV_remove_bookmark(handle, ptr::null());
+    CV_remove_bookmark(handle, name.as_ptr());
+    CV_get_bookmark_count(handle);
+    CV_get_bookmark(handle, 0, ptr::null_mut(), ptr::null_mut(), 0);
+    CV_get_bookmark(handle, 0, ptr::null_mut(), name_buffer.as_mut_ptr(), name_buffer.len());
+    CV_get_channel_count(handle, u32::MAX as _);
+    CV_get_channel_frame(handle, 0, 0);
+    CV_get_channel_frame(handle, u32::MAX as _, u32::MAX);
+    CV_get_chapter_at(handle, 0);
+    CV_set_event_callback(handle, None, ptr::null_mut());
+    CV_set_frame_ready_callback(handle, None, ptr::null_mut());
+    CV_run_callbacks_on_thread(handle);
+}
+
+#[test]
+fn test_without_source_null_handle() {
+    call_everything(ptr::null_mut());
+    assert!(!CV_shutdown(ptr::null_mut()));
+    CV_destroy(ptr::null_mut());
+    assert_eq!(CV_get_last_error_code(ptr::null_mut()), -1);
+    assert_eq!(unsafe { CStr::from_ptr(CV_get_last_error_text(ptr::null_mut())) }, c"Invalid handle");
+    // Null outputs of handle-less exports
+    assert_eq!(CV_list_backends(ptr::null_mut(), 8), CV_list_backends(ptr::null_mut(), 0));
+    CV_free_buffer(ptr::null_mut(), 16);
+    assert!(!CV_set_log_callback(None, -1, ptr::null_mut()));
+}
+
+#[test]
+fn test_without_source_uninitialized_handle() {
+    let handle = CV_create();
+    call_everything(handle);
+    assert!(!CV_shutdown(handle));
+    // Null strings are rejected before they are read
+    let version = c"1.0.0";
+    assert!(!CV_init(handle, ptr::null(), 123, 16, 16, version.as_ptr(), 0, 1024, 512, 256, 5000, 30000));
+    assert!(!CV_init(handle, version.as_ptr(), 123, 16, 16, ptr::null(), 0, 1024, 512, 256, 5000, 30000));
+    assert_eq!(CV_get_last_error_code(handle), 1);
+    assert!(!CV_init_asvr(handle, ptr::null(), 123, ptr::null(), ptr::null(), 16, 16, 512, 256, 5000));
+    assert!(!CV_init_from_memory(handle, ptr::null(), 0, 123, version.as_ptr(), version.as_ptr(), 16, 16, 512, 256, 5000));
+    // The error text points into the handle, so it stays readable until the next call
+    let text = unsafe { CStr::from_ptr(CV_get_last_error_text(handle)) }.to_owned();
+    assert!(!text.is_empty());
+    CV_destroy(handle);
+}
+
+#[test]
+fn test_without_source_handle_ids() {
+    // Ids need no audit feature: stale ones are rejected in every build
+    let ids: Vec<CVHandleId> = (0..8).map(|_| CV_create_id()).collect();
+    // Destroying races with calls on other threads, which keep the handle alive until they return
+    std::thread::scope(|scope| {
+        for &id in &ids {
+            scope.spawn(move || assert!(CV_destroy_id(id)));
+            scope.spawn(move || {
+                CV_get_frame_id(id, 0);
+                CV_get_last_error_text_id(id);
+                CV_run_callbacks_on_thread_id(id);
+            });
+        }
+    });
+    for id in ids {
+        assert!(!CV_destroy_id(id));
+        assert_eq!(CV_get_last_error_code_id(id), -1);
+        assert!(!CV_get_metadata_id(id, ptr::null_mut()));
+    }
+    assert!(!CV_destroy_id(0));
+}
+
+#[cfg(feature = "ffi-audit")]
+#[test]
+fn test_without_source_destroyed_handle() {
+    let handle = CV_create();
+    CV_destroy(handle);
+    // Every call on the destroyed handle is turned away like a null handle, a second destroy included
+    call_everything(handle);
+    assert_eq!(CV_get_last_error_code(handle), -1);
+    CV_destroy(handle);
+    // A pointer that never came from CV_create is refused as well
+    let mut bogus = [0u64; 4];
+    let bogus = bogus.as_mut_ptr() as *mut AlphaStreamCHandle;
+    assert_eq!(CV_get_total_frames(bogus), 0);
+    CV_destroy(bogus);
+}
+
+#[cfg(feature = "unstable")]
+#[test]
+fn test_buffer_churn() {
+    let handle = CV_create();
+    let file = test_file();
+    init(handle, &file);
+    call_everything(handle);
+    for frame in (0..FRAMES as c_ulonglong).chain((0..FRAMES as c_ulonglong).rev()) {
+        // Each call frees the buffer the previous one returned
+        let data = wait_frame(handle, frame);
+        let first = unsafe { *(data as *const u8) };
+        let mut output = CVFrameOutput::default();
+        assert!(CV_get_frame_output(handle, frame, &mut output));
+        let mut vertices: *const f32 = ptr::null();
+        let mut count = 0;
+        CV_get_triangle_strip_vertices(handle, frame, &mut vertices, &mut count);
+        if !vertices.is_null() {
+            assert!(unsafe { std::slice::from_raw_parts(vertices, count) }.iter().all(|v| v.is_finite()));
+        }
+        assert_eq!(unsafe { *output.bitmap }, first);
+    }
+    // Taken buffers outlive the handle and are freed on another thread
+    let (mut taken, mut len) = (ptr::null_mut(), 0);
+    assert!(CV_take_frame(handle, 0, &mut taken, &mut len));
+    wait_frame(handle, 1);
+    CV_destroy(handle);
+    let taken = taken as usize;
+    std::thread::spawn(move || {
+        assert!(unsafe { std::slice::from_raw_parts(taken as *const u8, len) }.len() == 256);
+        CV_free_buffer(taken as *mut u8, len);
+    })
+    .join()
+    .unwrap();
+}
+
+#[cfg(feature = "unstable")]
+/// Handle and counters of the reentrant callbacks
+struct Reentry {
+    handle: *mut AlphaStreamCHandle,
+    frames: usize,
+    events: usize,
+}
+
+#[cfg(feature = "unstable")]
+extern "C" fn on_frame_reentrant(frame_index: c_ulonglong, data: *const u8, len: usize, user_data: *mut c_void) {
+    let reentry = unsafe { &mut *(user_data as *mut Reentry) };
+    let copy = unsafe { std::slice::from_raw_parts(data, len) }.to_vec();
+    // Replaces the handle's frame buffer while the library is delivering callbacks
+    let frame = CV_get_frame(reentry.handle, frame_index as _);
+    if !frame.is_null() {
+        assert_eq!(unsafe { std::slice::from_raw_parts(frame as *const u8, len) }, copy.as_slice());
+    }
+    let mut stats = CVStats::default();
+    assert!(CV_get_stats(reentry.handle, &mut stats));
+    reentry.frames += 1;
+}
+
+#[cfg(feature = "unstable")]
+extern "C" fn on_event_reentrant(user_data: *mut c_void, _event: c_int, _value: c_ulonglong) {
+    let reentry = unsafe { &mut *(user_data as *mut Reentry) };
+    CV_set_processing_mode(reentry.handle, CV_PROCESSING_MODE_BITMAP);
+    CV_run_callbacks_on_thread(reentry.handle);
+    reentry.events += 1;
+}
+
+#[cfg(feature = "unstable")]
+#[test]
+fn test_reentrant_callbacks() {
+    let handle = CV_create();
+    let mut reentry = Reentry { handle, frames: 0, events: 0 };
+    let user_data = &mut reentry as *mut Reentry as *mut c_void;
+    assert!(CV_set_frame_ready_callback(handle, Some(on_frame_reentrant), user_data));
+    assert!(CV_set_event_callback(handle, Some(on_event_reentrant), user_data));
+    let file = test_file();
+    init(handle, &file);
+    for frame in 0..FRAMES as c_ulonglong {
+        wait_frame(handle, frame);
+    }
+    let start = std::time::Instant::now();
+    while reentry.frames == 0 && start.elapsed() < std::time::Duration::from_secs(5) {
+        CV_run_callbacks_on_thread(handle);
+    }
+    assert!(reentry.frames > 0 && reentry.events > 0);
+    CV_destroy(handle);
+}
+
+#[cfg(feature = "unstable")]
+#[test]
+fn test_concurrent_handles() {
+    // Handles are not shared between threads, but many live side by side and are created and destroyed
+    // concurrently, with some destroyed while frames are still being decoded
+    let file = std::sync::Arc::new(test_file());
+    let threads: Vec<_> = (0..8)
+        .map(|thread| {
+            let file = file.clone();
+            std::thread::spawn(move || {
+                for round in 0..3 {
+                    let handle = CV_create();
+                    init(handle, &file);
+                    assert!(CV_prefetch_range(handle, 0, FRAMES, CV_PRIORITY_INTERACTIVE));
+                    if (thread + round) % 2 == 0 {
+                        wait_frame(handle, round as c_ulonglong);
+                        call_everything(handle);
+                    } else {
+                        // Shut down while the prefetched frames are being decoded
+                        assert!(CV_shutdown(handle));
+                    }
+                    CV_destroy(handle);
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
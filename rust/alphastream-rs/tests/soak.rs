@@ -0,0 +1,154 @@
+// Soak test for long-running playback
+// Plays a synthetic 100k-frame scene at accelerated speed while seeking at random, and checks that
+// heap usage stays bounded (counted by a global allocator hook) and that no tasks pile up on the
+// processor's runtime. Slow leaks in the cache / scheduler interplay show up as steady growth.
+//
+// The default run is a short smoke test. The full soak is ignored; run it in release mode:
+//   ALPHASTREAM_SOAK_SECS=14400 cargo test --release --features unstable --test soak -- --ignored
+// ALPHASTREAM_SOAK_SEED picks a different seek sequence.
+// With the `mimalloc` or `jemalloc` feature the library owns the global allocator, so there is no
+// room for the counting hook and the soak test is left out. The scene comes from `testlib`, which
+// needs the `unstable` feature.
+#![cfg(all(feature = "unstable", not(any(feature = "mimalloc", feature = "jemalloc"))))]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use libalphastream::prelude::{AlphaStreamProcessorBuilder, ProcessingMode};
+use libalphastream::testlib::create_test_asvp;
+
+/// System allocator that keeps track of the bytes currently allocated
+struct CountingAllocator;
+
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            LIVE_BYTES.fetch_add(new_size, Ordering::Relaxed);
+            LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const FRAME_COUNT: u32 = 100_000;
+const PREFETCH_WINDOW: usize = 16;
+/// Playback speed: one frame per millisecond, 40x a 25 fps stream
+const FRAME_INTERVAL: Duration = Duration::from_millis(1);
+/// Growth over the warm-up peak that is tolerated before calling it a leak
+const MIN_HEAP_ALLOWANCE: usize = 8 << 20;
+
+/// xorshift64, so a failing seek sequence can be replayed from its seed
+struct SeekSequence(u64);
+
+impl SeekSequence {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    std::env::var(name).ok().map(|value| value.parse().unwrap_or_else(|_| panic!("{} must be a number", name)))
+}
+
+async fn soak(duration: Duration) {
+    let file = create_test_asvp(FRAME_COUNT).unwrap();
+    let processor = AlphaStreamProcessorBuilder::new()
+        .cache_capacity(256)
+        .prefetch_window(PREFETCH_WINDOW)
+        .processing_mode(ProcessingMode::Both)
+        .build_asvp(file.path().to_str().unwrap(), 64, 64)
+        .await
+        .unwrap();
+    let idle_tasks = processor.alive_tasks();
+    let max_tasks = idle_tasks + PREFETCH_WINDOW + 2;
+
+    let mut seeks = SeekSequence(env_u64("ALPHASTREAM_SOAK_SEED").unwrap_or(0x5EED_F00D).max(1));
+    let warm_up = (duration / 10).max(Duration::from_secs(1));
+    let start = Instant::now();
+    let mut heap_limit = None;
+    let mut warm_up_peak = 0;
+    let mut frame = 0usize;
+    let (mut played, mut shown, mut seek_count) = (0u64, 0u64, 0u64);
+
+    while start.elapsed() < duration {
+        if processor.get_frame(frame, 64, 64).await.is_some() {
+            shown += 1;
+        }
+        played += 1;
+
+        // Mostly sequential playback, with a random jump (forward or back) every few hundred frames
+        let roll = seeks.next();
+        frame = if roll.is_multiple_of(300) {
+            seek_count += 1;
+            (seeks.next() % u64::from(FRAME_COUNT)) as usize
+        } else {
+            (frame + 1) % FRAME_COUNT as usize
+        };
+
+        if played.is_multiple_of(64) {
+            let live = LIVE_BYTES.load(Ordering::Relaxed);
+            let tasks = processor.alive_tasks();
+            assert!(tasks <= max_tasks, "{} tasks alive after {} frames, at most {} expected", tasks, played, max_tasks);
+            match heap_limit {
+                None if start.elapsed() < warm_up => warm_up_peak = warm_up_peak.max(live),
+                None => heap_limit = Some(warm_up_peak + (warm_up_peak / 2).max(MIN_HEAP_ALLOWANCE)),
+                Some(limit) => assert!(
+                    live <= limit,
+                    "heap grew to {} bytes after {:?} ({} frames, {} seeks), limit {}",
+                    live, start.elapsed(), played, seek_count, limit
+                ),
+            }
+        }
+        tokio::time::sleep(FRAME_INTERVAL).await;
+    }
+    assert!(shown > 0, "no frame was ever decoded");
+
+    // Once playback stops, the decode tasks finish and only the idle ones remain
+    let drained = Instant::now();
+    while processor.alive_tasks() > idle_tasks {
+        assert!(
+            drained.elapsed() < Duration::from_secs(10),
+            "{} tasks still alive after playback stopped, {} when idle",
+            processor.alive_tasks(), idle_tasks
+        );
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    println!(
+        "soak: {} frames played, {} shown, {} seeks in {:?}; heap {} bytes, warm-up peak {}",
+        played, shown, seek_count, start.elapsed(), LIVE_BYTES.load(Ordering::Relaxed), warm_up_peak
+    );
+}
+
+#[tokio::test]
+async fn soak_smoke() {
+    soak(Duration::from_secs(3)).await;
+}
+
+#[tokio::test]
+#[ignore = "runs for an hour by default; set ALPHASTREAM_SOAK_SECS"]
+async fn soak_long_playback() {
+    soak(Duration::from_secs(env_u64("ALPHASTREAM_SOAK_SECS").unwrap_or(3600))).await;
+}